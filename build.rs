@@ -0,0 +1,11 @@
+fn main() {
+    // Описание сервиса gRPC компилируется только с feature `grpc`
+    #[cfg(feature = "grpc")]
+    {
+        let protoc = protoc_bin_vendored::protoc_bin_path().expect("protoc недоступен");
+        // SAFETY: сценарий сборки однопоточный, переменная читается только tonic-prost-build
+        unsafe { std::env::set_var("PROTOC", protoc) };
+        tonic_prost_build::compile_protos("proto/fin_parser.proto")
+            .expect("Невозможно скомпилировать proto/fin_parser.proto");
+    }
+}
@@ -0,0 +1,16 @@
+use std::env;
+use std::path::PathBuf;
+
+fn main() {
+    // Заголовок C генерируется в OUT_DIR и не хранится в исходниках
+    let out_dir = PathBuf::from(env::var("OUT_DIR").expect("OUT_DIR не задан"));
+    let config = cbindgen::Config::from_file("cbindgen.toml").expect("Некорректный cbindgen.toml");
+    cbindgen::Builder::new()
+        .with_src("src/lib.rs")
+        .with_config(config)
+        .generate()
+        .expect("Невозможно сгенерировать заголовок C")
+        .write_to_file(out_dir.join("fin_parser.h"));
+    println!("cargo::rerun-if-changed=src/lib.rs");
+    println!("cargo::rerun-if-changed=cbindgen.toml");
+}
@@ -0,0 +1,357 @@
+//! # fin-parser-ffi
+//! > Интерфейс C для чтения транзакций библиотеки fin-parser
+//!
+//! Функции возвращают код результата [FIN_PARSER_OK], [FIN_PARSER_END] или
+//! отрицательный код ошибки; текст последней ошибки потока доступен через
+//! [fin_parser_last_error]. Заголовок `fin_parser.h` генерируется
+//! cbindgen при сборке в каталог `OUT_DIR`; библиотека собирается как cdylib и staticlib.
+//!
+//! ```c
+//! FinParserReader *reader;
+//! if (fin_parser_reader_new("transactions.bin", FIN_PARSER_FORMAT_AUTO, &reader) != FIN_PARSER_OK) {
+//!     fprintf(stderr, "%s\n", fin_parser_last_error());
+//!     return 1;
+//! }
+//! FinParserTransaction tx;
+//! int32_t res;
+//! while ((res = fin_parser_read_tx(reader, &tx)) == FIN_PARSER_OK) {
+//!     printf("%llu %lld\n", tx.tx_id, tx.amount);
+//! }
+//! fin_parser_reader_free(reader);
+//! ```
+
+#![warn(missing_docs)]
+
+use fin_parser::builder::TxReaderBuilder;
+use fin_parser::error::ParsError;
+use fin_parser::format::Format;
+use fin_parser::transaction::{TxStatus, TxType};
+use fin_parser::tx_format::TxReader;
+use std::cell::RefCell;
+use std::ffi::{CStr, CString, c_char};
+use std::fs::File;
+use std::io::{Cursor, Read};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
+use std::ptr;
+
+/// Транзакция прочитана
+pub const FIN_PARSER_OK: i32 = 0;
+/// Транзакций больше нет
+pub const FIN_PARSER_END: i32 = 1;
+/// Передан нулевой указатель
+pub const FIN_PARSER_ERR_NULL: i32 = -1;
+/// Ошибка ввода-вывода
+pub const FIN_PARSER_ERR_IO: i32 = -2;
+/// Нарушение формата данных
+pub const FIN_PARSER_ERR_FORMAT: i32 = -3;
+/// Некорректный аргумент: неизвестный код формата или путь не в UTF-8
+pub const FIN_PARSER_ERR_ARGUMENT: i32 = -4;
+/// Внутренняя ошибка библиотеки
+pub const FIN_PARSER_ERR_INTERNAL: i32 = -5;
+
+/// Формат определяется по расширению файла или по содержимому
+pub const FIN_PARSER_FORMAT_AUTO: i32 = 0;
+/// Формат bin
+pub const FIN_PARSER_FORMAT_BIN: i32 = 1;
+/// Формат csv
+pub const FIN_PARSER_FORMAT_CSV: i32 = 2;
+/// Формат text
+pub const FIN_PARSER_FORMAT_TEXT: i32 = 3;
+
+/// Тип DEPOSIT. Коды типов и статусов совпадают с кодами формата bin
+pub const FIN_PARSER_TX_DEPOSIT: u8 = 0;
+/// Тип TRANSFER
+pub const FIN_PARSER_TX_TRANSFER: u8 = 1;
+/// Тип WITHDRAWAL
+pub const FIN_PARSER_TX_WITHDRAWAL: u8 = 2;
+
+/// Статус SUCCESS
+pub const FIN_PARSER_STATUS_SUCCESS: u8 = 0;
+/// Статус FAILURE
+pub const FIN_PARSER_STATUS_FAILURE: u8 = 1;
+/// Статус PENDING
+pub const FIN_PARSER_STATUS_PENDING: u8 = 2;
+
+/// Транзакция для C
+#[repr(C)]
+#[derive(Debug)]
+pub struct FinParserTransaction {
+    /// Идентификатор транзакции
+    pub tx_id: u64,
+    /// Тип транзакции, FIN_PARSER_TX_*
+    pub tx_type: u8,
+    /// Идентификатор инициатора транзакции
+    pub from_user_id: u64,
+    /// Идентификатор получателя транзакции
+    pub to_user_id: u64,
+    /// Сумма транзакции
+    pub amount: i64,
+    /// Время транзакции в миллисекундах от начала эпохи Unix
+    pub timestamp_ms: i64,
+    /// Статус транзакции, FIN_PARSER_STATUS_*
+    pub status: u8,
+    /// Описание в UTF-8, завершенное нулем. Принадлежит читателю и действительно
+    /// до следующего вызова fin_parser_read_tx или fin_parser_reader_free
+    pub description: *const c_char,
+    /// Длина описания в байтах без завершающего нуля
+    pub description_len: usize,
+}
+
+/// Читатель транзакций. Создается fin_parser_reader_new или
+/// fin_parser_reader_from_memory и удаляется fin_parser_reader_free
+pub struct FinParserReader {
+    reader: TxReader<Box<dyn Read + Send>>,
+    /// Описание последней транзакции с завершающим нулем
+    description: Vec<u8>,
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// Текст последней ошибки в текущем потоке или NULL, если ошибок не было.
+/// Строка действительна до следующей ошибки в этом потоке
+#[unsafe(no_mangle)]
+pub extern "C" fn fin_parser_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| {
+        last.borrow()
+            .as_ref()
+            .map_or(ptr::null(), |msg| msg.as_ptr())
+    })
+}
+
+/// Открытие файла. Формат FIN_PARSER_FORMAT_*; при FIN_PARSER_FORMAT_AUTO формат
+/// и сжатие определяются по расширению, а для неизвестных расширений — по содержимому.
+/// При успехе в *out записывается читатель
+///
+/// # Safety
+/// path — строка, завершенная нулем; out — указатель, доступный для записи
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn fin_parser_reader_new(
+    path: *const c_char,
+    format: i32,
+    out: *mut *mut FinParserReader,
+) -> i32 {
+    if path.is_null() || out.is_null() {
+        return fail(FIN_PARSER_ERR_NULL, "Нулевой указатель");
+    }
+    // SAFETY: path не нулевой и по контракту завершен нулем
+    let path = unsafe { CStr::from_ptr(path) };
+    guard(|| {
+        let path = path
+            .to_str()
+            .map_err(|_| (FIN_PARSER_ERR_ARGUMENT, "Путь не в UTF-8".to_owned()))?;
+        let builder = reader_builder(format)?;
+        let res = match format {
+            FIN_PARSER_FORMAT_AUTO if Format::from_path(Path::new(path)).is_err() => {
+                File::open(path)
+                    .map_err(ParsError::from)
+                    .and_then(|file| builder.build(file))
+            }
+            _ => builder.open(path),
+        };
+        // SAFETY: out не нулевой и по контракту доступен для записи
+        unsafe { *out = new_reader(res.map_err(error)?) };
+        Ok(FIN_PARSER_OK)
+    })
+}
+
+/// Чтение из буфера в памяти, данные копируются. Формат FIN_PARSER_FORMAT_*;
+/// при FIN_PARSER_FORMAT_AUTO формат определяется по содержимому
+///
+/// # Safety
+/// data указывает на len доступных для чтения байт (при len = 0 может быть NULL);
+/// out — указатель, доступный для записи
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn fin_parser_reader_from_memory(
+    data: *const u8,
+    len: usize,
+    format: i32,
+    out: *mut *mut FinParserReader,
+) -> i32 {
+    if (data.is_null() && len > 0) || out.is_null() {
+        return fail(FIN_PARSER_ERR_NULL, "Нулевой указатель");
+    }
+    let data = match len {
+        0 => Vec::new(),
+        // SAFETY: data не нулевой и по контракту указывает на len байт
+        _ => unsafe { std::slice::from_raw_parts(data, len) }.to_vec(),
+    };
+    guard(|| {
+        let reader = reader_builder(format)?
+            .build(Cursor::new(data))
+            .map_err(error)?;
+        // SAFETY: out не нулевой и по контракту доступен для записи
+        unsafe { *out = new_reader(reader) };
+        Ok(FIN_PARSER_OK)
+    })
+}
+
+/// Чтение следующей транзакции в *tx. Возвращает FIN_PARSER_OK, FIN_PARSER_END
+/// в конце данных или код ошибки
+///
+/// # Safety
+/// reader получен из fin_parser_reader_new или fin_parser_reader_from_memory и не удален;
+/// tx — указатель, доступный для записи
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn fin_parser_read_tx(
+    reader: *mut FinParserReader,
+    tx: *mut FinParserTransaction,
+) -> i32 {
+    if reader.is_null() || tx.is_null() {
+        return fail(FIN_PARSER_ERR_NULL, "Нулевой указатель");
+    }
+    // SAFETY: reader не нулевой и по контракту получен из Box::into_raw
+    let reader = unsafe { &mut *reader };
+    guard(|| {
+        let Some(val) = reader.reader.read_transaction().map_err(error)? else {
+            return Ok(FIN_PARSER_END);
+        };
+        reader.description.clear();
+        reader
+            .description
+            .extend_from_slice(val.description.as_bytes());
+        reader.description.push(0);
+        let res = FinParserTransaction {
+            tx_id: val.tx_id,
+            tx_type: match val.tx_type {
+                TxType::Deposit => FIN_PARSER_TX_DEPOSIT,
+                TxType::Transfer => FIN_PARSER_TX_TRANSFER,
+                TxType::Withdrawal => FIN_PARSER_TX_WITHDRAWAL,
+            },
+            from_user_id: val.from_user_id,
+            to_user_id: val.to_user_id,
+            amount: val.amount,
+            timestamp_ms: val.timestamp.timestamp_millis(),
+            status: match val.status {
+                TxStatus::Success => FIN_PARSER_STATUS_SUCCESS,
+                TxStatus::Failure => FIN_PARSER_STATUS_FAILURE,
+                TxStatus::Pending => FIN_PARSER_STATUS_PENDING,
+            },
+            description: reader.description.as_ptr().cast(),
+            description_len: val.description.len(),
+        };
+        // SAFETY: tx не нулевой и по контракту доступен для записи
+        unsafe { tx.write(res) };
+        Ok(FIN_PARSER_OK)
+    })
+}
+
+/// Удаление читателя. NULL допускается и игнорируется
+///
+/// # Safety
+/// reader получен из fin_parser_reader_new или fin_parser_reader_from_memory
+/// и удаляется один раз
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn fin_parser_reader_free(reader: *mut FinParserReader) {
+    if !reader.is_null() {
+        // SAFETY: по контракту reader получен из Box::into_raw и еще не удален
+        drop(unsafe { Box::from_raw(reader) });
+    }
+}
+
+fn reader_builder(format: i32) -> Result<TxReaderBuilder, (i32, String)> {
+    let builder = TxReaderBuilder::new();
+    match format {
+        FIN_PARSER_FORMAT_AUTO => Ok(builder),
+        FIN_PARSER_FORMAT_BIN => Ok(builder.format(Format::Bin)),
+        FIN_PARSER_FORMAT_CSV => Ok(builder.format(Format::Csv)),
+        FIN_PARSER_FORMAT_TEXT => Ok(builder.format(Format::Text)),
+        _ => Err((
+            FIN_PARSER_ERR_ARGUMENT,
+            format!("Неизвестный код формата: {format}"),
+        )),
+    }
+}
+
+fn new_reader(reader: TxReader<Box<dyn Read + Send>>) -> *mut FinParserReader {
+    Box::into_raw(Box::new(FinParserReader {
+        reader,
+        description: vec![0],
+    }))
+}
+
+/// Код и текст ошибки разбора
+fn error(e: ParsError) -> (i32, String) {
+    let code = match e {
+        ParsError::IoError(_) => FIN_PARSER_ERR_IO,
+        _ => FIN_PARSER_ERR_FORMAT,
+    };
+    (code, e.to_string())
+}
+
+/// Сохранение текста ошибки и возврат ее кода
+fn fail(code: i32, msg: &str) -> i32 {
+    // Сообщения библиотеки не содержат нулевых байт, но на всякий случай они отбрасываются
+    let msg = CString::new(msg.replace('\0', "")).unwrap_or_default();
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(msg));
+    code
+}
+
+/// Выполнение f с перехватом паники, которая не должна пересекать границу C
+fn guard<F: FnOnce() -> Result<i32, (i32, String)>>(f: F) -> i32 {
+    match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(code)) => code,
+        Ok(Err((code, msg))) => fail(code, &msg),
+        Err(_) => fail(FIN_PARSER_ERR_INTERNAL, "Внутренняя ошибка библиотеки"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::DateTime;
+    use fin_parser::transaction::Transaction;
+    use fin_parser::tx_format::TxWriter;
+
+    #[test]
+    fn test_ffi_reader() {
+        let tx = Transaction {
+            tx_id: 7,
+            tx_type: TxType::Withdrawal,
+            from_user_id: 1,
+            to_user_id: 0,
+            amount: 300,
+            timestamp: DateTime::from_timestamp_millis(1633036860123).unwrap(),
+            status: TxStatus::Pending,
+            description: "Record number 7".to_owned(),
+        };
+        let data = tx.to_bytes_format(Format::Bin).unwrap();
+        let mut reader = ptr::null_mut();
+        let mut res = std::mem::MaybeUninit::<FinParserTransaction>::uninit();
+        unsafe {
+            let code = fin_parser_reader_from_memory(data.as_ptr(), data.len(), 0, &mut reader);
+            assert_eq!(code, FIN_PARSER_OK);
+            assert_eq!(fin_parser_read_tx(reader, res.as_mut_ptr()), FIN_PARSER_OK);
+            let res = res.assume_init();
+            assert_eq!((res.tx_id, res.tx_type), (7, FIN_PARSER_TX_WITHDRAWAL));
+            assert_eq!(res.status, FIN_PARSER_STATUS_PENDING);
+            assert_eq!(res.timestamp_ms, 1633036860123);
+            let description = CStr::from_ptr(res.description).to_str().unwrap();
+            assert_eq!(description.len(), res.description_len);
+            assert_eq!(description, "Record number 7");
+            let mut res = std::mem::MaybeUninit::uninit();
+            assert_eq!(fin_parser_read_tx(reader, res.as_mut_ptr()), FIN_PARSER_END);
+            fin_parser_reader_free(reader);
+
+            let mut writer = TxWriter::new(Vec::new(), Format::Text).unwrap();
+            writer.write_transaction(&tx).unwrap();
+            let data = writer.into_inner().unwrap();
+            let code = fin_parser_reader_from_memory(data.as_ptr(), 10, 3, &mut reader);
+            assert_eq!(code, FIN_PARSER_OK);
+            let mut res = std::mem::MaybeUninit::uninit();
+            assert_eq!(
+                fin_parser_read_tx(reader, res.as_mut_ptr()),
+                FIN_PARSER_ERR_FORMAT
+            );
+            assert!(!fin_parser_last_error().is_null());
+            fin_parser_reader_free(reader);
+
+            let code = fin_parser_reader_from_memory(ptr::null(), 0, 9, &mut reader);
+            assert_eq!(code, FIN_PARSER_ERR_ARGUMENT);
+            let msg = CStr::from_ptr(fin_parser_last_error()).to_str().unwrap();
+            assert_eq!(msg, "Неизвестный код формата: 9");
+        }
+    }
+}
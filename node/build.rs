@@ -0,0 +1,4 @@
+fn main() {
+    // Параметры компоновки модуля Node.js
+    napi_build::setup();
+}
@@ -0,0 +1,275 @@
+//! # fin-parser-node
+//! > Модуль Node.js (N-API) для потокового чтения и записи файлов транзакций
+//!
+//! Модуль — это cdylib крейта, переименованная для Node.js:
+//! `cargo build --release -p fin-parser-node`, затем
+//! `cp target/release/libfin_parser_node.so fin_parser.node`.
+//! Формат и сжатие определяются по расширению файла или задаются явно
+//! (`bin`, `csv`, `text` и зарегистрированные форматы). Идентификаторы передаются
+//! как `bigint`, чтобы не терять точность значений больше 2^53.
+//!
+//! Функции Node-API разрешаются при загрузке модуля в процесс Node.js
+//! (napi `dyn-symbols`). Тесты собираются с napi `noop`, чтобы не искать их вне Node.js.
+//!
+//! ```js
+//! const { Reader, Writer } = require('./fin_parser.node');
+//! const reader = new Reader('transactions.bin');
+//! const writer = new Writer('pending.csv.gz');
+//! for (let batch; (batch = reader.readBatch(1000)).length > 0; ) {
+//!   writer.writeBatch(batch.filter((tx) => tx.status === 'PENDING'));
+//! }
+//! writer.finish();
+//! ```
+
+// Служебные функции классов, создаваемые макросом napi, не документированы
+#![allow(missing_docs)]
+
+use chrono::DateTime;
+use fin_parser::builder::{TxReaderBuilder, TxWriterBuilder};
+use fin_parser::error::ParsError;
+use fin_parser::format::Format;
+use fin_parser::reconcile::Field;
+use fin_parser::transaction::{Transaction, TxStatus, TxType};
+use fin_parser::tx_format::{TxReader, TxWriter};
+use napi::bindgen_prelude::{BigInt, Buffer};
+use napi_derive::napi;
+use std::fs::File;
+use std::io::{Cursor, Read, Write};
+use std::path::Path;
+
+/// Транзакция в представлении JavaScript
+#[napi(object, js_name = "Transaction")]
+pub struct JsTransaction {
+    /// Идентификатор транзакции
+    pub tx_id: BigInt,
+    /// Тип транзакции: `DEPOSIT`, `TRANSFER` или `WITHDRAWAL`
+    pub tx_type: String,
+    /// Идентификатор инициатора транзакции
+    pub from_user_id: BigInt,
+    /// Идентификатор получателя транзакции
+    pub to_user_id: BigInt,
+    /// Сумма транзакции
+    pub amount: i64,
+    /// Время транзакции в миллисекундах от начала эпохи Unix
+    pub timestamp: i64,
+    /// Статус транзакции: `SUCCESS`, `FAILURE` или `PENDING`
+    pub status: String,
+    /// Описание транзакции
+    pub description: String,
+}
+
+impl From<Transaction> for JsTransaction {
+    fn from(tx: Transaction) -> Self {
+        Self {
+            tx_id: tx.tx_id.into(),
+            tx_type: Field::TxType.value(&tx),
+            from_user_id: tx.from_user_id.into(),
+            to_user_id: tx.to_user_id.into(),
+            amount: tx.amount,
+            timestamp: tx.timestamp.timestamp_millis(),
+            status: Field::Status.value(&tx),
+            description: tx.description,
+        }
+    }
+}
+
+impl TryFrom<JsTransaction> for Transaction {
+    type Error = ParsError;
+
+    fn try_from(tx: JsTransaction) -> Result<Self, Self::Error> {
+        let (_, tx_id, _) = tx.tx_id.get_u64();
+        let wrong = |field: &str, val: &dyn std::fmt::Display| {
+            ParsError::WrongFormat(format!(
+                "Транзакция {tx_id}: некорректное поле {field}: {val}"
+            ))
+        };
+        let id = |field: &str, val: &BigInt| match val.get_u64() {
+            (false, val, true) => Ok(val),
+            _ => Err(wrong(field, &"значение вне диапазона u64")),
+        };
+        let mut res = Self {
+            tx_id: id("txId", &tx.tx_id)?,
+            tx_type: TxType::Deposit,
+            from_user_id: id("fromUserId", &tx.from_user_id)?,
+            to_user_id: id("toUserId", &tx.to_user_id)?,
+            amount: tx.amount,
+            timestamp: DateTime::from_timestamp_millis(tx.timestamp)
+                .ok_or_else(|| wrong("timestamp", &tx.timestamp))?,
+            status: TxStatus::Success,
+            description: tx.description,
+        };
+        Field::TxType
+            .set_value(&mut res, &tx.tx_type)
+            .map_err(|_| wrong("txType", &tx.tx_type))?;
+        Field::Status
+            .set_value(&mut res, &tx.status)
+            .map_err(|_| wrong("status", &tx.status))?;
+        Ok(res)
+    }
+}
+
+/// Читатель файла или буфера. Транзакции читаются по мере вызова `read` и `readBatch`
+#[napi]
+pub struct Reader {
+    reader: TxReader<Box<dyn Read + Send>>,
+}
+
+#[napi]
+impl Reader {
+    /// Открытие файла. Без format формат определяется по расширению или содержимому
+    #[napi(constructor)]
+    pub fn new(path: String, format: Option<String>) -> napi::Result<Self> {
+        let builder = reader_builder(format.as_deref())?;
+        let res = match Format::from_path(Path::new(&path)) {
+            Err(_) if format.is_none() => File::open(&path)
+                .map_err(ParsError::from)
+                .and_then(|file| builder.build(file)),
+            _ => builder.open(&path),
+        };
+        Ok(Self {
+            reader: res.map_err(js_error)?,
+        })
+    }
+
+    /// Чтение содержимого буфера. Без format формат определяется по содержимому
+    #[napi(factory)]
+    pub fn from_buffer(data: Buffer, format: Option<String>) -> napi::Result<Self> {
+        let reader = reader_builder(format.as_deref())?
+            .build(Cursor::new(data.to_vec()))
+            .map_err(js_error)?;
+        Ok(Self { reader })
+    }
+
+    /// Следующая транзакция или null в конце данных
+    #[napi]
+    pub fn read(&mut self) -> napi::Result<Option<JsTransaction>> {
+        let tx = self.reader.read_transaction().map_err(js_error)?;
+        Ok(tx.map(JsTransaction::from))
+    }
+
+    /// До size следующих транзакций. Пустой массив означает конец данных
+    #[napi]
+    pub fn read_batch(&mut self, size: u32) -> napi::Result<Vec<JsTransaction>> {
+        let mut res = Vec::new();
+        while res.len() < size as usize {
+            match self.reader.read_transaction().map_err(js_error)? {
+                Some(tx) => res.push(tx.into()),
+                None => break,
+            }
+        }
+        Ok(res)
+    }
+}
+
+/// Писатель файла. Файл дописывается и закрывается вызовом `finish`
+#[napi]
+pub struct Writer {
+    writer: Option<TxWriter<Box<dyn Write + Send>>>,
+}
+
+#[napi]
+impl Writer {
+    /// Создание файла. Без format формат и сжатие определяются по расширению.
+    /// Существующий файл перезаписывается
+    #[napi(constructor)]
+    pub fn new(path: String, format: Option<String>) -> napi::Result<Self> {
+        let mut builder = TxWriterBuilder::new();
+        if let Some(format) = format {
+            builder = builder.format(format.parse().map_err(js_error)?);
+        }
+        Ok(Self {
+            writer: Some(builder.create(path).map_err(js_error)?),
+        })
+    }
+
+    /// Запись транзакции
+    #[napi]
+    pub fn write(&mut self, tx: JsTransaction) -> napi::Result<()> {
+        let tx = Transaction::try_from(tx).map_err(js_error)?;
+        self.writer()?.write_transaction(&tx).map_err(js_error)
+    }
+
+    /// Запись массива транзакций
+    #[napi]
+    pub fn write_batch(&mut self, txs: Vec<JsTransaction>) -> napi::Result<()> {
+        let txs = txs
+            .into_iter()
+            .map(Transaction::try_from)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(js_error)?;
+        self.writer()?.write_batch(&txs).map_err(js_error)
+    }
+
+    /// Завершение записи. После вызова писатель недоступен
+    #[napi]
+    pub fn finish(&mut self) -> napi::Result<()> {
+        let Some(mut writer) = self.writer.take() else {
+            return Ok(());
+        };
+        writer.finish().map_err(js_error)?;
+        // Сжатый поток завершается при удалении писателя
+        drop(writer);
+        Ok(())
+    }
+
+    fn writer(&mut self) -> napi::Result<&mut TxWriter<Box<dyn Write + Send>>> {
+        self.writer
+            .as_mut()
+            .ok_or_else(|| napi::Error::from_reason("Запись уже завершена"))
+    }
+}
+
+fn reader_builder(format: Option<&str>) -> napi::Result<TxReaderBuilder> {
+    let builder = TxReaderBuilder::new();
+    match format {
+        Some(format) => Ok(builder.format(format.parse().map_err(js_error)?)),
+        None => Ok(builder),
+    }
+}
+
+fn js_error(e: ParsError) -> napi::Error {
+    napi::Error::from_reason(e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tx() -> Transaction {
+        Transaction {
+            tx_id: u64::MAX,
+            tx_type: TxType::Transfer,
+            from_user_id: 1,
+            to_user_id: 2,
+            amount: -100,
+            timestamp: DateTime::from_timestamp_millis(1633036860123).unwrap(),
+            status: TxStatus::Pending,
+            description: "Record number 1".to_owned(),
+        }
+    }
+
+    #[test]
+    fn test_convert() {
+        let js = JsTransaction::from(tx());
+        assert_eq!(
+            (js.tx_type.as_str(), js.status.as_str()),
+            ("TRANSFER", "PENDING")
+        );
+        assert_eq!(js.timestamp, 1633036860123);
+        assert_eq!(Transaction::try_from(js).unwrap(), tx());
+
+        let negative = JsTransaction {
+            to_user_id: BigInt {
+                sign_bit: true,
+                words: vec![1],
+            },
+            ..tx().into()
+        };
+        assert!(Transaction::try_from(negative).is_err());
+        let lowercase = JsTransaction {
+            tx_type: "deposit".to_owned(),
+            ..tx().into()
+        };
+        assert!(Transaction::try_from(lowercase).is_err());
+    }
+}
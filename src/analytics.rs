@@ -0,0 +1,403 @@
+use super::error::ParsError;
+use super::money::Money;
+use super::transaction::{Transaction, TransactionRef, TxStatus, TxType};
+use super::tx_format::TxReader;
+use chrono::{DateTime, NaiveDate, Utc};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::{Read, Write};
+
+/// Количество и суммы транзакций одной группы
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Aggregate {
+    /// Количество транзакций
+    pub count: u64,
+    /// Сумма транзакций. Хранится в i128, чтобы не переполняться на больших потоках
+    pub sum: i128,
+    /// Минимальная сумма транзакции
+    pub min: Option<i64>,
+    /// Максимальная сумма транзакции
+    pub max: Option<i64>,
+}
+
+impl Aggregate {
+    /// Учет суммы транзакции
+    pub fn add(&mut self, amount: i64) {
+        self.count += 1;
+        self.sum += amount as i128;
+        self.min = Some(self.min.map_or(amount, |val| val.min(amount)));
+        self.max = Some(self.max.map_or(amount, |val| val.max(amount)));
+    }
+
+    /// Объединение с агрегатом той же группы, посчитанным по другой части потока
+    pub fn merge(&mut self, other: &Aggregate) {
+        self.count += other.count;
+        self.sum += other.sum;
+        self.min = self.min.into_iter().chain(other.min).min();
+        self.max = self.max.into_iter().chain(other.max).max();
+    }
+
+    /// Сумма группы как [Money]. Ошибка, если сумма не помещается в i64
+    pub fn checked_sum(&self) -> Result<Money, ParsError> {
+        Money::try_from(self.sum)
+    }
+
+    /// Средняя сумма транзакции. Для пустой группы None
+    pub fn mean(&self) -> Option<f64> {
+        (self.count > 0).then(|| self.sum as f64 / self.count as f64)
+    }
+
+    fn json(&self) -> String {
+        let num = |val: Option<i64>| val.map_or("null".to_owned(), |val| val.to_string());
+        format!(
+            "{{\"count\":{},\"sum\":{},\"min\":{},\"max\":{},\"mean\":{}}}",
+            self.count,
+            self.sum,
+            num(self.min),
+            num(self.max),
+            self.mean()
+                .map_or("null".to_owned(), |val| format!("{val:.2}"))
+        )
+    }
+}
+
+impl fmt::Display for Aggregate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "количество {}, сумма {}", self.count, self.sum)?;
+        if let (Some(min), Some(max), Some(mean)) = (self.min, self.max, self.mean()) {
+            write!(f, ", мин. {min}, макс. {max}, среднее {mean:.2}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Сводная статистика по потоку транзакций. Собирается за один проход
+/// без хранения самих транзакций: память растет только с количеством групп
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Summary {
+    /// Все транзакции
+    pub total: Aggregate,
+    /// Группировка по типу
+    pub by_type: BTreeMap<TxType, Aggregate>,
+    /// Группировка по статусу
+    pub by_status: BTreeMap<TxStatus, Aggregate>,
+    /// Группировка по инициатору
+    pub by_from_user: BTreeMap<u64, Aggregate>,
+    /// Группировка по получателю
+    pub by_to_user: BTreeMap<u64, Aggregate>,
+    /// Группировка по дню (UTC)
+    pub by_day: BTreeMap<NaiveDate, Aggregate>,
+    /// Распределение сумм: ключ — количество десятичных знаков модуля суммы,
+    /// см. [magnitude_range]
+    pub by_magnitude: BTreeMap<u32, Aggregate>,
+    /// Время самой ранней транзакции
+    pub earliest: Option<DateTime<Utc>>,
+    /// Время самой поздней транзакции
+    pub latest: Option<DateTime<Utc>>,
+}
+
+impl Summary {
+    /// Учет транзакции
+    pub fn add(&mut self, tx: &TransactionRef<'_>) {
+        self.total.add(tx.amount);
+        self.by_type.entry(tx.tx_type).or_default().add(tx.amount);
+        self.by_status.entry(tx.status).or_default().add(tx.amount);
+        self.by_from_user
+            .entry(tx.from_user_id)
+            .or_default()
+            .add(tx.amount);
+        self.by_to_user
+            .entry(tx.to_user_id)
+            .or_default()
+            .add(tx.amount);
+        self.by_day
+            .entry(tx.timestamp.date_naive())
+            .or_default()
+            .add(tx.amount);
+        self.by_magnitude
+            .entry(magnitude(tx.amount))
+            .or_default()
+            .add(tx.amount);
+        self.earliest = Some(
+            self.earliest
+                .map_or(tx.timestamp, |val| val.min(tx.timestamp)),
+        );
+        self.latest = Some(
+            self.latest
+                .map_or(tx.timestamp, |val| val.max(tx.timestamp)),
+        );
+    }
+
+    /// Учет транзакции, владеющей описанием
+    pub fn add_tx(&mut self, tx: &Transaction) {
+        self.add(&TransactionRef::from(tx))
+    }
+
+    /// Объединение со статистикой, посчитанной по другой части потока
+    pub fn merge(&mut self, other: &Summary) {
+        fn merge_groups<K: Ord + Copy>(
+            lhs: &mut BTreeMap<K, Aggregate>,
+            rhs: &BTreeMap<K, Aggregate>,
+        ) {
+            for (key, val) in rhs {
+                lhs.entry(*key).or_default().merge(val);
+            }
+        }
+
+        self.total.merge(&other.total);
+        merge_groups(&mut self.by_type, &other.by_type);
+        merge_groups(&mut self.by_status, &other.by_status);
+        merge_groups(&mut self.by_from_user, &other.by_from_user);
+        merge_groups(&mut self.by_to_user, &other.by_to_user);
+        merge_groups(&mut self.by_day, &other.by_day);
+        merge_groups(&mut self.by_magnitude, &other.by_magnitude);
+        self.earliest = self.earliest.into_iter().chain(other.earliest).min();
+        self.latest = self.latest.into_iter().chain(other.latest).max();
+    }
+
+    /// Самые активные пользователи: до n пользователей с наибольшим количеством
+    /// транзакций, в которых они инициатор или получатель. Системный пользователь 0
+    /// не учитывается
+    pub fn busiest_users(&self, n: usize) -> Vec<(u64, Aggregate)> {
+        let mut users: BTreeMap<u64, Aggregate> = BTreeMap::new();
+        for (user, val) in self.by_from_user.iter().chain(&self.by_to_user) {
+            if *user != 0 {
+                users.entry(*user).or_default().merge(val);
+            }
+        }
+        let mut res: Vec<(u64, Aggregate)> = users.into_iter().collect();
+        res.sort_by(|lhs, rhs| rhs.1.count.cmp(&lhs.1.count).then(lhs.0.cmp(&rhs.0)));
+        res.truncate(n);
+        res
+    }
+
+    /// Отчет в json. Вместо группировок по пользователям выводятся
+    /// top самых активных пользователей, см. [Summary::busiest_users]
+    pub fn write_json<Out: Write>(&self, out: &mut Out, top: usize) -> Result<(), ParsError> {
+        fn write_groups<Out: Write, K: fmt::Debug>(
+            out: &mut Out,
+            name: &str,
+            groups: &BTreeMap<K, Aggregate>,
+        ) -> Result<(), ParsError> {
+            write!(out, ",\"{name}\":{{")?;
+            for (idx, (key, val)) in groups.iter().enumerate() {
+                let sep = if idx == 0 { "" } else { "," };
+                write!(out, "{sep}\"{key:?}\":{}", val.json())?;
+            }
+            write!(out, "}}")?;
+            Ok(())
+        }
+
+        write!(out, "{{\"total\":{}", self.total.json())?;
+        let time = |val: Option<DateTime<Utc>>| {
+            val.map_or("null".to_owned(), |val| format!("\"{}\"", val.to_rfc3339()))
+        };
+        write!(
+            out,
+            ",\"earliest\":{},\"latest\":{}",
+            time(self.earliest),
+            time(self.latest)
+        )?;
+        write_groups(out, "by_type", &self.by_type)?;
+        write_groups(out, "by_status", &self.by_status)?;
+        write!(out, ",\"by_day\":{{")?;
+        for (idx, (day, val)) in self.by_day.iter().enumerate() {
+            let sep = if idx == 0 { "" } else { "," };
+            write!(out, "{sep}\"{day}\":{}", val.json())?;
+        }
+        write!(out, "}}")?;
+        write_groups(out, "by_magnitude", &self.by_magnitude)?;
+        write!(out, ",\"busiest_users\":[")?;
+        for (idx, (user, val)) in self.busiest_users(top).iter().enumerate() {
+            let sep = if idx == 0 { "" } else { "," };
+            write!(out, "{sep}{{\"user_id\":{user},\"stats\":{}}}", val.json())?;
+        }
+        writeln!(out, "]}}")?;
+        Ok(())
+    }
+}
+
+/// Текстовый отчет по статистике
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fn write_groups<K: fmt::Debug>(
+            f: &mut fmt::Formatter<'_>,
+            title: &str,
+            groups: &BTreeMap<K, Aggregate>,
+        ) -> fmt::Result {
+            if groups.is_empty() {
+                return Ok(());
+            }
+            writeln!(f, "{title}:")?;
+            for (key, val) in groups {
+                writeln!(f, "  {key:?}: {val}")?;
+            }
+            Ok(())
+        }
+
+        writeln!(f, "Всего: {}", self.total)?;
+        if let (Some(earliest), Some(latest)) = (self.earliest, self.latest) {
+            writeln!(f, "Период: {earliest} - {latest}")?;
+        }
+        write_groups(f, "По типу", &self.by_type)?;
+        write_groups(f, "По статусу", &self.by_status)?;
+        write_groups(f, "По инициатору", &self.by_from_user)?;
+        write_groups(f, "По получателю", &self.by_to_user)?;
+        write_groups(f, "По дням", &self.by_day)?;
+        if !self.by_magnitude.is_empty() {
+            writeln!(f, "По сумме:")?;
+            for (digits, val) in &self.by_magnitude {
+                let (low, high) = magnitude_range(*digits);
+                writeln!(f, "  {low}..{high}: {val}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Количество десятичных знаков модуля суммы, для нуля 0
+pub(crate) fn magnitude(amount: i64) -> u32 {
+    amount
+        .unsigned_abs()
+        .checked_ilog10()
+        .map_or(0, |val| val + 1)
+}
+
+/// Границы модуля суммы для ключа [Summary::by_magnitude] включительно
+pub fn magnitude_range(digits: u32) -> (u64, u64) {
+    match digits {
+        0 => (0, 0),
+        _ => (
+            10u64.pow(digits - 1),
+            10u64.checked_pow(digits).map_or(u64::MAX, |val| val - 1),
+        ),
+    }
+}
+
+/// Подсчет статистики по оставшимся в потоке транзакциям без выделения памяти под описания
+pub fn summarize<In: Read>(reader: &mut TxReader<In>) -> Result<Summary, ParsError> {
+    let mut summary = Summary::default();
+    while let Some(tx) = reader.read_transaction_ref()? {
+        summary.add(&tx);
+    }
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::Format;
+    use crate::test_util::{reader, txs};
+    use chrono::TimeDelta;
+
+    fn txs_for_test() -> Vec<Transaction> {
+        txs(0..6)
+            .into_iter()
+            .map(|tx| Transaction {
+                tx_type: if tx.tx_id % 2 == 0 {
+                    TxType::Deposit
+                } else {
+                    TxType::Withdrawal
+                },
+                from_user_id: tx.tx_id % 2,
+                to_user_id: 10,
+                amount: tx.tx_id as i64 * 100 - 100,
+                timestamp: tx.timestamp + TimeDelta::hours(12 * tx.tx_id as i64),
+                ..tx
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_summarize() {
+        let mut reader = reader(&txs_for_test(), Format::Bin);
+        let summary = summarize(&mut reader).unwrap();
+
+        assert_eq!(
+            summary.total,
+            Aggregate {
+                count: 6,
+                sum: 900,
+                min: Some(-100),
+                max: Some(400),
+            }
+        );
+        assert_eq!(summary.total.mean(), Some(150.0));
+        assert_eq!(summary.by_type[&TxType::Deposit].sum, 300);
+        assert_eq!(summary.by_type[&TxType::Withdrawal].sum, 600);
+        assert!(!summary.by_type.contains_key(&TxType::Transfer));
+        assert_eq!(summary.by_status[&TxStatus::Success].count, 6);
+        assert_eq!(summary.by_from_user[&1].count, 3);
+        assert_eq!(summary.by_to_user[&10].count, 6);
+        let days: Vec<u64> = summary.by_day.values().map(|val| val.count).collect();
+        assert_eq!(days, [1, 2, 2, 1]);
+        assert_eq!(summary.earliest, Some(txs_for_test()[0].timestamp));
+        assert_eq!(summary.latest, Some(txs_for_test()[5].timestamp));
+        assert!(
+            summary
+                .to_string()
+                .contains("Withdrawal: количество 3, сумма 600")
+        );
+    }
+
+    #[test]
+    fn test_merge() {
+        let txs = txs_for_test();
+        let mut expected = Summary::default();
+        txs.iter().for_each(|tx| expected.add_tx(tx));
+
+        let mut lhs = Summary::default();
+        txs[..2].iter().for_each(|tx| lhs.add_tx(tx));
+        let mut rhs = Summary::default();
+        txs[2..].iter().for_each(|tx| rhs.add_tx(tx));
+        lhs.merge(&rhs);
+        assert_eq!(lhs, expected);
+
+        let mut empty = Summary::default();
+        empty.merge(&expected);
+        assert_eq!(empty, expected);
+        assert_eq!(Summary::default().total.mean(), None);
+    }
+
+    #[test]
+    fn test_busiest_users() {
+        let mut summary = Summary::default();
+        txs_for_test().iter().for_each(|tx| summary.add_tx(tx));
+        let users: Vec<(u64, u64)> = summary
+            .busiest_users(5)
+            .iter()
+            .map(|(user, val)| (*user, val.count))
+            .collect();
+        assert_eq!(users, [(10, 6), (1, 3)]);
+        let digits: Vec<(u32, u64)> = summary
+            .by_magnitude
+            .iter()
+            .map(|(digits, val)| (*digits, val.count))
+            .collect();
+        assert_eq!(digits, [(0, 1), (3, 5)]);
+        assert_eq!(magnitude_range(3), (100, 999));
+        assert_eq!(magnitude_range(20), (10u64.pow(19), u64::MAX));
+
+        let mut json = Vec::new();
+        summary.write_json(&mut json, 1).unwrap();
+        let json = String::from_utf8(json).unwrap();
+        assert!(json.starts_with(
+            "{\"total\":{\"count\":6,\"sum\":900,\"min\":-100,\"max\":400,\"mean\":150.00}"
+        ));
+        assert!(json.contains("\"by_type\":{\"Deposit\":{\"count\":3,"));
+        assert!(json.ends_with("\"busiest_users\":[{\"user_id\":10,\"stats\":{\"count\":6,\"sum\":900,\"min\":-100,\"max\":400,\"mean\":150.00}}]}\n"));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serialize() {
+        let mut summary = Summary::default();
+        summary.add_tx(&txs_for_test()[1]);
+        let json = serde_json::to_value(&summary).unwrap();
+        assert_eq!(json["total"]["sum"], 0);
+        assert_eq!(json["by_type"]["Withdrawal"]["count"], 1);
+        assert_eq!(json["by_day"]["2021-10-01"]["count"], 1);
+    }
+}
@@ -0,0 +1,117 @@
+use super::transaction::{Transaction, TxStatus, TxType};
+use chrono::{DateTime, Utc};
+use proptest::prelude::*;
+
+/// Наибольшее время транзакции: 9999-12-31T23:59:59.999Z в миллисекундах
+pub const MAX_TIMESTAMP_MS: i64 = 253_402_300_799_999;
+
+/// Допустимые описания: до 64 символов латиницы, кириллицы, цифр, пробельных символов
+/// и знаков препинания, включая кавычки и обратную косую черту, которые csv и text
+/// экранируют обратной косой чертой
+pub const DESCRIPTION_REGEX: &str = "[0-9A-Za-zА-Яа-яЁё .,:;#№_+\"\\\\\\n\\r\\t-]{0,64}";
+
+/// Время транзакции от начала эпохи до конца 9999 года с точностью до миллисекунды
+pub fn timestamp() -> impl Strategy<Value = DateTime<Utc>> {
+    (0..=MAX_TIMESTAMP_MS).prop_map(|millis| {
+        DateTime::from_timestamp_millis(millis).expect("Время в пределах MAX_TIMESTAMP_MS")
+    })
+}
+
+/// Описание транзакции, одинаково записываемое во всех форматах ([DESCRIPTION_REGEX])
+pub fn description() -> impl Strategy<Value = String> {
+    proptest::string::string_regex(DESCRIPTION_REGEX).expect("Корректное выражение описания")
+}
+
+impl Arbitrary for TxType {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: Self::Parameters) -> Self::Strategy {
+        prop_oneof![
+            Just(Self::Deposit),
+            Just(Self::Transfer),
+            Just(Self::Withdrawal)
+        ]
+        .boxed()
+    }
+}
+
+impl Arbitrary for TxStatus {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: Self::Parameters) -> Self::Strategy {
+        prop_oneof![
+            Just(Self::Success),
+            Just(Self::Failure),
+            Just(Self::Pending)
+        ]
+        .boxed()
+    }
+}
+
+/// Произвольная транзакция с любыми идентификаторами и суммой, временем из [timestamp]
+/// и описанием из [description]. Транзакция без изменений записывается и читается
+/// во всех форматах
+impl Arbitrary for Transaction {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: Self::Parameters) -> Self::Strategy {
+        (
+            any::<u64>(),
+            any::<TxType>(),
+            any::<u64>(),
+            any::<u64>(),
+            any::<i64>(),
+            timestamp(),
+            any::<TxStatus>(),
+            description(),
+        )
+            .prop_map(
+                |(
+                    tx_id,
+                    tx_type,
+                    from_user_id,
+                    to_user_id,
+                    amount,
+                    timestamp,
+                    status,
+                    description,
+                )| {
+                    Self {
+                        tx_id,
+                        tx_type,
+                        from_user_id,
+                        to_user_id,
+                        amount,
+                        timestamp,
+                        status,
+                        description,
+                    }
+                },
+            )
+            .boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::Format;
+    use crate::tx_format::{TxReader, TxWriter};
+    use std::io::Cursor;
+
+    proptest! {
+        #[test]
+        fn test_round_trip(txs in proptest::collection::vec(any::<Transaction>(), 0..20)) {
+            for fin_format in [Format::Bin, Format::Csv, Format::Text] {
+                let mut writer = TxWriter::new(Vec::new(), fin_format).unwrap();
+                writer.write_all(&txs).unwrap();
+                let data = writer.into_inner().unwrap();
+                let mut reader = TxReader::new(Cursor::new(data), fin_format).unwrap();
+                prop_assert_eq!(&reader.read_all().unwrap(), &txs);
+            }
+        }
+    }
+}
@@ -0,0 +1,464 @@
+use super::bin_format;
+use super::csv_format;
+use super::error::ParsError;
+use super::format::Format;
+use super::options::{ReaderOptions, WriterOptions};
+use super::report::ErrorReport;
+use super::text_format;
+use super::transaction::Transaction;
+use super::tx_format::{Position, TxReader, TxWriter};
+use super::warning::Warning;
+
+use futures_util::{Sink, Stream, stream};
+use std::io::{self, Read};
+use std::pin::Pin;
+use std::task::{Context, Poll, ready};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+/// Размер порции, читаемой из асинхронного потока за один вызов
+const READ_CHUNK_LEN: usize = 8 * 1024;
+
+/// Объем сериализованных данных, при котором [Sink] передает их в поток
+/// до приема следующей транзакции
+const SINK_BUFFER_LEN: usize = 64 * 1024;
+
+/// Буфер целых записей, из которого читает синхронный парсер.
+/// Конец данных в буфере парсер воспринимает как конец потока
+#[derive(Default)]
+struct FeedReader {
+    data: Vec<u8>,
+    pos: usize,
+}
+
+impl FeedReader {
+    fn push(&mut self, bytes: &[u8]) {
+        self.data.drain(..self.pos);
+        self.pos = 0;
+        self.data.extend_from_slice(bytes);
+    }
+}
+
+impl Read for FeedReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let cnt = Read::read(&mut &self.data[self.pos..], buf)?;
+        self.pos += cnt;
+        Ok(cnt)
+    }
+}
+
+fn unsupported_format() -> ParsError {
+    ParsError::WrongFormat(
+        "Асинхронная обработка пользовательских форматов не поддерживается".to_owned(),
+    )
+}
+
+/// Асинхронный читатель транзакций над [AsyncRead] (требует feature `async`).
+/// Из потока читаются целые записи, которые затем разбираются тем же парсером,
+/// что и в [TxReader]. Пользовательские форматы и сжатие не поддерживаются
+///
+/// ```
+/// # tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(async {
+/// use fin_parser::async_io::AsyncTxReader;
+/// use fin_parser::format::Format;
+///
+/// let data = "TX_ID,TX_TYPE,FROM_USER_ID,TO_USER_ID,AMOUNT,TIMESTAMP,STATUS,DESCRIPTION\n\
+///     1,DEPOSIT,0,1,100,1633036860000,SUCCESS,\"Record number 1\"\n";
+/// let mut reader = AsyncTxReader::new(data.as_bytes(), Format::Csv).unwrap();
+/// let tx = reader.read_transaction().await.unwrap().unwrap();
+/// assert_eq!(tx.description, "Record number 1");
+/// # });
+/// ```
+pub struct AsyncTxReader<In: AsyncRead + Unpin> {
+    stream: In,
+    reader: TxReader<FeedReader>,
+    fin_format: Format,
+    delimiter: u8,
+    // Прочитанные из потока байты, еще не переданные парсеру
+    pending: Vec<u8>,
+    header_fed: bool,
+    eof: bool,
+}
+
+impl<In: AsyncRead + Unpin> AsyncTxReader<In> {
+    /// Конструктор, принимающий на вход поток и формат данных
+    pub fn new(stream: In, fin_format: Format) -> Result<Self, ParsError> {
+        Self::with_options(stream, fin_format, ReaderOptions::default())
+    }
+
+    /// Конструктор с настройками чтения
+    pub fn with_options(
+        stream: In,
+        fin_format: Format,
+        options: ReaderOptions,
+    ) -> Result<Self, ParsError> {
+        if let Format::Custom(_) = fin_format {
+            return Err(unsupported_format());
+        }
+        let delimiter = options.delimiter;
+        Ok(Self {
+            stream,
+            reader: TxReader::with_options(FeedReader::default(), fin_format, options)?,
+            fin_format,
+            delimiter,
+            pending: Vec::new(),
+            header_fed: false,
+            eof: false,
+        })
+    }
+
+    fn frame_len(&self) -> Option<usize> {
+        match self.fin_format {
+            Format::Csv => csv_format::frame_len(&self.pending, self.delimiter),
+            Format::Text => text_format::frame_len(&self.pending),
+            Format::Bin => bin_format::frame_len(&self.pending),
+            Format::Custom(_) => None,
+        }
+    }
+
+    /// Передача парсеру хотя бы одной целой записи (для csv вместе с заголовком)
+    /// либо всех оставшихся данных, если поток закончился
+    async fn fill(&mut self) -> Result<(), ParsError> {
+        loop {
+            if let Some(len) = self.frame_len() {
+                let feed = self.reader.get_mut().ok_or_else(unsupported_format)?;
+                feed.push(&self.pending[..len]);
+                self.pending.drain(..len);
+                if self.fin_format == Format::Csv && !std::mem::replace(&mut self.header_fed, true)
+                {
+                    continue;
+                }
+                return Ok(());
+            }
+            if self.eof {
+                let feed = self.reader.get_mut().ok_or_else(unsupported_format)?;
+                feed.push(&self.pending);
+                self.pending.clear();
+                return Ok(());
+            }
+            self.pending.reserve(READ_CHUNK_LEN);
+            if self.stream.read_buf(&mut self.pending).await? == 0 {
+                self.eof = true;
+            }
+        }
+    }
+
+    /// Чтение одной транзакции
+    pub async fn read_transaction(&mut self) -> Result<Option<Transaction>, ParsError> {
+        loop {
+            if self.fin_format == Format::Csv && !self.header_fed && !self.eof {
+                self.fill().await?;
+                continue;
+            }
+            match self.reader.read_transaction()? {
+                Some(tx) => return Ok(Some(tx)),
+                None if self.eof && self.pending.is_empty() => return Ok(None),
+                None => self.fill().await?,
+            }
+        }
+    }
+
+    /// Чтение всех оставшихся в потоке транзакций
+    pub async fn read_all(&mut self) -> Result<Vec<Transaction>, ParsError> {
+        let mut res = Vec::new();
+        while let Some(tx) = self.read_transaction().await? {
+            res.push(tx);
+        }
+        Ok(res)
+    }
+
+    /// Преобразование в [Stream] транзакций. После первой ошибки поток завершается,
+    /// пропуск ошибочных записей настраивается через [ReaderOptions]
+    pub fn into_stream(self) -> impl Stream<Item = Result<Transaction, ParsError>> {
+        stream::unfold(Some(self), |reader| async move {
+            let mut reader = reader?;
+            match reader.read_transaction().await {
+                Ok(Some(tx)) => Some((Ok(tx), Some(reader))),
+                Ok(None) => None,
+                Err(e) => Some((Err(e), None)),
+            }
+        })
+    }
+
+    /// Отчет об ошибках записей, см. [TxReader::error_report]
+    pub fn error_report(&self) -> &ErrorReport {
+        self.reader.error_report()
+    }
+
+    /// Извлечение накопленных предупреждений, см. [TxReader::take_warnings]
+    pub fn take_warnings(&mut self) -> Vec<Warning> {
+        self.reader.take_warnings()
+    }
+
+    /// Текущая позиция читателя, см. [TxReader::position]
+    pub fn position(&self) -> Position {
+        self.reader.position()
+    }
+}
+
+/// Асинхронный писатель транзакций над [AsyncWrite] (требует feature `async`).
+/// Записи сериализуются в память и передаются в поток одним вызовом write_all.
+/// При уничтожении данные не дописываются, поэтому запись следует завершать
+/// вызовом [AsyncTxWriter::finish]. Пользовательские форматы не поддерживаются.
+///
+/// Писатель реализует [Sink] транзакций: закрытие [Sink] завершает запись
+/// аналогично [AsyncTxWriter::finish] и закрывает поток
+pub struct AsyncTxWriter<Out: AsyncWrite + Unpin> {
+    stream: Out,
+    writer: TxWriter<Vec<u8>>,
+}
+
+impl<Out: AsyncWrite + Unpin> AsyncTxWriter<Out> {
+    /// Конструктор, принимающий на вход поток и формат данных
+    pub fn new(stream: Out, fin_format: Format) -> Result<Self, ParsError> {
+        Self::with_options(stream, fin_format, WriterOptions::default())
+    }
+
+    /// Конструктор с настройками записи
+    pub fn with_options(
+        stream: Out,
+        fin_format: Format,
+        options: WriterOptions,
+    ) -> Result<Self, ParsError> {
+        if let Format::Custom(_) = fin_format {
+            return Err(unsupported_format());
+        }
+        Ok(Self {
+            stream,
+            writer: TxWriter::with_options(Vec::new(), fin_format, options)?,
+        })
+    }
+
+    /// Передача сериализованных данных в поток
+    async fn drain(&mut self) -> Result<(), ParsError> {
+        self.writer.flush()?;
+        let buf = self.writer.get_mut().ok_or_else(unsupported_format)?;
+        if !buf.is_empty() {
+            self.stream.write_all(buf).await?;
+            buf.clear();
+        }
+        Ok(())
+    }
+
+    /// Передача сериализованных данных в поток без ожидания
+    fn poll_drain(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), ParsError>> {
+        self.writer.flush()?;
+        let Some(buf) = self.writer.get_mut() else {
+            return Poll::Ready(Err(unsupported_format()));
+        };
+        while !buf.is_empty() {
+            let cnt = ready!(Pin::new(&mut self.stream).poll_write(cx, buf))?;
+            if cnt == 0 {
+                return Poll::Ready(Err(io::Error::from(io::ErrorKind::WriteZero).into()));
+            }
+            buf.drain(..cnt);
+        }
+        Poll::Ready(Ok(()))
+    }
+
+    /// Запись одной транзакции
+    pub async fn write_transaction(&mut self, tx: &Transaction) -> Result<(), ParsError> {
+        self.writer.write_transaction(tx)?;
+        self.drain().await
+    }
+
+    /// Запись набора транзакций одним вызовом write_all
+    pub async fn write_batch(&mut self, txs: &[Transaction]) -> Result<(), ParsError> {
+        self.writer.write_batch(txs)?;
+        self.drain().await
+    }
+
+    /// Сброс буферизованных данных в поток
+    pub async fn flush(&mut self) -> Result<(), ParsError> {
+        self.drain().await?;
+        self.stream.flush().await?;
+        Ok(())
+    }
+
+    /// Завершение записи, см. [TxWriter::finish]
+    pub async fn finish(&mut self) -> Result<(), ParsError> {
+        self.writer.finish()?;
+        self.drain().await?;
+        self.stream.flush().await?;
+        Ok(())
+    }
+
+    /// Завершение записи и возврат исходного потока
+    pub async fn into_inner(mut self) -> Result<Out, ParsError> {
+        self.finish().await?;
+        Ok(self.stream)
+    }
+}
+
+impl<Out: AsyncWrite + Unpin> Sink<Transaction> for AsyncTxWriter<Out> {
+    type Error = ParsError;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), ParsError>> {
+        let this = self.get_mut();
+        let buffered = this.writer.get_mut().map_or(0, |buf| buf.len());
+        if buffered >= SINK_BUFFER_LEN {
+            this.poll_drain(cx)
+        } else {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    fn start_send(self: Pin<&mut Self>, tx: Transaction) -> Result<(), ParsError> {
+        self.get_mut().writer.write_transaction(&tx)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), ParsError>> {
+        let this = self.get_mut();
+        ready!(this.poll_drain(cx))?;
+        Pin::new(&mut this.stream)
+            .poll_flush(cx)
+            .map_err(Into::into)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), ParsError>> {
+        let this = self.get_mut();
+        // Повторное завершение ничего не дописывает, поэтому его можно вызывать при каждом опросе
+        this.writer.finish()?;
+        ready!(this.poll_drain(cx))?;
+        Pin::new(&mut this.stream)
+            .poll_shutdown(cx)
+            .map_err(Into::into)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{encode, txs};
+    use crate::transaction::{TxStatus, TxType};
+    use futures_util::{SinkExt, StreamExt};
+    use tokio::io::ReadBuf;
+
+    /// Поток, отдающий данные мелкими порциями, как сетевое соединение
+    struct ChunkedStream {
+        data: Vec<u8>,
+        pos: usize,
+        step: usize,
+    }
+
+    impl AsyncRead for ChunkedStream {
+        fn poll_read(
+            mut self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+            buf: &mut ReadBuf<'_>,
+        ) -> Poll<io::Result<()>> {
+            let end = (self.pos + self.step)
+                .min(self.data.len())
+                .min(self.pos + buf.remaining());
+            buf.put_slice(&self.data[self.pos..end]);
+            self.pos = end;
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    fn txs_for_test() -> Vec<Transaction> {
+        txs(0..5)
+            .into_iter()
+            .map(|tx| Transaction {
+                tx_type: TxType::Withdrawal,
+                from_user_id: tx.tx_id,
+                to_user_id: 0,
+                amount: 50,
+                status: TxStatus::Pending,
+                description: format!("Record {},\nsplit: \"quoted\"", tx.tx_id),
+                ..tx
+            })
+            .collect()
+    }
+
+    fn block_on<F: Future>(future: F) -> F::Output {
+        tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(future)
+    }
+
+    #[test]
+    fn test_async_roundtrip() {
+        block_on(async {
+            for fin_format in Format::ALL {
+                let mut writer = AsyncTxWriter::new(Vec::new(), fin_format).unwrap();
+                writer.write_transaction(&txs_for_test()[0]).await.unwrap();
+                writer.write_batch(&txs_for_test()[1..]).await.unwrap();
+                let data = writer.into_inner().await.unwrap();
+
+                assert_eq!(data, encode(&txs_for_test(), fin_format));
+
+                for step in [1, 3, 1000] {
+                    let stream = ChunkedStream {
+                        data: data.clone(),
+                        pos: 0,
+                        step,
+                    };
+                    let mut reader = AsyncTxReader::new(stream, fin_format).unwrap();
+                    assert_eq!(reader.read_all().await.unwrap(), txs_for_test());
+                    assert_eq!(reader.position().bytes, data.len() as u64);
+                }
+            }
+        });
+    }
+
+    #[test]
+    fn test_async_empty() {
+        block_on(async {
+            for fin_format in [Format::Text, Format::Bin] {
+                let mut reader = AsyncTxReader::new(&[][..], fin_format).unwrap();
+                assert!(reader.read_transaction().await.unwrap().is_none());
+            }
+            // Как и при синхронном чтении, csv без заголовка считается ошибочным
+            let mut reader = AsyncTxReader::new(&[][..], Format::Csv).unwrap();
+            let err = reader.read_transaction().await.unwrap_err();
+            assert!(matches!(err.inner(), ParsError::BadHeader { .. }));
+        });
+    }
+
+    #[test]
+    fn test_async_truncated() {
+        block_on(async {
+            let mut data = encode(&txs_for_test(), Format::Bin);
+            data.truncate(data.len() - 3);
+
+            let mut reader = AsyncTxReader::new(&data[..], Format::Bin).unwrap();
+            let err = reader.read_all().await.unwrap_err();
+            assert!(matches!(err.inner(), ParsError::TruncatedRecord));
+        });
+    }
+
+    #[test]
+    fn test_stream_sink() {
+        block_on(async {
+            for fin_format in Format::ALL {
+                let mut sink = AsyncTxWriter::new(Vec::new(), fin_format).unwrap();
+                let mut txs = stream::iter(txs_for_test().into_iter().map(Ok));
+                sink.send_all(&mut txs).await.unwrap();
+                sink.close().await.unwrap();
+                let data = sink.stream;
+
+                let stream = ChunkedStream {
+                    data,
+                    pos: 0,
+                    step: 7,
+                };
+                let reader = AsyncTxReader::new(stream, fin_format).unwrap();
+                let txs: Vec<Transaction> =
+                    reader.into_stream().map(Result::unwrap).collect().await;
+                assert_eq!(txs, txs_for_test());
+            }
+        });
+    }
+
+    #[test]
+    fn test_stream_error() {
+        block_on(async {
+            let data = b"TX_ID,TX_TYPE\n1,DEPOSIT\n";
+            let reader = AsyncTxReader::new(&data[..], Format::Csv).unwrap();
+            let res: Vec<_> = reader.into_stream().collect().await;
+            assert_eq!(res.len(), 1);
+            assert!(res[0].is_err());
+        });
+    }
+}
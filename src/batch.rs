@@ -0,0 +1,366 @@
+use super::analytics::Aggregate;
+use super::error::ParsError;
+use super::filter::TxFilter;
+use super::format::TransactionRead;
+use super::transaction::{Transaction, TransactionRef, TxStatus, TxType};
+use chrono::{DateTime, Utc};
+use std::collections::BTreeMap;
+use std::ops::RangeBounds;
+
+/// Количество транзакций, читаемых за один вызов при загрузке
+const LOAD_CHUNK_LEN: usize = 1024;
+
+/// Транзакции в памяти по столбцам: каждое поле хранится в отдельном векторе,
+/// описания — в общей строке. Отбор и агрегаты проходят только по нужным столбцам,
+/// что на больших наборах быстрее, чем обход транзакций целиком
+///
+/// ```
+/// use fin_parser::batch::TransactionBatch;
+/// use fin_parser::filter::TxFilter;
+/// use fin_parser::format::Format;
+/// use fin_parser::transaction::TxStatus;
+/// use fin_parser::tx_format::TxReader;
+/// use std::io::Cursor;
+///
+/// let csv = "TX_ID,TX_TYPE,FROM_USER_ID,TO_USER_ID,AMOUNT,TIMESTAMP,STATUS,DESCRIPTION\n\
+///     1,DEPOSIT,0,5,100,1633036860000,SUCCESS,\"Record number 1\"\n\
+///     2,TRANSFER,5,6,40,1633036920000,PENDING,\"Record number 2\"\n\
+///     3,WITHDRAWAL,5,0,70,1633036980000,SUCCESS,\"Record number 3\"\n";
+/// let mut reader = TxReader::new(Cursor::new(csv), Format::Csv).unwrap();
+/// let batch = TransactionBatch::from_reader(&mut reader).unwrap();
+/// assert_eq!(batch.amounts(), [100, 40, 70]);
+///
+/// let success = batch.filter(&TxFilter::status(TxStatus::Success));
+/// assert_eq!(success.tx_ids(), [1, 3]);
+/// assert_eq!(success.aggregate().sum, 170);
+/// ```
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+pub struct TransactionBatch {
+    tx_ids: Vec<u64>,
+    tx_types: Vec<TxType>,
+    from_user_ids: Vec<u64>,
+    to_user_ids: Vec<u64>,
+    amounts: Vec<i64>,
+    timestamps: Vec<i64>,
+    statuses: Vec<TxStatus>,
+    // Описания подряд и конец описания каждой транзакции
+    descriptions: String,
+    description_ends: Vec<usize>,
+}
+
+impl TransactionBatch {
+    /// Пустой набор
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Пустой набор с памятью под capacity транзакций
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            tx_ids: Vec::with_capacity(capacity),
+            tx_types: Vec::with_capacity(capacity),
+            from_user_ids: Vec::with_capacity(capacity),
+            to_user_ids: Vec::with_capacity(capacity),
+            amounts: Vec::with_capacity(capacity),
+            timestamps: Vec::with_capacity(capacity),
+            statuses: Vec::with_capacity(capacity),
+            descriptions: String::new(),
+            description_ends: Vec::with_capacity(capacity),
+        }
+    }
+
+    /// Набор из всех оставшихся транзакций читателя
+    pub fn from_reader<R: TransactionRead + ?Sized>(reader: &mut R) -> Result<Self, ParsError> {
+        let mut res = Self::new();
+        res.load(reader)?;
+        Ok(res)
+    }
+
+    /// Добавление всех оставшихся транзакций читателя. Возвращает количество добавленных
+    pub fn load<R: TransactionRead + ?Sized>(&mut self, reader: &mut R) -> Result<u64, ParsError> {
+        let mut chunk = Vec::with_capacity(LOAD_CHUNK_LEN);
+        let mut loaded = 0;
+        while reader.read_batch(&mut chunk, LOAD_CHUNK_LEN)? > 0 {
+            chunk.iter().for_each(|tx| self.push(tx));
+            loaded += chunk.len() as u64;
+            chunk.clear();
+        }
+        Ok(loaded)
+    }
+
+    /// Добавление транзакции в конец набора. Время хранится с точностью до микросекунд
+    pub fn push<'a>(&mut self, tx: impl Into<TransactionRef<'a>>) {
+        let tx = tx.into();
+        self.tx_ids.push(tx.tx_id);
+        self.tx_types.push(tx.tx_type);
+        self.from_user_ids.push(tx.from_user_id);
+        self.to_user_ids.push(tx.to_user_id);
+        self.amounts.push(tx.amount);
+        self.timestamps.push(tx.timestamp.timestamp_micros());
+        self.statuses.push(tx.status);
+        self.descriptions.push_str(tx.description);
+        self.description_ends.push(self.descriptions.len());
+    }
+
+    /// Количество транзакций
+    pub fn len(&self) -> usize {
+        self.tx_ids.len()
+    }
+
+    /// Пуст ли набор
+    pub fn is_empty(&self) -> bool {
+        self.tx_ids.is_empty()
+    }
+
+    /// Столбец TX_ID
+    pub fn tx_ids(&self) -> &[u64] {
+        &self.tx_ids
+    }
+
+    /// Столбец TX_TYPE
+    pub fn tx_types(&self) -> &[TxType] {
+        &self.tx_types
+    }
+
+    /// Столбец FROM_USER_ID
+    pub fn from_user_ids(&self) -> &[u64] {
+        &self.from_user_ids
+    }
+
+    /// Столбец TO_USER_ID
+    pub fn to_user_ids(&self) -> &[u64] {
+        &self.to_user_ids
+    }
+
+    /// Столбец AMOUNT
+    pub fn amounts(&self) -> &[i64] {
+        &self.amounts
+    }
+
+    /// Столбец TIMESTAMP в микросекундах от начала эпохи
+    pub fn timestamps(&self) -> &[i64] {
+        &self.timestamps
+    }
+
+    /// Столбец STATUS
+    pub fn statuses(&self) -> &[TxStatus] {
+        &self.statuses
+    }
+
+    /// Описание транзакции с номером idx
+    pub fn description(&self, idx: usize) -> Option<&str> {
+        let end = *self.description_ends.get(idx)?;
+        let start = idx
+            .checked_sub(1)
+            .map_or(0, |prev| self.description_ends[prev]);
+        Some(&self.descriptions[start..end])
+    }
+
+    /// Транзакция с номером idx
+    pub fn get(&self, idx: usize) -> Option<TransactionRef<'_>> {
+        let description = self.description(idx)?;
+        Some(TransactionRef {
+            tx_id: self.tx_ids[idx],
+            tx_type: self.tx_types[idx],
+            from_user_id: self.from_user_ids[idx],
+            to_user_id: self.to_user_ids[idx],
+            amount: self.amounts[idx],
+            timestamp: DateTime::from_timestamp_micros(self.timestamps[idx])
+                .expect("Время получено из DateTime"),
+            status: self.statuses[idx],
+            description,
+        })
+    }
+
+    /// Транзакции набора по порядку
+    pub fn iter(&self) -> impl Iterator<Item = TransactionRef<'_>> {
+        (0..self.len()).filter_map(|idx| self.get(idx))
+    }
+
+    /// Транзакции набора, владеющие описаниями
+    pub fn to_transactions(&self) -> Vec<Transaction> {
+        self.iter().map(Transaction::from).collect()
+    }
+
+    /// Маска транзакций, удовлетворяющих условию. Условия на поля проверяются
+    /// по столбцам, связки объединяют маски
+    pub fn mask(&self, filter: &TxFilter) -> Vec<bool> {
+        match filter {
+            TxFilter::Any => vec![true; self.len()],
+            TxFilter::Status(status) => self.statuses.iter().map(|val| val == status).collect(),
+            TxFilter::Type(tx_type) => self.tx_types.iter().map(|val| val == tx_type).collect(),
+            TxFilter::Amount(start, end) => {
+                let range = (*start, *end);
+                self.amounts.iter().map(|val| range.contains(val)).collect()
+            }
+            TxFilter::Time(start, end) => {
+                // Сравнение в наносекундах: границы могут быть точнее столбца
+                let nanos = |time: &DateTime<Utc>| {
+                    time.timestamp() as i128 * 1_000_000_000 + time.timestamp_subsec_nanos() as i128
+                };
+                let range = (start.as_ref().map(nanos), end.as_ref().map(nanos));
+                self.timestamps
+                    .iter()
+                    .map(|val| range.contains(&(*val as i128 * 1000)))
+                    .collect()
+            }
+            TxFilter::FromUser(ids) => self
+                .from_user_ids
+                .iter()
+                .map(|id| ids.contains(id))
+                .collect(),
+            TxFilter::ToUser(ids) => self.to_user_ids.iter().map(|id| ids.contains(id)).collect(),
+            TxFilter::Description(regex) => (0..self.len())
+                .map(|idx| regex.is_match(self.description(idx).unwrap_or_default()))
+                .collect(),
+            TxFilter::And(filters) => self.combine(filters, true, |acc, val| acc && val),
+            TxFilter::Or(filters) => self.combine(filters, false, |acc, val| acc || val),
+            TxFilter::Not(filter) => self.mask(filter).into_iter().map(|val| !val).collect(),
+        }
+    }
+
+    fn combine(&self, filters: &[TxFilter], init: bool, op: fn(bool, bool) -> bool) -> Vec<bool> {
+        let mut res = vec![init; self.len()];
+        for filter in filters {
+            for (acc, val) in res.iter_mut().zip(self.mask(filter)) {
+                *acc = op(*acc, val);
+            }
+        }
+        res
+    }
+
+    /// Новый набор из транзакций, отмеченных в маске
+    pub fn select(&self, mask: &[bool]) -> Self {
+        let mut res = Self {
+            tx_ids: pick(&self.tx_ids, mask),
+            tx_types: pick(&self.tx_types, mask),
+            from_user_ids: pick(&self.from_user_ids, mask),
+            to_user_ids: pick(&self.to_user_ids, mask),
+            amounts: pick(&self.amounts, mask),
+            timestamps: pick(&self.timestamps, mask),
+            statuses: pick(&self.statuses, mask),
+            descriptions: String::new(),
+            description_ends: Vec::new(),
+        };
+        for idx in (0..self.len()).filter(|idx| mask.get(*idx) == Some(&true)) {
+            res.descriptions
+                .push_str(self.description(idx).unwrap_or_default());
+            res.description_ends.push(res.descriptions.len());
+        }
+        res
+    }
+
+    /// Новый набор из транзакций, удовлетворяющих условию
+    pub fn filter(&self, filter: &TxFilter) -> Self {
+        self.select(&self.mask(filter))
+    }
+
+    /// Количество и суммы всех транзакций набора
+    pub fn aggregate(&self) -> Aggregate {
+        let mut res = Aggregate::default();
+        if let (Some(min), Some(max)) = (self.amounts.iter().min(), self.amounts.iter().max()) {
+            res.count = self.len() as u64;
+            res.sum = self.amounts.iter().map(|val| *val as i128).sum();
+            res.min = Some(*min);
+            res.max = Some(*max);
+        }
+        res
+    }
+
+    /// Агрегаты сумм по статусам
+    pub fn aggregate_by_status(&self) -> BTreeMap<TxStatus, Aggregate> {
+        group(&self.statuses, &self.amounts)
+    }
+
+    /// Агрегаты сумм по типам транзакций
+    pub fn aggregate_by_type(&self) -> BTreeMap<TxType, Aggregate> {
+        group(&self.tx_types, &self.amounts)
+    }
+}
+
+impl<'a> Extend<TransactionRef<'a>> for TransactionBatch {
+    fn extend<I: IntoIterator<Item = TransactionRef<'a>>>(&mut self, iter: I) {
+        iter.into_iter().for_each(|tx| self.push(tx));
+    }
+}
+
+impl<'a> FromIterator<TransactionRef<'a>> for TransactionBatch {
+    fn from_iter<I: IntoIterator<Item = TransactionRef<'a>>>(iter: I) -> Self {
+        let mut res = Self::new();
+        res.extend(iter);
+        res
+    }
+}
+
+/// Значения столбца, отмеченные в маске
+fn pick<T: Copy>(column: &[T], mask: &[bool]) -> Vec<T> {
+    column
+        .iter()
+        .zip(mask)
+        .filter_map(|(val, keep)| keep.then_some(*val))
+        .collect()
+}
+
+/// Агрегаты сумм по значениям ключевого столбца
+fn group<K: Ord + Copy>(keys: &[K], amounts: &[i64]) -> BTreeMap<K, Aggregate> {
+    let mut res: BTreeMap<K, Aggregate> = BTreeMap::new();
+    for (key, amount) in keys.iter().zip(amounts) {
+        res.entry(*key).or_default().add(*amount);
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generate::{GenerateOptions, Generator};
+
+    #[test]
+    fn test_batch_columns() {
+        let txs: Vec<_> = Generator::new(GenerateOptions::new().with_seed(4), 200)
+            .unwrap()
+            .collect();
+        let batch: TransactionBatch = txs.iter().map(TransactionRef::from).collect();
+        assert_eq!(batch.len(), 200);
+        assert_eq!(batch.to_transactions(), txs);
+        assert_eq!(batch.get(200), None);
+
+        let aggregate = batch.aggregate();
+        assert_eq!(aggregate.count, 200);
+        assert_eq!(
+            aggregate.sum,
+            txs.iter().map(|tx| tx.amount as i128).sum::<i128>()
+        );
+        assert_eq!(TransactionBatch::new().aggregate(), Aggregate::default());
+
+        let by_status = batch.aggregate_by_status();
+        assert_eq!(by_status.values().map(|val| val.count).sum::<u64>(), 200);
+    }
+
+    #[test]
+    fn test_batch_filter() {
+        let txs: Vec<_> = Generator::new(GenerateOptions::new().with_seed(6), 300)
+            .unwrap()
+            .collect();
+        let batch: TransactionBatch = txs.iter().map(TransactionRef::from).collect();
+        let middle = txs[150].timestamp;
+        let filters = [
+            "status = PENDING AND amount > 1000",
+            "tx_type = DEPOSIT OR NOT status = SUCCESS",
+            r#"description ~ "5$" OR user_id = 3"#,
+            "",
+        ]
+        .map(|expr| expr.parse::<TxFilter>().unwrap())
+        .into_iter()
+        .chain([TxFilter::time(middle..), TxFilter::time(..=middle)]);
+        for filter in filters {
+            let expected: Vec<_> = txs
+                .iter()
+                .filter(|tx| filter.matches_tx(tx))
+                .cloned()
+                .collect();
+            let selected = batch.filter(&filter);
+            assert_eq!(selected.to_transactions(), expected, "{filter:?}");
+        }
+    }
+}
@@ -1,79 +1,334 @@
-use clap::Parser;
+use chrono::TimeDelta;
+use clap::{Parser, ValueEnum};
+use fin_parser::config::{Config, Section};
+use fin_parser::error::ParsError;
+use fin_parser::filter::{FilteredReader, TxFilter};
+use fin_parser::format::Format;
+use fin_parser::reconcile::{
+    CompareOptions, Field, Mismatch, ReconcileReport, diff_fields_with, reconcile_with,
+};
 use fin_parser::tx_format::TxReader;
 use std::fs::File;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
 
 #[derive(Parser)]
 #[command(name = "YpbComparer")]
 #[command(version = "1.0")]
 #[command(about = "Утилита для сравнения файлов транзакций")]
+#[command(after_help = "Код завершения: 0 для идентичных файлов, 1 при расхождениях, 2 при ошибке")]
 struct Args {
-    /// Путь первого файла
+    /// Путь первого файла, `-` для чтения из stdin
     #[arg(long, value_name = "FILE")]
     lhs_file: String,
 
-    /// Формат первого
+    /// Формат первого файла. Если не задан, определяется по расширению,
+    /// из настроек или по содержимому
     #[arg(long, value_name = "bin | csv | text")]
-    lhs_format: String,
+    lhs_format: Option<Format>,
 
-    /// Путь второго файла
+    /// Путь второго файла, `-` для чтения из stdin
     #[arg(long, value_name = "FILE")]
     rhs_file: String,
 
-    /// Формат второго файла
+    /// Формат второго файла. Если не задан, определяется по расширению,
+    /// из настроек или по содержимому
     #[arg(long, value_name = "bin | csv | text")]
-    rhs_format: String,
+    rhs_format: Option<Format>,
+
+    /// Условие отбора сравниваемых транзакций, например `status = PENDING AND amount > 1000`
+    #[arg(long = "where", value_name = "EXPR")]
+    filter: Option<TxFilter>,
+
+    /// Ключ сопоставления записей: position (по порядку записей) или tx_id
+    /// (независимо от порядка, записи первого файла загружаются в память)
+    #[arg(long, value_enum, default_value_t = Key::Position)]
+    key: Key,
+
+    /// То же, что --key tx_id
+    #[arg(long, conflicts_with = "key")]
+    by_id: bool,
+
+    /// Файл для отчета сверки. При сравнении по позиции записи с разными tx_id
+    /// на одной позиции и лишние записи одного из файлов попадают в отчет
+    /// как отсутствующие в другом файле
+    #[arg(long, value_name = "FILE")]
+    report: Option<String>,
+
+    /// Формат отчета сверки. Если не задан, определяется по расширению файла
+    /// отчета (`.json`, `.jsonl`), по умолчанию csv
+    #[arg(long, value_enum, requires = "report")]
+    report_format: Option<ReportFormat>,
+
+    /// Поля, которые не сравниваются, через запятую, например `description,timestamp`
+    #[arg(long, value_name = "FIELDS", value_delimiter = ',')]
+    ignore_fields: Vec<Field>,
+
+    /// Допустимое отклонение времени: число с единицей ms, s, m или h, например `1s`
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+    timestamp_tolerance: Option<TimeDelta>,
+
+    /// Допустимое отклонение суммы
+    #[arg(long, value_name = "AMOUNT")]
+    amount_tolerance: Option<u64>,
+
+    /// Максимальное количество выводимых различающихся транзакций.
+    /// Общее количество различий выводится в любом случае
+    #[arg(long, value_name = "N")]
+    max_diffs: Option<usize>,
+
+    /// Файл настроек. Если не задан, используется fin-parser.toml из текущего каталога
+    #[arg(long, value_name = "FILE")]
+    config: Option<PathBuf>,
+}
+
+/// Ключ сопоставления записей двух файлов
+#[derive(Clone, Copy, Eq, PartialEq, ValueEnum)]
+enum Key {
+    /// Запись сравнивается с записью на той же позиции
+    Position,
+    /// Записи сопоставляются по tx_id
+    #[value(name = "tx_id")]
+    TxId,
 }
 
-fn main() {
+/// Формат файла отчета сверки
+#[derive(Clone, Copy, ValueEnum)]
+enum ReportFormat {
+    /// csv со столбцами `TX_ID,RESULT,FIELD,LEFT,RIGHT`
+    Csv,
+    /// json
+    Json,
+    /// json lines с исправлениями транзакций первого файла по второму, по строке на транзакцию
+    TxDiffs,
+}
+
+impl ReportFormat {
+    fn from_path(path: &str) -> Option<Self> {
+        match Path::new(path).extension()?.to_str()? {
+            "csv" => Some(Self::Csv),
+            "json" => Some(Self::Json),
+            "jsonl" => Some(Self::TxDiffs),
+            _ => None,
+        }
+    }
+}
+
+/// Код завершения при расхождениях в файлах
+const DIFF_EXIT_CODE: u8 = 1;
+/// Код завершения при ошибке
+const ERROR_EXIT_CODE: u8 = 2;
+
+fn main() -> ExitCode {
     let args = Args::parse();
-    let lhs_file = match File::open(args.lhs_file) {
+    let by_id = args.by_id || args.key == Key::TxId;
+    if args.lhs_file == "-" && args.rhs_file == "-" {
+        eprintln!("Из stdin можно читать только один из файлов");
+        return ExitCode::from(ERROR_EXIT_CODE);
+    }
+    let config = match Config::load(args.config.as_deref()) {
         Ok(val) => val,
         Err(e) => {
-            eprintln!("Невозможно открыть файл: {e}");
-            return;
+            eprintln!("Невозможно прочитать настройки: {e}");
+            return ExitCode::from(ERROR_EXIT_CODE);
         }
     };
-
-    let mut lhs_reader = match TxReader::new(lhs_file, &args.lhs_format) {
-        Ok(val) => val,
+    let filter = args.filter.unwrap_or_default();
+    let mut lhs_reader = match open_reader(&args.lhs_file, args.lhs_format, &config.input) {
+        Ok(val) => FilteredReader::new(val, filter.clone()),
         Err(e) => {
             eprintln!("Невозможно создать парсер: {e}");
-            return;
+            return ExitCode::from(ERROR_EXIT_CODE);
         }
     };
 
-    let rhs_file = match File::open(args.rhs_file) {
-        Ok(val) => val,
+    let mut rhs_reader = match open_reader(&args.rhs_file, args.rhs_format, &config.input) {
+        Ok(val) => FilteredReader::new(val, filter),
         Err(e) => {
-            eprintln!("Невозможно открыть файл: {e}");
-            return;
+            eprintln!("Невозможно создать парсер: {e}");
+            return ExitCode::from(ERROR_EXIT_CODE);
         }
     };
 
-    let mut rhs_reader = match TxReader::new(rhs_file, &args.rhs_format) {
-        Ok(val) => val,
-        Err(e) => {
-            eprintln!("Невозможно создать парсер: {e}");
-            return;
+    let mut options = CompareOptions::new()
+        .with_timestamp_tolerance(args.timestamp_tolerance.unwrap_or_default())
+        .with_amount_tolerance(args.amount_tolerance.unwrap_or_default());
+    for field in &args.ignore_fields {
+        options = options.ignore(*field);
+    }
+
+    if by_id {
+        let report = match reconcile_with(&mut lhs_reader, &mut rhs_reader, &options) {
+            Ok(val) => val,
+            Err(e) => {
+                eprintln!("Ошибка сверки: {e}");
+                return ExitCode::from(ERROR_EXIT_CODE);
+            }
+        };
+        print!(
+            "{}",
+            report.display_limited(args.max_diffs.unwrap_or(usize::MAX))
+        );
+        if let Err(e) = write_report(
+            args.report.as_deref(),
+            args.report_format,
+            &config.output,
+            &report,
+        ) {
+            eprintln!("Невозможно записать отчет: {e}");
+            return ExitCode::from(ERROR_EXIT_CODE);
         }
-    };
+        if !report.is_clean() {
+            return ExitCode::from(DIFF_EXIT_CODE);
+        }
+        println!("Записи идентичны");
+        return ExitCode::SUCCESS;
+    }
 
+    // Сравнение по позиции: слева ожидаемые значения, справа фактические
+    let max_diffs = args.max_diffs.unwrap_or(usize::MAX);
+    let mut report = ReconcileReport::default();
+    let mut diffs = 0;
+    let mut record = 0;
+    let mut same_size = true;
     loop {
-        let lhs_fin_data = lhs_reader.read_transaction().expect("Ошибка чтения данных");
-        let rhs_fin_data = rhs_reader.read_transaction().expect("Ошибка чтения данных");
-        if lhs_fin_data.is_none() && rhs_fin_data.is_none() {
-            break;
-        }
+        let res = lhs_reader
+            .read_transaction()
+            .and_then(|lhs| Ok((lhs, rhs_reader.read_transaction()?)));
+        let (lhs, rhs) = match res {
+            Ok(val) => val,
+            Err(e) => {
+                eprintln!("Ошибка чтения данных: {e}");
+                return ExitCode::from(ERROR_EXIT_CODE);
+            }
+        };
+        let (lhs, rhs) = match (lhs, rhs) {
+            (Some(lhs), Some(rhs)) => (lhs, rhs),
+            (None, None) => break,
+            // Лишние записи дочитываются только для отчета
+            (lhs, rhs) => {
+                same_size = false;
+                report.missing_right.extend(lhs.map(|tx| tx.tx_id));
+                report.missing_left.extend(rhs.map(|tx| tx.tx_id));
+                continue;
+            }
+        };
 
-        if let Some((lhs, rhs)) = lhs_fin_data.zip(rhs_fin_data) {
-            if lhs != rhs {
-                println!("Записи содержат разные транзакции");
+        let fields = diff_fields_with(&lhs, &rhs, &options);
+        if lhs.tx_id != rhs.tx_id || !fields.is_empty() {
+            diffs += 1;
+            if diffs <= max_diffs {
+                if lhs.tx_id != rhs.tx_id {
+                    println!("  Запись {record}: tx_id {} != {}", lhs.tx_id, rhs.tx_id);
+                }
+                for diff in &fields {
+                    println!(
+                        "  Запись {record}, tx_id {}: {} {} != {}",
+                        lhs.tx_id,
+                        diff.field.name(),
+                        diff.left,
+                        diff.right
+                    );
+                }
             }
+        }
+        if lhs.tx_id != rhs.tx_id {
+            report.missing_right.push(lhs.tx_id);
+            report.missing_left.push(rhs.tx_id);
+        } else if fields.is_empty() {
+            report.matched += 1;
         } else {
-            println!("Записи разного размера");
-            return;
+            report.mismatched.push(Mismatch {
+                tx_id: lhs.tx_id,
+                fields,
+            });
         }
+        record += 1;
+    }
+    report.mismatched.sort_by_key(|val| val.tx_id);
+    report.missing_left.sort_unstable();
+    report.missing_right.sort_unstable();
+    if let Err(e) = write_report(
+        args.report.as_deref(),
+        args.report_format,
+        &config.output,
+        &report,
+    ) {
+        eprintln!("Невозможно записать отчет: {e}");
+        return ExitCode::from(ERROR_EXIT_CODE);
     }
 
-    println!("Записи идентичны");
+    if diffs > max_diffs {
+        println!("Различающихся записей: {diffs}, показано {max_diffs}");
+    } else if diffs > 0 {
+        println!("Различающихся записей: {diffs}");
+    }
+    if !same_size {
+        println!("Записи разного размера");
+    } else if diffs == 0 {
+        println!("Записи идентичны");
+        return ExitCode::SUCCESS;
+    }
+    ExitCode::from(DIFF_EXIT_CODE)
+}
+
+/// Запись отчета сверки в файл --report, если он задан
+fn write_report(
+    path: Option<&str>,
+    report_format: Option<ReportFormat>,
+    output: &Section,
+    report: &ReconcileReport,
+) -> Result<(), ParsError> {
+    let Some(path) = path else {
+        return Ok(());
+    };
+    let report_format = report_format.or_else(|| ReportFormat::from_path(path));
+    let mut file = File::create(output.resolve(Path::new(path)))?;
+    match report_format.unwrap_or(ReportFormat::Csv) {
+        ReportFormat::Csv => report.write_csv(&mut file),
+        ReportFormat::Json => report.write_json(&mut file),
+        ReportFormat::TxDiffs => report.write_tx_diffs(&mut file),
+    }
+}
+
+/// Разбор длительности вида `500ms`, `1s`, `2m`, `1h`
+fn parse_duration(value: &str) -> Result<TimeDelta, String> {
+    let split = value
+        .find(|ch: char| !ch.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (num, unit) = value.split_at(split);
+    let num: i64 = num
+        .parse()
+        .map_err(|_| format!("Некорректная длительность: {value}"))?;
+    let delta = match unit {
+        "ms" => TimeDelta::try_milliseconds(num),
+        "s" => TimeDelta::try_seconds(num),
+        "m" => TimeDelta::try_minutes(num),
+        "h" => TimeDelta::try_hours(num),
+        _ => {
+            return Err(format!(
+                "Некорректная единица длительности в {value}, ожидается ms, s, m или h"
+            ));
+        }
+    };
+    delta.ok_or_else(|| format!("Некорректная длительность: {value}"))
+}
+
+/// Читатель файла или stdin для пути `-`. Незаданный формат определяется
+/// по расширению, из настроек или по содержимому
+fn open_reader(
+    path: &str,
+    fin_format: Option<Format>,
+    config: &Section,
+) -> Result<TxReader<Box<dyn Read + Send>>, ParsError> {
+    let mut builder = config.reader_builder_for(Path::new(path));
+    if let Some(fin_format) = fin_format {
+        builder = builder.format(fin_format);
+    }
+    match path {
+        "-" => builder.build(io::stdin()),
+        _ if Format::from_path(Path::new(path)).is_ok() => builder.open(path),
+        _ => builder.build(File::open(path)?),
+    }
 }
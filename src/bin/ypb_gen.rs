@@ -0,0 +1,169 @@
+use chrono::{DateTime, NaiveDate, Utc};
+use clap::Parser;
+use fin_parser::config::Config;
+use fin_parser::converter::convert;
+use fin_parser::format::Format;
+use fin_parser::generate::{
+    AmountDistribution, DEFAULT_TEMPLATE, GenerateOptions, Generator, UserDistribution, Weights,
+};
+use fin_parser::transaction::{TxStatus, TxType};
+use std::io;
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+#[derive(Parser)]
+#[command(name = "YpbGenerator")]
+#[command(version = "1.0")]
+#[command(about = "Утилита для генерации синтетических транзакций")]
+struct Args {
+    /// Количество транзакций
+    #[arg(short = 'n', long, value_name = "N")]
+    count: u64,
+
+    /// Начальное значение генератора случайных чисел. Одинаковые параметры
+    /// дают одинаковый результат
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+
+    /// Путь к выходному файлу. Если не задан, данные выводятся в stdout
+    #[arg(long, value_name = "FILE")]
+    output_file: Option<PathBuf>,
+
+    /// Формат выходных данных. Если не задан, определяется по расширению выходного файла
+    #[arg(long, value_name = "bin | csv | text")]
+    output_format: Option<Format>,
+
+    /// Идентификатор первой транзакции
+    #[arg(long, value_name = "ID", default_value_t = 1)]
+    start_id: u64,
+
+    /// Веса типов, например `DEPOSIT=1,TRANSFER=3,WITHDRAWAL=1`
+    #[arg(long, value_name = "TYPE=WEIGHT,...")]
+    types: Option<Weights<TxType>>,
+
+    /// Веса статусов, например `SUCCESS=8,FAILURE=1,PENDING=1`
+    #[arg(long, value_name = "STATUS=WEIGHT,...")]
+    statuses: Option<Weights<TxStatus>>,
+
+    /// Минимальная сумма (включительно)
+    #[arg(
+        long,
+        value_name = "AMOUNT",
+        default_value_t = 1,
+        allow_negative_numbers = true
+    )]
+    min_amount: i64,
+
+    /// Максимальная сумма (включительно)
+    #[arg(
+        long,
+        value_name = "AMOUNT",
+        default_value_t = 10000,
+        allow_negative_numbers = true
+    )]
+    max_amount: i64,
+
+    /// Распределение сумм: uniform (равномерное) или log-uniform (равномерное по порядку)
+    #[arg(long, value_name = "uniform | log-uniform", default_value = "uniform")]
+    distribution: AmountDistribution,
+
+    /// Начало интервала времени (включительно): дата `2021-10-01` или время в формате RFC 3339
+    #[arg(long, value_name = "TIME", value_parser = parse_time, default_value = "2021-01-01")]
+    from_date: DateTime<Utc>,
+
+    /// Конец интервала времени (включительно), формат как у --from-date
+    #[arg(long, value_name = "TIME", value_parser = parse_time, default_value = "2022-01-01")]
+    to_date: DateTime<Utc>,
+
+    /// Транзакции упорядочены по времени
+    #[arg(long)]
+    sorted: bool,
+
+    /// Количество пользователей
+    #[arg(long, value_name = "N", default_value_t = 1000)]
+    users: u64,
+
+    /// Распределение активности пользователей: uniform (равномерное) или
+    /// skewed:USERS/SHARE — SHARE процентов участий у USERS процентов пользователей
+    #[arg(
+        long,
+        value_name = "uniform | skewed:USERS/SHARE",
+        default_value = "uniform"
+    )]
+    user_distribution: UserDistribution,
+
+    /// Шаблон описания с подстановками `{tx_id}`, `{type}`, `{status}`, `{from}`, `{to}`,
+    /// `{amount}`. Флаг можно повторить, шаблон выбирается случайно
+    #[arg(long, value_name = "TEMPLATE", default_value = DEFAULT_TEMPLATE)]
+    description: Vec<String>,
+
+    /// Файл настроек. Если не задан, используется fin-parser.toml из текущего каталога
+    #[arg(long, value_name = "FILE")]
+    config: Option<PathBuf>,
+}
+
+fn main() -> ExitCode {
+    let args = Args::parse();
+    let config = match Config::load(args.config.as_deref()) {
+        Ok(val) => val,
+        Err(e) => {
+            eprintln!("Невозможно прочитать настройки: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let mut options = GenerateOptions::new()
+        .with_seed(args.seed)
+        .with_start_id(args.start_id)
+        .with_amounts(args.min_amount, args.max_amount, args.distribution)
+        .with_time_range(args.from_date, args.to_date)
+        .with_sorted(args.sorted)
+        .with_users(args.users)
+        .with_user_distribution(args.user_distribution)
+        .with_templates(args.description);
+    if let Some(types) = args.types {
+        options = options.with_types(types);
+    }
+    if let Some(statuses) = args.statuses {
+        options = options.with_statuses(statuses);
+    }
+    let mut generator = match Generator::new(options, args.count) {
+        Ok(val) => val,
+        Err(e) => {
+            eprintln!("Некорректные параметры генерации: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let output_file = args.output_file.map(|path| config.output.resolve(&path));
+    let mut builder = config.output.writer_builder_for(output_file.as_deref());
+    if let Some(fin_format) = args.output_format {
+        builder = builder.format(fin_format);
+    }
+    let writer = match &output_file {
+        Some(path) => builder.create(path),
+        None => builder.build(io::stdout()),
+    };
+    let mut writer = match writer {
+        Ok(val) => val,
+        Err(e) => {
+            eprintln!("Невозможно создать писатель: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let res = convert(&mut generator, &mut writer).and_then(|_| writer.finish());
+    if let Err(e) = res {
+        eprintln!("Ошибка записи данных: {e}");
+        return ExitCode::FAILURE;
+    }
+    ExitCode::SUCCESS
+}
+
+/// Разбор времени: дата (полночь UTC) или время в формате RFC 3339
+fn parse_time(value: &str) -> Result<DateTime<Utc>, String> {
+    if let Ok(date) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        return Ok(date.and_time(Default::default()).and_utc());
+    }
+    DateTime::parse_from_rfc3339(value)
+        .map(|time| time.with_timezone(&Utc))
+        .map_err(|_| format!("Некорректное время: {value}"))
+}
@@ -0,0 +1,58 @@
+use clap::Parser;
+use fin_parser::config::Config;
+use fin_parser::grpc::FileService;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::process::ExitCode;
+use tonic::transport::Server;
+
+#[derive(Parser)]
+#[command(name = "YpbGrpcServer")]
+#[command(version = "1.0")]
+#[command(about = "gRPC-сервер для чтения и записи файлов транзакций из каталога")]
+#[command(after_help = "Описание сервиса: proto/fin_parser.proto (TransactionService)")]
+struct Args {
+    /// Каталог с файлами транзакций. Вложенные каталоги не обслуживаются
+    #[arg(value_name = "DIR")]
+    dir: PathBuf,
+
+    /// Адрес и порт сервера
+    #[arg(long, value_name = "ADDR", default_value = "127.0.0.1:50051")]
+    listen: SocketAddr,
+
+    /// Файл настроек. Если не задан, используется fin-parser.toml из текущего каталога
+    #[arg(long, value_name = "FILE")]
+    config: Option<PathBuf>,
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let args = Args::parse();
+    let config = match Config::load(args.config.as_deref()) {
+        Ok(val) => val,
+        Err(e) => {
+            eprintln!("Невозможно прочитать настройки: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let dir = config.input.resolve(&args.dir);
+    if !dir.is_dir() {
+        eprintln!("Каталог {} не найден", dir.display());
+        return ExitCode::FAILURE;
+    }
+    eprintln!(
+        "Каталог {} доступен по адресу {}",
+        dir.display(),
+        args.listen
+    );
+    let service = FileService::new(dir).with_config(&config);
+    let res = Server::builder()
+        .add_service(service.into_server())
+        .serve(args.listen)
+        .await;
+    if let Err(e) = res {
+        eprintln!("Ошибка сервера: {e}");
+        return ExitCode::FAILURE;
+    }
+    ExitCode::SUCCESS
+}
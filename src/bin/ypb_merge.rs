@@ -0,0 +1,142 @@
+use clap::Parser;
+use fin_parser::config::{Config, Section};
+use fin_parser::converter::convert;
+use fin_parser::dedup::{DedupReader, Keep};
+use fin_parser::error::ParsError;
+use fin_parser::format::{Format, TransactionRead};
+use fin_parser::merge::MergeSorted;
+use fin_parser::sort::{Order, SortKey};
+use fin_parser::transaction::Transaction;
+use fin_parser::tx_format::TxReader;
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+#[derive(Parser)]
+#[command(name = "YpbMerger")]
+#[command(version = "1.0")]
+#[command(about = "Утилита для объединения файлов транзакций")]
+struct Args {
+    /// Входные файлы, `-` для чтения из stdin. Формат и сжатие каждого файла определяются
+    /// по расширению, а для stdin и файлов без известного расширения из настроек
+    /// или по содержимому
+    #[arg(value_name = "FILE", required = true)]
+    input_files: Vec<String>,
+
+    /// Путь к выходному файлу. Если не задан, данные выводятся в stdout
+    #[arg(long, value_name = "FILE")]
+    output_file: Option<PathBuf>,
+
+    /// Формат выходных данных. Если не задан, определяется по расширению выходного файла
+    #[arg(long, value_name = "bin | csv | text")]
+    output_format: Option<Format>,
+
+    /// Поле, по которому отсортирован каждый входной файл. Файлы сливаются с сохранением
+    /// порядка по возрастанию поля. Если не задано, файлы записываются друг за другом
+    #[arg(long, value_name = "timestamp | tx_id | amount")]
+    sorted_by: Option<SortKey>,
+
+    /// Отбрасывание транзакций с уже встреченным tx_id. Идентификаторы хранятся в памяти
+    #[arg(long)]
+    dedup: bool,
+
+    /// Файл настроек. Если не задан, используется fin-parser.toml из текущего каталога
+    #[arg(long, value_name = "FILE")]
+    config: Option<PathBuf>,
+}
+
+fn main() -> ExitCode {
+    let args = Args::parse();
+    let config = match Config::load(args.config.as_deref()) {
+        Ok(val) => val,
+        Err(e) => {
+            eprintln!("Невозможно прочитать настройки: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    if args.input_files.iter().filter(|path| *path == "-").count() > 1 {
+        eprintln!("Из stdin можно читать только один из файлов");
+        return ExitCode::FAILURE;
+    }
+    let mut readers = Vec::new();
+    for path in &args.input_files {
+        match open_reader(path, &config.input) {
+            Ok(val) => readers.push(val),
+            Err(e) => {
+                eprintln!("Невозможно открыть файл {path}: {e}");
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+    let source: Box<dyn TransactionRead> = match args.sorted_by {
+        Some(key) => match MergeSorted::new(readers, key, Order::Ascending) {
+            Ok(val) => Box::new(val),
+            Err(e) => {
+                eprintln!("Ошибка чтения данных: {e}");
+                return ExitCode::FAILURE;
+            }
+        },
+        None => Box::new(Concat(readers.into())),
+    };
+
+    let output_file = args.output_file.map(|path| config.output.resolve(&path));
+    let mut builder = config.output.writer_builder_for(output_file.as_deref());
+    if let Some(fin_format) = args.output_format {
+        builder = builder.format(fin_format);
+    }
+    let writer = match &output_file {
+        Some(path) => builder.create(path),
+        None => builder.build(io::stdout()),
+    };
+    let mut writer = match writer {
+        Ok(val) => val,
+        Err(e) => {
+            eprintln!("Невозможно создать писатель: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let res = if args.dedup {
+        let mut reader = DedupReader::new(source, Keep::First);
+        let res = convert(&mut reader, &mut writer);
+        if !reader.duplicates().is_empty() {
+            eprintln!("Отброшено повторов: {}", reader.duplicates().len());
+        }
+        res
+    } else {
+        let mut reader = source;
+        convert(&mut reader, &mut writer)
+    };
+    if let Err(e) = res.and_then(|_| writer.finish()) {
+        eprintln!("Ошибка объединения: {e}");
+        return ExitCode::FAILURE;
+    }
+    ExitCode::SUCCESS
+}
+
+/// Открытие файла или stdin для пути `-`
+fn open_reader(path: &str, config: &Section) -> Result<TxReader<Box<dyn Read + Send>>, ParsError> {
+    let builder = config.reader_builder_for(Path::new(path));
+    match path {
+        "-" => builder.build(io::stdin()),
+        _ if Format::from_path(Path::new(path)).is_ok() => builder.open(path),
+        _ => builder.build(File::open(path)?),
+    }
+}
+
+/// Последовательное чтение потоков друг за другом
+struct Concat(VecDeque<TxReader<Box<dyn Read + Send>>>);
+
+impl TransactionRead for Concat {
+    fn read_transaction(&mut self) -> Result<Option<Transaction>, ParsError> {
+        while let Some(reader) = self.0.front_mut() {
+            if let Some(tx) = reader.read_transaction()? {
+                return Ok(Some(tx));
+            }
+            self.0.pop_front();
+        }
+        Ok(None)
+    }
+}
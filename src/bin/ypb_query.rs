@@ -0,0 +1,209 @@
+use clap::Parser;
+use fin_parser::config::{Config, Section};
+use fin_parser::error::ParsError;
+use fin_parser::filter::{FilteredReader, TxFilter};
+use fin_parser::format::{Format, TransactionRead};
+use fin_parser::index::OffsetIndex;
+use fin_parser::projection::Projection;
+use fin_parser::transaction::{Transaction, TransactionRef};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+#[derive(Parser)]
+#[command(name = "YpbQuery")]
+#[command(version = "1.0")]
+#[command(about = "Утилита для выборки транзакций из файла")]
+struct Args {
+    /// Путь файла, `-` для чтения из stdin
+    #[arg(value_name = "FILE")]
+    input_file: String,
+
+    /// Формат входного файла. Если не задан, определяется по расширению или содержимому
+    #[arg(long, value_name = "bin | csv | text")]
+    input_format: Option<Format>,
+
+    /// Условие отбора транзакций, например `status = PENDING AND amount > 1000`
+    #[arg(long = "where", value_name = "EXPR")]
+    filter: Option<TxFilter>,
+
+    /// Выводимые поля через запятую, например `tx_id,amount,status`. Поддерживаются
+    /// форматы csv (по умолчанию для stdout) и text, в которых выводятся лишь выбранные поля
+    #[arg(long, value_name = "FIELDS")]
+    select: Option<Projection>,
+
+    /// Поиск транзакции по tx_id через индекс смещений `<FILE>.idx` без чтения файла
+    /// целиком. Флаг можно повторить. Без актуального индекса он строится чтением файла
+    #[arg(long, value_name = "ID")]
+    tx_id: Vec<u64>,
+
+    /// Сохранить индекс смещений рядом с файлом для следующих поисков по --tx-id
+    #[arg(long, requires = "tx_id")]
+    save_index: bool,
+
+    /// Максимальное количество выводимых транзакций
+    #[arg(long, value_name = "N")]
+    limit: Option<u64>,
+
+    /// Путь к выходному файлу. Если не задан, данные выводятся в stdout
+    #[arg(long, value_name = "FILE")]
+    output_file: Option<PathBuf>,
+
+    /// Формат выходных данных. Если не задан, определяется по расширению выходного файла
+    #[arg(long, value_name = "bin | csv | text")]
+    output_format: Option<Format>,
+
+    /// Файл настроек. Если не задан, используется fin-parser.toml из текущего каталога
+    #[arg(long, value_name = "FILE")]
+    config: Option<PathBuf>,
+}
+
+fn main() -> ExitCode {
+    let mut args = Args::parse();
+    let config = match Config::load(args.config.as_deref()) {
+        Ok(val) => val,
+        Err(e) => {
+            eprintln!("Невозможно прочитать настройки: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    args.output_file = args.output_file.map(|path| config.output.resolve(&path));
+    if !args.tx_id.is_empty() {
+        let res = find_by_id(&args).and_then(|mut found| match &args.select {
+            Some(projection) => write_projection(&mut found, projection, &args, &config.output),
+            None => write_transactions(&mut found, &args, &config.output),
+        });
+        if let Err(e) = res {
+            eprintln!("Ошибка поиска: {e}");
+            return ExitCode::FAILURE;
+        }
+        return ExitCode::SUCCESS;
+    }
+    let mut builder = config.input.reader_builder_for(Path::new(&args.input_file));
+    if let Some(fin_format) = args.input_format {
+        builder = builder.format(fin_format);
+    }
+    let res = match args.input_file.as_str() {
+        "-" => builder.build(io::stdin()),
+        path => builder.open(path),
+    };
+    let reader = match res {
+        Ok(val) => val,
+        Err(e) => {
+            eprintln!("Невозможно создать парсер: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let mut reader = FilteredReader::new(reader, args.filter.clone().unwrap_or_default());
+
+    let res = match &args.select {
+        Some(projection) => write_projection(&mut reader, projection, &args, &config.output),
+        None => write_transactions(&mut reader, &args, &config.output),
+    };
+    if let Err(e) = res {
+        eprintln!("Ошибка выборки: {e}");
+        return ExitCode::FAILURE;
+    }
+    ExitCode::SUCCESS
+}
+
+/// Транзакции, найденные по индексу
+struct Found(std::vec::IntoIter<Transaction>);
+
+impl TransactionRead for Found {
+    fn read_transaction(&mut self) -> Result<Option<Transaction>, ParsError> {
+        Ok(self.0.next())
+    }
+}
+
+/// Поиск транзакций --tx-id по индексу смещений с отбором по --where
+fn find_by_id(args: &Args) -> Result<Found, ParsError> {
+    let path = match args.input_file.as_str() {
+        "-" => {
+            return Err(ParsError::WrongFormat(
+                "Поиск по --tx-id требует файла, а не stdin".to_owned(),
+            ));
+        }
+        path => Path::new(path),
+    };
+    let index = OffsetIndex::load_or_build(path)?;
+    if args.save_index {
+        index.save(OffsetIndex::sidecar_path(path))?;
+    }
+    let filter = args.filter.clone().unwrap_or_default();
+    let mut found = Vec::new();
+    for tx_id in &args.tx_id {
+        if let Some(tx) = index.read(File::open(path)?, *tx_id)?
+            && filter.matches(&TransactionRef::from(&tx))
+        {
+            found.push(tx);
+        }
+    }
+    Ok(Found(found.into_iter()))
+}
+
+/// Запись отобранных транзакций целиком в выходном формате
+fn write_transactions<R: TransactionRead>(
+    reader: &mut R,
+    args: &Args,
+    config: &Section,
+) -> Result<(), ParsError> {
+    let mut builder = config.writer_builder_for(args.output_file.as_deref());
+    if let Some(fin_format) = args.output_format {
+        builder = builder.format(fin_format);
+    }
+    let mut writer = match &args.output_file {
+        Some(path) => builder.create(path)?,
+        None => builder.build(io::stdout())?,
+    };
+    let mut written = 0;
+    while written < args.limit.unwrap_or(u64::MAX)
+        && let Some(tx) = reader.read_transaction()?
+    {
+        writer.write_transaction(&tx)?;
+        written += 1;
+    }
+    writer.finish()
+}
+
+/// Запись выбранных полей отобранных транзакций
+fn write_projection<R: TransactionRead>(
+    reader: &mut R,
+    projection: &Projection,
+    args: &Args,
+    config: &Section,
+) -> Result<(), ParsError> {
+    let output_format = match (&args.output_format, &args.output_file) {
+        (Some(fin_format), _) => *fin_format,
+        (None, Some(path)) => match config.format_for(path) {
+            Some(val) => val,
+            None => Format::from_path(path)?.0,
+        },
+        (None, None) => config.format.unwrap_or(Format::Csv),
+    };
+    if !matches!(output_format, Format::Csv | Format::Text) {
+        return Err(ParsError::WrongFormat(format!(
+            "Выбор полей не поддерживается форматом {output_format}"
+        )));
+    }
+    let mut out: BufWriter<Box<dyn Write>> = BufWriter::new(match &args.output_file {
+        Some(path) => Box::new(File::create(path)?),
+        None => Box::new(io::stdout()),
+    });
+    if output_format == Format::Csv {
+        projection.write_csv_header(&mut out)?;
+    }
+    let mut written = 0;
+    while written < args.limit.unwrap_or(u64::MAX)
+        && let Some(tx) = reader.read_transaction()?
+    {
+        match output_format {
+            Format::Text => projection.write_text(&mut out, &tx)?,
+            _ => projection.write_csv(&mut out, &tx)?,
+        }
+        written += 1;
+    }
+    out.flush()?;
+    Ok(())
+}
@@ -0,0 +1,576 @@
+use clap::Parser;
+use fin_parser::compression::Compression;
+use fin_parser::config::{Config, Section};
+use fin_parser::error::ParsError;
+use fin_parser::filter::{FilteredReader, TxFilter};
+use fin_parser::follow::Follow;
+use fin_parser::format::Format;
+use fin_parser::index::{INDEX_EXTENSION, OffsetIndex};
+use fin_parser::reconcile::json_string;
+use fin_parser::transaction::Transaction;
+use fin_parser::tx_format::TxReader;
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use tiny_http::{Header, Method, Request, Response, ResponseBox, Server, StatusCode};
+use tungstenite::handshake::derive_accept_key;
+use tungstenite::protocol::Role;
+use tungstenite::{Message, WebSocket};
+
+#[derive(Parser)]
+#[command(name = "YpbServer")]
+#[command(version = "1.0")]
+#[command(about = "HTTP-сервер для чтения файлов транзакций из каталога")]
+#[command(after_help = "Запросы:
+  GET /files                    список файлов каталога
+  GET /files/NAME               транзакции файла в формате NDJSON. Параметры: where, status,
+                                type, from, to, skip, limit, например
+                                /files/a.bin?status=PENDING&from=2021-10-01&limit=100
+  GET /files/NAME/tx/TX_ID      транзакция по tx_id. Для несжатых файлов bin используется
+                                индекс смещений
+  GET /files/NAME/tail          WebSocket: транзакции, дописываемые в несжатый файл, по мере
+                                появления. Параметры как у /files/NAME и since=start для
+                                отправки сначала уже записанных транзакций")]
+struct Args {
+    /// Каталог с файлами транзакций. Вложенные каталоги не обслуживаются
+    #[arg(value_name = "DIR")]
+    dir: PathBuf,
+
+    /// Адрес и порт сервера
+    #[arg(long, value_name = "ADDR", default_value = "127.0.0.1:8080")]
+    listen: String,
+
+    /// Сохранение построенных индексов в файлы `<имя>.idx` рядом с файлами данных,
+    /// чтобы после перезапуска не строить их заново
+    #[arg(long)]
+    write_index: bool,
+
+    /// Файл настроек. Если не задан, используется fin-parser.toml из текущего каталога
+    #[arg(long, value_name = "FILE")]
+    config: Option<PathBuf>,
+}
+
+/// Общее состояние обработчиков запросов
+struct State {
+    dir: PathBuf,
+    config: Section,
+    write_index: bool,
+    /// Индексы файлов bin, построенные или прочитанные при первом обращении
+    indexes: Mutex<HashMap<PathBuf, Arc<OffsetIndex>>>,
+}
+
+/// Интервал отправки ping клиенту WebSocket, если новых транзакций нет.
+/// По неудачной отправке обнаруживается отключение клиента
+const PING_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Количество транзакций в очереди между потоком чтения файла и соединением WebSocket
+const TAIL_QUEUE_LEN: usize = 256;
+
+/// Очередь транзакций дописываемого файла и флаг остановки его чтения
+type Tail = (Receiver<Result<Transaction, ParsError>>, Arc<AtomicBool>);
+
+/// Ответ с ошибкой: код и сообщение
+struct HttpError(u16, String);
+
+impl From<ParsError> for HttpError {
+    fn from(e: ParsError) -> Self {
+        HttpError(500, e.to_string())
+    }
+}
+
+fn main() -> ExitCode {
+    let args = Args::parse();
+    let config = match Config::load(args.config.as_deref()) {
+        Ok(val) => val,
+        Err(e) => {
+            eprintln!("Невозможно прочитать настройки: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let dir = config.input.resolve(&args.dir);
+    if !dir.is_dir() {
+        eprintln!("Каталог {} не найден", dir.display());
+        return ExitCode::FAILURE;
+    }
+    let server = match Server::http(&args.listen) {
+        Ok(val) => val,
+        Err(e) => {
+            eprintln!("Невозможно запустить сервер на {}: {e}", args.listen);
+            return ExitCode::FAILURE;
+        }
+    };
+    eprintln!(
+        "Каталог {} доступен по адресу http://{}",
+        dir.display(),
+        args.listen
+    );
+    let state = Arc::new(State {
+        dir,
+        config: config.input,
+        write_index: args.write_index,
+        indexes: Mutex::new(HashMap::new()),
+    });
+    for request in server.incoming_requests() {
+        let state = Arc::clone(&state);
+        thread::spawn(move || handle(&state, request));
+    }
+    ExitCode::SUCCESS
+}
+
+/// Обработка запроса. Ошибки отправки ответа (например, клиент закрыл соединение) игнорируются
+fn handle(state: &State, request: Request) {
+    let (path, query) = match request.url().split_once('?') {
+        Some((path, query)) => (path.to_owned(), query.to_owned()),
+        None => (request.url().to_owned(), String::new()),
+    };
+    let segments: Vec<String> = path
+        .split('/')
+        .filter(|segment| !segment.is_empty())
+        .map(decode)
+        .collect();
+    let segments: Vec<&str> = segments.iter().map(String::as_str).collect();
+    let method = request.method().clone();
+    let res = match (&method, segments.as_slice()) {
+        (Method::Get, ["files"]) => list_files(state).map(json_response),
+        (Method::Get, ["files", name]) => stream_file(state, name, &query).map(Response::boxed),
+        (Method::Get, ["files", name, "tx", tx_id]) => {
+            get_transaction(state, name, tx_id).map(|tx| json_response(tx.to_json() + "\n"))
+        }
+        (Method::Get, ["files", name, "tail"]) => return tail(state, request, name, &query),
+        (Method::Get, _) => Err(HttpError(404, format!("Неизвестный путь {path}"))),
+        (method, _) => Err(HttpError(405, format!("Метод {method} не поддерживается"))),
+    };
+    let _ = match res {
+        Ok(response) => request.respond(response),
+        Err(e) => request.respond(error_response(e)),
+    };
+}
+
+fn error_response(HttpError(code, message): HttpError) -> ResponseBox {
+    Response::from_string(format!("{{\"error\":{}}}\n", json_string(&message)))
+        .with_status_code(code)
+        .with_header(content_type("application/json"))
+        .boxed()
+}
+
+/// Список файлов каталога: `[{"name":"a.bin","format":"bin","compression":"none","size":54}]`
+fn list_files(state: &State) -> Result<String, HttpError> {
+    let mut files = Vec::new();
+    for entry in fs::read_dir(&state.dir).map_err(ParsError::from)? {
+        let entry = entry.map_err(ParsError::from)?;
+        let path = entry.path();
+        let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+            continue;
+        };
+        let Ok(meta) = entry.metadata() else {
+            continue;
+        };
+        if !meta.is_file() || path.extension().is_some_and(|ext| ext == INDEX_EXTENSION) {
+            continue;
+        }
+        let (fin_format, compression) = match Format::from_path(&path) {
+            Ok(val) => val,
+            Err(_) => match state.config.format {
+                Some(fin_format) => (fin_format, state.config.compression.unwrap_or_default()),
+                None => continue,
+            },
+        };
+        files.push((name.to_owned(), fin_format, compression, meta.len()));
+    }
+    files.sort_by(|lhs, rhs| lhs.0.cmp(&rhs.0));
+    let items: Vec<String> = files
+        .iter()
+        .map(|(name, fin_format, compression, size)| {
+            format!(
+                "{{\"name\":{},\"format\":\"{fin_format}\",\"compression\":\"{compression}\",\"size\":{size}}}",
+                json_string(name)
+            )
+        })
+        .collect();
+    Ok(format!("[{}]\n", items.join(",")))
+}
+
+/// Параметры отбора из строки запроса
+struct Query {
+    filter: TxFilter,
+    skip: u64,
+    limit: u64,
+    /// Для tail: выдача транзакций с начала файла, а не только дописанных
+    since_start: Option<bool>,
+}
+
+impl FromStr for Query {
+    type Err = HttpError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut query = Query {
+            filter: TxFilter::default(),
+            skip: 0,
+            limit: u64::MAX,
+            since_start: None,
+        };
+        for (key, value) in form_urlencoded::parse(s.as_bytes()) {
+            let condition = match key.as_ref() {
+                "where" => value.parse(),
+                "status" => condition("status", "=", &value),
+                "type" => condition("tx_type", "=", &value),
+                "from" => condition("timestamp", ">=", &value),
+                "to" => condition("timestamp", "<", &value),
+                "skip" => {
+                    query.skip = number(&key, &value)?;
+                    continue;
+                }
+                "limit" => {
+                    query.limit = number(&key, &value)?;
+                    continue;
+                }
+                "since" => {
+                    query.since_start = match value.as_ref() {
+                        "start" => Some(true),
+                        "end" => Some(false),
+                        _ => {
+                            return Err(HttpError(
+                                400,
+                                format!(
+                                    "Параметр since: ожидается start или end, получено {value}"
+                                ),
+                            ));
+                        }
+                    };
+                    continue;
+                }
+                _ => return Err(HttpError(400, format!("Неизвестный параметр {key}"))),
+            };
+            let condition =
+                condition.map_err(|e| HttpError(400, format!("Параметр {key}: {e}")))?;
+            query.filter = query.filter.and(condition);
+        }
+        Ok(query)
+    }
+}
+
+/// Потоковая выдача отобранных транзакций файла, по одному объекту json в строке
+fn stream_file(state: &State, name: &str, query: &str) -> Result<Response<NdJson>, HttpError> {
+    let Query {
+        filter,
+        skip,
+        limit,
+        since_start,
+    } = query.parse()?;
+    if since_start.is_some() {
+        return Err(HttpError(
+            400,
+            "Параметр since поддерживается только для tail".to_owned(),
+        ));
+    }
+    let reader = open_reader(state, name)?;
+    let body = NdJson {
+        reader: FilteredReader::new(reader, filter),
+        skip,
+        limit,
+        line: Vec::new(),
+        pos: 0,
+        done: false,
+    };
+    Ok(Response::new(
+        StatusCode(200),
+        vec![content_type("application/x-ndjson")],
+        body,
+        None,
+        None,
+    ))
+}
+
+/// Транзакция по tx_id. Несжатые файлы bin читаются по индексу смещений,
+/// остальные просматриваются до первой транзакции с этим tx_id
+fn get_transaction(state: &State, name: &str, tx_id: &str) -> Result<Transaction, HttpError> {
+    let tx_id: u64 = number("tx_id", tx_id)?;
+    let path = file_path(state, name)?;
+    let res = match Format::from_path(&path) {
+        Ok((Format::Bin, Compression::None)) => {
+            let index = offset_index(state, &path)?;
+            index.read(
+                BufReader::new(File::open(&path).map_err(ParsError::from)?),
+                tx_id,
+            )?
+        }
+        _ => {
+            let mut reader = open_reader(state, name)?;
+            loop {
+                match reader.read_transaction()? {
+                    Some(tx) if tx.tx_id == tx_id => break Some(tx),
+                    Some(_) => continue,
+                    None => break None,
+                }
+            }
+        }
+    };
+    res.ok_or_else(|| HttpError(404, format!("Транзакция {tx_id} не найдена в {name}")))
+}
+
+/// Индекс файла bin из кэша, из файла индекса или построенный заново
+fn offset_index(state: &State, path: &Path) -> Result<Arc<OffsetIndex>, HttpError> {
+    if let Some(index) = state.indexes.lock().unwrap().get(path) {
+        return Ok(Arc::clone(index));
+    }
+    let index = OffsetIndex::load_or_build(path)?;
+    if state.write_index {
+        let sidecar = OffsetIndex::sidecar_path(path);
+        if !sidecar.exists()
+            && let Err(e) = index.save(&sidecar)
+        {
+            eprintln!("Невозможно сохранить индекс {}: {e}", sidecar.display());
+        }
+    }
+    let index = Arc::new(index);
+    state
+        .indexes
+        .lock()
+        .unwrap()
+        .insert(path.to_owned(), Arc::clone(&index));
+    Ok(index)
+}
+
+/// Подключение WebSocket к /files/NAME/tail: транзакции, дописываемые в файл,
+/// отправляются клиенту текстовыми сообщениями json по мере появления.
+/// Ошибка чтения передается сообщением `{"error":"..."}`, после которого соединение закрывается
+fn tail(state: &State, request: Request, name: &str, query: &str) {
+    let key = request
+        .headers()
+        .iter()
+        .find(|header| header.field.equiv("Sec-WebSocket-Key"))
+        .map(|header| header.value.to_string());
+    let res = match key {
+        Some(key) => follow(state, name, query).map(|tail| (key, tail)),
+        None => Err(HttpError(400, "Ожидается запрос WebSocket".to_owned())),
+    };
+    let (key, (receiver, stop)) = match res {
+        Ok(val) => val,
+        Err(e) => {
+            let _ = request.respond(error_response(e));
+            return;
+        }
+    };
+    // Заголовки Upgrade и Connection добавляет tiny_http
+    let response = Response::empty(101).with_header(header(
+        "Sec-WebSocket-Accept",
+        &derive_accept_key(key.as_bytes()),
+    ));
+    let stream = request.upgrade("websocket", response);
+    let mut socket = WebSocket::from_raw_socket(stream, Role::Server, None);
+    loop {
+        let message = match receiver.recv_timeout(PING_INTERVAL) {
+            Ok(Ok(tx)) => Message::text(tx.to_json()),
+            Ok(Err(e)) => Message::text(format!("{{\"error\":{}}}", json_string(&e.to_string()))),
+            Err(RecvTimeoutError::Timeout) => Message::Ping(Default::default()),
+            Err(RecvTimeoutError::Disconnected) => break,
+        };
+        // Ошибка отправки означает, что клиент отключился
+        if socket.send(message).is_err() {
+            break;
+        }
+    }
+    stop.store(true, Ordering::Relaxed);
+    let _ = socket.close(None);
+    let _ = socket.flush();
+}
+
+/// Отобранные транзакции дописываемого файла. Файл читается в отдельном потоке
+/// до установки флага остановки; без since=start пропускаются транзакции,
+/// записанные до подключения
+fn follow(state: &State, name: &str, query: &str) -> Result<Tail, HttpError> {
+    let Query {
+        filter,
+        mut skip,
+        mut limit,
+        since_start,
+    } = query.parse()?;
+    let path = file_path(state, name)?;
+    let (fin_format, compression) = match Format::from_path(&path) {
+        Ok(val) => val,
+        Err(e) => (
+            state.config.format.ok_or(HttpError(400, e.to_string()))?,
+            state.config.compression.unwrap_or_default(),
+        ),
+    };
+    if compression != Compression::None {
+        return Err(HttpError(
+            400,
+            format!("Ожидание данных в сжатом файле {name} не поддерживается"),
+        ));
+    }
+    let builder = state.config.reader_builder().format(fin_format);
+    // Неполная последняя запись не учитывается и будет отправлена после дозаписи
+    let existing = match since_start {
+        Some(true) => 0,
+        _ => {
+            let file = File::open(&path).map_err(ParsError::from)?;
+            let mut reader = builder.clone().build(file)?;
+            reader.count().unwrap_or_else(|_| reader.position().records)
+        }
+    };
+    let follow = Follow::open(&path).map_err(ParsError::from)?;
+    let stop = follow.stop_flag();
+    let (sender, receiver) = mpsc::sync_channel(TAIL_QUEUE_LEN);
+    thread::spawn(move || {
+        let res = builder.build(follow).and_then(|mut reader| {
+            reader.skip(existing)?;
+            Ok(reader)
+        });
+        let mut reader = match res {
+            Ok(reader) => FilteredReader::new(reader, filter),
+            Err(e) => {
+                let _ = sender.send(Err(e));
+                return;
+            }
+        };
+        while limit > 0 {
+            match reader.read_transaction() {
+                Ok(Some(_)) if skip > 0 => skip -= 1,
+                Ok(Some(tx)) => {
+                    limit -= 1;
+                    if sender.send(Ok(tx)).is_err() {
+                        break;
+                    }
+                }
+                Ok(None) => break,
+                Err(e) => {
+                    let _ = sender.send(Err(e));
+                    break;
+                }
+            }
+        }
+    });
+    Ok((receiver, stop))
+}
+
+/// Путь файла каталога. Имена с разделителями пути и скрытые файлы не допускаются
+fn file_path(state: &State, name: &str) -> Result<PathBuf, HttpError> {
+    let not_found = || HttpError(404, format!("Файл {name} не найден"));
+    if name.starts_with('.') || name.contains(['/', '\\']) {
+        return Err(not_found());
+    }
+    let path = state.dir.join(name);
+    match path.is_file() {
+        true => Ok(path),
+        false => Err(not_found()),
+    }
+}
+
+fn open_reader(state: &State, name: &str) -> Result<TxReader<Box<dyn Read + Send>>, HttpError> {
+    let path = file_path(state, name)?;
+    let builder = state.config.reader_builder_for(&path);
+    let res = match Format::from_path(&path) {
+        Ok(_) => builder.open(&path),
+        Err(_) => File::open(&path)
+            .map_err(ParsError::from)
+            .and_then(|file| builder.build(file)),
+    };
+    Ok(res?)
+}
+
+/// Условие `field op value` на языке where. Значение берется в кавычки,
+/// поэтому не может изменить структуру выражения
+fn condition(field: &str, op: &str, value: &str) -> Result<TxFilter, ParsError> {
+    let value = value.replace('\\', "\\\\").replace('"', "\\\"");
+    format!("{field} {op} \"{value}\"").parse()
+}
+
+fn number(key: &str, value: &str) -> Result<u64, HttpError> {
+    value.parse().map_err(|_| {
+        HttpError(
+            400,
+            format!("Параметр {key}: ожидается число, получено {value}"),
+        )
+    })
+}
+
+/// Декодирование %XX в сегменте пути
+fn decode(segment: &str) -> String {
+    form_urlencoded::parse(format!("s={}", segment.replace('+', "%2B")).as_bytes())
+        .next()
+        .map(|(_, value)| value.into_owned())
+        .unwrap_or_default()
+}
+
+fn header(name: &str, value: &str) -> Header {
+    Header::from_bytes(name, value).unwrap()
+}
+
+fn content_type(value: &str) -> Header {
+    header("Content-Type", value)
+}
+
+fn json_response(body: String) -> ResponseBox {
+    Response::from_string(body)
+        .with_header(content_type("application/json"))
+        .boxed()
+}
+
+/// Тело ответа NDJSON, формируемое по мере чтения файла. Ошибка чтения посреди
+/// потока передается последней строкой вида `{"error":"..."}`, так как код ответа
+/// к этому моменту уже отправлен
+struct NdJson {
+    reader: FilteredReader<Box<dyn Read + Send>>,
+    skip: u64,
+    limit: u64,
+    line: Vec<u8>,
+    pos: usize,
+    done: bool,
+}
+
+impl NdJson {
+    /// Следующая строка ответа. false, если поток закончился
+    fn next_line(&mut self) -> bool {
+        if self.done {
+            return false;
+        }
+        let res = loop {
+            if self.limit == 0 {
+                break Ok(None);
+            }
+            match self.reader.read_transaction() {
+                Ok(Some(_)) if self.skip > 0 => self.skip -= 1,
+                res => break res,
+            }
+        };
+        let line = match res {
+            Ok(Some(tx)) => {
+                self.limit -= 1;
+                tx.to_json()
+            }
+            Ok(None) => {
+                self.done = true;
+                return false;
+            }
+            Err(e) => {
+                self.done = true;
+                format!("{{\"error\":{}}}", json_string(&e.to_string()))
+            }
+        };
+        self.line = line.into_bytes();
+        self.line.push(b'\n');
+        self.pos = 0;
+        true
+    }
+}
+
+impl Read for NdJson {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.pos == self.line.len() && !self.next_line() {
+            return Ok(0);
+        }
+        let len = buf.len().min(self.line.len() - self.pos);
+        buf[..len].copy_from_slice(&self.line[self.pos..self.pos + len]);
+        self.pos += len;
+        Ok(len)
+    }
+}
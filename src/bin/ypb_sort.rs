@@ -0,0 +1,133 @@
+use clap::Parser;
+use fin_parser::config::Config;
+use fin_parser::converter::convert;
+use fin_parser::format::Format;
+use fin_parser::sort::{
+    DEFAULT_RUN_RECORDS, ExternalSorter, Order, SortKey, canonicalize, sort_stream,
+};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+#[derive(Parser)]
+#[command(name = "YpbSorter")]
+#[command(version = "1.0")]
+#[command(about = "Утилита для сортировки файла транзакций")]
+struct Args {
+    /// Путь файла, `-` для чтения из stdin
+    #[arg(value_name = "FILE")]
+    input_file: String,
+
+    /// Формат входного файла. Если не задан, определяется по расширению или содержимому
+    #[arg(long, value_name = "bin | csv | text")]
+    input_format: Option<Format>,
+
+    /// Путь к выходному файлу. Если не задан, данные выводятся в stdout
+    #[arg(long, value_name = "FILE")]
+    output_file: Option<PathBuf>,
+
+    /// Формат выходных данных. Если не задан, определяется по расширению выходного файла
+    #[arg(long, value_name = "bin | csv | text")]
+    output_format: Option<Format>,
+
+    /// Поле сортировки. Транзакции с равными значениями сохраняют исходный порядок
+    #[arg(
+        long,
+        value_name = "timestamp | tx_id | amount",
+        default_value = "timestamp"
+    )]
+    by: SortKey,
+
+    /// Сортировка по убыванию
+    #[arg(long)]
+    desc: bool,
+
+    /// Внешняя сортировка для файлов, не помещающихся в память: участки файла
+    /// сортируются по отдельности во временных файлах и затем сливаются
+    #[arg(long)]
+    external: bool,
+
+    /// Каноническая форма: сортировка по tx_id и остальным полям, чтобы выгрузки
+    /// одних и тех же данных совпадали побайтно. Файл сортируется в памяти
+    #[arg(long, conflicts_with_all = ["by", "desc", "external"])]
+    canonical: bool,
+
+    /// Каталог временных файлов внешней сортировки. По умолчанию временный каталог системы
+    #[arg(long, value_name = "DIR", requires = "external")]
+    tmp_dir: Option<PathBuf>,
+
+    /// Количество транзакций в одном участке внешней сортировки
+    #[arg(long, value_name = "N", default_value_t = DEFAULT_RUN_RECORDS, requires = "external")]
+    run_records: usize,
+
+    /// Файл настроек. Если не задан, используется fin-parser.toml из текущего каталога
+    #[arg(long, value_name = "FILE")]
+    config: Option<PathBuf>,
+}
+
+fn main() -> ExitCode {
+    let args = Args::parse();
+    let config = match Config::load(args.config.as_deref()) {
+        Ok(val) => val,
+        Err(e) => {
+            eprintln!("Невозможно прочитать настройки: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let mut builder = config.input.reader_builder_for(Path::new(&args.input_file));
+    if let Some(fin_format) = args.input_format {
+        builder = builder.format(fin_format);
+    }
+    let res = match args.input_file.as_str() {
+        "-" => builder.build(io::stdin()),
+        path => builder.open(path),
+    };
+    let mut reader = match res {
+        Ok(val) => val,
+        Err(e) => {
+            eprintln!("Невозможно создать парсер: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let output_file = args.output_file.map(|path| config.output.resolve(&path));
+    let mut builder = config.output.writer_builder_for(output_file.as_deref());
+    if let Some(fin_format) = args.output_format {
+        builder = builder.format(fin_format);
+    }
+    let writer = match &output_file {
+        Some(path) => builder.create(path),
+        None => builder.build(io::stdout()),
+    };
+    let mut writer = match writer {
+        Ok(val) => val,
+        Err(e) => {
+            eprintln!("Невозможно создать писатель: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let order = match args.desc {
+        true => Order::Descending,
+        false => Order::Ascending,
+    };
+    let res = match args.external {
+        _ if args.canonical => canonicalize(&mut reader, &mut writer),
+        true => {
+            let mut sorter = ExternalSorter::new(args.by, order).with_run_records(args.run_records);
+            if let Some(dir) = &args.tmp_dir {
+                sorter = sorter.with_temp_dir(dir);
+            }
+            sorter
+                .sort(&mut reader)
+                .and_then(|mut runs| convert(&mut runs, &mut writer))
+        }
+        false => sort_stream(&mut reader, args.by, order)
+            .and_then(|txs| writer.write_all(txs.as_slice()).map(|_| 0)),
+    };
+    if let Err(e) = res.and_then(|_| writer.finish()) {
+        eprintln!("Ошибка сортировки: {e}");
+        return ExitCode::FAILURE;
+    }
+    ExitCode::SUCCESS
+}
@@ -0,0 +1,141 @@
+use clap::{Parser, ValueEnum};
+use fin_parser::analytics::{Summary, magnitude_range, summarize};
+use fin_parser::config::Config;
+use fin_parser::error::ParsError;
+use fin_parser::format::Format;
+use fin_parser::pivot::{PivotFormat, pivot};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+#[derive(Parser)]
+#[command(name = "YpbStats")]
+#[command(version = "1.0")]
+#[command(about = "Утилита для подсчета статистики по файлу транзакций")]
+struct Args {
+    /// Путь файла, `-` для чтения из stdin
+    #[arg(value_name = "FILE")]
+    input_file: String,
+
+    /// Формат файла. Если не задан, определяется по расширению или содержимому
+    #[arg(long, value_name = "bin | csv | text")]
+    format: Option<Format>,
+
+    /// Формат отчета
+    #[arg(long, value_enum, default_value_t = ReportFormat::Text)]
+    report_format: ReportFormat,
+
+    /// Количество выводимых самых активных пользователей
+    #[arg(long, value_name = "N", default_value_t = 10)]
+    top: usize,
+
+    /// Вместо статистики вывести сводную таблицу по месяцам, типам и статусам
+    #[arg(long, value_name = "csv | markdown | html")]
+    pivot: Option<PivotFormat>,
+
+    /// Файл настроек. Если не задан, используется fin-parser.toml из текущего каталога
+    #[arg(long, value_name = "FILE")]
+    config: Option<PathBuf>,
+}
+
+/// Формат отчета статистики
+#[derive(Clone, Copy, ValueEnum)]
+enum ReportFormat {
+    /// Текстовый отчет
+    Text,
+    /// json
+    Json,
+}
+
+fn main() -> ExitCode {
+    let args = Args::parse();
+    let config = match Config::load(args.config.as_deref()) {
+        Ok(val) => val,
+        Err(e) => {
+            eprintln!("Невозможно прочитать настройки: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let mut builder = config.input.reader_builder_for(Path::new(&args.input_file));
+    if let Some(fin_format) = args.format {
+        builder = builder.format(fin_format);
+    }
+    let res = match args.input_file.as_str() {
+        "-" => builder.build(io::stdin()),
+        path => builder.open(path),
+    };
+    let mut reader = match res {
+        Ok(val) => val,
+        Err(e) => {
+            eprintln!("Невозможно создать парсер: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    if let Some(pivot_format) = args.pivot {
+        let table = match pivot(&mut reader) {
+            Ok(val) => val,
+            Err(e) => {
+                eprintln!("Ошибка чтения данных: {e}");
+                return ExitCode::FAILURE;
+            }
+        };
+        if let Err(e) = table.write(&mut io::stdout().lock(), pivot_format) {
+            eprintln!("Невозможно вывести отчет: {e}");
+            return ExitCode::FAILURE;
+        }
+        return ExitCode::SUCCESS;
+    }
+    let summary = match summarize(&mut reader) {
+        Ok(val) => val,
+        Err(e) => {
+            eprintln!("Ошибка чтения данных: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let res = match args.report_format {
+        ReportFormat::Text => write_text(&mut io::stdout().lock(), &summary, args.top),
+        ReportFormat::Json => summary.write_json(&mut io::stdout().lock(), args.top),
+    };
+    if let Err(e) = res {
+        eprintln!("Невозможно вывести отчет: {e}");
+        return ExitCode::FAILURE;
+    }
+    ExitCode::SUCCESS
+}
+
+/// Текстовый отчет без группировок по отдельным пользователям и дням,
+/// которых в больших файлах слишком много для чтения
+fn write_text<Out: Write>(out: &mut Out, summary: &Summary, top: usize) -> Result<(), ParsError> {
+    writeln!(out, "Всего: {}", summary.total)?;
+    if let (Some(earliest), Some(latest)) = (summary.earliest, summary.latest) {
+        writeln!(out, "Период: {earliest} - {latest}")?;
+    }
+    if !summary.by_type.is_empty() {
+        writeln!(out, "По типу:")?;
+        for (tx_type, val) in &summary.by_type {
+            writeln!(out, "  {tx_type:?}: {val}")?;
+        }
+    }
+    if !summary.by_status.is_empty() {
+        writeln!(out, "По статусу:")?;
+        for (status, val) in &summary.by_status {
+            writeln!(out, "  {status:?}: {val}")?;
+        }
+    }
+    let users = summary.busiest_users(top);
+    if !users.is_empty() {
+        writeln!(out, "Самые активные пользователи:")?;
+        for (user, val) in users {
+            writeln!(out, "  {user}: {val}")?;
+        }
+    }
+    if !summary.by_magnitude.is_empty() {
+        writeln!(out, "Распределение сумм:")?;
+        for (digits, val) in &summary.by_magnitude {
+            let (low, high) = magnitude_range(*digits);
+            writeln!(out, "  {low}..{high}: {}", val.count)?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,105 @@
+use clap::Parser;
+use fin_parser::config::Config;
+use fin_parser::format::Format;
+use fin_parser::options::ErrorPolicy;
+use fin_parser::validate::{Rule, Validator};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+#[derive(Parser)]
+#[command(name = "YpbValidator")]
+#[command(version = "1.0")]
+#[command(about = "Утилита для проверки файла транзакций перед загрузкой")]
+#[command(
+    after_help = "Код завершения: 0 для корректного файла, 1 при ошибках разбора или нарушениях правил, 2 при ошибке чтения"
+)]
+struct Args {
+    /// Путь файла, `-` для чтения из stdin
+    #[arg(value_name = "FILE")]
+    input_file: String,
+
+    /// Формат файла. Если не задан, определяется по расширению или содержимому
+    #[arg(long, value_name = "bin | csv | text")]
+    format: Option<Format>,
+
+    /// Отключаемое правило: duplicate_tx_id, non_positive_amount, system_user,
+    /// self_transfer или future_timestamp. Можно указать несколько раз
+    #[arg(long, value_name = "RULE")]
+    disable: Vec<Rule>,
+
+    /// Файл настроек. Если не задан, используется fin-parser.toml из текущего каталога
+    #[arg(long, value_name = "FILE")]
+    config: Option<PathBuf>,
+}
+
+/// Код завершения при ошибках разбора или нарушениях правил
+const INVALID_EXIT_CODE: u8 = 1;
+/// Код завершения при ошибке чтения
+const ERROR_EXIT_CODE: u8 = 2;
+
+fn main() -> ExitCode {
+    let args = Args::parse();
+    let config = match Config::load(args.config.as_deref()) {
+        Ok(val) => val,
+        Err(e) => {
+            eprintln!("Невозможно прочитать настройки: {e}");
+            return ExitCode::from(ERROR_EXIT_CODE);
+        }
+    };
+    let mut builder = config
+        .input
+        .reader_builder_for(Path::new(&args.input_file))
+        .strict(true)
+        .error_policy(ErrorPolicy::Skip);
+    if let Some(fin_format) = args.format {
+        builder = builder.format(fin_format);
+    }
+    let res = match args.input_file.as_str() {
+        "-" => builder.build(io::stdin()),
+        path => builder.open(path),
+    };
+    let mut reader = match res {
+        Ok(val) => val,
+        Err(e) => {
+            eprintln!("Невозможно создать парсер: {e}");
+            return ExitCode::from(ERROR_EXIT_CODE);
+        }
+    };
+
+    let mut validator = Validator::new();
+    for rule in &args.disable {
+        validator = validator.without(*rule);
+    }
+    let report = match validator.validate(&mut reader) {
+        Ok(val) => val,
+        Err(e) => {
+            eprintln!("Ошибка чтения данных: {e}");
+            return ExitCode::from(ERROR_EXIT_CODE);
+        }
+    };
+
+    let errors = reader.error_report();
+    if !errors.is_empty() {
+        print!("{errors}");
+    }
+    for finding in &report.findings {
+        println!("{finding}");
+    }
+
+    println!("Проверено записей: {}", report.records);
+    if errors.is_empty() && report.is_valid() {
+        println!("Нарушений нет");
+        return ExitCode::SUCCESS;
+    }
+    if !errors.is_empty() {
+        println!("Записей с ошибками разбора: {}", errors.len());
+    }
+    if !report.is_valid() {
+        println!("Нарушений правил: {}", report.findings.len());
+    }
+    for (rule, count) in report.counts() {
+        println!("  {}: {count}", rule.name());
+    }
+    ExitCode::from(INVALID_EXIT_CODE)
+}
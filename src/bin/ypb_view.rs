@@ -0,0 +1,419 @@
+use clap::Parser;
+use fin_parser::compression::Compression;
+use fin_parser::config::{Config, Section};
+use fin_parser::error::ParsError;
+use fin_parser::filter::TxFilter;
+use fin_parser::format::Format;
+use fin_parser::options::ErrorPolicy;
+use fin_parser::reconcile::Field;
+use fin_parser::report::ErrorReport;
+use fin_parser::transaction::Transaction;
+use ratatui::DefaultTerminal;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Modifier, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Borders, Paragraph, Row, Table, TableState, Wrap};
+use std::fs;
+use std::io::{self, Cursor, Read};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+/// Количество байт в строке шестнадцатеричного дампа
+const HEX_LINE_LEN: usize = 16;
+
+#[derive(Parser)]
+#[command(name = "YpbViewer")]
+#[command(version = "1.0")]
+#[command(about = "Интерактивный просмотр файла транзакций")]
+#[command(
+    after_help = "Клавиши: стрелки, PgUp/PgDn, Home/End — перемещение; / — условие отбора; \
+    # — переход к tx_id; Esc — отмена ввода; q — выход"
+)]
+struct Args {
+    /// Путь файла. Файл целиком загружается в память
+    #[arg(value_name = "FILE")]
+    input_file: String,
+
+    /// Формат файла. Если не задан, определяется по расширению или содержимому
+    #[arg(long, value_name = "bin | csv | text")]
+    format: Option<Format>,
+
+    /// Файл настроек. Если не задан, используется fin-parser.toml из текущего каталога
+    #[arg(long, value_name = "FILE")]
+    config: Option<PathBuf>,
+}
+
+/// Прочитанная транзакция и занимаемый ею фрагмент файла
+struct Record {
+    tx: Transaction,
+    /// Порядковый номер записи, начиная с нуля
+    record: u64,
+    /// Начало фрагмента в распакованных данных. Фрагмент включает пропущенные
+    /// перед транзакцией записи с ошибками
+    start: usize,
+    end: usize,
+    /// Количество пропущенных перед транзакцией записей с ошибками
+    skipped: usize,
+}
+
+/// Загруженный файл
+struct Document {
+    data: Vec<u8>,
+    fin_format: Format,
+    records: Vec<Record>,
+    errors: ErrorReport,
+}
+
+impl Document {
+    /// Загрузка файла с пропуском записей с ошибками
+    fn load(path: &str, fin_format: Option<Format>, config: &Section) -> Result<Self, ParsError> {
+        let raw = fs::read(path)?;
+        let (path_format, compression) = match Format::from_path(Path::new(path)) {
+            Ok((fin_format, compression)) => (Some(fin_format), compression),
+            Err(_) => (
+                config.format,
+                config
+                    .compression
+                    .unwrap_or_else(|| Compression::detect(&raw)),
+            ),
+        };
+        let mut data = Vec::new();
+        compression
+            .wrap_reader(Cursor::new(raw))
+            .read_to_end(&mut data)?;
+
+        let fin_format = match fin_format.or(path_format) {
+            Some(val) => val,
+            None => Format::detect(&data)?,
+        };
+        let mut reader = config
+            .reader_builder()
+            .format(fin_format)
+            .error_policy(ErrorPolicy::Skip)
+            .build(Cursor::new(data.clone()))?;
+        let mut records = Vec::new();
+        // Заголовок csv читается вместе с первой записью и не входит в ее фрагмент
+        let mut start = match fin_format {
+            Format::Csv => data
+                .iter()
+                .position(|byte| *byte == b'\n')
+                .map_or(0, |pos| pos + 1),
+            _ => 0,
+        };
+        let mut errors = 0;
+        while let Some(tx) = reader.read_transaction()? {
+            let position = reader.position();
+            let skipped = reader.error_report().len() - errors;
+            errors += skipped;
+            records.push(Record {
+                tx,
+                record: position.records - 1,
+                start,
+                end: position.bytes as usize,
+                skipped,
+            });
+            start = position.bytes as usize;
+        }
+        Ok(Self {
+            data,
+            fin_format,
+            records,
+            errors: reader.take_error_report(),
+        })
+    }
+
+    /// Исходный фрагмент записи: шестнадцатеричный дамп для bin, строки для текстовых форматов
+    fn raw_lines(&self, record: &Record) -> Vec<String> {
+        let raw = &self.data[record.start.min(self.data.len())..record.end.min(self.data.len())];
+        match self.fin_format {
+            Format::Bin => raw
+                .chunks(HEX_LINE_LEN)
+                .enumerate()
+                .map(|(idx, chunk)| {
+                    let hex: Vec<String> = chunk.iter().map(|byte| format!("{byte:02x}")).collect();
+                    let text: String = chunk
+                        .iter()
+                        .map(|byte| match byte.is_ascii_graphic() || *byte == b' ' {
+                            true => *byte as char,
+                            false => '.',
+                        })
+                        .collect();
+                    format!(
+                        "{:08x}  {:<width$}  {text}",
+                        record.start + idx * HEX_LINE_LEN,
+                        hex.join(" "),
+                        width = HEX_LINE_LEN * 3 - 1
+                    )
+                })
+                .collect(),
+            _ => String::from_utf8_lossy(raw)
+                .lines()
+                .map(str::to_owned)
+                .collect(),
+        }
+    }
+}
+
+/// Режим строки ввода
+#[derive(Clone, Copy, Eq, PartialEq)]
+enum Input {
+    /// Ввод не выполняется
+    None,
+    /// Условие отбора
+    Filter,
+    /// Идентификатор транзакции для перехода
+    Jump,
+}
+
+/// Состояние просмотра
+struct App {
+    doc: Document,
+    path: String,
+    /// Номера записей, прошедших отбор
+    visible: Vec<usize>,
+    filter: Option<String>,
+    table: TableState,
+    input: Input,
+    line: String,
+    message: Option<String>,
+}
+
+impl App {
+    fn new(doc: Document, path: String) -> Self {
+        let visible = (0..doc.records.len()).collect();
+        let mut table = TableState::default();
+        table.select(Some(0));
+        Self {
+            doc,
+            path,
+            visible,
+            filter: None,
+            table,
+            input: Input::None,
+            line: String::new(),
+            message: None,
+        }
+    }
+
+    fn selected(&self) -> Option<&Record> {
+        let idx = *self.visible.get(self.table.selected()?)?;
+        self.doc.records.get(idx)
+    }
+
+    fn apply_filter(&mut self, expr: &str) {
+        if expr.trim().is_empty() {
+            self.visible = (0..self.doc.records.len()).collect();
+            self.filter = None;
+            self.table.select(Some(0));
+            return;
+        }
+        match expr.parse::<TxFilter>() {
+            Ok(filter) => {
+                self.visible = (0..self.doc.records.len())
+                    .filter(|idx| filter.matches_tx(&self.doc.records[*idx].tx))
+                    .collect();
+                self.filter = Some(expr.to_owned());
+                self.table.select(Some(0));
+            }
+            Err(e) => self.message = Some(e.to_string()),
+        }
+    }
+
+    fn jump(&mut self, tx_id: &str) {
+        let Ok(tx_id) = tx_id.trim().parse::<u64>() else {
+            self.message = Some(format!("Некорректный tx_id: {tx_id}"));
+            return;
+        };
+        let pos = self
+            .visible
+            .iter()
+            .position(|idx| self.doc.records[*idx].tx.tx_id == tx_id);
+        match pos {
+            Some(pos) => self.table.select(Some(pos)),
+            None => self.message = Some(format!("Транзакция {tx_id} не найдена")),
+        }
+    }
+
+    fn move_by(&mut self, delta: isize) {
+        if self.visible.is_empty() {
+            return;
+        }
+        let current = self.table.selected().unwrap_or(0) as isize;
+        let last = self.visible.len() as isize - 1;
+        self.table
+            .select(Some((current + delta).clamp(0, last) as usize));
+    }
+
+    /// Обработка клавиши. Возвращает false для выхода
+    fn on_key(&mut self, code: KeyCode) -> bool {
+        if self.input != Input::None {
+            match code {
+                KeyCode::Esc => self.input = Input::None,
+                KeyCode::Enter => {
+                    let line = std::mem::take(&mut self.line);
+                    match self.input {
+                        Input::Filter => self.apply_filter(&line),
+                        _ => self.jump(&line),
+                    }
+                    self.input = Input::None;
+                }
+                KeyCode::Backspace => {
+                    self.line.pop();
+                }
+                KeyCode::Char(ch) => self.line.push(ch),
+                _ => {}
+            }
+            return true;
+        }
+        self.message = None;
+        match code {
+            KeyCode::Char('q') | KeyCode::Esc => return false,
+            KeyCode::Down | KeyCode::Char('j') => self.move_by(1),
+            KeyCode::Up | KeyCode::Char('k') => self.move_by(-1),
+            KeyCode::PageDown => self.move_by(20),
+            KeyCode::PageUp => self.move_by(-20),
+            KeyCode::Home | KeyCode::Char('g') => self.table.select(Some(0)),
+            KeyCode::End | KeyCode::Char('G') => self.move_by(isize::MAX / 2),
+            KeyCode::Char('/') => {
+                self.input = Input::Filter;
+                self.line = self.filter.clone().unwrap_or_default();
+            }
+            KeyCode::Char('#') => {
+                self.input = Input::Jump;
+                self.line.clear();
+            }
+            _ => {}
+        }
+        true
+    }
+
+    fn draw(&mut self, frame: &mut ratatui::Frame) {
+        let [main, status] =
+            Layout::vertical([Constraint::Min(3), Constraint::Length(1)]).areas(frame.area());
+        let [table_area, raw_area] =
+            Layout::horizontal([Constraint::Percentage(60), Constraint::Percentage(40)])
+                .areas(main);
+
+        let header = Row::new(
+            ["RECORD", "TX_ID"]
+                .into_iter()
+                .chain(FIELDS.iter().map(Field::name)),
+        )
+        .style(Style::new().add_modifier(Modifier::BOLD));
+        let rows = self.visible.iter().map(|idx| {
+            let record = &self.doc.records[*idx];
+            Row::new(
+                [record.record.to_string(), record.tx.tx_id.to_string()]
+                    .into_iter()
+                    .chain(FIELDS.iter().map(|field| field.value(&record.tx))),
+            )
+        });
+        let widths = [
+            Constraint::Length(8),
+            Constraint::Length(10),
+            Constraint::Length(10),
+            Constraint::Length(12),
+            Constraint::Length(12),
+            Constraint::Length(10),
+            Constraint::Length(13),
+            Constraint::Length(7),
+            Constraint::Min(10),
+        ];
+        let table = Table::new(rows, widths)
+            .header(header)
+            .block(Block::new().borders(Borders::ALL).title(self.path.as_str()))
+            .row_highlight_style(Style::new().add_modifier(Modifier::REVERSED));
+        frame.render_stateful_widget(table, table_area, &mut self.table);
+
+        let mut raw: Vec<Line> = Vec::new();
+        if let Some(record) = self.selected() {
+            raw.push(Line::from(format!(
+                "Байты {}..{}",
+                record.start, record.end
+            )));
+            if record.skipped > 0 {
+                raw.push(Line::from(format!(
+                    "Перед записью пропущено записей с ошибками: {}",
+                    record.skipped
+                )));
+            }
+            raw.extend(self.doc.raw_lines(record).into_iter().map(Line::from));
+        }
+        let raw = Paragraph::new(raw)
+            .block(Block::new().borders(Borders::ALL).title("Исходные данные"))
+            .wrap(Wrap { trim: false });
+        frame.render_widget(raw, raw_area);
+
+        let status_line = match (&self.input, &self.message) {
+            (Input::Filter, _) => format!("Условие отбора: {}", self.line),
+            (Input::Jump, _) => format!("Перейти к tx_id: {}", self.line),
+            (Input::None, Some(message)) => message.clone(),
+            (Input::None, None) => format!(
+                "{} | записей: {} | показано: {} | ошибок: {}{}",
+                self.doc.fin_format.name(),
+                self.doc.records.len(),
+                self.visible.len(),
+                self.doc.errors.len(),
+                self.filter
+                    .as_ref()
+                    .map(|filter| format!(" | отбор: {filter}"))
+                    .unwrap_or_default()
+            ),
+        };
+        frame.render_widget(Paragraph::new(status_line), status);
+    }
+}
+
+/// Выводимые поля транзакции после TX_ID
+const FIELDS: [Field; 7] = [
+    Field::TxType,
+    Field::FromUserId,
+    Field::ToUserId,
+    Field::Amount,
+    Field::Timestamp,
+    Field::Status,
+    Field::Description,
+];
+
+fn main() -> ExitCode {
+    let args = Args::parse();
+    let config = match Config::load(args.config.as_deref()) {
+        Ok(val) => val,
+        Err(e) => {
+            eprintln!("Невозможно прочитать настройки: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let doc = match Document::load(&args.input_file, args.format, &config.input) {
+        Ok(val) => val,
+        Err(e) => {
+            eprintln!("Невозможно прочитать файл: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let mut app = App::new(doc, args.input_file);
+    let terminal = ratatui::init();
+    let res = run(terminal, &mut app);
+    ratatui::restore();
+    if let Err(e) = res {
+        eprintln!("Ошибка терминала: {e}");
+        return ExitCode::FAILURE;
+    }
+    if !app.doc.errors.is_empty() {
+        eprint!("{}", app.doc.errors);
+    }
+    ExitCode::SUCCESS
+}
+
+fn run(mut terminal: DefaultTerminal, app: &mut App) -> io::Result<()> {
+    loop {
+        terminal.draw(|frame| app.draw(frame))?;
+        if let Event::Key(key) = event::read()?
+            && key.kind == KeyEventKind::Press
+            && !app.on_key(key.code)
+        {
+            return Ok(());
+        }
+    }
+}
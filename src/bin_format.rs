@@ -1,10 +1,165 @@
-use super::error::ParsError;
+use super::constants::{
+    AMOUNT, CNT_VALUES, DESCRIPTION, FROM_USER_ID, HEADER_VALUES, MAGIC, MERKLE_MAGIC,
+    SCHEMA_MAGIC, STATUS, TIMESTAMP, TO_USER_ID, TX_ID, TX_TYPE,
+};
+use super::error::{ErrorPosition, ParsError};
+use super::merkle::{MerkleBuilder, MerkleFooter};
+use super::options::{BinVersion, ReaderOptions, WriterOptions};
 use super::transaction::*;
-use super::utils::remove_quotes;
-use chrono::DateTime;
-use std::io::{BufReader, Read, Write};
+use super::utils::{
+    CountingReader, CountingWriter, check_timestamp, invalid_enum_value, parse_description,
+    remove_quotes, timestamp_from_unit, timestamp_to_unit,
+};
+use super::warning::{Warning, WarningSink};
+use std::io::{self, BufReader, BufWriter, IoSlice, Read, Write};
+use std::ops::RangeBounds;
 
-const MAGIC: u32 = 0x5950424E;
+const MAGIC_LEN: u64 = std::mem::size_of::<u32>() as u64;
+/// Длина фиксированной части записи: от сигнатуры до длины описания включительно
+const HEAD_LEN: usize = 54;
+/// Версия формата в заголовке схемы
+const SCHEMA_VERSION: u16 = 2;
+/// Длина начала заголовка схемы: сигнатура, версия и количество полей
+const SCHEMA_HEAD_LEN: usize = 8;
+
+/// Тип значения поля в схеме bin версии 2. Числа записываются в big-endian,
+/// байтовая строка — длиной u32 и байтами
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+enum FieldType {
+    U8 = 1,
+    U16 = 2,
+    U32 = 3,
+    U64 = 4,
+    I64 = 5,
+    Bytes = 6,
+}
+
+impl FieldType {
+    fn from_code(code: u8) -> Option<Self> {
+        [
+            Self::U8,
+            Self::U16,
+            Self::U32,
+            Self::U64,
+            Self::I64,
+            Self::Bytes,
+        ]
+        .into_iter()
+        .find(|field_type| *field_type as u8 == code)
+    }
+}
+
+/// Поля записи версии 1 в порядке записи и их типы. Индексы совпадают с HEADER_VALUES
+const DEFAULT_FIELDS: [(&str, FieldType); CNT_VALUES] = [
+    (TX_ID, FieldType::U64),
+    (TX_TYPE, FieldType::U8),
+    (FROM_USER_ID, FieldType::U64),
+    (TO_USER_ID, FieldType::U64),
+    (AMOUNT, FieldType::I64),
+    (TIMESTAMP, FieldType::U64),
+    (STATUS, FieldType::U8),
+    (DESCRIPTION, FieldType::Bytes),
+];
+
+/// Схема полей из заголовка файла bin версии 2
+#[derive(Debug)]
+struct Schema {
+    /// Поля в порядке записи: индекс известного поля в HEADER_VALUES
+    /// (None для неизвестных полей) и тип
+    fields: Vec<(Option<usize>, FieldType)>,
+    /// Порядок и типы полей совпадают с версией 1: записи читаются без разбора по схеме
+    default_layout: bool,
+    /// Исходные байты заголовка для частей параллельного разбора
+    #[cfg(feature = "parallel")]
+    header: Vec<u8>,
+}
+
+impl Schema {
+    /// Заголовок схемы с полями fields
+    fn encode(fields: &[(&str, FieldType)]) -> Vec<u8> {
+        let mut res = SCHEMA_MAGIC.to_be_bytes().to_vec();
+        res.extend_from_slice(&SCHEMA_VERSION.to_be_bytes());
+        res.extend_from_slice(&(fields.len() as u16).to_be_bytes());
+        for (name, field_type) in fields {
+            res.push(name.len() as u8);
+            res.extend_from_slice(name.as_bytes());
+            res.push(*field_type as u8);
+        }
+        res
+    }
+
+    /// Чтение заголовка, сигнатура которого уже прочитана
+    fn read<In: Read>(input: &mut In) -> Result<Self, ParsError> {
+        let mut header = SCHEMA_MAGIC.to_be_bytes().to_vec();
+        let mut read = |len: usize| -> Result<Vec<u8>, ParsError> {
+            let mut buf = vec![0; len];
+            input.read_exact(&mut buf).map_err(|e| match e.kind() {
+                io::ErrorKind::UnexpectedEof => bad_schema("заголовок обрезан".to_owned()),
+                _ => ParsError::IoError(e),
+            })?;
+            header.extend_from_slice(&buf);
+            Ok(buf)
+        };
+        let head = read(SCHEMA_HEAD_LEN - MAGIC_LEN as usize)?;
+        let version = u16::from_be_bytes([head[0], head[1]]);
+        if version != SCHEMA_VERSION {
+            return Err(bad_schema(format!("неподдерживаемая версия {version}")));
+        }
+        let count = u16::from_be_bytes([head[2], head[3]]);
+        let mut fields = Vec::with_capacity(count as usize);
+        let mut seen = [false; CNT_VALUES];
+        for _ in 0..count {
+            let name_len = read(1)?[0] as usize;
+            let name = String::from_utf8(read(name_len)?).map_err(|e| e.utf8_error())?;
+            let code = read(1)?[0];
+            let field_type = FieldType::from_code(code)
+                .ok_or_else(|| bad_schema(format!("неизвестный тип {code} поля {name}")))?;
+            let known = HEADER_VALUES.iter().position(|val| *val == name);
+            if let Some(idx) = known {
+                if std::mem::replace(&mut seen[idx], true) {
+                    return Err(bad_schema(format!("поле {name} указано повторно")));
+                }
+                if field_type != DEFAULT_FIELDS[idx].1 {
+                    return Err(bad_schema(format!("неверный тип {code} поля {name}")));
+                }
+            }
+            fields.push((known, field_type));
+        }
+        if let Some(idx) = seen.iter().position(|seen| !seen) {
+            return Err(ParsError::MissingField {
+                name: HEADER_VALUES[idx].to_owned(),
+            });
+        }
+        let default_layout = fields.len() == CNT_VALUES
+            && fields
+                .iter()
+                .enumerate()
+                .all(|(idx, field)| *field == (Some(idx), DEFAULT_FIELDS[idx].1));
+        Ok(Self {
+            fields,
+            default_layout,
+            #[cfg(feature = "parallel")]
+            header,
+        })
+    }
+}
+
+fn bad_schema(reason: String) -> ParsError {
+    ParsError::BadSchema { reason }
+}
+
+/// Запись всех срезов в поток через write_vectored с дозаписью при неполной записи
+fn write_all_vectored<T: Write>(stream: &mut T, mut bufs: &mut [IoSlice<'_>]) -> io::Result<()> {
+    while !bufs.is_empty() {
+        match stream.write_vectored(bufs) {
+            Ok(0) => return Err(io::Error::from(io::ErrorKind::WriteZero)),
+            Ok(cnt) => IoSlice::advance_slices(&mut bufs, cnt),
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(())
+}
 
 fn read_u8<T: Read>(stream: &mut T) -> Result<u8, ParsError> {
     let mut buf = [0u8; std::mem::size_of::<u8>()];
@@ -13,6 +168,13 @@ fn read_u8<T: Read>(stream: &mut T) -> Result<u8, ParsError> {
     Ok(res)
 }
 
+fn read_u16<T: Read>(stream: &mut T) -> Result<u16, ParsError> {
+    let mut buf = [0u8; std::mem::size_of::<u16>()];
+    stream.read_exact(&mut buf)?;
+    let res = u16::from_be_bytes(buf);
+    Ok(res)
+}
+
 fn read_u32<T: Read>(stream: &mut T) -> Result<u32, ParsError> {
     let mut buf = [0u8; std::mem::size_of::<u32>()];
     stream.read_exact(&mut buf)?;
@@ -34,7 +196,59 @@ fn read_i64<T: Read>(stream: &mut T) -> Result<i64, ParsError> {
     Ok(res)
 }
 
-#[derive(Eq, PartialEq, Debug)]
+/// Запись одной транзакции записью bin версии 1 с настройками по умолчанию
+pub(crate) fn write_record<Out: Write>(out: &mut Out, tx: &Transaction) -> Result<(), ParsError> {
+    BinTxRecord::from_transaction(tx, &WriterOptions::default()).serialize(out)
+}
+
+/// Чтение одной записи bin версии 1 с настройками по умолчанию.
+/// Конец потока внутри записи означает обрезанную запись
+pub(crate) fn read_record<In: Read>(input: &mut In) -> Result<Transaction, ParsError> {
+    let options = ReaderOptions::default();
+    let mut record = BinTxRecord::default();
+    read_u32(input)
+        .and_then(|magic| record.deserialize_body(magic, input, &options))
+        .map_err(|e| match e {
+            ParsError::EndOfStream => ParsError::TruncatedRecord,
+            e => e,
+        })?;
+    Ok(record.to_transaction_ref(&options)?.to_transaction())
+}
+
+/// Длина первой целой записи в начале буфера или None, если запись еще не дочитана.
+/// При неверной сигнатуре возвращается длина всего буфера, чтобы ошибку обработал читатель
+#[cfg(feature = "async")]
+pub fn frame_len(buf: &[u8]) -> Option<usize> {
+    let header_len = MAGIC_LEN as usize + std::mem::size_of::<u32>();
+    let header = buf.get(..header_len)?;
+    let magic = u32::from_be_bytes([header[0], header[1], header[2], header[3]]);
+    if magic == SCHEMA_MAGIC {
+        return schema_header_len(buf);
+    }
+    if magic == MERKLE_MAGIC {
+        let len = super::merkle::FOOTER_LEN;
+        return (buf.len() >= len).then_some(len);
+    }
+    if magic != MAGIC {
+        return Some(buf.len());
+    }
+    let record_size = u32::from_be_bytes([header[4], header[5], header[6], header[7]]) as usize;
+    let len = header_len + record_size;
+    (buf.len() >= len).then_some(len)
+}
+
+/// Длина заголовка схемы в начале буфера или None, если заголовок еще не дочитан
+#[cfg(feature = "async")]
+fn schema_header_len(buf: &[u8]) -> Option<usize> {
+    let count = u16::from_be_bytes([*buf.get(6)?, *buf.get(7)?]);
+    let mut len = SCHEMA_HEAD_LEN;
+    for _ in 0..count {
+        len += 1 + *buf.get(len)? as usize + 1;
+    }
+    (buf.len() >= len).then_some(len)
+}
+
+#[derive(Eq, PartialEq, Debug, Default)]
 struct BinTxRecord {
     magic: u32,
     record_size: u32,
@@ -50,27 +264,50 @@ struct BinTxRecord {
 }
 
 impl BinTxRecord {
+    /// Запись фиксированной части и описания одним векторным вызовом без копирования описания
     fn serialize<Out: Write>(&self, out: &mut Out) -> Result<(), ParsError> {
-        let mut buf = Vec::new();
-        buf.extend_from_slice(&self.magic.to_be_bytes());
-        buf.extend_from_slice(&self.record_size.to_be_bytes());
-        buf.extend_from_slice(&self.tx_id.to_be_bytes());
-        buf.extend_from_slice(&self.tx_type.to_be_bytes());
-        buf.extend_from_slice(&self.from_user_id.to_be_bytes());
-        buf.extend_from_slice(&self.to_user_id.to_be_bytes());
-        buf.extend_from_slice(&self.amount.to_be_bytes());
-        buf.extend_from_slice(&self.timestamp.to_be_bytes());
-        buf.extend_from_slice(&self.status.to_be_bytes());
-        buf.extend_from_slice(&self.desc_len.to_be_bytes());
-        buf.extend_from_slice(self.description.as_bytes());
-        out.write_all(&buf)?;
+        let mut head = [0u8; HEAD_LEN];
+        let mut pos = 0;
+        for field in [
+            &self.magic.to_be_bytes()[..],
+            &self.record_size.to_be_bytes(),
+            &self.tx_id.to_be_bytes(),
+            &self.tx_type.to_be_bytes(),
+            &self.from_user_id.to_be_bytes(),
+            &self.to_user_id.to_be_bytes(),
+            &self.amount.to_be_bytes(),
+            &self.timestamp.to_be_bytes(),
+            &self.status.to_be_bytes(),
+            &self.desc_len.to_be_bytes(),
+        ] {
+            head[pos..pos + field.len()].copy_from_slice(field);
+            pos += field.len();
+        }
+        let mut bufs = [
+            IoSlice::new(&head),
+            IoSlice::new(self.description.as_bytes()),
+        ];
+        write_all_vectored(out, &mut bufs)?;
         Ok(())
     }
 
-    fn deserialize<In: Read>(input: &mut BufReader<In>) -> Result<Self, ParsError> {
+    #[cfg(test)]
+    fn deserialize<In: Read>(input: &mut In, options: &ReaderOptions) -> Result<Self, ParsError> {
         let magic = read_u32(input)?;
+        let mut record = Self::default();
+        record.deserialize_body(magic, input, options)?;
+        Ok(record)
+    }
+
+    /// Чтение записи, сигнатура которой уже прочитана. Память под описание переиспользуется
+    fn deserialize_body<In: Read>(
+        &mut self,
+        magic: u32,
+        input: &mut In,
+        options: &ReaderOptions,
+    ) -> Result<(), ParsError> {
         if magic != MAGIC {
-            return Err(ParsError::WrongFormat(format! {"Неверный magic: {magic}"}));
+            return Err(ParsError::BadMagic { found: magic });
         }
         let record_size = read_u32(input)?;
 
@@ -82,12 +319,20 @@ impl BinTxRecord {
         let timestamp = read_u64(input)?;
         let status = read_u8(input)?;
         let desc_len = read_u32(input)?;
+        if desc_len as usize > options.max_description_len.saturating_add(2) {
+            return Err(ParsError::DescriptionTooLong {
+                len: desc_len as usize,
+                max: options.max_description_len,
+            });
+        }
 
-        let mut desc_buf = vec![0u8; desc_len as usize];
+        let mut desc_buf = std::mem::take(&mut self.description).into_bytes();
+        desc_buf.clear();
+        desc_buf.resize(desc_len as usize, 0);
         input.read_exact(&mut desc_buf)?;
-        let description = std::str::from_utf8(&desc_buf)?;
+        let description = String::from_utf8(desc_buf).map_err(|e| e.utf8_error())?;
 
-        Ok(Self {
+        *self = Self {
             magic,
             record_size,
             tx_id,
@@ -98,20 +343,99 @@ impl BinTxRecord {
             timestamp,
             status,
             desc_len,
-            description: description.to_owned(),
-        })
+            description,
+        };
+        Ok(())
     }
 
-    fn to_transaction(&self) -> Result<Transaction, ParsError> {
+    /// Чтение записи по схеме файла: известные поля берутся по именам, неизвестные
+    /// и байты после последнего поля схемы пропускаются
+    fn deserialize_schema<In: Read>(
+        &mut self,
+        magic: u32,
+        input: &mut In,
+        schema: &Schema,
+        options: &ReaderOptions,
+    ) -> Result<(), ParsError> {
+        if magic != MAGIC {
+            return Err(ParsError::BadMagic { found: magic });
+        }
+        let record_size = read_u32(input)?;
+        let mut body = input.take(record_size as u64);
+
+        let mut values = [0u64; CNT_VALUES];
+        let mut desc_buf = std::mem::take(&mut self.description).into_bytes();
+        desc_buf.clear();
+        for &(known, field_type) in &schema.fields {
+            let value = match field_type {
+                FieldType::U8 => read_u8(&mut body)? as u64,
+                FieldType::U16 => read_u16(&mut body)? as u64,
+                FieldType::U32 => read_u32(&mut body)? as u64,
+                FieldType::U64 => read_u64(&mut body)?,
+                FieldType::I64 => read_i64(&mut body)? as u64,
+                FieldType::Bytes => {
+                    let len = read_u32(&mut body)?;
+                    if known.is_some() {
+                        if len as usize > options.max_description_len.saturating_add(2) {
+                            return Err(ParsError::DescriptionTooLong {
+                                len: len as usize,
+                                max: options.max_description_len,
+                            });
+                        }
+                        desc_buf.resize(len as usize, 0);
+                        body.read_exact(&mut desc_buf)?;
+                    } else {
+                        let len = len as u64;
+                        if io::copy(&mut (&mut body).take(len), &mut io::sink())? != len {
+                            return Err(ParsError::EndOfStream);
+                        }
+                    }
+                    len as u64
+                }
+            };
+            if let Some(idx) = known {
+                values[idx] = value;
+            }
+        }
+        io::copy(&mut body, &mut io::sink())?;
+        if body.limit() > 0 {
+            return Err(ParsError::EndOfStream);
+        }
+        let description = String::from_utf8(desc_buf).map_err(|e| e.utf8_error())?;
+
+        let [
+            tx_id,
+            tx_type,
+            from_user_id,
+            to_user_id,
+            amount,
+            timestamp,
+            status,
+            desc_len,
+        ] = values;
+        *self = Self {
+            magic,
+            record_size,
+            tx_id,
+            tx_type: tx_type as u8,
+            from_user_id,
+            to_user_id,
+            amount: amount as i64,
+            timestamp,
+            status: status as u8,
+            desc_len: desc_len as u32,
+            description,
+        };
+        Ok(())
+    }
+
+    fn to_transaction_ref(&self, options: &ReaderOptions) -> Result<TransactionRef<'_>, ParsError> {
         let tx_type = match self.tx_type {
             0 => TxType::Deposit,
             1 => TxType::Transfer,
             2 => TxType::Withdrawal,
             _ => {
-                return Err(ParsError::WrongFormat(format!(
-                    "Wrong tx_type: {}",
-                    self.tx_type
-                )));
+                return Err(invalid_enum_value(TX_TYPE, self.tx_type));
             }
         };
         let status = match self.status {
@@ -119,30 +443,14 @@ impl BinTxRecord {
             1 => TxStatus::Failure,
             2 => TxStatus::Pending,
             _ => {
-                return Err(ParsError::WrongFormat(format!(
-                    "Wrong status: {}",
-                    self.status
-                )));
+                return Err(invalid_enum_value(STATUS, self.status));
             }
         };
 
-        let timestamp = if let Some(val) = DateTime::from_timestamp_millis(self.timestamp as i64) {
-            val
-        } else {
-            return Err(ParsError::WrongFormat(format!(
-                "Wrong timestamp: {}",
-                self.timestamp
-            )));
-        };
-
-        if !(self.description.starts_with('"') && self.description.ends_with('"')) {
-            return Err(ParsError::WrongFormat(format!(
-                "Wrong description: {}",
-                self.description
-            )));
-        }
+        let timestamp = timestamp_from_unit(self.timestamp, options.timestamp_unit)?;
+        let description = parse_description(&self.description, options)?;
 
-        Ok(Transaction {
+        Ok(TransactionRef {
             tx_id: self.tx_id,
             from_user_id: self.from_user_id,
             tx_type,
@@ -150,11 +458,34 @@ impl BinTxRecord {
             amount: self.amount,
             timestamp,
             status,
-            description: remove_quotes(&self.description),
+            description,
         })
     }
 
-    fn from_transaction(tx: &Transaction) -> Self {
+    /// Время записи входит в интервал чтения. Запись с неверным временем
+    /// не пропускается, чтобы ошибку вернул ее разбор
+    fn in_time_range(&self, options: &ReaderOptions) -> bool {
+        match &options.time_range {
+            Some(range) => timestamp_from_unit(self.timestamp, options.timestamp_unit)
+                .map_or(true, |timestamp| range.contains(&timestamp)),
+            None => true,
+        }
+    }
+
+    /// Проверка полей без построения транзакции: те же ошибки, что у [Self::to_transaction_ref]
+    fn validate(&self, options: &ReaderOptions) -> Result<(), ParsError> {
+        if self.tx_type > 2 {
+            return Err(invalid_enum_value(TX_TYPE, self.tx_type));
+        }
+        if self.status > 2 {
+            return Err(invalid_enum_value(STATUS, self.status));
+        }
+        check_timestamp(self.timestamp, options.timestamp_unit)?;
+        parse_description(&self.description, options)?;
+        Ok(())
+    }
+
+    fn from_transaction(tx: &Transaction, options: &WriterOptions) -> Self {
         let tx_type = match tx.tx_type {
             TxType::Deposit => 0,
             TxType::Transfer => 1,
@@ -167,7 +498,7 @@ impl BinTxRecord {
             TxStatus::Pending => 2,
         } as u8;
 
-        let timestamp = tx.timestamp.timestamp_millis() as u64;
+        let timestamp = timestamp_to_unit(&tx.timestamp, options.timestamp_unit);
 
         let description = format!("\"{}\"", tx.description);
         let desc_len = description.len() as u32;
@@ -197,51 +528,494 @@ impl BinTxRecord {
 }
 
 pub struct BinTxReader<In: Read> {
-    stream: BufReader<In>,
+    stream: CountingReader<BufReader<In>>,
+    options: ReaderOptions,
+    records: u64,
+    warnings: WarningSink,
+    // Сигнатура следующей записи уже прочитана при поиске границы записи
+    magic_read: bool,
+    // Ошибка возникла посреди записи, и для продолжения нужен поиск следующей сигнатуры
+    resync_needed: bool,
+    // Запись переиспользуется между вызовами, чтобы не выделять память под описание
+    record: BinTxRecord,
+    // Начало потока проверено на заголовок схемы
+    header_read: bool,
+    // Схема полей файла версии 2
+    schema: Option<Schema>,
+    // Последний прочитанный блок корня дерева Меркла
+    merkle_footer: Option<MerkleFooter>,
+    // Смещение первой записи после заголовка схемы
+    data_start: u64,
+    // Смещение начала последней прочитанной записи
+    last_record_start: u64,
 }
 
 impl<In: Read> BinTxReader<In> {
-    pub fn new(stream: In) -> Result<Self, ParsError> {
+    pub fn with_options(stream: In, options: ReaderOptions) -> Result<Self, ParsError> {
         Ok(Self {
-            stream: BufReader::new(stream),
+            stream: CountingReader::new(BufReader::new(stream)),
+            warnings: WarningSink::new(&options),
+            options,
+            records: 0,
+            magic_read: false,
+            resync_needed: false,
+            record: BinTxRecord::default(),
+            header_read: false,
+            schema: None,
+            merkle_footer: None,
+            data_start: 0,
+            last_record_start: 0,
         })
     }
 
-    pub fn read_transaction(&mut self) -> Result<Option<Transaction>, ParsError> {
-        let record = match BinTxRecord::deserialize(&mut self.stream) {
+    /// Чтение заголовка схемы в начале потока. Файл версии 1 начинается сразу
+    /// с сигнатуры первой записи
+    fn read_header(&mut self) -> Result<(), ParsError> {
+        let magic = match read_u32(&mut self.stream) {
             Ok(val) => val,
+            Err(e) => return Err(self.truncated(e, 0)),
+        };
+        // Пустой поток проверяется снова при следующем чтении: данные потока,
+        // наполняемого по частям, могут появиться позже
+        self.header_read = true;
+        match magic {
+            MAGIC => self.magic_read = true,
+            // Файл без записей состоит из одного блока корня
+            MERKLE_MAGIC => self.read_merkle_footer()?,
+            SCHEMA_MAGIC => {
+                self.schema = Some(Schema::read(&mut self.stream)?);
+                self.data_start = self.stream.count();
+                // Заголовок не относится к первой записи
+                self.stream.clear_captured();
+            }
+            found => {
+                self.resync_needed = true;
+                return Err(ParsError::BadMagic { found });
+            }
+        }
+        Ok(())
+    }
+
+    /// Проверка заголовка перед первой записью. Пустой поток ошибкой не считается
+    fn ensure_header(&mut self) -> Result<(), ParsError> {
+        match self.header_read {
+            true => Ok(()),
+            false => match self.read_header() {
+                Ok(()) | Err(ParsError::EndOfStream) => Ok(()),
+                Err(e) => Err(e.at(self.error_position(0))),
+            },
+        }
+    }
+
+    /// Исходные байты заголовка схемы для частей параллельного разбора.
+    /// Для файлов версии 1 пусто
+    #[cfg(feature = "parallel")]
+    pub fn schema_header(&mut self) -> Result<Vec<u8>, ParsError> {
+        self.ensure_header()?;
+        Ok(self
+            .schema
+            .as_ref()
+            .map(|schema| schema.header.clone())
+            .unwrap_or_default())
+    }
+
+    /// Длина заголовка схемы файла версии 2, для версии 1 ноль
+    pub fn header_len(&mut self) -> Result<u64, ParsError> {
+        self.ensure_header()?;
+        Ok(self.data_start)
+    }
+
+    pub fn bytes_read(&self) -> u64 {
+        self.stream.count()
+    }
+
+    /// Смещение начала последней прочитанной записи
+    pub fn last_record_start(&self) -> u64 {
+        self.last_record_start
+    }
+
+    /// Чтение блока корня дерева Меркла после его сигнатуры. Блок записью не считается,
+    /// чтение продолжается со следующей записи, если файлы записаны подряд
+    fn read_merkle_footer(&mut self) -> Result<(), ParsError> {
+        let footer = MerkleFooter::read_body(&mut self.stream).map_err(|e| match e {
+            ParsError::EndOfStream => ParsError::TruncatedRecord,
+            e => e,
+        })?;
+        self.merkle_footer = Some(footer);
+        Ok(())
+    }
+
+    /// Последний прочитанный блок корня дерева Меркла
+    pub fn merkle_footer(&self) -> Option<&MerkleFooter> {
+        self.merkle_footer.as_ref()
+    }
+
+    /// Сохранение исходных байт записей для [Self::take_rejected]
+    pub fn set_capture_raw(&mut self, capture: bool) {
+        self.stream.set_capture(capture);
+    }
+
+    /// Очистка исходных байт после успешно прочитанной записи
+    pub fn clear_captured(&mut self) {
+        self.stream.clear_captured();
+    }
+
+    /// Смещение начала и исходные байты записи, пропущенной через resync.
+    /// Найденная resync сигнатура следующей записи остается в начале ее байт
+    pub fn take_rejected(&mut self) -> (u64, Vec<u8>) {
+        let magic_len = if self.magic_read {
+            MAGIC_LEN as usize
+        } else {
+            0
+        };
+        let end = self.stream.count();
+        let Some(captured) = self.stream.captured() else {
+            return (end, Vec::new());
+        };
+        let start = end - captured.len() as u64;
+        let next = captured.split_off(captured.len().saturating_sub(magic_len));
+        (start, std::mem::replace(captured, next))
+    }
+
+    #[cfg(feature = "async")]
+    pub fn get_mut(&mut self) -> &mut In {
+        self.stream.get_mut().get_mut()
+    }
+
+    fn error_position(&self, record_start: u64) -> ErrorPosition {
+        ErrorPosition {
+            record: self.records,
+            byte: record_start,
+            line: None,
+            column: None,
+        }
+    }
+
+    fn record_start(&self) -> u64 {
+        let magic_len = if self.magic_read { MAGIC_LEN } else { 0 };
+        self.stream.count() - magic_len
+    }
+
+    fn read_magic(&mut self) -> Result<u32, ParsError> {
+        if std::mem::take(&mut self.magic_read) {
+            Ok(MAGIC)
+        } else {
+            read_u32(&mut self.stream)
+        }
+    }
+
+    /// Конец потока внутри записи означает обрезанную запись
+    fn truncated(&self, e: ParsError, record_start: u64) -> ParsError {
+        match e {
+            ParsError::EndOfStream if self.stream.count() != record_start => {
+                ParsError::TruncatedRecord
+            }
+            _ => e,
+        }
+    }
+
+    /// Пропуск записи без разбора тела: используется RECORD_SIZE из заголовка
+    pub fn skip_record(&mut self) -> Result<bool, ParsError> {
+        // Для отбора по времени тело записи все же разбирается
+        if self.options.time_range.is_some() {
+            return self.read_record().map(|res| res.is_some());
+        }
+        self.copy_record(&mut std::io::sink())
+    }
+
+    /// Чтение исходных байт записи целиком, запись дописывается в out.
+    /// Возвращает false, если поток закончился
+    #[cfg(feature = "parallel")]
+    pub fn read_raw_record(&mut self, out: &mut Vec<u8>) -> Result<bool, ParsError> {
+        self.copy_record(out)
+    }
+
+    #[cfg(feature = "parallel")]
+    pub fn options(&self) -> &ReaderOptions {
+        &self.options
+    }
+
+    /// Копирование записи в out без разбора тела
+    fn copy_record<Out: Write>(&mut self, out: &mut Out) -> Result<bool, ParsError> {
+        self.ensure_header()?;
+        let record_start = self.record_start();
+        let res = self
+            .copy_record_body(out)
+            .map_err(|e| self.truncated(e, record_start));
+        match res {
+            Ok(true) => {
+                self.records += 1;
+                self.last_record_start = record_start;
+                Ok(true)
+            }
+            Ok(false) | Err(ParsError::EndOfStream) => Ok(false),
             Err(e) => {
-                if let ParsError::EndOfStream = e {
-                    return Ok(None);
-                } else {
-                    return Err(ParsError::from(e));
+                self.resync_needed = true;
+                Err(e.at(self.error_position(record_start)))
+            }
+        }
+    }
+
+    fn copy_record_body<Out: Write>(&mut self, out: &mut Out) -> Result<bool, ParsError> {
+        let magic = match self.read_magic() {
+            Ok(val) => val,
+            Err(ParsError::EndOfStream) => return Ok(false),
+            Err(e) => return Err(e),
+        };
+        if magic == MERKLE_MAGIC {
+            self.read_merkle_footer()?;
+            return self.copy_record_body(out);
+        }
+        if magic != MAGIC {
+            return Err(ParsError::BadMagic { found: magic });
+        }
+        let record_size = read_u32(&mut self.stream)?;
+        out.write_all(&magic.to_be_bytes())?;
+        out.write_all(&record_size.to_be_bytes())?;
+        let record_size = record_size as u64;
+        let copied = std::io::copy(&mut (&mut self.stream).take(record_size), out)?;
+        if copied != record_size {
+            return Err(ParsError::EndOfStream);
+        }
+        Ok(true)
+    }
+
+    pub fn take_warnings(&mut self) -> Vec<Warning> {
+        self.warnings.take()
+    }
+
+    /// Переход к следующей записи после ошибки чтения: если ошибка возникла посреди
+    /// записи, поток просматривается до следующей сигнатуры
+    pub fn resync(&mut self) -> Result<(), ParsError> {
+        self.records += 1;
+        if !std::mem::take(&mut self.resync_needed) {
+            return Ok(());
+        }
+        let mut window = 0u32;
+        loop {
+            match read_u8(&mut self.stream) {
+                Ok(byte) => {
+                    window = (window << 8) | byte as u32;
+                    if window == MAGIC {
+                        self.magic_read = true;
+                        return Ok(());
+                    }
                 }
+                Err(ParsError::EndOfStream) => return Ok(()),
+                Err(e) => return Err(e),
             }
+        }
+    }
+
+    pub fn read_transaction(&mut self) -> Result<Option<Transaction>, ParsError> {
+        Ok(self.read_transaction_ref()?.map(Transaction::from))
+    }
+
+    /// Чтение транзакции с описанием, заимствованным из буфера записи
+    pub fn read_transaction_ref(&mut self) -> Result<Option<TransactionRef<'_>>, ParsError> {
+        let Some(record_start) = self.read_record()? else {
+            return Ok(None);
         };
+        let res = self
+            .record
+            .to_transaction_ref(&self.options)
+            .inspect(|tx| {
+                self.warnings
+                    .check_transaction(self.records, tx, &self.record.description)
+            })
+            .map_err(|e| e.at(self.error_position(record_start)))?;
+        self.records += 1;
+        Ok(Some(res))
+    }
 
-        Ok(Some(record.to_transaction()?))
+    /// Проверка записи без построения транзакции
+    pub fn validate_record(&mut self) -> Result<bool, ParsError> {
+        let Some(record_start) = self.read_record()? else {
+            return Ok(false);
+        };
+        self.record
+            .validate(&self.options)
+            .map_err(|e| e.at(self.error_position(record_start)))?;
+        self.records += 1;
+        Ok(true)
+    }
+
+    /// Чтение следующей записи в переиспользуемую запись без проверки значений полей.
+    /// Записи вне интервала времени из настроек пропускаются.
+    /// Возвращает смещение начала записи или None, если поток закончился
+    fn read_record(&mut self) -> Result<Option<u64>, ParsError> {
+        loop {
+            let res = self.read_any_record()?;
+            if res.is_none() || self.record.in_time_range(&self.options) {
+                return Ok(res);
+            }
+        }
+    }
+
+    fn read_any_record(&mut self) -> Result<Option<u64>, ParsError> {
+        self.ensure_header()?;
+        let record_start = self.record_start();
+        let record = match self.read_magic() {
+            Ok(MERKLE_MAGIC) => {
+                if let Err(e) = self.read_merkle_footer() {
+                    return Err(e.at(self.error_position(record_start)));
+                }
+                return self.read_any_record();
+            }
+            res => res,
+        };
+        let res = record
+            .and_then(|magic| match &self.schema {
+                Some(schema) if !schema.default_layout => {
+                    self.record
+                        .deserialize_schema(magic, &mut self.stream, schema, &self.options)
+                }
+                _ => self
+                    .record
+                    .deserialize_body(magic, &mut self.stream, &self.options),
+            })
+            .inspect_err(|_| self.resync_needed = true)
+            .map_err(|e| self.truncated(e, record_start));
+        match res {
+            Ok(()) => {
+                self.last_record_start = record_start;
+                Ok(Some(record_start))
+            }
+            Err(ParsError::EndOfStream) => Ok(None),
+            Err(e) => Err(e.at(self.error_position(record_start))),
+        }
+    }
+
+    /// Описание последней прочитанной транзакции
+    pub fn last_description(&self) -> &str {
+        remove_quotes(&self.record.description)
     }
 }
 
 pub struct BinTxWriter<Out: Write> {
-    stream: Out,
+    stream: CountingWriter<BufWriter<Out>>,
+    options: WriterOptions,
+    buf: Vec<u8>,
+    // Заголовок схемы записан или не нужен (версия 1)
+    header_written: bool,
+    // Дерево Меркла для блока корня в конце файла
+    merkle: Option<MerkleBuilder>,
 }
 
 impl<Out: Write> BinTxWriter<Out> {
-    pub fn new(stream: Out) -> Result<Self, ParsError> {
-        Ok(Self { stream })
+    pub fn with_options(stream: Out, options: WriterOptions) -> Result<Self, ParsError> {
+        Ok(Self {
+            stream: CountingWriter::new(BufWriter::new(stream)),
+            header_written: options.bin_version == BinVersion::V1,
+            merkle: options.merkle.then(MerkleBuilder::new),
+            options,
+            buf: Vec::new(),
+        })
+    }
+
+    /// Запись заголовка схемы версии 2 перед первой записью
+    fn write_header(&mut self) -> Result<(), ParsError> {
+        if !std::mem::replace(&mut self.header_written, true) {
+            self.stream.write_all(&Schema::encode(&DEFAULT_FIELDS))?;
+        }
+        Ok(())
     }
 
     pub fn write_transaction(&mut self, data: &Transaction) -> Result<(), ParsError> {
-        let record = BinTxRecord::from_transaction(&data);
+        self.write_header()?;
+        let record = BinTxRecord::from_transaction(data, &self.options);
         record.serialize(&mut self.stream)?;
+        if let Some(merkle) = &mut self.merkle {
+            merkle.push(data);
+        }
         Ok(())
     }
+
+    /// Запись набора транзакций: записи собираются в буфер и пишутся в поток одним write_all
+    pub fn write_batch(&mut self, txs: &[Transaction]) -> Result<(), ParsError> {
+        self.buf.clear();
+        if !std::mem::replace(&mut self.header_written, true) {
+            self.buf.extend_from_slice(&Schema::encode(&DEFAULT_FIELDS));
+        }
+        for tx in txs {
+            BinTxRecord::from_transaction(tx, &self.options).serialize(&mut self.buf)?;
+        }
+        self.stream.write_all(&self.buf)?;
+        if let Some(merkle) = &mut self.merkle {
+            txs.iter().for_each(|tx| merkle.push(tx));
+        }
+        Ok(())
+    }
+
+    /// Писатель в память с теми же настройками для сериализации части данных.
+    /// Заголовок схемы пишется в исходный поток, а писатель части его не повторяет
+    #[cfg(feature = "parallel")]
+    pub fn chunk_writer(&mut self) -> BinTxWriter<Vec<u8>> {
+        BinTxWriter {
+            stream: CountingWriter::new(BufWriter::new(Vec::new())),
+            options: self.options.clone(),
+            buf: Vec::new(),
+            header_written: true,
+            merkle: None,
+        }
+    }
+
+    /// Запись уже сериализованных данных этого формата после заголовка схемы
+    #[cfg(feature = "parallel")]
+    pub fn write_raw(&mut self, data: &[u8]) -> Result<(), ParsError> {
+        self.write_header()?;
+        self.stream.write_all(data)?;
+        if let Some(merkle) = &mut self.merkle {
+            // Отпечатки считаются по транзакциям, поэтому записи части разбираются заново
+            let options = ReaderOptions {
+                timestamp_unit: self.options.timestamp_unit,
+                ..Default::default()
+            };
+            let mut reader = BinTxReader::with_options(data, options)?;
+            while let Some(tx) = reader.read_transaction_ref()? {
+                merkle.push(tx);
+            }
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "async")]
+    pub fn get_mut(&mut self) -> &mut Out {
+        self.stream.get_mut().get_mut()
+    }
+
+    pub fn flush(&mut self) -> Result<(), ParsError> {
+        self.stream.flush()?;
+        Ok(())
+    }
+
+    /// Завершение записи: заголовок схемы пустого файла и блок корня дерева Меркла
+    pub fn finish(&mut self) -> Result<(), ParsError> {
+        self.write_header()?;
+        if let Some(merkle) = self.merkle.take() {
+            self.stream.write_all(&merkle.footer().encode())?;
+        }
+        self.flush()
+    }
+
+    /// Количество байт, переданных в поток, включая еще не сброшенные из буфера
+    pub fn bytes_written(&self) -> u64 {
+        self.stream.count()
+    }
+
+    pub fn into_inner(self) -> Result<Out, ParsError> {
+        self.stream
+            .into_inner()
+            .into_inner()
+            .map_err(|e| ParsError::IoError(e.into_error()))
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::options::TimestampUnit;
+    use chrono::DateTime;
     use hex_literal::hex;
     use std::io::Cursor;
 
@@ -276,7 +1050,7 @@ mod tests {
             from_user_id: 0,
             to_user_id: 9223372036854775807,
             amount: 100,
-            timestamp: DateTime::from_timestamp_millis(1633036860000 as i64).unwrap(),
+            timestamp: DateTime::from_timestamp_millis(1633036860000).unwrap(),
             status: TxStatus::Failure,
             description: "Record number 1".to_owned(),
         }
@@ -289,7 +1063,7 @@ mod tests {
             from_user_id: 9223372036854775807,
             to_user_id: 9223372036854775807,
             amount: 200,
-            timestamp: DateTime::from_timestamp_millis(1633036920000 as i64).unwrap(),
+            timestamp: DateTime::from_timestamp_millis(1633036920000).unwrap(),
             status: TxStatus::Pending,
             description: "Record number 2".to_owned(),
         }
@@ -315,7 +1089,7 @@ mod tests {
     fn test_bin_from_transaction() {
         let tx = tx1_for_test();
         let expected = bin_record_for_test();
-        let record = BinTxRecord::from_transaction(&tx);
+        let record = BinTxRecord::from_transaction(&tx, &WriterOptions::default());
 
         assert_eq!(record, expected);
     }
@@ -324,7 +1098,9 @@ mod tests {
     fn test_bin_to_transaction() {
         let bin_record = bin_record_for_test();
         let expected = tx1_for_test();
-        let tx = bin_record.to_transaction().unwrap();
+        let tx = bin_record
+            .to_transaction_ref(&ReaderOptions::default())
+            .unwrap();
 
         assert_eq!(tx, expected);
     }
@@ -343,7 +1119,7 @@ mod tests {
     fn test_deserialize_bin_record() {
         let expected = bin_record_for_test();
         let mut buf = BufReader::new(Cursor::new(EXPECTED_BIN));
-        let record = BinTxRecord::deserialize(&mut buf).unwrap();
+        let record = BinTxRecord::deserialize(&mut buf, &ReaderOptions::default()).unwrap();
 
         assert_eq!(record, expected);
     }
@@ -351,7 +1127,7 @@ mod tests {
     #[test]
     fn test_bin_reader() {
         let stream = Cursor::new(EXPECTED_BIN_MULT);
-        let mut bin_reader = BinTxReader::new(stream).unwrap();
+        let mut bin_reader = BinTxReader::with_options(stream, ReaderOptions::default()).unwrap();
 
         let mut fin_info = Vec::new();
         while let Some(tx) = bin_reader.read_transaction().unwrap() {
@@ -367,10 +1143,183 @@ mod tests {
     fn test_bin_writer() {
         let buf = Vec::new();
         let stream = Cursor::new(buf);
-        let mut bin_writer = BinTxWriter::new(stream).unwrap();
+        let mut bin_writer = BinTxWriter::with_options(stream, WriterOptions::default()).unwrap();
 
         bin_writer.write_transaction(&tx1_for_test()).unwrap();
         bin_writer.write_transaction(&tx2_for_test()).unwrap();
-        assert_eq!(bin_writer.stream.get_ref(), EXPECTED_BIN_MULT);
+        assert_eq!(
+            bin_writer.into_inner().unwrap().into_inner(),
+            EXPECTED_BIN_MULT
+        );
+    }
+
+    #[test]
+    fn test_bin_timestamp_unit() {
+        let mut bin_record = bin_record_for_test();
+        bin_record.timestamp = 1633036860;
+        let options = ReaderOptions {
+            timestamp_unit: TimestampUnit::Seconds,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            bin_record.to_transaction_ref(&options).unwrap(),
+            tx1_for_test()
+        );
+    }
+
+    #[test]
+    fn test_bin_description_limit() {
+        let options = ReaderOptions {
+            max_description_len: 4,
+            ..Default::default()
+        };
+        let mut buf = BufReader::new(Cursor::new(EXPECTED_BIN));
+
+        assert!(BinTxRecord::deserialize(&mut buf, &options).is_err());
+    }
+
+    #[test]
+    fn test_bin_skip_record() {
+        let stream = Cursor::new(EXPECTED_BIN_MULT);
+        let mut bin_reader = BinTxReader::with_options(stream, ReaderOptions::default()).unwrap();
+
+        assert!(bin_reader.skip_record().unwrap());
+        assert_eq!(bin_reader.bytes_read(), EXPECTED_BIN.len() as u64);
+        assert_eq!(bin_reader.read_transaction().unwrap(), Some(tx2_for_test()));
+        assert!(!bin_reader.skip_record().unwrap());
+        assert_eq!(bin_reader.bytes_read(), EXPECTED_BIN_MULT.len() as u64);
+    }
+
+    #[test]
+    fn test_bin_error_position() {
+        let mut data = EXPECTED_BIN_MULT.to_vec();
+        data.truncate(data.len() - 4);
+        let mut bin_reader =
+            BinTxReader::with_options(Cursor::new(data), ReaderOptions::default()).unwrap();
+
+        assert_eq!(bin_reader.read_transaction().unwrap(), Some(tx1_for_test()));
+        let err = bin_reader.read_transaction().unwrap_err();
+        assert_eq!(
+            err.position(),
+            Some(&ErrorPosition {
+                record: 1,
+                byte: EXPECTED_BIN.len() as u64,
+                line: None,
+                column: None,
+            })
+        );
+        assert!(matches!(err.inner(), ParsError::TruncatedRecord));
+    }
+
+    #[test]
+    fn test_bin_schema_v2() {
+        let options = WriterOptions {
+            bin_version: BinVersion::V2,
+            ..Default::default()
+        };
+        let mut bin_writer = BinTxWriter::with_options(Vec::new(), options.clone()).unwrap();
+        bin_writer.write_transaction(&tx1_for_test()).unwrap();
+        bin_writer.write_transaction(&tx2_for_test()).unwrap();
+        let data = bin_writer.into_inner().unwrap();
+        let header = Schema::encode(&DEFAULT_FIELDS);
+        assert!(data.starts_with(&SCHEMA_MAGIC.to_be_bytes()));
+        assert_eq!(data[..header.len()], header);
+        assert_eq!(data[header.len()..], *EXPECTED_BIN_MULT);
+
+        let mut bin_reader =
+            BinTxReader::with_options(Cursor::new(data), ReaderOptions::default()).unwrap();
+        assert_eq!(bin_reader.read_transaction().unwrap(), Some(tx1_for_test()));
+        assert!(bin_reader.skip_record().unwrap());
+        assert_eq!(bin_reader.read_transaction().unwrap(), None);
+
+        // Пустой файл версии 2 состоит из заголовка
+        let mut bin_writer = BinTxWriter::with_options(Vec::new(), options).unwrap();
+        bin_writer.finish().unwrap();
+        let data = bin_writer.into_inner().unwrap();
+        assert_eq!(data, header);
+        let mut bin_reader =
+            BinTxReader::with_options(Cursor::new(data), ReaderOptions::default()).unwrap();
+        assert_eq!(bin_reader.read_transaction().unwrap(), None);
+    }
+
+    #[test]
+    fn test_bin_schema_unknown_fields() {
+        // Файл более новой версии: другой порядок полей, новые поля и байты после схемы
+        let fields = [
+            ("CURRENCY", FieldType::Bytes),
+            (STATUS, FieldType::U8),
+            (TX_ID, FieldType::U64),
+            ("FEE", FieldType::I64),
+            (TX_TYPE, FieldType::U8),
+            (FROM_USER_ID, FieldType::U64),
+            (TO_USER_ID, FieldType::U64),
+            ("FLAGS", FieldType::U16),
+            (AMOUNT, FieldType::I64),
+            (TIMESTAMP, FieldType::U64),
+            (DESCRIPTION, FieldType::Bytes),
+            ("REGION", FieldType::U32),
+        ];
+        let mut data = Schema::encode(&fields);
+        for tx in [tx1_for_test(), tx2_for_test()] {
+            let record = BinTxRecord::from_transaction(&tx, &WriterOptions::default());
+            let mut body = Vec::new();
+            body.extend_from_slice(&3u32.to_be_bytes());
+            body.extend_from_slice(b"RUB");
+            body.push(record.status);
+            body.extend_from_slice(&record.tx_id.to_be_bytes());
+            body.extend_from_slice(&(-5i64).to_be_bytes());
+            body.push(record.tx_type);
+            body.extend_from_slice(&record.from_user_id.to_be_bytes());
+            body.extend_from_slice(&record.to_user_id.to_be_bytes());
+            body.extend_from_slice(&7u16.to_be_bytes());
+            body.extend_from_slice(&record.amount.to_be_bytes());
+            body.extend_from_slice(&record.timestamp.to_be_bytes());
+            body.extend_from_slice(&record.desc_len.to_be_bytes());
+            body.extend_from_slice(record.description.as_bytes());
+            body.extend_from_slice(&42u32.to_be_bytes());
+            body.extend_from_slice(b"future");
+            data.extend_from_slice(&MAGIC.to_be_bytes());
+            data.extend_from_slice(&(body.len() as u32).to_be_bytes());
+            data.extend_from_slice(&body);
+        }
+
+        let mut bin_reader =
+            BinTxReader::with_options(Cursor::new(data.clone()), ReaderOptions::default()).unwrap();
+        assert_eq!(bin_reader.read_transaction().unwrap(), Some(tx1_for_test()));
+        assert_eq!(bin_reader.last_description(), "Record number 1");
+        assert_eq!(bin_reader.read_transaction().unwrap(), Some(tx2_for_test()));
+        assert_eq!(bin_reader.read_transaction().unwrap(), None);
+        assert_eq!(bin_reader.bytes_read(), data.len() as u64);
+
+        let mut bin_reader =
+            BinTxReader::with_options(Cursor::new(data), ReaderOptions::default()).unwrap();
+        assert!(bin_reader.skip_record().unwrap());
+        assert_eq!(bin_reader.read_transaction().unwrap(), Some(tx2_for_test()));
+    }
+
+    #[test]
+    fn test_bin_schema_errors() {
+        let read_err = |header: Vec<u8>| {
+            let mut bin_reader =
+                BinTxReader::with_options(Cursor::new(header), ReaderOptions::default()).unwrap();
+            bin_reader.read_transaction().unwrap_err()
+        };
+
+        let err = read_err(Schema::encode(&DEFAULT_FIELDS[..7]));
+        assert!(matches!(err.inner(), ParsError::MissingField { name } if name == DESCRIPTION));
+
+        let mut fields = DEFAULT_FIELDS.to_vec();
+        fields[0].1 = FieldType::U32;
+        let err = read_err(Schema::encode(&fields));
+        assert_eq!(err.code(), "bad_schema");
+        assert!(!err.is_recoverable());
+
+        let mut header = Schema::encode(&DEFAULT_FIELDS);
+        let last = header.len() - 1;
+        header[last] = 0x7f;
+        assert_eq!(read_err(header.clone()).code(), "bad_schema");
+        header.truncate(last);
+        assert_eq!(read_err(header).code(), "bad_schema");
     }
 }
@@ -0,0 +1,292 @@
+use super::builder::TxReaderBuilder;
+use super::error::ParsError;
+use super::format::TransactionRead;
+use super::index::is_fresh;
+use super::sample::SplitMix64;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+
+/// Сигнатура файла фильтра
+const BLOOM_MAGIC: [u8; 4] = *b"YPBF";
+
+/// Версия формата файла фильтра
+const BLOOM_VERSION: u32 = 1;
+
+/// Расширение файла фильтра, добавляемое к имени файла данных
+pub const BLOOM_EXTENSION: &str = "bloom";
+
+/// Доля ложноположительных ответов по умолчанию
+pub const DEFAULT_FALSE_POSITIVE_RATE: f64 = 0.01;
+
+/// Фильтр Блума по tx_id файла транзакций. Отвечает на вопрос «может ли
+/// транзакция быть в файле»: ответ false точен, ответ true ошибочен с долей
+/// не больше заданной при построении. Позволяет сверке и удалению повторов
+/// не читать файлы, в которых искомых транзакций заведомо нет.
+///
+/// Фильтр хранится рядом с файлом данных в файле `<имя>.bloom`
+/// ([BloomFilter::sidecar_path]): сигнатура `YPBF`, версия (u32), количество
+/// хеш-функций (u32), количество бит (u64), количество добавленных tx_id (u64)
+/// и биты словами u64. Все числа в порядке big-endian. Хеши не зависят
+/// от платформы и версии компилятора
+///
+/// ```
+/// use fin_parser::bloom::BloomFilter;
+///
+/// let mut filter = BloomFilter::new(1000, 0.01);
+/// filter.extend([1, 2, 3]);
+/// assert!(filter.contains(2));
+/// assert_eq!(filter.len(), 3);
+/// ```
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct BloomFilter {
+    bits: Vec<u64>,
+    num_bits: u64,
+    hashes: u32,
+    items: u64,
+}
+
+impl BloomFilter {
+    /// Пустой фильтр, рассчитанный на expected tx_id с долей ложноположительных
+    /// ответов fp_rate
+    pub fn new(expected: u64, fp_rate: f64) -> Self {
+        let expected = expected.max(1) as f64;
+        let fp_rate = fp_rate.clamp(1e-9, 0.5);
+        let ln2 = std::f64::consts::LN_2;
+        let num_bits = (-expected * fp_rate.ln() / (ln2 * ln2)).ceil().max(64.0) as u64;
+        let hashes = (num_bits as f64 / expected * ln2).round().clamp(1.0, 32.0) as u32;
+        Self::with_params(num_bits, hashes)
+    }
+
+    /// Пустой фильтр с заданным количеством бит и хеш-функций
+    pub fn with_params(num_bits: u64, hashes: u32) -> Self {
+        let num_bits = num_bits.max(1);
+        Self {
+            bits: vec![0; num_bits.div_ceil(64) as usize],
+            num_bits,
+            hashes: hashes.max(1),
+            items: 0,
+        }
+    }
+
+    /// Построение фильтра по tx_id оставшихся в потоке транзакций.
+    /// Размер фильтра подбирается по количеству транзакций, поэтому tx_id
+    /// сначала собираются в памяти
+    pub fn build<R: TransactionRead + ?Sized>(
+        reader: &mut R,
+        fp_rate: f64,
+    ) -> Result<Self, ParsError> {
+        let mut ids = Vec::new();
+        while let Some(tx) = reader.read_transaction()? {
+            ids.push(tx.tx_id);
+        }
+        let mut res = Self::new(ids.len() as u64, fp_rate);
+        res.extend(ids);
+        Ok(res)
+    }
+
+    /// Построение фильтра по файлу транзакций. Формат и сжатие определяются по расширению
+    pub fn build_file<P: AsRef<Path>>(path: P, fp_rate: f64) -> Result<Self, ParsError> {
+        let mut reader = TxReaderBuilder::new().open(path)?;
+        let mut ids = Vec::new();
+        while let Some(tx) = reader.read_transaction_ref()? {
+            ids.push(tx.tx_id);
+        }
+        let mut res = Self::new(ids.len() as u64, fp_rate);
+        res.extend(ids);
+        Ok(res)
+    }
+
+    /// Путь файла фильтра для файла данных: `transactions.bin` -> `transactions.bin.bloom`
+    pub fn sidecar_path<P: AsRef<Path>>(path: P) -> PathBuf {
+        let mut name = path.as_ref().as_os_str().to_owned();
+        name.push(".");
+        name.push(BLOOM_EXTENSION);
+        PathBuf::from(name)
+    }
+
+    /// Фильтр файла данных из файла фильтра, если он есть и не старше файла данных
+    pub fn load_fresh<P: AsRef<Path>>(path: P) -> Result<Option<Self>, ParsError> {
+        let path = path.as_ref();
+        let sidecar = Self::sidecar_path(path);
+        match is_fresh(path, &sidecar) {
+            true => Self::load(&sidecar).map(Some),
+            false => Ok(None),
+        }
+    }
+
+    /// Файл данных может содержать хотя бы одну из транзакций. Без актуального
+    /// файла фильтра ответ true: файл придется прочитать
+    pub fn file_may_contain<P: AsRef<Path>, I: IntoIterator<Item = u64>>(
+        path: P,
+        tx_ids: I,
+    ) -> Result<bool, ParsError> {
+        Ok(Self::load_fresh(path)?.is_none_or(|filter| filter.contains_any(tx_ids)))
+    }
+
+    /// Чтение фильтра из файла фильтра
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, ParsError> {
+        Self::read_from(BufReader::new(File::open(path)?))
+    }
+
+    /// Сохранение фильтра в файл фильтра
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), ParsError> {
+        let mut out = BufWriter::new(File::create(path)?);
+        self.write_to(&mut out)?;
+        out.flush()?;
+        Ok(())
+    }
+
+    /// Чтение фильтра из потока
+    pub fn read_from<In: Read>(mut stream: In) -> Result<Self, ParsError> {
+        let mut header = [0; 28];
+        stream.read_exact(&mut header)?;
+        if header[..4] != BLOOM_MAGIC {
+            return Err(ParsError::WrongFormat(
+                "Файл не является фильтром Блума".to_owned(),
+            ));
+        }
+        let version = u32::from_be_bytes(header[4..8].try_into().unwrap());
+        if version != BLOOM_VERSION {
+            return Err(ParsError::WrongFormat(format!(
+                "Неподдерживаемая версия фильтра Блума: {version}"
+            )));
+        }
+        let hashes = u32::from_be_bytes(header[8..12].try_into().unwrap());
+        let num_bits = u64::from_be_bytes(header[12..20].try_into().unwrap());
+        let items = u64::from_be_bytes(header[20..].try_into().unwrap());
+        if hashes == 0 || num_bits == 0 {
+            return Err(ParsError::WrongFormat(
+                "Пустые параметры фильтра Блума".to_owned(),
+            ));
+        }
+        let mut res = Self::with_params(num_bits, hashes);
+        res.items = items;
+        let mut buf = [0; 8];
+        for word in &mut res.bits {
+            stream.read_exact(&mut buf)?;
+            *word = u64::from_be_bytes(buf);
+        }
+        Ok(res)
+    }
+
+    /// Запись фильтра в поток
+    pub fn write_to<Out: Write>(&self, mut stream: Out) -> Result<(), ParsError> {
+        stream.write_all(&BLOOM_MAGIC)?;
+        stream.write_all(&BLOOM_VERSION.to_be_bytes())?;
+        stream.write_all(&self.hashes.to_be_bytes())?;
+        stream.write_all(&self.num_bits.to_be_bytes())?;
+        stream.write_all(&self.items.to_be_bytes())?;
+        for word in &self.bits {
+            stream.write_all(&word.to_be_bytes())?;
+        }
+        Ok(())
+    }
+
+    /// Номера бит tx_id: двойное хеширование двумя значениями SplitMix64
+    fn positions(&self, tx_id: u64) -> impl Iterator<Item = u64> + use<> {
+        let mut rng = SplitMix64(tx_id);
+        let (first, step) = (rng.next_u64(), rng.next_u64() | 1);
+        let num_bits = self.num_bits;
+        (0..self.hashes as u64)
+            .map(move |idx| first.wrapping_add(idx.wrapping_mul(step)) % num_bits)
+    }
+
+    /// Добавление tx_id
+    pub fn insert(&mut self, tx_id: u64) {
+        for pos in self.positions(tx_id) {
+            self.bits[(pos / 64) as usize] |= 1 << (pos % 64);
+        }
+        self.items += 1;
+    }
+
+    /// Транзакция с tx_id может быть в файле. false означает, что ее там точно нет
+    pub fn contains(&self, tx_id: u64) -> bool {
+        self.positions(tx_id)
+            .all(|pos| self.bits[(pos / 64) as usize] & (1 << (pos % 64)) != 0)
+    }
+
+    /// Хотя бы одна из транзакций может быть в файле
+    pub fn contains_any<I: IntoIterator<Item = u64>>(&self, tx_ids: I) -> bool {
+        tx_ids.into_iter().any(|tx_id| self.contains(tx_id))
+    }
+
+    /// Объединение с фильтром с теми же параметрами, например построенным по другому файлу
+    pub fn merge(&mut self, other: &BloomFilter) -> Result<(), ParsError> {
+        if (self.num_bits, self.hashes) != (other.num_bits, other.hashes) {
+            return Err(ParsError::WrongFormat(format!(
+                "Параметры фильтров Блума не совпадают: {} бит, {} хешей и {} бит, {} хешей",
+                self.num_bits, self.hashes, other.num_bits, other.hashes
+            )));
+        }
+        for (word, other) in self.bits.iter_mut().zip(&other.bits) {
+            *word |= other;
+        }
+        self.items += other.items;
+        Ok(())
+    }
+
+    /// Количество добавленных tx_id, включая повторы
+    pub fn len(&self) -> u64 {
+        self.items
+    }
+
+    /// В фильтр ничего не добавлено
+    pub fn is_empty(&self) -> bool {
+        self.items == 0
+    }
+
+    /// Количество бит фильтра
+    pub fn num_bits(&self) -> u64 {
+        self.num_bits
+    }
+
+    /// Количество хеш-функций
+    pub fn hashes(&self) -> u32 {
+        self.hashes
+    }
+
+    /// Ожидаемая доля ложноположительных ответов по доле установленных бит
+    pub fn false_positive_rate(&self) -> f64 {
+        let set: u32 = self.bits.iter().map(|word| word.count_ones()).sum();
+        (set as f64 / self.num_bits as f64).powi(self.hashes as i32)
+    }
+}
+
+impl Extend<u64> for BloomFilter {
+    fn extend<I: IntoIterator<Item = u64>>(&mut self, iter: I) {
+        for tx_id in iter {
+            self.insert(tx_id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generate::{GenerateOptions, Generator};
+
+    #[test]
+    fn test_bloom_filter() {
+        let mut source = Generator::new(GenerateOptions::new().with_seed(3), 2000).unwrap();
+        let filter = BloomFilter::build(&mut source, 0.01).unwrap();
+        assert_eq!(filter.len(), 2000);
+        // Generator выдает tx_id подряд с единицы
+        assert!((1..=2000).all(|tx_id| filter.contains(tx_id)));
+        let false_positives = (1_000_000..1_010_000)
+            .filter(|tx_id| filter.contains(*tx_id))
+            .count();
+        assert!(false_positives < 300, "{false_positives}");
+
+        let mut buf = Vec::new();
+        filter.write_to(&mut buf).unwrap();
+        assert_eq!(BloomFilter::read_from(buf.as_slice()).unwrap(), filter);
+        assert!(BloomFilter::read_from(&buf[1..]).is_err());
+
+        let mut other = BloomFilter::with_params(filter.num_bits(), filter.hashes());
+        other.insert(5_000_000);
+        other.merge(&filter).unwrap();
+        assert!(other.contains(5_000_000) && other.contains(1999));
+        assert!(other.merge(&BloomFilter::new(10, 0.01)).is_err());
+    }
+}
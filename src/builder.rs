@@ -0,0 +1,328 @@
+use super::compression::Compression;
+use super::error::ParsError;
+use super::format::{DETECT_PREFIX_LEN, Format};
+use super::index::{RangeReader, TimeIndex};
+use super::metrics::Metrics;
+use super::options::{BinVersion, ErrorPolicy, ReaderOptions, TimestampUnit, WriterOptions};
+use super::tx_format::{TxReader, TxWriter};
+use chrono::{DateTime, Utc};
+use std::fs::File;
+use std::io::{Cursor, Read, Write};
+use std::ops::RangeBounds;
+use std::path::Path;
+
+/// Построитель [TxReader] с настройками чтения
+///
+/// ```
+/// use fin_parser::builder::TxReaderBuilder;
+/// use fin_parser::format::Format;
+/// use std::io::Cursor;
+///
+/// let csv = "TX_ID;TX_TYPE;FROM_USER_ID;TO_USER_ID;AMOUNT;TIMESTAMP;STATUS;DESCRIPTION\n\
+///     1;DEPOSIT;0;5;100;1633036860;SUCCESS;\"Record number 1\"\n";
+/// let mut reader = TxReaderBuilder::new()
+///     .format(Format::Csv)
+///     .delimiter(b';')
+///     .timestamp_unit(fin_parser::options::TimestampUnit::Seconds)
+///     .build(Cursor::new(csv))
+///     .unwrap();
+/// let tx = reader.read_transaction().unwrap().unwrap();
+/// assert_eq!(tx.timestamp.timestamp_millis(), 1633036860000);
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct TxReaderBuilder {
+    fin_format: Option<Format>,
+    options: ReaderOptions,
+    metrics: Option<Metrics>,
+}
+
+impl TxReaderBuilder {
+    /// Построитель с настройками по умолчанию
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Формат данных. Если не задан, определяется по расширению файла или содержимому потока
+    pub fn format(mut self, fin_format: Format) -> Self {
+        self.fin_format = Some(fin_format);
+        self
+    }
+
+    /// Строгий режим (по умолчанию): описание обязано быть в кавычках, заголовок csv
+    /// должен точно соответствовать спецификации, а запись text не может содержать лишних полей
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.options.strict = strict;
+        self
+    }
+
+    /// Разделитель полей csv
+    pub fn delimiter(mut self, delimiter: u8) -> Self {
+        self.options.delimiter = delimiter;
+        self
+    }
+
+    /// Единица измерения времени в поле TIMESTAMP
+    pub fn timestamp_unit(mut self, unit: TimestampUnit) -> Self {
+        self.options.timestamp_unit = unit;
+        self
+    }
+
+    /// Максимальная длина описания транзакции в байтах
+    pub fn max_description_len(mut self, len: usize) -> Self {
+        self.options.max_description_len = len;
+        self
+    }
+
+    /// Сжатие входного потока
+    pub fn compression(mut self, compression: Compression) -> Self {
+        self.options.compression = compression;
+        self
+    }
+
+    /// Поведение при ошибке в записи, см. [TxReader::with_error_policy]
+    pub fn error_policy(mut self, policy: ErrorPolicy) -> Self {
+        self.options.error_policy = policy;
+        self
+    }
+
+    /// Предел количества пропущенных ошибочных записей, см. [TxReader::with_max_errors]
+    pub fn max_errors(mut self, max_errors: usize) -> Self {
+        self.options.max_errors = Some(max_errors);
+        self
+    }
+
+    /// Сбор предупреждений о подозрительных, но допустимых данных,
+    /// см. [TxReader::take_warnings]
+    pub fn collect_warnings(mut self, collect: bool) -> Self {
+        self.options.collect_warnings = collect;
+        self
+    }
+
+    /// Чтение только транзакций с временем из интервала, остальные записи пропускаются.
+    /// Поддерживается только для bin. Если у открываемого файла есть актуальный индекс
+    /// времени ([TimeIndex]), читаются только блоки файла, пересекающиеся с интервалом.
+    /// Смещения в ошибках при этом считаются от начала прочитанных блоков
+    pub fn time_range<R: RangeBounds<DateTime<Utc>>>(mut self, range: R) -> Self {
+        self.options.time_range = Some((range.start_bound().cloned(), range.end_bound().cloned()));
+        self
+    }
+
+    /// Учет чтения в метриках, см. [TxReader::with_metrics]
+    pub fn metrics(mut self, metrics: Metrics) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// Текущие настройки чтения
+    pub fn options(&self) -> &ReaderOptions {
+        &self.options
+    }
+
+    /// Создание читателя поверх потока
+    pub fn build<In: Read + Send + 'static>(
+        self,
+        stream: In,
+    ) -> Result<TxReader<Box<dyn Read + Send>>, ParsError> {
+        let mut stream = self.options.compression.wrap_reader(stream);
+        let fin_format = match self.fin_format {
+            Some(val) => val,
+            None => {
+                let mut prefix = Vec::new();
+                stream
+                    .by_ref()
+                    .take(DETECT_PREFIX_LEN)
+                    .read_to_end(&mut prefix)?;
+                let fin_format = Format::detect(&prefix)?;
+                stream = Box::new(Cursor::new(prefix).chain(stream));
+                fin_format
+            }
+        };
+        let reader = TxReader::with_custom(stream, fin_format, self.options)?;
+        Ok(match self.metrics {
+            Some(metrics) => reader.with_metrics(metrics),
+            None => reader,
+        })
+    }
+
+    /// Открытие файла на чтение. Незаданные формат и сжатие определяются по расширению файла
+    pub fn open<P: AsRef<Path>>(
+        mut self,
+        path: P,
+    ) -> Result<TxReader<Box<dyn Read + Send>>, ParsError> {
+        let path = path.as_ref();
+        match Format::from_path(path) {
+            Ok((fin_format, compression)) => {
+                self.fin_format.get_or_insert(fin_format);
+                if self.options.compression == Compression::None {
+                    self.options.compression = compression;
+                }
+            }
+            Err(e) => {
+                if self.fin_format.is_none() {
+                    return Err(e);
+                }
+            }
+        }
+        let file = File::open(path)?;
+        if let Some(range) = self.options.time_range
+            && self.fin_format == Some(Format::Bin)
+            && self.options.compression == Compression::None
+            && let Some(index) = TimeIndex::load_fresh(path)?
+        {
+            return self.build(RangeReader::new(file, index.byte_ranges(range)));
+        }
+        self.build(file)
+    }
+}
+
+/// Построитель [TxWriter] с настройками записи
+#[derive(Clone, Debug, Default)]
+pub struct TxWriterBuilder {
+    fin_format: Option<Format>,
+    options: WriterOptions,
+    metrics: Option<Metrics>,
+}
+
+impl TxWriterBuilder {
+    /// Построитель с настройками по умолчанию
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Формат данных. Если не задан, определяется по расширению файла
+    pub fn format(mut self, fin_format: Format) -> Self {
+        self.fin_format = Some(fin_format);
+        self
+    }
+
+    /// Разделитель полей csv
+    pub fn delimiter(mut self, delimiter: u8) -> Self {
+        self.options.delimiter = delimiter;
+        self
+    }
+
+    /// Единица измерения времени в поле TIMESTAMP
+    pub fn timestamp_unit(mut self, unit: TimestampUnit) -> Self {
+        self.options.timestamp_unit = unit;
+        self
+    }
+
+    /// Сжатие выходного потока
+    pub fn compression(mut self, compression: Compression) -> Self {
+        self.options.compression = compression;
+        self
+    }
+
+    /// Версия формата bin. Версия 2 записывает в начало файла схему полей
+    pub fn bin_version(mut self, version: BinVersion) -> Self {
+        self.options.bin_version = version;
+        self
+    }
+
+    /// Запись в конец файла bin корня дерева Меркла над отпечатками транзакций
+    /// для последующей проверки доказательств включения, см. [crate::merkle::MerkleTree].
+    /// Для других форматов не действует
+    pub fn merkle(mut self, merkle: bool) -> Self {
+        self.options.merkle = merkle;
+        self
+    }
+
+    /// Учет записи в метриках, см. [TxWriter::with_metrics]
+    pub fn metrics(mut self, metrics: Metrics) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// Текущие настройки записи
+    pub fn options(&self) -> &WriterOptions {
+        &self.options
+    }
+
+    /// Создание писателя поверх потока
+    pub fn build<Out: Write + Send + 'static>(
+        self,
+        stream: Out,
+    ) -> Result<TxWriter<Box<dyn Write + Send>>, ParsError> {
+        let fin_format = self
+            .fin_format
+            .ok_or_else(|| ParsError::WrongFormat("Не задан формат выходных данных".to_owned()))?;
+        let stream = self.options.compression.wrap_writer(stream);
+        let writer = TxWriter::with_custom(stream, fin_format, self.options)?;
+        Ok(match self.metrics {
+            Some(metrics) => writer.with_metrics(metrics),
+            None => writer,
+        })
+    }
+
+    /// Создание файла на запись. Незаданные формат и сжатие определяются по расширению файла.
+    /// Существующий файл перезаписывается
+    pub fn create<P: AsRef<Path>>(
+        mut self,
+        path: P,
+    ) -> Result<TxWriter<Box<dyn Write + Send>>, ParsError> {
+        let path = path.as_ref();
+        match Format::from_path(path) {
+            Ok((fin_format, compression)) => {
+                self.fin_format.get_or_insert(fin_format);
+                if self.options.compression == Compression::None {
+                    self.options.compression = compression;
+                }
+            }
+            Err(e) => {
+                if self.fin_format.is_none() {
+                    return Err(e);
+                }
+            }
+        }
+        let file = File::create(path)?;
+        self.build(file)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{encode, tx};
+
+    #[test]
+    fn test_reader_builder_detect() {
+        let buf = encode(&[tx(1)], Format::Bin);
+
+        let mut reader = TxReaderBuilder::new().build(Cursor::new(buf)).unwrap();
+        assert_eq!(reader.read_all().unwrap(), vec![tx(1)]);
+    }
+
+    #[test]
+    fn test_builder_create_open() {
+        let path =
+            std::env::temp_dir().join(format!("fin_parser_builder_{}.out", std::process::id()));
+        let mut writer = TxWriterBuilder::new()
+            .format(Format::Csv)
+            .delimiter(b'|')
+            .timestamp_unit(TimestampUnit::Micros)
+            .compression(Compression::Gzip)
+            .create(&path)
+            .unwrap();
+        writer.write_transaction(&tx(1)).unwrap();
+        writer.finish().unwrap();
+        drop(writer);
+
+        let mut reader = TxReaderBuilder::new()
+            .delimiter(b'|')
+            .timestamp_unit(TimestampUnit::Micros)
+            .compression(Compression::Gzip)
+            .open(&path);
+        assert!(reader.is_err());
+
+        reader = TxReaderBuilder::new()
+            .format(Format::Csv)
+            .delimiter(b'|')
+            .timestamp_unit(TimestampUnit::Micros)
+            .compression(Compression::Gzip)
+            .open(&path);
+        let txs = reader.unwrap().read_all().unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(txs, vec![tx(1)]);
+    }
+}
@@ -0,0 +1,124 @@
+use super::error::ParsError;
+use super::format::{Format, TransactionRead, read_fin_data};
+use super::transaction::Transaction;
+use super::tx_format::TxReader;
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+/// Читатель нескольких файлов одного формата как одного потока, см. [TxReader::chain].
+/// Файлы открываются по очереди, заголовок проверяется у каждого файла csv,
+/// поэтому файлы не нужно склеивать с удалением заголовков
+pub struct ChainedReader {
+    paths: Vec<PathBuf>,
+    fin_format: Option<Format>,
+    next: usize,
+    current: Option<TxReader<Box<dyn Read + Send>>>,
+}
+
+impl ChainedReader {
+    /// Проверка файлов и создание читателя. Формат каждого файла определяется
+    /// по расширению и должен совпадать у всех файлов, сжатие может различаться
+    pub(crate) fn open<P: AsRef<Path>>(
+        paths: impl IntoIterator<Item = P>,
+    ) -> Result<Self, ParsError> {
+        let paths: Vec<PathBuf> = paths
+            .into_iter()
+            .map(|path| path.as_ref().to_path_buf())
+            .collect();
+        let mut fin_format = None;
+        for path in &paths {
+            let (val, _) = Format::from_path(path)?;
+            match fin_format {
+                None => fin_format = Some(val),
+                Some(expected) if expected != val => {
+                    return Err(ParsError::WrongFormat(format!(
+                        "Файл {} в формате {}, ожидался формат {}",
+                        path.display(),
+                        val.name(),
+                        expected.name()
+                    )));
+                }
+                Some(_) => {}
+            }
+            fs::metadata(path)?;
+        }
+        Ok(Self {
+            paths,
+            fin_format,
+            next: 0,
+            current: None,
+        })
+    }
+
+    /// Общий формат файлов. None для пустого списка файлов
+    pub fn format(&self) -> Option<Format> {
+        self.fin_format
+    }
+
+    /// Путь читаемого файла
+    pub fn current_path(&self) -> Option<&Path> {
+        match self.current {
+            Some(_) => self.paths.get(self.next - 1).map(PathBuf::as_path),
+            None => None,
+        }
+    }
+
+    /// Чтение следующей транзакции. None означает конец последнего файла
+    pub fn read_transaction(&mut self) -> Result<Option<Transaction>, ParsError> {
+        loop {
+            if let Some(reader) = &mut self.current {
+                if let Some(tx) = reader.read_transaction()? {
+                    return Ok(Some(tx));
+                }
+                self.current = None;
+            }
+            let Some(path) = self.paths.get(self.next) else {
+                return Ok(None);
+            };
+            self.current = Some(TxReader::from_path(path)?);
+            self.next += 1;
+        }
+    }
+
+    /// Метод чтения всех оставшихся транзакций всех файлов
+    pub fn read_all(&mut self) -> Result<Vec<Transaction>, ParsError> {
+        read_fin_data(self)
+    }
+}
+
+impl TransactionRead for ChainedReader {
+    fn read_transaction(&mut self) -> Result<Option<Transaction>, ParsError> {
+        ChainedReader::read_transaction(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::txs;
+    use crate::tx_format::write_file;
+
+    #[test]
+    fn test_chain() {
+        let dir = std::env::temp_dir().join(format!("fin-parser-chain-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let paths = [dir.join("1.csv"), dir.join("2.csv"), dir.join("3.csv")];
+        write_file(&paths[0], Format::Csv, &txs(0..2)).unwrap();
+        write_file(&paths[1], Format::Csv, &[]).unwrap();
+        write_file(&paths[2], Format::Csv, &txs(2..3)).unwrap();
+
+        let mut reader = TxReader::chain(&paths).unwrap();
+        assert_eq!(reader.format(), Some(Format::Csv));
+        assert!(reader.current_path().is_none());
+        assert_eq!(reader.read_transaction().unwrap().unwrap().tx_id, 0);
+        assert_eq!(reader.current_path(), Some(paths[0].as_path()));
+        assert_eq!(reader.read_all().unwrap(), txs(1..3));
+
+        fs::write(dir.join("4.bin"), b"").unwrap();
+        let res = TxReader::chain([&paths[0], &dir.join("4.bin")]);
+        assert!(matches!(res, Err(ParsError::WrongFormat(_))));
+        assert!(TxReader::chain([dir.join("5.csv")]).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,204 @@
+use super::error::ParsError;
+use flate2::Compression as GzLevel;
+use flate2::read::MultiGzDecoder;
+use flate2::write::GzEncoder;
+use std::fmt;
+#[cfg(feature = "zstd")]
+use std::io;
+use std::io::{BufReader, Read, Write};
+use std::str::FromStr;
+
+const GZIP_EXT: &str = "gz";
+#[cfg(feature = "zstd")]
+const ZSTD_EXT: &str = "zst";
+
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+#[cfg(feature = "zstd")]
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+
+/// Сжатие потока транзакций
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Default)]
+pub enum Compression {
+    /// Без сжатия
+    #[default]
+    None,
+    /// gzip
+    Gzip,
+    /// zstd (требует feature `zstd`)
+    #[cfg(feature = "zstd")]
+    Zstd,
+}
+
+impl Compression {
+    /// Определение сжатия по расширению файла
+    pub fn from_extension(ext: &str) -> Option<Self> {
+        match ext {
+            GZIP_EXT => Some(Self::Gzip),
+            #[cfg(feature = "zstd")]
+            ZSTD_EXT => Some(Self::Zstd),
+            _ => None,
+        }
+    }
+
+    /// Определение сжатия по первым байтам потока. Поток без известной сигнатуры
+    /// считается несжатым
+    pub fn detect(prefix: &[u8]) -> Self {
+        if prefix.starts_with(GZIP_MAGIC) {
+            return Self::Gzip;
+        }
+        #[cfg(feature = "zstd")]
+        if prefix.starts_with(ZSTD_MAGIC) {
+            return Self::Zstd;
+        }
+        Self::None
+    }
+
+    /// Имя сжатия, принимаемое [Compression::from_str]
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::None => "none",
+            Self::Gzip => "gzip",
+            #[cfg(feature = "zstd")]
+            Self::Zstd => "zstd",
+        }
+    }
+
+    /// Обертка над потоком чтения, распаковывающая данные и буферизующая чтение
+    pub fn wrap_reader<In: Read + Send + 'static>(&self, stream: In) -> Box<dyn Read + Send> {
+        match self {
+            Self::None => Box::new(BufReader::new(stream)),
+            Self::Gzip => Box::new(BufReader::new(MultiGzDecoder::new(stream))),
+            #[cfg(feature = "zstd")]
+            Self::Zstd => match zstd::stream::read::Decoder::new(stream) {
+                Ok(decoder) => Box::new(BufReader::new(decoder)),
+                Err(e) => Box::new(FailedStream::from(e)),
+            },
+        }
+    }
+
+    /// Обертка над потоком записи, сжимающая данные. Запись буферизуют сами писатели
+    pub fn wrap_writer<Out: Write + Send + 'static>(&self, stream: Out) -> Box<dyn Write + Send> {
+        match self {
+            Self::None => Box::new(stream),
+            Self::Gzip => Box::new(GzEncoder::new(stream, GzLevel::default())),
+            // Кадр zstd завершается при удалении обертки
+            #[cfg(feature = "zstd")]
+            Self::Zstd => match zstd::stream::write::Encoder::new(stream, 0) {
+                Ok(encoder) => Box::new(encoder.auto_finish()),
+                Err(e) => Box::new(FailedStream::from(e)),
+            },
+        }
+    }
+}
+
+impl FromStr for Compression {
+    type Err = ParsError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(Self::None),
+            "gzip" | GZIP_EXT => Ok(Self::Gzip),
+            #[cfg(feature = "zstd")]
+            "zstd" | ZSTD_EXT => Ok(Self::Zstd),
+            _ => Err(ParsError::WrongFormat(format!("Неизвестное сжатие: {s}"))),
+        }
+    }
+}
+
+impl fmt::Display for Compression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Поток, который не удалось создать: ошибка создания возвращается при каждом обращении
+#[cfg(feature = "zstd")]
+struct FailedStream {
+    kind: io::ErrorKind,
+    message: String,
+}
+
+#[cfg(feature = "zstd")]
+impl From<io::Error> for FailedStream {
+    fn from(e: io::Error) -> Self {
+        Self {
+            kind: e.kind(),
+            message: e.to_string(),
+        }
+    }
+}
+
+#[cfg(feature = "zstd")]
+impl FailedStream {
+    fn error(&self) -> io::Error {
+        io::Error::new(self.kind, self.message.clone())
+    }
+}
+
+#[cfg(feature = "zstd")]
+impl Read for FailedStream {
+    fn read(&mut self, _buf: &mut [u8]) -> io::Result<usize> {
+        Err(self.error())
+    }
+}
+
+#[cfg(feature = "zstd")]
+impl Write for FailedStream {
+    fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+        Err(self.error())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Err(self.error())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_gzip_roundtrip() {
+        let mut compressed = Vec::new();
+        {
+            let mut encoder = GzEncoder::new(&mut compressed, GzLevel::default());
+            encoder.write_all(b"TX_ID,TX_TYPE").unwrap();
+            encoder.finish().unwrap();
+        }
+        assert_ne!(compressed, b"TX_ID,TX_TYPE");
+
+        let mut reader = Compression::Gzip.wrap_reader(Cursor::new(compressed));
+        let mut res = String::new();
+        reader.read_to_string(&mut res).unwrap();
+        assert_eq!(res, "TX_ID,TX_TYPE");
+    }
+
+    #[test]
+    fn test_detect() {
+        assert_eq!(Compression::detect(&[0x1f, 0x8b, 0x08]), Compression::Gzip);
+        assert_eq!(Compression::detect(b"TX_ID"), Compression::None);
+        assert_eq!(Compression::detect(&[]), Compression::None);
+        assert_eq!("gzip".parse::<Compression>().unwrap(), Compression::Gzip);
+        assert!("lzma".parse::<Compression>().is_err());
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn test_zstd_roundtrip() {
+        let path = std::env::temp_dir().join(format!("fin-parser-zstd-{}", std::process::id()));
+        {
+            let file = std::fs::File::create(&path).unwrap();
+            let mut writer = Compression::Zstd.wrap_writer(file);
+            writer.write_all(b"TX_ID,TX_TYPE").unwrap();
+        }
+        let compressed = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(Compression::detect(&compressed), Compression::Zstd);
+
+        let mut reader = Compression::Zstd.wrap_reader(Cursor::new(compressed));
+        let mut res = String::new();
+        reader.read_to_string(&mut res).unwrap();
+        assert_eq!(res, "TX_ID,TX_TYPE");
+    }
+}
@@ -0,0 +1,340 @@
+//! Общие настройки утилит из файла `fin-parser.toml`.
+//!
+//! Поддерживается подмножество toml: разделы `[input]` и `[output]`, пары
+//! `ключ = значение` со строками в двойных кавычках и true/false,
+//! комментарии после `#`. Ключи до первого раздела относятся к обоим разделам:
+//! ```toml
+//! delimiter = ";"
+//! timestamp_unit = "seconds"
+//!
+//! [input]
+//! format = "csv"
+//! strict = false
+//!
+//! [output]
+//! format = "bin"
+//! compression = "gzip"
+//! bin_version = "2"
+//! merkle = true
+//! dir = "/data/out"
+//! ```
+
+use super::builder::{TxReaderBuilder, TxWriterBuilder};
+use super::compression::Compression;
+use super::error::ParsError;
+use super::format::Format;
+use super::options::{BinVersion, TimestampUnit};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Имя файла настроек, который ищется в текущем каталоге
+pub const CONFIG_FILE: &str = "fin-parser.toml";
+
+/// Настройки чтения или записи. Незаданные значения берутся из аргументов
+/// командной строки или значений по умолчанию
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+pub struct Section {
+    /// Формат данных для файлов без известного расширения и stdin
+    pub format: Option<Format>,
+    /// Разделитель полей csv
+    pub delimiter: Option<u8>,
+    /// Единица измерения времени в поле TIMESTAMP
+    pub timestamp_unit: Option<TimestampUnit>,
+    /// Строгий режим (только для чтения)
+    pub strict: Option<bool>,
+    /// Сжатие данных
+    pub compression: Option<Compression>,
+    /// Версия формата bin (только для записи)
+    pub bin_version: Option<BinVersion>,
+    /// Запись корня дерева Меркла в конец файла bin (только для записи)
+    pub merkle: Option<bool>,
+    /// Каталог для относительных путей (только для записи)
+    pub dir: Option<PathBuf>,
+}
+
+impl Section {
+    /// Значения раздела, дополненные значениями other
+    fn or(self, other: &Section) -> Self {
+        Self {
+            format: self.format.or(other.format),
+            delimiter: self.delimiter.or(other.delimiter),
+            timestamp_unit: self.timestamp_unit.or(other.timestamp_unit),
+            strict: self.strict.or(other.strict),
+            compression: self.compression.or(other.compression),
+            bin_version: self.bin_version.or(other.bin_version),
+            merkle: self.merkle.or(other.merkle),
+            dir: self.dir.or_else(|| other.dir.clone()),
+        }
+    }
+
+    /// Формат файла: по расширению, а если оно неизвестно, из настроек
+    pub fn format_for(&self, path: &Path) -> Option<Format> {
+        match Format::from_path(path) {
+            Ok((fin_format, _)) => Some(fin_format),
+            Err(_) => self.format,
+        }
+    }
+
+    /// Построитель читателя с разделителем, единицей времени и строгим режимом из настроек
+    pub fn reader_builder(&self) -> TxReaderBuilder {
+        let mut builder = TxReaderBuilder::new();
+        if let Some(delimiter) = self.delimiter {
+            builder = builder.delimiter(delimiter);
+        }
+        if let Some(unit) = self.timestamp_unit {
+            builder = builder.timestamp_unit(unit);
+        }
+        if let Some(strict) = self.strict {
+            builder = builder.strict(strict);
+        }
+        builder
+    }
+
+    /// Построитель читателя файла path (`-` для stdin). Формат и сжатие из настроек
+    /// применяются, только если их нельзя определить по расширению
+    pub fn reader_builder_for(&self, path: &Path) -> TxReaderBuilder {
+        let mut builder = self.reader_builder();
+        if Format::from_path(path).is_err() {
+            if let Some(fin_format) = self.format {
+                builder = builder.format(fin_format);
+            }
+            if let Some(compression) = self.compression {
+                builder = builder.compression(compression);
+            }
+        }
+        builder
+    }
+
+    /// Построитель писателя с разделителем, единицей времени, версией bin
+    /// и корнем дерева Меркла из настроек
+    pub fn writer_builder(&self) -> TxWriterBuilder {
+        let mut builder = TxWriterBuilder::new();
+        if let Some(delimiter) = self.delimiter {
+            builder = builder.delimiter(delimiter);
+        }
+        if let Some(unit) = self.timestamp_unit {
+            builder = builder.timestamp_unit(unit);
+        }
+        if let Some(version) = self.bin_version {
+            builder = builder.bin_version(version);
+        }
+        if let Some(merkle) = self.merkle {
+            builder = builder.merkle(merkle);
+        }
+        builder
+    }
+
+    /// Построитель писателя в файл path или в stdout (None). Формат и сжатие из настроек
+    /// применяются, только если их нельзя определить по расширению
+    pub fn writer_builder_for(&self, path: Option<&Path>) -> TxWriterBuilder {
+        let mut builder = self.writer_builder();
+        if path.is_none_or(|path| Format::from_path(path).is_err()) {
+            if let Some(fin_format) = self.format {
+                builder = builder.format(fin_format);
+            }
+            if let Some(compression) = self.compression {
+                builder = builder.compression(compression);
+            }
+        }
+        builder
+    }
+
+    /// Путь файла с учетом каталога: относительный путь отсчитывается от dir
+    pub fn resolve(&self, path: &Path) -> PathBuf {
+        match &self.dir {
+            Some(dir) if path.is_relative() => dir.join(path),
+            _ => path.to_path_buf(),
+        }
+    }
+}
+
+/// Настройки утилит
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+pub struct Config {
+    /// Настройки чтения
+    pub input: Section,
+    /// Настройки записи
+    pub output: Section,
+}
+
+impl Config {
+    /// Загрузка настроек: из файла path, если он задан, иначе из [CONFIG_FILE]
+    /// в текущем каталоге, если он есть. Без файла настройки пустые
+    pub fn load(path: Option<&Path>) -> Result<Self, ParsError> {
+        match path {
+            Some(path) => Self::from_file(path),
+            None if Path::new(CONFIG_FILE).is_file() => Self::from_file(Path::new(CONFIG_FILE)),
+            None => Ok(Self::default()),
+        }
+    }
+
+    /// Чтение настроек из файла
+    pub fn from_file(path: &Path) -> Result<Self, ParsError> {
+        let text = fs::read_to_string(path)?;
+        Self::parse(&text).map_err(|e| match e {
+            ParsError::WrongFormat(msg) => {
+                ParsError::WrongFormat(format!("{}: {msg}", path.display()))
+            }
+            e => e,
+        })
+    }
+
+    /// Разбор текста настроек
+    pub fn parse(text: &str) -> Result<Self, ParsError> {
+        let mut common = Section::default();
+        let mut input = Section::default();
+        let mut output = Section::default();
+        let mut section = &mut common;
+        for (idx, line) in text.lines().enumerate() {
+            let error = |msg: String| ParsError::WrongFormat(format!("строка {}: {msg}", idx + 1));
+            let line = strip_comment(line).trim();
+            if line.is_empty() {
+                continue;
+            }
+            if let Some(name) = line.strip_prefix('[').and_then(|val| val.strip_suffix(']')) {
+                section = match name.trim() {
+                    "input" => &mut input,
+                    "output" => &mut output,
+                    name => return Err(error(format!("неизвестный раздел {name}"))),
+                };
+                continue;
+            }
+            let Some((key, value)) = line.split_once('=') else {
+                return Err(error(format!("ожидается ключ = значение: {line}")));
+            };
+            set_value(section, key.trim(), value.trim()).map_err(error)?;
+        }
+        Ok(Self {
+            input: input.or(&common),
+            output: output.or(&common),
+        })
+    }
+}
+
+/// Строка без комментария. Символ `#` внутри кавычек комментарием не считается
+fn strip_comment(line: &str) -> &str {
+    let mut quoted = false;
+    let mut escaped = false;
+    for (idx, ch) in line.char_indices() {
+        match ch {
+            _ if escaped => escaped = false,
+            '\\' if quoted => escaped = true,
+            '"' => quoted = !quoted,
+            '#' if !quoted => return &line[..idx],
+            _ => {}
+        }
+    }
+    line
+}
+
+/// Строка в двойных кавычках с экранированием `\"`, `\\`, `\t`
+fn parse_string(value: &str) -> Result<String, String> {
+    let inner = value
+        .strip_prefix('"')
+        .and_then(|val| val.strip_suffix('"'))
+        .ok_or_else(|| format!("ожидается строка в кавычках: {value}"))?;
+    let mut res = String::with_capacity(inner.len());
+    let mut chars = inner.chars();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '\\' => match chars.next() {
+                Some('"') => res.push('"'),
+                Some('\\') => res.push('\\'),
+                Some('t') => res.push('\t'),
+                _ => return Err(format!("неизвестная escape-последовательность в {value}")),
+            },
+            ch => res.push(ch),
+        }
+    }
+    Ok(res)
+}
+
+fn parse_bool(value: &str) -> Result<bool, String> {
+    match value {
+        "true" => Ok(true),
+        "false" => Ok(false),
+        _ => Err(format!("ожидается true или false: {value}")),
+    }
+}
+
+fn set_value(section: &mut Section, key: &str, value: &str) -> Result<(), String> {
+    let string = || parse_string(value);
+    match key {
+        "format" => section.format = Some(string()?.parse().map_err(|e| format!("{e}"))?),
+        "delimiter" => {
+            let delimiter = string()?;
+            match delimiter.as_bytes() {
+                [byte] => section.delimiter = Some(*byte),
+                _ => return Err(format!("разделитель должен быть одним байтом: {value}")),
+            }
+        }
+        "timestamp_unit" => {
+            section.timestamp_unit = Some(string()?.parse().map_err(|e| format!("{e}"))?)
+        }
+        "strict" => section.strict = Some(parse_bool(value)?),
+        "compression" => section.compression = Some(string()?.parse().map_err(|e| format!("{e}"))?),
+        "bin_version" => section.bin_version = Some(string()?.parse().map_err(|e| format!("{e}"))?),
+        "merkle" => section.merkle = Some(parse_bool(value)?),
+        "dir" => section.dir = Some(PathBuf::from(string()?)),
+        _ => return Err(format!("неизвестный ключ {key}")),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let config = Config::parse(
+            "# общие настройки\n\
+            delimiter = \";\"\n\
+            timestamp_unit = \"seconds\"\n\
+            \n\
+            [input]\n\
+            format = \"csv\" # партнерские выгрузки\n\
+            strict = false\n\
+            [output]\n\
+            delimiter = \"\\t\"\n\
+            compression = \"gzip\"\n\
+            bin_version = \"2\"\n\
+            merkle = true\n\
+            dir = \"/data/#out\"\n",
+        )
+        .unwrap();
+        assert_eq!(config.input.format, Some(Format::Csv));
+        assert_eq!(config.input.delimiter, Some(b';'));
+        assert_eq!(config.input.strict, Some(false));
+        assert_eq!(config.output.delimiter, Some(b'\t'));
+        assert_eq!(config.output.timestamp_unit, Some(TimestampUnit::Seconds));
+        assert_eq!(config.output.compression, Some(Compression::Gzip));
+        assert_eq!(config.output.bin_version, Some(BinVersion::V2));
+        assert!(config.output.writer_builder().options().merkle);
+        assert_eq!(
+            config.output.resolve(Path::new("a.bin")),
+            Path::new("/data/#out/a.bin")
+        );
+        assert_eq!(
+            config.input.format_for(Path::new("a.bin")),
+            Some(Format::Bin)
+        );
+        assert_eq!(config.input.format_for(Path::new("-")), Some(Format::Csv));
+        assert!(!config.input.reader_builder().options().strict);
+        let builder = config.output.writer_builder_for(Some(Path::new("a.csv")));
+        assert_eq!(builder.options().compression, Compression::None);
+        let builder = config.output.writer_builder_for(None);
+        assert_eq!(builder.options().compression, Compression::Gzip);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let err = Config::parse("[input]\nformat = csv\n").unwrap_err();
+        assert!(err.to_string().contains("строка 2"));
+        assert!(Config::parse("[filters]\n").is_err());
+        assert!(Config::parse("delimiter = \";;\"\n").is_err());
+        assert!(Config::parse("strict = yes\n").is_err());
+        assert!(Config::parse("colour = \"red\"\n").is_err());
+        assert_eq!(Config::parse("").unwrap(), Config::default());
+    }
+}
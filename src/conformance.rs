@@ -0,0 +1,317 @@
+use super::error::ParsError;
+use super::format::Format;
+use super::options::{ReaderOptions, WriterOptions};
+use super::reconcile::diff_fields;
+use super::transaction::{Transaction, TxStatus, TxType};
+use super::tx_format::{TxReader, TxWriter};
+use chrono::DateTime;
+use std::io::{self, Cursor, Write};
+use std::sync::{Arc, Mutex};
+
+/// Канонические байты [vectors] в формате bin. Эталонные файлы лежат в каталоге
+/// `conformance` репозитория и могут использоваться реализациями на других языках
+const BIN_VECTORS: &[u8] = include_bytes!("../conformance/vectors.bin");
+/// Канонические байты [vectors] в формате csv
+const CSV_VECTORS: &[u8] = include_bytes!("../conformance/vectors.csv");
+/// Канонические байты [vectors] в формате text
+const TEXT_VECTORS: &[u8] = include_bytes!("../conformance/vectors.txt");
+
+/// Канонические тестовые транзакции: граничные значения идентификаторов, сумм
+/// и времени, все типы и статусы, пустое описание, кириллица и эмодзи, знаки
+/// препинания, пробелы по краям и переводы строк в описании. Значения в
+/// независимом от формата виде (json по строке на транзакцию) лежат в
+/// `conformance/vectors.jsonl`
+pub fn vectors() -> Vec<Transaction> {
+    let tx =
+        |tx_id, tx_type, from_user_id, to_user_id, amount, millis, status, description: &str| {
+            Transaction {
+                tx_id,
+                tx_type,
+                from_user_id,
+                to_user_id,
+                amount,
+                timestamp: DateTime::from_timestamp_millis(millis).unwrap_or_default(),
+                status,
+                description: description.to_owned(),
+            }
+        };
+    vec![
+        tx(
+            1,
+            TxType::Deposit,
+            0,
+            1,
+            100,
+            1633036860000,
+            TxStatus::Success,
+            "Terminal deposit",
+        ),
+        tx(
+            u64::MAX,
+            TxType::Transfer,
+            u64::MAX,
+            1,
+            i64::MAX,
+            253402300799999,
+            TxStatus::Pending,
+            "Перевод между счетами",
+        ),
+        tx(
+            0,
+            TxType::Withdrawal,
+            1,
+            0,
+            i64::MIN,
+            0,
+            TxStatus::Failure,
+            "",
+        ),
+        tx(
+            42,
+            TxType::Transfer,
+            7,
+            9,
+            -1,
+            1,
+            TxStatus::Success,
+            "Commas, semicolons; colons: #hash",
+        ),
+        tx(
+            43,
+            TxType::Deposit,
+            0,
+            2,
+            0,
+            1633036860123,
+            TxStatus::Pending,
+            "Line one\nLine two\twith tab",
+        ),
+        tx(
+            44,
+            TxType::Withdrawal,
+            3,
+            0,
+            1,
+            1633036920000,
+            TxStatus::Failure,
+            "  spaces around  ",
+        ),
+        tx(
+            45,
+            TxType::Transfer,
+            9,
+            7,
+            250,
+            1633036980000,
+            TxStatus::Success,
+            "Оплата ☕ 🚀",
+        ),
+    ]
+}
+
+/// Канонические байты встроенного формата для [vectors]. Для пользовательских форматов None
+pub fn canonical_bytes(fin_format: Format) -> Option<&'static [u8]> {
+    match fin_format {
+        Format::Bin => Some(BIN_VECTORS),
+        Format::Csv => Some(CSV_VECTORS),
+        Format::Text => Some(TEXT_VECTORS),
+        Format::Custom(_) => None,
+    }
+}
+
+/// Проверка формата на [vectors]. Повторная запись векторов должна давать одинаковые
+/// байты, совпадающие с [canonical_bytes] для встроенных форматов, а чтение записанных
+/// и канонических байт — возвращать исходные транзакции без изменений.
+/// Ошибка описывает первое найденное расхождение
+///
+/// ```
+/// use fin_parser::conformance::verify_roundtrip;
+/// use fin_parser::format::Format;
+///
+/// for fin_format in Format::ALL {
+///     verify_roundtrip(fin_format).unwrap();
+/// }
+/// ```
+pub fn verify_roundtrip(fin_format: Format) -> Result<(), ParsError> {
+    let error = |msg: String| ParsError::WrongFormat(format!("Формат {fin_format}: {msg}"));
+    let txs = vectors();
+    let data = encode(fin_format, &txs)?;
+    if encode(fin_format, &txs)? != data {
+        return Err(error(
+            "повторная запись векторов дает другие байты".to_owned(),
+        ));
+    }
+    if let Some(canonical) = canonical_bytes(fin_format) {
+        compare_bytes(&data, canonical).map_err(error)?;
+    }
+    let mut reader =
+        TxReader::with_custom(Cursor::new(data), fin_format, ReaderOptions::default())?;
+    compare_txs(&reader.read_all()?, &txs).map_err(error)?;
+    if let Some(canonical) = canonical_bytes(fin_format) {
+        let mut reader = TxReader::new(Cursor::new(canonical), fin_format)?;
+        compare_txs(&reader.read_all()?, &txs)
+            .map_err(|msg| error(format!("канонические байты: {msg}")))?;
+    }
+    Ok(())
+}
+
+/// Буфер в памяти, доступный после удаления писателя: поток пользовательского
+/// формата нельзя получить обратно через [TxWriter::into_inner]
+#[derive(Clone, Default)]
+struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut data = self
+            .0
+            .lock()
+            .map_err(|_| io::Error::other("Буфер недоступен"))?;
+        data.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+fn encode(fin_format: Format, txs: &[Transaction]) -> Result<Vec<u8>, ParsError> {
+    let buffer = SharedBuffer::default();
+    let mut writer = TxWriter::with_custom(buffer.clone(), fin_format, WriterOptions::default())?;
+    writer.write_all(txs)?;
+    writer.finish()?;
+    drop(writer);
+    let data = buffer
+        .0
+        .lock()
+        .map_err(|_| io::Error::other("Буфер недоступен"))?;
+    Ok(data.clone())
+}
+
+fn compare_bytes(data: &[u8], canonical: &[u8]) -> Result<(), String> {
+    let pos = data
+        .iter()
+        .zip(canonical)
+        .position(|(left, right)| left != right);
+    match pos {
+        Some(pos) => Err(format!(
+            "байт {pos}: 0x{:02x} вместо канонического 0x{:02x}",
+            data[pos], canonical[pos]
+        )),
+        None if data.len() != canonical.len() => Err(format!(
+            "записано {} байт вместо {} канонических",
+            data.len(),
+            canonical.len()
+        )),
+        None => Ok(()),
+    }
+}
+
+fn compare_txs(read: &[Transaction], txs: &[Transaction]) -> Result<(), String> {
+    for (idx, (left, right)) in read.iter().zip(txs).enumerate() {
+        if left.tx_id != right.tx_id {
+            return Err(format!(
+                "вектор {idx}: TX_ID {} вместо {}",
+                left.tx_id, right.tx_id
+            ));
+        }
+        if let Some(diff) = diff_fields(left, right).first() {
+            return Err(format!(
+                "вектор {idx}: {} {:?} вместо {:?}",
+                diff.field.name(),
+                diff.left,
+                diff.right
+            ));
+        }
+    }
+    match read.len() == txs.len() {
+        true => Ok(()),
+        false => Err(format!(
+            "прочитано {} транзакций вместо {}",
+            read.len(),
+            txs.len()
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::{TransactionRead, TransactionWrite, TxFormat, register_format};
+    use std::io::Read;
+
+    /// csv, у которого писатель по флагу обрезает пробелы по краям описания
+    struct CsvAlias(&'static str, bool);
+
+    struct TrimWriter(TxWriter<Box<dyn Write + Send>>);
+
+    impl TransactionWrite for TrimWriter {
+        fn write_transaction(&mut self, tx: &Transaction) -> Result<(), ParsError> {
+            let trimmed = Transaction {
+                tx_id: tx.tx_id,
+                tx_type: tx.tx_type,
+                from_user_id: tx.from_user_id,
+                to_user_id: tx.to_user_id,
+                amount: tx.amount,
+                timestamp: tx.timestamp,
+                status: tx.status,
+                description: tx.description.trim().to_owned(),
+            };
+            self.0.write_transaction(&trimmed)
+        }
+
+        fn finish(&mut self) -> Result<(), ParsError> {
+            self.0.finish()
+        }
+    }
+
+    impl TxFormat for CsvAlias {
+        fn name(&self) -> &'static str {
+            self.0
+        }
+
+        fn reader(
+            &self,
+            stream: Box<dyn Read + Send>,
+        ) -> Result<Box<dyn TransactionRead + Send>, ParsError> {
+            Ok(Box::new(TxReader::new(stream, Format::Csv)?))
+        }
+
+        fn writer(
+            &self,
+            stream: Box<dyn Write + Send>,
+        ) -> Result<Box<dyn TransactionWrite + Send>, ParsError> {
+            let writer = TxWriter::new(stream, Format::Csv)?;
+            match self.1 {
+                true => Ok(Box::new(TrimWriter(writer))),
+                false => Ok(Box::new(writer)),
+            }
+        }
+    }
+
+    #[test]
+    fn test_builtin_formats() {
+        for fin_format in Format::ALL {
+            verify_roundtrip(fin_format).unwrap();
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_json_vectors() {
+        let json: String = vectors().iter().map(|tx| tx.to_json() + "\n").collect();
+        assert_eq!(
+            json.as_bytes(),
+            include_bytes!("../conformance/vectors.jsonl")
+        );
+    }
+
+    #[test]
+    fn test_custom_format() {
+        let alias = register_format(CsvAlias("conformance_csv", false)).unwrap();
+        verify_roundtrip(alias).unwrap();
+        let lossy = register_format(CsvAlias("conformance_trim", true)).unwrap();
+        let e = verify_roundtrip(lossy).unwrap_err().to_string();
+        assert!(e.contains("вектор 5: DESCRIPTION"), "{e}");
+    }
+}
@@ -1,5 +1,11 @@
 pub const CNT_VALUES: usize = 8;
 
+pub const MAGIC: u32 = 0x5950424E;
+/// Сигнатура заголовка со схемой полей в начале файла bin версии 2 ('YPBS')
+pub const SCHEMA_MAGIC: u32 = 0x59504253;
+/// Сигнатура блока с корнем дерева Меркла в конце файла bin ('YPBM')
+pub const MERKLE_MAGIC: u32 = 0x5950424D;
+
 pub const TX_ID: &str = "TX_ID";
 pub const TX_TYPE: &str = "TX_TYPE";
 pub const FROM_USER_ID: &str = "FROM_USER_ID";
@@ -9,6 +15,17 @@ pub const TIMESTAMP: &str = "TIMESTAMP";
 pub const STATUS: &str = "STATUS";
 pub const DESCRIPTION: &str = "DESCRIPTION";
 
+pub const HEADER_VALUES: [&str; CNT_VALUES] = [
+    TX_ID,
+    TX_TYPE,
+    FROM_USER_ID,
+    TO_USER_ID,
+    AMOUNT,
+    TIMESTAMP,
+    STATUS,
+    DESCRIPTION,
+];
+
 pub const DEPOSIT: &str = "DEPOSIT";
 pub const TRANSFER: &str = "TRANSFER";
 pub const WITHDRAWAL: &str = "WITHDRAWAL";
@@ -16,3 +33,6 @@ pub const WITHDRAWAL: &str = "WITHDRAWAL";
 pub const SUCCESS: &str = "SUCCESS";
 pub const FAILURE: &str = "FAILURE";
 pub const PENDING: &str = "PENDING";
+
+/// Максимальная длина фрагмента исходной записи, сохраняемого для сообщений об ошибках
+pub const MAX_SNIPPET_LEN: usize = 256;
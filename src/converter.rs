@@ -0,0 +1,641 @@
+use super::error::ParsError;
+use super::format::{TransactionRead, TransactionWrite};
+use super::tx_format::{TxReader, TxWriter};
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+use std::io::{Read, Write};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+/// Количество записей в одной части потока при параллельной конвертации по умолчанию
+#[cfg(feature = "parallel")]
+pub const DEFAULT_CHUNK_RECORDS: usize = 16 * 1024;
+
+/// Количество транзакций, одновременно находящихся в памяти при конвертации по умолчанию
+pub const DEFAULT_MAX_IN_FLIGHT: usize = 1024;
+
+/// Интервал в записях между вызовами обработчика хода конвертации по умолчанию
+pub const DEFAULT_PROGRESS_RECORDS: u64 = 10_000;
+
+/// Статистика конвертации
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Default)]
+pub struct ConversionStats {
+    /// Количество записанных транзакций
+    pub records: u64,
+    /// Количество байт, прочитанных из потока формата (после распаковки).
+    /// Для пользовательских форматов всегда 0
+    pub bytes_in: u64,
+    /// Количество байт, записанных в поток формата (до сжатия).
+    /// Для пользовательских форматов всегда 0
+    pub bytes_out: u64,
+    /// Длительность конвертации
+    pub duration: Duration,
+}
+
+/// Потоковая конвертация транзакций из одного формата в другой
+/// с буфером на [DEFAULT_MAX_IN_FLIGHT] транзакций, см. [convert_bounded].
+/// Возвращается количество записанных транзакций
+pub fn convert<R, W>(from: &mut R, to: &mut W) -> Result<u64, ParsError>
+where
+    R: TransactionRead + ?Sized,
+    W: TransactionWrite + ?Sized,
+{
+    convert_bounded(from, to, DEFAULT_MAX_IN_FLIGHT)
+}
+
+/// Потоковая конвертация, при которой в памяти одновременно находится не более
+/// max_in_flight транзакций (не меньше одной): они читаются пачкой и записываются
+/// одним вызовом [TransactionWrite::write_batch], после чего буфер переиспользуется.
+/// Пиковая память не зависит от размера потока и складывается из буфера транзакций
+/// (каждая с описанием не длиннее [crate::options::ReaderOptions::max_description_len]) и
+/// внутренних буферов читателя и писателя. Запись блокируется, пока писатель не примет
+/// пачку, поэтому чтение не опережает медленный выходной поток.
+/// Транзакции, прочитанные до ошибки чтения, записываются перед ее возвратом.
+/// С feature `tracing` конвертация выполняется в span `convert` с событиями о записанных
+/// пачках, завершении и ошибке, а читатели и писатели сообщают об ошибках записей и сбросе буферов.
+/// Возвращается количество записанных транзакций
+pub fn convert_bounded<R, W>(
+    from: &mut R,
+    to: &mut W,
+    max_in_flight: usize,
+) -> Result<u64, ParsError>
+where
+    R: TransactionRead + ?Sized,
+    W: TransactionWrite + ?Sized,
+{
+    #[cfg(feature = "tracing")]
+    let _span = tracing::info_span!("convert", max_in_flight).entered();
+    let mut cnt = 0;
+    let res = convert_batches(from, to, max_in_flight.max(1), &mut cnt);
+    #[cfg(feature = "tracing")]
+    match &res {
+        Ok(()) => tracing::debug!(records = cnt, "Конвертация завершена"),
+        Err(e) => tracing::error!(records = cnt, error = %e, "Ошибка конвертации"),
+    }
+    res.map(|()| cnt)
+}
+
+/// Перенос транзакций пачками, cnt — количество уже записанных транзакций
+fn convert_batches<R, W>(
+    from: &mut R,
+    to: &mut W,
+    max_in_flight: usize,
+    cnt: &mut u64,
+) -> Result<(), ParsError>
+where
+    R: TransactionRead + ?Sized,
+    W: TransactionWrite + ?Sized,
+{
+    let mut txs = Vec::new();
+    loop {
+        txs.clear();
+        let res = from.read_batch(&mut txs, max_in_flight);
+        if !txs.is_empty() {
+            to.write_batch(&txs)?;
+            *cnt += txs.len() as u64;
+            #[cfg(feature = "tracing")]
+            tracing::trace!(
+                batch = txs.len(),
+                records = *cnt,
+                "Записана пачка транзакций"
+            );
+        }
+        if res? == 0 {
+            return Ok(());
+        }
+    }
+}
+
+/// Потоковая конвертация аналогично [convert_bounded] с возвратом статистики.
+/// Байты считаются от текущих позиций читателя и писателя
+pub fn convert_with_stats<In: Read, Out: Write>(
+    from: &mut TxReader<In>,
+    to: &mut TxWriter<Out>,
+    max_in_flight: usize,
+) -> Result<ConversionStats, ParsError> {
+    let start = Instant::now();
+    let bytes_in = from.position().bytes;
+    let bytes_out = to.bytes_written();
+    let records = convert_bounded(from, to, max_in_flight)?;
+    Ok(ConversionStats {
+        records,
+        bytes_in: from.position().bytes - bytes_in,
+        bytes_out: to.bytes_written() - bytes_out,
+        duration: start.elapsed(),
+    })
+}
+
+/// Ход конвертации, передаваемый обработчику [convert_with_progress]
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Default)]
+pub struct Progress {
+    /// Количество записанных транзакций
+    pub records: u64,
+    /// Количество байт, прочитанных из потока формата (после распаковки)
+    pub bytes_in: u64,
+    /// Количество байт, записанных в поток формата (до сжатия)
+    pub bytes_out: u64,
+    /// Время от начала конвертации
+    pub elapsed: Duration,
+    /// Конвертация завершена, вызов обработчика последний
+    pub done: bool,
+}
+
+/// Настройки конвертации с отслеживанием хода
+#[derive(Clone, Debug)]
+pub struct ProgressOptions {
+    every_records: Option<u64>,
+    every_bytes: Option<u64>,
+    max_in_flight: usize,
+    cancel: Option<Arc<AtomicBool>>,
+}
+
+impl Default for ProgressOptions {
+    fn default() -> Self {
+        Self {
+            every_records: Some(DEFAULT_PROGRESS_RECORDS),
+            every_bytes: None,
+            max_in_flight: DEFAULT_MAX_IN_FLIGHT,
+            cancel: None,
+        }
+    }
+}
+
+impl ProgressOptions {
+    /// Настройки по умолчанию: обработчик вызывается каждые [DEFAULT_PROGRESS_RECORDS]
+    /// записей, отмена не предусмотрена
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Вызов обработчика каждые n записей (не меньше одной). None отключает интервал в записях
+    pub fn with_every_records(mut self, n: Option<u64>) -> Self {
+        self.every_records = n.map(|n| n.max(1));
+        self
+    }
+
+    /// Вызов обработчика каждые n прочитанных байт (не меньше одного).
+    /// None отключает интервал в байтах
+    pub fn with_every_bytes(mut self, n: Option<u64>) -> Self {
+        self.every_bytes = n.map(|n| n.max(1));
+        self
+    }
+
+    /// Наибольшее количество транзакций в памяти, см. [convert_bounded]
+    pub fn with_max_in_flight(mut self, max_in_flight: usize) -> Self {
+        self.max_in_flight = max_in_flight;
+        self
+    }
+
+    /// Флаг отмены. Установленный флаг, в том числе из обработчика или другого потока,
+    /// прерывает конвертацию перед чтением следующей пачки
+    pub fn with_cancel_flag(mut self, cancel: Arc<AtomicBool>) -> Self {
+        self.cancel = Some(cancel);
+        self
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancel
+            .as_ref()
+            .is_some_and(|cancel| cancel.load(Ordering::Relaxed))
+    }
+
+    /// Пора ли вызвать обработчик после reported
+    fn is_due(&self, progress: &Progress, reported: &Progress) -> bool {
+        let due = |every: Option<u64>, val: u64, prev: u64| every.is_some_and(|n| val - prev >= n);
+        due(self.every_records, progress.records, reported.records)
+            || due(self.every_bytes, progress.bytes_in, reported.bytes_in)
+    }
+}
+
+/// Потоковая конвертация аналогично [convert_bounded] с вызовом обработчика хода
+/// по достижении интервала из настроек и по завершении (с [Progress::done]).
+/// Интервалы проверяются после записи каждой пачки, поэтому пачка не длиннее интервала
+/// в записях. При установленном флаге отмены конвертация прерывается с ошибкой
+/// [ParsError::Cancelled], уже прочитанные транзакции к этому моменту записаны.
+/// Байты считаются от текущих позиций читателя и писателя
+///
+/// ```
+/// use fin_parser::converter::{ProgressOptions, convert_with_progress};
+/// use fin_parser::format::Format;
+/// use fin_parser::tx_format::{TxReader, TxWriter};
+/// use std::io::Cursor;
+///
+/// let csv = "TX_ID,TX_TYPE,FROM_USER_ID,TO_USER_ID,AMOUNT,TIMESTAMP,STATUS,DESCRIPTION\n\
+///     1,DEPOSIT,0,5,100,1633036860000,SUCCESS,\"Record number 1\"\n\
+///     2,DEPOSIT,0,6,200,1633036920000,SUCCESS,\"Record number 2\"\n";
+/// let mut reader = TxReader::new(Cursor::new(csv), Format::Csv).unwrap();
+/// let mut writer = TxWriter::new(Vec::new(), Format::Bin).unwrap();
+/// let options = ProgressOptions::new().with_every_records(Some(1));
+/// let mut calls = Vec::new();
+/// convert_with_progress(&mut reader, &mut writer, &options, |progress| {
+///     calls.push((progress.records, progress.done));
+/// })
+/// .unwrap();
+/// assert_eq!(calls, [(1, false), (2, false), (2, true)]);
+/// ```
+pub fn convert_with_progress<In, Out, F>(
+    from: &mut TxReader<In>,
+    to: &mut TxWriter<Out>,
+    options: &ProgressOptions,
+    mut on_progress: F,
+) -> Result<ConversionStats, ParsError>
+where
+    In: Read,
+    Out: Write,
+    F: FnMut(&Progress),
+{
+    let start = Instant::now();
+    let bytes_in = from.position().bytes;
+    let bytes_out = to.bytes_written();
+    let batch_len = match options.every_records {
+        Some(n) => options.max_in_flight.min(n as usize),
+        None => options.max_in_flight,
+    }
+    .max(1);
+    let mut progress = Progress::default();
+    let mut reported = Progress::default();
+    let mut txs = Vec::new();
+    loop {
+        if options.is_cancelled() {
+            return Err(ParsError::Cancelled {
+                records: progress.records,
+            });
+        }
+        txs.clear();
+        let res = from.read_batch(&mut txs, batch_len);
+        if !txs.is_empty() {
+            to.write_batch(&txs)?;
+        }
+        progress = Progress {
+            records: progress.records + txs.len() as u64,
+            bytes_in: from.position().bytes - bytes_in,
+            bytes_out: to.bytes_written() - bytes_out,
+            elapsed: start.elapsed(),
+            done: false,
+        };
+        if res? == 0 {
+            progress.done = true;
+            on_progress(&progress);
+            return Ok(ConversionStats {
+                records: progress.records,
+                bytes_in: progress.bytes_in,
+                bytes_out: progress.bytes_out,
+                duration: progress.elapsed,
+            });
+        }
+        if options.is_due(&progress, &reported) {
+            on_progress(&progress);
+            reported = progress;
+        }
+    }
+}
+
+/// Параллельная конвертация транзакций (требует feature `parallel`).
+/// Поток делится на части по chunk_records целых записей, части разбираются
+/// и сериализуются в пуле потоков rayon, а результат записывается в исходном порядке.
+/// Ошибка в любой записи прерывает конвертацию, место ошибки указывается
+/// от начала исходного потока. Если один из форматов пользовательский,
+/// выполняется обычная последовательная конвертация [convert].
+/// Возвращается количество записанных транзакций
+#[cfg(feature = "parallel")]
+pub fn convert_parallel<In: Read, Out: Write>(
+    from: &mut TxReader<In>,
+    to: &mut TxWriter<Out>,
+    chunk_records: usize,
+) -> Result<u64, ParsError> {
+    convert_parallel_with_progress(from, to, chunk_records, |_| ())
+}
+
+/// Параллельная конвертация [convert_parallel] с отчетом о ходе: on_written
+/// вызывается в потоке вызывающего после записи каждой части с количеством
+/// записанных в ней транзакций
+#[cfg(feature = "parallel")]
+pub fn convert_parallel_with_progress<In, Out, F>(
+    from: &mut TxReader<In>,
+    to: &mut TxWriter<Out>,
+    chunk_records: usize,
+    mut on_written: F,
+) -> Result<u64, ParsError>
+where
+    In: Read,
+    Out: Write,
+    F: FnMut(u64),
+{
+    let chunk_records = chunk_records.max(1);
+    #[cfg(feature = "tracing")]
+    let _span = tracing::info_span!("convert_parallel", chunk_records).entered();
+    let mut cnt = 0;
+    if let Some(tx) = from.take_peeked() {
+        to.write_transaction(&tx)?;
+        cnt += 1;
+        on_written(1);
+    }
+    if to.is_custom() {
+        let rest = convert(from, to)?;
+        on_written(rest);
+        return Ok(cnt + rest);
+    }
+    // Одновременно в памяти держится по две части на поток пула
+    let batch_len = rayon::current_num_threads() * 2;
+    loop {
+        let mut batch = Vec::with_capacity(batch_len);
+        while batch.len() < batch_len {
+            let Some(chunk) = from.read_raw_chunk(chunk_records)? else {
+                let rest = convert(from, to)?;
+                on_written(rest);
+                return Ok(cnt + rest);
+            };
+            if chunk.records == 0 {
+                break;
+            }
+            let writer = to.chunk_writer()?;
+            let last = chunk.records < chunk_records;
+            batch.push((chunk, writer));
+            if last {
+                break;
+            }
+        }
+        if batch.is_empty() {
+            return Ok(cnt);
+        }
+        let done = batch.len() < batch_len;
+
+        let results: Vec<Result<(Vec<u8>, u64), ParsError>> = batch
+            .into_par_iter()
+            .map(|(chunk, mut writer)| {
+                let txs = chunk.parse()?;
+                writer.write_batch(&txs)?;
+                Ok((writer.into_inner()?, txs.len() as u64))
+            })
+            .collect();
+        for res in results {
+            let (data, records) = res?;
+            to.write_raw(&data, records)?;
+            cnt += records;
+            on_written(records);
+            #[cfg(feature = "tracing")]
+            tracing::trace!(batch = records, records = cnt, "Записана часть потока");
+        }
+        if done {
+            return Ok(cnt);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::Format;
+    #[cfg(feature = "parallel")]
+    use crate::test_util::{encode, txs};
+    #[cfg(feature = "parallel")]
+    use crate::transaction::*;
+    use std::io::Cursor;
+
+    const CSV_MULT: &str = r#"TX_ID,TX_TYPE,FROM_USER_ID,TO_USER_ID,AMOUNT,TIMESTAMP,STATUS,DESCRIPTION
+1000000000000000,DEPOSIT,0,9223372036854775807,100,1633036860000,FAILURE,"Record number 1"
+1000000000000001,TRANSFER,9223372036854775807,9223372036854775807,200,1633036920000,PENDING,"Record number 2"
+"#;
+
+    #[test]
+    fn test_convert() {
+        let mut reader = TxReader::new(Cursor::new(CSV_MULT.as_bytes()), Format::Csv).unwrap();
+        let mut writer = TxWriter::new(Cursor::new(Vec::new()), Format::Bin).unwrap();
+        let cnt = convert(&mut reader, &mut writer).unwrap();
+        assert_eq!(cnt, 2);
+
+        let buf = writer.into_inner().unwrap().into_inner();
+        let mut bin_reader = TxReader::new(Cursor::new(buf), Format::Bin).unwrap();
+        let txs = bin_reader.read_all().unwrap();
+        assert_eq!(txs.len(), 2);
+        assert_eq!(txs[1].description, "Record number 2");
+    }
+
+    #[test]
+    fn test_convert_with_stats() {
+        for max_in_flight in [0, 1, 100] {
+            let mut reader = TxReader::new(Cursor::new(CSV_MULT.as_bytes()), Format::Csv).unwrap();
+            let mut writer = TxWriter::new(Vec::new(), Format::Bin).unwrap();
+            let stats = convert_with_stats(&mut reader, &mut writer, max_in_flight).unwrap();
+            assert_eq!(stats.records, 2);
+            assert_eq!(stats.bytes_in, CSV_MULT.len() as u64);
+            let buf = writer.into_inner().unwrap();
+            assert_eq!(stats.bytes_out, buf.len() as u64);
+        }
+    }
+
+    #[test]
+    fn test_convert_bounded_error() {
+        let input = CSV_MULT.replacen("TRANSFER", "TRANSFUR", 1);
+        let mut reader = TxReader::new(Cursor::new(input.into_bytes()), Format::Csv).unwrap();
+        let mut writer = TxWriter::new(Vec::new(), Format::Text).unwrap();
+        assert!(convert_bounded(&mut reader, &mut writer, 10).is_err());
+
+        let buf = writer.into_inner().unwrap();
+        let txs = TxReader::new(Cursor::new(buf), Format::Text)
+            .unwrap()
+            .read_all()
+            .unwrap();
+        assert_eq!(txs.len(), 1);
+        assert_eq!(txs[0].description, "Record number 1");
+    }
+
+    #[test]
+    fn test_convert_with_progress() {
+        // Заголовок и две пары записей
+        let input = format!("{CSV_MULT}{}", CSV_MULT.split_once('\n').unwrap().1);
+        let mut reader = TxReader::new(Cursor::new(input.into_bytes()), Format::Csv).unwrap();
+        let mut writer = TxWriter::new(Vec::new(), Format::Text).unwrap();
+        let options = ProgressOptions::new()
+            .with_every_records(None)
+            .with_every_bytes(Some(100));
+        let mut calls = Vec::new();
+        let stats = convert_with_progress(&mut reader, &mut writer, &options, |progress| {
+            calls.push(*progress)
+        })
+        .unwrap();
+        assert_eq!(stats.records, 4);
+        assert!(calls.len() > 1);
+        assert!(
+            calls
+                .windows(2)
+                .all(|pair| pair[0].records <= pair[1].records)
+        );
+        let last = calls.last().unwrap();
+        assert!(last.done);
+        assert_eq!((last.records, last.bytes_out), (4, stats.bytes_out));
+
+        // Флаг, установленный обработчиком, прерывает конвертацию после первой пачки
+        let cancel = Arc::new(AtomicBool::new(false));
+        let options = ProgressOptions::new()
+            .with_every_records(Some(1))
+            .with_cancel_flag(cancel.clone());
+        let mut reader = TxReader::new(Cursor::new(CSV_MULT.as_bytes()), Format::Csv).unwrap();
+        let mut writer = TxWriter::new(Vec::new(), Format::Bin).unwrap();
+        let err = convert_with_progress(&mut reader, &mut writer, &options, |_| {
+            cancel.store(true, Ordering::Relaxed)
+        })
+        .unwrap_err();
+        assert!(matches!(err, ParsError::Cancelled { records: 1 }));
+        let buf = writer.into_inner().unwrap();
+        assert_eq!(
+            TxReader::new(Cursor::new(buf), Format::Bin)
+                .unwrap()
+                .count()
+                .unwrap(),
+            1
+        );
+    }
+
+    /// Подписчик tracing, сохраняющий уровень и текст событий
+    #[cfg(feature = "tracing")]
+    #[derive(Clone, Default)]
+    struct EventLog(std::sync::Arc<std::sync::Mutex<Vec<(tracing::Level, String)>>>);
+
+    #[cfg(feature = "tracing")]
+    impl tracing::Subscriber for EventLog {
+        fn enabled(&self, _: &tracing::Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, _: &tracing::span::Attributes<'_>) -> tracing::span::Id {
+            tracing::span::Id::from_u64(1)
+        }
+
+        fn record(&self, _: &tracing::span::Id, _: &tracing::span::Record<'_>) {}
+
+        fn record_follows_from(&self, _: &tracing::span::Id, _: &tracing::span::Id) {}
+
+        fn event(&self, event: &tracing::Event<'_>) {
+            struct Message(String);
+            impl tracing::field::Visit for Message {
+                fn record_debug(
+                    &mut self,
+                    field: &tracing::field::Field,
+                    value: &dyn std::fmt::Debug,
+                ) {
+                    if field.name() == "message" {
+                        self.0 = format!("{value:?}");
+                    }
+                }
+            }
+            let mut message = Message(String::new());
+            event.record(&mut message);
+            let level = *event.metadata().level();
+            self.0.lock().unwrap().push((level, message.0));
+        }
+
+        fn enter(&self, _: &tracing::span::Id) {}
+
+        fn exit(&self, _: &tracing::span::Id) {}
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn test_convert_tracing() {
+        use crate::options::ErrorPolicy;
+        use tracing::Level;
+
+        let log = EventLog::default();
+        tracing::subscriber::with_default(log.clone(), || {
+            let input = CSV_MULT.replacen("TRANSFER", "TRANSFUR", 1);
+            let mut reader = TxReader::new(Cursor::new(input.into_bytes()), Format::Csv)
+                .unwrap()
+                .with_error_policy(ErrorPolicy::Skip);
+            let mut writer = TxWriter::new(Vec::new(), Format::Bin).unwrap();
+            assert_eq!(convert(&mut reader, &mut writer).unwrap(), 1);
+            writer.into_inner().unwrap();
+        });
+        let events = log.0.lock().unwrap();
+        let count = |level: Level, message: &str| {
+            events
+                .iter()
+                .filter(|(lvl, msg)| *lvl == level && msg == message)
+                .count()
+        };
+        assert_eq!(count(Level::WARN, "Ошибка чтения записи"), 1);
+        assert_eq!(count(Level::TRACE, "Прочитана транзакция"), 1);
+        assert_eq!(count(Level::DEBUG, "Конвертация завершена"), 1);
+        assert_eq!(count(Level::DEBUG, "Запись завершена"), 1);
+    }
+
+    #[cfg(feature = "parallel")]
+    fn txs_for_test(cnt: u64) -> Vec<Transaction> {
+        txs(0..cnt)
+            .into_iter()
+            .map(|tx| Transaction {
+                to_user_id: tx.tx_id + 1,
+                amount: tx.tx_id as i64 * 10,
+                description: format!("Record, number {}\nsecond line", tx.tx_id),
+                ..tx
+            })
+            .collect()
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn test_convert_parallel() {
+        let txs = txs_for_test(100);
+        for from_format in Format::ALL {
+            let input = encode(&txs, from_format);
+
+            for to_format in Format::ALL {
+                let mut reader = TxReader::new(Cursor::new(input.clone()), from_format).unwrap();
+                let mut writer = TxWriter::new(Vec::new(), to_format).unwrap();
+                assert_eq!(reader.peek().unwrap(), Some(&txs[0]));
+                let mut written = Vec::new();
+                let cnt = convert_parallel_with_progress(&mut reader, &mut writer, 7, |records| {
+                    written.push(records)
+                })
+                .unwrap();
+                assert_eq!(cnt, 100);
+                assert_eq!(written.iter().sum::<u64>(), 100);
+                assert!(written.len() > 1);
+                assert_eq!(reader.position().records, 100);
+                let output = writer.into_inner().unwrap();
+
+                assert_eq!(output, encode(&txs, to_format));
+            }
+        }
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn test_convert_parallel_empty() {
+        let mut reader = TxReader::new(Cursor::new(Vec::new()), Format::Bin).unwrap();
+        let mut writer = TxWriter::new(Vec::new(), Format::Csv).unwrap();
+        assert_eq!(convert_parallel(&mut reader, &mut writer, 7).unwrap(), 0);
+        let output = writer.into_inner().unwrap();
+        assert!(output.starts_with(b"TX_ID,"));
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn test_convert_parallel_error() {
+        let data = CSV_MULT.replace("PENDING", "UNKNOWN");
+        let mut reader = TxReader::new(Cursor::new(data.into_bytes()), Format::Csv).unwrap();
+        let mut writer = TxWriter::new(Vec::new(), Format::Bin).unwrap();
+        let err = convert_parallel(&mut reader, &mut writer, 1).unwrap_err();
+        assert_eq!(err.code(), "invalid_enum_value");
+
+        // Место ошибки в части совпадает с местом при последовательном чтении
+        let txs = txs_for_test(100);
+        for from_format in [Format::Csv, Format::Text] {
+            let input = String::from_utf8(encode(&txs, from_format))
+                .unwrap()
+                .replace("530", "5z0")
+                .into_bytes();
+            let expected = TxReader::new(Cursor::new(input.clone()), from_format)
+                .unwrap()
+                .read_all()
+                .unwrap_err();
+            let mut reader = TxReader::new(Cursor::new(input), from_format).unwrap();
+            let mut writer = TxWriter::new(Vec::new(), Format::Bin).unwrap();
+            let err = convert_parallel(&mut reader, &mut writer, 7).unwrap_err();
+            assert_eq!(err.code(), "invalid_number");
+            assert_eq!(err.position().unwrap().record, 53);
+            assert_eq!(err.position(), expected.position());
+        }
+    }
+}
@@ -0,0 +1,314 @@
+use super::error::ParsError;
+use super::format::Format;
+use super::sample::SplitMix64;
+use super::tx_format::TxReader;
+use std::fmt;
+use std::io::Cursor;
+use std::ops::Range;
+
+/// Смещение поля DESC_LEN от начала записи bin
+const DESC_LEN_OFFSET: usize = 50;
+
+/// Вид повреждения файла
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Corruption {
+    /// Инвертирование одного бита записи
+    BitFlip,
+    /// Обрезка файла посреди записи: запись и все следующие за ней теряются
+    Truncate,
+    /// Повтор записи сразу после нее
+    DuplicateRecord,
+    /// Неверное значение DESC_LEN записи (только для формата bin): читатель выходит
+    /// за границу записи или не дочитывает ее
+    BadLength,
+}
+
+impl fmt::Display for Corruption {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::BitFlip => "bit-flip",
+            Self::Truncate => "truncate",
+            Self::DuplicateRecord => "duplicate",
+            Self::BadLength => "bad-length",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Внесенное повреждение
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Injection {
+    /// Вид повреждения
+    pub corruption: Corruption,
+    /// Номер поврежденной записи, начиная с нуля, в файле на момент повреждения
+    pub record: usize,
+    /// Смещение измененного байта (для обрезки — новая длина файла,
+    /// для повтора — начало копии записи)
+    pub offset: usize,
+}
+
+/// Внесение контролируемых повреждений в корректный файл транзакций для проверки
+/// восстановления при чтении
+/// ([ErrorPolicy::Skip](crate::options::ErrorPolicy::Skip)).
+/// Границы записей определяются чтением исходного файла, поэтому повреждение
+/// затрагивает ровно одну запись. Одинаковый seed дает одинаковые повреждения
+///
+/// ```
+/// use fin_parser::corrupt::{Corruption, Corruptor};
+/// use fin_parser::format::Format;
+/// use fin_parser::generate::{GenerateOptions, Generator};
+/// use fin_parser::options::ErrorPolicy;
+/// use fin_parser::tx_format::{TxReader, TxWriter};
+/// use std::io::Cursor;
+///
+/// let txs: Vec<_> = Generator::new(GenerateOptions::new(), 10).unwrap().collect();
+/// let mut writer = TxWriter::new(Vec::new(), Format::Bin).unwrap();
+/// writer.write_all(&txs).unwrap();
+///
+/// let mut corruptor = Corruptor::new(writer.into_inner().unwrap(), Format::Bin).unwrap();
+/// corruptor.inject_at(Corruption::BadLength, 3).unwrap();
+/// let data = corruptor.into_inner();
+///
+/// let mut reader = TxReader::new(Cursor::new(data), Format::Bin)
+///     .unwrap()
+///     .with_error_policy(ErrorPolicy::Skip);
+/// let read = reader.read_all().unwrap();
+/// assert_eq!(read[..3], txs[..3]);
+/// assert!(!reader.error_report().is_empty());
+/// ```
+pub struct Corruptor {
+    data: Vec<u8>,
+    fin_format: Format,
+    spans: Vec<Range<usize>>,
+    injections: Vec<Injection>,
+    rng: SplitMix64,
+}
+
+impl Corruptor {
+    /// Подготовка файла к повреждению. Файл должен читаться без ошибок,
+    /// пользовательские форматы не поддерживаются
+    pub fn new(data: Vec<u8>, fin_format: Format) -> Result<Self, ParsError> {
+        let spans = record_spans(&data, fin_format)?;
+        Ok(Self {
+            data,
+            fin_format,
+            spans,
+            injections: Vec::new(),
+            rng: SplitMix64(0),
+        })
+    }
+
+    /// Seed для выбора записей и байт. По умолчанию 0
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.rng = SplitMix64(seed);
+        self
+    }
+
+    /// Количество записей в файле с учетом внесенных повреждений
+    pub fn records(&self) -> usize {
+        self.spans.len()
+    }
+
+    /// Повреждение случайной записи
+    pub fn inject(&mut self, corruption: Corruption) -> Result<&Injection, ParsError> {
+        if self.spans.is_empty() {
+            return Err(ParsError::WrongFormat(
+                "В файле нет записей для повреждения".to_owned(),
+            ));
+        }
+        let record = self.rng.below(self.spans.len() as u64) as usize;
+        self.inject_at(corruption, record)
+    }
+
+    /// Повреждение записи с номером record. Место повреждения внутри записи
+    /// выбирается случайно
+    pub fn inject_at(
+        &mut self,
+        corruption: Corruption,
+        record: usize,
+    ) -> Result<&Injection, ParsError> {
+        let Some(span) = self.spans.get(record).cloned() else {
+            return Err(ParsError::WrongFormat(format!(
+                "Запись {record} отсутствует: в файле {} записей",
+                self.spans.len()
+            )));
+        };
+        let offset = match corruption {
+            Corruption::BitFlip => {
+                let offset = span.start + self.below(span.len());
+                self.data[offset] ^= 1 << self.rng.below(8);
+                offset
+            }
+            Corruption::Truncate => {
+                let offset = span.start + self.below(span.len());
+                self.data.truncate(offset);
+                self.spans.truncate(record + 1);
+                self.spans[record].end = offset;
+                offset
+            }
+            Corruption::DuplicateRecord => {
+                let copy = self.data[span.clone()].to_vec();
+                self.data.splice(span.end..span.end, copy);
+                for later in &mut self.spans[record + 1..] {
+                    later.start += span.len();
+                    later.end += span.len();
+                }
+                self.spans
+                    .insert(record + 1, span.end..span.end + span.len());
+                span.end
+            }
+            Corruption::BadLength => {
+                if self.fin_format != Format::Bin || span.len() < DESC_LEN_OFFSET + 4 {
+                    return Err(ParsError::WrongFormat(format!(
+                        "Повреждение {corruption} возможно только для записей формата bin"
+                    )));
+                }
+                // Изменяется один из младших 8 бит длины описания: сдвиг не больше 128 байт
+                // затрагивает соседние записи, но не остаток файла
+                let offset = span.start + DESC_LEN_OFFSET;
+                let field: [u8; 4] = self.data[offset..offset + 4]
+                    .try_into()
+                    .expect("Поле из 4 байт");
+                let len = u32::from_be_bytes(field) ^ (1 << self.rng.below(8));
+                self.data[offset..offset + 4].copy_from_slice(&len.to_be_bytes());
+                offset
+            }
+        };
+        self.injections.push(Injection {
+            corruption,
+            record,
+            offset,
+        });
+        Ok(self.injections.last().expect("Повреждение добавлено"))
+    }
+
+    /// Внесенные повреждения в порядке внесения
+    pub fn injections(&self) -> &[Injection] {
+        &self.injections
+    }
+
+    /// Текущее содержимое файла
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Извлечение поврежденного файла
+    pub fn into_inner(self) -> Vec<u8> {
+        self.data
+    }
+
+    fn below(&mut self, bound: usize) -> usize {
+        self.rng.below(bound as u64) as usize
+    }
+}
+
+/// Диапазоны байт записей корректного файла. Записи следуют друг за другом,
+/// заголовок csv в диапазоны не входит
+fn record_spans(data: &[u8], fin_format: Format) -> Result<Vec<Range<usize>>, ParsError> {
+    let mut start = match fin_format {
+        Format::Csv => data
+            .iter()
+            .position(|&byte| byte == b'\n')
+            .map_or(data.len(), |pos| pos + 1),
+        Format::Bin | Format::Text => 0,
+        Format::Custom(name) => {
+            return Err(ParsError::WrongFormat(format!(
+                "Повреждение файлов формата {name} не поддерживается"
+            )));
+        }
+    };
+    let mut reader = TxReader::new(Cursor::new(data), fin_format)?;
+    let mut spans = Vec::new();
+    while reader.skip(1)? == 1 {
+        let end = reader.position().bytes as usize;
+        spans.push(start..end);
+        start = end;
+    }
+    Ok(spans)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generate::{GenerateOptions, Generator};
+    use crate::options::ErrorPolicy;
+    use crate::transaction::Transaction;
+    use crate::tx_format::TxWriter;
+
+    fn file_for_test(fin_format: Format) -> (Vec<u8>, Vec<Transaction>) {
+        let txs: Vec<_> = Generator::new(GenerateOptions::new().with_seed(5), 20)
+            .unwrap()
+            .collect();
+        let mut writer = TxWriter::new(Vec::new(), fin_format).unwrap();
+        writer.write_all(&txs).unwrap();
+        (writer.into_inner().unwrap(), txs)
+    }
+
+    fn read_skip(data: Vec<u8>, fin_format: Format) -> (Vec<Transaction>, usize) {
+        let mut reader = TxReader::new(Cursor::new(data), fin_format)
+            .unwrap()
+            .with_error_policy(ErrorPolicy::Skip);
+        let txs = reader.read_all().unwrap();
+        (txs, reader.error_report().len())
+    }
+
+    #[test]
+    fn test_structural() {
+        for fin_format in Format::ALL {
+            let (data, txs) = file_for_test(fin_format);
+
+            let mut corruptor = Corruptor::new(data.clone(), fin_format).unwrap();
+            assert_eq!(corruptor.records(), 20);
+            corruptor.inject_at(Corruption::DuplicateRecord, 4).unwrap();
+            assert_eq!(corruptor.records(), 21);
+            let (read, errors) = read_skip(corruptor.into_inner(), fin_format);
+            assert_eq!((read.len(), errors), (21, 0));
+            assert_eq!(read[4], read[5]);
+            assert_eq!(read[5..], txs[4..]);
+
+            let mut corruptor = Corruptor::new(data.clone(), fin_format).unwrap();
+            let offset = corruptor.inject_at(Corruption::Truncate, 7).unwrap().offset;
+            assert!(offset < data.len());
+            let (read, _) = read_skip(corruptor.into_inner(), fin_format);
+            assert_eq!(read[..7], txs[..7]);
+            assert!(read.len() <= 8);
+
+            let mut corruptor = Corruptor::new(data, fin_format).unwrap();
+            let res = corruptor.inject_at(Corruption::BadLength, 2);
+            if fin_format == Format::Bin {
+                res.unwrap();
+                let (read, errors) = read_skip(corruptor.into_inner(), fin_format);
+                assert_eq!(read[..2], txs[..2]);
+                assert_eq!(read[read.len() - 10..], txs[10..]);
+                assert!(errors >= 1);
+            } else {
+                assert!(res.is_err());
+            }
+        }
+    }
+
+    #[test]
+    fn test_bit_flips() {
+        for fin_format in Format::ALL {
+            let (data, txs) = file_for_test(fin_format);
+            for seed in 0..50 {
+                let mut corruptor = Corruptor::new(data.clone(), fin_format)
+                    .unwrap()
+                    .with_seed(seed);
+                let record = corruptor.inject(Corruption::BitFlip).unwrap().record;
+                let (read, _) = read_skip(corruptor.into_inner(), fin_format);
+                // Записи до поврежденной читаются без изменений
+                assert_eq!(read[..record], txs[..record], "{fin_format} seed {seed}");
+            }
+        }
+        let (data, _) = file_for_test(Format::Csv);
+        let first = |seed| {
+            let mut corruptor = Corruptor::new(data.clone(), Format::Csv)
+                .unwrap()
+                .with_seed(seed);
+            corruptor.inject(Corruption::BitFlip).unwrap();
+            corruptor.into_inner()
+        };
+        assert_eq!(first(9), first(9));
+    }
+}
@@ -1,29 +1,26 @@
 use super::constants::*;
 use super::error::ParsError;
+use super::options::{ReaderOptions, WriterOptions};
 use super::transaction::*;
-use super::utils::{read_byte, remove_quotes};
-use chrono::DateTime;
+use super::utils::{
+    CountingReader, CountingWriter, Location, check_timestamp, invalid_enum_value,
+    parse_description, parse_number, quote_description, read_byte, remove_quotes, snippet,
+    timestamp_from_unit, timestamp_to_unit,
+};
+use super::warning::{Warning, WarningKind, WarningSink};
 use std::collections::HashMap;
-use std::io::{Read, Write};
-
-const HEADER_VALUES: [&str; CNT_VALUES] = [
-    TX_ID,
-    TX_TYPE,
-    FROM_USER_ID,
-    TO_USER_ID,
-    AMOUNT,
-    TIMESTAMP,
-    STATUS,
-    DESCRIPTION,
-];
+use std::io::{BufWriter, Read, Write};
 
+/// Вид прочитанной лексемы. Текст значения остается во внутреннем буфере парсера
+/// и доступен через [Parser::value] до чтения следующей лексемы
 enum Token {
-    Value(String),
-    EndOfLine(String),
-    EndOfStream(Option<String>),
+    Value,
+    EndOfLine,
+    EndOfStream { has_value: bool },
 }
 
-#[derive(Clone, Copy)]
+#[allow(clippy::enum_variant_names)]
+#[derive(Clone, Copy, Eq, PartialEq)]
 enum ParserState {
     WaitStartRecord,
     WaitStartValue,
@@ -34,93 +31,130 @@ enum ParserState {
 
 struct Parser<In: Read> {
     state: ParserState,
-    stream: In,
+    stream: CountingReader<In>,
+    record_start: Location,
+    raw: Vec<u8>,
+    // Сохранять запись в raw целиком, а не только начало для сообщений об ошибках
+    capture: bool,
+    buf: Vec<u8>,
+    delimiter: u8,
 }
 
 impl<In: Read> Parser<In> {
-    fn new(stream: In) -> Self {
+    fn new(stream: In, delimiter: u8) -> Self {
         Self {
             state: ParserState::WaitStartRecord,
-            stream,
+            stream: CountingReader::new(stream),
+            record_start: Location::default(),
+            raw: Vec::new(),
+            capture: false,
+            buf: Vec::new(),
+            delimiter,
         }
     }
 
+    /// Запоминание положения первого байта записи
+    fn mark_record_start(&mut self, byte: u8) {
+        self.raw.clear();
+        self.raw.push(byte);
+        let location = self.stream.location();
+        self.record_start = Location {
+            byte: location.byte - 1,
+            line: location.line,
+            column: location.column - 1,
+        };
+    }
+
+    /// Переход к началу следующей строки после ошибки внутри записи
+    fn resync(&mut self) -> Result<(), ParsError> {
+        if self.state == ParserState::WaitStartRecord {
+            return Ok(());
+        }
+        self.state = ParserState::WaitStartRecord;
+        loop {
+            match read_byte(&mut self.stream) {
+                Ok(b'\n') | Err(ParsError::EndOfStream) => return Ok(()),
+                Ok(_) => continue,
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Текст последнего прочитанного значения без окружающих пробелов
+    fn value(&self) -> Result<&str, ParsError> {
+        Ok(std::str::from_utf8(&self.buf)?.trim())
+    }
+
     fn get_next_token(&mut self) -> Result<Token, ParsError> {
-        let mut buf = Vec::new();
+        self.buf.clear();
         loop {
             let byte = match read_byte(&mut self.stream) {
                 Ok(val) => val,
-                Err(e) => match e {
-                    ParsError::EndOfStream => {
-                        let res = std::str::from_utf8(&buf)?.trim().to_string();
-                        if res.is_empty() {
-                            return Ok(Token::EndOfStream(None));
-                        } else {
-                            return Ok(Token::EndOfStream(Some(res)));
-                        }
-                    }
-                    _ => {
-                        return Err(e);
-                    }
-                },
+                Err(ParsError::EndOfStream) => {
+                    let has_value = !self.value()?.is_empty();
+                    return Ok(Token::EndOfStream { has_value });
+                }
+                Err(e) => return Err(e),
             };
+            if self.capture || self.raw.len() < MAX_SNIPPET_LEN {
+                self.raw.push(byte);
+            }
             match self.state {
                 ParserState::WaitStartRecord => {
-                    if byte == ' ' as u8 || byte == '\n' as u8 {
+                    if byte == b' ' || byte == b'\n' {
                         continue;
                     }
+                    self.mark_record_start(byte);
 
-                    if byte == '"' as u8 {
-                        buf.push(byte);
+                    if byte == b'"' {
+                        self.buf.push(byte);
                         self.state = ParserState::WaitEndString;
                         continue;
                     }
 
-                    buf.push(byte);
+                    self.buf.push(byte);
                     self.state = ParserState::WaitEndRegular;
                 }
                 ParserState::WaitStartValue => {
-                    if byte == ' ' as u8 {
+                    if byte == b' ' {
                         continue;
                     }
 
-                    if byte == '"' as u8 {
-                        buf.push(byte);
+                    if byte == b'"' {
+                        self.buf.push(byte);
                         self.state = ParserState::WaitEndString;
                         continue;
                     }
-                    buf.push(byte);
+                    self.buf.push(byte);
                     self.state = ParserState::WaitEndRegular;
                 }
                 ParserState::WaitEndRegular => {
-                    if byte == ',' as u8 {
-                        let val_text = std::str::from_utf8(&buf)?.trim();
+                    if byte == self.delimiter {
                         self.state = ParserState::WaitStartValue;
-                        return Ok(Token::Value(val_text.to_owned()));
+                        return Ok(Token::Value);
                     }
 
-                    if byte == '\n' as u8 {
-                        let val_text = std::str::from_utf8(&buf)?.trim();
+                    if byte == b'\n' {
                         self.state = ParserState::WaitStartRecord;
-                        return Ok(Token::EndOfLine(val_text.to_owned()));
+                        return Ok(Token::EndOfLine);
                     }
-                    buf.push(byte);
+                    self.buf.push(byte);
                 }
 
                 ParserState::WaitEndString => {
-                    if byte == '\\' as u8 {
+                    if byte == b'\\' {
                         self.state = ParserState::WaitEscaped;
                         continue;
                     }
-                    if byte == '"' as u8 {
-                        buf.push(byte);
+                    if byte == b'"' {
+                        self.buf.push(byte);
                         self.state = ParserState::WaitEndRegular;
                         continue;
                     }
-                    buf.push(byte);
+                    self.buf.push(byte);
                 }
                 ParserState::WaitEscaped => {
-                    buf.push(byte);
+                    self.buf.push(byte);
                     self.state = ParserState::WaitEndString;
                     continue;
                 }
@@ -129,57 +163,80 @@ impl<In: Read> Parser<In> {
     }
 }
 
+/// Длина первой целой строки в начале буфера или None, если строка еще не дочитана.
+/// Переходы повторяют [Parser::get_next_token]
+#[cfg(feature = "async")]
+pub fn frame_len(buf: &[u8], delimiter: u8) -> Option<usize> {
+    let mut state = ParserState::WaitStartRecord;
+    for (idx, &byte) in buf.iter().enumerate() {
+        state = match state {
+            ParserState::WaitStartRecord if byte == b' ' || byte == b'\n' => state,
+            ParserState::WaitStartValue if byte == b' ' => state,
+            ParserState::WaitStartRecord | ParserState::WaitStartValue if byte == b'"' => {
+                ParserState::WaitEndString
+            }
+            ParserState::WaitStartRecord | ParserState::WaitStartValue => {
+                ParserState::WaitEndRegular
+            }
+            ParserState::WaitEndRegular if byte == delimiter => ParserState::WaitStartValue,
+            ParserState::WaitEndRegular if byte == b'\n' => return Some(idx + 1),
+            ParserState::WaitEndRegular => state,
+            ParserState::WaitEndString if byte == b'\\' => ParserState::WaitEscaped,
+            ParserState::WaitEndString if byte == b'"' => ParserState::WaitEndRegular,
+            ParserState::WaitEndString => state,
+            ParserState::WaitEscaped => ParserState::WaitEndString,
+        };
+    }
+    None
+}
+
 #[derive(Eq, PartialEq, Debug)]
 struct CsvTxRecord {
     fields: Vec<String>,
 }
 
 impl CsvTxRecord {
-    fn serialize<Out: Write>(&self, out: &mut Out) -> Result<(), ParsError> {
-        let mut res = String::new();
+    /// Запись строки в поток. Поток писателя буферизован, поэтому поля пишутся по отдельности
+    /// без сборки промежуточной строки
+    fn serialize<Out: Write>(&self, out: &mut Out, delimiter: u8) -> Result<(), ParsError> {
         for (idx, val) in self.fields.iter().enumerate() {
             if idx > 0 {
-                res.push(',');
+                out.write_all(&[delimiter])?;
             }
-            res.push_str(val);
+            out.write_all(val.as_bytes())?;
         }
-        res.push('\n');
-        out.write_all(res.as_bytes())?;
+        out.write_all(b"\n")?;
         Ok(())
     }
 
-    fn to_transaction(&self, header: &HashMap<String, usize>) -> Result<Transaction, ParsError> {
+    fn to_transaction_ref(
+        &self,
+        header: &HashMap<String, usize>,
+        options: &ReaderOptions,
+    ) -> Result<TransactionRef<'_>, ParsError> {
         if self.fields.len() != header.len() {
-            return Err(ParsError::WrongFormat(
-                "Количество полей не соответствует заголовку".to_owned(),
-            ));
+            return Err(ParsError::FieldCountMismatch {
+                expected: header.len(),
+                found: self.fields.len(),
+            });
         }
 
-        let tx_id = self.fields[header[TX_ID]].parse::<u64>()?;
+        let tx_id = parse_number::<u64>(TX_ID, &self.fields[header[TX_ID]])?;
         let tx_type = self.fields[header[TX_TYPE]].as_str();
         let tx_type = match tx_type {
             DEPOSIT => TxType::Deposit,
             TRANSFER => TxType::Transfer,
             WITHDRAWAL => TxType::Withdrawal,
             _ => {
-                return Err(ParsError::WrongFormat(format!(
-                    "Неверный формат TX_TYPE: {tx_type}"
-                )));
+                return Err(invalid_enum_value(TX_TYPE, tx_type));
             }
         };
 
-        let from_user_id = self.fields[header[FROM_USER_ID]].parse::<u64>()?;
-        let to_user_id = self.fields[header[TO_USER_ID]].parse::<u64>()?;
-        let amount = self.fields[header[AMOUNT]].parse::<i64>()?;
-        let timestamp = self.fields[header[TIMESTAMP]].parse::<u64>()?;
-        let timestamp = if let Some(val) = DateTime::from_timestamp_millis(timestamp as i64) {
-            val
-        } else {
-            return Err(ParsError::WrongFormat(format!(
-                "Wrong timestamp: {}",
-                timestamp
-            )));
-        };
+        let from_user_id = parse_number::<u64>(FROM_USER_ID, &self.fields[header[FROM_USER_ID]])?;
+        let to_user_id = parse_number::<u64>(TO_USER_ID, &self.fields[header[TO_USER_ID]])?;
+        let amount = parse_number::<i64>(AMOUNT, &self.fields[header[AMOUNT]])?;
+        let timestamp = parse_number::<u64>(TIMESTAMP, &self.fields[header[TIMESTAMP]])?;
+        let timestamp = timestamp_from_unit(timestamp, options.timestamp_unit)?;
 
         let status = self.fields[header[STATUS]].as_str();
         let status = match status {
@@ -187,22 +244,13 @@ impl CsvTxRecord {
             FAILURE => TxStatus::Failure,
             PENDING => TxStatus::Pending,
             _ => {
-                return Err(ParsError::WrongFormat(format!(
-                    "Неверный формат STATUS: {status}"
-                )));
+                return Err(invalid_enum_value(STATUS, status));
             }
         };
 
-        let description = self.fields[header[DESCRIPTION]].as_str();
-
-        if !(description.starts_with('"') && description.ends_with('"')) {
-            return Err(ParsError::WrongFormat(format!(
-                "Wrong description: {}",
-                description
-            )));
-        }
+        let description = parse_description(&self.fields[header[DESCRIPTION]], options)?;
 
-        Ok(Transaction {
+        Ok(TransactionRef {
             tx_id,
             tx_type,
             from_user_id,
@@ -210,12 +258,45 @@ impl CsvTxRecord {
             amount,
             timestamp,
             status,
-            description: remove_quotes(&description),
+            description,
         })
     }
 
-    fn from_transaction(tx: &Transaction, header: &HashMap<String, usize>) -> Self {
-        let mut fields = vec![String::new(); CNT_VALUES];
+    /// Проверка полей без построения транзакции: те же ошибки, что у [Self::to_transaction_ref]
+    fn validate(
+        &self,
+        header: &HashMap<String, usize>,
+        options: &ReaderOptions,
+    ) -> Result<(), ParsError> {
+        if self.fields.len() != header.len() {
+            return Err(ParsError::FieldCountMismatch {
+                expected: header.len(),
+                found: self.fields.len(),
+            });
+        }
+        let field = |name: &str| self.fields[header[name]].as_str();
+        parse_number::<u64>(TX_ID, field(TX_ID))?;
+        if ![DEPOSIT, TRANSFER, WITHDRAWAL].contains(&field(TX_TYPE)) {
+            return Err(invalid_enum_value(TX_TYPE, field(TX_TYPE)));
+        }
+        parse_number::<u64>(FROM_USER_ID, field(FROM_USER_ID))?;
+        parse_number::<u64>(TO_USER_ID, field(TO_USER_ID))?;
+        parse_number::<i64>(AMOUNT, field(AMOUNT))?;
+        let timestamp = parse_number::<u64>(TIMESTAMP, field(TIMESTAMP))?;
+        check_timestamp(timestamp, options.timestamp_unit)?;
+        if ![SUCCESS, FAILURE, PENDING].contains(&field(STATUS)) {
+            return Err(invalid_enum_value(STATUS, field(STATUS)));
+        }
+        parse_description(field(DESCRIPTION), options)?;
+        Ok(())
+    }
+
+    fn from_transaction(
+        tx: &Transaction,
+        header: &HashMap<String, usize>,
+        options: &WriterOptions,
+    ) -> Self {
+        let mut fields = vec![String::new(); header.len()];
         fields[header[TX_ID]] = tx.tx_id.to_string();
         fields[header[TX_TYPE]] = match tx.tx_type {
             TxType::Deposit => DEPOSIT.to_owned(),
@@ -225,57 +306,96 @@ impl CsvTxRecord {
         fields[header[FROM_USER_ID]] = tx.from_user_id.to_string();
         fields[header[TO_USER_ID]] = tx.to_user_id.to_string();
         fields[header[AMOUNT]] = tx.amount.to_string();
-        let timestamp = tx.timestamp.timestamp_millis() as u64;
+        let timestamp = timestamp_to_unit(&tx.timestamp, options.timestamp_unit);
         fields[header[TIMESTAMP]] = timestamp.to_string();
         fields[header[STATUS]] = match tx.status {
             TxStatus::Success => SUCCESS.to_owned(),
             TxStatus::Failure => FAILURE.to_owned(),
             TxStatus::Pending => PENDING.to_owned(),
         };
-        fields[header[DESCRIPTION]] = format!("\"{}\"", tx.description);
+        fields[header[DESCRIPTION]] = quote_description(&tx.description);
         Self { fields }
     }
 }
 
 pub struct CsvTxReader<In: Read> {
     parser: Parser<In>,
+    // Запись переиспользуется между вызовами, чтобы не выделять память под поля
+    record: CsvTxRecord,
     header: Option<HashMap<String, usize>>,
+    options: ReaderOptions,
+    records: u64,
+    warnings: WarningSink,
 }
 
 impl<In: Read> CsvTxReader<In> {
-    pub fn new(stream: In) -> Result<Self, ParsError> {
+    pub fn with_options(stream: In, options: ReaderOptions) -> Result<Self, ParsError> {
         Ok(Self {
-            parser: Parser::new(stream),
+            parser: Parser::new(stream, options.delimiter),
+            record: CsvTxRecord { fields: Vec::new() },
             header: None,
+            warnings: WarningSink::new(&options),
+            options,
+            records: 0,
         })
     }
 
-    fn read_values(&mut self) -> Result<Vec<String>, ParsError> {
-        let mut res = Vec::new();
+    /// Ошибка в данных записи: указывается начало записи и ее исходный текст
+    fn record_error(&self, e: ParsError) -> ParsError {
+        let position = self.parser.record_start.error_position(self.records);
+        e.at_record(position, Some(self.raw_record()))
+    }
+
+    /// Ошибка разбиения на поля: указывается место, до которого дочитан поток
+    fn stream_error(&self, e: ParsError) -> ParsError {
+        let position = self.parser.stream.location().error_position(self.records);
+        e.at_record(position, Some(self.raw_record()))
+    }
+
+    /// Чтение значений одной строки в поля переиспользуемой записи.
+    /// Возвращает false, если поток закончился и значений нет
+    fn read_values(&mut self) -> Result<bool, ParsError> {
+        let fields = &mut self.record.fields;
+        let mut cnt = 0;
         loop {
-            match self.parser.get_next_token()? {
-                Token::Value(val) => res.push(val),
-                Token::EndOfLine(val) => {
-                    res.push(val);
-                    return Ok(res);
-                }
-                Token::EndOfStream(val) => {
-                    if let Some(reminder) = val {
-                        res.push(reminder);
+            let token = self.parser.get_next_token()?;
+            if !matches!(token, Token::EndOfStream { has_value: false }) {
+                let val = self.parser.value()?;
+                match fields.get_mut(cnt) {
+                    Some(field) => {
+                        field.clear();
+                        field.push_str(val);
                     }
-                    return Ok(res);
+                    None => fields.push(val.to_owned()),
                 }
+                cnt += 1;
+            }
+            if !matches!(token, Token::Value) {
+                fields.truncate(cnt);
+                return Ok(cnt > 0);
             }
         }
     }
 
     fn read_header(&mut self) -> Result<(), ParsError> {
-        let header = self.read_values()?;
-        if header != HEADER_VALUES {
-            return Err(ParsError::WrongFormat(format!(
-                "Неверный заголовок: {:?}",
-                header
-            )));
+        self.read_values().map_err(|e| self.stream_error(e))?;
+        let header = self.record.fields.clone();
+        let valid = if self.options.strict {
+            header == HEADER_VALUES
+        } else {
+            HEADER_VALUES
+                .iter()
+                .all(|name| header.iter().any(|val| val == name))
+        };
+        if !valid {
+            return Err(self.record_error(ParsError::BadHeader { found: header }));
+        }
+
+        for name in header.iter() {
+            if !HEADER_VALUES.contains(&name.as_str()) {
+                let kind = WarningKind::UnknownField { name: name.clone() };
+                self.warnings.push(self.records, kind);
+            }
         }
 
         let res: HashMap<String, usize> = header
@@ -284,58 +404,225 @@ impl<In: Read> CsvTxReader<In> {
             .map(|(idx, name)| (name, idx))
             .collect();
         self.header = Some(res);
+        // Заголовок не относится к первой записи
+        self.parser.stream.clear_captured();
+        Ok(())
+    }
+
+    pub fn bytes_read(&self) -> u64 {
+        self.parser.stream.count()
+    }
+
+    /// Смещение начала последней прочитанной записи
+    pub fn last_record_start(&self) -> u64 {
+        self.parser.record_start.byte
+    }
+
+    /// Положение начала последней прочитанной записи
+    #[cfg(feature = "parallel")]
+    pub fn last_record_location(&self) -> Location {
+        self.parser.record_start
+    }
+
+    /// Сохранение исходных байт записей для [Self::take_rejected]
+    pub fn set_capture_raw(&mut self, capture: bool) {
+        self.parser.stream.set_capture(capture);
+    }
+
+    /// Очистка исходных байт после успешно прочитанной записи
+    pub fn clear_captured(&mut self) {
+        self.parser.stream.clear_captured();
+    }
+
+    /// Смещение начала и исходные байты записи, пропущенной через resync
+    pub fn take_rejected(&mut self) -> (u64, Vec<u8>) {
+        let end = self.parser.stream.count();
+        let raw = self
+            .parser
+            .stream
+            .captured()
+            .map(std::mem::take)
+            .unwrap_or_default();
+        (end - raw.len() as u64, raw)
+    }
+
+    #[cfg(feature = "async")]
+    pub fn get_mut(&mut self) -> &mut In {
+        self.parser.stream.get_mut()
+    }
+
+    /// Пропуск записи без построения транзакции
+    pub fn skip_record(&mut self) -> Result<bool, ParsError> {
+        if self.header.is_none() {
+            self.read_header()?;
+        }
+        if !self.read_values().map_err(|e| self.stream_error(e))? {
+            return Ok(false);
+        }
+        self.records += 1;
+        Ok(true)
+    }
+
+    /// Проверка записи без построения транзакции
+    pub fn validate_record(&mut self) -> Result<bool, ParsError> {
+        if self.header.is_none() {
+            self.read_header()?;
+        }
+        if !self.read_values().map_err(|e| self.stream_error(e))? {
+            return Ok(false);
+        }
+        let Some(header) = self.header.as_ref() else {
+            return Err(ParsError::WrongFormat("Отсутствует заголовок".to_owned()));
+        };
+        self.record
+            .validate(header, &self.options)
+            .map_err(|e| self.record_error(e))?;
+        self.records += 1;
+        Ok(true)
+    }
+
+    /// Чтение исходного текста записи целиком, строка дописывается в out.
+    /// Возвращает false, если поток закончился
+    #[cfg(feature = "parallel")]
+    pub fn read_raw_record(&mut self, out: &mut Vec<u8>) -> Result<bool, ParsError> {
+        if self.header.is_none() {
+            self.read_header()?;
+        }
+        self.parser.capture = true;
+        let res = self.skip_record();
+        self.parser.capture = false;
+        if !res? {
+            return Ok(false);
+        }
+        out.extend_from_slice(&self.parser.raw);
+        if !out.ends_with(b"\n") {
+            out.push(b'\n');
+        }
+        Ok(true)
+    }
+
+    /// Строка заголовка в исходном порядке столбцов
+    #[cfg(feature = "parallel")]
+    pub fn header_line(&mut self) -> Result<Vec<u8>, ParsError> {
+        if self.header.is_none() {
+            self.read_header()?;
+        }
+        let mut names: Vec<(&String, &usize)> = self.header.iter().flatten().collect();
+        names.sort_by_key(|(_, idx)| **idx);
+        let mut res = Vec::new();
+        for (idx, (name, _)) in names.into_iter().enumerate() {
+            if idx > 0 {
+                res.push(self.options.delimiter);
+            }
+            res.extend_from_slice(name.as_bytes());
+        }
+        res.push(b'\n');
+        Ok(res)
+    }
+
+    #[cfg(feature = "parallel")]
+    pub fn options(&self) -> &ReaderOptions {
+        &self.options
+    }
+
+    /// Исходный текст текущей записи, усеченный до MAX_SNIPPET_LEN байт
+    fn raw_record(&self) -> String {
+        snippet(&self.parser.raw)
+    }
+
+    pub fn take_warnings(&mut self) -> Vec<Warning> {
+        self.warnings.take()
+    }
+
+    /// Переход к следующей записи после ошибки чтения
+    pub fn resync(&mut self) -> Result<(), ParsError> {
+        self.parser.resync()?;
+        if self.header.is_some() {
+            self.records += 1;
+        }
         Ok(())
     }
 
     pub fn read_transaction(&mut self) -> Result<Option<Transaction>, ParsError> {
+        Ok(self.read_transaction_ref()?.map(Transaction::from))
+    }
+
+    /// Чтение транзакции с описанием, заимствованным из буфера записи
+    pub fn read_transaction_ref(&mut self) -> Result<Option<TransactionRef<'_>>, ParsError> {
         if self.header.is_none() {
             self.read_header()?;
         }
-        let fields = self.read_values()?;
-        if fields.is_empty() {
+        if !self.read_values().map_err(|e| self.stream_error(e))? {
             return Ok(None);
         }
-        let csv_record = CsvTxRecord { fields };
 
-        if let Some(header) = self.header.as_ref() {
-            Ok(Some(csv_record.to_transaction(header)?))
-        } else {
+        let Some(header) = self.header.as_ref() else {
             return Err(ParsError::WrongFormat("Отсутствует заголовок".to_owned()));
-        }
+        };
+        let tx = self
+            .record
+            .to_transaction_ref(header, &self.options)
+            .map_err(|e| self.record_error(e))?;
+        let raw_description = &self.record.fields[header[DESCRIPTION]];
+        self.warnings
+            .check_transaction(self.records, &tx, raw_description);
+        self.records += 1;
+        Ok(Some(tx))
+    }
+
+    /// Описание последней прочитанной транзакции
+    pub fn last_description(&self) -> &str {
+        self.header
+            .as_ref()
+            .and_then(|header| header.get(DESCRIPTION))
+            .and_then(|idx| self.record.fields.get(*idx))
+            .map_or("", |val| remove_quotes(val))
     }
 }
 
 pub struct CsvTxWriter<Out: Write> {
-    stream: Out,
+    stream: CountingWriter<BufWriter<Out>>,
     header: Option<HashMap<String, usize>>,
+    options: WriterOptions,
+    buf: Vec<u8>,
 }
 
 impl<Out: Write> CsvTxWriter<Out> {
-    pub fn new(stream: Out) -> Result<Self, ParsError> {
+    pub fn with_options(stream: Out, options: WriterOptions) -> Result<Self, ParsError> {
         Ok(Self {
-            stream,
+            stream: CountingWriter::new(BufWriter::new(stream)),
             header: None,
+            options,
+            buf: Vec::new(),
         })
     }
 
-    pub fn write_header(&mut self) -> Result<(), ParsError> {
+    fn header_line(&self) -> String {
         let mut header_str = String::new();
         for (idx, field) in HEADER_VALUES.into_iter().enumerate() {
             if idx > 0 {
-                header_str.push(',');
+                header_str.push(self.options.delimiter as char);
             }
             header_str.push_str(field);
         }
         header_str.push('\n');
+        header_str
+    }
+
+    pub fn write_header(&mut self) -> Result<(), ParsError> {
+        let header_str = self.header_line();
         self.stream.write_all(header_str.as_bytes())?;
+        self.set_header();
+        Ok(())
+    }
 
+    fn set_header(&mut self) {
         let header: HashMap<String, usize> = HEADER_VALUES
             .into_iter()
             .enumerate()
             .map(|(idx, name)| (name.to_string(), idx))
             .collect();
         self.header = Some(header);
-        Ok(())
     }
 
     pub fn write_transaction(&mut self, data: &Transaction) -> Result<(), ParsError> {
@@ -344,18 +631,92 @@ impl<Out: Write> CsvTxWriter<Out> {
         }
 
         if let Some(header) = self.header.as_ref() {
-            let record = CsvTxRecord::from_transaction(&data, header);
-            record.serialize(&mut self.stream)?;
+            let record = CsvTxRecord::from_transaction(data, header, &self.options);
+            record.serialize(&mut self.stream, self.options.delimiter)?;
         } else {
             return Err(ParsError::WrongFormat("Не записан заголовок".to_owned()));
         }
         Ok(())
     }
+
+    /// Запись набора транзакций: заголовок (если он еще не записан) и записи собираются
+    /// в буфер и пишутся в поток одним write_all
+    pub fn write_batch(&mut self, txs: &[Transaction]) -> Result<(), ParsError> {
+        self.buf.clear();
+        if self.header.is_none() {
+            let header_str = self.header_line();
+            self.buf.extend_from_slice(header_str.as_bytes());
+            self.set_header();
+        }
+        if let Some(header) = self.header.as_ref() {
+            for tx in txs {
+                CsvTxRecord::from_transaction(tx, header, &self.options)
+                    .serialize(&mut self.buf, self.options.delimiter)?;
+            }
+        }
+        self.stream.write_all(&self.buf)?;
+        Ok(())
+    }
+
+    /// Писатель в память с теми же настройками для сериализации части данных.
+    /// Заголовок пишется в исходный поток, а писатель части его не повторяет
+    #[cfg(feature = "parallel")]
+    pub fn chunk_writer(&mut self) -> Result<CsvTxWriter<Vec<u8>>, ParsError> {
+        if self.header.is_none() {
+            self.write_header()?;
+        }
+        Ok(CsvTxWriter {
+            stream: CountingWriter::new(BufWriter::new(Vec::new())),
+            header: self.header.clone(),
+            options: self.options.clone(),
+            buf: Vec::new(),
+        })
+    }
+
+    /// Запись уже сериализованных данных этого формата после заголовка
+    #[cfg(feature = "parallel")]
+    pub fn write_raw(&mut self, data: &[u8]) -> Result<(), ParsError> {
+        if self.header.is_none() {
+            self.write_header()?;
+        }
+        self.stream.write_all(data)?;
+        Ok(())
+    }
+
+    #[cfg(feature = "async")]
+    pub fn get_mut(&mut self) -> &mut Out {
+        self.stream.get_mut().get_mut()
+    }
+
+    pub fn flush(&mut self) -> Result<(), ParsError> {
+        self.stream.flush()?;
+        Ok(())
+    }
+
+    pub fn finish(&mut self) -> Result<(), ParsError> {
+        if self.header.is_none() {
+            self.write_header()?;
+        }
+        self.flush()
+    }
+
+    /// Количество байт, переданных в поток, включая еще не сброшенные из буфера
+    pub fn bytes_written(&self) -> u64 {
+        self.stream.count()
+    }
+
+    pub fn into_inner(self) -> Result<Out, ParsError> {
+        self.stream
+            .into_inner()
+            .into_inner()
+            .map_err(|e| ParsError::IoError(e.into_error()))
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use chrono::DateTime;
     use std::io::Cursor;
 
     const EXPECTED_CSV: &str = "1000000000000000,DEPOSIT,0,9223372036854775807,100,1633036860000,FAILURE,\"Record number 1\"\n";
@@ -373,7 +734,7 @@ mod tests {
             from_user_id: 0,
             to_user_id: 9223372036854775807,
             amount: 100,
-            timestamp: DateTime::from_timestamp_millis(1633036860000 as i64).unwrap(),
+            timestamp: DateTime::from_timestamp_millis(1633036860000).unwrap(),
             status: TxStatus::Failure,
             description: "Record number 1".to_owned(),
         }
@@ -386,7 +747,7 @@ mod tests {
             from_user_id: 9223372036854775807,
             to_user_id: 9223372036854775807,
             amount: 200,
-            timestamp: DateTime::from_timestamp_millis(1633036920000 as i64).unwrap(),
+            timestamp: DateTime::from_timestamp_millis(1633036920000).unwrap(),
             status: TxStatus::Pending,
             description: "Record number 2".to_owned(),
         }
@@ -421,7 +782,7 @@ mod tests {
         let tx = tx1_for_test();
         let expected = csv_record_for_test();
         let header = get_header();
-        let record = CsvTxRecord::from_transaction(&tx, &header);
+        let record = CsvTxRecord::from_transaction(&tx, &header, &WriterOptions::default());
 
         assert_eq!(record, expected);
     }
@@ -431,7 +792,9 @@ mod tests {
         let csv_record = csv_record_for_test();
         let expected = tx1_for_test();
         let header = get_header();
-        let tx = csv_record.to_transaction(&header).unwrap();
+        let tx = csv_record
+            .to_transaction_ref(&header, &ReaderOptions::default())
+            .unwrap();
 
         assert_eq!(tx, expected);
     }
@@ -441,7 +804,7 @@ mod tests {
         let record = csv_record_for_test();
         let buf = Vec::new();
         let mut cursor = Cursor::new(buf);
-        record.serialize(&mut cursor).unwrap();
+        record.serialize(&mut cursor, b',').unwrap();
 
         assert_eq!(std::str::from_utf8(cursor.get_ref()).unwrap(), EXPECTED_CSV);
     }
@@ -449,7 +812,7 @@ mod tests {
     #[test]
     fn test_csv_reader() {
         let stream = Cursor::new(EXPECTED_CSV_MULT.as_bytes());
-        let mut csv_reader = CsvTxReader::new(stream).unwrap();
+        let mut csv_reader = CsvTxReader::with_options(stream, ReaderOptions::default()).unwrap();
 
         let mut fin_info = Vec::new();
         while let Some(tx) = csv_reader.read_transaction().unwrap() {
@@ -465,14 +828,14 @@ mod tests {
     fn test_csv_writer() {
         let buf = Vec::new();
         let stream = Cursor::new(buf);
-        let mut csv_writer = CsvTxWriter::new(stream).unwrap();
+        let mut csv_writer = CsvTxWriter::with_options(stream, WriterOptions::default()).unwrap();
 
         csv_writer.write_transaction(&tx1_for_test()).unwrap();
         csv_writer.write_transaction(&tx2_for_test()).unwrap();
 
-        let buf = csv_writer.stream.get_ref();
+        let buf = csv_writer.into_inner().unwrap().into_inner();
         let stream = Cursor::new(buf);
-        let mut csv_reader = CsvTxReader::new(stream).unwrap();
+        let mut csv_reader = CsvTxReader::with_options(stream, ReaderOptions::default()).unwrap();
         let mut fin_info = Vec::new();
         while let Some(tx) = csv_reader.read_transaction().unwrap() {
             fin_info.push(tx);
@@ -482,4 +845,113 @@ mod tests {
         assert_eq!(fin_info[0], tx1_for_test());
         assert_eq!(fin_info[1], tx2_for_test());
     }
+
+    #[test]
+    fn test_csv_escaped_description() {
+        let mut csv_writer =
+            CsvTxWriter::with_options(Vec::new(), WriterOptions::default()).unwrap();
+        let descriptions = ["\"_,a", "a\"b", "a\\b\\", "\\\""];
+        let txs: Vec<_> = descriptions
+            .iter()
+            .map(|description| Transaction {
+                description: description.to_string(),
+                ..tx1_for_test()
+            })
+            .collect();
+        for tx in &txs {
+            csv_writer.write_transaction(tx).unwrap();
+        }
+        let buf = csv_writer.into_inner().unwrap();
+        assert!(std::str::from_utf8(&buf).unwrap().contains(r#","\"_,a""#));
+
+        let mut csv_reader =
+            CsvTxReader::with_options(Cursor::new(buf), ReaderOptions::default()).unwrap();
+        let mut fin_info = Vec::new();
+        while let Some(tx) = csv_reader.read_transaction().unwrap() {
+            fin_info.push(tx);
+        }
+        assert_eq!(fin_info, txs);
+    }
+
+    #[test]
+    fn test_csv_delimiter() {
+        let writer_options = WriterOptions {
+            delimiter: b';',
+            ..Default::default()
+        };
+        let mut csv_writer = CsvTxWriter::with_options(Vec::new(), writer_options).unwrap();
+        csv_writer.write_transaction(&tx1_for_test()).unwrap();
+        let buf = csv_writer.into_inner().unwrap();
+        assert!(
+            std::str::from_utf8(&buf)
+                .unwrap()
+                .starts_with("TX_ID;TX_TYPE;")
+        );
+
+        let reader_options = ReaderOptions {
+            delimiter: b';',
+            ..Default::default()
+        };
+        let mut csv_reader = CsvTxReader::with_options(Cursor::new(buf), reader_options).unwrap();
+        assert_eq!(csv_reader.read_transaction().unwrap(), Some(tx1_for_test()));
+    }
+
+    #[test]
+    fn test_csv_lenient() {
+        const LENIENT_CSV: &str = "STATUS,TX_ID,TX_TYPE,FROM_USER_ID,TO_USER_ID,AMOUNT,TIMESTAMP,DESCRIPTION\n\
+            FAILURE,1000000000000000,DEPOSIT,0,9223372036854775807,100,1633036860000,Record number 1\n";
+
+        let mut csv_reader = CsvTxReader::with_options(
+            Cursor::new(LENIENT_CSV.as_bytes()),
+            ReaderOptions::default(),
+        )
+        .unwrap();
+        let err = csv_reader.read_transaction().unwrap_err();
+        assert!(matches!(err.inner(), ParsError::BadHeader { .. }));
+
+        let options = ReaderOptions {
+            strict: false,
+            ..Default::default()
+        };
+        let mut csv_reader =
+            CsvTxReader::with_options(Cursor::new(LENIENT_CSV.as_bytes()), options).unwrap();
+        assert_eq!(csv_reader.read_transaction().unwrap(), Some(tx1_for_test()));
+    }
+
+    #[test]
+    fn test_csv_skip_record() {
+        let stream = Cursor::new(EXPECTED_CSV_MULT.as_bytes());
+        let mut csv_reader = CsvTxReader::with_options(stream, ReaderOptions::default()).unwrap();
+
+        assert!(csv_reader.skip_record().unwrap());
+        assert_eq!(csv_reader.read_transaction().unwrap(), Some(tx2_for_test()));
+        assert!(!csv_reader.skip_record().unwrap());
+        assert_eq!(csv_reader.bytes_read(), EXPECTED_CSV_MULT.len() as u64);
+    }
+
+    #[test]
+    fn test_csv_error_position() {
+        let data = EXPECTED_CSV_MULT.replace("PENDING", "UNKNOWN");
+        let mut csv_reader =
+            CsvTxReader::with_options(Cursor::new(data.as_bytes()), ReaderOptions::default())
+                .unwrap();
+
+        assert_eq!(csv_reader.read_transaction().unwrap(), Some(tx1_for_test()));
+        let err = csv_reader.read_transaction().unwrap_err();
+        let position = err.position().unwrap();
+        assert_eq!(position.record, 1);
+        assert_eq!(position.line, Some(5));
+        assert_eq!(position.column, Some(9));
+        assert_eq!(position.byte, data.find("1000000000000001").unwrap() as u64);
+        assert!(matches!(
+            err.inner(),
+            ParsError::InvalidEnumValue { field, value } if field == STATUS && value == "UNKNOWN"
+        ));
+        assert_eq!(err.field(), Some(STATUS));
+        assert!(
+            err.snippet()
+                .unwrap()
+                .starts_with("1000000000000001,TRANSFER,")
+        );
+    }
 }
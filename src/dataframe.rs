@@ -0,0 +1,189 @@
+use super::constants::*;
+use super::error::ParsError;
+use super::format::TransactionRead;
+use super::reconcile::Field;
+use super::transaction::{Transaction, TxStatus, TxType};
+use super::tx_format::TxReader;
+use chrono::DateTime;
+use polars::prelude::*;
+use std::path::Path;
+
+/// Имена столбцов DataFrame в порядке полей транзакции
+pub const COLUMNS: [&str; 8] = [
+    "tx_id",
+    "tx_type",
+    "from_user_id",
+    "to_user_id",
+    "amount",
+    "timestamp",
+    "status",
+    "description",
+];
+
+/// DataFrame из всех оставшихся транзакций читателя. Столбцы [COLUMNS]: идентификаторы
+/// UInt64, сумма Int64, время Datetime(ms, UTC), тип, статус и описание String
+pub fn to_dataframe<R: TransactionRead + ?Sized>(reader: &mut R) -> Result<DataFrame, ParsError> {
+    let mut tx_id = Vec::new();
+    let mut tx_type = Vec::new();
+    let mut from_user_id = Vec::new();
+    let mut to_user_id = Vec::new();
+    let mut amount = Vec::new();
+    let mut timestamp = Vec::new();
+    let mut status = Vec::new();
+    let mut description = Vec::new();
+    while let Some(tx) = reader.read_transaction()? {
+        tx_id.push(tx.tx_id);
+        tx_type.push(Field::TxType.value(&tx));
+        from_user_id.push(tx.from_user_id);
+        to_user_id.push(tx.to_user_id);
+        amount.push(tx.amount);
+        timestamp.push(tx.timestamp.timestamp_millis());
+        status.push(Field::Status.value(&tx));
+        description.push(tx.description);
+    }
+    let timestamp = Int64Chunked::from_vec(COLUMNS[5].into(), timestamp)
+        .into_datetime(TimeUnit::Milliseconds, Some(TimeZone::UTC));
+    DataFrame::new(vec![
+        Column::new(COLUMNS[0].into(), tx_id),
+        Column::new(COLUMNS[1].into(), tx_type),
+        Column::new(COLUMNS[2].into(), from_user_id),
+        Column::new(COLUMNS[3].into(), to_user_id),
+        Column::new(COLUMNS[4].into(), amount),
+        timestamp.into_series().into_column(),
+        Column::new(COLUMNS[6].into(), status),
+        Column::new(COLUMNS[7].into(), description),
+    ])
+    .map_err(polars_error)
+}
+
+/// DataFrame из файла транзакций. Формат и сжатие определяются по расширению файла
+pub fn read_dataframe<P: AsRef<Path>>(path: P) -> Result<DataFrame, ParsError> {
+    to_dataframe(&mut TxReader::from_path(path)?)
+}
+
+/// Транзакции из DataFrame со столбцами [COLUMNS]. Лишние столбцы не учитываются,
+/// числовые столбцы приводятся к нужному типу без потери значений. Время задается
+/// столбцом Datetime или числом миллисекунд от начала эпохи
+pub fn from_dataframe(df: &DataFrame) -> Result<Vec<Transaction>, ParsError> {
+    let column = |idx: usize, dtype: &DataType| {
+        df.column(COLUMNS[idx])
+            .and_then(|col| col.strict_cast(dtype))
+            .map_err(polars_error)
+    };
+    let tx_id = column(0, &DataType::UInt64)?;
+    let tx_type = column(1, &DataType::String)?;
+    let from_user_id = column(2, &DataType::UInt64)?;
+    let to_user_id = column(3, &DataType::UInt64)?;
+    let amount = column(4, &DataType::Int64)?;
+    let timestamp = match df.column(COLUMNS[5]).map_err(polars_error)?.dtype() {
+        DataType::Datetime(_, tz) => {
+            column(5, &DataType::Datetime(TimeUnit::Milliseconds, tz.clone()))?
+                .datetime()
+                .map_err(polars_error)?
+                .physical()
+                .clone()
+        }
+        _ => column(5, &DataType::Int64)?
+            .i64()
+            .map_err(polars_error)?
+            .clone(),
+    };
+    let status = column(6, &DataType::String)?;
+    let description = column(7, &DataType::String)?;
+
+    let tx_id = tx_id.u64().map_err(polars_error)?;
+    let tx_type = tx_type.str().map_err(polars_error)?;
+    let from_user_id = from_user_id.u64().map_err(polars_error)?;
+    let to_user_id = to_user_id.u64().map_err(polars_error)?;
+    let amount = amount.i64().map_err(polars_error)?;
+    let status = status.str().map_err(polars_error)?;
+    let description = description.str().map_err(polars_error)?;
+
+    let mut res = Vec::with_capacity(df.height());
+    for row in 0..df.height() {
+        let tx_type = match not_null(tx_type.get(row), row, 1)? {
+            DEPOSIT => TxType::Deposit,
+            TRANSFER => TxType::Transfer,
+            WITHDRAWAL => TxType::Withdrawal,
+            val => return Err(wrong_value(row, 1, val)),
+        };
+        let status = match not_null(status.get(row), row, 6)? {
+            SUCCESS => TxStatus::Success,
+            FAILURE => TxStatus::Failure,
+            PENDING => TxStatus::Pending,
+            val => return Err(wrong_value(row, 6, val)),
+        };
+        let millis = not_null(timestamp.get(row), row, 5)?;
+        res.push(Transaction {
+            tx_id: not_null(tx_id.get(row), row, 0)?,
+            tx_type,
+            from_user_id: not_null(from_user_id.get(row), row, 2)?,
+            to_user_id: not_null(to_user_id.get(row), row, 3)?,
+            amount: not_null(amount.get(row), row, 4)?,
+            timestamp: DateTime::from_timestamp_millis(millis)
+                .ok_or_else(|| wrong_value(row, 5, millis))?,
+            status,
+            description: not_null(description.get(row), row, 7)?.to_owned(),
+        });
+    }
+    Ok(res)
+}
+
+fn not_null<T>(val: Option<T>, row: usize, idx: usize) -> Result<T, ParsError> {
+    val.ok_or_else(|| {
+        ParsError::WrongFormat(format!(
+            "Строка {row}: пустое значение столбца {}",
+            COLUMNS[idx]
+        ))
+    })
+}
+
+fn wrong_value(row: usize, idx: usize, val: impl std::fmt::Display) -> ParsError {
+    ParsError::WrongFormat(format!(
+        "Строка {row}: некорректное значение столбца {}: {val}",
+        COLUMNS[idx]
+    ))
+}
+
+fn polars_error(e: PolarsError) -> ParsError {
+    ParsError::WrongFormat(e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::Format;
+    use std::io::Cursor;
+
+    const CSV: &str = "TX_ID,TX_TYPE,FROM_USER_ID,TO_USER_ID,AMOUNT,TIMESTAMP,STATUS,DESCRIPTION
+1,DEPOSIT,0,9,100,1633036860000,SUCCESS,\"Record number 1\"
+2,TRANSFER,9,7,-250,1633036920000,PENDING,\"Record number 2\"
+";
+
+    #[test]
+    fn test_dataframe() {
+        let mut reader = TxReader::new(Cursor::new(CSV), Format::Csv).unwrap();
+        let df = to_dataframe(&mut reader).unwrap();
+        assert_eq!(df.shape(), (2, 8));
+        assert_eq!(df.get_column_names_str(), COLUMNS);
+        assert_eq!(
+            df.column("timestamp").unwrap().dtype(),
+            &DataType::Datetime(TimeUnit::Milliseconds, Some(TimeZone::UTC))
+        );
+        let txs = from_dataframe(&df).unwrap();
+        let mut reader = TxReader::new(Cursor::new(CSV), Format::Csv).unwrap();
+        assert_eq!(txs, reader.read_all().unwrap());
+
+        // Числовые столбцы другого типа приводятся, отрицательный идентификатор — ошибка
+        let mut df = df;
+        df.with_column(Column::new("tx_id".into(), [1i64, -2]))
+            .unwrap();
+        assert!(from_dataframe(&df).is_err());
+        df.with_column(Column::new("tx_id".into(), [1i64, 2]))
+            .unwrap();
+        df.with_column(Column::new("timestamp".into(), [0i32, 60_000]))
+            .unwrap();
+        let txs = from_dataframe(&df).unwrap();
+        assert_eq!(txs[1].timestamp.timestamp(), 60);
+    }
+}
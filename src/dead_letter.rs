@@ -0,0 +1,227 @@
+use super::error::ParsError;
+use super::reconcile::json_string;
+use std::fmt::Write as _;
+use std::io::Write;
+
+/// Запись, пропущенная читателем в режиме
+/// [ErrorPolicy::Skip](crate::options::ErrorPolicy::Skip)
+#[derive(Debug)]
+pub struct Rejected<'a> {
+    /// Порядковый номер записи, начиная с нуля
+    pub record: u64,
+    /// Смещение начала исходных байт записи от начала потока
+    pub byte: u64,
+    /// Ошибка разбора
+    pub error: &'a ParsError,
+    /// Исходные байты записи от конца предыдущей записи до места, с которого
+    /// продолжено чтение. Для csv и text это строки записи, для bin — запись целиком
+    pub raw: &'a [u8],
+}
+
+/// Получатель пропущенных записей. Ошибка получателя прерывает чтение.
+/// Реализован для замыканий `FnMut(&Rejected) -> Result<(), ParsError>`
+pub trait DeadLetter: Send {
+    /// Обработка пропущенной записи
+    fn reject(&mut self, rejected: &Rejected<'_>) -> Result<(), ParsError>;
+
+    /// Сброс буферизованных данных
+    fn flush(&mut self) -> Result<(), ParsError> {
+        Ok(())
+    }
+}
+
+impl<F> DeadLetter for F
+where
+    F: FnMut(&Rejected<'_>) -> Result<(), ParsError> + Send,
+{
+    fn reject(&mut self, rejected: &Rejected<'_>) -> Result<(), ParsError> {
+        self(rejected)
+    }
+}
+
+/// Запись пропущенных записей в поток json по строке на запись:
+/// `{"record":1,"byte":120,"code":"invalid_enum_value","error":"...","raw":"..."}`.
+/// Исходные байты в корректной UTF-8 записываются строкой `raw`, иначе (как правило,
+/// для bin) — шестнадцатеричной строкой `raw_hex`
+///
+/// ```
+/// use fin_parser::dead_letter::DeadLetterWriter;
+/// use fin_parser::format::Format;
+/// use fin_parser::options::ErrorPolicy;
+/// use fin_parser::tx_format::TxReader;
+/// use std::io::Cursor;
+///
+/// let csv = "TX_ID,TX_TYPE,FROM_USER_ID,TO_USER_ID,AMOUNT,TIMESTAMP,STATUS,DESCRIPTION\n\
+///     1,DEPOSIT,0,5,100,1633036860000,SUCCESS,\"Record number 1\"\n\
+///     2,DEPOZIT,0,6,200,1633036920000,SUCCESS,\"Record number 2\"\n";
+/// let mut reader = TxReader::new(Cursor::new(csv), Format::Csv)
+///     .unwrap()
+///     .with_error_policy(ErrorPolicy::Skip)
+///     .with_dead_letter(DeadLetterWriter::new(Vec::new()));
+/// assert_eq!(reader.read_all().unwrap().len(), 1);
+/// ```
+pub struct DeadLetterWriter<W: Write + Send> {
+    stream: W,
+}
+
+impl<W: Write + Send> DeadLetterWriter<W> {
+    /// Запись в поток, например в файл
+    pub fn new(stream: W) -> Self {
+        Self { stream }
+    }
+
+    /// Возврат исходного потока
+    pub fn into_inner(self) -> W {
+        self.stream
+    }
+}
+
+impl<W: Write + Send> DeadLetter for DeadLetterWriter<W> {
+    fn reject(&mut self, rejected: &Rejected<'_>) -> Result<(), ParsError> {
+        let raw = match std::str::from_utf8(rejected.raw) {
+            Ok(text) => format!("\"raw\":{}", json_string(text)),
+            Err(_) => {
+                let mut hex = String::with_capacity(rejected.raw.len() * 2);
+                for byte in rejected.raw {
+                    let _ = write!(hex, "{byte:02x}");
+                }
+                format!("\"raw_hex\":\"{hex}\"")
+            }
+        };
+        writeln!(
+            self.stream,
+            "{{\"record\":{},\"byte\":{},\"code\":\"{}\",\"error\":{},{raw}}}",
+            rejected.record,
+            rejected.byte,
+            rejected.error.code(),
+            json_string(&rejected.error.inner().to_string()),
+        )?;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), ParsError> {
+        self.stream.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::Format;
+    use crate::generate::{GenerateOptions, Generator};
+    use crate::options::ErrorPolicy;
+    use crate::tx_format::{TxReader, TxWriter};
+    use std::io::Cursor;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn test_dead_letter_raw() {
+        let txs: Vec<_> = Generator::new(GenerateOptions::new().with_seed(3), 10)
+            .unwrap()
+            .collect();
+        for fin_format in Format::ALL {
+            let mut writer = TxWriter::new(Vec::new(), fin_format).unwrap();
+            writer.write_all(&txs).unwrap();
+            let data = writer.into_inner().unwrap();
+            let header_len = match fin_format {
+                Format::Csv => data.iter().position(|&byte| byte == b'\n').unwrap() + 1,
+                _ => 0,
+            };
+
+            // Границы записи 4 и байт поля, значение которого портится
+            let mut reader = TxReader::new(Cursor::new(data.clone()), fin_format).unwrap();
+            reader.skip(4).unwrap();
+            let start = reader.position().bytes as usize;
+            reader.skip(1).unwrap();
+            let end = reader.position().bytes as usize;
+            let field = match fin_format {
+                Format::Csv => start,
+                Format::Text => start + find(&data[start..end], b"AMOUNT: ") + 8,
+                _ => start + 16,
+            };
+            let mut bad = data.clone();
+            bad[field] = b'x';
+
+            let rejected = Arc::new(Mutex::new(Vec::new()));
+            let sink = rejected.clone();
+            let mut reader = TxReader::new(Cursor::new(bad.clone()), fin_format)
+                .unwrap()
+                .with_error_policy(ErrorPolicy::Skip)
+                .with_dead_letter(move |rejected_tx: &Rejected<'_>| {
+                    sink.lock().unwrap().push((
+                        rejected_tx.record,
+                        rejected_tx.byte,
+                        rejected_tx.raw.to_vec(),
+                    ));
+                    Ok(())
+                });
+            let read = reader.read_all().unwrap();
+            let rejected = rejected.lock().unwrap();
+            assert_eq!(read.len(), 9, "{fin_format}");
+            assert_eq!(rejected.len(), 1, "{fin_format}");
+            let (record, byte, raw) = &rejected[0];
+            assert_eq!((*record, *byte), (4, start as u64), "{fin_format}");
+            assert_eq!(raw[..], bad[start..end], "{fin_format}");
+
+            // Исправленная запись загружается повторно
+            let mut fixed = data[..header_len].to_vec();
+            fixed.extend_from_slice(raw);
+            fixed[header_len + field - start] = data[field];
+            let reloaded = TxReader::new(Cursor::new(fixed), fin_format)
+                .unwrap()
+                .read_all()
+                .unwrap();
+            assert_eq!(reloaded[..], txs[4..5], "{fin_format}");
+        }
+    }
+
+    fn find(data: &[u8], needle: &[u8]) -> usize {
+        data.windows(needle.len())
+            .position(|window| window == needle)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_dead_letter_writer() {
+        let csv = "TX_ID,TX_TYPE,FROM_USER_ID,TO_USER_ID,AMOUNT,TIMESTAMP,STATUS,DESCRIPTION
+1,DEPOSIT,0,5,100,1633036860000,SUCCESS,\"Record number 1\"
+2,DEPOZIT,0,6,200,1633036920000,SUCCESS,\"Record \"\"2\"\"\"
+";
+        let path = std::env::temp_dir().join(format!(
+            "fin-parser-dead-letter-{}.jsonl",
+            std::process::id()
+        ));
+        let out = std::fs::File::create(&path).unwrap();
+        let mut reader = TxReader::new(Cursor::new(csv), Format::Csv)
+            .unwrap()
+            .with_error_policy(ErrorPolicy::Skip)
+            .with_dead_letter(DeadLetterWriter::new(out));
+        assert_eq!(reader.read_all().unwrap().len(), 1);
+        drop(reader);
+        let line = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let byte = csv.find("2,DEPOZIT").unwrap();
+        assert!(line.starts_with(&format!(
+            "{{\"record\":1,\"byte\":{byte},\"code\":\"invalid_enum_value\",\"error\":"
+        )));
+        assert!(line.ends_with(
+            ",\"raw\":\"2,DEPOZIT,0,6,200,1633036920000,SUCCESS,\\\"Record \\\"\\\"2\\\"\\\"\\\"\\n\"}\n"
+        ));
+
+        let mut dead = DeadLetterWriter::new(Vec::new());
+        let error = ParsError::EndOfStream;
+        dead.reject(&Rejected {
+            record: 0,
+            byte: 0,
+            error: &error,
+            raw: &[0xff, 0x01],
+        })
+        .unwrap();
+        assert!(
+            String::from_utf8(dead.into_inner())
+                .unwrap()
+                .ends_with("\"raw_hex\":\"ff01\"}\n")
+        );
+    }
+}
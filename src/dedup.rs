@@ -0,0 +1,220 @@
+use super::error::ParsError;
+use super::format::{TransactionRead, read_fin_data};
+use super::sort::{ExternalSorter, MergedRuns, Order, SortKey};
+use super::transaction::Transaction;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+/// Какую из транзакций с повторяющимся tx_id оставить
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Default)]
+pub enum Keep {
+    /// Первую встреченную
+    #[default]
+    First,
+    /// Последнюю встреченную
+    Last,
+    /// Вернуть ошибку [ParsError::DuplicateTxId] на первом повторе
+    Error,
+}
+
+enum Source {
+    Stream,
+    Buffered(std::vec::IntoIter<Transaction>),
+    Sorted {
+        runs: MergedRuns,
+        next: Option<Transaction>,
+    },
+}
+
+/// Читатель, отбрасывающий транзакции с повторяющимся tx_id.
+///
+/// В режиме по умолчанию идентификаторы хранятся в хеш-множестве, а порядок потока
+/// сохраняется. Для [Keep::First] и [Keep::Error] поток читается по одной транзакции,
+/// для [Keep::Last] весь поток загружается в память при первом чтении.
+/// В режиме с диском ([DedupReader::on_disk]) поток сортируется по tx_id внешней
+/// сортировкой и повторы отбрасываются среди соседних записей: память не зависит
+/// от количества идентификаторов, но транзакции возвращаются в порядке tx_id
+pub struct DedupReader<R: TransactionRead> {
+    reader: R,
+    keep: Keep,
+    sorter: Option<ExternalSorter>,
+    source: Option<Source>,
+    seen: HashSet<u64>,
+    duplicates: Vec<u64>,
+}
+
+impl<R: TransactionRead> DedupReader<R> {
+    /// Читатель с хранением идентификаторов в памяти
+    pub fn new(reader: R, keep: Keep) -> Self {
+        Self {
+            reader,
+            keep,
+            sorter: None,
+            source: None,
+            seen: HashSet::new(),
+            duplicates: Vec::new(),
+        }
+    }
+
+    /// Переход в режим с диском: сортировка по tx_id участками по run_records
+    /// транзакций с временными файлами в temp_dir, см. [ExternalSorter]
+    pub fn on_disk<P: AsRef<Path>>(mut self, run_records: usize, temp_dir: P) -> Self {
+        let sorter = ExternalSorter::new(SortKey::TxId, Order::Ascending)
+            .with_run_records(run_records)
+            .with_temp_dir(temp_dir);
+        self.sorter = Some(sorter);
+        self
+    }
+
+    /// Идентификаторы отброшенных транзакций, по одному на каждую отброшенную запись
+    pub fn duplicates(&self) -> &[u64] {
+        &self.duplicates
+    }
+
+    /// Чтение следующей транзакции без повторов. None означает конец потока
+    pub fn read_transaction(&mut self) -> Result<Option<Transaction>, ParsError> {
+        let source = match self.source.take() {
+            Some(source) => source,
+            None => self.open()?,
+        };
+        let source = self.source.insert(source);
+        match source {
+            Source::Stream => loop {
+                let Some(tx) = self.reader.read_transaction()? else {
+                    return Ok(None);
+                };
+                if self.seen.insert(tx.tx_id) {
+                    return Ok(Some(tx));
+                }
+                self.duplicates.push(tx.tx_id);
+                if self.keep == Keep::Error {
+                    return Err(ParsError::DuplicateTxId { tx_id: tx.tx_id });
+                }
+            },
+            Source::Buffered(txs) => Ok(txs.next()),
+            Source::Sorted { runs, next } => {
+                let mut current = match next.take() {
+                    Some(tx) => tx,
+                    None => match runs.next().transpose()? {
+                        Some(tx) => tx,
+                        None => return Ok(None),
+                    },
+                };
+                while let Some(tx) = runs.next().transpose()? {
+                    if tx.tx_id != current.tx_id {
+                        *next = Some(tx);
+                        break;
+                    }
+                    self.duplicates.push(tx.tx_id);
+                    match self.keep {
+                        Keep::First => {}
+                        Keep::Last => current = tx,
+                        Keep::Error => return Err(ParsError::DuplicateTxId { tx_id: tx.tx_id }),
+                    }
+                }
+                Ok(Some(current))
+            }
+        }
+    }
+
+    fn open(&mut self) -> Result<Source, ParsError> {
+        if let Some(sorter) = &self.sorter {
+            let runs = sorter.sort(&mut self.reader)?;
+            return Ok(Source::Sorted { runs, next: None });
+        }
+        if self.keep != Keep::Last {
+            return Ok(Source::Stream);
+        }
+
+        let txs = read_fin_data(&mut self.reader)?;
+        let mut last: HashMap<u64, usize> = HashMap::new();
+        for (idx, tx) in txs.iter().enumerate() {
+            if last.insert(tx.tx_id, idx).is_some() {
+                self.duplicates.push(tx.tx_id);
+            }
+        }
+        let txs: Vec<Transaction> = txs
+            .into_iter()
+            .enumerate()
+            .filter(|(idx, tx)| last[&tx.tx_id] == *idx)
+            .map(|(_, tx)| tx)
+            .collect();
+        Ok(Source::Buffered(txs.into_iter()))
+    }
+
+    /// Метод чтения всех оставшихся в потоке транзакций без повторов
+    pub fn read_all(&mut self) -> Result<Vec<Transaction>, ParsError> {
+        read_fin_data(self)
+    }
+
+    /// Возврат исходного читателя
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+impl<R: TransactionRead> TransactionRead for DedupReader<R> {
+    fn read_transaction(&mut self) -> Result<Option<Transaction>, ParsError> {
+        DedupReader::read_transaction(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::Format;
+    use crate::test_util::{reader, tx};
+    use crate::tx_format::TxReader;
+    use std::io::Cursor;
+
+    fn reader_for_test() -> TxReader<Cursor<Vec<u8>>> {
+        let txs =
+            [(3, 1), (1, 2), (3, 3), (2, 4), (1, 5), (3, 6)].map(|(tx_id, amount)| Transaction {
+                amount,
+                ..tx(tx_id)
+            });
+        reader(&txs, Format::Bin)
+    }
+
+    fn ids_and_amounts(txs: &[Transaction]) -> Vec<(u64, i64)> {
+        txs.iter().map(|tx| (tx.tx_id, tx.amount)).collect()
+    }
+
+    #[test]
+    fn test_dedup_memory() {
+        let mut reader = DedupReader::new(reader_for_test(), Keep::First);
+        let txs = reader.read_all().unwrap();
+        assert_eq!(ids_and_amounts(&txs), [(3, 1), (1, 2), (2, 4)]);
+        assert_eq!(reader.duplicates(), [3, 1, 3]);
+
+        let mut reader = DedupReader::new(reader_for_test(), Keep::Last);
+        let txs = reader.read_all().unwrap();
+        assert_eq!(ids_and_amounts(&txs), [(2, 4), (1, 5), (3, 6)]);
+        assert_eq!(reader.duplicates().len(), 3);
+
+        let mut reader = DedupReader::new(reader_for_test(), Keep::Error);
+        assert_eq!(reader.read_transaction().unwrap().unwrap().tx_id, 3);
+        assert_eq!(reader.read_transaction().unwrap().unwrap().tx_id, 1);
+        let err = reader.read_transaction().unwrap_err();
+        assert!(matches!(err, ParsError::DuplicateTxId { tx_id: 3 }));
+    }
+
+    #[test]
+    fn test_dedup_disk() {
+        let temp_dir = std::env::temp_dir();
+        let mut reader = DedupReader::new(reader_for_test(), Keep::First).on_disk(2, &temp_dir);
+        let txs = reader.read_all().unwrap();
+        assert_eq!(ids_and_amounts(&txs), [(1, 2), (2, 4), (3, 1)]);
+        assert_eq!(reader.duplicates(), [1, 3, 3]);
+
+        let mut reader = DedupReader::new(reader_for_test(), Keep::Last).on_disk(2, &temp_dir);
+        let txs = reader.read_all().unwrap();
+        assert_eq!(ids_and_amounts(&txs), [(1, 5), (2, 4), (3, 6)]);
+
+        let mut reader = DedupReader::new(reader_for_test(), Keep::Error).on_disk(2, &temp_dir);
+        assert!(matches!(
+            reader.read_all().unwrap_err(),
+            ParsError::DuplicateTxId { tx_id: 1 }
+        ));
+    }
+}
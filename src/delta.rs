@@ -0,0 +1,460 @@
+use super::bin_format::{read_record, write_record};
+use super::error::ParsError;
+use super::format::{TransactionRead, TransactionWrite};
+use super::transaction::Transaction;
+use std::collections::{HashMap, HashSet};
+use std::io::{self, Read, Write};
+
+/// Сигнатура разностного файла
+const DELTA_MAGIC: [u8; 4] = *b"YPBD";
+
+/// Версия формата разностного файла
+const DELTA_VERSION: u32 = 1;
+
+const OP_INSERT: u8 = 1;
+const OP_UPDATE: u8 = 2;
+const OP_DELETE: u8 = 3;
+
+/// Изменение снимка по одной транзакции
+#[derive(Eq, PartialEq, Debug)]
+pub enum DeltaOp {
+    /// Новая транзакция, которой не было в снимке
+    Insert(Transaction),
+    /// Новая версия транзакции с тем же tx_id
+    Update(Transaction),
+    /// Удаление транзакции с tx_id
+    Delete(u64),
+}
+
+impl DeltaOp {
+    /// Идентификатор изменяемой транзакции
+    pub fn tx_id(&self) -> u64 {
+        match self {
+            Self::Insert(tx) | Self::Update(tx) => tx.tx_id,
+            Self::Delete(tx_id) => *tx_id,
+        }
+    }
+}
+
+/// Изменения снимка транзакций: добавления, новые версии и удаления по tx_id.
+/// Вместо всего архива передаются только изменения, новый снимок собирается
+/// из предыдущего функцией [apply].
+///
+/// Разностный файл ([Delta::write_to]): сигнатура `YPBD`, версия (u32) и изменения
+/// до конца потока. Изменение начинается с кода (u8): 1 — добавление, 2 — новая версия,
+/// за которыми следует запись bin версии 1, или 3 — удаление, за которым следует
+/// tx_id (u64, big-endian)
+///
+/// ```
+/// use fin_parser::delta::{Delta, apply};
+/// use fin_parser::generate::{GenerateOptions, Generator};
+/// use fin_parser::format::Format;
+/// use fin_parser::tx_format::TxWriter;
+///
+/// let mut base = Generator::new(GenerateOptions::new().with_seed(1), 5).unwrap();
+/// let mut delta = Delta::new();
+/// delta.delete(3);
+///
+/// let mut out = TxWriter::new(Vec::new(), Format::Csv).unwrap();
+/// assert_eq!(apply(&mut base, &delta, &mut out).unwrap(), 4);
+/// ```
+#[derive(Eq, PartialEq, Debug, Default)]
+pub struct Delta {
+    ops: Vec<DeltaOp>,
+}
+
+impl Delta {
+    /// Пустой набор изменений
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Изменения, превращающие снимок base в снимок target. Снимок base
+    /// держится в памяти целиком, target читается потоком. Добавления и новые
+    /// версии идут в порядке target, удаления — в конце по возрастанию tx_id
+    pub fn between<B, T>(base: &mut B, target: &mut T) -> Result<Self, ParsError>
+    where
+        B: TransactionRead + ?Sized,
+        T: TransactionRead + ?Sized,
+    {
+        let mut prev = HashMap::new();
+        while let Some(tx) = base.read_transaction()? {
+            let tx_id = tx.tx_id;
+            if prev.insert(tx_id, tx).is_some() {
+                return Err(ParsError::DuplicateTxId { tx_id });
+            }
+        }
+        let mut res = Self::new();
+        let mut seen = HashSet::new();
+        while let Some(tx) = target.read_transaction()? {
+            if !seen.insert(tx.tx_id) {
+                return Err(ParsError::DuplicateTxId { tx_id: tx.tx_id });
+            }
+            match prev.remove(&tx.tx_id) {
+                None => res.insert(tx),
+                Some(old) if old != tx => res.update(tx),
+                Some(_) => (),
+            }
+        }
+        let mut deleted: Vec<u64> = prev.into_keys().collect();
+        deleted.sort_unstable();
+        for tx_id in deleted {
+            res.delete(tx_id);
+        }
+        Ok(res)
+    }
+
+    /// Добавление транзакции
+    pub fn insert(&mut self, tx: Transaction) {
+        self.ops.push(DeltaOp::Insert(tx));
+    }
+
+    /// Новая версия транзакции
+    pub fn update(&mut self, tx: Transaction) {
+        self.ops.push(DeltaOp::Update(tx));
+    }
+
+    /// Удаление транзакции
+    pub fn delete(&mut self, tx_id: u64) {
+        self.ops.push(DeltaOp::Delete(tx_id));
+    }
+
+    /// Изменения в порядке добавления
+    pub fn ops(&self) -> &[DeltaOp] {
+        &self.ops
+    }
+
+    /// Количество изменений
+    pub fn len(&self) -> usize {
+        self.ops.len()
+    }
+
+    /// Изменений нет
+    pub fn is_empty(&self) -> bool {
+        self.ops.is_empty()
+    }
+
+    /// Чтение разностного файла из потока
+    pub fn read_from<In: Read>(mut stream: In) -> Result<Self, ParsError> {
+        let mut header = [0; 8];
+        stream.read_exact(&mut header)?;
+        if header[..4] != DELTA_MAGIC {
+            return Err(ParsError::WrongFormat(
+                "Файл не является разностным файлом".to_owned(),
+            ));
+        }
+        let version = u32::from_be_bytes(header[4..].try_into().unwrap());
+        if version != DELTA_VERSION {
+            return Err(ParsError::WrongFormat(format!(
+                "Неподдерживаемая версия разностного файла: {version}"
+            )));
+        }
+        let mut res = Self::new();
+        let mut code = [0; 1];
+        loop {
+            // Конец потока допустим только на границе изменений
+            match stream.read_exact(&mut code) {
+                Ok(()) => (),
+                Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(res),
+                Err(e) => return Err(e.into()),
+            }
+            match code[0] {
+                OP_INSERT => res.insert(read_record(&mut stream)?),
+                OP_UPDATE => res.update(read_record(&mut stream)?),
+                OP_DELETE => {
+                    let mut buf = [0; 8];
+                    stream.read_exact(&mut buf).map_err(|e| match e.kind() {
+                        io::ErrorKind::UnexpectedEof => ParsError::TruncatedRecord,
+                        _ => e.into(),
+                    })?;
+                    res.delete(u64::from_be_bytes(buf));
+                }
+                code => {
+                    return Err(ParsError::WrongFormat(format!(
+                        "Неизвестный код изменения: {code}"
+                    )));
+                }
+            }
+        }
+    }
+
+    /// Запись разностного файла в поток
+    pub fn write_to<Out: Write>(&self, mut stream: Out) -> Result<(), ParsError> {
+        stream.write_all(&DELTA_MAGIC)?;
+        stream.write_all(&DELTA_VERSION.to_be_bytes())?;
+        for op in &self.ops {
+            match op {
+                DeltaOp::Insert(tx) => {
+                    stream.write_all(&[OP_INSERT])?;
+                    write_record(&mut stream, tx)?;
+                }
+                DeltaOp::Update(tx) => {
+                    stream.write_all(&[OP_UPDATE])?;
+                    write_record(&mut stream, tx)?;
+                }
+                DeltaOp::Delete(tx_id) => {
+                    stream.write_all(&[OP_DELETE])?;
+                    stream.write_all(&tx_id.to_be_bytes())?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Сборка нового снимка из снимка base и изменений delta с записью в out.
+/// Порядок транзакций base сохраняется, новые версии занимают место прежних,
+/// добавленные транзакции записываются в конце. Ошибка [ParsError::MissingTxId],
+/// если изменяемой или удаляемой транзакции нет в base, и [ParsError::DuplicateTxId],
+/// если добавляемая уже есть в base или одна транзакция изменяется дважды.
+/// Снимок base читается в память целиком и записывается только после проверки
+/// изменений, поэтому при ошибке в out ничего не пишется.
+/// Возвращает количество записанных транзакций
+pub fn apply<B, W>(base: &mut B, delta: &Delta, out: &mut W) -> Result<u64, ParsError>
+where
+    B: TransactionRead + ?Sized,
+    W: TransactionWrite + ?Sized,
+{
+    let mut changes = HashMap::new();
+    for op in &delta.ops {
+        if changes.insert(op.tx_id(), op).is_some() {
+            return Err(ParsError::DuplicateTxId { tx_id: op.tx_id() });
+        }
+    }
+    let mut found = HashSet::new();
+    let mut res = Vec::new();
+    while let Some(tx) = base.read_transaction()? {
+        match changes.get(&tx.tx_id) {
+            None => res.push(tx),
+            Some(DeltaOp::Insert(_)) => {
+                return Err(ParsError::DuplicateTxId { tx_id: tx.tx_id });
+            }
+            Some(DeltaOp::Update(new)) => {
+                found.insert(tx.tx_id);
+                res.push(new.clone());
+            }
+            Some(DeltaOp::Delete(_)) => {
+                found.insert(tx.tx_id);
+            }
+        }
+    }
+    if let Some(op) = delta
+        .ops
+        .iter()
+        .find(|op| !matches!(op, DeltaOp::Insert(_)) && !found.contains(&op.tx_id()))
+    {
+        return Err(ParsError::MissingTxId { tx_id: op.tx_id() });
+    }
+    for op in &delta.ops {
+        if let DeltaOp::Insert(tx) = op {
+            res.push(tx.clone());
+        }
+    }
+    for tx in &res {
+        out.write_transaction(tx)?;
+    }
+    Ok(res.len() as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::Format;
+    use crate::generate::{GenerateOptions, Generator};
+    use crate::test_util::{reader, tx, txs};
+    use crate::tx_format::{TxReader, TxWriter};
+
+    fn base_for_test() -> Vec<Transaction> {
+        Generator::new(GenerateOptions::new().with_seed(5), 50)
+            .unwrap()
+            .collect()
+    }
+
+    /// Снимок base без tx_id, оканчивающихся на 3, с измененной первой
+    /// транзакцией и добавленной транзакцией 1000
+    fn target_for_test(base: &[Transaction]) -> Vec<Transaction> {
+        let mut target: Vec<_> = base
+            .iter()
+            .filter(|tx| tx.tx_id % 10 != 3)
+            .cloned()
+            .collect();
+        target[0].amount += 1;
+        target.push(Transaction {
+            tx_id: 1000,
+            ..base[1].clone()
+        });
+        target
+    }
+
+    fn apply_to(base: &[Transaction], delta: &Delta) -> Result<Vec<Transaction>, ParsError> {
+        let mut out = TxWriter::new(Vec::new(), Format::Bin)?;
+        let written = apply(&mut reader(base, Format::Bin), delta, &mut out)?;
+        let buf = out.into_inner()?;
+        let res = TxReader::new(buf.as_slice(), Format::Bin)?.read_all()?;
+        assert_eq!(written, res.len() as u64);
+        Ok(res)
+    }
+
+    /// Разностный файл с сигнатурой, версией version и изменениями ops
+    fn delta_file(version: u32, ops: &[u8]) -> Vec<u8> {
+        let mut buf = DELTA_MAGIC.to_vec();
+        buf.extend_from_slice(&version.to_be_bytes());
+        buf.extend_from_slice(ops);
+        buf
+    }
+
+    #[test]
+    fn test_delta_apply() {
+        let base = base_for_test();
+        let target = target_for_test(&base);
+        let delta = Delta::between(
+            &mut reader(&base, Format::Bin),
+            &mut reader(&target, Format::Csv),
+        )
+        .unwrap();
+        assert_eq!(delta.len(), 7);
+        assert_eq!(delta.ops()[0], DeltaOp::Update(target[0].clone()));
+        assert_eq!(delta.ops()[1].tx_id(), 1000);
+        assert_eq!(delta.ops()[2], DeltaOp::Delete(3));
+        assert_eq!(apply_to(&base, &delta).unwrap(), target);
+    }
+
+    #[test]
+    fn test_delta_file() {
+        let base = base_for_test();
+        let target = target_for_test(&base);
+        let delta = Delta::between(
+            &mut reader(&base, Format::Bin),
+            &mut reader(&target, Format::Bin),
+        )
+        .unwrap();
+        let mut buf = Vec::new();
+        delta.write_to(&mut buf).unwrap();
+        assert_eq!(Delta::read_from(buf.as_slice()).unwrap(), delta);
+        assert!(
+            Delta::read_from(delta_file(DELTA_VERSION, &[]).as_slice())
+                .unwrap()
+                .is_empty()
+        );
+
+        // Поток обрывается внутри tx_id удаления
+        assert!(matches!(
+            Delta::read_from(&buf[..buf.len() - 1]),
+            Err(ParsError::TruncatedRecord)
+        ));
+        // Поток обрывается внутри записи добавления
+        let truncated = delta_file(DELTA_VERSION, &[OP_INSERT, 0, 0]);
+        assert!(matches!(
+            Delta::read_from(truncated.as_slice()),
+            Err(ParsError::TruncatedRecord)
+        ));
+    }
+
+    /// Поток, возвращающий ошибку чтения
+    struct FailingStream;
+
+    impl Read for FailingStream {
+        fn read(&mut self, _buf: &mut [u8]) -> io::Result<usize> {
+            Err(io::Error::other("сбой чтения"))
+        }
+    }
+
+    #[test]
+    fn test_delta_read_error() {
+        // Ошибка чтения внутри tx_id удаления не считается обрывом записи
+        let buf = delta_file(DELTA_VERSION, &[OP_DELETE, 0, 0]);
+        let err = Delta::read_from(buf.as_slice().chain(FailingStream)).unwrap_err();
+        assert!(matches!(err, ParsError::IoError(_)), "{err:?}");
+    }
+
+    #[test]
+    fn test_delta_bad_header() {
+        assert!(matches!(
+            Delta::read_from(&b"YPBD"[..]),
+            Err(ParsError::EndOfStream)
+        ));
+
+        let mut buf = delta_file(DELTA_VERSION, &[]);
+        buf[..4].copy_from_slice(b"YPBX");
+        let err = Delta::read_from(buf.as_slice()).unwrap_err();
+        assert!(err.to_string().contains("не является разностным"), "{err}");
+
+        let buf = delta_file(DELTA_VERSION + 1, &[]);
+        let err = Delta::read_from(buf.as_slice()).unwrap_err();
+        assert!(
+            err.to_string().contains("версия разностного файла: 2"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn test_delta_unknown_op() {
+        let mut ops = vec![OP_DELETE];
+        ops.extend_from_slice(&7u64.to_be_bytes());
+        ops.push(9);
+        let err = Delta::read_from(delta_file(DELTA_VERSION, &ops).as_slice()).unwrap_err();
+        assert!(
+            err.to_string().contains("Неизвестный код изменения: 9"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn test_delta_duplicates() {
+        let snapshot = txs(1..4);
+        let duplicated = [tx(1), tx(2), tx(1)];
+        assert!(matches!(
+            Delta::between(
+                &mut reader(&duplicated, Format::Bin),
+                &mut reader(&snapshot, Format::Bin)
+            ),
+            Err(ParsError::DuplicateTxId { tx_id: 1 })
+        ));
+        assert!(matches!(
+            Delta::between(
+                &mut reader(&snapshot, Format::Bin),
+                &mut reader(&duplicated, Format::Bin)
+            ),
+            Err(ParsError::DuplicateTxId { tx_id: 1 })
+        ));
+
+        let mut delta = Delta::new();
+        delta.insert(tx(1));
+        assert!(matches!(
+            apply_to(&snapshot, &delta),
+            Err(ParsError::DuplicateTxId { tx_id: 1 })
+        ));
+    }
+
+    #[test]
+    fn test_update_applied_twice() {
+        let snapshot = txs(1..4);
+        let mut delta = Delta::new();
+        delta.update(Transaction { amount: 1, ..tx(2) });
+        delta.update(Transaction { amount: 2, ..tx(2) });
+        assert!(matches!(
+            apply_to(&snapshot, &delta),
+            Err(ParsError::DuplicateTxId { tx_id: 2 })
+        ));
+    }
+
+    #[test]
+    fn test_apply_missing() {
+        let snapshot = txs(1..4);
+        for op in [DeltaOp::Delete(5), DeltaOp::Update(tx(5))] {
+            let delta = Delta { ops: vec![op] };
+            let err = apply_to(&snapshot, &delta).unwrap_err();
+            assert!(matches!(err, ParsError::MissingTxId { tx_id: 5 }));
+            assert_eq!(err.code(), "missing_tx_id");
+        }
+
+        // Частичный снимок не записывается
+        let mut delta = Delta::new();
+        delta.update(Transaction { amount: 1, ..tx(2) });
+        delta.delete(5);
+        let mut out = TxWriter::new(Vec::new(), Format::Bin).unwrap();
+        let res = apply(&mut reader(&snapshot, Format::Bin), &delta, &mut out);
+        assert!(matches!(res, Err(ParsError::MissingTxId { tx_id: 5 })));
+        assert!(out.into_inner().unwrap().is_empty());
+    }
+}
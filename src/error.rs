@@ -1,19 +1,475 @@
+use std::fmt;
 use std::io;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU8, Ordering};
 use thiserror::Error;
 
-/// Класс описания ошибок библиотеки парсинга.
+use super::constants::{AMOUNT, DESCRIPTION, TIMESTAMP};
+
+/// Переменная окружения, задающая язык сообщений об ошибках по умолчанию: `ru` или `en`
+pub const LANGUAGE_ENV: &str = "FIN_PARSER_LANG";
+
+/// Язык сообщений об ошибках и предупреждениях
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Default)]
+pub enum Language {
+    /// Русский (по умолчанию)
+    #[default]
+    Ru,
+    /// Английский
+    En,
+}
+
+impl FromStr for Language {
+    type Err = ParsError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "ru" => Ok(Self::Ru),
+            "en" => Ok(Self::En),
+            _ => Err(ParsError::WrongFormat(format!("Unknown language: {s}"))),
+        }
+    }
+}
+
+const LANGUAGE_UNSET: u8 = 0;
+const LANGUAGE_RU: u8 = 1;
+const LANGUAGE_EN: u8 = 2;
+
+static LANGUAGE: AtomicU8 = AtomicU8::new(LANGUAGE_UNSET);
+
+/// Выбор языка сообщений для всего процесса
+pub fn set_language(lang: Language) {
+    let val = match lang {
+        Language::Ru => LANGUAGE_RU,
+        Language::En => LANGUAGE_EN,
+    };
+    LANGUAGE.store(val, Ordering::Relaxed);
+}
+
+/// Текущий язык сообщений. Если язык не выбран через [set_language],
+/// он берется из переменной окружения [LANGUAGE_ENV], иначе используется русский
+pub fn language() -> Language {
+    match LANGUAGE.load(Ordering::Relaxed) {
+        LANGUAGE_RU => Language::Ru,
+        LANGUAGE_EN => Language::En,
+        _ => {
+            let lang = std::env::var(LANGUAGE_ENV)
+                .ok()
+                .and_then(|val| val.parse().ok())
+                .unwrap_or_default();
+            set_language(lang);
+            lang
+        }
+    }
+}
+
+/// Место в потоке, где обнаружена ошибка разбора
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Default)]
+pub struct ErrorPosition {
+    /// Порядковый номер записи, начиная с нуля
+    pub record: u64,
+    /// Смещение в байтах от начала потока
+    pub byte: u64,
+    /// Номер строки, начиная с единицы. Только для текстовых форматов
+    pub line: Option<u64>,
+    /// Номер столбца в байтах, начиная с единицы. Только для текстовых форматов
+    pub column: Option<u64>,
+}
+
+impl fmt::Display for ErrorPosition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_lang(f, language())
+    }
+}
 
+impl ErrorPosition {
+    fn fmt_lang(&self, f: &mut fmt::Formatter<'_>, lang: Language) -> fmt::Result {
+        let (record, byte, line, column) = match lang {
+            Language::Ru => ("запись", "байт", "строка", "столбец"),
+            Language::En => ("record", "byte", "line", "column"),
+        };
+        write!(f, "{record} {}, {byte} {}", self.record, self.byte)?;
+        if let Some(val) = self.line {
+            write!(f, ", {line} {val}")?;
+        }
+        if let Some(val) = self.column {
+            write!(f, ", {column} {val}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Класс описания ошибок библиотеки парсинга.
+/// Текст ошибки выводится на языке [language], стабильный код ошибки
+/// возвращает [ParsError::code]
 #[derive(Error, Debug)]
 pub enum ParsError {
-    /// Ошибка ввода-вывода с текстовым описанием
-    #[error("Ошибка ввода-вывода: {0}")]
-    IoError(String),
-    /// Ошибка, указывающая на неверный формат данных
-    #[error("Ошибка формата: {0}")]
+    /// Ошибка ввода-вывода. Исходная ошибка доступна через [std::error::Error::source]
+    IoError(#[source] io::Error),
+    /// Прочие нарушения формата данных, не описываемые отдельными вариантами
     WrongFormat(String),
+    /// В записи отсутствует обязательное поле
+    MissingField {
+        /// Имя поля
+        name: String,
+    },
+    /// Недопустимое значение перечисления (TX_TYPE, STATUS)
+    InvalidEnumValue {
+        /// Имя поля
+        field: String,
+        /// Прочитанное значение
+        value: String,
+    },
+    /// Значение числового поля не является числом нужного типа
+    InvalidNumber {
+        /// Имя поля
+        field: String,
+        /// Прочитанное значение
+        value: String,
+    },
+    /// Метка времени вне допустимого диапазона
+    InvalidTimestamp {
+        /// Прочитанное значение
+        value: u64,
+    },
+    /// Описание не заключено в кавычки
+    InvalidDescription {
+        /// Прочитанное значение
+        value: String,
+    },
+    /// Описание длиннее допустимого
+    DescriptionTooLong {
+        /// Длина описания в байтах
+        len: usize,
+        /// Допустимая длина в байтах
+        max: usize,
+    },
+    /// Строка не является корректной UTF-8 последовательностью
+    InvalidUtf8(#[source] std::str::Utf8Error),
+    /// Неверный заголовок CSV
+    BadHeader {
+        /// Прочитанный заголовок
+        found: Vec<String>,
+    },
+    /// Неверная схема полей в заголовке файла bin версии 2
+    BadSchema {
+        /// Причина
+        reason: String,
+    },
+    /// Неверная сигнатура бинарной записи
+    BadMagic {
+        /// Прочитанная сигнатура
+        found: u32,
+    },
+    /// Количество полей записи не соответствует ожидаемому
+    FieldCountMismatch {
+        /// Ожидаемое количество полей
+        expected: usize,
+        /// Прочитанное количество полей
+        found: usize,
+    },
+    /// Поток закончился посреди записи
+    TruncatedRecord,
+    /// Контрольная сумма данных не совпадает с записанной
+    ChecksumMismatch {
+        /// Записанная контрольная сумма
+        expected: u32,
+        /// Вычисленная контрольная сумма
+        found: u32,
+    },
+    /// Количество пропущенных ошибочных записей превысило предел
+    TooManyErrors {
+        /// Допустимое количество ошибок
+        limit: usize,
+    },
+    /// Повторяющийся идентификатор транзакции
+    DuplicateTxId {
+        /// Идентификатор транзакции
+        tx_id: u64,
+    },
+    /// Изменяемой или удаляемой транзакции нет в потоке
+    MissingTxId {
+        /// Идентификатор транзакции
+        tx_id: u64,
+    },
+    /// Формат с указанным именем не поддерживается
+    UnknownFormat {
+        /// Имя формата
+        name: String,
+    },
+    /// Операция отменена пользователем
+    Cancelled {
+        /// Количество транзакций, обработанных до отмены
+        records: u64,
+    },
+    /// Результат арифметики над суммами вне диапазона i64
+    AmountOverflow {
+        /// Точное значение результата
+        value: i128,
+    },
     /// Конец потока
-    #[error("Конец потока")]
     EndOfStream,
+    /// Ошибка с указанием места в потоке, где она обнаружена
+    WithPosition {
+        /// Место в потоке
+        position: ErrorPosition,
+        /// Исходная ошибка, она же [std::error::Error::source]
+        #[source]
+        error: Box<ParsError>,
+        /// Фрагмент исходного текста записи (только для текстовых форматов)
+        snippet: Option<String>,
+    },
+}
+
+impl ParsError {
+    /// Стабильный код вида ошибки, не зависящий от языка сообщений.
+    /// Для ошибки с местом в потоке возвращается код исходной ошибки
+    pub fn code(&self) -> &'static str {
+        match self.inner() {
+            Self::IoError(_) => "io_error",
+            Self::WrongFormat(_) => "wrong_format",
+            Self::MissingField { .. } => "missing_field",
+            Self::InvalidEnumValue { .. } => "invalid_enum_value",
+            Self::InvalidNumber { .. } => "invalid_number",
+            Self::InvalidTimestamp { .. } => "invalid_timestamp",
+            Self::InvalidDescription { .. } => "invalid_description",
+            Self::DescriptionTooLong { .. } => "description_too_long",
+            Self::InvalidUtf8(_) => "invalid_utf8",
+            Self::BadHeader { .. } => "bad_header",
+            Self::BadSchema { .. } => "bad_schema",
+            Self::BadMagic { .. } => "bad_magic",
+            Self::FieldCountMismatch { .. } => "field_count_mismatch",
+            Self::TruncatedRecord => "truncated_record",
+            Self::ChecksumMismatch { .. } => "checksum_mismatch",
+            Self::TooManyErrors { .. } => "too_many_errors",
+            Self::DuplicateTxId { .. } => "duplicate_tx_id",
+            Self::MissingTxId { .. } => "missing_tx_id",
+            Self::UnknownFormat { .. } => "unknown_format",
+            Self::Cancelled { .. } => "cancelled",
+            Self::AmountOverflow { .. } => "amount_overflow",
+            Self::EndOfStream => "end_of_stream",
+            Self::WithPosition { error, .. } => error.code(),
+        }
+    }
+
+    fn fmt_ru(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::IoError(e) => write!(f, "Ошибка ввода-вывода: {e}"),
+            Self::WrongFormat(msg) => write!(f, "Ошибка формата: {msg}"),
+            Self::MissingField { name } => write!(f, "Отсутствует поле {name}"),
+            Self::InvalidEnumValue { field, value } => {
+                write!(f, "Недопустимое значение поля {field}: {value}")
+            }
+            Self::InvalidNumber { field, value } => {
+                write!(f, "Неверное числовое значение поля {field}: {value}")
+            }
+            Self::InvalidTimestamp { value } => write!(f, "Неверный формат времени: {value}"),
+            Self::InvalidDescription { value } => {
+                write!(f, "Неверный формат описания: {value}")
+            }
+            Self::DescriptionTooLong { len, max } => {
+                write!(f, "Слишком длинное описание: {len} байт (допустимо {max})")
+            }
+            Self::InvalidUtf8(e) => write!(f, "Неверная UTF-8 строка: {e}"),
+            Self::BadHeader { found } => write!(f, "Неверный заголовок: {found:?}"),
+            Self::BadSchema { reason } => write!(f, "Неверная схема полей bin: {reason}"),
+            Self::BadMagic { found } => write!(f, "Неверный magic: {found:#x}"),
+            Self::FieldCountMismatch { expected, found } => write!(
+                f,
+                "Неверное количество полей: ожидалось {expected}, прочитано {found}"
+            ),
+            Self::TruncatedRecord => write!(f, "Неполная запись"),
+            Self::ChecksumMismatch { expected, found } => write!(
+                f,
+                "Неверная контрольная сумма: ожидалось {expected:#x}, вычислено {found:#x}"
+            ),
+            Self::TooManyErrors { limit } => {
+                write!(f, "Превышено допустимое количество ошибок: {limit}")
+            }
+            Self::DuplicateTxId { tx_id } => {
+                write!(f, "Повторяющийся идентификатор транзакции: {tx_id}")
+            }
+            Self::MissingTxId { tx_id } => write!(f, "Транзакции нет в потоке: {tx_id}"),
+            Self::UnknownFormat { name } => write!(f, "Неподдерживаемый формат: {name}"),
+            Self::Cancelled { records } => {
+                write!(f, "Операция отменена после {records} транзакций")
+            }
+            Self::AmountOverflow { value } => write!(f, "Переполнение суммы: {value}"),
+            Self::EndOfStream => write!(f, "Конец потока"),
+            Self::WithPosition {
+                position,
+                error,
+                snippet,
+            } => {
+                write!(f, "{} (", error.localized(Language::Ru))?;
+                position.fmt_lang(f, Language::Ru)?;
+                write!(f, ")")?;
+                if let Some(snippet) = snippet {
+                    write!(f, ": {snippet:?}")?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    fn fmt_en(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::IoError(e) => write!(f, "I/O error: {e}"),
+            Self::WrongFormat(msg) => write!(f, "Format error: {msg}"),
+            Self::MissingField { name } => write!(f, "Missing field {name}"),
+            Self::InvalidEnumValue { field, value } => {
+                write!(f, "Invalid value of field {field}: {value}")
+            }
+            Self::InvalidNumber { field, value } => {
+                write!(f, "Invalid numeric value of field {field}: {value}")
+            }
+            Self::InvalidTimestamp { value } => write!(f, "Invalid timestamp: {value}"),
+            Self::InvalidDescription { value } => write!(f, "Invalid description: {value}"),
+            Self::DescriptionTooLong { len, max } => {
+                write!(f, "Description too long: {len} bytes (max {max})")
+            }
+            Self::InvalidUtf8(e) => write!(f, "Invalid UTF-8 string: {e}"),
+            Self::BadHeader { found } => write!(f, "Invalid header: {found:?}"),
+            Self::BadSchema { reason } => write!(f, "Invalid bin field schema: {reason}"),
+            Self::BadMagic { found } => write!(f, "Invalid magic: {found:#x}"),
+            Self::FieldCountMismatch { expected, found } => write!(
+                f,
+                "Wrong number of fields: expected {expected}, found {found}"
+            ),
+            Self::TruncatedRecord => write!(f, "Truncated record"),
+            Self::ChecksumMismatch { expected, found } => write!(
+                f,
+                "Checksum mismatch: expected {expected:#x}, computed {found:#x}"
+            ),
+            Self::TooManyErrors { limit } => write!(f, "Too many errors: limit is {limit}"),
+            Self::DuplicateTxId { tx_id } => write!(f, "Duplicate transaction id: {tx_id}"),
+            Self::MissingTxId { tx_id } => write!(f, "Transaction not found: {tx_id}"),
+            Self::UnknownFormat { name } => write!(f, "Unsupported format: {name}"),
+            Self::Cancelled { records } => {
+                write!(f, "Operation cancelled after {records} transactions")
+            }
+            Self::AmountOverflow { value } => write!(f, "Amount overflow: {value}"),
+            Self::EndOfStream => write!(f, "End of stream"),
+            Self::WithPosition {
+                position,
+                error,
+                snippet,
+            } => {
+                write!(f, "{} (", error.localized(Language::En))?;
+                position.fmt_lang(f, Language::En)?;
+                write!(f, ")")?;
+                if let Some(snippet) = snippet {
+                    write!(f, ": {snippet:?}")?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Текст ошибки на заданном языке независимо от выбранного для процесса
+    pub fn localized(&self, lang: Language) -> Localized<'_> {
+        Localized { error: self, lang }
+    }
+
+    /// Добавление к ошибке места в потоке. Конец потока и ошибки,
+    /// уже содержащие место, не изменяются
+    pub fn at(self, position: ErrorPosition) -> Self {
+        self.at_record(position, None)
+    }
+
+    /// Добавление к ошибке места в потоке и фрагмента исходного текста записи
+    pub fn at_record(self, position: ErrorPosition, snippet: Option<String>) -> Self {
+        match self {
+            Self::EndOfStream | Self::WithPosition { .. } => self,
+            _ => Self::WithPosition {
+                position,
+                error: Box::new(self),
+                snippet,
+            },
+        }
+    }
+
+    /// Фрагмент исходного текста записи, в которой обнаружена ошибка, если он известен
+    pub fn snippet(&self) -> Option<&str> {
+        match self {
+            Self::WithPosition { snippet, .. } => snippet.as_deref(),
+            _ => None,
+        }
+    }
+
+    /// Имя поля записи, к которому относится ошибка, если оно известно
+    pub fn field(&self) -> Option<&str> {
+        match self.inner() {
+            Self::MissingField { name } => Some(name),
+            Self::InvalidEnumValue { field, .. } | Self::InvalidNumber { field, .. } => Some(field),
+            Self::InvalidTimestamp { .. } => Some(TIMESTAMP),
+            Self::AmountOverflow { .. } => Some(AMOUNT),
+            Self::InvalidDescription { .. } | Self::DescriptionTooLong { .. } => Some(DESCRIPTION),
+            _ => None,
+        }
+    }
+
+    /// Место в потоке, где обнаружена ошибка, если оно известно
+    pub fn position(&self) -> Option<&ErrorPosition> {
+        match self {
+            Self::WithPosition { position, .. } => Some(position),
+            _ => None,
+        }
+    }
+
+    /// Ошибка относится к данным одной записи, и после нее чтение может быть
+    /// продолжено со следующей записи. Ошибки ввода-вывода, заголовка и выбора
+    /// формата не позволяют продолжить чтение
+    pub fn is_recoverable(&self) -> bool {
+        !matches!(
+            self.inner(),
+            Self::IoError(_)
+                | Self::EndOfStream
+                | Self::BadHeader { .. }
+                | Self::BadSchema { .. }
+                | Self::UnknownFormat { .. }
+                | Self::Cancelled { .. }
+        )
+    }
+
+    /// Вид ошибки ввода-вывода, например для повтора при [io::ErrorKind::Interrupted]
+    /// или обработки [io::ErrorKind::BrokenPipe]
+    pub fn io_error_kind(&self) -> Option<io::ErrorKind> {
+        match self.inner() {
+            Self::IoError(e) => Some(e.kind()),
+            _ => None,
+        }
+    }
+
+    /// Исходная ошибка без указания места в потоке
+    pub fn inner(&self) -> &ParsError {
+        match self {
+            Self::WithPosition { error, .. } => error.inner(),
+            _ => self,
+        }
+    }
+}
+
+/// Ошибка, выводимая на заданном языке, см. [ParsError::localized]
+pub struct Localized<'a> {
+    error: &'a ParsError,
+    lang: Language,
+}
+
+impl fmt::Display for Localized<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.lang {
+            Language::Ru => self.error.fmt_ru(f),
+            Language::En => self.error.fmt_en(f),
+        }
+    }
+}
+
+/// Текст сообщения на текущем языке. Текст [ParsError::WrongFormat] не переводится
+impl fmt::Display for ParsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.localized(language()).fmt(f)
+    }
 }
 
 /// Ошибка ввода-вывода io::Error преобразуется по следующим правилам:
@@ -23,7 +479,7 @@ impl From<io::Error> for ParsError {
     fn from(e: io::Error) -> Self {
         match e.kind() {
             io::ErrorKind::UnexpectedEof => ParsError::EndOfStream,
-            _ => Self::IoError(format!("{e}")),
+            _ => Self::IoError(e),
         }
     }
 }
@@ -31,7 +487,7 @@ impl From<io::Error> for ParsError {
 /// Ошибка, возникающая при парсинге UTF8-строки
 impl From<std::str::Utf8Error> for ParsError {
     fn from(e: std::str::Utf8Error) -> Self {
-        Self::WrongFormat(format!("{e}"))
+        Self::InvalidUtf8(e)
     }
 }
 
@@ -41,3 +497,69 @@ impl From<std::num::ParseIntError> for ParsError {
         Self::WrongFormat(format!("{e}"))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::error::Error;
+
+    /// Первая ошибка типа T в цепочке source, начиная с источника err
+    fn find_source<T: Error + 'static>(err: &dyn Error) -> Option<&T> {
+        let mut source = err.source();
+        while let Some(val) = source {
+            if let Some(res) = val.downcast_ref::<T>() {
+                return Some(res);
+            }
+            source = val.source();
+        }
+        None
+    }
+
+    #[test]
+    fn test_io_error_source() {
+        let err = ParsError::from(io::Error::from(io::ErrorKind::BrokenPipe)).at(ErrorPosition {
+            record: 2,
+            ..Default::default()
+        });
+        assert_eq!(err.io_error_kind(), Some(io::ErrorKind::BrokenPipe));
+
+        let io_err = find_source::<io::Error>(&err).unwrap();
+        assert_eq!(io_err.kind(), io::ErrorKind::BrokenPipe);
+
+        let bytes = vec![0xff];
+        let utf8 = std::str::from_utf8(&bytes).unwrap_err();
+        let err = ParsError::InvalidUtf8(utf8).at(ErrorPosition::default());
+        assert!(find_source::<std::str::Utf8Error>(&err).is_some());
+    }
+
+    #[test]
+    fn test_code_and_language() {
+        let err = ParsError::MissingField {
+            name: "STATUS".to_owned(),
+        }
+        .at(ErrorPosition {
+            record: 1,
+            byte: 10,
+            line: Some(3),
+            column: None,
+        });
+        assert_eq!(err.code(), "missing_field");
+        assert_eq!(err.field(), Some("STATUS"));
+        assert_eq!(
+            err.localized(Language::En).to_string(),
+            "Missing field STATUS (record 1, byte 10, line 3)"
+        );
+        assert_eq!(
+            err.localized(Language::Ru).to_string(),
+            "Отсутствует поле STATUS (запись 1, байт 10, строка 3)"
+        );
+        assert_eq!("EN".parse::<Language>().unwrap(), Language::En);
+    }
+
+    #[test]
+    fn test_unexpected_eof() {
+        let err = ParsError::from(io::Error::from(io::ErrorKind::UnexpectedEof));
+        assert!(matches!(err, ParsError::EndOfStream));
+        assert_eq!(err.io_error_kind(), None);
+    }
+}
@@ -0,0 +1,322 @@
+use super::error::ParsError;
+use super::format::TransactionRead;
+use super::query;
+use super::transaction::{Transaction, TransactionRef, TxStatus, TxType};
+use super::tx_format::TxReader;
+use chrono::{DateTime, Utc};
+use regex::Regex;
+use std::collections::HashSet;
+use std::io::Read;
+use std::ops::{Bound, RangeBounds};
+use std::str::FromStr;
+
+/// Условие отбора транзакций. Условия объединяются через [TxFilter::and],
+/// [TxFilter::or] и [TxFilter::not]
+///
+/// ```
+/// use fin_parser::filter::TxFilter;
+/// use fin_parser::transaction::{TxStatus, TxType};
+///
+/// let filter = TxFilter::status(TxStatus::Success)
+///     .and(TxFilter::tx_type(TxType::Deposit).or(TxFilter::amount(1000..)))
+///     .and(TxFilter::description("^Record").unwrap().not());
+/// ```
+#[derive(Clone, Debug, Default)]
+pub enum TxFilter {
+    /// Любая транзакция
+    #[default]
+    Any,
+    /// Статус транзакции
+    Status(TxStatus),
+    /// Тип транзакции
+    Type(TxType),
+    /// Сумма в диапазоне
+    Amount(Bound<i64>, Bound<i64>),
+    /// Время в диапазоне
+    Time(Bound<DateTime<Utc>>, Bound<DateTime<Utc>>),
+    /// Инициатор из набора
+    FromUser(HashSet<u64>),
+    /// Получатель из набора
+    ToUser(HashSet<u64>),
+    /// Описание, содержащее совпадение с регулярным выражением
+    Description(Regex),
+    /// Выполнены все условия
+    And(Vec<TxFilter>),
+    /// Выполнено хотя бы одно условие
+    Or(Vec<TxFilter>),
+    /// Условие не выполнено
+    Not(Box<TxFilter>),
+}
+
+impl TxFilter {
+    /// Отбор по статусу
+    pub fn status(status: TxStatus) -> Self {
+        Self::Status(status)
+    }
+
+    /// Отбор по типу
+    pub fn tx_type(tx_type: TxType) -> Self {
+        Self::Type(tx_type)
+    }
+
+    /// Отбор по диапазону суммы, например `100..=200` или `..0`
+    pub fn amount<R: RangeBounds<i64>>(range: R) -> Self {
+        Self::Amount(range.start_bound().cloned(), range.end_bound().cloned())
+    }
+
+    /// Отбор по диапазону времени транзакции
+    pub fn time<R: RangeBounds<DateTime<Utc>>>(range: R) -> Self {
+        Self::Time(range.start_bound().cloned(), range.end_bound().cloned())
+    }
+
+    /// Отбор по инициатору транзакции
+    pub fn from_users<I: IntoIterator<Item = u64>>(ids: I) -> Self {
+        Self::FromUser(ids.into_iter().collect())
+    }
+
+    /// Отбор по получателю транзакции
+    pub fn to_users<I: IntoIterator<Item = u64>>(ids: I) -> Self {
+        Self::ToUser(ids.into_iter().collect())
+    }
+
+    /// Отбор транзакций, в которых пользователь из набора является инициатором или получателем
+    pub fn users<I: IntoIterator<Item = u64>>(ids: I) -> Self {
+        let ids: HashSet<u64> = ids.into_iter().collect();
+        Self::FromUser(ids.clone()).or(Self::ToUser(ids))
+    }
+
+    /// Отбор по регулярному выражению, которое ищется в описании
+    pub fn description(pattern: &str) -> Result<Self, ParsError> {
+        let regex = Regex::new(pattern).map_err(|e| {
+            ParsError::WrongFormat(format!("Некорректное регулярное выражение: {e}"))
+        })?;
+        Ok(Self::Description(regex))
+    }
+
+    /// Объединение условий: выполнены оба
+    pub fn and(self, other: TxFilter) -> Self {
+        match self {
+            Self::Any => other,
+            Self::And(mut filters) => {
+                filters.push(other);
+                Self::And(filters)
+            }
+            filter => Self::And(vec![filter, other]),
+        }
+    }
+
+    /// Объединение условий: выполнено хотя бы одно
+    pub fn or(self, other: TxFilter) -> Self {
+        match self {
+            Self::Or(mut filters) => {
+                filters.push(other);
+                Self::Or(filters)
+            }
+            filter => Self::Or(vec![filter, other]),
+        }
+    }
+
+    /// Отрицание условия
+    #[allow(clippy::should_implement_trait)]
+    pub fn not(self) -> Self {
+        match self {
+            Self::Not(filter) => *filter,
+            filter => Self::Not(Box::new(filter)),
+        }
+    }
+
+    /// Проверка транзакции
+    pub fn matches(&self, tx: &TransactionRef<'_>) -> bool {
+        match self {
+            Self::Any => true,
+            Self::Status(status) => tx.status == *status,
+            Self::Type(tx_type) => tx.tx_type == *tx_type,
+            Self::Amount(start, end) => (*start, *end).contains(&tx.amount),
+            Self::Time(start, end) => (*start, *end).contains(&tx.timestamp),
+            Self::FromUser(ids) => ids.contains(&tx.from_user_id),
+            Self::ToUser(ids) => ids.contains(&tx.to_user_id),
+            Self::Description(regex) => regex.is_match(tx.description),
+            Self::And(filters) => filters.iter().all(|filter| filter.matches(tx)),
+            Self::Or(filters) => filters.iter().any(|filter| filter.matches(tx)),
+            Self::Not(filter) => !filter.matches(tx),
+        }
+    }
+
+    /// Проверка транзакции, владеющей описанием
+    pub fn matches_tx(&self, tx: &Transaction) -> bool {
+        self.matches(&TransactionRef::from(tx))
+    }
+}
+
+/// Разбор условия из выражения вида `status = PENDING AND amount > 1000 AND timestamp >= 2021-10-01`.
+/// Поддерживаются поля status, tx_type, amount, timestamp, from_user_id, to_user_id,
+/// user_id (инициатор или получатель) и description, операторы `= != < <= > >=`,
+/// `~` (регулярное выражение в описании), связки AND, OR, NOT и скобки.
+/// Время задается датой, в формате RFC 3339 или в миллисекундах. Пустое выражение
+/// пропускает все транзакции
+impl FromStr for TxFilter {
+    type Err = ParsError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        query::parse(s)
+    }
+}
+
+/// Читатель, возвращающий только транзакции, удовлетворяющие условию.
+/// Отброшенные записи проверяются без выделения памяти под описание
+pub struct FilteredReader<In: Read> {
+    reader: TxReader<In>,
+    filter: TxFilter,
+}
+
+impl<In: Read> FilteredReader<In> {
+    /// Создание читателя поверх [TxReader]
+    pub fn new(reader: TxReader<In>, filter: TxFilter) -> Self {
+        Self { reader, filter }
+    }
+
+    /// Условие отбора
+    pub fn filter(&self) -> &TxFilter {
+        &self.filter
+    }
+
+    /// Чтение следующей подходящей транзакции. None означает конец потока
+    pub fn read_transaction(&mut self) -> Result<Option<Transaction>, ParsError> {
+        while let Some(tx) = self.reader.read_transaction_ref()? {
+            if self.filter.matches(&tx) {
+                return Ok(Some(tx.to_transaction()));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Подсчет оставшихся в потоке подходящих записей без построения транзакций
+    pub fn count(&mut self) -> Result<u64, ParsError> {
+        let mut cnt = 0;
+        while let Some(tx) = self.reader.read_transaction_ref()? {
+            if self.filter.matches(&tx) {
+                cnt += 1;
+            }
+        }
+        Ok(cnt)
+    }
+
+    /// Метод чтения всех оставшихся в потоке подходящих транзакций
+    pub fn read_all(&mut self) -> Result<Vec<Transaction>, ParsError> {
+        let mut res = Vec::new();
+        while let Some(tx) = self.read_transaction()? {
+            res.push(tx);
+        }
+        Ok(res)
+    }
+
+    /// Исходный читатель
+    pub fn get_ref(&self) -> &TxReader<In> {
+        &self.reader
+    }
+
+    /// Исходный читатель
+    pub fn get_mut(&mut self) -> &mut TxReader<In> {
+        &mut self.reader
+    }
+
+    /// Возврат исходного читателя
+    pub fn into_inner(self) -> TxReader<In> {
+        self.reader
+    }
+}
+
+impl<In: Read> TransactionRead for FilteredReader<In> {
+    fn read_transaction(&mut self) -> Result<Option<Transaction>, ParsError> {
+        FilteredReader::read_transaction(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::Format;
+    use crate::test_util::{encode, txs};
+    use chrono::TimeDelta;
+    use std::io::Cursor;
+
+    fn txs_for_test() -> Vec<Transaction> {
+        txs(0..10)
+            .into_iter()
+            .map(|tx| Transaction {
+                tx_type: if tx.tx_id % 2 == 0 {
+                    TxType::Deposit
+                } else {
+                    TxType::Transfer
+                },
+                from_user_id: tx.tx_id % 3,
+                to_user_id: 100 + tx.tx_id,
+                amount: tx.tx_id as i64 * 100,
+                timestamp: tx.timestamp + TimeDelta::seconds(tx.tx_id as i64),
+                status: if tx.tx_id < 5 {
+                    TxStatus::Success
+                } else {
+                    TxStatus::Failure
+                },
+                ..tx
+            })
+            .collect()
+    }
+
+    fn select(filter: &TxFilter) -> Vec<u64> {
+        txs_for_test()
+            .iter()
+            .filter(|tx| filter.matches_tx(tx))
+            .map(|tx| tx.tx_id)
+            .collect()
+    }
+
+    #[test]
+    fn test_filters() {
+        assert_eq!(select(&TxFilter::default()).len(), 10);
+        assert_eq!(
+            select(&TxFilter::status(TxStatus::Failure)),
+            [5, 6, 7, 8, 9]
+        );
+        assert_eq!(
+            select(&TxFilter::tx_type(TxType::Transfer)),
+            [1, 3, 5, 7, 9]
+        );
+        assert_eq!(select(&TxFilter::amount(200..=400)), [2, 3, 4]);
+        assert_eq!(select(&TxFilter::amount(..100)), [0]);
+        let start = DateTime::from_timestamp(1633036868, 0).unwrap();
+        assert_eq!(select(&TxFilter::time(start..)), [8, 9]);
+        assert_eq!(select(&TxFilter::from_users([2])), [2, 5, 8]);
+        assert_eq!(select(&TxFilter::to_users([101, 109])), [1, 9]);
+        assert_eq!(select(&TxFilter::users([2, 101])), [1, 2, 5, 8]);
+        assert_eq!(select(&TxFilter::description("[13]$").unwrap()), [1, 3]);
+        assert!(TxFilter::description("(").is_err());
+    }
+
+    #[test]
+    fn test_composition() {
+        let filter = TxFilter::status(TxStatus::Success)
+            .and(TxFilter::tx_type(TxType::Deposit).or(TxFilter::amount(300..)));
+        assert_eq!(select(&filter), [0, 2, 3, 4]);
+        assert_eq!(select(&filter.clone().not()), [1, 5, 6, 7, 8, 9]);
+        assert_eq!(select(&filter.not().not()), [0, 2, 3, 4]);
+    }
+
+    #[test]
+    fn test_filtered_reader() {
+        for fin_format in Format::ALL {
+            let buf = encode(&txs_for_test(), fin_format);
+
+            let filter = TxFilter::tx_type(TxType::Transfer).and(TxFilter::amount(..=500));
+            let reader = TxReader::new(Cursor::new(buf.clone()), fin_format).unwrap();
+            let mut reader = FilteredReader::new(reader, filter.clone());
+            let txs = reader.read_all().unwrap();
+            let ids: Vec<u64> = txs.iter().map(|tx| tx.tx_id).collect();
+            assert_eq!(ids, [1, 3, 5]);
+            assert_eq!(reader.get_ref().position().records, 10);
+
+            let reader = TxReader::new(Cursor::new(buf), fin_format).unwrap();
+            assert_eq!(FilteredReader::new(reader, filter).count().unwrap(), 3);
+        }
+    }
+}
@@ -0,0 +1,224 @@
+use super::error::ParsError;
+use super::format::TransactionRead;
+use super::transaction::{TransactionRef, TxStatus, TxType};
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::str::FromStr;
+
+/// Длина отпечатка в байтах
+pub const FINGERPRINT_LEN: usize = 32;
+
+/// Отпечаток содержимого: SHA-256 канонического представления транзакции или потока.
+/// Не зависит от формата и настроек записи, поэтому подходит для обнаружения изменений
+/// и как ключ идемпотентной загрузки. Выводится и разбирается шестнадцатеричной строкой
+#[derive(Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+pub struct Fingerprint(pub [u8; FINGERPRINT_LEN]);
+
+impl Fingerprint {
+    /// Байты отпечатка
+    pub fn as_bytes(&self) -> &[u8; FINGERPRINT_LEN] {
+        &self.0
+    }
+}
+
+impl fmt::Display for Fingerprint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for byte in self.0 {
+            write!(f, "{byte:02x}")?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Fingerprint {
+    type Err = ParsError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let wrong = || ParsError::WrongFormat(format!("Неверный отпечаток: {s}"));
+        if s.len() != FINGERPRINT_LEN * 2 || !s.is_ascii() {
+            return Err(wrong());
+        }
+        let mut res = [0u8; FINGERPRINT_LEN];
+        for (idx, byte) in res.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&s[idx * 2..idx * 2 + 2], 16).map_err(|_| wrong())?;
+        }
+        Ok(Self(res))
+    }
+}
+
+/// Отпечаток транзакции. Каноническое представление совпадает с телом записи bin
+/// версии 1: поля в порядке спецификации в big-endian, время в миллисекундах,
+/// описание без кавычек с длиной u32
+pub(crate) fn transaction_fingerprint(tx: &TransactionRef<'_>) -> Fingerprint {
+    let tx_type: u8 = match tx.tx_type {
+        TxType::Deposit => 0,
+        TxType::Transfer => 1,
+        TxType::Withdrawal => 2,
+    };
+    let status: u8 = match tx.status {
+        TxStatus::Success => 0,
+        TxStatus::Failure => 1,
+        TxStatus::Pending => 2,
+    };
+    let mut hasher = Sha256::new();
+    hasher.update(tx.tx_id.to_be_bytes());
+    hasher.update([tx_type]);
+    hasher.update(tx.from_user_id.to_be_bytes());
+    hasher.update(tx.to_user_id.to_be_bytes());
+    hasher.update(tx.amount.to_be_bytes());
+    hasher.update(tx.timestamp.timestamp_millis().to_be_bytes());
+    hasher.update([status]);
+    hasher.update((tx.description.len() as u32).to_be_bytes());
+    hasher.update(tx.description.as_bytes());
+    Fingerprint(hasher.finalize().into())
+}
+
+/// Потоковый отпечаток файла: SHA-256 последовательности отпечатков транзакций
+/// и их количества. Совпадает у файлов с одними и теми же транзакциями в одном порядке
+/// независимо от формата. Для сравнения выгрузок с разным порядком записей поток
+/// предварительно приводится к канонической форме ([crate::sort::canonicalize])
+///
+/// ```
+/// use fin_parser::fingerprint::digest_stream;
+/// use fin_parser::format::Format;
+/// use fin_parser::tx_format::TxReader;
+/// use std::io::Cursor;
+///
+/// let csv = "TX_ID,TX_TYPE,FROM_USER_ID,TO_USER_ID,AMOUNT,TIMESTAMP,STATUS,DESCRIPTION\n\
+///     1,DEPOSIT,0,5,100,1633036860000,SUCCESS,\"Record number 1\"\n";
+/// let text = "TX_ID: 1\nTX_TYPE: DEPOSIT\nFROM_USER_ID: 0\nTO_USER_ID: 5\nAMOUNT: 100\n\
+///     TIMESTAMP: 1633036860000\nSTATUS: SUCCESS\nDESCRIPTION: \"Record number 1\"\n";
+///
+/// let csv_digest = digest_stream(&mut TxReader::new(Cursor::new(csv), Format::Csv).unwrap());
+/// let text_digest = digest_stream(&mut TxReader::new(Cursor::new(text), Format::Text).unwrap());
+/// assert_eq!(csv_digest.unwrap(), text_digest.unwrap());
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct FileDigest {
+    hasher: Sha256,
+    records: u64,
+}
+
+impl FileDigest {
+    /// Отпечаток пустого потока
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Учет следующей транзакции потока
+    pub fn update<'a>(&mut self, tx: impl Into<TransactionRef<'a>>) {
+        self.update_fingerprint(&transaction_fingerprint(&tx.into()));
+    }
+
+    /// Учет следующей транзакции по ее отпечатку
+    pub fn update_fingerprint(&mut self, fingerprint: &Fingerprint) {
+        self.hasher.update(fingerprint.as_bytes());
+        self.records += 1;
+    }
+
+    /// Количество учтенных транзакций
+    pub fn records(&self) -> u64 {
+        self.records
+    }
+
+    /// Отпечаток потока
+    pub fn finish(self) -> Fingerprint {
+        let mut hasher = self.hasher;
+        hasher.update(self.records.to_be_bytes());
+        Fingerprint(hasher.finalize().into())
+    }
+}
+
+/// Отпечаток всех оставшихся в потоке транзакций, см. [FileDigest]
+pub fn digest_stream<R: TransactionRead + ?Sized>(
+    reader: &mut R,
+) -> Result<Fingerprint, ParsError> {
+    let mut digest = FileDigest::new();
+    while let Some(tx) = reader.read_transaction()? {
+        digest.update(&tx);
+    }
+    Ok(digest.finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::Format;
+    use crate::generate::{GenerateOptions, Generator};
+    use crate::sort::canonicalize;
+    use crate::transaction::Transaction;
+    use crate::tx_format::{TxReader, TxWriter};
+    use chrono::DateTime;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_transaction_fingerprint() {
+        let tx = Transaction {
+            tx_id: 1000000000000000,
+            tx_type: TxType::Deposit,
+            from_user_id: 0,
+            to_user_id: 9223372036854775807,
+            amount: 100,
+            timestamp: DateTime::from_timestamp_millis(1633036860000).unwrap(),
+            status: TxStatus::Failure,
+            description: "Record number 1".to_owned(),
+        };
+        // Значение зафиксировано: отпечатки сохраняются между версиями библиотеки
+        let fingerprint = tx.fingerprint();
+        assert_eq!(
+            fingerprint.to_string(),
+            "90bcb736bd37baec005cf66d5a266d03b2ed15f7f836fa594a1b74305bf33250"
+        );
+        assert_eq!(
+            fingerprint.to_string().parse::<Fingerprint>().unwrap(),
+            fingerprint
+        );
+        assert_eq!(TransactionRef::from(&tx).fingerprint(), fingerprint);
+
+        for fin_format in Format::ALL {
+            let data = tx.to_bytes_format(fin_format).unwrap();
+            let read = Transaction::from_bytes_format(&data, fin_format).unwrap();
+            assert_eq!(read.fingerprint(), fingerprint, "{fin_format}");
+        }
+        let changed = Transaction {
+            amount: 101,
+            ..Transaction::from(TransactionRef::from(&tx))
+        };
+        assert_ne!(changed.fingerprint(), fingerprint);
+        assert!("xyz".parse::<Fingerprint>().is_err());
+    }
+
+    #[test]
+    fn test_digest_stream() {
+        let txs: Vec<_> = Generator::new(GenerateOptions::new().with_seed(8), 50)
+            .unwrap()
+            .collect();
+        let digest = |fin_format, txs: &[Transaction]| {
+            let mut writer = TxWriter::new(Vec::new(), fin_format).unwrap();
+            writer.write_all(txs).unwrap();
+            let data = writer.into_inner().unwrap();
+            digest_stream(&mut TxReader::new(Cursor::new(data), fin_format).unwrap()).unwrap()
+        };
+        let expected = digest(Format::Csv, &txs);
+        assert_eq!(digest(Format::Text, &txs), expected);
+        assert_eq!(digest(Format::Bin, &txs), expected);
+        assert_ne!(digest(Format::Bin, &txs[1..]), expected);
+        assert_eq!(digest(Format::Bin, &[]), FileDigest::new().finish());
+
+        // После канонической формы порядок записей не влияет на отпечаток
+        let mut reversed = txs.clone();
+        reversed.reverse();
+        let canonical = |txs: &[Transaction]| {
+            let mut writer = TxWriter::new(Vec::new(), Format::Bin).unwrap();
+            writer.write_all(txs).unwrap();
+            let data = writer.into_inner().unwrap();
+            let mut reader = TxReader::new(Cursor::new(data), Format::Bin).unwrap();
+            let mut writer = TxWriter::new(Vec::new(), Format::Bin).unwrap();
+            canonicalize(&mut reader, &mut writer).unwrap();
+            let data = writer.into_inner().unwrap();
+            digest_stream(&mut TxReader::new(Cursor::new(data), Format::Bin).unwrap()).unwrap()
+        };
+        assert_ne!(digest(Format::Bin, &reversed), expected);
+        assert_eq!(canonical(&reversed), canonical(&txs));
+    }
+}
@@ -0,0 +1,172 @@
+use super::error::ParsError;
+use super::format::Format;
+use super::options::ReaderOptions;
+use super::tx_format::TxReader;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::Duration;
+
+/// Интервал опроса источника после конца данных по умолчанию
+pub const DEFAULT_POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Обертка над потоком Read для чтения дописываемых файлов (аналог `tail -f`).
+/// Встретив конец данных, обертка ждет poll_interval и повторяет чтение, поэтому
+/// читатель транзакций поверх нее не видит конца потока, а запись, дописанная
+/// производителем не полностью, дочитывается после ее завершения.
+/// Конец потока возвращается после установки флага остановки
+/// ([Follow::stop_flag]) или, если задан idle_timeout, после того как
+/// новые данные не появлялись дольше idle_timeout.
+/// Ожидание реализовано опросом, уведомления файловой системы не используются
+pub struct Follow<R: Read> {
+    inner: R,
+    poll_interval: Duration,
+    idle_timeout: Option<Duration>,
+    stop: Arc<AtomicBool>,
+}
+
+impl<R: Read> Follow<R> {
+    /// Обертка с интервалом опроса [DEFAULT_POLL_INTERVAL] без ограничения ожидания
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            poll_interval: DEFAULT_POLL_INTERVAL,
+            idle_timeout: None,
+            stop: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Интервал опроса источника после конца данных
+    pub fn with_poll_interval(mut self, poll_interval: Duration) -> Self {
+        self.poll_interval = poll_interval;
+        self
+    }
+
+    /// Время ожидания новых данных, после которого возвращается конец потока
+    pub fn with_idle_timeout(mut self, idle_timeout: Duration) -> Self {
+        self.idle_timeout = Some(idle_timeout);
+        self
+    }
+
+    /// Флаг остановки. После установки флага в true обертка возвращает конец потока,
+    /// как только данные закончатся. Флаг можно передать в другой поток
+    pub fn stop_flag(&self) -> Arc<AtomicBool> {
+        Arc::clone(&self.stop)
+    }
+
+    /// Возврат исходного потока
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl Follow<File> {
+    /// Открытие файла на чтение с ожиданием дописываемых данных
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Ok(Self::new(File::open(path)?))
+    }
+}
+
+impl<R: Read> Read for Follow<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut idle = Duration::ZERO;
+        loop {
+            let cnt = self.inner.read(buf)?;
+            if cnt > 0 || buf.is_empty() || self.stop.load(Ordering::Relaxed) {
+                return Ok(cnt);
+            }
+            if let Some(idle_timeout) = self.idle_timeout
+                && idle >= idle_timeout
+            {
+                return Ok(0);
+            }
+            thread::sleep(self.poll_interval);
+            idle += self.poll_interval;
+        }
+    }
+}
+
+/// Читатель транзакций из дописываемого файла, см. [Follow]
+pub fn follow_file<P: AsRef<Path>>(
+    path: P,
+    fin_format: Format,
+    poll_interval: Duration,
+) -> Result<(TxReader<Follow<File>>, Arc<AtomicBool>), ParsError> {
+    let follow = Follow::open(path)?.with_poll_interval(poll_interval);
+    let stop = follow.stop_flag();
+    let reader = TxReader::with_custom(follow, fin_format, ReaderOptions::default())?;
+    Ok((reader, stop))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transaction::{Transaction, TxStatus, TxType};
+    use crate::tx_format::TxWriter;
+    use chrono::DateTime;
+    use std::fs::{self, OpenOptions};
+    use std::io::Write;
+
+    fn bin_for_test(cnt: u64) -> Vec<u8> {
+        let txs: Vec<Transaction> = (0..cnt)
+            .map(|idx| Transaction {
+                tx_id: idx,
+                tx_type: TxType::Deposit,
+                from_user_id: 0,
+                to_user_id: 1,
+                amount: 100,
+                timestamp: DateTime::from_timestamp(1633036860, 0).unwrap(),
+                status: TxStatus::Success,
+                description: format!("Record number {idx}"),
+            })
+            .collect();
+        let mut writer = TxWriter::new(Vec::new(), Format::Bin).unwrap();
+        writer.write_all(&txs).unwrap();
+        writer.into_inner().unwrap()
+    }
+
+    #[test]
+    fn test_follow_file() {
+        let path =
+            std::env::temp_dir().join(format!("fin-parser-follow-{}.bin", std::process::id()));
+        let data = bin_for_test(3);
+        // Первая запись и половина второй
+        let split = data.len() / 2;
+        fs::write(&path, &data[..split]).unwrap();
+
+        let (mut reader, stop) = follow_file(&path, Format::Bin, Duration::from_millis(5)).unwrap();
+        let producer = {
+            let path = path.clone();
+            let rest = data[split..].to_vec();
+            thread::spawn(move || {
+                let mut file = OpenOptions::new().append(true).open(path).unwrap();
+                for chunk in rest.chunks(7) {
+                    thread::sleep(Duration::from_millis(2));
+                    file.write_all(chunk).unwrap();
+                }
+            })
+        };
+
+        let ids: Vec<u64> = (0..3)
+            .map(|_| reader.read_transaction().unwrap().unwrap().tx_id)
+            .collect();
+        assert_eq!(ids, [0, 1, 2]);
+        producer.join().unwrap();
+        stop.store(true, Ordering::Relaxed);
+        assert!(reader.read_transaction().unwrap().is_none());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_idle_timeout() {
+        let mut follow = Follow::new(io::Cursor::new(b"abc".to_vec()))
+            .with_poll_interval(Duration::from_millis(1))
+            .with_idle_timeout(Duration::from_millis(5));
+        let mut buf = Vec::new();
+        follow.read_to_end(&mut buf).unwrap();
+        assert_eq!(buf, b"abc");
+    }
+}
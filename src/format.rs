@@ -0,0 +1,461 @@
+use super::compression::Compression;
+use super::constants::{HEADER_VALUES, MAGIC, MERKLE_MAGIC, SCHEMA_MAGIC};
+use super::error::ParsError;
+use super::transaction::Transaction;
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::{Arc, LazyLock, RwLock};
+
+const CSV_FORMAT: &str = "csv";
+const TEXT_FORMAT: &str = "text";
+const BIN_FORMAT: &str = "bin";
+const TXT_EXT: &str = "txt";
+
+/// Количество байт, просматриваемых при определении формата по содержимому
+pub(crate) const DETECT_PREFIX_LEN: u64 = 512;
+
+/// Поддерживаемые форматы записи транзакций
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Hash)]
+pub enum Format {
+    /// csv
+    Csv,
+    /// text
+    Text,
+    /// bin
+    Bin,
+    /// Формат, зарегистрированный приложением через [register_format]
+    Custom(&'static str),
+}
+
+impl Format {
+    /// Все поддерживаемые форматы
+    pub const ALL: [Format; 3] = [Format::Csv, Format::Text, Format::Bin];
+
+    /// Строковое имя формата
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Csv => CSV_FORMAT,
+            Self::Text => TEXT_FORMAT,
+            Self::Bin => BIN_FORMAT,
+            Self::Custom(name) => name,
+        }
+    }
+
+    /// Определение формата по первым байтам потока: magic записи, схемы или корня для bin,
+    /// заголовок для csv, пары `KEY: value` или комментарии для text
+    pub fn detect(prefix: &[u8]) -> Result<Self, ParsError> {
+        if prefix.starts_with(&MAGIC.to_be_bytes())
+            || prefix.starts_with(&SCHEMA_MAGIC.to_be_bytes())
+            || prefix.starts_with(&MERKLE_MAGIC.to_be_bytes())
+        {
+            return Ok(Self::Bin);
+        }
+
+        let text = String::from_utf8_lossy(prefix);
+        if let Some(line) = text.lines().map(str::trim).find(|line| !line.is_empty()) {
+            if line.starts_with('#') {
+                return Ok(Self::Text);
+            }
+            if let Some((key, _)) = line.split_once(':')
+                && HEADER_VALUES.contains(&key.trim())
+            {
+                return Ok(Self::Text);
+            }
+            if let Some((key, _)) = line.split_once(',')
+                && HEADER_VALUES.contains(&key.trim())
+            {
+                return Ok(Self::Csv);
+            }
+        }
+
+        Err(ParsError::WrongFormat(
+            "Невозможно определить формат данных".to_owned(),
+        ))
+    }
+
+    /// Определение формата по расширению файла: csv, txt (text), bin
+    /// или имя зарегистрированного пользовательского формата
+    pub fn from_extension(ext: &str) -> Result<Self, ParsError> {
+        match ext {
+            TXT_EXT => Ok(Self::Text),
+            _ => ext.parse(),
+        }
+    }
+
+    /// Определение формата и сжатия по имени файла, например `data.csv` или `data.csv.gz`
+    pub fn from_path(path: &Path) -> Result<(Self, Compression), ParsError> {
+        let wrong_path = || {
+            ParsError::WrongFormat(format!(
+                "Невозможно определить формат файла: {}",
+                path.display()
+            ))
+        };
+        let mut ext = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .ok_or_else(wrong_path)?;
+        let mut compression = Compression::None;
+        if let Some(val) = Compression::from_extension(ext) {
+            compression = val;
+            ext = path
+                .file_stem()
+                .map(Path::new)
+                .and_then(|stem| stem.extension())
+                .and_then(|e| e.to_str())
+                .ok_or_else(wrong_path)?;
+        }
+        let fin_format = Self::from_extension(ext).map_err(|_| wrong_path())?;
+        Ok((fin_format, compression))
+    }
+}
+
+/// Источник транзакций, читающий их по одной
+pub trait TransactionRead {
+    /// Чтение следующей транзакции. None означает конец потока
+    fn read_transaction(&mut self) -> Result<Option<Transaction>, ParsError>;
+
+    /// Чтение до max транзакций с добавлением в конец out.
+    /// Возвращает количество прочитанных транзакций, 0 означает конец потока
+    fn read_batch(&mut self, out: &mut Vec<Transaction>, max: usize) -> Result<usize, ParsError> {
+        let mut cnt = 0;
+        while cnt < max {
+            match self.read_transaction()? {
+                Some(tx) => out.push(tx),
+                None => break,
+            }
+            cnt += 1;
+        }
+        Ok(cnt)
+    }
+}
+
+/// Приемник транзакций, записывающий их по одной
+pub trait TransactionWrite {
+    /// Запись одной транзакции
+    fn write_transaction(&mut self, tx: &Transaction) -> Result<(), ParsError>;
+
+    /// Запись набора транзакций. Встроенные форматы записывают набор одним вызовом write_all
+    fn write_batch(&mut self, txs: &[Transaction]) -> Result<(), ParsError> {
+        for tx in txs {
+            self.write_transaction(tx)?;
+        }
+        Ok(())
+    }
+
+    /// Сброс буферизованных данных
+    fn flush(&mut self) -> Result<(), ParsError> {
+        Ok(())
+    }
+
+    /// Завершение записи: запись служебных данных формата и сброс буферов
+    fn finish(&mut self) -> Result<(), ParsError> {
+        self.flush()
+    }
+}
+
+impl<R: TransactionRead + ?Sized> TransactionRead for &mut R {
+    fn read_transaction(&mut self) -> Result<Option<Transaction>, ParsError> {
+        (**self).read_transaction()
+    }
+
+    fn read_batch(&mut self, out: &mut Vec<Transaction>, max: usize) -> Result<usize, ParsError> {
+        (**self).read_batch(out, max)
+    }
+}
+
+impl<R: TransactionRead + ?Sized> TransactionRead for Box<R> {
+    fn read_transaction(&mut self) -> Result<Option<Transaction>, ParsError> {
+        (**self).read_transaction()
+    }
+
+    fn read_batch(&mut self, out: &mut Vec<Transaction>, max: usize) -> Result<usize, ParsError> {
+        (**self).read_batch(out, max)
+    }
+}
+
+impl<W: TransactionWrite + ?Sized> TransactionWrite for &mut W {
+    fn write_transaction(&mut self, tx: &Transaction) -> Result<(), ParsError> {
+        (**self).write_transaction(tx)
+    }
+
+    fn write_batch(&mut self, txs: &[Transaction]) -> Result<(), ParsError> {
+        (**self).write_batch(txs)
+    }
+
+    fn flush(&mut self) -> Result<(), ParsError> {
+        (**self).flush()
+    }
+
+    fn finish(&mut self) -> Result<(), ParsError> {
+        (**self).finish()
+    }
+}
+
+impl<W: TransactionWrite + ?Sized> TransactionWrite for Box<W> {
+    fn write_transaction(&mut self, tx: &Transaction) -> Result<(), ParsError> {
+        (**self).write_transaction(tx)
+    }
+
+    fn write_batch(&mut self, txs: &[Transaction]) -> Result<(), ParsError> {
+        (**self).write_batch(txs)
+    }
+
+    fn flush(&mut self) -> Result<(), ParsError> {
+        (**self).flush()
+    }
+
+    fn finish(&mut self) -> Result<(), ParsError> {
+        (**self).finish()
+    }
+}
+
+/// Чтение всех транзакций из любого источника независимо от формата
+pub fn read_fin_data<R: TransactionRead + ?Sized>(
+    reader: &mut R,
+) -> Result<Vec<Transaction>, ParsError> {
+    let mut res = Vec::new();
+    while let Some(tx) = reader.read_transaction()? {
+        res.push(tx);
+    }
+    Ok(res)
+}
+
+/// Запись набора транзакций в любой приемник независимо от формата
+pub fn write_fin_data<W: TransactionWrite + ?Sized>(
+    writer: &mut W,
+    txs: &[Transaction],
+) -> Result<(), ParsError> {
+    writer.write_batch(txs)
+}
+
+/// Пользовательский формат транзакций, который можно зарегистрировать
+/// через [register_format] и использовать наравне со встроенными. Читатели
+/// и писатели таких форматов создаются через [crate::tx_format::TxReader::with_custom],
+/// [crate::tx_format::TxWriter::with_custom] или построители
+pub trait TxFormat: Send + Sync {
+    /// Имя формата, по которому он выбирается из строки
+    fn name(&self) -> &'static str;
+
+    /// Создание читателя транзакций поверх потока
+    fn reader(
+        &self,
+        stream: Box<dyn Read + Send>,
+    ) -> Result<Box<dyn TransactionRead + Send>, ParsError>;
+
+    /// Создание писателя транзакций поверх потока
+    fn writer(
+        &self,
+        stream: Box<dyn Write + Send>,
+    ) -> Result<Box<dyn TransactionWrite + Send>, ParsError>;
+}
+
+static REGISTRY: LazyLock<RwLock<HashMap<&'static str, Arc<dyn TxFormat>>>> =
+    LazyLock::new(|| RwLock::new(HashMap::new()));
+
+/// Регистрация пользовательского формата. Повторная регистрация формата с тем же
+/// именем заменяет предыдущую, переопределить встроенные форматы нельзя
+pub fn register_format<F: TxFormat + 'static>(fin_format: F) -> Result<Format, ParsError> {
+    let name = fin_format.name();
+    if Format::ALL.iter().any(|f| f.name() == name) {
+        return Err(ParsError::WrongFormat(format!(
+            "Формат {name} является встроенным"
+        )));
+    }
+    let mut registry = REGISTRY
+        .write()
+        .map_err(|_| ParsError::IoError(io::Error::other("Реестр форматов недоступен")))?;
+    registry.insert(name, Arc::new(fin_format));
+    Ok(Format::Custom(name))
+}
+
+/// Поиск зарегистрированного пользовательского формата по имени
+pub(crate) fn find_format(name: &str) -> Result<Arc<dyn TxFormat>, ParsError> {
+    let registry = REGISTRY
+        .read()
+        .map_err(|_| ParsError::IoError(io::Error::other("Реестр форматов недоступен")))?;
+    registry
+        .get(name)
+        .cloned()
+        .ok_or_else(|| ParsError::UnknownFormat {
+            name: name.to_owned(),
+        })
+}
+
+impl FromStr for Format {
+    type Err = ParsError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            CSV_FORMAT => Ok(Self::Csv),
+            TEXT_FORMAT => Ok(Self::Text),
+            BIN_FORMAT => Ok(Self::Bin),
+            _ => Ok(Self::Custom(find_format(s)?.name())),
+        }
+    }
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tx_format::TxWriter;
+
+    #[test]
+    fn test_format_from_str() {
+        assert_eq!("csv".parse::<Format>().unwrap(), Format::Csv);
+        assert_eq!("text".parse::<Format>().unwrap(), Format::Text);
+        assert_eq!("bin".parse::<Format>().unwrap(), Format::Bin);
+        assert!("xml".parse::<Format>().is_err());
+    }
+
+    #[test]
+    fn test_format_from_path() {
+        assert_eq!(
+            Format::from_path(Path::new("out/data.csv")).unwrap(),
+            (Format::Csv, Compression::None)
+        );
+        assert_eq!(
+            Format::from_path(Path::new("data.txt")).unwrap(),
+            (Format::Text, Compression::None)
+        );
+        assert_eq!(
+            Format::from_path(Path::new("data.2021.bin.gz")).unwrap(),
+            (Format::Bin, Compression::Gzip)
+        );
+        assert!(Format::from_path(Path::new("data")).is_err());
+        assert!(Format::from_path(Path::new("data.gz")).is_err());
+        assert!(Format::from_path(Path::new("data.xml")).is_err());
+    }
+
+    #[test]
+    fn test_format_detect() {
+        assert_eq!(
+            Format::detect(b"YPBN\x00\x00\x00\x3f").unwrap(),
+            Format::Bin
+        );
+        assert_eq!(
+            Format::detect(b"YPBS\x00\x02\x00\x08").unwrap(),
+            Format::Bin
+        );
+        assert_eq!(
+            Format::detect(b"\n  TX_ID,TX_TYPE,FROM_USER_ID,TO_USER_ID").unwrap(),
+            Format::Csv
+        );
+        assert_eq!(
+            Format::detect(b"# Record 1 (DEPOSIT)\nTX_TYPE: DEPOSIT").unwrap(),
+            Format::Text
+        );
+        assert_eq!(Format::detect(b"TX_TYPE: DEPOSIT\n").unwrap(), Format::Text);
+        assert!(Format::detect(b"<xml/>").is_err());
+        assert!(Format::detect(b"").is_err());
+    }
+
+    struct UpperCsv;
+
+    struct UpperCsvWriter(Box<dyn Write + Send>);
+
+    impl TransactionWrite for UpperCsvWriter {
+        fn write_transaction(&mut self, tx: &Transaction) -> Result<(), ParsError> {
+            writeln!(self.0, "{};{}", tx.tx_id, tx.description.to_uppercase())?;
+            Ok(())
+        }
+    }
+
+    impl TxFormat for UpperCsv {
+        fn name(&self) -> &'static str {
+            "upper"
+        }
+
+        fn reader(
+            &self,
+            _stream: Box<dyn Read + Send>,
+        ) -> Result<Box<dyn TransactionRead + Send>, ParsError> {
+            Err(ParsError::WrongFormat("Только запись".to_owned()))
+        }
+
+        fn writer(
+            &self,
+            stream: Box<dyn Write + Send>,
+        ) -> Result<Box<dyn TransactionWrite + Send>, ParsError> {
+            Ok(Box::new(UpperCsvWriter(stream)))
+        }
+    }
+
+    struct FakeBin;
+
+    impl TxFormat for FakeBin {
+        fn name(&self) -> &'static str {
+            "bin"
+        }
+
+        fn reader(
+            &self,
+            _stream: Box<dyn Read + Send>,
+        ) -> Result<Box<dyn TransactionRead + Send>, ParsError> {
+            unreachable!()
+        }
+
+        fn writer(
+            &self,
+            _stream: Box<dyn Write + Send>,
+        ) -> Result<Box<dyn TransactionWrite + Send>, ParsError> {
+            unreachable!()
+        }
+    }
+
+    struct VecSource(Vec<Transaction>);
+
+    impl TransactionRead for VecSource {
+        fn read_transaction(&mut self) -> Result<Option<Transaction>, ParsError> {
+            Ok(self.0.pop())
+        }
+    }
+
+    #[test]
+    fn test_fin_data_generic() {
+        let tx = "TX_ID: 1\nTX_TYPE: DEPOSIT\nFROM_USER_ID: 0\nTO_USER_ID: 2\nAMOUNT: 3\n\
+            TIMESTAMP: 1633036860000\nSTATUS: SUCCESS\nDESCRIPTION: \"a\"\n"
+            .parse::<Transaction>()
+            .unwrap();
+        let mut reader: Box<dyn TransactionRead + Send> = Box::new(VecSource(vec![tx]));
+        let txs = read_fin_data(&mut reader).unwrap();
+        assert_eq!(txs.len(), 1);
+        assert_eq!(txs[0].tx_id, 1);
+        assert!(read_fin_data(&mut reader).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_register_format() {
+        assert!("upper".parse::<Format>().is_err());
+        let fin_format = register_format(UpperCsv).unwrap();
+        assert_eq!(fin_format, Format::Custom("upper"));
+        assert_eq!("upper".parse::<Format>().unwrap(), fin_format);
+        assert_eq!(fin_format.to_string(), "upper");
+
+        assert!(TxWriter::new(Vec::new(), fin_format).is_err());
+        let mut writer = TxWriter::with_custom(Vec::new(), fin_format, Default::default()).unwrap();
+        writer.write_all(&[]).unwrap();
+        writer.finish().unwrap();
+    }
+
+    #[test]
+    fn test_register_builtin_format() {
+        assert!(register_format(FakeBin).is_err());
+        assert_eq!("bin".parse::<Format>().unwrap(), Format::Bin);
+    }
+
+    #[test]
+    fn test_format_display() {
+        for format in Format::ALL {
+            assert_eq!(format.to_string().parse::<Format>().unwrap(), format);
+        }
+    }
+}
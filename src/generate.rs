@@ -0,0 +1,590 @@
+use super::analytics::{magnitude, magnitude_range};
+use super::constants::{DEPOSIT, FAILURE, PENDING, SUCCESS, TRANSFER, WITHDRAWAL};
+use super::error::ParsError;
+use super::format::TransactionRead;
+use super::sample::SplitMix64;
+use super::transaction::{Transaction, TxStatus, TxType};
+use chrono::{DateTime, TimeDelta, Utc};
+use std::str::FromStr;
+
+/// Шаблон описания по умолчанию
+pub const DEFAULT_TEMPLATE: &str = "Record number {tx_id}";
+
+/// Веса значений: значение выбирается с вероятностью, пропорциональной весу.
+/// Разбирается из строки вида `DEPOSIT=1,TRANSFER=3`
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct Weights<T>(pub Vec<(T, u32)>);
+
+impl<T: Copy> Weights<T> {
+    fn total(&self) -> u64 {
+        self.0.iter().map(|(_, weight)| *weight as u64).sum()
+    }
+
+    fn weight_of(&self, val: T) -> u32
+    where
+        T: PartialEq,
+    {
+        self.0
+            .iter()
+            .filter(|(key, _)| *key == val)
+            .map(|(_, weight)| *weight)
+            .sum()
+    }
+
+    fn pick(&self, rng: &mut SplitMix64) -> T {
+        let mut point = rng.below(self.total());
+        for (val, weight) in &self.0 {
+            if point < *weight as u64 {
+                return *val;
+            }
+            point -= *weight as u64;
+        }
+        unreachable!("Сумма весов проверяется при создании генератора")
+    }
+}
+
+fn parse_weights<T>(s: &str, parse: impl Fn(&str) -> Option<T>) -> Result<Weights<T>, ParsError> {
+    let mut res = Vec::new();
+    for item in s.split(',') {
+        let weight = item
+            .split_once('=')
+            .and_then(|(name, weight)| Some((parse(name.trim())?, weight.trim().parse().ok()?)));
+        match weight {
+            Some(val) => res.push(val),
+            None => {
+                return Err(ParsError::WrongFormat(format!(
+                    "Некорректный вес {item}, ожидается ЗНАЧЕНИЕ=ВЕС"
+                )));
+            }
+        }
+    }
+    Ok(Weights(res))
+}
+
+impl FromStr for Weights<TxType> {
+    type Err = ParsError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_weights(s, |name| match name.to_ascii_uppercase().as_str() {
+            DEPOSIT => Some(TxType::Deposit),
+            TRANSFER => Some(TxType::Transfer),
+            WITHDRAWAL => Some(TxType::Withdrawal),
+            _ => None,
+        })
+    }
+}
+
+impl FromStr for Weights<TxStatus> {
+    type Err = ParsError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_weights(s, |name| match name.to_ascii_uppercase().as_str() {
+            SUCCESS => Some(TxStatus::Success),
+            FAILURE => Some(TxStatus::Failure),
+            PENDING => Some(TxStatus::Pending),
+            _ => None,
+        })
+    }
+}
+
+/// Распределение сумм
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Default)]
+pub enum AmountDistribution {
+    /// Равномерное на отрезке
+    #[default]
+    Uniform,
+    /// Равномерное по порядку: сначала выбирается количество десятичных знаков,
+    /// затем сумма внутри порядка. Мелкие и крупные суммы встречаются одинаково часто
+    LogUniform,
+}
+
+impl FromStr for AmountDistribution {
+    type Err = ParsError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "uniform" => Ok(Self::Uniform),
+            "log-uniform" => Ok(Self::LogUniform),
+            _ => Err(ParsError::WrongFormat(format!(
+                "Неизвестное распределение сумм: {s}"
+            ))),
+        }
+    }
+}
+
+/// Распределение активности пользователей
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Default)]
+pub enum UserDistribution {
+    /// Все пользователи участвуют в транзакциях одинаково часто
+    #[default]
+    Uniform,
+    /// Доля share_percent участий приходится на активных пользователей — первые
+    /// users_percent процентов идентификаторов, остальные делятся между прочими.
+    /// Разбирается из строки `skewed:USERS/SHARE`, просто `skewed` означает 20/80
+    Skewed {
+        /// Процент активных пользователей, от 1 до 99
+        users_percent: u8,
+        /// Процент участий активных пользователей, от 0 до 100
+        share_percent: u8,
+    },
+}
+
+impl FromStr for UserDistribution {
+    type Err = ParsError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let error = || {
+            ParsError::WrongFormat(format!(
+                "Неизвестное распределение пользователей: {s}, ожидается uniform или skewed:USERS/SHARE"
+            ))
+        };
+        let percents = match s.split_once(':') {
+            None if s == "uniform" => return Ok(Self::Uniform),
+            None if s == "skewed" => (20, 80),
+            Some(("skewed", percents)) => {
+                let (users, share) = percents.split_once('/').ok_or_else(error)?;
+                let parse = |val: &str| val.trim().parse().map_err(|_| error());
+                (parse(users)?, parse(share)?)
+            }
+            _ => return Err(error()),
+        };
+        Ok(Self::Skewed {
+            users_percent: percents.0,
+            share_percent: percents.1,
+        })
+    }
+}
+
+/// Настройки генератора транзакций
+#[derive(Clone, Debug)]
+pub struct GenerateOptions {
+    seed: u64,
+    start_id: u64,
+    types: Weights<TxType>,
+    statuses: Weights<TxStatus>,
+    min_amount: i64,
+    max_amount: i64,
+    distribution: AmountDistribution,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+    sorted: bool,
+    users: u64,
+    user_distribution: UserDistribution,
+    templates: Vec<String>,
+}
+
+impl Default for GenerateOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl GenerateOptions {
+    /// Настройки по умолчанию: seed 0, все типы и статусы с равными весами,
+    /// суммы от 1 до 10000, время в течение 2021 года, 1000 пользователей
+    pub fn new() -> Self {
+        Self {
+            seed: 0,
+            start_id: 1,
+            types: Weights(vec![
+                (TxType::Deposit, 1),
+                (TxType::Transfer, 1),
+                (TxType::Withdrawal, 1),
+            ]),
+            statuses: Weights(vec![
+                (TxStatus::Success, 1),
+                (TxStatus::Failure, 1),
+                (TxStatus::Pending, 1),
+            ]),
+            min_amount: 1,
+            max_amount: 10000,
+            distribution: AmountDistribution::Uniform,
+            from: DateTime::from_timestamp(1609459200, 0).unwrap_or_default(),
+            to: DateTime::from_timestamp(1640995200, 0).unwrap_or_default(),
+            sorted: false,
+            users: 1000,
+            user_distribution: UserDistribution::Uniform,
+            templates: vec![DEFAULT_TEMPLATE.to_owned()],
+        }
+    }
+
+    /// Начальное значение генератора случайных чисел. Одинаковые настройки
+    /// дают одинаковые транзакции на любой платформе
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Идентификатор первой транзакции, следующие идут подряд
+    pub fn with_start_id(mut self, start_id: u64) -> Self {
+        self.start_id = start_id;
+        self
+    }
+
+    /// Веса типов транзакций
+    pub fn with_types(mut self, types: Weights<TxType>) -> Self {
+        self.types = types;
+        self
+    }
+
+    /// Веса статусов транзакций
+    pub fn with_statuses(mut self, statuses: Weights<TxStatus>) -> Self {
+        self.statuses = statuses;
+        self
+    }
+
+    /// Границы сумм (включительно) и их распределение
+    pub fn with_amounts(mut self, min: i64, max: i64, distribution: AmountDistribution) -> Self {
+        self.min_amount = min;
+        self.max_amount = max;
+        self.distribution = distribution;
+        self
+    }
+
+    /// Границы времени транзакций (включительно)
+    pub fn with_time_range(mut self, from: DateTime<Utc>, to: DateTime<Utc>) -> Self {
+        self.from = from;
+        self.to = to;
+        self
+    }
+
+    /// Транзакции упорядочены по времени
+    pub fn with_sorted(mut self, sorted: bool) -> Self {
+        self.sorted = sorted;
+        self
+    }
+
+    /// Количество пользователей, идентификаторы от 1 до users
+    pub fn with_users(mut self, users: u64) -> Self {
+        self.users = users;
+        self
+    }
+
+    /// Распределение активности пользователей
+    pub fn with_user_distribution(mut self, distribution: UserDistribution) -> Self {
+        self.user_distribution = distribution;
+        self
+    }
+
+    /// Шаблоны описаний, для каждой транзакции выбирается случайный. Подстановки:
+    /// `{tx_id}`, `{type}`, `{status}`, `{from}`, `{to}`, `{amount}`
+    pub fn with_templates(mut self, templates: Vec<String>) -> Self {
+        self.templates = templates;
+        self
+    }
+
+    fn check(&self) -> Result<(), ParsError> {
+        let error = |msg: &str| Err(ParsError::WrongFormat(msg.to_owned()));
+        if self.types.total() == 0 || self.statuses.total() == 0 {
+            return error("Сумма весов типов и статусов должна быть больше нуля");
+        }
+        if self.min_amount > self.max_amount {
+            return error("Минимальная сумма больше максимальной");
+        }
+        if self.distribution == AmountDistribution::LogUniform && self.min_amount < 0 {
+            return error("Распределение по порядку допускает только неотрицательные суммы");
+        }
+        if self.from > self.to {
+            return error("Начало интервала времени позже конца");
+        }
+        if self.users == 0 {
+            return error("Количество пользователей должно быть больше нуля");
+        }
+        if let UserDistribution::Skewed {
+            users_percent,
+            share_percent,
+        } = self.user_distribution
+            && (!(1..=99).contains(&users_percent) || share_percent > 100)
+        {
+            return error(
+                "Процент активных пользователей должен быть от 1 до 99, их доли — до 100",
+            );
+        }
+        if self.users < 2 && self.types.weight_of(TxType::Transfer) > 0 {
+            return error("Для переводов нужно не меньше двух пользователей");
+        }
+        if self.templates.is_empty() {
+            return error("Не задано ни одного шаблона описания");
+        }
+        Ok(())
+    }
+}
+
+/// Генератор синтетических транзакций для нагрузочных тестов и корпусов фаззинга.
+/// Депозиты приходят от системного пользователя 0, списания уходят ему,
+/// переводы выполняются между разными пользователями
+///
+/// ```
+/// use fin_parser::generate::{GenerateOptions, Generator};
+///
+/// let options = GenerateOptions::new().with_seed(7);
+/// let txs: Vec<_> = Generator::new(options.clone(), 3).unwrap().collect();
+/// let again: Vec<_> = Generator::new(options, 3).unwrap().collect();
+/// assert_eq!(txs, again);
+/// assert_eq!(txs[2].tx_id, 3);
+/// ```
+pub struct Generator {
+    options: GenerateOptions,
+    rng: SplitMix64,
+    count: u64,
+    next: u64,
+}
+
+impl Generator {
+    /// Генератор count транзакций. Возвращает ошибку при противоречивых настройках
+    pub fn new(options: GenerateOptions, count: u64) -> Result<Self, ParsError> {
+        options.check()?;
+        Ok(Self {
+            rng: SplitMix64(options.seed),
+            options,
+            count,
+            next: 0,
+        })
+    }
+
+    /// Число из отрезка [min, max]
+    fn in_range(&mut self, min: i64, max: i64) -> i64 {
+        let span = max.abs_diff(min);
+        let offset = match span {
+            u64::MAX => self.rng.next_u64(),
+            _ => self.rng.below(span + 1),
+        };
+        min.wrapping_add(offset as i64)
+    }
+
+    fn amount(&mut self) -> i64 {
+        let (min, max) = (self.options.min_amount, self.options.max_amount);
+        match self.options.distribution {
+            AmountDistribution::Uniform => self.in_range(min, max),
+            AmountDistribution::LogUniform => {
+                let digits = self.in_range(magnitude(min) as i64, magnitude(max) as i64);
+                let (low, high) = magnitude_range(digits as u32);
+                self.in_range(
+                    (low as i64).max(min),
+                    i64::try_from(high).unwrap_or(i64::MAX).min(max),
+                )
+            }
+        }
+    }
+
+    fn timestamp(&mut self) -> DateTime<Utc> {
+        let from = self.options.from.timestamp_millis();
+        let to = self.options.to.timestamp_millis();
+        let millis = match self.options.sorted {
+            // Интервал делится на равные части, транзакция попадает в свою часть
+            true => {
+                let step = (to - from) as i128 / self.count.max(1) as i128;
+                let start = from + (step * self.next as i128) as i64;
+                self.in_range(start, (start + step as i64).min(to))
+            }
+            false => self.in_range(from, to),
+        };
+        self.options.from + TimeDelta::milliseconds(millis - from)
+    }
+
+    fn user(&mut self) -> u64 {
+        let users = self.options.users;
+        let UserDistribution::Skewed {
+            users_percent,
+            share_percent,
+        } = self.options.user_distribution
+        else {
+            return 1 + self.rng.below(users);
+        };
+        if users == 1 {
+            return 1;
+        }
+        // Активных пользователей не меньше одного, и хотя бы один остается прочим
+        let hot = (users as u128 * users_percent as u128 / 100).clamp(1, users as u128 - 1) as u64;
+        match self.rng.below(100) < share_percent as u64 {
+            true => 1 + self.rng.below(hot),
+            false => hot + 1 + self.rng.below(users - hot),
+        }
+    }
+
+    /// Пользователь, отличный от from. Получатель перевода выбирается по распределению,
+    /// а при совпадении с отправителем — среди остальных пользователей
+    fn other_user(&mut self, from: u64) -> u64 {
+        let users = self.options.users;
+        if self.options.user_distribution != UserDistribution::Uniform {
+            let to = self.user();
+            if to != from {
+                return to;
+            }
+        }
+        1 + (from + self.rng.below(users - 1)) % users
+    }
+
+    /// Следующая транзакция. None после count транзакций
+    pub fn generate(&mut self) -> Option<Transaction> {
+        if self.next == self.count {
+            return None;
+        }
+        let tx_id = self.options.start_id.wrapping_add(self.next);
+        let tx_type = self.options.types.pick(&mut self.rng);
+        let status = self.options.statuses.pick(&mut self.rng);
+        let (from_user_id, to_user_id) = match tx_type {
+            TxType::Deposit => (0, self.user()),
+            TxType::Withdrawal => (self.user(), 0),
+            TxType::Transfer => {
+                let from = self.user();
+                (from, self.other_user(from))
+            }
+        };
+        let amount = self.amount();
+        let timestamp = self.timestamp();
+        let template = self.rng.below(self.options.templates.len() as u64) as usize;
+        let description = self.options.templates[template]
+            .replace("{tx_id}", &tx_id.to_string())
+            .replace("{type}", type_name(tx_type))
+            .replace("{status}", status_name(status))
+            .replace("{from}", &from_user_id.to_string())
+            .replace("{to}", &to_user_id.to_string())
+            .replace("{amount}", &amount.to_string());
+        self.next += 1;
+        Some(Transaction {
+            tx_id,
+            tx_type,
+            from_user_id,
+            to_user_id,
+            amount,
+            timestamp,
+            status,
+            description,
+        })
+    }
+}
+
+impl Iterator for Generator {
+    type Item = Transaction;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.generate()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let left = usize::try_from(self.count - self.next).ok();
+        (left.unwrap_or(usize::MAX), left)
+    }
+}
+
+impl TransactionRead for Generator {
+    fn read_transaction(&mut self) -> Result<Option<Transaction>, ParsError> {
+        Ok(self.generate())
+    }
+}
+
+fn type_name(val: TxType) -> &'static str {
+    match val {
+        TxType::Deposit => DEPOSIT,
+        TxType::Transfer => TRANSFER,
+        TxType::Withdrawal => WITHDRAWAL,
+    }
+}
+
+fn status_name(val: TxStatus) -> &'static str {
+    match val {
+        TxStatus::Success => SUCCESS,
+        TxStatus::Failure => FAILURE,
+        TxStatus::Pending => PENDING,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generate() {
+        let from = DateTime::from_timestamp(1633036860, 0).unwrap();
+        let to = DateTime::from_timestamp(1633123260, 0).unwrap();
+        let options = GenerateOptions::new()
+            .with_seed(42)
+            .with_start_id(100)
+            .with_types("transfer=3,deposit=1".parse().unwrap())
+            .with_statuses("SUCCESS=1".parse().unwrap())
+            .with_amounts(5, 500000, AmountDistribution::LogUniform)
+            .with_time_range(from, to)
+            .with_sorted(true)
+            .with_users(2)
+            .with_templates(vec!["{type} {from}->{to}: {amount}".to_owned()]);
+        let txs: Vec<Transaction> = Generator::new(options.clone(), 200).unwrap().collect();
+        assert_eq!(txs.len(), 200);
+        assert_eq!(
+            txs,
+            Generator::new(options, 200).unwrap().collect::<Vec<_>>()
+        );
+
+        assert_eq!(txs[0].tx_id, 100);
+        assert!(
+            txs.windows(2)
+                .all(|pair| pair[0].timestamp <= pair[1].timestamp)
+        );
+        assert!(
+            txs.iter()
+                .all(|tx| tx.timestamp >= from && tx.timestamp <= to)
+        );
+        assert!(txs.iter().all(|tx| (5..=500000).contains(&tx.amount)));
+        assert!(txs.iter().any(|tx| tx.amount < 100));
+        assert!(txs.iter().all(|tx| tx.status == TxStatus::Success));
+        for tx in &txs {
+            match tx.tx_type {
+                TxType::Deposit => assert_eq!(tx.from_user_id, 0),
+                TxType::Transfer => assert_ne!(tx.from_user_id, tx.to_user_id),
+                TxType::Withdrawal => panic!("Вес WITHDRAWAL не задан"),
+            }
+            assert_eq!(
+                tx.description,
+                format!(
+                    "{} {}->{}: {}",
+                    type_name(tx.tx_type),
+                    tx.from_user_id,
+                    tx.to_user_id,
+                    tx.amount
+                )
+            );
+        }
+    }
+
+    #[test]
+    fn test_options_check() {
+        let options = GenerateOptions::new().with_amounts(10, 1, AmountDistribution::Uniform);
+        assert!(Generator::new(options, 1).is_err());
+        let options = GenerateOptions::new().with_users(1);
+        assert!(Generator::new(options, 1).is_err());
+        let options = GenerateOptions::new()
+            .with_users(1)
+            .with_types("DEPOSIT=1".parse().unwrap());
+        assert!(Generator::new(options, 1).is_ok());
+        assert!("DEPOSIT=x".parse::<Weights<TxType>>().is_err());
+        assert!("REFUND=1".parse::<Weights<TxType>>().is_err());
+        assert_eq!(
+            "log-uniform".parse::<AmountDistribution>().unwrap(),
+            AmountDistribution::LogUniform
+        );
+        assert_eq!(
+            "skewed:10/90".parse::<UserDistribution>().unwrap(),
+            UserDistribution::Skewed {
+                users_percent: 10,
+                share_percent: 90
+            }
+        );
+        assert!("skewed:10".parse::<UserDistribution>().is_err());
+        let options = GenerateOptions::new().with_user_distribution("skewed:0/50".parse().unwrap());
+        assert!(Generator::new(options, 1).is_err());
+    }
+
+    #[test]
+    fn test_skewed_users() {
+        let options = GenerateOptions::new()
+            .with_seed(3)
+            .with_users(100)
+            .with_types("TRANSFER=1".parse().unwrap())
+            .with_user_distribution("skewed:10/90".parse().unwrap());
+        let txs: Vec<_> = Generator::new(options, 10000).unwrap().collect();
+        assert!(txs.iter().all(|tx| tx.from_user_id != tx.to_user_id));
+        let hot = txs.iter().filter(|tx| tx.from_user_id <= 10).count();
+        assert!((8700..=9300).contains(&hot), "{hot}");
+        assert!(txs.iter().any(|tx| tx.to_user_id > 10));
+    }
+}
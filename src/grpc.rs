@@ -0,0 +1,344 @@
+use super::config::{Config, Section};
+use super::error::ParsError;
+use super::filter::{FilteredReader, TxFilter};
+use super::format::Format;
+use super::transaction::{Transaction, TxStatus, TxType};
+use super::tx_format::TxReader;
+use chrono::DateTime;
+use proto::transaction_service_server::{TransactionService, TransactionServiceServer};
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::{Request, Response, Status, Streaming};
+
+/// Типы сообщений, клиент и сервер, сгенерированные из `proto/fin_parser.proto`
+#[allow(missing_docs)]
+pub mod proto {
+    tonic::include_proto!("fin_parser");
+}
+
+/// Количество транзакций в очереди между потоком чтения-записи файла и соединением
+const CHANNEL_CAPACITY: usize = 256;
+
+impl From<&Transaction> for proto::Transaction {
+    fn from(tx: &Transaction) -> Self {
+        let tx_type = match tx.tx_type {
+            TxType::Deposit => proto::TxType::Deposit,
+            TxType::Transfer => proto::TxType::Transfer,
+            TxType::Withdrawal => proto::TxType::Withdrawal,
+        };
+        let status = match tx.status {
+            TxStatus::Success => proto::TxStatus::Success,
+            TxStatus::Failure => proto::TxStatus::Failure,
+            TxStatus::Pending => proto::TxStatus::Pending,
+        };
+        Self {
+            tx_id: tx.tx_id,
+            tx_type: tx_type.into(),
+            from_user_id: tx.from_user_id,
+            to_user_id: tx.to_user_id,
+            amount: tx.amount,
+            timestamp: tx.timestamp.timestamp_millis(),
+            status: status.into(),
+            description: tx.description.clone(),
+        }
+    }
+}
+
+impl TryFrom<proto::Transaction> for Transaction {
+    type Error = ParsError;
+
+    fn try_from(tx: proto::Transaction) -> Result<Self, Self::Error> {
+        let wrong = |field: &str, val: i64| {
+            ParsError::WrongFormat(format!(
+                "Транзакция {}: некорректное поле {field}: {val}",
+                tx.tx_id
+            ))
+        };
+        let tx_type = match proto::TxType::try_from(tx.tx_type) {
+            Ok(proto::TxType::Deposit) => TxType::Deposit,
+            Ok(proto::TxType::Transfer) => TxType::Transfer,
+            Ok(proto::TxType::Withdrawal) => TxType::Withdrawal,
+            _ => return Err(wrong("tx_type", tx.tx_type.into())),
+        };
+        let status = match proto::TxStatus::try_from(tx.status) {
+            Ok(proto::TxStatus::Success) => TxStatus::Success,
+            Ok(proto::TxStatus::Failure) => TxStatus::Failure,
+            Ok(proto::TxStatus::Pending) => TxStatus::Pending,
+            _ => return Err(wrong("status", tx.status.into())),
+        };
+        let timestamp = DateTime::from_timestamp_millis(tx.timestamp)
+            .ok_or_else(|| wrong("timestamp", tx.timestamp))?;
+        Ok(Self {
+            tx_id: tx.tx_id,
+            tx_type,
+            from_user_id: tx.from_user_id,
+            to_user_id: tx.to_user_id,
+            amount: tx.amount,
+            timestamp,
+            status,
+            description: tx.description,
+        })
+    }
+}
+
+/// Сервис `TransactionService` поверх каталога файлов транзакций. Файлы задаются
+/// именами внутри каталога; формат и сжатие определяются по расширению, а для
+/// неизвестных расширений берутся из настроек ([FileService::with_config]).
+///
+/// ReadTransactions читает файл в отдельном потоке и передает транзакции по мере
+/// чтения. WriteTransactions пишет во временный файл и переименовывает его
+/// после успешного завершения потока, поэтому прерванная запись не портит файл
+#[derive(Clone, Debug)]
+pub struct FileService {
+    dir: PathBuf,
+    input: Section,
+    output: Section,
+}
+
+impl FileService {
+    /// Сервис каталога dir
+    pub fn new<P: Into<PathBuf>>(dir: P) -> Self {
+        Self {
+            dir: dir.into(),
+            input: Section::default(),
+            output: Section::default(),
+        }
+    }
+
+    /// Настройки чтения и записи файлов
+    pub fn with_config(mut self, config: &Config) -> Self {
+        self.input = config.input.clone();
+        self.output = config.output.clone();
+        self
+    }
+
+    /// Сервис для добавления в [tonic::transport::Server]
+    pub fn into_server(self) -> TransactionServiceServer<Self> {
+        TransactionServiceServer::new(self)
+    }
+
+    /// Путь файла каталога. Имена с разделителями пути и скрытые файлы не допускаются
+    fn file_path(&self, name: &str) -> Result<PathBuf, Status> {
+        if name.is_empty() || name.starts_with('.') || name.contains(['/', '\\']) {
+            return Err(Status::invalid_argument(format!(
+                "Некорректное имя файла: {name}"
+            )));
+        }
+        Ok(self.dir.join(name))
+    }
+
+    fn open_reader(&self, path: &Path) -> Result<TxReader<Box<dyn Read + Send>>, ParsError> {
+        let builder = self.input.reader_builder_for(path);
+        match Format::from_path(path) {
+            Ok(_) => builder.open(path),
+            Err(_) => builder.build(File::open(path)?),
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl TransactionService for FileService {
+    type ReadTransactionsStream = ReceiverStream<Result<proto::Transaction, Status>>;
+
+    async fn read_transactions(
+        &self,
+        request: Request<proto::ReadRequest>,
+    ) -> Result<Response<Self::ReadTransactionsStream>, Status> {
+        let request = request.into_inner();
+        let path = self.file_path(&request.file)?;
+        if !path.is_file() {
+            return Err(Status::not_found(format!(
+                "Файл {} не найден",
+                request.file
+            )));
+        }
+        let filter = match request.filter.as_str() {
+            "" => TxFilter::default(),
+            expr => expr
+                .parse()
+                .map_err(|e: ParsError| Status::invalid_argument(e.to_string()))?,
+        };
+        let reader = self.open_reader(&path).map_err(internal)?;
+        let limit = match request.limit {
+            0 => u64::MAX,
+            limit => limit,
+        };
+        let (sender, receiver) = mpsc::channel(CHANNEL_CAPACITY);
+        tokio::task::spawn_blocking(move || {
+            let mut reader = FilteredReader::new(reader, filter);
+            for _ in 0..limit {
+                let item = match reader.read_transaction() {
+                    Ok(Some(tx)) => Ok(proto::Transaction::from(&tx)),
+                    Ok(None) => break,
+                    Err(e) => Err(internal(e)),
+                };
+                let failed = item.is_err();
+                // Ошибка отправки означает, что клиент закрыл поток
+                if sender.blocking_send(item).is_err() || failed {
+                    break;
+                }
+            }
+        });
+        Ok(Response::new(ReceiverStream::new(receiver)))
+    }
+
+    async fn write_transactions(
+        &self,
+        request: Request<Streaming<proto::WriteRequest>>,
+    ) -> Result<Response<proto::WriteSummary>, Status> {
+        let mut stream = request.into_inner();
+        let Some(first) = stream.message().await? else {
+            return Err(Status::invalid_argument("Пустой поток записи"));
+        };
+        let path = self.file_path(&first.file)?;
+        let mut builder = self.output.writer_builder_for(Some(&path));
+        if let Ok((fin_format, compression)) = Format::from_path(&path) {
+            builder = builder.format(fin_format).compression(compression);
+        }
+        let mut tmp_name = path.file_name().unwrap_or_default().to_os_string();
+        tmp_name.push(format!(".tmp-{}", std::process::id()));
+        let tmp_path = path.with_file_name(tmp_name);
+        let mut writer = File::create(&tmp_path)
+            .map_err(ParsError::from)
+            .and_then(|file| builder.build(file))
+            .map_err(|e| {
+                let _ = fs::remove_file(&tmp_path);
+                Status::invalid_argument(format!("Невозможно создать файл {}: {e}", first.file))
+            })?;
+
+        let (sender, mut receiver) = mpsc::channel::<Transaction>(CHANNEL_CAPACITY);
+        let task = tokio::task::spawn_blocking(move || {
+            let mut written = 0;
+            while let Some(tx) = receiver.blocking_recv() {
+                writer.write_transaction(&tx)?;
+                written += 1;
+            }
+            writer.finish()?;
+            // Сжатый поток завершается при удалении писателя
+            drop(writer);
+            Ok::<_, ParsError>(written)
+        });
+        let received = async {
+            let mut request = first;
+            loop {
+                if let Some(tx) = request.transaction {
+                    let tx = Transaction::try_from(tx)
+                        .map_err(|e| Status::invalid_argument(e.to_string()))?;
+                    // Писатель остановился с ошибкой, она будет получена из task
+                    if sender.send(tx).await.is_err() {
+                        break;
+                    }
+                }
+                match stream.message().await? {
+                    Some(val) => request = val,
+                    None => break,
+                }
+            }
+            Ok(())
+        }
+        .await;
+        drop(sender);
+        let written = task.await.map_err(|e| Status::internal(e.to_string()))?;
+
+        let res = received.and_then(|_| written.map_err(internal));
+        let res = res.and_then(|written| {
+            fs::rename(&tmp_path, &path)
+                .map(|_| written)
+                .map_err(|e| internal(e.into()))
+        });
+        if res.is_err() {
+            let _ = fs::remove_file(&tmp_path);
+        }
+        Ok(Response::new(proto::WriteSummary { written: res? }))
+    }
+}
+
+fn internal(e: ParsError) -> Status {
+    Status::internal(e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util;
+    use proto::transaction_service_client::TransactionServiceClient;
+    use tokio::net::TcpListener;
+    use tokio_stream::wrappers::TcpListenerStream;
+
+    fn tx(tx_id: u64) -> Transaction {
+        Transaction {
+            tx_type: TxType::Transfer,
+            from_user_id: 1,
+            to_user_id: 2,
+            amount: 100 * tx_id as i64,
+            timestamp: DateTime::from_timestamp_millis(1633036860123).unwrap(),
+            status: TxStatus::Pending,
+            ..test_util::tx(tx_id)
+        }
+    }
+
+    #[test]
+    fn test_convert() {
+        let msg = proto::Transaction::from(&tx(7));
+        assert_eq!(msg.tx_type, proto::TxType::Transfer as i32);
+        assert_eq!(msg.timestamp, 1633036860123);
+        assert_eq!(Transaction::try_from(msg.clone()).unwrap(), tx(7));
+        let unspecified = proto::Transaction { status: 0, ..msg };
+        assert!(Transaction::try_from(unspecified).is_err());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_service() {
+        let dir = std::env::temp_dir().join(format!("fin_parser_grpc_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(
+            tonic::transport::Server::builder()
+                .add_service(FileService::new(&dir).into_server())
+                .serve_with_incoming(TcpListenerStream::new(listener)),
+        );
+        let mut client = TransactionServiceClient::connect(format!("http://{addr}"))
+            .await
+            .unwrap();
+
+        let requests: Vec<proto::WriteRequest> = (1..=5)
+            .map(|tx_id| proto::WriteRequest {
+                file: "a.csv.gz".to_owned(),
+                transaction: Some(proto::Transaction::from(&tx(tx_id))),
+            })
+            .collect();
+        let summary = client
+            .write_transactions(tokio_stream::iter(requests))
+            .await
+            .unwrap();
+        assert_eq!(summary.into_inner().written, 5);
+
+        let request = proto::ReadRequest {
+            file: "a.csv.gz".to_owned(),
+            filter: "amount > 100".to_owned(),
+            limit: 3,
+        };
+        let mut stream = client
+            .read_transactions(request)
+            .await
+            .unwrap()
+            .into_inner();
+        let mut res = Vec::new();
+        while let Some(msg) = stream.message().await.unwrap() {
+            res.push(Transaction::try_from(msg).unwrap());
+        }
+        assert_eq!(res, vec![tx(2), tx(3), tx(4)]);
+
+        let request = proto::ReadRequest {
+            file: "../a.csv.gz".to_owned(),
+            ..Default::default()
+        };
+        let status = client.read_transactions(request).await.unwrap_err();
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
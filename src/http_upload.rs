@@ -0,0 +1,238 @@
+use super::error::ParsError;
+use super::format::TransactionWrite;
+use super::transaction::Transaction;
+use std::thread;
+use std::time::Duration;
+
+/// Количество транзакций в одном запросе по умолчанию
+pub const DEFAULT_BATCH_SIZE: usize = 500;
+
+/// Количество повторов запроса по умолчанию
+pub const DEFAULT_RETRIES: u32 = 3;
+
+/// Пауза перед первым повтором по умолчанию. Каждый следующий повтор ждет вдвое дольше
+pub const DEFAULT_BACKOFF: Duration = Duration::from_millis(500);
+
+/// Заголовок с ключом идемпотентности пакета
+pub const IDEMPOTENCY_HEADER: &str = "Idempotency-Key";
+
+/// Отправка транзакций пакетами POST-запросами на HTTP-адрес. Тело запроса —
+/// json-массив объектов [Transaction::to_json].
+///
+/// Каждый пакет отправляется с заголовком [IDEMPOTENCY_HEADER], который вычисляется
+/// по телу запроса: повтор запроса и повторная отправка того же файла дают тот же ключ.
+/// Запрос повторяется с нарастающей паузой при сетевых ошибках и ответах 429 и 5xx,
+/// остальные ответы с ошибкой прерывают отправку
+pub struct HttpUploader {
+    agent: ureq::Agent,
+    endpoint: String,
+    headers: Vec<(String, String)>,
+    batch_size: usize,
+    retries: u32,
+    backoff: Duration,
+    batch: Vec<String>,
+    uploaded: u64,
+}
+
+impl HttpUploader {
+    /// Отправка на адрес endpoint
+    pub fn new(endpoint: &str) -> Self {
+        Self {
+            agent: ureq::Agent::new(),
+            endpoint: endpoint.to_owned(),
+            headers: Vec::new(),
+            batch_size: DEFAULT_BATCH_SIZE,
+            retries: DEFAULT_RETRIES,
+            backoff: DEFAULT_BACKOFF,
+            batch: Vec::new(),
+            uploaded: 0,
+        }
+    }
+
+    /// Количество транзакций в одном запросе
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Количество повторов запроса после первой неудачной попытки
+    pub fn with_retries(mut self, retries: u32) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    /// Пауза перед первым повтором
+    pub fn with_backoff(mut self, backoff: Duration) -> Self {
+        self.backoff = backoff;
+        self
+    }
+
+    /// Дополнительный заголовок запроса, например `Authorization`
+    pub fn with_header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_owned(), value.to_owned()));
+        self
+    }
+
+    /// Количество транзакций, принятых сервером
+    pub fn uploaded(&self) -> u64 {
+        self.uploaded
+    }
+
+    /// Отправка пакета с повторами
+    fn send(&self, body: &str) -> Result<(), ParsError> {
+        let key = idempotency_key(body);
+        let mut attempt = 0;
+        loop {
+            let mut request = self
+                .agent
+                .post(&self.endpoint)
+                .set("Content-Type", "application/json")
+                .set(IDEMPOTENCY_HEADER, &key);
+            for (name, value) in &self.headers {
+                request = request.set(name, value);
+            }
+            let (retry_after, error) = match request.send_string(body) {
+                Ok(_) => return Ok(()),
+                Err(ureq::Error::Status(code, response)) if code == 429 || code >= 500 => {
+                    let retry_after = response
+                        .header("Retry-After")
+                        .and_then(|val| val.trim().parse().ok())
+                        .map(Duration::from_secs);
+                    (
+                        retry_after,
+                        format!("ответ {code} {}", response.status_text()),
+                    )
+                }
+                Err(ureq::Error::Status(code, response)) => {
+                    return Err(ParsError::WrongFormat(format!(
+                        "Сервер отклонил пакет: ответ {code} {}",
+                        response.status_text()
+                    )));
+                }
+                Err(ureq::Error::Transport(e)) => (None, e.to_string()),
+            };
+            if attempt >= self.retries {
+                return Err(ParsError::WrongFormat(format!(
+                    "Пакет не отправлен за {} попыток: {error}",
+                    attempt + 1
+                )));
+            }
+            thread::sleep(retry_after.unwrap_or(self.backoff * 2u32.saturating_pow(attempt)));
+            attempt += 1;
+        }
+    }
+}
+
+impl TransactionWrite for HttpUploader {
+    fn write_transaction(&mut self, tx: &Transaction) -> Result<(), ParsError> {
+        self.batch.push(tx.to_json());
+        if self.batch.len() >= self.batch_size {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Отправка накопленного неполного пакета
+    fn flush(&mut self) -> Result<(), ParsError> {
+        if self.batch.is_empty() {
+            return Ok(());
+        }
+        let body = format!("[{}]", self.batch.join(","));
+        self.send(&body)?;
+        self.uploaded += self.batch.len() as u64;
+        self.batch.clear();
+        Ok(())
+    }
+}
+
+/// Ключ идемпотентности: хеш FNV-1a тела запроса. В отличие от [std::hash::DefaultHasher]
+/// не зависит от версии компилятора, поэтому ключ повторной отправки не меняется
+fn idempotency_key(body: &str) -> String {
+    let hash = body.bytes().fold(0xcbf29ce484222325u64, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    });
+    format!("{hash:016x}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+
+    fn tx(tx_id: u64) -> Transaction {
+        Transaction {
+            to_user_id: 2,
+            description: "Record \"quoted\"".to_owned(),
+            ..test_util::tx(tx_id)
+        }
+    }
+
+    /// Сервер, отвечающий по очереди кодами codes. Возвращает ключ идемпотентности
+    /// и тело каждого запроса
+    fn serve(listener: TcpListener, codes: Vec<u16>) -> Vec<(String, String)> {
+        let mut requests = Vec::new();
+        for code in codes {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+            let (mut key, mut len) = (String::new(), 0);
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                let line = line.trim_end();
+                if line.is_empty() {
+                    break;
+                }
+                if let Some((name, value)) = line.split_once(": ") {
+                    match name.to_ascii_lowercase().as_str() {
+                        "idempotency-key" => key = value.to_owned(),
+                        "content-length" => len = value.parse().unwrap(),
+                        _ => {}
+                    }
+                }
+            }
+            let mut body = vec![0; len];
+            reader.read_exact(&mut body).unwrap();
+            requests.push((key, String::from_utf8(body).unwrap()));
+            write!(
+                reader.get_mut(),
+                "HTTP/1.1 {code} X\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+            )
+            .unwrap();
+        }
+        requests
+    }
+
+    #[test]
+    fn test_upload() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let endpoint = format!("http://{}/ingest", listener.local_addr().unwrap());
+        let server = thread::spawn(move || serve(listener, vec![200, 503, 200, 400]));
+
+        let mut uploader = HttpUploader::new(&endpoint)
+            .with_batch_size(2)
+            .with_backoff(Duration::from_millis(1));
+        for tx_id in 1..=4 {
+            uploader.write_transaction(&tx(tx_id)).unwrap();
+        }
+        assert_eq!(uploader.uploaded(), 4);
+        uploader.write_transaction(&tx(5)).unwrap();
+        assert!(uploader.finish().is_err());
+        assert_eq!(uploader.uploaded(), 4);
+
+        let requests = server.join().unwrap();
+        assert_eq!(
+            requests[0].1,
+            "[{\"tx_id\":1,\"tx_type\":\"DEPOSIT\",\"from_user_id\":0,\"to_user_id\":2,\
+            \"amount\":100,\"timestamp\":1633036860000,\"status\":\"SUCCESS\",\
+            \"description\":\"Record \\\"quoted\\\"\"},{\"tx_id\":2,\"tx_type\":\"DEPOSIT\",\
+            \"from_user_id\":0,\"to_user_id\":2,\"amount\":100,\"timestamp\":1633036860000,\
+            \"status\":\"SUCCESS\",\"description\":\"Record \\\"quoted\\\"\"}]"
+        );
+        // Повтор после 503 отправляется с тем же ключом
+        assert_eq!(requests[1], requests[2]);
+        assert_ne!(requests[0].0, requests[1].0);
+        assert_eq!(requests[0].0, idempotency_key(&requests[0].1));
+    }
+}
@@ -0,0 +1,650 @@
+use super::compression::Compression;
+use super::error::ParsError;
+use super::format::Format;
+use super::options::TimeRange;
+use super::transaction::Transaction;
+use super::tx_format::TxReader;
+use chrono::{DateTime, Utc};
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Cursor, Read, Seek, SeekFrom, Write};
+use std::ops::{Bound, RangeBounds};
+use std::path::{Path, PathBuf};
+use std::vec;
+
+/// Сигнатура файла индекса
+const INDEX_MAGIC: [u8; 4] = *b"YPBI";
+
+/// Версия формата файла индекса
+const INDEX_VERSION: u32 = 2;
+
+/// Расширение файла индекса, добавляемое к имени файла данных
+pub const INDEX_EXTENSION: &str = "idx";
+
+/// Сигнатура файла индекса времени
+const TIME_INDEX_MAGIC: [u8; 4] = *b"YPBT";
+
+/// Версия формата файла индекса времени
+const TIME_INDEX_VERSION: u32 = 1;
+
+/// Расширение файла индекса времени, добавляемое к имени файла данных
+pub const TIME_INDEX_EXTENSION: &str = "tidx";
+
+/// Количество записей в блоке индекса времени по умолчанию
+pub const DEFAULT_TIME_BLOCK_LEN: usize = 1024;
+
+/// Время изменения файла индекса не раньше времени изменения файла данных
+pub(crate) fn is_fresh(path: &Path, sidecar: &Path) -> bool {
+    let modified = |path: &Path| fs::metadata(path).and_then(|meta| meta.modified());
+    matches!((modified(path), modified(sidecar)), (Ok(data), Ok(index)) if index >= data)
+}
+
+/// Код формата файла данных в файле индекса
+fn format_code(fin_format: Format) -> Result<u8, ParsError> {
+    match fin_format {
+        Format::Bin => Ok(0),
+        Format::Csv => Ok(1),
+        Format::Text => Ok(2),
+        Format::Custom(name) => Err(ParsError::WrongFormat(format!(
+            "Индекс не поддерживается для формата {name}"
+        ))),
+    }
+}
+
+/// Индекс смещений записей в файлах bin, csv и text: tx_id -> смещение начала записи
+/// в байтах. Позволяет прочитать транзакцию по tx_id без просмотра файла целиком.
+///
+/// Индекс хранится рядом с файлом данных в файле `<имя>.idx`
+/// ([OffsetIndex::sidecar_path]): сигнатура `YPBI`, версия (u32), формат файла данных
+/// (u8: 0 — bin, 1 — csv, 2 — text), смещение первой записи (u64), количество
+/// записей (u64) и пары tx_id, смещение (u64), отсортированные по tx_id.
+/// Байты до первой записи (заголовок csv или схема bin версии 2) при чтении
+/// по смещению читаются перед записью. Все числа в порядке big-endian,
+/// как в самом формате bin. Индексы версии 1 (только bin, без формата
+/// и смещения первой записи) по-прежнему читаются
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct OffsetIndex {
+    fin_format: Format,
+    data_start: u64,
+    entries: Vec<(u64, u64)>,
+}
+
+impl Default for OffsetIndex {
+    fn default() -> Self {
+        Self {
+            fin_format: Format::Bin,
+            data_start: 0,
+            entries: Vec::new(),
+        }
+    }
+}
+
+impl OffsetIndex {
+    /// Построение индекса чтением несжатого потока bin с начала
+    pub fn build<In: Read + Send + 'static>(stream: In) -> Result<Self, ParsError> {
+        Self::build_format(stream, Format::Bin)
+    }
+
+    /// Построение индекса чтением несжатого потока в формате bin, csv или text с начала
+    pub fn build_format<In: Read + Send + 'static>(
+        stream: In,
+        fin_format: Format,
+    ) -> Result<Self, ParsError> {
+        format_code(fin_format)?;
+        let mut reader = TxReader::new(stream, fin_format)?;
+        let mut entries = Vec::new();
+        while let Some(tx) = reader.read_transaction_ref()? {
+            let tx_id = tx.tx_id;
+            entries.push((tx_id, reader.last_record_start()));
+        }
+        let data_start = entries.first().map_or(0, |(_, offset)| *offset);
+        // Устойчивая сортировка: для повторяющихся tx_id первой остается более ранняя запись
+        entries.sort_by_key(|(tx_id, _)| *tx_id);
+        Ok(Self {
+            fin_format,
+            data_start,
+            entries,
+        })
+    }
+
+    /// Построение индекса файла. Формат определяется по расширению, для файлов
+    /// с неизвестным расширением используется bin. Сжатые файлы не поддерживаются
+    pub fn build_file<P: AsRef<Path>>(path: P) -> Result<Self, ParsError> {
+        let path = path.as_ref();
+        let fin_format = match Format::from_path(path) {
+            Ok((_, compression)) if compression != Compression::None => {
+                return Err(ParsError::WrongFormat(format!(
+                    "Индекс не поддерживается для сжатых файлов: {}",
+                    path.display()
+                )));
+            }
+            Ok((fin_format, _)) => fin_format,
+            Err(_) => Format::Bin,
+        };
+        Self::build_format(BufReader::new(File::open(path)?), fin_format)
+    }
+
+    /// Индекс файла bin из файла индекса, если он есть и не старше файла данных,
+    /// иначе построенный заново. Новый индекс не сохраняется, для этого есть [OffsetIndex::save]
+    pub fn load_or_build<P: AsRef<Path>>(path: P) -> Result<Self, ParsError> {
+        let path = path.as_ref();
+        let sidecar = Self::sidecar_path(path);
+        match is_fresh(path, &sidecar) {
+            true => Self::load(&sidecar),
+            false => Self::build_file(path),
+        }
+    }
+
+    /// Путь файла индекса для файла данных: `transactions.bin` -> `transactions.bin.idx`
+    pub fn sidecar_path<P: AsRef<Path>>(path: P) -> PathBuf {
+        let mut name = path.as_ref().as_os_str().to_owned();
+        name.push(".");
+        name.push(INDEX_EXTENSION);
+        PathBuf::from(name)
+    }
+
+    /// Чтение индекса из файла индекса
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, ParsError> {
+        Self::read_from(BufReader::new(File::open(path)?))
+    }
+
+    /// Сохранение индекса в файл индекса
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), ParsError> {
+        let mut out = BufWriter::new(File::create(path)?);
+        self.write_to(&mut out)?;
+        out.flush()?;
+        Ok(())
+    }
+
+    /// Чтение индекса из потока
+    pub fn read_from<In: Read>(mut stream: In) -> Result<Self, ParsError> {
+        let mut header = [0; 8];
+        stream.read_exact(&mut header)?;
+        if header[..4] != INDEX_MAGIC {
+            return Err(ParsError::WrongFormat(
+                "Файл не является индексом".to_owned(),
+            ));
+        }
+        let version = u32::from_be_bytes(header[4..].try_into().unwrap());
+        let (fin_format, data_start) = match version {
+            1 => (Format::Bin, 0),
+            INDEX_VERSION => {
+                let mut buf = [0; 9];
+                stream.read_exact(&mut buf)?;
+                let fin_format = match buf[0] {
+                    0 => Format::Bin,
+                    1 => Format::Csv,
+                    2 => Format::Text,
+                    code => {
+                        return Err(ParsError::WrongFormat(format!(
+                            "Неизвестный формат в индексе: {code}"
+                        )));
+                    }
+                };
+                (fin_format, u64::from_be_bytes(buf[1..].try_into().unwrap()))
+            }
+            _ => {
+                return Err(ParsError::WrongFormat(format!(
+                    "Неподдерживаемая версия индекса: {version}"
+                )));
+            }
+        };
+        let mut buf = [0; 8];
+        stream.read_exact(&mut buf)?;
+        let count = u64::from_be_bytes(buf);
+        let mut entries = Vec::new();
+        let mut buf = [0; 16];
+        for _ in 0..count {
+            stream.read_exact(&mut buf)?;
+            entries.push((
+                u64::from_be_bytes(buf[..8].try_into().unwrap()),
+                u64::from_be_bytes(buf[8..].try_into().unwrap()),
+            ));
+        }
+        if !entries.is_sorted_by_key(|(tx_id, _)| *tx_id) {
+            return Err(ParsError::WrongFormat(
+                "Записи индекса не отсортированы по tx_id".to_owned(),
+            ));
+        }
+        Ok(Self {
+            fin_format,
+            data_start,
+            entries,
+        })
+    }
+
+    /// Запись индекса в поток
+    pub fn write_to<Out: Write>(&self, mut stream: Out) -> Result<(), ParsError> {
+        stream.write_all(&INDEX_MAGIC)?;
+        stream.write_all(&INDEX_VERSION.to_be_bytes())?;
+        stream.write_all(&[format_code(self.fin_format)?])?;
+        stream.write_all(&self.data_start.to_be_bytes())?;
+        stream.write_all(&(self.entries.len() as u64).to_be_bytes())?;
+        for (tx_id, offset) in &self.entries {
+            stream.write_all(&tx_id.to_be_bytes())?;
+            stream.write_all(&offset.to_be_bytes())?;
+        }
+        Ok(())
+    }
+
+    /// Смещение записи с заданным tx_id. Для повторяющихся tx_id — смещение первой записи
+    pub fn offset(&self, tx_id: u64) -> Option<u64> {
+        let pos = self.entries.partition_point(|(id, _)| *id < tx_id);
+        self.entries
+            .get(pos)
+            .filter(|(id, _)| *id == tx_id)
+            .map(|(_, offset)| *offset)
+    }
+
+    /// Формат файла данных
+    pub fn format(&self) -> Format {
+        self.fin_format
+    }
+
+    /// Количество записей в индексе
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Пуст ли индекс
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Чтение транзакции с заданным tx_id из потока, по которому построен индекс.
+    /// None, если транзакции нет в индексе
+    pub fn read<In: Read + Seek + Send + 'static>(
+        &self,
+        mut stream: In,
+        tx_id: u64,
+    ) -> Result<Option<Transaction>, ParsError> {
+        let Some(offset) = self.offset(tx_id) else {
+            return Ok(None);
+        };
+        // data_start прочитан из файла индекса: буфер растет по мере чтения, а не заранее
+        let mut prefix = Vec::new();
+        stream.seek(SeekFrom::Start(0))?;
+        (&mut stream)
+            .take(self.data_start)
+            .read_to_end(&mut prefix)?;
+        if (prefix.len() as u64) < self.data_start {
+            return Err(ParsError::WrongFormat(format!(
+                "Индекс не соответствует файлу: начало данных {} за концом файла",
+                self.data_start
+            )));
+        }
+        stream.seek(SeekFrom::Start(offset))?;
+        let tx = TxReader::new(Cursor::new(prefix).chain(stream), self.fin_format)?
+            .read_transaction()?
+            .filter(|tx| tx.tx_id == tx_id)
+            .ok_or_else(|| {
+                ParsError::WrongFormat(format!(
+                    "Индекс не соответствует файлу: по смещению {offset} нет транзакции {tx_id}"
+                ))
+            })?;
+        Ok(Some(tx))
+    }
+}
+
+/// Блок подряд идущих записей файла bin в индексе времени
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub struct TimeBlock {
+    /// Смещение начала первой записи блока
+    pub start: u64,
+    /// Смещение конца последней записи блока
+    pub end: u64,
+    /// Наименьшее время транзакции в блоке
+    pub min: DateTime<Utc>,
+    /// Наибольшее время транзакции в блоке
+    pub max: DateTime<Utc>,
+}
+
+impl TimeBlock {
+    fn intersects(&self, range: &TimeRange) -> bool {
+        let after_start = match range.0 {
+            Bound::Included(start) => self.max >= start,
+            Bound::Excluded(start) => self.max > start,
+            Bound::Unbounded => true,
+        };
+        let before_end = match range.1 {
+            Bound::Included(end) => self.min <= end,
+            Bound::Excluded(end) => self.min < end,
+            Bound::Unbounded => true,
+        };
+        after_start && before_end
+    }
+}
+
+/// Разреженный индекс времени файла bin: для каждого блока из подряд идущих записей
+/// хранятся его границы в байтах и наименьшее и наибольшее время транзакций.
+/// Порядок записей по времени не требуется, но чем он ближе к упорядоченному,
+/// тем меньше блоков попадает в интервал. Читатель с отбором по времени
+/// ([crate::builder::TxReaderBuilder::time_range]) читает только такие блоки.
+///
+/// Индекс хранится рядом с файлом данных в файле `<имя>.tidx`
+/// ([TimeIndex::sidecar_path]): сигнатура `YPBT`, версия (u32), длина заголовка
+/// схемы файла данных (u64), количество блоков (u64) и для каждого блока
+/// начало, конец (u64), наименьшее и наибольшее время в микросекундах (i64).
+/// Все числа в порядке big-endian
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+pub struct TimeIndex {
+    header_len: u64,
+    blocks: Vec<TimeBlock>,
+}
+
+impl TimeIndex {
+    /// Построение индекса чтением несжатого потока bin с начала,
+    /// по block_len записей в блоке
+    pub fn build<In: Read + Send + 'static>(
+        stream: In,
+        block_len: usize,
+    ) -> Result<Self, ParsError> {
+        let block_len = block_len.max(1);
+        let mut reader = TxReader::new(stream, Format::Bin)?;
+        let header_len = reader.bin_header_len()?;
+        let mut blocks: Vec<TimeBlock> = Vec::new();
+        let mut start = header_len;
+        let mut count = 0;
+        while let Some(tx) = reader.read_transaction_ref()? {
+            let timestamp = tx.timestamp;
+            let end = reader.position().bytes;
+            match blocks.last_mut() {
+                Some(block) if count < block_len => {
+                    block.end = end;
+                    block.min = block.min.min(timestamp);
+                    block.max = block.max.max(timestamp);
+                }
+                _ => {
+                    blocks.push(TimeBlock {
+                        start,
+                        end,
+                        min: timestamp,
+                        max: timestamp,
+                    });
+                    count = 0;
+                }
+            }
+            count += 1;
+            start = end;
+        }
+        Ok(Self { header_len, blocks })
+    }
+
+    /// Построение индекса файла bin
+    pub fn build_file<P: AsRef<Path>>(path: P, block_len: usize) -> Result<Self, ParsError> {
+        Self::build(BufReader::new(File::open(path)?), block_len)
+    }
+
+    /// Путь файла индекса времени для файла данных: `transactions.bin` -> `transactions.bin.tidx`
+    pub fn sidecar_path<P: AsRef<Path>>(path: P) -> PathBuf {
+        let mut name = path.as_ref().as_os_str().to_owned();
+        name.push(".");
+        name.push(TIME_INDEX_EXTENSION);
+        PathBuf::from(name)
+    }
+
+    /// Индекс файла bin из файла индекса времени, если он есть и не старше файла данных
+    pub fn load_fresh<P: AsRef<Path>>(path: P) -> Result<Option<Self>, ParsError> {
+        let path = path.as_ref();
+        let sidecar = Self::sidecar_path(path);
+        match is_fresh(path, &sidecar) {
+            true => Self::load(&sidecar).map(Some),
+            false => Ok(None),
+        }
+    }
+
+    /// Чтение индекса из файла индекса времени
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, ParsError> {
+        Self::read_from(BufReader::new(File::open(path)?))
+    }
+
+    /// Сохранение индекса в файл индекса времени
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), ParsError> {
+        let mut out = BufWriter::new(File::create(path)?);
+        self.write_to(&mut out)?;
+        out.flush()?;
+        Ok(())
+    }
+
+    /// Чтение индекса из потока
+    pub fn read_from<In: Read>(mut stream: In) -> Result<Self, ParsError> {
+        let mut header = [0; 24];
+        stream.read_exact(&mut header)?;
+        if header[..4] != TIME_INDEX_MAGIC {
+            return Err(ParsError::WrongFormat(
+                "Файл не является индексом времени".to_owned(),
+            ));
+        }
+        let version = u32::from_be_bytes(header[4..8].try_into().unwrap());
+        if version != TIME_INDEX_VERSION {
+            return Err(ParsError::WrongFormat(format!(
+                "Неподдерживаемая версия индекса времени: {version}"
+            )));
+        }
+        let header_len = u64::from_be_bytes(header[8..16].try_into().unwrap());
+        let count = u64::from_be_bytes(header[16..].try_into().unwrap());
+        let time = |buf: &[u8]| {
+            let micros = i64::from_be_bytes(buf.try_into().unwrap());
+            DateTime::from_timestamp_micros(micros).ok_or_else(|| {
+                ParsError::WrongFormat(format!("Неверное время в индексе: {micros}"))
+            })
+        };
+        let mut blocks = Vec::new();
+        let mut buf = [0; 32];
+        for _ in 0..count {
+            stream.read_exact(&mut buf)?;
+            blocks.push(TimeBlock {
+                start: u64::from_be_bytes(buf[..8].try_into().unwrap()),
+                end: u64::from_be_bytes(buf[8..16].try_into().unwrap()),
+                min: time(&buf[16..24])?,
+                max: time(&buf[24..])?,
+            });
+        }
+        Ok(Self { header_len, blocks })
+    }
+
+    /// Запись индекса в поток
+    pub fn write_to<Out: Write>(&self, mut stream: Out) -> Result<(), ParsError> {
+        stream.write_all(&TIME_INDEX_MAGIC)?;
+        stream.write_all(&TIME_INDEX_VERSION.to_be_bytes())?;
+        stream.write_all(&self.header_len.to_be_bytes())?;
+        stream.write_all(&(self.blocks.len() as u64).to_be_bytes())?;
+        for block in &self.blocks {
+            stream.write_all(&block.start.to_be_bytes())?;
+            stream.write_all(&block.end.to_be_bytes())?;
+            stream.write_all(&block.min.timestamp_micros().to_be_bytes())?;
+            stream.write_all(&block.max.timestamp_micros().to_be_bytes())?;
+        }
+        Ok(())
+    }
+
+    /// Блоки индекса в порядке файла
+    pub fn blocks(&self) -> &[TimeBlock] {
+        &self.blocks
+    }
+
+    /// Участки файла в байтах, которые нужно прочитать для транзакций из интервала:
+    /// заголовок схемы и блоки, пересекающиеся с интервалом. Соседние участки объединяются
+    pub fn byte_ranges<R: RangeBounds<DateTime<Utc>>>(&self, range: R) -> Vec<(u64, u64)> {
+        let range = (range.start_bound().cloned(), range.end_bound().cloned());
+        let mut res: Vec<(u64, u64)> = Vec::new();
+        if self.header_len > 0 {
+            res.push((0, self.header_len));
+        }
+        for block in self.blocks.iter().filter(|block| block.intersects(&range)) {
+            match res.last_mut() {
+                Some(last) if last.1 == block.start => last.1 = block.end,
+                _ => res.push((block.start, block.end)),
+            }
+        }
+        res
+    }
+}
+
+/// Поток, составленный из участков другого потока: между участками выполняется
+/// переход к началу следующего участка
+pub(crate) struct RangeReader<In> {
+    stream: In,
+    ranges: vec::IntoIter<(u64, u64)>,
+    left: u64,
+}
+
+impl<In: Read + Seek> RangeReader<In> {
+    pub(crate) fn new(stream: In, ranges: Vec<(u64, u64)>) -> Self {
+        Self {
+            stream,
+            ranges: ranges.into_iter(),
+            left: 0,
+        }
+    }
+}
+
+impl<In: Read + Seek> Read for RangeReader<In> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.left == 0 {
+            let Some((start, end)) = self.ranges.next() else {
+                return Ok(0);
+            };
+            self.stream.seek(SeekFrom::Start(start))?;
+            self.left = end.saturating_sub(start);
+        }
+        let max = buf
+            .len()
+            .min(usize::try_from(self.left).unwrap_or(usize::MAX));
+        let n = self.stream.read(&mut buf[..max])?;
+        self.left -= n as u64;
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::{TxReaderBuilder, TxWriterBuilder};
+    use crate::options::BinVersion;
+    use crate::test_util::{encode, tx};
+    use crate::tx_format::TxWriter;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_offset_index() {
+        let mut writer = TxWriter::new(Vec::new(), Format::Bin).unwrap();
+        for tx_id in [30, 10, 20, 10] {
+            writer.write_transaction(&tx(tx_id)).unwrap();
+        }
+        let data = writer.into_inner().unwrap();
+
+        let index = OffsetIndex::build(Cursor::new(data.clone())).unwrap();
+        assert_eq!(index.len(), 4);
+        assert_eq!(index.offset(30), Some(0));
+        assert_eq!(index.offset(15), None);
+        assert!(index.offset(10) < index.offset(20));
+        for tx_id in [10, 20, 30] {
+            let res = index.read(Cursor::new(data.clone()), tx_id).unwrap();
+            assert_eq!(res, Some(tx(tx_id)));
+        }
+        assert_eq!(index.read(Cursor::new(data.clone()), 40).unwrap(), None);
+
+        let mut buf = Vec::new();
+        index.write_to(&mut buf).unwrap();
+        assert_eq!(OffsetIndex::read_from(buf.as_slice()).unwrap(), index);
+        assert!(OffsetIndex::read_from(&buf[1..]).is_err());
+    }
+
+    #[test]
+    fn test_offset_index_formats() {
+        let v2 = TxWriterBuilder::new()
+            .bin_version(BinVersion::V2)
+            .options()
+            .clone();
+        let cases = [
+            (Format::Csv, TxWriterBuilder::new().options().clone()),
+            (Format::Text, TxWriterBuilder::new().options().clone()),
+            (Format::Bin, v2),
+        ];
+        for (fin_format, options) in cases {
+            let mut writer = TxWriter::with_options(Vec::new(), fin_format, options).unwrap();
+            for tx_id in [30, 10, 20] {
+                writer.write_transaction(&tx(tx_id)).unwrap();
+            }
+            let data = writer.into_inner().unwrap();
+
+            let index = OffsetIndex::build_format(Cursor::new(data.clone()), fin_format).unwrap();
+            assert_eq!(index.format(), fin_format);
+            for tx_id in [10, 20, 30] {
+                let res = index.read(Cursor::new(data.clone()), tx_id).unwrap();
+                assert_eq!(res, Some(tx(tx_id)), "{fin_format}");
+            }
+
+            let mut buf = Vec::new();
+            index.write_to(&mut buf).unwrap();
+            assert_eq!(OffsetIndex::read_from(buf.as_slice()).unwrap(), index);
+        }
+    }
+
+    #[test]
+    fn test_offset_index_data_start() {
+        let data = encode(&[tx(10)], Format::Csv);
+        let index = OffsetIndex::build_format(Cursor::new(data.clone()), Format::Csv).unwrap();
+        // Начало данных из поврежденного индекса не выделяется заранее
+        let index = OffsetIndex {
+            data_start: u64::MAX,
+            ..index
+        };
+        let err = index.read(Cursor::new(data), 10).unwrap_err();
+        assert!(err.to_string().contains("за концом файла"), "{err}");
+    }
+
+    #[test]
+    fn test_time_index() {
+        let options = TxWriterBuilder::new()
+            .bin_version(BinVersion::V2)
+            .merkle(true)
+            .options()
+            .clone();
+        let mut writer = TxWriter::with_options(Vec::new(), Format::Bin, options).unwrap();
+        for tx_id in 0..100 {
+            let mut tx = tx(tx_id);
+            tx.timestamp = DateTime::from_timestamp(1633036800 + tx_id as i64 * 3600, 0).unwrap();
+            writer.write_transaction(&tx).unwrap();
+        }
+        let data = writer.into_inner().unwrap();
+        let time = |tx_id: i64| DateTime::from_timestamp(1633036800 + tx_id * 3600, 0).unwrap();
+
+        let index = TimeIndex::build(Cursor::new(data.clone()), 10).unwrap();
+        assert_eq!(index.blocks().len(), 10);
+        assert_eq!(index.blocks()[2].min, time(20));
+        let ranges = index.byte_ranges(time(20)..time(35));
+        assert_eq!(ranges.len(), 2);
+        assert_eq!(ranges[1], (index.blocks()[2].start, index.blocks()[3].end));
+
+        let mut buf = Vec::new();
+        index.write_to(&mut buf).unwrap();
+        assert_eq!(TimeIndex::read_from(buf.as_slice()).unwrap(), index);
+
+        let path = std::env::temp_dir().join(format!("fin_parser_tidx_{}.bin", std::process::id()));
+        fs::write(&path, &data).unwrap();
+        let select = |builder: TxReaderBuilder| {
+            let mut reader = builder.time_range(time(20)..time(35)).open(&path).unwrap();
+            let mut ids = Vec::new();
+            while let Some(tx) = reader.read_transaction().unwrap() {
+                ids.push(tx.tx_id);
+            }
+            (ids, reader.position().bytes)
+        };
+
+        // Без индекса записи вне интервала пропускаются при чтении всего файла
+        let (ids, bytes) = select(TxReaderBuilder::new());
+        assert_eq!(ids, (20..35).collect::<Vec<_>>());
+        assert_eq!(bytes, data.len() as u64);
+
+        index.save(TimeIndex::sidecar_path(&path)).unwrap();
+        let (ids, bytes) = select(TxReaderBuilder::new());
+        fs::remove_file(TimeIndex::sidecar_path(&path)).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(ids, (20..35).collect::<Vec<_>>());
+        assert_eq!(
+            bytes,
+            ranges.iter().map(|(start, end)| end - start).sum::<u64>()
+        );
+    }
+}
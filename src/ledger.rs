@@ -0,0 +1,162 @@
+use super::error::ParsError;
+use super::transaction::{Transaction, TransactionRef, TxStatus, TxType};
+use super::tx_format::TxReader;
+use chrono::{DateTime, Utc};
+use std::collections::BTreeMap;
+use std::io::Read;
+
+/// Балансы пользователей на некоторый момент времени
+pub type Balances = BTreeMap<u64, i128>;
+
+/// Книга балансов пользователей, собираемая из потока транзакций.
+/// Учитываются только успешные транзакции: зачисление увеличивает баланс получателя,
+/// трата уменьшает баланс инициатора, передача переводит сумму от инициатора получателю.
+/// Транзакции могут идти в любом порядке времени: для снимков на момент времени
+/// хранятся изменения балансов, сгруппированные по метке времени
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+pub struct BalanceBook {
+    balances: Balances,
+    history: BTreeMap<u64, BTreeMap<DateTime<Utc>, i128>>,
+    applied: u64,
+    skipped: u64,
+}
+
+impl BalanceBook {
+    /// Пустая книга
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Построение книги по оставшимся в потоке транзакциям без выделения памяти под описания
+    pub fn from_reader<In: Read>(reader: &mut TxReader<In>) -> Result<Self, ParsError> {
+        let mut book = Self::new();
+        while let Some(tx) = reader.read_transaction_ref()? {
+            book.apply(&tx);
+        }
+        Ok(book)
+    }
+
+    /// Учет транзакции. Возвращает false, если транзакция не изменила балансы
+    /// из-за статуса
+    pub fn apply(&mut self, tx: &TransactionRef<'_>) -> bool {
+        if tx.status != TxStatus::Success {
+            self.skipped += 1;
+            return false;
+        }
+        let amount = tx.amount as i128;
+        match tx.tx_type {
+            TxType::Deposit => self.change(tx.to_user_id, tx.timestamp, amount),
+            TxType::Withdrawal => self.change(tx.from_user_id, tx.timestamp, -amount),
+            TxType::Transfer => {
+                self.change(tx.from_user_id, tx.timestamp, -amount);
+                self.change(tx.to_user_id, tx.timestamp, amount);
+            }
+        }
+        self.applied += 1;
+        true
+    }
+
+    /// Учет транзакции, владеющей описанием
+    pub fn apply_tx(&mut self, tx: &Transaction) -> bool {
+        self.apply(&TransactionRef::from(tx))
+    }
+
+    fn change(&mut self, user_id: u64, timestamp: DateTime<Utc>, delta: i128) {
+        *self.balances.entry(user_id).or_default() += delta;
+        *self
+            .history
+            .entry(user_id)
+            .or_default()
+            .entry(timestamp)
+            .or_default() += delta;
+    }
+
+    /// Текущий баланс пользователя. Для неизвестного пользователя 0
+    pub fn balance(&self, user_id: u64) -> i128 {
+        self.balances.get(&user_id).copied().unwrap_or_default()
+    }
+
+    /// Текущие балансы всех пользователей, участвовавших в учтенных транзакциях
+    pub fn balances(&self) -> &Balances {
+        &self.balances
+    }
+
+    /// Балансы с учетом только транзакций со временем не позже time.
+    /// Пользователи без таких транзакций в снимок не попадают
+    pub fn snapshot_at(&self, time: DateTime<Utc>) -> Balances {
+        self.history
+            .iter()
+            .filter_map(|(user_id, changes)| {
+                let mut changes = changes.range(..=time).peekable();
+                changes.peek()?;
+                Some((*user_id, changes.map(|(_, delta)| delta).sum()))
+            })
+            .collect()
+    }
+
+    /// Количество учтенных транзакций
+    pub fn applied(&self) -> u64 {
+        self.applied
+    }
+
+    /// Количество транзакций, пропущенных из-за статуса
+    pub fn skipped(&self) -> u64 {
+        self.skipped
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::Format;
+    use crate::test_util::{reader, tx};
+
+    fn txs_for_test() -> Vec<Transaction> {
+        [
+            (TxType::Deposit, 0, 1, 1000, 10, TxStatus::Success),
+            (TxType::Transfer, 1, 2, 300, 30, TxStatus::Success),
+            (TxType::Withdrawal, 2, 0, 100, 40, TxStatus::Success),
+            (TxType::Transfer, 1, 2, 500, 50, TxStatus::Failure),
+            (TxType::Deposit, 0, 2, 50, 20, TxStatus::Pending),
+            (TxType::Deposit, 0, 1, 10, 20, TxStatus::Success),
+        ]
+        .into_iter()
+        .map(|(tx_type, from, to, amount, secs, status)| Transaction {
+            tx_type,
+            from_user_id: from,
+            to_user_id: to,
+            amount,
+            timestamp: DateTime::from_timestamp(secs, 0).unwrap(),
+            status,
+            ..tx(secs as u64)
+        })
+        .collect()
+    }
+
+    #[test]
+    fn test_balances() {
+        let mut reader = reader(&txs_for_test(), Format::Csv);
+        let book = BalanceBook::from_reader(&mut reader).unwrap();
+
+        assert_eq!(book.balance(1), 710);
+        assert_eq!(book.balance(2), 200);
+        assert_eq!(book.balance(0), 0);
+        assert_eq!(book.balances().len(), 2);
+        assert_eq!(book.applied(), 4);
+        assert_eq!(book.skipped(), 2);
+    }
+
+    #[test]
+    fn test_snapshot() {
+        let mut book = BalanceBook::new();
+        for tx in txs_for_test() {
+            book.apply_tx(&tx);
+        }
+        let at = |secs| book.snapshot_at(DateTime::from_timestamp(secs, 0).unwrap());
+        assert!(at(5).is_empty());
+        assert_eq!(at(10), Balances::from([(1, 1000)]));
+        assert_eq!(at(29), Balances::from([(1, 1010)]));
+        assert_eq!(at(30), Balances::from([(1, 710), (2, 300)]));
+        assert_eq!(at(100), *book.balances());
+    }
+}
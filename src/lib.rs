@@ -6,14 +6,119 @@
 //! Библиотека для чтения и записи транзакций в форматах bin, csv, text.
 
 #![warn(missing_docs)]
+/// Публикация транзакций в брокер AMQP
+#[cfg(feature = "amqp")]
+pub mod amqp;
+/// Сводная статистика по транзакциям
+pub mod analytics;
+/// Стратегии proptest для транзакций
+#[cfg(feature = "proptest")]
+pub mod arbitrary;
+/// Асинхронное чтение-запись транзакций
+#[cfg(feature = "async")]
+pub mod async_io;
+/// Столбцовое хранение транзакций в памяти
+pub mod batch;
 mod bin_format;
+/// Фильтр Блума по tx_id файлов транзакций
+pub mod bloom;
+/// Построители читателей и писателей с настройками
+pub mod builder;
+/// Последовательное чтение нескольких файлов
+pub mod chain;
+/// Сжатие потоков транзакций
+pub mod compression;
+/// Настройки утилит из файла fin-parser.toml
+pub mod config;
+/// Проверка совместимости форматов на канонических векторах
+pub mod conformance;
 mod constants;
+/// Конвертация транзакций между форматами
+pub mod converter;
+/// Внесение контролируемых повреждений в файлы транзакций
+pub mod corrupt;
 mod csv_format;
+/// Преобразование транзакций в DataFrame Polars и обратно
+#[cfg(feature = "polars")]
+pub mod dataframe;
+/// Вывод записей, пропущенных при чтении
+pub mod dead_letter;
+/// Удаление повторяющихся транзакций
+pub mod dedup;
+/// Разностные файлы снимков транзакций
+pub mod delta;
 /// Ошибки в системе
 pub mod error;
+/// Отбор транзакций по условиям
+pub mod filter;
+/// Отпечатки содержимого транзакций и файлов
+pub mod fingerprint;
+/// Чтение дописываемых файлов
+pub mod follow;
+/// Форматы записи транзакций
+pub mod format;
+/// Генерация синтетических транзакций
+pub mod generate;
+/// Сервис gRPC для чтения и записи файлов транзакций
+#[cfg(feature = "grpc")]
+pub mod grpc;
+/// Отправка транзакций пакетами по HTTP
+#[cfg(feature = "http")]
+pub mod http_upload;
+/// Индексы файлов транзакций: смещения записей и время
+pub mod index;
+/// Балансы пользователей
+pub mod ledger;
+/// Слияние отсортированных потоков
+pub mod merge;
+/// Дерево Меркла и доказательства включения транзакций
+pub mod merkle;
+/// Метрики пропускной способности и ошибок
+pub mod metrics;
+/// Суммы транзакций с проверкой переполнения
+pub mod money;
+/// Настройки чтения и записи
+pub mod options;
+/// Загрузка транзакций в PostgreSQL
+#[cfg(feature = "postgres")]
+pub mod pg_copy;
+/// Цепочки этапов обработки транзакций
+pub mod pipeline;
+/// Сводные таблицы по месяцам, типам и статусам транзакций
+pub mod pivot;
+/// Проекция транзакций на выбранные поля
+pub mod projection;
+mod query;
+/// Сверка потоков транзакций по идентификатору
+pub mod reconcile;
+/// Чтение-запись транзакций в потоки Redis
+#[cfg(feature = "redis")]
+pub mod redis_stream;
+/// Отчет об ошибках чтения
+pub mod report;
+/// Случайные и регулярные выборки транзакций
+pub mod sample;
+/// Сортировка транзакций
+pub mod sort;
+/// Разбиение потока транзакций по разделам
+pub mod split;
+/// Потокобезопасная запись транзакций
+pub mod sync_writer;
+#[cfg(test)]
+pub(crate) mod test_util;
 mod text_format;
 /// Транзакция
 pub mod transaction;
 /// Чтение-запись транзакций
 pub mod tx_format;
 mod utils;
+/// Проверка транзакций набором правил
+pub mod validate;
+/// Предупреждения чтения
+pub mod warning;
+/// Чтение файлов, поступающих в каталог
+pub mod watch;
+/// Группировка транзакций по окнам времени
+pub mod window;
+
+pub use tx_format::{count_records, read_file, write_file};
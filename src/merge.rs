@@ -0,0 +1,232 @@
+use super::error::ParsError;
+use super::format::TransactionRead;
+use super::sort::{Order, SortKey};
+use super::transaction::Transaction;
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::collections::binary_heap::PeekMut;
+
+struct HeapEntry {
+    tx: Transaction,
+    source: usize,
+    key: SortKey,
+    order: Order,
+}
+
+impl HeapEntry {
+    fn compare(&self, other: &Self) -> Ordering {
+        let ord = match self.order {
+            Order::Ascending => self.key.compare(&self.tx, &other.tx),
+            Order::Descending => self.key.compare(&other.tx, &self.tx),
+        };
+        // При равенстве поля первым идет поток с меньшим номером
+        ord.then(self.source.cmp(&other.source))
+    }
+}
+
+impl PartialEq for HeapEntry {
+    fn eq(&self, other: &Self) -> bool {
+        self.compare(other) == Ordering::Equal
+    }
+}
+
+impl Eq for HeapEntry {}
+
+impl PartialOrd for HeapEntry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// BinaryHeap возвращает наибольший элемент, поэтому порядок обратный
+impl Ord for HeapEntry {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.compare(self)
+    }
+}
+
+/// K-путевое слияние потоков, каждый из которых уже отсортирован по одному полю
+/// в одном направлении. Из каждого потока в памяти держится по одной транзакции.
+/// Транзакции с равными значениями поля идут в порядке номеров потоков,
+/// внутри потока порядок сохраняется. Если поток оказался неотсортированным,
+/// возвращается ошибка [ParsError::WrongFormat] с номером потока
+pub struct MergeSorted<R: TransactionRead> {
+    readers: Vec<R>,
+    heap: BinaryHeap<HeapEntry>,
+    key: SortKey,
+    order: Order,
+}
+
+impl<R: TransactionRead> MergeSorted<R> {
+    /// Слияние потоков readers, отсортированных по полю key в направлении order.
+    /// Из каждого потока сразу читается первая транзакция
+    pub fn new(readers: Vec<R>, key: SortKey, order: Order) -> Result<Self, ParsError> {
+        let mut merged = Self {
+            readers,
+            heap: BinaryHeap::new(),
+            key,
+            order,
+        };
+        for source in 0..merged.readers.len() {
+            if let Some(tx) = merged.readers[source].read_transaction()? {
+                merged.push(tx, source);
+            }
+        }
+        Ok(merged)
+    }
+
+    fn push(&mut self, tx: Transaction, source: usize) {
+        self.heap.push(HeapEntry {
+            tx,
+            source,
+            key: self.key,
+            order: self.order,
+        });
+    }
+
+    /// Чтение следующей транзакции общего потока. None означает конец всех потоков.
+    /// Транзакция остается в куче, пока из ее потока не прочитана следующая,
+    /// поэтому после ошибки чтения потока она возвращается повторным вызовом
+    pub fn read_transaction(&mut self) -> Result<Option<Transaction>, ParsError> {
+        let Some(mut top) = self.heap.peek_mut() else {
+            return Ok(None);
+        };
+        let Some(tx) = self.readers[top.source].read_transaction()? else {
+            return Ok(Some(PeekMut::pop(top).tx));
+        };
+        let ord = self.key.compare(&tx, &top.tx);
+        let unsorted = match self.order {
+            Order::Ascending => ord == Ordering::Less,
+            Order::Descending => ord == Ordering::Greater,
+        };
+        if unsorted {
+            return Err(ParsError::WrongFormat(format!(
+                "Поток {} не отсортирован: транзакция {} идет после {}",
+                top.source, tx.tx_id, top.tx.tx_id
+            )));
+        }
+        Ok(Some(std::mem::replace(&mut top.tx, tx)))
+    }
+
+    /// Возврат исходных читателей
+    pub fn into_inner(self) -> Vec<R> {
+        self.readers
+    }
+}
+
+impl<R: TransactionRead> Iterator for MergeSorted<R> {
+    type Item = Result<Transaction, ParsError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_transaction().transpose()
+    }
+}
+
+impl<R: TransactionRead> TransactionRead for MergeSorted<R> {
+    fn read_transaction(&mut self) -> Result<Option<Transaction>, ParsError> {
+        MergeSorted::read_transaction(self)
+    }
+}
+
+/// Слияние потоков, отсортированных по возрастанию поля key, в один упорядоченный поток.
+/// Потоки могут быть в разных форматах, например `Vec<Box<dyn TransactionRead>>`
+pub fn merge_sorted<R: TransactionRead>(
+    readers: Vec<R>,
+    key: SortKey,
+) -> Result<MergeSorted<R>, ParsError> {
+    MergeSorted::new(readers, key, Order::Ascending)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::Format;
+    use crate::test_util;
+    use chrono::DateTime;
+
+    fn tx(tx_id: u64, secs: i64) -> Transaction {
+        Transaction {
+            timestamp: DateTime::from_timestamp(secs, 0).unwrap(),
+            ..test_util::tx(tx_id)
+        }
+    }
+
+    fn reader(txs: &[Transaction], format: Format) -> Box<dyn TransactionRead> {
+        Box::new(test_util::reader(txs, format))
+    }
+
+    #[test]
+    fn test_merge_sorted() {
+        let readers = vec![
+            reader(&[tx(1, 10), tx(2, 30), tx(3, 50)], Format::Bin),
+            reader(&[tx(4, 20), tx(5, 30)], Format::Csv),
+            reader(&[], Format::Text),
+            reader(&[tx(6, 5), tx(7, 30), tx(8, 60)], Format::Text),
+        ];
+        let ids: Vec<u64> = merge_sorted(readers, SortKey::Timestamp)
+            .unwrap()
+            .map(|tx| tx.unwrap().tx_id)
+            .collect();
+        assert_eq!(ids, [6, 1, 4, 2, 5, 7, 3, 8]);
+    }
+
+    #[test]
+    fn test_merge_descending() {
+        let readers = vec![
+            reader(&[tx(9, 0), tx(4, 0)], Format::Bin),
+            reader(&[tx(8, 0), tx(7, 0), tx(1, 0)], Format::Bin),
+        ];
+        let mut merged = MergeSorted::new(readers, SortKey::TxId, Order::Descending).unwrap();
+        let txs = crate::format::read_fin_data(&mut merged).unwrap();
+        let ids: Vec<u64> = txs.iter().map(|tx| tx.tx_id).collect();
+        assert_eq!(ids, [9, 8, 7, 4, 1]);
+        assert_eq!(merged.into_inner().len(), 2);
+    }
+
+    #[test]
+    fn test_merge_unsorted() {
+        let readers = vec![
+            reader(&[tx(1, 10)], Format::Bin),
+            reader(&[tx(2, 20), tx(3, 15)], Format::Csv),
+        ];
+        let mut merged = merge_sorted(readers, SortKey::Timestamp).unwrap();
+        assert_eq!(merged.read_transaction().unwrap().unwrap().tx_id, 1);
+        let err = merged.read_transaction().unwrap_err();
+        assert!(matches!(err, ParsError::WrongFormat(msg) if msg.contains("Поток 1")));
+    }
+
+    /// Поток, возвращающий ошибку чтения на заданной позиции
+    struct FailingReader {
+        items: Vec<Result<Transaction, ParsError>>,
+    }
+
+    impl TransactionRead for FailingReader {
+        fn read_transaction(&mut self) -> Result<Option<Transaction>, ParsError> {
+            if self.items.is_empty() {
+                return Ok(None);
+            }
+            self.items.remove(0).map(Some)
+        }
+    }
+
+    #[test]
+    fn test_merge_read_error() {
+        let failing = FailingReader {
+            items: vec![
+                Ok(tx(1, 10)),
+                Err(ParsError::TruncatedRecord),
+                Ok(tx(3, 30)),
+            ],
+        };
+        let rest = FailingReader {
+            items: vec![Ok(tx(2, 20))],
+        };
+        let mut merged = merge_sorted(vec![failing, rest], SortKey::Timestamp).unwrap();
+        assert!(matches!(
+            merged.read_transaction(),
+            Err(ParsError::TruncatedRecord)
+        ));
+        let ids: Vec<u64> = merged.map(|tx| tx.unwrap().tx_id).collect();
+        assert_eq!(ids, [1, 2, 3]);
+    }
+}
@@ -0,0 +1,434 @@
+use super::constants::MERKLE_MAGIC;
+use super::error::ParsError;
+use super::fingerprint::{FINGERPRINT_LEN, Fingerprint};
+use super::format::TransactionRead;
+use super::transaction::TransactionRef;
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::io::{Read, Seek, SeekFrom};
+use std::str::FromStr;
+
+/// Длина блока корня дерева в конце файла bin: сигнатура, количество листьев и корень
+pub const FOOTER_LEN: usize = 4 + 8 + FINGERPRINT_LEN;
+
+/// Хэш листа: SHA-256 от байта 0x00 и отпечатка транзакции
+fn leaf_hash(fingerprint: &Fingerprint) -> Fingerprint {
+    let mut hasher = Sha256::new();
+    hasher.update([0x00]);
+    hasher.update(fingerprint.as_bytes());
+    Fingerprint(hasher.finalize().into())
+}
+
+/// Хэш узла: SHA-256 от байта 0x01 и хэшей левого и правого потомков
+fn node_hash(left: &Fingerprint, right: &Fingerprint) -> Fingerprint {
+    let mut hasher = Sha256::new();
+    hasher.update([0x01]);
+    hasher.update(left.as_bytes());
+    hasher.update(right.as_bytes());
+    Fingerprint(hasher.finalize().into())
+}
+
+/// Корень дерева без листьев
+fn empty_root() -> Fingerprint {
+    Fingerprint(Sha256::digest([]).into())
+}
+
+/// Наибольшая степень двойки, меньшая n (n > 1)
+fn split_point(n: usize) -> usize {
+    1 << (usize::BITS - 1 - (n - 1).leading_zeros())
+}
+
+/// Корень поддерева над хэшами листьев
+fn subtree_root(leaves: &[Fingerprint]) -> Fingerprint {
+    match leaves {
+        [] => empty_root(),
+        [leaf] => *leaf,
+        _ => {
+            let (left, right) = leaves.split_at(split_point(leaves.len()));
+            node_hash(&subtree_root(left), &subtree_root(right))
+        }
+    }
+}
+
+/// Корень дерева Меркла и количество листьев, записанные в конце файла bin
+/// ([crate::builder::TxWriterBuilder::merkle])
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub struct MerkleFooter {
+    /// Количество транзакций, по которым построено дерево
+    pub leaves: u64,
+    /// Корень дерева
+    pub root: Fingerprint,
+}
+
+impl MerkleFooter {
+    /// Байты блока вместе с сигнатурой
+    pub(crate) fn encode(&self) -> [u8; FOOTER_LEN] {
+        let mut res = [0; FOOTER_LEN];
+        res[..4].copy_from_slice(&MERKLE_MAGIC.to_be_bytes());
+        res[4..12].copy_from_slice(&self.leaves.to_be_bytes());
+        res[12..].copy_from_slice(self.root.as_bytes());
+        res
+    }
+
+    /// Чтение блока после сигнатуры
+    pub(crate) fn read_body<In: Read>(stream: &mut In) -> Result<Self, ParsError> {
+        let mut body = [0; FOOTER_LEN - 4];
+        stream.read_exact(&mut body)?;
+        let leaves = u64::from_be_bytes(body[..8].try_into().expect("Поле из 8 байт"));
+        let root = Fingerprint(body[8..].try_into().expect("Поле из 32 байт"));
+        Ok(Self { leaves, root })
+    }
+
+    /// Чтение блока из конца несжатого файла bin без чтения записей.
+    /// None, если файл не заканчивается блоком корня
+    pub fn read_from_end<In: Read + Seek>(mut stream: In) -> Result<Option<Self>, ParsError> {
+        let len = stream.seek(SeekFrom::End(0))?;
+        if len < FOOTER_LEN as u64 {
+            return Ok(None);
+        }
+        stream.seek(SeekFrom::End(-(FOOTER_LEN as i64)))?;
+        let mut magic = [0; 4];
+        stream.read_exact(&mut magic)?;
+        if u32::from_be_bytes(magic) != MERKLE_MAGIC {
+            return Ok(None);
+        }
+        Self::read_body(&mut stream).map(Some)
+    }
+}
+
+/// Потоковое вычисление корня дерева Меркла: хранятся только корни полных
+/// поддеревьев, по одному на уровень. Используется писателем bin при записи
+#[derive(Clone, Debug, Default)]
+pub struct MerkleBuilder {
+    // Корни полных поддеревьев с их высотой, от самого левого
+    stack: Vec<(u32, Fingerprint)>,
+    leaves: u64,
+}
+
+impl MerkleBuilder {
+    /// Построитель пустого дерева
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Добавление транзакции в конец дерева
+    pub fn push<'a>(&mut self, tx: impl Into<TransactionRef<'a>>) {
+        self.push_fingerprint(&tx.into().fingerprint());
+    }
+
+    /// Добавление транзакции по ее отпечатку
+    pub fn push_fingerprint(&mut self, fingerprint: &Fingerprint) {
+        let mut node = (0, leaf_hash(fingerprint));
+        while let Some(&(height, left)) = self.stack.last()
+            && height == node.0
+        {
+            self.stack.pop();
+            node = (height + 1, node_hash(&left, &node.1));
+        }
+        self.stack.push(node);
+        self.leaves += 1;
+    }
+
+    /// Количество листьев
+    pub fn leaves(&self) -> u64 {
+        self.leaves
+    }
+
+    /// Корень дерева над добавленными транзакциями
+    pub fn root(&self) -> Fingerprint {
+        let mut nodes = self.stack.iter().rev().map(|(_, hash)| *hash);
+        match nodes.next() {
+            Some(last) => nodes.fold(last, |right, left| node_hash(&left, &right)),
+            None => empty_root(),
+        }
+    }
+
+    /// Блок корня для записи в конец файла
+    pub fn footer(&self) -> MerkleFooter {
+        MerkleFooter {
+            leaves: self.leaves,
+            root: self.root(),
+        }
+    }
+}
+
+/// Дерево Меркла над отпечатками транзакций ([Fingerprint]) в порядке записи.
+/// Лист — хэш отпечатка с префиксом 0x00, узел — хэш потомков с префиксом 0x01,
+/// неполное дерево делится по наибольшей степени двойки, как в RFC 6962.
+/// Позволяет выдать аудитору отдельные транзакции с доказательствами включения
+/// ([InclusionProof]), которые проверяются по корню из конца файла без самого файла
+///
+/// ```
+/// use fin_parser::builder::TxWriterBuilder;
+/// use fin_parser::format::Format;
+/// use fin_parser::generate::{GenerateOptions, Generator};
+/// use fin_parser::merkle::MerkleTree;
+/// use fin_parser::tx_format::{TxReader, TxWriter};
+/// use std::io::Cursor;
+///
+/// let txs: Vec<_> = Generator::new(GenerateOptions::new(), 10).unwrap().collect();
+/// let options = TxWriterBuilder::new().merkle(true).options().clone();
+/// let mut writer = TxWriter::with_options(Vec::new(), Format::Bin, options).unwrap();
+/// writer.write_all(&txs).unwrap();
+/// let data = writer.into_inner().unwrap();
+///
+/// let mut reader = TxReader::new(Cursor::new(data), Format::Bin).unwrap();
+/// let tree = MerkleTree::from_reader(&mut reader).unwrap();
+/// let footer = *reader.merkle_footer().unwrap();
+/// assert_eq!(tree.root(), footer.root);
+///
+/// // Аудитору передаются транзакция, доказательство и корень
+/// let proof = tree.proof(3).unwrap();
+/// assert!(proof.verify(&txs[3], &footer.root));
+/// assert!(!proof.verify(&txs[4], &footer.root));
+/// ```
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+pub struct MerkleTree {
+    leaves: Vec<Fingerprint>,
+}
+
+impl MerkleTree {
+    /// Дерево над отпечатками транзакций
+    pub fn from_fingerprints<'a, I>(fingerprints: I) -> Self
+    where
+        I: IntoIterator<Item = &'a Fingerprint>,
+    {
+        Self {
+            leaves: fingerprints.into_iter().map(leaf_hash).collect(),
+        }
+    }
+
+    /// Дерево над всеми оставшимися в потоке транзакциями
+    pub fn from_reader<R: TransactionRead + ?Sized>(reader: &mut R) -> Result<Self, ParsError> {
+        let mut leaves = Vec::new();
+        while let Some(tx) = reader.read_transaction()? {
+            leaves.push(leaf_hash(&tx.fingerprint()));
+        }
+        Ok(Self { leaves })
+    }
+
+    /// Количество листьев
+    pub fn len(&self) -> usize {
+        self.leaves.len()
+    }
+
+    /// Пусто ли дерево
+    pub fn is_empty(&self) -> bool {
+        self.leaves.is_empty()
+    }
+
+    /// Корень дерева
+    pub fn root(&self) -> Fingerprint {
+        subtree_root(&self.leaves)
+    }
+
+    /// Доказательство включения транзакции с порядковым номером index.
+    /// None, если такой транзакции нет
+    pub fn proof(&self, index: usize) -> Option<InclusionProof> {
+        if index >= self.leaves.len() {
+            return None;
+        }
+        let mut path = Vec::new();
+        let (mut leaves, mut pos) = (&self.leaves[..], index);
+        // Путь собирается от корня, а хранится от листа
+        while leaves.len() > 1 {
+            let (left, right) = leaves.split_at(split_point(leaves.len()));
+            if pos < left.len() {
+                path.push(subtree_root(right));
+                leaves = left;
+            } else {
+                path.push(subtree_root(left));
+                pos -= left.len();
+                leaves = right;
+            }
+        }
+        path.reverse();
+        Some(InclusionProof {
+            index: index as u64,
+            leaves: self.leaves.len() as u64,
+            path,
+        })
+    }
+}
+
+/// Доказательство включения транзакции в дерево Меркла: хэши соседних поддеревьев
+/// на пути от листа к корню. Выводится и разбирается строкой
+/// `<номер>/<листьев>:<хэш>,<хэш>,...` с хэшами в шестнадцатеричном виде
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct InclusionProof {
+    /// Порядковый номер транзакции, начиная с нуля
+    pub index: u64,
+    /// Количество листьев дерева
+    pub leaves: u64,
+    /// Хэши соседних поддеревьев от листа к корню
+    pub path: Vec<Fingerprint>,
+}
+
+impl InclusionProof {
+    /// Проверка, что транзакция входит в дерево с корнем root под номером index
+    pub fn verify<'a>(&self, tx: impl Into<TransactionRef<'a>>, root: &Fingerprint) -> bool {
+        self.verify_fingerprint(&tx.into().fingerprint(), root)
+    }
+
+    /// Проверка по отпечатку транзакции (алгоритм RFC 9162, раздел 2.1.3.2)
+    pub fn verify_fingerprint(&self, fingerprint: &Fingerprint, root: &Fingerprint) -> bool {
+        if self.index >= self.leaves {
+            return false;
+        }
+        let (mut index, mut last) = (self.index, self.leaves - 1);
+        let mut hash = leaf_hash(fingerprint);
+        for sibling in &self.path {
+            if last == 0 {
+                return false;
+            }
+            if index & 1 == 1 || index == last {
+                hash = node_hash(sibling, &hash);
+                while index & 1 == 0 && index != 0 {
+                    index >>= 1;
+                    last >>= 1;
+                }
+            } else {
+                hash = node_hash(&hash, sibling);
+            }
+            index >>= 1;
+            last >>= 1;
+        }
+        last == 0 && hash == *root
+    }
+}
+
+impl fmt::Display for InclusionProof {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}:", self.index, self.leaves)?;
+        for (idx, hash) in self.path.iter().enumerate() {
+            if idx > 0 {
+                f.write_str(",")?;
+            }
+            write!(f, "{hash}")?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for InclusionProof {
+    type Err = ParsError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let wrong = || ParsError::WrongFormat(format!("Неверное доказательство включения: {s}"));
+        let (position, path) = s.trim().split_once(':').ok_or_else(wrong)?;
+        let (index, leaves) = position.split_once('/').ok_or_else(wrong)?;
+        let path = match path {
+            "" => Vec::new(),
+            path => path
+                .split(',')
+                .map(str::parse)
+                .collect::<Result<_, _>>()
+                .map_err(|_| wrong())?,
+        };
+        Ok(Self {
+            index: index.parse().map_err(|_| wrong())?,
+            leaves: leaves.parse().map_err(|_| wrong())?,
+            path,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::TxWriterBuilder;
+    use crate::format::Format;
+    use crate::test_util::txs;
+    use crate::transaction::Transaction;
+    use crate::tx_format::{TxReader, TxWriter};
+    use std::io::Cursor;
+
+    fn merkle_writer() -> TxWriter<Vec<u8>> {
+        let options = TxWriterBuilder::new().merkle(true).options().clone();
+        TxWriter::with_options(Vec::new(), Format::Bin, options).unwrap()
+    }
+
+    #[test]
+    fn test_merkle_proofs() {
+        for count in [0, 1, 2, 3, 5, 8, 13] {
+            let txs = txs(0..count);
+            let fingerprints: Vec<_> = txs.iter().map(Transaction::fingerprint).collect();
+            let tree = MerkleTree::from_fingerprints(&fingerprints);
+            let mut builder = MerkleBuilder::new();
+            txs.iter().for_each(|tx| builder.push(tx));
+            let root = tree.root();
+            assert_eq!(builder.root(), root, "{count}");
+            assert_eq!(builder.leaves(), count);
+
+            for (idx, tx) in txs.iter().enumerate() {
+                let proof = tree.proof(idx).unwrap();
+                assert!(proof.verify(tx, &root), "{count} {idx}");
+                let parsed: InclusionProof = proof.to_string().parse().unwrap();
+                assert_eq!(parsed, proof);
+
+                let other = &txs[(idx + 1) % txs.len()];
+                assert_eq!(proof.verify(other, &root), count == 1, "{count} {idx}");
+                let moved = InclusionProof {
+                    index: (proof.index + 1) % proof.leaves,
+                    ..proof.clone()
+                };
+                assert_eq!(moved.verify(tx, &root), count == 1, "{count} {idx}");
+            }
+            assert_eq!(tree.proof(count as usize), None);
+        }
+        assert!("1/2".parse::<InclusionProof>().is_err());
+        assert!("x/2:".parse::<InclusionProof>().is_err());
+    }
+
+    #[test]
+    fn test_merkle_footer() {
+        let txs = txs(0..7);
+        for batch in [false, true] {
+            let mut writer = merkle_writer();
+            match batch {
+                true => writer.write_all(&txs).unwrap(),
+                false => txs
+                    .iter()
+                    .for_each(|tx| writer.write_transaction(tx).unwrap()),
+            }
+            let data = writer.into_inner().unwrap();
+            let footer = MerkleFooter::read_from_end(Cursor::new(&data))
+                .unwrap()
+                .unwrap();
+            assert_eq!(footer.leaves, 7);
+
+            let mut reader = TxReader::new(Cursor::new(data.clone()), Format::Bin).unwrap();
+            assert_eq!(reader.merkle_footer(), None);
+            let tree = MerkleTree::from_reader(&mut reader).unwrap();
+            assert_eq!(reader.merkle_footer(), Some(&footer));
+            assert_eq!(tree.root(), footer.root);
+
+            // Несколько файлов подряд читаются целиком, блок корня записями не считается
+            let mut twice = data.clone();
+            twice.extend_from_slice(&data);
+            let read = TxReader::new(Cursor::new(twice), Format::Bin)
+                .unwrap()
+                .read_all()
+                .unwrap();
+            assert_eq!(read.len(), 14);
+        }
+
+        // Пустой файл состоит из одного блока корня
+        let data = merkle_writer().into_inner().unwrap();
+        assert_eq!(data.len(), FOOTER_LEN);
+        assert_eq!(Format::detect(&data).unwrap(), Format::Bin);
+        let mut reader = TxReader::new(Cursor::new(data), Format::Bin).unwrap();
+        assert_eq!(reader.read_all().unwrap().len(), 0);
+        assert_eq!(
+            reader.merkle_footer().unwrap().root,
+            MerkleTree::default().root()
+        );
+
+        let plain = TxReader::new(Cursor::new(Vec::new()), Format::Bin).unwrap();
+        assert_eq!(plain.merkle_footer(), None);
+        assert_eq!(
+            MerkleFooter::read_from_end(Cursor::new(Vec::new())).unwrap(),
+            None
+        );
+    }
+}
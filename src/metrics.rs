@@ -0,0 +1,243 @@
+use std::fmt::Write;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+/// Счетчики пропускной способности и ошибок, общие для всех копий [Metrics]
+#[derive(Debug)]
+struct Counters {
+    records_read: AtomicU64,
+    records_written: AtomicU64,
+    bytes_read: AtomicU64,
+    bytes_written: AtomicU64,
+    errors: AtomicU64,
+    started: Instant,
+}
+
+/// Метрики чтения и записи транзакций. Копии ссылаются на одни и те же счетчики,
+/// поэтому одни метрики можно передать нескольким читателям и писателям
+/// ([crate::tx_format::TxReader::with_metrics], [crate::tx_format::TxWriter::with_metrics])
+/// и читать из другого потока, пока идет конвертация
+///
+/// ```
+/// use fin_parser::converter::convert;
+/// use fin_parser::format::Format;
+/// use fin_parser::metrics::Metrics;
+/// use fin_parser::tx_format::{TxReader, TxWriter};
+/// use std::io::Cursor;
+///
+/// let csv = "TX_ID,TX_TYPE,FROM_USER_ID,TO_USER_ID,AMOUNT,TIMESTAMP,STATUS,DESCRIPTION\n\
+///     1,DEPOSIT,0,5,100,1633036860000,SUCCESS,\"Record number 1\"\n";
+/// let metrics = Metrics::new();
+/// let mut reader = TxReader::new(Cursor::new(csv), Format::Csv)
+///     .unwrap()
+///     .with_metrics(metrics.clone());
+/// let mut writer = TxWriter::new(Vec::new(), Format::Bin)
+///     .unwrap()
+///     .with_metrics(metrics.clone());
+/// convert(&mut reader, &mut writer).unwrap();
+///
+/// let snapshot = metrics.snapshot();
+/// assert_eq!((snapshot.records_read, snapshot.records_written), (1, 1));
+/// assert_eq!(snapshot.bytes_read, csv.len() as u64);
+/// assert!(snapshot.to_prometheus().contains("fin_parser_records_read_total 1\n"));
+/// ```
+#[derive(Clone, Debug)]
+pub struct Metrics {
+    counters: Arc<Counters>,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self {
+            counters: Arc::new(Counters {
+                records_read: AtomicU64::new(0),
+                records_written: AtomicU64::new(0),
+                bytes_read: AtomicU64::new(0),
+                bytes_written: AtomicU64::new(0),
+                errors: AtomicU64::new(0),
+                started: Instant::now(),
+            }),
+        }
+    }
+}
+
+impl Metrics {
+    /// Новые метрики с нулевыми счетчиками. Время работы отсчитывается от создания
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Учет прочитанных записей и байт
+    pub fn add_read(&self, records: u64, bytes: u64) {
+        self.counters
+            .records_read
+            .fetch_add(records, Ordering::Relaxed);
+        self.counters.bytes_read.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    /// Учет записанных транзакций и байт
+    pub fn add_written(&self, records: u64, bytes: u64) {
+        self.counters
+            .records_written
+            .fetch_add(records, Ordering::Relaxed);
+        self.counters
+            .bytes_written
+            .fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    /// Учет ошибки чтения или записи
+    pub fn add_error(&self) {
+        self.counters.errors.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Текущие значения счетчиков
+    pub fn snapshot(&self) -> MetricsSnapshot {
+        let counters = &self.counters;
+        MetricsSnapshot {
+            records_read: counters.records_read.load(Ordering::Relaxed),
+            records_written: counters.records_written.load(Ordering::Relaxed),
+            bytes_read: counters.bytes_read.load(Ordering::Relaxed),
+            bytes_written: counters.bytes_written.load(Ordering::Relaxed),
+            errors: counters.errors.load(Ordering::Relaxed),
+            elapsed: counters.started.elapsed(),
+        }
+    }
+}
+
+/// Значения метрик на момент вызова [Metrics::snapshot]
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Default)]
+pub struct MetricsSnapshot {
+    /// Количество прочитанных записей, включая пропущенные через
+    /// [crate::tx_format::TxReader::skip]. Ошибочные записи учитываются в errors
+    pub records_read: u64,
+    /// Количество записанных транзакций
+    pub records_written: u64,
+    /// Количество байт, прочитанных из потоков форматов (после распаковки).
+    /// Для пользовательских форматов не учитывается
+    pub bytes_read: u64,
+    /// Количество байт, записанных в потоки форматов (до сжатия).
+    /// Для пользовательских форматов не учитывается
+    pub bytes_written: u64,
+    /// Количество ошибок чтения и записи, включая пропущенные записи
+    pub errors: u64,
+    /// Время от создания метрик
+    pub elapsed: Duration,
+}
+
+impl MetricsSnapshot {
+    /// Средняя скорость чтения в записях в секунду
+    pub fn records_read_per_sec(&self) -> f64 {
+        match self.elapsed.as_secs_f64() {
+            0.0 => 0.0,
+            secs => self.records_read as f64 / secs,
+        }
+    }
+
+    /// Метрики в текстовом формате Prometheus с префиксом `fin_parser_`
+    pub fn to_prometheus(&self) -> String {
+        let counters = [
+            (
+                "records_read_total",
+                "Прочитанные записи",
+                self.records_read,
+            ),
+            (
+                "records_written_total",
+                "Записанные транзакции",
+                self.records_written,
+            ),
+            (
+                "bytes_read_total",
+                "Байты, прочитанные из потоков форматов",
+                self.bytes_read,
+            ),
+            (
+                "bytes_written_total",
+                "Байты, записанные в потоки форматов",
+                self.bytes_written,
+            ),
+            ("errors_total", "Ошибки чтения и записи", self.errors),
+        ];
+        let mut res = String::new();
+        for (name, help, value) in counters {
+            let _ = write!(
+                res,
+                "# HELP fin_parser_{name} {help}\n# TYPE fin_parser_{name} counter\nfin_parser_{name} {value}\n"
+            );
+        }
+        let _ = write!(
+            res,
+            "# HELP fin_parser_elapsed_seconds Время работы\n# TYPE fin_parser_elapsed_seconds gauge\nfin_parser_elapsed_seconds {}\n",
+            self.elapsed.as_secs_f64()
+        );
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::converter::convert;
+    use crate::format::Format;
+    use crate::options::ErrorPolicy;
+    use crate::tx_format::{TxReader, TxWriter};
+    use std::io::Cursor;
+
+    const CSV: &str = "TX_ID,TX_TYPE,FROM_USER_ID,TO_USER_ID,AMOUNT,TIMESTAMP,STATUS,DESCRIPTION
+1,DEPOSIT,0,9,100,1633036860000,SUCCESS,\"Record number 1\"
+2,TRANSFUR,9,7,250,1633036920000,PENDING,\"Record number 2\"
+3,WITHDRAWAL,7,0,50,1633036980000,FAILURE,\"Record number 3\"
+";
+
+    #[test]
+    fn test_metrics() {
+        let metrics = Metrics::new();
+        let mut reader = TxReader::new(Cursor::new(CSV), Format::Csv)
+            .unwrap()
+            .with_error_policy(ErrorPolicy::Skip)
+            .with_metrics(metrics.clone());
+        let mut writer = TxWriter::new(Vec::new(), Format::Bin)
+            .unwrap()
+            .with_metrics(metrics.clone());
+        assert_eq!(convert(&mut reader, &mut writer).unwrap(), 2);
+        let bytes_written = writer.into_inner().unwrap().len() as u64;
+
+        let snapshot = metrics.snapshot();
+        assert_eq!(snapshot.records_read, 2);
+        assert_eq!(snapshot.records_written, 2);
+        assert_eq!(snapshot.bytes_read, CSV.len() as u64);
+        assert_eq!(snapshot.bytes_written, bytes_written);
+        assert_eq!(snapshot.errors, 1);
+
+        let text = snapshot.to_prometheus();
+        assert!(
+            text.contains("# TYPE fin_parser_errors_total counter\nfin_parser_errors_total 1\n")
+        );
+        assert!(text.contains("fin_parser_records_written_total 2\n"));
+        assert!(text.ends_with(&format!(
+            "fin_parser_elapsed_seconds {}\n",
+            snapshot.elapsed.as_secs_f64()
+        )));
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn test_metrics_parallel() {
+        let input = CSV.replace("TRANSFUR", "TRANSFER");
+        let metrics = Metrics::new();
+        let mut reader = TxReader::new(Cursor::new(input.clone()), Format::Csv)
+            .unwrap()
+            .with_metrics(metrics.clone());
+        let mut writer = TxWriter::new(Vec::new(), Format::Text)
+            .unwrap()
+            .with_metrics(metrics.clone());
+        crate::converter::convert_parallel(&mut reader, &mut writer, 2).unwrap();
+        let bytes_written = writer.into_inner().unwrap().len() as u64;
+
+        let snapshot = metrics.snapshot();
+        assert_eq!((snapshot.records_read, snapshot.records_written), (3, 3));
+        assert_eq!(snapshot.bytes_read, input.len() as u64);
+        assert_eq!(snapshot.bytes_written, bytes_written);
+    }
+}
@@ -0,0 +1,130 @@
+use super::error::ParsError;
+use std::fmt;
+use std::str::FromStr;
+
+/// Сумма транзакции с проверяемой арифметикой. Операции считаются в i128,
+/// и результат вне диапазона i64 возвращается ошибкой [ParsError::AmountOverflow]
+/// с точным значением вместо молчаливого переполнения
+///
+/// ```
+/// use fin_parser::money::Money;
+///
+/// let balance = Money::new(100).checked_sub(Money::new(250)).unwrap();
+/// assert_eq!(balance, Money::new(-150));
+/// assert!(Money::MAX.checked_add(Money::new(1)).is_err());
+/// assert!(Money::MIN.checked_neg().is_err());
+/// ```
+#[derive(Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Money(i64);
+
+impl Money {
+    /// Нулевая сумма
+    pub const ZERO: Money = Money(0);
+    /// Наибольшая сумма
+    pub const MAX: Money = Money(i64::MAX);
+    /// Наименьшая сумма
+    pub const MIN: Money = Money(i64::MIN);
+
+    /// Сумма из значения поля amount
+    pub const fn new(value: i64) -> Self {
+        Self(value)
+    }
+
+    /// Значение суммы
+    pub const fn value(self) -> i64 {
+        self.0
+    }
+
+    /// Сложение
+    pub fn checked_add(self, other: Money) -> Result<Money, ParsError> {
+        Money::try_from(self.0 as i128 + other.0 as i128)
+    }
+
+    /// Вычитание
+    pub fn checked_sub(self, other: Money) -> Result<Money, ParsError> {
+        Money::try_from(self.0 as i128 - other.0 as i128)
+    }
+
+    /// Смена знака. Ошибка только для [Money::MIN]
+    pub fn checked_neg(self) -> Result<Money, ParsError> {
+        Money::try_from(-(self.0 as i128))
+    }
+
+    /// Модуль суммы. Ошибка только для [Money::MIN]
+    pub fn checked_abs(self) -> Result<Money, ParsError> {
+        Money::try_from((self.0 as i128).abs())
+    }
+
+    /// Сумма последовательности. Промежуточные значения хранятся в i128,
+    /// поэтому ошибка возвращается только для итога вне диапазона i64
+    pub fn checked_sum<I: IntoIterator<Item = Money>>(iter: I) -> Result<Money, ParsError> {
+        Money::try_from(iter.into_iter().map(|val| val.0 as i128).sum::<i128>())
+    }
+}
+
+impl From<i64> for Money {
+    fn from(value: i64) -> Self {
+        Self(value)
+    }
+}
+
+impl From<Money> for i64 {
+    fn from(value: Money) -> Self {
+        value.0
+    }
+}
+
+impl From<Money> for i128 {
+    fn from(value: Money) -> Self {
+        value.0 as i128
+    }
+}
+
+/// Приведение результата вычислений в i128, например [crate::analytics::Aggregate::sum]
+impl TryFrom<i128> for Money {
+    type Error = ParsError;
+
+    fn try_from(value: i128) -> Result<Self, Self::Error> {
+        i64::try_from(value)
+            .map(Self)
+            .map_err(|_| ParsError::AmountOverflow { value })
+    }
+}
+
+impl FromStr for Money {
+    type Err = ParsError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Self(s.parse()?))
+    }
+}
+
+impl fmt::Display for Money {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_money_overflow() {
+        let err = Money::MAX.checked_add(Money::new(2)).unwrap_err();
+        assert!(
+            matches!(err, ParsError::AmountOverflow { value } if value == i64::MAX as i128 + 2)
+        );
+        assert_eq!(err.code(), "amount_overflow");
+        assert!(Money::MIN.checked_sub(Money::new(1)).is_err());
+        assert!(Money::MIN.checked_abs().is_err());
+        assert_eq!(Money::MAX.checked_neg().unwrap(), Money::new(-i64::MAX));
+
+        // Промежуточное переполнение не мешает, если итог помещается в i64
+        let sum = Money::checked_sum([Money::MAX, Money::MAX, Money::new(-i64::MAX)]).unwrap();
+        assert_eq!(sum, Money::MAX);
+        assert!(Money::checked_sum([Money::MAX, Money::new(1)]).is_err());
+        assert_eq!("-42".parse::<Money>().unwrap().value(), -42);
+    }
+}
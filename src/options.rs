@@ -0,0 +1,126 @@
+use super::compression::Compression;
+use super::error::ParsError;
+use chrono::{DateTime, Utc};
+use std::ops::Bound;
+use std::str::FromStr;
+
+/// Максимальная длина описания транзакции по умолчанию
+pub const DEFAULT_MAX_DESCRIPTION_LEN: usize = 1024 * 1024;
+
+/// Единица измерения времени в поле TIMESTAMP
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Default)]
+pub enum TimestampUnit {
+    /// Секунды от эпохи Unix
+    Seconds,
+    /// Миллисекунды от эпохи Unix (значение по спецификации форматов)
+    #[default]
+    Millis,
+    /// Микросекунды от эпохи Unix
+    Micros,
+}
+
+impl FromStr for TimestampUnit {
+    type Err = ParsError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "seconds" | "s" => Ok(Self::Seconds),
+            "millis" | "ms" => Ok(Self::Millis),
+            "micros" | "us" => Ok(Self::Micros),
+            _ => Err(ParsError::WrongFormat(format!(
+                "Неизвестная единица времени: {s}"
+            ))),
+        }
+    }
+}
+
+/// Версия формата bin при записи. Читатель определяет версию по началу файла
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Default)]
+pub enum BinVersion {
+    /// Только записи, порядок полей фиксирован спецификацией
+    #[default]
+    V1,
+    /// Заголовок файла со схемой полей (имена, типы, порядок). Читатель пропускает
+    /// неизвестные ему поля, поэтому файлы, записанные более новыми версиями
+    /// библиотеки с дополнительными полями, читаются прежними
+    V2,
+}
+
+impl FromStr for BinVersion {
+    type Err = ParsError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "1" | "v1" => Ok(Self::V1),
+            "2" | "v2" => Ok(Self::V2),
+            _ => Err(ParsError::WrongFormat(format!(
+                "Неизвестная версия формата bin: {s}"
+            ))),
+        }
+    }
+}
+
+/// Интервал времени транзакций для отбора при чтении
+pub(crate) type TimeRange = (Bound<DateTime<Utc>>, Bound<DateTime<Utc>>);
+
+/// Поведение читателя при ошибке в записи
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Default)]
+pub enum ErrorPolicy {
+    /// Чтение прекращается на первой ошибке
+    #[default]
+    Fail,
+    /// Ошибочная запись пропускается, чтение продолжается со следующей записи
+    Skip,
+}
+
+/// Настройки чтения транзакций
+#[derive(Clone, Debug)]
+pub struct ReaderOptions {
+    pub(crate) strict: bool,
+    pub(crate) delimiter: u8,
+    pub(crate) timestamp_unit: TimestampUnit,
+    pub(crate) max_description_len: usize,
+    pub(crate) compression: Compression,
+    pub(crate) error_policy: ErrorPolicy,
+    pub(crate) max_errors: Option<usize>,
+    pub(crate) collect_warnings: bool,
+    pub(crate) time_range: Option<TimeRange>,
+}
+
+impl Default for ReaderOptions {
+    fn default() -> Self {
+        Self {
+            strict: true,
+            delimiter: b',',
+            timestamp_unit: TimestampUnit::default(),
+            max_description_len: DEFAULT_MAX_DESCRIPTION_LEN,
+            compression: Compression::default(),
+            error_policy: ErrorPolicy::default(),
+            max_errors: None,
+            collect_warnings: false,
+            time_range: None,
+        }
+    }
+}
+
+/// Настройки записи транзакций
+#[derive(Clone, Debug)]
+pub struct WriterOptions {
+    pub(crate) delimiter: u8,
+    pub(crate) timestamp_unit: TimestampUnit,
+    pub(crate) compression: Compression,
+    pub(crate) bin_version: BinVersion,
+    pub(crate) merkle: bool,
+}
+
+impl Default for WriterOptions {
+    fn default() -> Self {
+        Self {
+            delimiter: b',',
+            timestamp_unit: TimestampUnit::default(),
+            compression: Compression::default(),
+            bin_version: BinVersion::default(),
+            merkle: false,
+        }
+    }
+}
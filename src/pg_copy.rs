@@ -0,0 +1,163 @@
+use super::error::ParsError;
+use super::format::TransactionWrite;
+use super::reconcile::Field;
+use super::transaction::Transaction;
+use postgres::binary_copy::BinaryCopyInWriter;
+use postgres::types::{ToSql, Type};
+use postgres::{Client, NoTls};
+use std::str::FromStr;
+
+/// Столбцы таблицы в порядке полей транзакции
+const COLUMNS: &str =
+    "tx_id, tx_type, from_user_id, to_user_id, amount, timestamp, status, description";
+
+/// Типы столбцов [COLUMNS]. Таблица может быть создана так:
+/// ```sql
+/// CREATE TABLE transactions (
+///     tx_id BIGINT, tx_type TEXT, from_user_id BIGINT, to_user_id BIGINT,
+///     amount BIGINT, timestamp TIMESTAMPTZ, status TEXT, description TEXT
+/// );
+/// ```
+const TYPES: [Type; 8] = [
+    Type::INT8,
+    Type::TEXT,
+    Type::INT8,
+    Type::INT8,
+    Type::INT8,
+    Type::TIMESTAMPTZ,
+    Type::TEXT,
+    Type::TEXT,
+];
+
+/// Имя таблицы: идентификатор с необязательной схемой, например `public.transactions`.
+/// Допускаются только латинские буквы, цифры и `_`, поэтому имя безопасно
+/// подставляется в команду COPY
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct TableName(String);
+
+impl TableName {
+    /// Имя таблицы
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl FromStr for TableName {
+    type Err = ParsError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<&str> = s.split('.').collect();
+        let valid = parts.len() <= 2
+            && parts.iter().all(|part| {
+                part.starts_with(|ch: char| ch.is_ascii_alphabetic() || ch == '_')
+                    && part
+                        .chars()
+                        .all(|ch| ch.is_ascii_alphanumeric() || ch == '_')
+            });
+        match valid {
+            true => Ok(Self(s.to_owned())),
+            false => Err(ParsError::WrongFormat(format!(
+                "Некорректное имя таблицы: {s}"
+            ))),
+        }
+    }
+}
+
+/// Подключение к PostgreSQL по строке подключения, например
+/// `host=localhost user=loader dbname=bank` или `postgresql://loader@localhost/bank`
+pub fn connect(conninfo: &str) -> Result<Client, ParsError> {
+    Client::connect(conninfo, NoTls).map_err(pg_error)
+}
+
+/// Запись транзакций в таблицу PostgreSQL командой `COPY ... FROM STDIN (FORMAT binary)`.
+/// Транзакции передаются на сервер по мере записи, без промежуточного файла.
+/// Данные фиксируются в [TransactionWrite::finish]; писатель, удаленный без finish,
+/// отменяет COPY
+pub struct PgCopyWriter<'a> {
+    writer: Option<BinaryCopyInWriter<'a>>,
+    rows: u64,
+}
+
+impl<'a> PgCopyWriter<'a> {
+    /// Начало COPY в таблицу table
+    pub fn new(client: &'a mut Client, table: &TableName) -> Result<Self, ParsError> {
+        let query = format!(
+            "COPY {} ({COLUMNS}) FROM STDIN (FORMAT binary)",
+            table.as_str()
+        );
+        let copy = client.copy_in(&query).map_err(pg_error)?;
+        Ok(Self {
+            writer: Some(BinaryCopyInWriter::new(copy, &TYPES)),
+            rows: 0,
+        })
+    }
+
+    /// Количество строк, записанных сервером. Известно после finish
+    pub fn rows(&self) -> u64 {
+        self.rows
+    }
+}
+
+impl TransactionWrite for PgCopyWriter<'_> {
+    fn write_transaction(&mut self, tx: &Transaction) -> Result<(), ParsError> {
+        let writer = self
+            .writer
+            .as_mut()
+            .ok_or_else(|| ParsError::WrongFormat("COPY уже завершен".to_owned()))?;
+        let tx_id = to_bigint("TX_ID", tx.tx_id)?;
+        let from_user_id = to_bigint("FROM_USER_ID", tx.from_user_id)?;
+        let to_user_id = to_bigint("TO_USER_ID", tx.to_user_id)?;
+        let tx_type = Field::TxType.value(tx);
+        let status = Field::Status.value(tx);
+        let values: [&(dyn ToSql + Sync); 8] = [
+            &tx_id,
+            &tx_type,
+            &from_user_id,
+            &to_user_id,
+            &tx.amount,
+            &tx.timestamp,
+            &status,
+            &tx.description,
+        ];
+        writer.write(&values).map_err(pg_error)
+    }
+
+    fn finish(&mut self) -> Result<(), ParsError> {
+        if let Some(writer) = self.writer.take() {
+            self.rows = writer.finish().map_err(pg_error)?;
+        }
+        Ok(())
+    }
+}
+
+/// Значение u64 для столбца BIGINT
+fn to_bigint(field: &str, val: u64) -> Result<i64, ParsError> {
+    i64::try_from(val)
+        .map_err(|_| ParsError::WrongFormat(format!("{field} {val} не помещается в BIGINT")))
+}
+
+/// Ошибка с сообщением сервера, если оно есть
+fn pg_error(e: postgres::Error) -> ParsError {
+    match e.as_db_error() {
+        Some(db) => ParsError::WrongFormat(format!("Ошибка PostgreSQL: {}", db.message())),
+        None => ParsError::WrongFormat(format!("Ошибка PostgreSQL: {e}")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_table_name() {
+        assert_eq!(
+            "public.transactions".parse::<TableName>().unwrap().as_str(),
+            "public.transactions"
+        );
+        assert!("_tx2".parse::<TableName>().is_ok());
+        for name in ["", "2tx", "a.b.c", "tx; DROP TABLE tx", "tx\"", "a."] {
+            assert!(name.parse::<TableName>().is_err(), "{name}");
+        }
+        assert!(to_bigint("TX_ID", u64::MAX).is_err());
+    }
+}
@@ -0,0 +1,436 @@
+use super::dedup::Keep;
+use super::error::ParsError;
+use super::filter::TxFilter;
+use super::format::{TransactionRead, TransactionWrite};
+use super::options::ErrorPolicy;
+use super::transaction::Transaction;
+use regex::Regex;
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
+
+/// Этап обработки транзакций в [Pipeline]
+pub trait TxStage: Send {
+    /// Имя этапа для статистики
+    fn name(&self) -> &str;
+
+    /// Обработка транзакции. None означает, что транзакция отброшена
+    fn process(&mut self, tx: Transaction) -> Result<Option<Transaction>, ParsError>;
+
+    /// Завершение обработки после конца потока. Этапы, придерживающие транзакции,
+    /// возвращают их здесь, и они передаются следующим этапам
+    fn finish(&mut self) -> Result<Vec<Transaction>, ParsError> {
+        Ok(Vec::new())
+    }
+}
+
+/// Отбор транзакций по условию
+pub struct Filter {
+    filter: TxFilter,
+}
+
+impl Filter {
+    /// Этап, пропускающий только транзакции, подходящие под условие
+    pub fn new(filter: TxFilter) -> Self {
+        Self { filter }
+    }
+}
+
+impl TxStage for Filter {
+    fn name(&self) -> &str {
+        "filter"
+    }
+
+    fn process(&mut self, tx: Transaction) -> Result<Option<Transaction>, ParsError> {
+        Ok(self.filter.matches_tx(&tx).then_some(tx))
+    }
+}
+
+/// Преобразование транзакций функцией
+pub struct Map<F: FnMut(Transaction) -> Transaction + Send> {
+    func: F,
+}
+
+impl<F: FnMut(Transaction) -> Transaction + Send> Map<F> {
+    /// Этап, заменяющий каждую транзакцию результатом func
+    pub fn new(func: F) -> Self {
+        Self { func }
+    }
+}
+
+impl<F: FnMut(Transaction) -> Transaction + Send> TxStage for Map<F> {
+    fn name(&self) -> &str {
+        "map"
+    }
+
+    fn process(&mut self, tx: Transaction) -> Result<Option<Transaction>, ParsError> {
+        Ok(Some((self.func)(tx)))
+    }
+}
+
+/// Удаление транзакций с повторяющимся tx_id, см. [crate::dedup::DedupReader].
+/// Для [Keep::Last] все транзакции придерживаются до конца потока
+pub struct Dedup {
+    keep: Keep,
+    seen: HashSet<u64>,
+    held: Vec<Transaction>,
+    last: HashMap<u64, usize>,
+}
+
+impl Dedup {
+    /// Этап удаления повторов с выбором оставляемой транзакции
+    pub fn new(keep: Keep) -> Self {
+        Self {
+            keep,
+            seen: HashSet::new(),
+            held: Vec::new(),
+            last: HashMap::new(),
+        }
+    }
+}
+
+impl TxStage for Dedup {
+    fn name(&self) -> &str {
+        "dedup"
+    }
+
+    fn process(&mut self, tx: Transaction) -> Result<Option<Transaction>, ParsError> {
+        match self.keep {
+            Keep::Last => {
+                self.last.insert(tx.tx_id, self.held.len());
+                self.held.push(tx);
+                Ok(None)
+            }
+            _ if self.seen.insert(tx.tx_id) => Ok(Some(tx)),
+            Keep::First => Ok(None),
+            Keep::Error => Err(ParsError::DuplicateTxId { tx_id: tx.tx_id }),
+        }
+    }
+
+    fn finish(&mut self) -> Result<Vec<Transaction>, ParsError> {
+        let last = std::mem::take(&mut self.last);
+        Ok(std::mem::take(&mut self.held)
+            .into_iter()
+            .enumerate()
+            .filter(|(idx, tx)| last[&tx.tx_id] == *idx)
+            .map(|(_, tx)| tx)
+            .collect())
+    }
+}
+
+/// Скрытие описаний транзакций
+pub struct Redact {
+    pattern: Option<Regex>,
+    replacement: String,
+}
+
+impl Redact {
+    /// Этап, заменяющий описание целиком на replacement
+    pub fn description(replacement: &str) -> Self {
+        Self {
+            pattern: None,
+            replacement: replacement.to_owned(),
+        }
+    }
+
+    /// Этап, заменяющий в описании все совпадения с регулярным выражением на replacement
+    pub fn pattern(pattern: &str, replacement: &str) -> Result<Self, ParsError> {
+        let pattern = Regex::new(pattern).map_err(|e| {
+            ParsError::WrongFormat(format!("Некорректное регулярное выражение: {e}"))
+        })?;
+        Ok(Self {
+            pattern: Some(pattern),
+            replacement: replacement.to_owned(),
+        })
+    }
+}
+
+impl TxStage for Redact {
+    fn name(&self) -> &str {
+        "redact"
+    }
+
+    fn process(&mut self, mut tx: Transaction) -> Result<Option<Transaction>, ParsError> {
+        tx.description = match &self.pattern {
+            Some(pattern) => pattern
+                .replace_all(&tx.description, self.replacement.as_str())
+                .into_owned(),
+            None => self.replacement.clone(),
+        };
+        Ok(Some(tx))
+    }
+}
+
+/// Проверка транзакций функцией, возвращающей описание нарушения
+pub struct Validate<F: FnMut(&Transaction) -> Result<(), String> + Send> {
+    check: F,
+    policy: ErrorPolicy,
+}
+
+impl<F: FnMut(&Transaction) -> Result<(), String> + Send> Validate<F> {
+    /// Этап проверки, прерывающий обработку на первой ошибочной транзакции
+    pub fn new(check: F) -> Self {
+        Self {
+            check,
+            policy: ErrorPolicy::Fail,
+        }
+    }
+
+    /// Поведение при ошибочной транзакции: прервать обработку или отбросить транзакцию
+    pub fn with_policy(mut self, policy: ErrorPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+}
+
+impl<F: FnMut(&Transaction) -> Result<(), String> + Send> TxStage for Validate<F> {
+    fn name(&self) -> &str {
+        "validate"
+    }
+
+    fn process(&mut self, tx: Transaction) -> Result<Option<Transaction>, ParsError> {
+        match ((self.check)(&tx), self.policy) {
+            (Ok(()), _) => Ok(Some(tx)),
+            (Err(_), ErrorPolicy::Skip) => Ok(None),
+            (Err(msg), ErrorPolicy::Fail) => Err(ParsError::WrongFormat(format!(
+                "Транзакция {} не прошла проверку: {msg}",
+                tx.tx_id
+            ))),
+        }
+    }
+}
+
+/// Статистика одного этапа
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+pub struct StageStats {
+    /// Имя этапа
+    pub name: String,
+    /// Количество транзакций, поступивших на этап
+    pub input: u64,
+    /// Количество транзакций, переданных дальше
+    pub output: u64,
+}
+
+impl StageStats {
+    /// Количество отброшенных транзакций
+    pub fn dropped(&self) -> u64 {
+        self.input.saturating_sub(self.output)
+    }
+}
+
+/// Статистика выполнения [Pipeline::run]
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+pub struct PipelineStats {
+    /// Количество прочитанных транзакций
+    pub read: u64,
+    /// Количество записанных транзакций
+    pub written: u64,
+    /// Статистика этапов в порядке их добавления
+    pub stages: Vec<StageStats>,
+    /// Длительность обработки
+    pub duration: Duration,
+}
+
+/// Цепочка этапов обработки между источником и приемником транзакций
+///
+/// ```
+/// use fin_parser::dedup::Keep;
+/// use fin_parser::format::Format;
+/// use fin_parser::pipeline::{Dedup, Filter, Pipeline, Redact};
+/// use fin_parser::tx_format::{TxReader, TxWriter};
+/// use std::io::Cursor;
+///
+/// let reader = TxReader::new(Cursor::new(Vec::new()), Format::Text).unwrap();
+/// let writer = TxWriter::new(Vec::new(), Format::Bin).unwrap();
+/// let mut pipeline = Pipeline::new(reader, writer)
+///     .stage(Filter::new("status = SUCCESS".parse().unwrap()))
+///     .stage(Dedup::new(Keep::First))
+///     .stage(Redact::description("***"));
+/// let stats = pipeline.run().unwrap();
+/// assert_eq!(stats.stages.len(), 3);
+/// ```
+pub struct Pipeline<R: TransactionRead, W: TransactionWrite> {
+    reader: R,
+    writer: W,
+    stages: Vec<Box<dyn TxStage>>,
+}
+
+impl<R: TransactionRead, W: TransactionWrite> Pipeline<R, W> {
+    /// Цепочка без этапов, копирующая транзакции из reader в writer
+    pub fn new(reader: R, writer: W) -> Self {
+        Self {
+            reader,
+            writer,
+            stages: Vec::new(),
+        }
+    }
+
+    /// Добавление этапа в конец цепочки
+    pub fn stage<S: TxStage + 'static>(mut self, stage: S) -> Self {
+        self.stages.push(Box::new(stage));
+        self
+    }
+
+    /// Обработка всех оставшихся в источнике транзакций и завершение записи
+    pub fn run(&mut self) -> Result<PipelineStats, ParsError> {
+        let start = Instant::now();
+        let mut stats = PipelineStats {
+            stages: self
+                .stages
+                .iter()
+                .map(|stage| StageStats {
+                    name: stage.name().to_owned(),
+                    ..Default::default()
+                })
+                .collect(),
+            ..Default::default()
+        };
+        while let Some(tx) = self.reader.read_transaction()? {
+            stats.read += 1;
+            self.push(tx, 0, &mut stats)?;
+        }
+        for idx in 0..self.stages.len() {
+            for tx in self.stages[idx].finish()? {
+                stats.stages[idx].output += 1;
+                self.push(tx, idx + 1, &mut stats)?;
+            }
+        }
+        self.writer.finish()?;
+        stats.duration = start.elapsed();
+        Ok(stats)
+    }
+
+    /// Передача транзакции этапам начиная с from и запись результата
+    fn push(
+        &mut self,
+        mut tx: Transaction,
+        from: usize,
+        stats: &mut PipelineStats,
+    ) -> Result<(), ParsError> {
+        for (stage, stage_stats) in self.stages[from..]
+            .iter_mut()
+            .zip(&mut stats.stages[from..])
+        {
+            stage_stats.input += 1;
+            match stage.process(tx)? {
+                Some(val) => tx = val,
+                None => return Ok(()),
+            }
+            stage_stats.output += 1;
+        }
+        self.writer.write_transaction(&tx)?;
+        stats.written += 1;
+        Ok(())
+    }
+
+    /// Возврат источника и приемника
+    pub fn into_inner(self) -> (R, W) {
+        (self.reader, self.writer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::Format;
+    use crate::test_util::{reader, tx};
+    use crate::transaction::TxStatus;
+    use crate::tx_format::{TxReader, TxWriter};
+    use std::io::Cursor;
+
+    fn reader_for_test() -> TxReader<Cursor<Vec<u8>>> {
+        let txs = [
+            (1, 100, TxStatus::Success),
+            (2, 200, TxStatus::Failure),
+            (1, 300, TxStatus::Success),
+            (3, -5, TxStatus::Success),
+            (4, 400, TxStatus::Success),
+        ]
+        .map(|(tx_id, amount, status)| Transaction {
+            amount,
+            status,
+            description: format!("Card 4111-1111 record {tx_id}"),
+            ..tx(tx_id)
+        });
+        reader(&txs, Format::Csv)
+    }
+
+    fn writer_for_test() -> TxWriter<Vec<u8>> {
+        TxWriter::new(Vec::new(), Format::Csv).unwrap()
+    }
+
+    fn check_amount(tx: &Transaction) -> Result<(), String> {
+        match tx.amount {
+            0.. => Ok(()),
+            _ => Err("отрицательная сумма".to_owned()),
+        }
+    }
+
+    #[test]
+    fn test_pipeline() {
+        let writer = TxWriter::new(Vec::new(), Format::Bin).unwrap();
+        let mut pipeline = Pipeline::new(reader_for_test(), writer)
+            .stage(Filter::new(TxFilter::status(TxStatus::Success)))
+            .stage(Dedup::new(Keep::Last))
+            .stage(Validate::new(check_amount).with_policy(ErrorPolicy::Skip))
+            .stage(Redact::pattern(r"\d{4}-\d{4}", "****").unwrap())
+            .stage(Map::new(|mut tx: Transaction| {
+                tx.amount *= 2;
+                tx
+            }));
+        let stats = pipeline.run().unwrap();
+        assert_eq!(stats.read, 5);
+        assert_eq!(stats.written, 2);
+        let counts: Vec<(&str, u64, u64)> = stats
+            .stages
+            .iter()
+            .map(|stage| (stage.name.as_str(), stage.input, stage.output))
+            .collect();
+        assert_eq!(
+            counts,
+            [
+                ("filter", 5, 4),
+                ("dedup", 4, 3),
+                ("validate", 3, 2),
+                ("redact", 2, 2),
+                ("map", 2, 2),
+            ]
+        );
+        assert_eq!(stats.stages[1].dropped(), 1);
+
+        let (_, writer) = pipeline.into_inner();
+        let buf = writer.into_inner().unwrap();
+        let txs = TxReader::new(Cursor::new(buf), Format::Bin)
+            .unwrap()
+            .read_all()
+            .unwrap();
+        let res: Vec<(u64, i64, &str)> = txs
+            .iter()
+            .map(|tx| (tx.tx_id, tx.amount, tx.description.as_str()))
+            .collect();
+        assert_eq!(
+            res,
+            [
+                (1, 600, "Card **** record 1"),
+                (4, 800, "Card **** record 4")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_pipeline_errors() {
+        let mut pipeline =
+            Pipeline::new(reader_for_test(), writer_for_test()).stage(Validate::new(check_amount));
+        assert!(matches!(
+            pipeline.run().unwrap_err(),
+            ParsError::WrongFormat(msg) if msg.contains("Транзакция 3")
+        ));
+
+        let mut pipeline =
+            Pipeline::new(reader_for_test(), writer_for_test()).stage(Dedup::new(Keep::Error));
+        assert!(matches!(
+            pipeline.run().unwrap_err(),
+            ParsError::DuplicateTxId { tx_id: 1 }
+        ));
+        assert!(Redact::pattern("(", "").is_err());
+    }
+}
@@ -0,0 +1,377 @@
+use super::analytics::Aggregate;
+use super::constants::*;
+use super::error::ParsError;
+use super::format::TransactionRead;
+use super::transaction::{Transaction, TransactionRef, TxStatus, TxType};
+use chrono::{DateTime, Datelike, Utc};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::Write;
+use std::str::FromStr;
+
+/// Статусы в порядке столбцов сводной таблицы
+const STATUSES: [TxStatus; 3] = [TxStatus::Success, TxStatus::Failure, TxStatus::Pending];
+
+/// Месяц транзакции (UTC)
+#[derive(Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Month {
+    /// Год
+    pub year: i32,
+    /// Номер месяца от 1 до 12
+    pub month: u32,
+}
+
+impl From<DateTime<Utc>> for Month {
+    fn from(timestamp: DateTime<Utc>) -> Self {
+        Self {
+            year: timestamp.year(),
+            month: timestamp.month(),
+        }
+    }
+}
+
+/// Месяц в виде `2021-10`
+impl fmt::Display for Month {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:04}-{:02}", self.year, self.month)
+    }
+}
+
+/// Формат вывода сводной таблицы
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Default)]
+pub enum PivotFormat {
+    /// csv с заголовком
+    #[default]
+    Csv,
+    /// Таблица markdown
+    Markdown,
+    /// Таблица html
+    Html,
+}
+
+impl FromStr for PivotFormat {
+    type Err = ParsError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "csv" => Ok(Self::Csv),
+            "markdown" | "md" => Ok(Self::Markdown),
+            "html" => Ok(Self::Html),
+            _ => Err(ParsError::WrongFormat(format!(
+                "Неизвестный формат отчета: {s}"
+            ))),
+        }
+    }
+}
+
+impl fmt::Display for PivotFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Csv => "csv",
+            Self::Markdown => "markdown",
+            Self::Html => "html",
+        })
+    }
+}
+
+/// Сводная таблица количества и сумм транзакций по месяцам, типам и статусам.
+/// Собирается за один проход, память растет только с количеством групп
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Pivot {
+    cells: BTreeMap<(Month, TxType, TxStatus), Aggregate>,
+}
+
+impl Pivot {
+    /// Пустая таблица
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Учет транзакции
+    pub fn add(&mut self, tx: &TransactionRef<'_>) {
+        self.cells
+            .entry((Month::from(tx.timestamp), tx.tx_type, tx.status))
+            .or_default()
+            .add(tx.amount);
+    }
+
+    /// Учет транзакции, владеющей описанием
+    pub fn add_tx(&mut self, tx: &Transaction) {
+        self.add(&TransactionRef::from(tx))
+    }
+
+    /// Объединение с таблицей, посчитанной по другой части потока
+    pub fn merge(&mut self, other: &Pivot) {
+        for (key, val) in &other.cells {
+            self.cells.entry(*key).or_default().merge(val);
+        }
+    }
+
+    /// Ячейка таблицы. Для группы без транзакций None
+    pub fn get(&self, month: Month, tx_type: TxType, status: TxStatus) -> Option<&Aggregate> {
+        self.cells.get(&(month, tx_type, status))
+    }
+
+    /// Ячейки таблицы в порядке месяца, типа и статуса
+    pub fn iter(&self) -> impl Iterator<Item = (Month, TxType, TxStatus, &Aggregate)> {
+        self.cells
+            .iter()
+            .map(|(&(month, tx_type, status), val)| (month, tx_type, status, val))
+    }
+
+    /// Месяцы, в которых есть транзакции
+    pub fn months(&self) -> Vec<Month> {
+        let mut res: Vec<Month> = self.cells.keys().map(|key| key.0).collect();
+        res.dedup();
+        res
+    }
+
+    /// Итог по всем ячейкам
+    pub fn total(&self) -> Aggregate {
+        let mut res = Aggregate::default();
+        for val in self.cells.values() {
+            res.merge(val);
+        }
+        res
+    }
+
+    /// Таблица пуста
+    pub fn is_empty(&self) -> bool {
+        self.cells.is_empty()
+    }
+
+    /// Вывод таблицы: строка на месяц и тип, для каждого статуса количество и сумма,
+    /// затем итог строки. Последняя строка содержит итоги по столбцам
+    pub fn write<Out: Write>(&self, out: &mut Out, fmt: PivotFormat) -> Result<(), ParsError> {
+        let (header, rows) = self.table();
+        match fmt {
+            PivotFormat::Csv => {
+                writeln!(out, "{}", header.join(","))?;
+                for row in &rows {
+                    writeln!(out, "{}", row.join(","))?;
+                }
+            }
+            PivotFormat::Markdown => {
+                writeln!(out, "| {} |", header.join(" | "))?;
+                let align: Vec<&str> = header
+                    .iter()
+                    .enumerate()
+                    .map(|(idx, _)| if idx < 2 { "---" } else { "---:" })
+                    .collect();
+                writeln!(out, "|{}|", align.join("|"))?;
+                for row in &rows {
+                    writeln!(out, "| {} |", row.join(" | "))?;
+                }
+            }
+            PivotFormat::Html => {
+                writeln!(out, "<table>")?;
+                writeln!(out, "<thead>")?;
+                write_html_row(out, "th", &header)?;
+                writeln!(out, "</thead>")?;
+                writeln!(out, "<tbody>")?;
+                let (total, body) = rows.split_last().expect("Итоговая строка есть всегда");
+                for row in body {
+                    write_html_row(out, "td", row)?;
+                }
+                writeln!(out, "</tbody>")?;
+                writeln!(out, "<tfoot>")?;
+                write_html_row(out, "td", total)?;
+                writeln!(out, "</tfoot>")?;
+                writeln!(out, "</table>")?;
+            }
+        }
+        Ok(())
+    }
+
+    fn table(&self) -> (Vec<String>, Vec<Vec<String>>) {
+        let mut header = vec!["month".to_owned(), "tx_type".to_owned()];
+        for status in STATUSES {
+            let name = status_name(status);
+            header.push(format!("{name}_count"));
+            header.push(format!("{name}_sum"));
+        }
+        header.push("count".to_owned());
+        header.push("sum".to_owned());
+
+        let mut rows = Vec::new();
+        let mut by_status = BTreeMap::<TxStatus, Aggregate>::new();
+        let mut row_key = None;
+        let mut row_cells = BTreeMap::<TxStatus, Aggregate>::new();
+        for (&(month, tx_type, status), val) in &self.cells {
+            if row_key.is_some_and(|key| key != (month, tx_type)) {
+                let (month, tx_type) = row_key.take().expect("Ключ проверен выше");
+                rows.push(table_row(
+                    month.to_string(),
+                    type_name(tx_type).to_owned(),
+                    &row_cells,
+                ));
+                row_cells.clear();
+            }
+            row_key = Some((month, tx_type));
+            row_cells.insert(status, *val);
+            by_status.entry(status).or_default().merge(val);
+        }
+        if let Some((month, tx_type)) = row_key {
+            rows.push(table_row(
+                month.to_string(),
+                type_name(tx_type).to_owned(),
+                &row_cells,
+            ));
+        }
+        rows.push(table_row("TOTAL".to_owned(), String::new(), &by_status));
+        (header, rows)
+    }
+}
+
+fn table_row(month: String, tx_type: String, cells: &BTreeMap<TxStatus, Aggregate>) -> Vec<String> {
+    let mut row = vec![month, tx_type];
+    let mut total = Aggregate::default();
+    for status in STATUSES {
+        let val = cells.get(&status).copied().unwrap_or_default();
+        row.push(val.count.to_string());
+        row.push(val.sum.to_string());
+        total.merge(&val);
+    }
+    row.push(total.count.to_string());
+    row.push(total.sum.to_string());
+    row
+}
+
+fn write_html_row<Out: Write>(out: &mut Out, tag: &str, cells: &[String]) -> Result<(), ParsError> {
+    write!(out, "<tr>")?;
+    for cell in cells {
+        write!(out, "<{tag}>{cell}</{tag}>")?;
+    }
+    writeln!(out, "</tr>")?;
+    Ok(())
+}
+
+fn type_name(val: TxType) -> &'static str {
+    match val {
+        TxType::Deposit => DEPOSIT,
+        TxType::Transfer => TRANSFER,
+        TxType::Withdrawal => WITHDRAWAL,
+    }
+}
+
+fn status_name(val: TxStatus) -> &'static str {
+    match val {
+        TxStatus::Success => SUCCESS,
+        TxStatus::Failure => FAILURE,
+        TxStatus::Pending => PENDING,
+    }
+}
+
+/// Подсчет сводной таблицы по оставшимся в потоке транзакциям
+///
+/// ```
+/// use fin_parser::format::Format;
+/// use fin_parser::generate::{GenerateOptions, Generator};
+/// use fin_parser::pivot::{PivotFormat, pivot};
+///
+/// let mut source = Generator::new(GenerateOptions::new().with_seed(7), 100).unwrap();
+/// let table = pivot(&mut source).unwrap();
+/// assert_eq!(table.total().count, 100);
+///
+/// let mut out = Vec::new();
+/// table.write(&mut out, PivotFormat::Markdown).unwrap();
+/// assert!(String::from_utf8(out).unwrap().starts_with("| month | tx_type |"));
+/// ```
+pub fn pivot<R: TransactionRead + ?Sized>(reader: &mut R) -> Result<Pivot, ParsError> {
+    let mut res = Pivot::new();
+    while let Some(tx) = reader.read_transaction()? {
+        res.add_tx(&tx);
+    }
+    Ok(res)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::tx;
+
+    fn txs_for_test() -> Vec<Transaction> {
+        // 30 сентября, 1 и 2 октября 2021
+        [
+            (1633003200, TxType::Deposit, TxStatus::Success, 100),
+            (1633089600, TxType::Deposit, TxStatus::Success, 200),
+            (1633089600, TxType::Deposit, TxStatus::Failure, 50),
+            (1633176000, TxType::Withdrawal, TxStatus::Pending, 70),
+        ]
+        .into_iter()
+        .enumerate()
+        .map(|(idx, (secs, tx_type, status, amount))| Transaction {
+            tx_type,
+            amount,
+            timestamp: DateTime::from_timestamp(secs, 0).unwrap(),
+            status,
+            ..tx(idx as u64)
+        })
+        .collect()
+    }
+
+    #[test]
+    fn test_pivot_cells() {
+        let mut table = Pivot::new();
+        for tx in &txs_for_test() {
+            table.add_tx(tx);
+        }
+        let sep = Month {
+            year: 2021,
+            month: 9,
+        };
+        let oct = Month {
+            year: 2021,
+            month: 10,
+        };
+        assert_eq!(table.months(), vec![sep, oct]);
+        assert_eq!(oct.to_string(), "2021-10");
+        assert_eq!(
+            table
+                .get(oct, TxType::Deposit, TxStatus::Success)
+                .map(|val| (val.count, val.sum)),
+            Some((1, 200))
+        );
+        assert!(
+            table
+                .get(sep, TxType::Withdrawal, TxStatus::Pending)
+                .is_none()
+        );
+        assert_eq!(table.total().sum, 420);
+
+        let mut merged = Pivot::new();
+        merged.merge(&table);
+        merged.merge(&table);
+        assert_eq!(merged.total().count, 8);
+    }
+
+    #[test]
+    fn test_pivot_render() {
+        let mut table = Pivot::new();
+        for tx in &txs_for_test() {
+            table.add_tx(tx);
+        }
+
+        let mut out = Vec::new();
+        table.write(&mut out, PivotFormat::Csv).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "month,tx_type,SUCCESS_count,SUCCESS_sum,FAILURE_count,FAILURE_sum,PENDING_count,PENDING_sum,count,sum\n\
+             2021-09,DEPOSIT,1,100,0,0,0,0,1,100\n\
+             2021-10,DEPOSIT,1,200,1,50,0,0,2,250\n\
+             2021-10,WITHDRAWAL,0,0,0,0,1,70,1,70\n\
+             TOTAL,,2,300,1,50,1,70,4,420\n"
+        );
+
+        let mut out = Vec::new();
+        table.write(&mut out, PivotFormat::Html).unwrap();
+        let html = String::from_utf8(out).unwrap();
+        assert!(html.contains("<tfoot>\n<tr><td>TOTAL</td><td></td><td>2</td>"));
+        assert_eq!(html.matches("<tr>").count(), 5);
+
+        assert_eq!("md".parse::<PivotFormat>().unwrap(), PivotFormat::Markdown);
+        assert!("pdf".parse::<PivotFormat>().is_err());
+    }
+}
@@ -0,0 +1,147 @@
+use super::constants::TX_ID;
+use super::error::ParsError;
+use super::reconcile::Field;
+use super::transaction::Transaction;
+use super::utils::quote_description;
+use std::io::Write;
+use std::str::FromStr;
+
+/// Столбец проекции
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum Column {
+    /// Идентификатор транзакции
+    TxId,
+    /// Остальные поля
+    Field(Field),
+}
+
+impl Column {
+    /// Имя столбца в заголовке csv
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::TxId => TX_ID,
+            Self::Field(field) => field.name(),
+        }
+    }
+
+    /// Значение столбца, см. [Field::value]
+    pub fn value(&self, tx: &Transaction) -> String {
+        match self {
+            Self::TxId => tx.tx_id.to_string(),
+            Self::Field(field) => field.value(tx),
+        }
+    }
+}
+
+/// Разбор имени поля в заголовке csv без учета регистра
+impl FromStr for Column {
+    type Err = ParsError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.eq_ignore_ascii_case(TX_ID) {
+            true => Ok(Self::TxId),
+            false => s.parse().map(Self::Field),
+        }
+    }
+}
+
+/// Проекция транзакций на выбранные поля в заданном порядке.
+/// Разбирается из списка имен полей через запятую, например `tx_id,amount,status`
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct Projection {
+    columns: Vec<Column>,
+}
+
+impl Projection {
+    /// Проекция на столбцы columns
+    pub fn new(columns: Vec<Column>) -> Self {
+        Self { columns }
+    }
+
+    /// Выбранные столбцы
+    pub fn columns(&self) -> &[Column] {
+        &self.columns
+    }
+
+    /// Запись строки заголовка csv
+    pub fn write_csv_header<Out: Write>(&self, out: &mut Out) -> Result<(), ParsError> {
+        let names: Vec<&str> = self.columns.iter().map(Column::name).collect();
+        writeln!(out, "{}", names.join(","))?;
+        Ok(())
+    }
+
+    /// Запись транзакции строкой csv. Описание заключается в кавычки, как в формате csv
+    pub fn write_csv<Out: Write>(&self, out: &mut Out, tx: &Transaction) -> Result<(), ParsError> {
+        let values: Vec<String> = self
+            .columns
+            .iter()
+            .map(|column| quoted(column, tx))
+            .collect();
+        writeln!(out, "{}", values.join(","))?;
+        Ok(())
+    }
+
+    /// Запись транзакции записью формата text из выбранных полей
+    pub fn write_text<Out: Write>(&self, out: &mut Out, tx: &Transaction) -> Result<(), ParsError> {
+        for column in &self.columns {
+            writeln!(out, "{}: {}", column.name(), quoted(column, tx))?;
+        }
+        writeln!(out)?;
+        Ok(())
+    }
+}
+
+impl FromStr for Projection {
+    type Err = ParsError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let columns = s
+            .split(',')
+            .map(|name| name.trim().parse())
+            .collect::<Result<Vec<Column>, ParsError>>()?;
+        Ok(Self::new(columns))
+    }
+}
+
+/// Значение столбца для записи: описание заключается в кавычки
+fn quoted(column: &Column, tx: &Transaction) -> String {
+    match column {
+        Column::Field(Field::Description) => quote_description(&tx.description),
+        _ => column.value(tx),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transaction::{TxStatus, TxType};
+    use chrono::DateTime;
+
+    #[test]
+    fn test_projection() {
+        let tx = Transaction {
+            tx_id: 7,
+            tx_type: TxType::Transfer,
+            from_user_id: 1,
+            to_user_id: 2,
+            amount: 300,
+            timestamp: DateTime::from_timestamp(1633036860, 0).unwrap(),
+            status: TxStatus::Pending,
+            description: "Record number 7".to_owned(),
+        };
+        let projection: Projection = "tx_id, STATUS,description,amount".parse().unwrap();
+        assert_eq!(projection.columns()[0], Column::TxId);
+
+        let mut out = Vec::new();
+        projection.write_csv_header(&mut out).unwrap();
+        projection.write_csv(&mut out, &tx).unwrap();
+        projection.write_text(&mut out, &tx).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "TX_ID,STATUS,DESCRIPTION,AMOUNT\n\
+            7,PENDING,\"Record number 7\",300\n\
+            TX_ID: 7\nSTATUS: PENDING\nDESCRIPTION: \"Record number 7\"\nAMOUNT: 300\n\n"
+        );
+        assert!("tx_id,balance".parse::<Projection>().is_err());
+    }
+}
@@ -0,0 +1,352 @@
+//! Разбор условий отбора вида `status = PENDING AND amount > 1000 AND timestamp >= 2021-10-01`.
+//!
+//! Грамматика:
+//! ```text
+//! expr    := and ("OR" and)*
+//! and     := unary ("AND" unary)*
+//! unary   := "NOT" unary | "(" expr ")" | field op value
+//! op      := "=" | "!=" | "<" | "<=" | ">" | ">=" | "~"
+//! ```
+//! Ключевые слова и имена полей не зависят от регистра. Значения описания
+//! заключаются в двойные кавычки, `~` ищет в описании регулярное выражение
+
+use super::constants::*;
+use super::error::ParsError;
+use super::filter::TxFilter;
+use super::transaction::{TxStatus, TxType};
+use chrono::{DateTime, NaiveDate, Utc};
+use std::ops::Bound;
+
+#[derive(Clone, PartialEq, Debug)]
+enum Token {
+    Word(String),
+    Quoted(String),
+    Op(Op),
+    Open,
+    Close,
+}
+
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Match,
+}
+
+fn query_error(msg: impl std::fmt::Display) -> ParsError {
+    ParsError::WrongFormat(format!("Ошибка в условии отбора: {msg}"))
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>, ParsError> {
+    let mut tokens = Vec::new();
+    let mut chars = input.chars().peekable();
+    while let Some(ch) = chars.next() {
+        let token = match ch {
+            ch if ch.is_whitespace() => continue,
+            '(' => Token::Open,
+            ')' => Token::Close,
+            '=' => Token::Op(Op::Eq),
+            '~' => Token::Op(Op::Match),
+            '!' if chars.next_if_eq(&'=').is_some() => Token::Op(Op::Ne),
+            '<' if chars.next_if_eq(&'=').is_some() => Token::Op(Op::Le),
+            '<' => Token::Op(Op::Lt),
+            '>' if chars.next_if_eq(&'=').is_some() => Token::Op(Op::Ge),
+            '>' => Token::Op(Op::Gt),
+            '"' => {
+                let mut val = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(ch) => val.push(ch),
+                            None => return Err(query_error("незакрытая кавычка")),
+                        },
+                        Some(ch) => val.push(ch),
+                        None => return Err(query_error("незакрытая кавычка")),
+                    }
+                }
+                Token::Quoted(val)
+            }
+            ch if is_word_char(ch) => {
+                let mut val = String::from(ch);
+                while let Some(ch) = chars.next_if(|ch| is_word_char(*ch)) {
+                    val.push(ch);
+                }
+                Token::Word(val)
+            }
+            ch => return Err(query_error(format!("неожиданный символ '{ch}'"))),
+        };
+        tokens.push(token);
+    }
+    Ok(tokens)
+}
+
+fn is_word_char(ch: char) -> bool {
+    ch.is_alphanumeric() || matches!(ch, '_' | '-' | '+' | ':' | '.')
+}
+
+struct QueryParser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl QueryParser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Result<Token, ParsError> {
+        let token = self
+            .tokens
+            .get(self.pos)
+            .cloned()
+            .ok_or_else(|| query_error("неожиданный конец выражения"))?;
+        self.pos += 1;
+        Ok(token)
+    }
+
+    fn next_keyword(&mut self, keyword: &str) -> bool {
+        let found =
+            matches!(self.peek(), Some(Token::Word(val)) if val.eq_ignore_ascii_case(keyword));
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn expr(&mut self) -> Result<TxFilter, ParsError> {
+        let mut filter = self.and()?;
+        while self.next_keyword("OR") {
+            filter = filter.or(self.and()?);
+        }
+        Ok(filter)
+    }
+
+    fn and(&mut self) -> Result<TxFilter, ParsError> {
+        let mut filter = self.unary()?;
+        while self.next_keyword("AND") {
+            filter = filter.and(self.unary()?);
+        }
+        Ok(filter)
+    }
+
+    fn unary(&mut self) -> Result<TxFilter, ParsError> {
+        if self.next_keyword("NOT") {
+            return Ok(self.unary()?.not());
+        }
+        if self.peek() == Some(&Token::Open) {
+            self.pos += 1;
+            let filter = self.expr()?;
+            if self.next()? != Token::Close {
+                return Err(query_error("ожидалась ')'"));
+            }
+            return Ok(filter);
+        }
+        self.comparison()
+    }
+
+    fn comparison(&mut self) -> Result<TxFilter, ParsError> {
+        let Token::Word(field) = self.next()? else {
+            return Err(query_error("ожидалось имя поля"));
+        };
+        let Token::Op(op) = self.next()? else {
+            return Err(query_error(format!("ожидался оператор после {field}")));
+        };
+        let value = match self.next()? {
+            Token::Word(val) | Token::Quoted(val) => val,
+            _ => return Err(query_error(format!("ожидалось значение поля {field}"))),
+        };
+
+        let field = field.to_ascii_uppercase();
+        let filter = match field.as_str() {
+            STATUS => equality(op, &field, TxFilter::status(parse_status(&value)?))?,
+            TX_TYPE | "TYPE" => equality(op, &field, TxFilter::tx_type(parse_type(&value)?))?,
+            FROM_USER_ID => equality(
+                op,
+                &field,
+                TxFilter::from_users([parse_num(&field, &value)?]),
+            )?,
+            TO_USER_ID => equality(op, &field, TxFilter::to_users([parse_num(&field, &value)?]))?,
+            "USER_ID" => equality(op, &field, TxFilter::users([parse_num(&field, &value)?]))?,
+            AMOUNT => {
+                let val = parse_num(&field, &value)?;
+                range(op, &field, val, TxFilter::Amount)?
+            }
+            TIMESTAMP => {
+                let val = parse_timestamp(&value)?;
+                range(op, &field, val, TxFilter::Time)?
+            }
+            DESCRIPTION => match op {
+                Op::Match => TxFilter::description(&value)?,
+                _ => {
+                    let exact = TxFilter::description(&format!("^{}$", regex::escape(&value)))?;
+                    equality(op, &field, exact)?
+                }
+            },
+            _ => return Err(query_error(format!("неизвестное поле {field}"))),
+        };
+        Ok(filter)
+    }
+}
+
+/// Условие для полей, допускающих только сравнение на равенство
+fn equality(op: Op, field: &str, filter: TxFilter) -> Result<TxFilter, ParsError> {
+    match op {
+        Op::Eq => Ok(filter),
+        Op::Ne => Ok(filter.not()),
+        _ => Err(query_error(format!(
+            "недопустимый оператор для поля {field}"
+        ))),
+    }
+}
+
+/// Условие для упорядоченных полей
+fn range<T: Copy>(
+    op: Op,
+    field: &str,
+    val: T,
+    filter: fn(Bound<T>, Bound<T>) -> TxFilter,
+) -> Result<TxFilter, ParsError> {
+    let res = match op {
+        Op::Eq => filter(Bound::Included(val), Bound::Included(val)),
+        Op::Ne => filter(Bound::Included(val), Bound::Included(val)).not(),
+        Op::Lt => filter(Bound::Unbounded, Bound::Excluded(val)),
+        Op::Le => filter(Bound::Unbounded, Bound::Included(val)),
+        Op::Gt => filter(Bound::Excluded(val), Bound::Unbounded),
+        Op::Ge => filter(Bound::Included(val), Bound::Unbounded),
+        Op::Match => {
+            return Err(query_error(format!(
+                "недопустимый оператор для поля {field}"
+            )));
+        }
+    };
+    Ok(res)
+}
+
+fn parse_status(value: &str) -> Result<TxStatus, ParsError> {
+    match value.to_ascii_uppercase().as_str() {
+        SUCCESS => Ok(TxStatus::Success),
+        FAILURE => Ok(TxStatus::Failure),
+        PENDING => Ok(TxStatus::Pending),
+        _ => Err(query_error(format!("неизвестный статус {value}"))),
+    }
+}
+
+fn parse_type(value: &str) -> Result<TxType, ParsError> {
+    match value.to_ascii_uppercase().as_str() {
+        DEPOSIT => Ok(TxType::Deposit),
+        TRANSFER => Ok(TxType::Transfer),
+        WITHDRAWAL => Ok(TxType::Withdrawal),
+        _ => Err(query_error(format!("неизвестный тип {value}"))),
+    }
+}
+
+fn parse_num<T: std::str::FromStr>(field: &str, value: &str) -> Result<T, ParsError> {
+    value
+        .parse()
+        .map_err(|_| query_error(format!("значение {value} поля {field} не является числом")))
+}
+
+/// Время задается датой (`2021-10-01`, полночь UTC), в формате RFC 3339
+/// или числом миллисекунд от начала эпохи
+fn parse_timestamp(value: &str) -> Result<DateTime<Utc>, ParsError> {
+    if let Ok(date) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        return Ok(date.and_time(Default::default()).and_utc());
+    }
+    if let Ok(time) = DateTime::parse_from_rfc3339(value) {
+        return Ok(time.with_timezone(&Utc));
+    }
+    value
+        .parse()
+        .ok()
+        .and_then(DateTime::from_timestamp_millis)
+        .ok_or_else(|| query_error(format!("некорректное время {value}")))
+}
+
+/// Разбор выражения в условие отбора
+pub fn parse(input: &str) -> Result<TxFilter, ParsError> {
+    let mut parser = QueryParser {
+        tokens: tokenize(input)?,
+        pos: 0,
+    };
+    if parser.peek().is_none() {
+        return Ok(TxFilter::Any);
+    }
+    let filter = parser.expr()?;
+    if let Some(token) = parser.peek() {
+        return Err(query_error(format!("лишний фрагмент {token:?}")));
+    }
+    Ok(filter)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transaction::Transaction;
+
+    fn tx_for_test() -> Transaction {
+        Transaction {
+            tx_id: 1,
+            tx_type: TxType::Transfer,
+            from_user_id: 10,
+            to_user_id: 20,
+            amount: 1500,
+            timestamp: DateTime::from_timestamp(1633046400, 0).unwrap(),
+            status: TxStatus::Pending,
+            description: "Rent \"October\"".to_owned(),
+        }
+    }
+
+    fn check(query: &str) -> bool {
+        parse(query).unwrap().matches_tx(&tx_for_test())
+    }
+
+    #[test]
+    fn test_query() {
+        assert!(check(""));
+        assert!(check(
+            "status = PENDING AND amount > 1000 AND timestamp >= 2021-10-01"
+        ));
+        assert!(!check("status = PENDING AND amount > 1500"));
+        assert!(check("amount >= 1500 and amount <= 1500 and amount != 1"));
+        assert!(check("type = deposit OR tx_type = TRANSFER"));
+        assert!(check("NOT status = SUCCESS"));
+        assert!(!check("NOT (status = PENDING OR amount < 0)"));
+        assert!(check(
+            "(status = SUCCESS OR amount>1000) AND from_user_id=10"
+        ));
+        assert!(check("user_id = 20 AND to_user_id != 10"));
+        assert!(check(
+            "timestamp < 2021-10-01T00:00:01Z AND timestamp = 1633046400000"
+        ));
+        assert!(check(
+            r#"description ~ "^Rent" AND description = "Rent \"October\"""#
+        ));
+        assert!(!check(r#"description = "Rent""#));
+    }
+
+    #[test]
+    fn test_query_errors() {
+        for query in [
+            "status",
+            "status =",
+            "status = DONE",
+            "status > PENDING",
+            "amount ~ 1",
+            "amount = abc",
+            "timestamp >= yesterday",
+            "price = 1",
+            "(status = PENDING",
+            "status = PENDING amount = 1",
+            r#"description ~ "("#,
+            r#"description = "open"#,
+            "amount # 1",
+        ] {
+            assert!(parse(query).is_err(), "{query}");
+        }
+    }
+}
@@ -0,0 +1,791 @@
+use super::constants::*;
+use super::error::ParsError;
+use super::format::TransactionRead;
+use super::transaction::{Transaction, TxStatus, TxType};
+use super::utils::{invalid_enum_value, parse_number};
+use chrono::{DateTime, TimeDelta};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+#[cfg(feature = "serde")]
+use std::io::BufRead;
+use std::io::Write;
+use std::str::FromStr;
+
+/// Поле транзакции, по которому может быть расхождение
+#[derive(Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "SCREAMING_SNAKE_CASE"))]
+pub enum Field {
+    /// Тип транзакции
+    TxType,
+    /// Инициатор
+    FromUserId,
+    /// Получатель
+    ToUserId,
+    /// Сумма
+    Amount,
+    /// Время
+    Timestamp,
+    /// Статус
+    Status,
+    /// Описание
+    Description,
+}
+
+impl Field {
+    /// Имя поля в заголовке csv
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::TxType => TX_TYPE,
+            Self::FromUserId => FROM_USER_ID,
+            Self::ToUserId => TO_USER_ID,
+            Self::Amount => AMOUNT,
+            Self::Timestamp => TIMESTAMP,
+            Self::Status => STATUS,
+            Self::Description => DESCRIPTION,
+        }
+    }
+
+    /// Значение поля транзакции в виде строки: тип и статус как в csv,
+    /// время в миллисекундах, описание без кавычек
+    pub fn value(&self, tx: &Transaction) -> String {
+        match self {
+            Self::TxType => type_name(tx.tx_type).to_owned(),
+            Self::FromUserId => tx.from_user_id.to_string(),
+            Self::ToUserId => tx.to_user_id.to_string(),
+            Self::Amount => tx.amount.to_string(),
+            Self::Timestamp => tx.timestamp.timestamp_millis().to_string(),
+            Self::Status => status_name(tx.status).to_owned(),
+            Self::Description => tx.description.clone(),
+        }
+    }
+
+    /// Установка значения поля транзакции из строки в виде [Field::value]
+    pub fn set_value(&self, tx: &mut Transaction, val: &str) -> Result<(), ParsError> {
+        match self {
+            Self::TxType => {
+                tx.tx_type = match val {
+                    DEPOSIT => TxType::Deposit,
+                    TRANSFER => TxType::Transfer,
+                    WITHDRAWAL => TxType::Withdrawal,
+                    _ => return Err(invalid_enum_value(TX_TYPE, val)),
+                }
+            }
+            Self::FromUserId => tx.from_user_id = parse_number(FROM_USER_ID, val)?,
+            Self::ToUserId => tx.to_user_id = parse_number(TO_USER_ID, val)?,
+            Self::Amount => tx.amount = parse_number(AMOUNT, val)?,
+            Self::Timestamp => {
+                tx.timestamp = DateTime::from_timestamp_millis(parse_number(TIMESTAMP, val)?)
+                    .ok_or_else(|| ParsError::InvalidNumber {
+                        field: TIMESTAMP.to_owned(),
+                        value: val.to_owned(),
+                    })?
+            }
+            Self::Status => {
+                tx.status = match val {
+                    SUCCESS => TxStatus::Success,
+                    FAILURE => TxStatus::Failure,
+                    PENDING => TxStatus::Pending,
+                    _ => return Err(invalid_enum_value(STATUS, val)),
+                }
+            }
+            Self::Description => tx.description = val.to_owned(),
+        }
+        Ok(())
+    }
+}
+
+/// Разбор имени поля в заголовке csv без учета регистра, например `AMOUNT` или `description`
+impl FromStr for Field {
+    type Err = ParsError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let fields = [
+            Self::TxType,
+            Self::FromUserId,
+            Self::ToUserId,
+            Self::Amount,
+            Self::Timestamp,
+            Self::Status,
+            Self::Description,
+        ];
+        fields
+            .into_iter()
+            .find(|field| field.name().eq_ignore_ascii_case(s))
+            .ok_or_else(|| ParsError::WrongFormat(format!("Неизвестное поле транзакции: {s}")))
+    }
+}
+
+/// Настройки сравнения транзакций: игнорируемые поля и допустимые отклонения
+#[derive(Clone, Debug, Default)]
+pub struct CompareOptions {
+    ignore: HashSet<Field>,
+    timestamp_tolerance: TimeDelta,
+    amount_tolerance: u64,
+}
+
+impl CompareOptions {
+    /// Точное сравнение всех полей
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Поле не сравнивается
+    pub fn ignore(mut self, field: Field) -> Self {
+        self.ignore.insert(field);
+        self
+    }
+
+    /// Допустимое отклонение времени, например для округленных при выгрузке значений
+    pub fn with_timestamp_tolerance(mut self, tolerance: TimeDelta) -> Self {
+        self.timestamp_tolerance = tolerance.abs();
+        self
+    }
+
+    /// Допустимое отклонение суммы
+    pub fn with_amount_tolerance(mut self, tolerance: u64) -> Self {
+        self.amount_tolerance = tolerance;
+        self
+    }
+}
+
+/// Расхождение значений одного поля
+#[derive(Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(deny_unknown_fields))]
+pub struct FieldDiff {
+    /// Поле
+    pub field: Field,
+    /// Значение в левом потоке
+    pub left: String,
+    /// Значение в правом потоке
+    pub right: String,
+}
+
+/// Транзакция, присутствующая в обоих потоках с разными значениями полей
+#[derive(Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Mismatch {
+    /// Идентификатор транзакции
+    pub tx_id: u64,
+    /// Различающиеся поля в порядке [Field]
+    pub fields: Vec<FieldDiff>,
+}
+
+/// Изменения полей между двумя версиями транзакции с одним tx_id: исправление,
+/// которое применяется к прежней версии вместо замены транзакции целиком.
+/// В [FieldDiff] left — прежнее значение, right — новое. Сверка дает исправления
+/// левого потока по правому ([ReconcileReport::tx_diffs])
+///
+/// ```
+/// use fin_parser::generate::{GenerateOptions, Generator};
+/// use fin_parser::reconcile::TxDiff;
+///
+/// let mut old = Generator::new(GenerateOptions::new().with_seed(1), 1).unwrap().next().unwrap();
+/// let mut new = old.clone();
+/// new.amount += 100;
+/// let diff = TxDiff::between(&old, &new);
+/// diff.apply(&mut old).unwrap();
+/// assert_eq!(old, new);
+/// ```
+#[derive(Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(deny_unknown_fields))]
+pub struct TxDiff {
+    /// Идентификатор транзакции
+    pub tx_id: u64,
+    /// Измененные поля в порядке [Field]
+    pub changes: Vec<FieldDiff>,
+}
+
+impl TxDiff {
+    /// Изменения, превращающие old в new. tx_id берется из old
+    pub fn between(old: &Transaction, new: &Transaction) -> Self {
+        Self {
+            tx_id: old.tx_id,
+            changes: diff_fields(old, new),
+        }
+    }
+
+    /// Версии транзакции совпадают
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// Применение изменений к транзакции. Транзакция должна иметь тот же tx_id,
+    /// а изменяемые поля — прежние значения, иначе транзакция не меняется
+    /// и возвращается ошибка
+    pub fn apply(&self, tx: &mut Transaction) -> Result<(), ParsError> {
+        if tx.tx_id != self.tx_id {
+            return Err(ParsError::WrongFormat(format!(
+                "Исправление транзакции {} не применимо к транзакции {}",
+                self.tx_id, tx.tx_id
+            )));
+        }
+        let mut res = tx.clone();
+        for change in &self.changes {
+            let current = change.field.value(tx);
+            if current != change.left {
+                return Err(ParsError::WrongFormat(format!(
+                    "Поле {} транзакции {} имеет значение {current} вместо {}",
+                    change.field.name(),
+                    self.tx_id,
+                    change.left
+                )));
+            }
+            change.field.set_value(&mut res, &change.right)?;
+        }
+        *tx = res;
+        Ok(())
+    }
+
+    /// Запись изменений в json вида
+    /// `{"tx_id": 2, "changes": [{"field": "AMOUNT", "left": "200", "right": "250"}]}`
+    /// одной строкой. Запись не требует feature `serde`, обратный разбор — `read_tx_diffs`
+    /// с feature `serde`
+    pub fn write_json<Out: Write>(&self, out: &mut Out) -> Result<(), ParsError> {
+        write!(out, "{{\"tx_id\":{},\"changes\":[", self.tx_id)?;
+        write_field_diffs(out, &self.changes)?;
+        writeln!(out, "]}}")?;
+        Ok(())
+    }
+}
+
+/// Чтение исправлений в формате json lines, записанных
+/// [ReconcileReport::write_tx_diffs]. Пустые строки пропускаются
+#[cfg(feature = "serde")]
+pub fn read_tx_diffs<In: BufRead>(input: In) -> Result<Vec<TxDiff>, ParsError> {
+    let mut res = Vec::new();
+    for line in input.lines() {
+        let line = line?;
+        if !line.trim().is_empty() {
+            res.push(serde_json::from_str(&line).map_err(|e| {
+                ParsError::WrongFormat(format!("Некорректный json исправления: {e}"))
+            })?);
+        }
+    }
+    Ok(res)
+}
+
+impl From<Mismatch> for TxDiff {
+    fn from(val: Mismatch) -> Self {
+        Self {
+            tx_id: val.tx_id,
+            changes: val.fields,
+        }
+    }
+}
+
+/// Результат сверки двух потоков по tx_id. Списки упорядочены по tx_id
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ReconcileReport {
+    /// Количество совпавших транзакций
+    pub matched: u64,
+    /// Транзакции с расхождениями
+    pub mismatched: Vec<Mismatch>,
+    /// Идентификаторы транзакций, отсутствующих в левом потоке
+    pub missing_left: Vec<u64>,
+    /// Идентификаторы транзакций, отсутствующих в правом потоке
+    pub missing_right: Vec<u64>,
+}
+
+impl ReconcileReport {
+    /// Потоки содержат одни и те же транзакции
+    pub fn is_clean(&self) -> bool {
+        self.mismatched.is_empty() && self.missing_left.is_empty() && self.missing_right.is_empty()
+    }
+
+    /// Текстовый отчет, в котором для каждого списка (расхождения, отсутствующие слева
+    /// и справа) выводится не более max_diffs транзакций. Количество расхождений
+    /// выводится полностью
+    pub fn display_limited(&self, max_diffs: usize) -> LimitedReport<'_> {
+        LimitedReport {
+            report: self,
+            max_diffs,
+        }
+    }
+
+    /// Запись отчета в csv со столбцами `TX_ID,RESULT,FIELD,LEFT,RIGHT`.
+    /// RESULT принимает значения MISMATCH (по строке на поле), MISSING_LEFT, MISSING_RIGHT.
+    /// Совпавшие транзакции в отчет не попадают
+    pub fn write_csv<Out: Write>(&self, out: &mut Out) -> Result<(), ParsError> {
+        writeln!(out, "{TX_ID},RESULT,FIELD,LEFT,RIGHT")?;
+        for mismatch in &self.mismatched {
+            for diff in &mismatch.fields {
+                writeln!(
+                    out,
+                    "{},MISMATCH,{},{},{}",
+                    mismatch.tx_id,
+                    diff.field.name(),
+                    quote(&diff.left),
+                    quote(&diff.right)
+                )?;
+            }
+        }
+        for tx_id in &self.missing_left {
+            writeln!(out, "{tx_id},MISSING_LEFT,,,")?;
+        }
+        for tx_id in &self.missing_right {
+            writeln!(out, "{tx_id},MISSING_RIGHT,,,")?;
+        }
+        Ok(())
+    }
+
+    /// Исправления транзакций левого потока, приводящие их к правому потоку,
+    /// в порядке tx_id. Отсутствующие в одном из потоков транзакции не входят
+    pub fn tx_diffs(&self) -> Vec<TxDiff> {
+        self.mismatched.iter().cloned().map(TxDiff::from).collect()
+    }
+
+    /// Запись исправлений [ReconcileReport::tx_diffs] в формате json lines:
+    /// по строке [TxDiff::write_json] на транзакцию
+    pub fn write_tx_diffs<Out: Write>(&self, out: &mut Out) -> Result<(), ParsError> {
+        for diff in self.tx_diffs() {
+            diff.write_json(out)?;
+        }
+        Ok(())
+    }
+
+    /// Запись отчета в json вида
+    /// `{"matched": 2, "mismatched": [{"tx_id": 1, "fields": [{"field": "AMOUNT", "left": "100", "right": "150"}]}], "missing_left": [], "missing_right": [4]}`.
+    /// Имена полей совпадают с заголовком csv. Запись не требует feature `serde`
+    pub fn write_json<Out: Write>(&self, out: &mut Out) -> Result<(), ParsError> {
+        write!(out, "{{\"matched\":{},\"mismatched\":[", self.matched)?;
+        for (idx, mismatch) in self.mismatched.iter().enumerate() {
+            let sep = if idx == 0 { "" } else { "," };
+            write!(out, "{sep}{{\"tx_id\":{},\"fields\":[", mismatch.tx_id)?;
+            write_field_diffs(out, &mismatch.fields)?;
+            write!(out, "]}}")?;
+        }
+        writeln!(
+            out,
+            "],\"missing_left\":{:?},\"missing_right\":{:?}}}",
+            self.missing_left, self.missing_right
+        )?;
+        Ok(())
+    }
+}
+
+impl fmt::Display for ReconcileReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.display_limited(usize::MAX).fmt(f)
+    }
+}
+
+/// Текстовый отчет сверки с ограничением количества выводимых транзакций,
+/// см. [ReconcileReport::display_limited]
+pub struct LimitedReport<'a> {
+    report: &'a ReconcileReport,
+    max_diffs: usize,
+}
+
+impl fmt::Display for LimitedReport<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let report = self.report;
+        let limit = |ids: &[u64]| ids.len().min(self.max_diffs);
+        writeln!(f, "Совпало: {}", report.matched)?;
+        writeln!(f, "Расхождений: {}", report.mismatched.len())?;
+        for mismatch in report.mismatched.iter().take(self.max_diffs) {
+            for diff in &mismatch.fields {
+                writeln!(
+                    f,
+                    "  {}: {} {} != {}",
+                    mismatch.tx_id,
+                    diff.field.name(),
+                    diff.left,
+                    diff.right
+                )?;
+            }
+        }
+        let missing_left = &report.missing_left[..limit(&report.missing_left)];
+        let missing_right = &report.missing_right[..limit(&report.missing_right)];
+        writeln!(f, "Нет слева: {missing_left:?}")?;
+        writeln!(f, "Нет справа: {missing_right:?}")?;
+        let truncated = report.mismatched.len() > self.max_diffs
+            || missing_left.len() < report.missing_left.len()
+            || missing_right.len() < report.missing_right.len();
+        if truncated {
+            writeln!(
+                f,
+                "Показано не более {} транзакций каждого списка (нет слева: {}, нет справа: {})",
+                self.max_diffs,
+                report.missing_left.len(),
+                report.missing_right.len()
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Строка json в кавычках с экранированием специальных символов
+pub fn json_string(val: &str) -> String {
+    let mut res = String::with_capacity(val.len() + 2);
+    res.push('"');
+    for ch in val.chars() {
+        match ch {
+            '"' => res.push_str("\\\""),
+            '\\' => res.push_str("\\\\"),
+            '\n' => res.push_str("\\n"),
+            '\r' => res.push_str("\\r"),
+            '\t' => res.push_str("\\t"),
+            ch if ch.is_control() => res.push_str(&format!("\\u{:04x}", ch as u32)),
+            ch => res.push(ch),
+        }
+    }
+    res.push('"');
+    res
+}
+
+fn write_field_diffs<Out: Write>(out: &mut Out, diffs: &[FieldDiff]) -> Result<(), ParsError> {
+    for (idx, diff) in diffs.iter().enumerate() {
+        let sep = if idx == 0 { "" } else { "," };
+        write!(
+            out,
+            "{sep}{{\"field\":\"{}\",\"left\":{},\"right\":{}}}",
+            diff.field.name(),
+            json_string(&diff.left),
+            json_string(&diff.right)
+        )?;
+    }
+    Ok(())
+}
+
+fn quote(val: &str) -> String {
+    format!("\"{}\"", val.replace('"', "\"\""))
+}
+
+pub(crate) fn type_name(val: TxType) -> &'static str {
+    match val {
+        TxType::Deposit => DEPOSIT,
+        TxType::Transfer => TRANSFER,
+        TxType::Withdrawal => WITHDRAWAL,
+    }
+}
+
+pub(crate) fn status_name(val: TxStatus) -> &'static str {
+    match val {
+        TxStatus::Success => SUCCESS,
+        TxStatus::Failure => FAILURE,
+        TxStatus::Pending => PENDING,
+    }
+}
+
+/// Сравнение полей двух транзакций с одним tx_id
+pub fn diff_fields(left: &Transaction, right: &Transaction) -> Vec<FieldDiff> {
+    diff_fields_with(left, right, &CompareOptions::default())
+}
+
+/// Сравнение полей двух транзакций с одним tx_id с учетом настроек сравнения
+pub fn diff_fields_with(
+    left: &Transaction,
+    right: &Transaction,
+    options: &CompareOptions,
+) -> Vec<FieldDiff> {
+    let mut res = Vec::new();
+    let mut check = |field: Field, differ: bool| {
+        if differ && !options.ignore.contains(&field) {
+            res.push(FieldDiff {
+                field,
+                left: field.value(left),
+                right: field.value(right),
+            });
+        }
+    };
+    check(Field::TxType, left.tx_type != right.tx_type);
+    check(Field::FromUserId, left.from_user_id != right.from_user_id);
+    check(Field::ToUserId, left.to_user_id != right.to_user_id);
+    check(
+        Field::Amount,
+        left.amount.abs_diff(right.amount) > options.amount_tolerance,
+    );
+    check(
+        Field::Timestamp,
+        (left.timestamp - right.timestamp).abs() > options.timestamp_tolerance,
+    );
+    check(Field::Status, left.status != right.status);
+    check(Field::Description, left.description != right.description);
+    res
+}
+
+/// Сверка двух потоков по tx_id независимо от порядка записей.
+/// Левый поток целиком загружается в память, правый читается по одной транзакции.
+/// Повтор tx_id внутри одного потока приводит к ошибке [ParsError::DuplicateTxId]
+pub fn reconcile<L, R>(left: &mut L, right: &mut R) -> Result<ReconcileReport, ParsError>
+where
+    L: TransactionRead + ?Sized,
+    R: TransactionRead + ?Sized,
+{
+    reconcile_with(left, right, &CompareOptions::default())
+}
+
+/// Сверка двух потоков по tx_id с учетом настроек сравнения, см. [reconcile]
+pub fn reconcile_with<L, R>(
+    left: &mut L,
+    right: &mut R,
+    options: &CompareOptions,
+) -> Result<ReconcileReport, ParsError>
+where
+    L: TransactionRead + ?Sized,
+    R: TransactionRead + ?Sized,
+{
+    let mut pending: HashMap<u64, Transaction> = HashMap::new();
+    while let Some(tx) = left.read_transaction()? {
+        let tx_id = tx.tx_id;
+        if pending.insert(tx_id, tx).is_some() {
+            return Err(ParsError::DuplicateTxId { tx_id });
+        }
+    }
+
+    let mut report = ReconcileReport::default();
+    let mut seen = HashSet::new();
+    while let Some(tx) = right.read_transaction()? {
+        if !seen.insert(tx.tx_id) {
+            return Err(ParsError::DuplicateTxId { tx_id: tx.tx_id });
+        }
+        match pending.remove(&tx.tx_id) {
+            Some(val) => {
+                let fields = diff_fields_with(&val, &tx, options);
+                if fields.is_empty() {
+                    report.matched += 1;
+                } else {
+                    report.mismatched.push(Mismatch {
+                        tx_id: tx.tx_id,
+                        fields,
+                    });
+                }
+            }
+            None => report.missing_left.push(tx.tx_id),
+        }
+    }
+    report.missing_right = pending.into_keys().collect();
+
+    report.mismatched.sort_unstable_by_key(|val| val.tx_id);
+    report.missing_left.sort_unstable();
+    report.missing_right.sort_unstable();
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::Format;
+    use crate::test_util::{self, reader};
+
+    fn tx(tx_id: u64, amount: i64, description: &str) -> Transaction {
+        Transaction {
+            amount,
+            description: description.to_owned(),
+            ..test_util::tx(tx_id)
+        }
+    }
+
+    fn report_for_test() -> ReconcileReport {
+        let mut left = reader(
+            &[
+                tx(1, 100, "a"),
+                tx(2, 200, "b"),
+                tx(3, 300, "c"),
+                tx(4, 1, ""),
+            ],
+            Format::Bin,
+        );
+        let mut changed = tx(2, 250, "b \"new\"");
+        changed.status = TxStatus::Pending;
+        let mut right = reader(
+            &[tx(5, 1, ""), tx(3, 300, "c"), changed, tx(1, 100, "a")],
+            Format::Text,
+        );
+        reconcile(&mut left, &mut right).unwrap()
+    }
+
+    #[test]
+    fn test_reconcile() {
+        let report = report_for_test();
+        assert_eq!(report.matched, 2);
+        assert_eq!(report.missing_left, [5]);
+        assert_eq!(report.missing_right, [4]);
+        assert_eq!(report.mismatched.len(), 1);
+        let fields: Vec<Field> = report.mismatched[0]
+            .fields
+            .iter()
+            .map(|diff| diff.field)
+            .collect();
+        assert_eq!(fields, [Field::Amount, Field::Status, Field::Description]);
+        assert!(!report.is_clean());
+        assert_eq!(
+            report.display_limited(0).to_string(),
+            "Совпало: 2\n\
+            Расхождений: 1\n\
+            Нет слева: []\n\
+            Нет справа: []\n\
+            Показано не более 0 транзакций каждого списка (нет слева: 1, нет справа: 1)\n"
+        );
+
+        let mut out = Vec::new();
+        report.write_csv(&mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "TX_ID,RESULT,FIELD,LEFT,RIGHT\n\
+            2,MISMATCH,AMOUNT,\"200\",\"250\"\n\
+            2,MISMATCH,STATUS,\"SUCCESS\",\"PENDING\"\n\
+            2,MISMATCH,DESCRIPTION,\"b\",\"b \"\"new\"\"\"\n\
+            5,MISSING_LEFT,,,\n\
+            4,MISSING_RIGHT,,,\n"
+        );
+    }
+
+    #[test]
+    fn test_write_json() {
+        let mut out = Vec::new();
+        report_for_test().write_json(&mut out).unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&out).unwrap();
+        assert_eq!(json["matched"], 2);
+        assert_eq!(json["mismatched"][0]["tx_id"], 2);
+        assert_eq!(json["mismatched"][0]["fields"][0]["field"], "AMOUNT");
+        assert_eq!(json["mismatched"][0]["fields"][2]["right"], "b \"new\"");
+        assert_eq!(json["missing_left"][0], 5);
+        assert_eq!(json["missing_right"][0], 4);
+    }
+
+    #[test]
+    fn test_reconcile_duplicates() {
+        let txs = [tx(1, 100, ""), tx(1, 100, "")];
+        let res = reconcile(
+            &mut reader(&txs, Format::Bin),
+            &mut reader(&[], Format::Bin),
+        );
+        assert!(matches!(res, Err(ParsError::DuplicateTxId { tx_id: 1 })));
+        let res = reconcile(
+            &mut reader(&[], Format::Bin),
+            &mut reader(&txs, Format::Bin),
+        );
+        assert!(matches!(res, Err(ParsError::DuplicateTxId { tx_id: 1 })));
+
+        let report = reconcile(
+            &mut reader(&txs[..1], Format::Bin),
+            &mut reader(&txs[..1], Format::Csv),
+        )
+        .unwrap();
+        assert!(report.is_clean());
+    }
+
+    #[test]
+    fn test_compare_options() {
+        let left = tx(1, 100, "Record");
+        let mut right = tx(1, 103, "record");
+        right.timestamp += TimeDelta::milliseconds(900);
+        assert_eq!(diff_fields(&left, &right).len(), 3);
+
+        let options = CompareOptions::new()
+            .ignore("description".parse().unwrap())
+            .with_timestamp_tolerance(TimeDelta::seconds(1))
+            .with_amount_tolerance(3);
+        assert!(diff_fields_with(&left, &right, &options).is_empty());
+        let options = options.with_amount_tolerance(2);
+        let fields = diff_fields_with(&left, &right, &options);
+        assert_eq!(fields.len(), 1);
+        assert_eq!(fields[0].field, Field::Amount);
+        assert!("TX_ID".parse::<Field>().is_err());
+    }
+
+    #[test]
+    fn test_tx_diff() {
+        let diffs = report_for_test().tx_diffs();
+        assert_eq!(diffs.len(), 1);
+        let mut old = tx(2, 200, "b");
+        let mut new = tx(2, 250, "b \"new\"");
+        new.status = TxStatus::Pending;
+        assert_eq!(diffs[0], TxDiff::between(&old, &new));
+        diffs[0].apply(&mut old).unwrap();
+        assert_eq!(old, new);
+        // Повторное применение: прежние значения уже не совпадают
+        assert!(diffs[0].apply(&mut old).is_err());
+        assert_eq!(old, new);
+        assert!(diffs[0].apply(&mut tx(3, 200, "b")).is_err());
+
+        let mut moved = tx(2, 200, "b");
+        moved.tx_type = TxType::Withdrawal;
+        moved.timestamp += TimeDelta::milliseconds(1500);
+        let diff = TxDiff::between(&tx(2, 200, "b"), &moved);
+        let mut old = tx(2, 200, "b");
+        diff.apply(&mut old).unwrap();
+        assert_eq!(old, moved);
+    }
+
+    #[test]
+    fn test_tx_diff_json() {
+        let mut out = Vec::new();
+        report_for_test().write_tx_diffs(&mut out).unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&out).unwrap();
+        assert_eq!(json["tx_id"], 2);
+        assert_eq!(json["changes"][0]["field"], "AMOUNT");
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_read_tx_diffs() {
+        let mut out = Vec::new();
+        report_for_test().write_tx_diffs(&mut out).unwrap();
+        let mut moved = tx(7, 100, "строка\t\"в кавычках\" \\ 😀\u{1}");
+        moved.tx_type = TxType::Withdrawal;
+        let diff = TxDiff::between(&tx(7, 100, ""), &moved);
+        diff.write_json(&mut out).unwrap();
+        out.extend_from_slice(b"\n");
+        let diffs = read_tx_diffs(out.as_slice()).unwrap();
+        assert_eq!(diffs, [report_for_test().tx_diffs().remove(0), diff]);
+
+        let spaced =
+            r#" { "changes" : [ { "right": "1", "left": "2", "field": "AMOUNT" } ], "tx_id": 3 } "#;
+        let diffs = read_tx_diffs(format!("\n{spaced}\n").as_bytes()).unwrap();
+        assert_eq!(
+            (diffs[0].tx_id, diffs[0].changes[0].field),
+            (3, Field::Amount)
+        );
+        for bad in [
+            "{",
+            r#"{"changes":[]}"#,
+            r#"{"tx_id":1,"changes":[]} x"#,
+            r#"{"tx_id":1,"changes":[{"field":"TX_ID","left":"1","right":"2"}]}"#,
+            r#"{"tx_id":1,"changes":[{"field":"AMOUNT","left":"1\q","right":"2"}]}"#,
+            r#"{"tx_id":1,"changes":[{"field":"AMOUNT","left":"\ud800\u0041","right":"2"}]}"#,
+            r#"{"tx_id":1,"changes":[{"field":"AMOUNT","left":"\udc00","right":"2"}]}"#,
+            r#"{"tx_id":1,"changes":[{"field":"AMOUNT","left":"1","right":"2","extra":0}]}"#,
+            r#"{"tx_id":1,"extra":0}"#,
+        ] {
+            assert!(
+                matches!(
+                    read_tx_diffs(bad.as_bytes()),
+                    Err(ParsError::WrongFormat(_))
+                ),
+                "{bad}"
+            );
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serialize() {
+        let json = serde_json::to_value(report_for_test()).unwrap();
+        let mut out = Vec::new();
+        report_for_test().write_tx_diffs(&mut out).unwrap();
+        let diff: TxDiff = serde_json::from_slice(&out).unwrap();
+        assert_eq!(diff, report_for_test().tx_diffs().remove(0));
+        assert_eq!(json["matched"], 2);
+        assert_eq!(json["mismatched"][0]["fields"][0]["field"], "AMOUNT");
+        // serde и write_json пишут поле одинаково, именем столбца csv
+        for name in [
+            TX_TYPE,
+            FROM_USER_ID,
+            TO_USER_ID,
+            AMOUNT,
+            TIMESTAMP,
+            STATUS,
+            DESCRIPTION,
+        ] {
+            let field: Field = name.parse().unwrap();
+            assert_eq!(serde_json::to_value(field).unwrap(), name);
+        }
+        assert_eq!(json["missing_right"][0], 4);
+    }
+}
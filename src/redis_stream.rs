@@ -0,0 +1,236 @@
+use super::error::ParsError;
+use super::format::{TransactionRead, TransactionWrite};
+use super::options::{ReaderOptions, WriterOptions};
+use super::text_format;
+use super::transaction::Transaction;
+use redis::streams::{StreamMaxlen, StreamReadOptions, StreamReadReply};
+use redis::{Commands, Connection, RedisError};
+use std::collections::VecDeque;
+use std::time::Duration;
+
+/// Количество записей, запрашиваемых одной командой XREAD
+pub const DEFAULT_READ_COUNT: usize = 512;
+
+/// Запись транзакций в поток Redis командой XADD. Каждая транзакция становится
+/// записью потока с полями формата text: `TX_ID`, `TX_TYPE`, ..., `DESCRIPTION`
+/// (описание в кавычках)
+pub struct StreamWriter {
+    connection: Connection,
+    key: String,
+    max_len: Option<usize>,
+    options: WriterOptions,
+    last_id: Option<String>,
+}
+
+impl StreamWriter {
+    /// Подключение к Redis по адресу вида `redis://host:6379/0` для записи в поток key
+    pub fn connect(url: &str, key: &str) -> Result<Self, ParsError> {
+        Ok(Self {
+            connection: connect(url)?,
+            key: key.to_owned(),
+            max_len: None,
+            options: WriterOptions::default(),
+            last_id: None,
+        })
+    }
+
+    /// Приблизительное ограничение длины потока (`MAXLEN ~`): старые записи удаляются,
+    /// чтобы поток служил буфером ограниченного размера
+    pub fn with_max_len(mut self, max_len: usize) -> Self {
+        self.max_len = Some(max_len);
+        self
+    }
+
+    /// Настройки записи полей, например единица времени TIMESTAMP
+    pub fn with_options(mut self, options: WriterOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Идентификатор последней добавленной записи потока
+    pub fn last_id(&self) -> Option<&str> {
+        self.last_id.as_deref()
+    }
+}
+
+impl TransactionWrite for StreamWriter {
+    fn write_transaction(&mut self, tx: &Transaction) -> Result<(), ParsError> {
+        let fields = text_format::to_fields(tx, &self.options);
+        let id: Option<String> = match self.max_len {
+            Some(max_len) => {
+                self.connection
+                    .xadd_maxlen(&self.key, StreamMaxlen::Approx(max_len), "*", &fields)
+            }
+            None => self.connection.xadd(&self.key, "*", &fields),
+        }
+        .map_err(redis_error)?;
+        self.last_id = id;
+        Ok(())
+    }
+
+    /// Запись набора транзакций одним конвейером команд
+    fn write_batch(&mut self, txs: &[Transaction]) -> Result<(), ParsError> {
+        let mut pipe = redis::pipe();
+        for tx in txs {
+            let fields = text_format::to_fields(tx, &self.options);
+            match self.max_len {
+                Some(max_len) => {
+                    pipe.xadd_maxlen(&self.key, StreamMaxlen::Approx(max_len), "*", &fields)
+                }
+                None => pipe.xadd(&self.key, "*", &fields),
+            };
+        }
+        let ids: Vec<Option<String>> = pipe.query(&mut self.connection).map_err(redis_error)?;
+        if let Some(id) = ids.into_iter().next_back() {
+            self.last_id = id;
+        }
+        Ok(())
+    }
+}
+
+/// Чтение транзакций из потока Redis командой XREAD, начиная с записи после
+/// заданного идентификатора (по умолчанию с начала потока). Без ожидания чтение
+/// заканчивается на последней записи потока, с ожиданием ([StreamReader::with_block])
+/// концом считается отсутствие новых записей за время ожидания
+pub struct StreamReader {
+    connection: Connection,
+    key: String,
+    last_id: String,
+    count: usize,
+    block: Option<Duration>,
+    options: ReaderOptions,
+    buffer: VecDeque<(String, Vec<(String, String)>)>,
+}
+
+impl StreamReader {
+    /// Подключение к Redis по адресу вида `redis://host:6379/0` для чтения потока key
+    pub fn connect(url: &str, key: &str) -> Result<Self, ParsError> {
+        Ok(Self {
+            connection: connect(url)?,
+            key: key.to_owned(),
+            last_id: "0".to_owned(),
+            count: DEFAULT_READ_COUNT,
+            block: None,
+            options: ReaderOptions::default(),
+            buffer: VecDeque::new(),
+        })
+    }
+
+    /// Чтение записей после id, например сохраненного [StreamReader::last_id]
+    /// для продолжения чтения или `$` для чтения только новых записей
+    pub fn with_start_id(mut self, id: &str) -> Self {
+        self.last_id = id.to_owned();
+        self
+    }
+
+    /// Количество записей, запрашиваемых одной командой
+    pub fn with_count(mut self, count: usize) -> Self {
+        self.count = count.max(1);
+        self
+    }
+
+    /// Ожидание новых записей в конце потока (`XREAD BLOCK`)
+    pub fn with_block(mut self, block: Duration) -> Self {
+        self.block = Some(block);
+        self
+    }
+
+    /// Настройки разбора полей, например строгий режим и единица времени TIMESTAMP
+    pub fn with_options(mut self, options: ReaderOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Идентификатор последней прочитанной записи потока
+    pub fn last_id(&self) -> &str {
+        &self.last_id
+    }
+
+    /// Запрос следующей порции записей. false, если новых записей нет
+    fn fetch(&mut self) -> Result<bool, ParsError> {
+        let mut read_options = StreamReadOptions::default().count(self.count);
+        if let Some(block) = self.block {
+            read_options = read_options.block(block.as_millis() as usize);
+        }
+        let reply: Option<StreamReadReply> = self
+            .connection
+            .xread_options(&[&self.key], &[&self.last_id], &read_options)
+            .map_err(redis_error)?;
+        for entry in reply
+            .into_iter()
+            .flat_map(|val| val.keys)
+            .flat_map(|val| val.ids)
+        {
+            let fields = entry
+                .map
+                .into_iter()
+                .map(|(name, value)| Ok((name, redis::from_redis_value::<String>(&value)?)))
+                .collect::<Result<Vec<_>, RedisError>>()
+                .map_err(redis_error)?;
+            self.buffer.push_back((entry.id, fields));
+        }
+        Ok(!self.buffer.is_empty())
+    }
+}
+
+impl TransactionRead for StreamReader {
+    fn read_transaction(&mut self) -> Result<Option<Transaction>, ParsError> {
+        if self.buffer.is_empty() && !self.fetch()? {
+            return Ok(None);
+        }
+        let Some((id, fields)) = self.buffer.pop_front() else {
+            return Ok(None);
+        };
+        self.last_id = id;
+        text_format::from_fields(fields, &self.options)
+            .map(Some)
+            .map_err(|e| ParsError::WrongFormat(format!("Запись {}: {e}", self.last_id)))
+    }
+}
+
+fn connect(url: &str) -> Result<Connection, ParsError> {
+    redis::Client::open(url)
+        .and_then(|client| client.get_connection())
+        .map_err(redis_error)
+}
+
+fn redis_error(e: RedisError) -> ParsError {
+    ParsError::WrongFormat(format!("Ошибка Redis: {e}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transaction::{TxStatus, TxType};
+    use chrono::DateTime;
+    use redis::Value;
+
+    /// Значение поля, как его возвращает XREAD
+    fn bulk(val: &str) -> Value {
+        Value::BulkString(val.as_bytes().to_vec())
+    }
+
+    #[test]
+    fn test_fields() {
+        let tx = Transaction {
+            tx_id: 7,
+            tx_type: TxType::Transfer,
+            from_user_id: 1,
+            to_user_id: 2,
+            amount: 300,
+            timestamp: DateTime::from_timestamp(1633036860, 0).unwrap(),
+            status: TxStatus::Pending,
+            description: "Record number 7".to_owned(),
+        };
+        let fields = text_format::to_fields(&tx, &WriterOptions::default());
+        assert_eq!(fields[0], ("TX_ID".to_owned(), "7".to_owned()));
+        // Значения приходят из XREAD в виде Value в произвольном порядке
+        let fields = fields
+            .into_iter()
+            .rev()
+            .map(|(name, value)| (name, redis::from_redis_value(&bulk(&value)).unwrap()))
+            .collect();
+        let res = text_format::from_fields(fields, &ReaderOptions::default()).unwrap();
+        assert_eq!(res, tx);
+    }
+}
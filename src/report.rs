@@ -0,0 +1,122 @@
+use super::error::{Language, ParsError, language};
+use std::fmt;
+
+/// Ошибка в записи, пропущенной при чтении
+#[derive(Debug)]
+pub struct ErrorEntry {
+    /// Порядковый номер записи, начиная с нуля
+    pub record: u64,
+    /// Ошибка разбора
+    pub error: ParsError,
+    /// Фрагмент исходного текста записи (только для текстовых форматов)
+    pub snippet: Option<String>,
+}
+
+impl fmt::Display for ErrorEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let record = match language() {
+            Language::Ru => "Запись",
+            Language::En => "Record",
+        };
+        write!(f, "{record} {}: {}", self.record, self.error)?;
+        if let Some(snippet) = &self.snippet {
+            write!(f, "\n    {snippet}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Отчет об ошибках, накопленных при чтении в режиме
+/// [ErrorPolicy::Skip](crate::options::ErrorPolicy::Skip).
+/// Если задан предел количества ошибок, то при его превышении чтение прерывается
+#[derive(Debug, Default)]
+pub struct ErrorReport {
+    entries: Vec<ErrorEntry>,
+    max_errors: Option<usize>,
+}
+
+impl ErrorReport {
+    /// Создание пустого отчета с пределом количества ошибок
+    pub fn new(max_errors: Option<usize>) -> Self {
+        Self {
+            entries: Vec::new(),
+            max_errors,
+        }
+    }
+
+    /// Предел количества ошибок
+    pub fn max_errors(&self) -> Option<usize> {
+        self.max_errors
+    }
+
+    /// Накопленные ошибки в порядке их возникновения
+    pub fn entries(&self) -> &[ErrorEntry] {
+        &self.entries
+    }
+
+    /// Количество накопленных ошибок
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Отчет не содержит ошибок
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Добавление ошибки в отчет. Возвращает [ParsError::TooManyErrors],
+    /// если количество ошибок превысило предел
+    pub fn push(&mut self, entry: ErrorEntry) -> Result<(), ParsError> {
+        self.entries.push(entry);
+        match self.max_errors {
+            Some(limit) if self.entries.len() > limit => Err(ParsError::TooManyErrors { limit }),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl fmt::Display for ErrorReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let errors = match language() {
+            Language::Ru => "Ошибок",
+            Language::En => "Errors",
+        };
+        writeln!(f, "{errors}: {}", self.entries.len())?;
+        for entry in &self.entries {
+            writeln!(f, "{entry}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry_for_test(record: u64) -> ErrorEntry {
+        ErrorEntry {
+            record,
+            error: ParsError::TruncatedRecord,
+            snippet: Some("1,DEPOSIT".to_owned()),
+        }
+    }
+
+    #[test]
+    fn test_report_limit() {
+        let mut report = ErrorReport::new(Some(2));
+        report.push(entry_for_test(0)).unwrap();
+        report.push(entry_for_test(3)).unwrap();
+        assert!(matches!(
+            report.push(entry_for_test(5)),
+            Err(ParsError::TooManyErrors { limit: 2 })
+        ));
+        assert_eq!(report.len(), 3);
+        assert_eq!(
+            report.to_string(),
+            "Ошибок: 3\n\
+            Запись 0: Неполная запись\n    1,DEPOSIT\n\
+            Запись 3: Неполная запись\n    1,DEPOSIT\n\
+            Запись 5: Неполная запись\n    1,DEPOSIT\n"
+        );
+    }
+}
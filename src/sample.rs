@@ -0,0 +1,175 @@
+use super::error::ParsError;
+use super::format::TransactionRead;
+use super::transaction::Transaction;
+
+/// Генератор псевдослучайных чисел SplitMix64. Выборка с одним и тем же seed
+/// воспроизводится на любой платформе
+pub(crate) struct SplitMix64(pub(crate) u64);
+
+impl SplitMix64 {
+    pub(crate) fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut val = self.0;
+        val = (val ^ (val >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        val = (val ^ (val >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        val ^ (val >> 31)
+    }
+
+    /// Число из диапазона [0, bound)
+    pub(crate) fn below(&mut self, bound: u64) -> u64 {
+        ((self.next_u64() as u128 * bound as u128) >> 64) as u64
+    }
+}
+
+/// Равномерная случайная выборка k транзакций из оставшихся в потоке (алгоритм R).
+/// Поток читается один раз, в памяти держится не больше k транзакций.
+/// Транзакции возвращаются в порядке потока. Если в потоке не больше k транзакций,
+/// возвращаются все. Одинаковый seed на одном потоке дает одинаковую выборку
+pub fn reservoir<R: TransactionRead + ?Sized>(
+    reader: &mut R,
+    k: usize,
+    seed: u64,
+) -> Result<Vec<Transaction>, ParsError> {
+    let mut rng = SplitMix64(seed);
+    let mut sample: Vec<(u64, Transaction)> = Vec::with_capacity(k);
+    let mut idx = 0u64;
+    while let Some(tx) = reader.read_transaction()? {
+        if sample.len() < k {
+            sample.push((idx, tx));
+        } else {
+            let slot = rng.below(idx + 1);
+            if slot < k as u64 {
+                sample[slot as usize] = (idx, tx);
+            }
+        }
+        idx += 1;
+    }
+    sample.sort_unstable_by_key(|(idx, _)| *idx);
+    Ok(sample.into_iter().map(|(_, tx)| tx).collect())
+}
+
+/// Читатель, возвращающий каждую n-ю транзакцию исходного потока,
+/// начиная с первой. Остальные транзакции читаются и отбрасываются
+pub struct EveryNth<R: TransactionRead> {
+    reader: R,
+    n: u64,
+}
+
+impl<R: TransactionRead> EveryNth<R> {
+    /// Чтение следующей транзакции выборки. None означает конец потока
+    pub fn read_transaction(&mut self) -> Result<Option<Transaction>, ParsError> {
+        let tx = self.reader.read_transaction()?;
+        if tx.is_some() {
+            for _ in 1..self.n {
+                if self.reader.read_transaction()?.is_none() {
+                    break;
+                }
+            }
+        }
+        Ok(tx)
+    }
+
+    /// Возврат исходного читателя
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+impl<R: TransactionRead> Iterator for EveryNth<R> {
+    type Item = Result<Transaction, ParsError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_transaction().transpose()
+    }
+}
+
+impl<R: TransactionRead> TransactionRead for EveryNth<R> {
+    fn read_transaction(&mut self) -> Result<Option<Transaction>, ParsError> {
+        EveryNth::read_transaction(self)
+    }
+}
+
+/// Выборка каждой n-й транзакции (n не меньше 1), см. [EveryNth]
+pub fn every_nth<R: TransactionRead>(reader: R, n: u64) -> EveryNth<R> {
+    EveryNth {
+        reader,
+        n: n.max(1),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::Format;
+    use crate::transaction::{TxStatus, TxType};
+    use crate::tx_format::{TxReader, TxWriter};
+    use chrono::DateTime;
+    use std::io::Cursor;
+
+    fn reader_for_test(cnt: u64) -> TxReader<Cursor<Vec<u8>>> {
+        let txs: Vec<Transaction> = (0..cnt)
+            .map(|idx| Transaction {
+                tx_id: idx,
+                tx_type: TxType::Deposit,
+                from_user_id: 0,
+                to_user_id: 1,
+                amount: 100,
+                timestamp: DateTime::from_timestamp(1633036860, 0).unwrap(),
+                status: TxStatus::Success,
+                description: String::new(),
+            })
+            .collect();
+        let mut writer = TxWriter::new(Vec::new(), Format::Bin).unwrap();
+        writer.write_all(&txs).unwrap();
+        let buf = writer.into_inner().unwrap();
+        TxReader::new(Cursor::new(buf), Format::Bin).unwrap()
+    }
+
+    fn ids(txs: &[Transaction]) -> Vec<u64> {
+        txs.iter().map(|tx| tx.tx_id).collect()
+    }
+
+    #[test]
+    fn test_reservoir() {
+        let sample = reservoir(&mut reader_for_test(1000), 10, 42).unwrap();
+        assert_eq!(sample.len(), 10);
+        assert!(ids(&sample).windows(2).all(|pair| pair[0] < pair[1]));
+        let again = reservoir(&mut reader_for_test(1000), 10, 42).unwrap();
+        assert_eq!(sample, again);
+        let other = reservoir(&mut reader_for_test(1000), 10, 7).unwrap();
+        assert_ne!(sample, other);
+
+        let all = reservoir(&mut reader_for_test(5), 10, 42).unwrap();
+        assert_eq!(ids(&all), [0, 1, 2, 3, 4]);
+        assert!(
+            reservoir(&mut reader_for_test(5), 0, 42)
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_reservoir_uniform() {
+        // Каждая транзакция из 20 должна попадать в выборку из 5 примерно в четверти случаев
+        let mut hits = [0u32; 20];
+        for seed in 0..2000 {
+            for id in ids(&reservoir(&mut reader_for_test(20), 5, seed).unwrap()) {
+                hits[id as usize] += 1;
+            }
+        }
+        assert!(hits.iter().all(|cnt| (400..600).contains(cnt)), "{hits:?}");
+    }
+
+    #[test]
+    fn test_every_nth() {
+        let txs: Vec<Transaction> = every_nth(reader_for_test(10), 3)
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(ids(&txs), [0, 3, 6, 9]);
+
+        let txs: Vec<Transaction> = every_nth(reader_for_test(3), 0)
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(ids(&txs), [0, 1, 2]);
+    }
+}
@@ -0,0 +1,411 @@
+use super::error::ParsError;
+use super::format::{Format, TransactionRead, TransactionWrite, read_fin_data};
+use super::merge::MergeSorted;
+use super::transaction::Transaction;
+use super::tx_format::{TxReader, TxWriter};
+use std::cmp::Ordering;
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering as AtomicOrdering};
+
+/// Количество транзакций в одном отсортированном участке внешней сортировки по умолчанию
+pub const DEFAULT_RUN_RECORDS: usize = 1 << 20;
+
+static RUN_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Поле, по которому сортируются транзакции
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum SortKey {
+    /// Время транзакции
+    Timestamp,
+    /// Идентификатор транзакции
+    TxId,
+    /// Сумма транзакции
+    Amount,
+}
+
+/// Порядок сортировки
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Default)]
+pub enum Order {
+    /// По возрастанию
+    #[default]
+    Ascending,
+    /// По убыванию
+    Descending,
+}
+
+impl SortKey {
+    /// Сравнение транзакций по полю
+    pub fn compare(&self, lhs: &Transaction, rhs: &Transaction) -> Ordering {
+        match self {
+            Self::Timestamp => lhs.timestamp.cmp(&rhs.timestamp),
+            Self::TxId => lhs.tx_id.cmp(&rhs.tx_id),
+            Self::Amount => lhs.amount.cmp(&rhs.amount),
+        }
+    }
+}
+
+impl FromStr for SortKey {
+    type Err = ParsError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "timestamp" => Ok(Self::Timestamp),
+            "tx_id" => Ok(Self::TxId),
+            "amount" => Ok(Self::Amount),
+            _ => Err(ParsError::WrongFormat(format!(
+                "Неизвестное поле сортировки: {s}"
+            ))),
+        }
+    }
+}
+
+/// Сортировка транзакций в памяти. Сортировка устойчивая: транзакции с равными
+/// значениями поля сохраняют порядок исходного потока при любом направлении
+pub fn sort_transactions(txs: &mut [Transaction], key: SortKey, order: Order) {
+    match order {
+        Order::Ascending => txs.sort_by(|lhs, rhs| key.compare(lhs, rhs)),
+        Order::Descending => txs.sort_by(|lhs, rhs| key.compare(rhs, lhs)),
+    }
+}
+
+/// Чтение всех оставшихся в потоке транзакций и их сортировка в памяти.
+/// Оставшиеся элементы итератора доступны срезом через `as_slice`,
+/// что позволяет передать их писателю одним вызовом
+/// [crate::tx_format::TxWriter::write_all]
+pub fn sort_stream<R: TransactionRead + ?Sized>(
+    reader: &mut R,
+    key: SortKey,
+    order: Order,
+) -> Result<std::vec::IntoIter<Transaction>, ParsError> {
+    let mut txs = read_fin_data(reader)?;
+    sort_transactions(&mut txs, key, order);
+    Ok(txs.into_iter())
+}
+
+/// Канонический порядок транзакций: по tx_id, а при равных tx_id по остальным полям,
+/// чтобы порядок не зависел от порядка записей в исходном потоке
+pub fn canonical_order(lhs: &Transaction, rhs: &Transaction) -> Ordering {
+    let key = |tx: &Transaction| {
+        (
+            tx.tx_id,
+            tx.timestamp,
+            tx.tx_type,
+            tx.from_user_id,
+            tx.to_user_id,
+            tx.amount,
+            tx.status,
+        )
+    };
+    key(lhs)
+        .cmp(&key(rhs))
+        .then_with(|| lhs.description.cmp(&rhs.description))
+}
+
+/// Запись всех оставшихся в потоке транзакций в канонической форме: транзакции
+/// сортируются в памяти в порядке [canonical_order], а порядок полей, кавычки, пробелы
+/// и запись времени задает писатель. Поэтому две выгрузки одних и тех же данных,
+/// прочитанные из любых форматов, после записи писателями с одинаковыми настройками
+/// совпадают побайтно и сравниваются через `cmp`. Писатель, созданный
+/// [TxWriter::new], записывает форму по спецификации формата.
+/// Возвращается количество записанных транзакций
+///
+/// ```
+/// use fin_parser::format::Format;
+/// use fin_parser::sort::canonicalize;
+/// use fin_parser::tx_format::{TxReader, TxWriter};
+/// use std::io::Cursor;
+///
+/// let csv = "TX_ID,TX_TYPE,FROM_USER_ID,TO_USER_ID,AMOUNT,TIMESTAMP,STATUS,DESCRIPTION\n\
+///     2,DEPOSIT,0,6,200,1633036920000,SUCCESS,\"Record number 2\"\n\
+///     1,DEPOSIT,0,5,100,1633036860000,SUCCESS,\"Record number 1\"\n";
+/// let text = "TX_ID: 1\nTX_TYPE: DEPOSIT\nFROM_USER_ID: 0\nTO_USER_ID: 5\nAMOUNT: 100\n\
+///     TIMESTAMP: 1633036860000\nSTATUS: SUCCESS\nDESCRIPTION: \"Record number 1\"\n\n\
+///     DESCRIPTION: \"Record number 2\"\nSTATUS: SUCCESS\nTIMESTAMP: 1633036920000\n\
+///     AMOUNT:   200\nTO_USER_ID: 6\nFROM_USER_ID: 0\nTX_TYPE: DEPOSIT\nTX_ID: 2\n";
+///
+/// let canonical = |data: &'static str, fin_format| {
+///     let mut reader = TxReader::new(Cursor::new(data), fin_format).unwrap();
+///     let mut writer = TxWriter::new(Vec::new(), Format::Csv).unwrap();
+///     canonicalize(&mut reader, &mut writer).unwrap();
+///     writer.into_inner().unwrap()
+/// };
+/// assert_eq!(canonical(csv, Format::Csv), canonical(text, Format::Text));
+/// ```
+pub fn canonicalize<R, W>(reader: &mut R, writer: &mut W) -> Result<u64, ParsError>
+where
+    R: TransactionRead + ?Sized,
+    W: TransactionWrite + ?Sized,
+{
+    let mut txs = read_fin_data(reader)?;
+    txs.sort_by(canonical_order);
+    writer.write_batch(&txs)?;
+    Ok(txs.len() as u64)
+}
+
+/// Внешняя сортировка потоков, не помещающихся в память. Поток читается участками
+/// по run_records транзакций, каждый участок сортируется в памяти и сбрасывается
+/// во временный файл формата bin, после чего участки сливаются k-путевым слиянием.
+/// В памяти одновременно находится не более одного участка при записи
+/// и по одной транзакции на участок при слиянии
+#[derive(Clone, Debug)]
+pub struct ExternalSorter {
+    key: SortKey,
+    order: Order,
+    run_records: usize,
+    temp_dir: PathBuf,
+}
+
+impl ExternalSorter {
+    /// Сортировщик с участками по [DEFAULT_RUN_RECORDS] транзакций
+    /// во временном каталоге системы
+    pub fn new(key: SortKey, order: Order) -> Self {
+        Self {
+            key,
+            order,
+            run_records: DEFAULT_RUN_RECORDS,
+            temp_dir: std::env::temp_dir(),
+        }
+    }
+
+    /// Количество транзакций в одном участке (не меньше одной)
+    pub fn with_run_records(mut self, run_records: usize) -> Self {
+        self.run_records = run_records.max(1);
+        self
+    }
+
+    /// Каталог для временных файлов
+    pub fn with_temp_dir<P: AsRef<Path>>(mut self, temp_dir: P) -> Self {
+        self.temp_dir = temp_dir.as_ref().to_path_buf();
+        self
+    }
+
+    /// Сортировка всех оставшихся в потоке транзакций. Последний участок остается
+    /// в памяти, поэтому поток, уместившийся в один участок, сортируется без временных
+    /// файлов. Временные файлы удаляются вместе с возвращенным итератором
+    pub fn sort<R: TransactionRead + ?Sized>(
+        &self,
+        reader: &mut R,
+    ) -> Result<MergedRuns, ParsError> {
+        let mut runs = Vec::new();
+        let mut merged = MergedRuns {
+            merged: None,
+            paths: Vec::new(),
+        };
+        let mut txs = Vec::new();
+        reader.read_batch(&mut txs, self.run_records)?;
+        loop {
+            sort_transactions(&mut txs, self.key, self.order);
+            // Последний участок не сбрасывается на диск: для этого заранее читается
+            // одна транзакция следующего участка
+            let mut next = Vec::new();
+            if txs.len() == self.run_records {
+                reader.read_batch(&mut next, 1)?;
+            }
+            if next.is_empty() {
+                runs.push(Run::Memory(txs.into_iter()));
+                break;
+            }
+
+            let path = self.temp_dir.join(format!(
+                "fin-parser-sort-{}-{}.bin",
+                std::process::id(),
+                RUN_COUNTER.fetch_add(1, AtomicOrdering::Relaxed)
+            ));
+            merged.paths.push(path.clone());
+            let mut writer = TxWriter::new(File::create(&path)?, Format::Bin)?;
+            writer.write_all(&txs)?;
+            writer.finish()?;
+            drop(txs);
+            let run_reader = TxReader::new(BufReader::new(File::open(&path)?), Format::Bin)?;
+            runs.push(Run::File(Box::new(run_reader)));
+
+            txs = next;
+            reader.read_batch(&mut txs, self.run_records - 1)?;
+        }
+        merged.merged = Some(MergeSorted::new(runs, self.key, self.order)?);
+        Ok(merged)
+    }
+}
+
+enum Run {
+    Memory(std::vec::IntoIter<Transaction>),
+    File(Box<TxReader<BufReader<File>>>),
+}
+
+impl TransactionRead for Run {
+    fn read_transaction(&mut self) -> Result<Option<Transaction>, ParsError> {
+        match self {
+            Self::Memory(txs) => Ok(txs.next()),
+            Self::File(reader) => reader.read_transaction(),
+        }
+    }
+}
+
+/// Итератор по результату внешней сортировки, сливающий отсортированные участки
+pub struct MergedRuns {
+    // Option, чтобы закрыть файлы участков до их удаления
+    merged: Option<MergeSorted<Run>>,
+    paths: Vec<PathBuf>,
+}
+
+impl MergedRuns {
+    /// Количество участков, сброшенных во временные файлы
+    pub fn spilled_runs(&self) -> usize {
+        self.paths.len()
+    }
+}
+
+impl Iterator for MergedRuns {
+    type Item = Result<Transaction, ParsError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.merged.as_mut()?.next()
+    }
+}
+
+impl TransactionRead for MergedRuns {
+    fn read_transaction(&mut self) -> Result<Option<Transaction>, ParsError> {
+        self.next().transpose()
+    }
+}
+
+impl Drop for MergedRuns {
+    fn drop(&mut self) {
+        self.merged = None;
+        for path in &self.paths {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::Format;
+    use crate::test_util::{encode, reader, tx};
+    use crate::transaction::{TxStatus, TxType};
+    use crate::tx_format::TxReader;
+    use chrono::DateTime;
+    use std::io::Cursor;
+
+    fn txs_for_test() -> Vec<Transaction> {
+        [(3, 20, 100), (1, 10, 300), (2, 30, 100), (4, 10, 200)]
+            .into_iter()
+            .map(|(tx_id, secs, amount)| Transaction {
+                amount,
+                timestamp: DateTime::from_timestamp(secs, 0).unwrap(),
+                ..tx(tx_id)
+            })
+            .collect()
+    }
+
+    fn sorted_ids(key: SortKey, order: Order) -> Vec<u64> {
+        sort_stream(&mut reader(&txs_for_test(), Format::Text), key, order)
+            .unwrap()
+            .map(|tx| tx.tx_id)
+            .collect()
+    }
+
+    #[test]
+    fn test_sort_stream() {
+        assert_eq!(sorted_ids(SortKey::TxId, Order::Ascending), [1, 2, 3, 4]);
+        assert_eq!(sorted_ids(SortKey::TxId, Order::Descending), [4, 3, 2, 1]);
+        assert_eq!(
+            sorted_ids(SortKey::Timestamp, Order::Ascending),
+            [1, 4, 3, 2]
+        );
+        assert_eq!(
+            sorted_ids(SortKey::Timestamp, Order::Descending),
+            [2, 3, 1, 4]
+        );
+        assert_eq!(sorted_ids(SortKey::Amount, Order::Ascending), [3, 2, 4, 1]);
+        assert_eq!(sorted_ids(SortKey::Amount, Order::Descending), [1, 4, 3, 2]);
+        assert_eq!("tx_id".parse::<SortKey>().unwrap(), SortKey::TxId);
+        assert!("status".parse::<SortKey>().is_err());
+    }
+
+    #[test]
+    fn test_sorted_write() {
+        let mut input = reader(&txs_for_test(), Format::Csv);
+        let sorted = sort_stream(&mut input, SortKey::Timestamp, Order::Ascending).unwrap();
+
+        let txs = reader(sorted.as_slice(), Format::Bin).read_all().unwrap();
+        assert_eq!(txs, sorted.collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_external_sort() {
+        let txs: Vec<Transaction> = (0..50u64)
+            .map(|idx| Transaction {
+                tx_type: TxType::Transfer,
+                from_user_id: idx,
+                to_user_id: idx + 1,
+                amount: (idx * 37 % 11) as i64,
+                timestamp: DateTime::from_timestamp((idx * 7919 % 50) as i64, 0).unwrap(),
+                status: TxStatus::Pending,
+                ..tx(idx)
+            })
+            .collect();
+        let buf = encode(&txs, Format::Bin);
+
+        let temp_dir = std::env::temp_dir().join(format!("fin-parser-test-{}", std::process::id()));
+        fs::create_dir_all(&temp_dir).unwrap();
+        for run_records in [1, 7, 50, 100] {
+            for (key, order) in [
+                (SortKey::Timestamp, Order::Ascending),
+                (SortKey::Amount, Order::Descending),
+            ] {
+                let mut expected = read_fin_data(
+                    &mut TxReader::new(Cursor::new(buf.clone()), Format::Bin).unwrap(),
+                )
+                .unwrap();
+                sort_transactions(&mut expected, key, order);
+
+                let mut reader = TxReader::new(Cursor::new(buf.clone()), Format::Bin).unwrap();
+                let sorter = ExternalSorter::new(key, order)
+                    .with_run_records(run_records)
+                    .with_temp_dir(&temp_dir);
+                let merged = sorter.sort(&mut reader).unwrap();
+                assert_eq!(merged.spilled_runs(), 50usize.div_ceil(run_records) - 1);
+                let sorted: Vec<Transaction> = merged.collect::<Result<_, _>>().unwrap();
+                assert_eq!(sorted, expected);
+                assert_eq!(fs::read_dir(&temp_dir).unwrap().count(), 0);
+            }
+        }
+        fs::remove_dir(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_canonicalize() {
+        let mut txs = txs_for_test();
+        // Повторяющийся tx_id с другим описанием
+        let mut dup = txs_for_test().remove(0);
+        dup.description = "Повтор".to_owned();
+        txs.insert(0, dup);
+        let mut reversed = txs_for_test();
+        reversed.reverse();
+        reversed.push(txs[0].clone());
+
+        for out_format in Format::ALL {
+            let mut outputs = Vec::new();
+            for (in_format, input) in [(Format::Text, &txs), (Format::Bin, &reversed)] {
+                let mut reader = reader(input, in_format);
+                let mut writer = TxWriter::new(Vec::new(), out_format).unwrap();
+                assert_eq!(canonicalize(&mut reader, &mut writer).unwrap(), 5);
+                outputs.push(writer.into_inner().unwrap());
+            }
+            assert_eq!(outputs[0], outputs[1], "{out_format}");
+
+            let canonical = TxReader::new(Cursor::new(outputs.remove(0)), out_format)
+                .unwrap()
+                .read_all()
+                .unwrap();
+            let ids: Vec<_> = canonical.iter().map(|tx| tx.tx_id).collect();
+            assert_eq!(ids, [1, 2, 3, 3, 4]);
+            assert_eq!(canonical[3].description, "Повтор");
+        }
+    }
+}
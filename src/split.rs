@@ -0,0 +1,217 @@
+use super::constants::{DEPOSIT, TRANSFER, WITHDRAWAL};
+use super::error::ParsError;
+use super::format::TransactionWrite;
+use super::transaction::{Transaction, TxType};
+use super::tx_format::TxWriter;
+use std::collections::BTreeMap;
+use std::collections::btree_map::Entry;
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+
+/// Функция, вычисляющая ключ раздела по транзакции
+pub type KeyFn<K> = Box<dyn FnMut(&Transaction) -> K + Send>;
+
+/// Функция, открывающая приемник для нового раздела
+pub type OpenFn<K, W> = Box<dyn FnMut(&K) -> Result<W, ParsError> + Send>;
+
+/// Ключ раздела по дню транзакции (UTC), например `2021-10-01`
+pub fn by_day(tx: &Transaction) -> String {
+    tx.timestamp.format("%Y-%m-%d").to_string()
+}
+
+/// Ключ раздела по типу транзакции, например `DEPOSIT`
+pub fn by_type(tx: &Transaction) -> String {
+    let val = match tx.tx_type {
+        TxType::Deposit => DEPOSIT,
+        TxType::Transfer => TRANSFER,
+        TxType::Withdrawal => WITHDRAWAL,
+    };
+    val.to_owned()
+}
+
+/// Ключ раздела по инициатору транзакции
+pub fn by_from_user(tx: &Transaction) -> String {
+    tx.from_user_id.to_string()
+}
+
+/// Ключ раздела по получателю транзакции
+pub fn by_to_user(tx: &Transaction) -> String {
+    tx.to_user_id.to_string()
+}
+
+/// Писатель, распределяющий транзакции по нескольким приемникам по ключу.
+/// Приемник раздела открывается при первой транзакции с новым ключом
+/// и остается открытым до [SplitWriter::finish] или [SplitWriter::into_inner]
+pub struct SplitWriter<K: Ord, W: TransactionWrite> {
+    key: KeyFn<K>,
+    open: OpenFn<K, W>,
+    writers: BTreeMap<K, W>,
+}
+
+impl<K: Ord, W: TransactionWrite> SplitWriter<K, W> {
+    /// Конструктор, принимающий функцию ключа и функцию открытия приемника раздела
+    pub fn new<F, O>(key: F, open: O) -> Self
+    where
+        F: FnMut(&Transaction) -> K + Send + 'static,
+        O: FnMut(&K) -> Result<W, ParsError> + Send + 'static,
+    {
+        Self {
+            key: Box::new(key),
+            open: Box::new(open),
+            writers: BTreeMap::new(),
+        }
+    }
+
+    /// Запись транзакции в приемник ее раздела
+    pub fn write_transaction(&mut self, tx: &Transaction) -> Result<(), ParsError> {
+        let writer = match self.writers.entry((self.key)(tx)) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                let writer = (self.open)(entry.key())?;
+                entry.insert(writer)
+            }
+        };
+        writer.write_transaction(tx)
+    }
+
+    /// Метод записи набора транзакций
+    pub fn write_all(&mut self, txs: &[Transaction]) -> Result<(), ParsError> {
+        for tx in txs {
+            self.write_transaction(tx)?;
+        }
+        Ok(())
+    }
+
+    /// Ключи открытых разделов по возрастанию
+    pub fn keys(&self) -> impl Iterator<Item = &K> {
+        self.writers.keys()
+    }
+
+    /// Сброс буферизованных данных всех разделов
+    pub fn flush(&mut self) -> Result<(), ParsError> {
+        for writer in self.writers.values_mut() {
+            writer.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Завершение записи всех разделов. Завершаются все разделы,
+    /// даже если некоторые из них вернули ошибку; возвращается первая ошибка
+    pub fn finish(&mut self) -> Result<(), ParsError> {
+        let mut res = Ok(());
+        for writer in self.writers.values_mut() {
+            let finished = writer.finish();
+            if res.is_ok() {
+                res = finished;
+            }
+        }
+        res
+    }
+
+    /// Завершение записи и возврат приемников разделов
+    pub fn into_inner(mut self) -> Result<BTreeMap<K, W>, ParsError> {
+        self.finish()?;
+        Ok(self.writers)
+    }
+}
+
+impl SplitWriter<String, TxWriter<Box<dyn Write + Send>>> {
+    /// Запись разделов в файлы `dir/<ключ>.<ext>`, например `out/2021-10-01.bin`.
+    /// Формат и сжатие определяются по расширению ext аналогично [TxWriter::create],
+    /// каталог создается при открытии первого раздела
+    pub fn in_dir<P, F>(dir: P, ext: &str, key: F) -> Self
+    where
+        P: Into<PathBuf>,
+        F: FnMut(&Transaction) -> String + Send + 'static,
+    {
+        let dir = dir.into();
+        let ext = ext.to_owned();
+        Self::new(key, move |key: &String| {
+            fs::create_dir_all(&dir)?;
+            TxWriter::create(dir.join(format!("{key}.{ext}")))
+        })
+    }
+}
+
+impl<K: Ord, W: TransactionWrite> TransactionWrite for SplitWriter<K, W> {
+    fn write_transaction(&mut self, tx: &Transaction) -> Result<(), ParsError> {
+        SplitWriter::write_transaction(self, tx)
+    }
+
+    fn flush(&mut self) -> Result<(), ParsError> {
+        SplitWriter::flush(self)
+    }
+
+    fn finish(&mut self) -> Result<(), ParsError> {
+        SplitWriter::finish(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::Format;
+    use crate::test_util::txs;
+    use crate::tx_format::{TxReader, read_file};
+    use chrono::TimeDelta;
+    use std::io::Cursor;
+
+    fn txs_for_test() -> Vec<Transaction> {
+        txs(0..6)
+            .into_iter()
+            .map(|tx| Transaction {
+                tx_type: if tx.tx_id % 3 == 0 {
+                    TxType::Transfer
+                } else {
+                    TxType::Deposit
+                },
+                from_user_id: tx.tx_id % 2,
+                to_user_id: 10,
+                timestamp: tx.timestamp + TimeDelta::hours(12 * tx.tx_id as i64),
+                ..tx
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_split_by_type() {
+        let mut writer =
+            SplitWriter::new(by_type, |_: &String| TxWriter::new(Vec::new(), Format::Csv));
+        writer.write_all(&txs_for_test()).unwrap();
+        let writers = writer.into_inner().unwrap();
+        assert_eq!(writers.keys().collect::<Vec<_>>(), ["DEPOSIT", "TRANSFER"]);
+
+        for (key, writer) in writers {
+            let buf = writer.into_inner().unwrap();
+            let txs = TxReader::new(Cursor::new(buf), Format::Csv)
+                .unwrap()
+                .read_all()
+                .unwrap();
+            let ids: Vec<u64> = txs.iter().map(|tx| tx.tx_id).collect();
+            match key.as_str() {
+                "DEPOSIT" => assert_eq!(ids, [1, 2, 4, 5]),
+                _ => assert_eq!(ids, [0, 3]),
+            }
+        }
+    }
+
+    #[test]
+    fn test_split_in_dir() {
+        let dir = std::env::temp_dir().join(format!("fin-parser-split-{}", std::process::id()));
+        let mut writer = SplitWriter::in_dir(&dir, "bin", by_day);
+        writer.write_all(&txs_for_test()).unwrap();
+        assert_eq!(
+            writer.keys().collect::<Vec<_>>(),
+            ["2021-09-30", "2021-10-01", "2021-10-02", "2021-10-03"]
+        );
+        writer.finish().unwrap();
+        drop(writer);
+
+        let txs = read_file(dir.join("2021-10-01.bin"), Format::Bin).unwrap();
+        let ids: Vec<u64> = txs.iter().map(|tx| tx.tx_id).collect();
+        assert_eq!(ids, [1, 2]);
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 4);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,134 @@
+use super::error::ParsError;
+use super::format::TransactionWrite;
+use super::transaction::Transaction;
+use super::tx_format::TxWriter;
+use std::io::{self, Write};
+use std::sync::{Mutex, MutexGuard};
+
+/// Потокобезопасная обертка над [TxWriter]. Каждая запись (и каждый набор записей
+/// в [SyncTxWriter::write_all]) выполняется под блокировкой, поэтому записи разных
+/// потоков никогда не перемешиваются внутри одной транзакции. Обертку можно
+/// разделять между потоками, если поток записи Out реализует Send; 'static
+/// не требуется, поэтому подходят и заимствованные буферы
+///
+/// ```
+/// use fin_parser::format::Format;
+/// use fin_parser::sync_writer::SyncTxWriter;
+/// use fin_parser::tx_format::TxWriter;
+///
+/// let mut buf = Vec::new();
+/// let writer = SyncTxWriter::new(TxWriter::new(&mut buf, Format::Csv).unwrap());
+/// std::thread::scope(|scope| {
+///     for _ in 0..4 {
+///         scope.spawn(|| writer.flush().unwrap());
+///     }
+/// });
+/// writer.finish().unwrap();
+/// drop(writer);
+/// assert!(buf.starts_with(b"TX_ID,"));
+/// ```
+pub struct SyncTxWriter<Out: Write> {
+    writer: Mutex<TxWriter<Out>>,
+}
+
+impl<Out: Write> SyncTxWriter<Out> {
+    /// Конструктор, принимающий на вход писателя транзакций
+    pub fn new(writer: TxWriter<Out>) -> Self {
+        Self {
+            writer: Mutex::new(writer),
+        }
+    }
+
+    fn lock(&self) -> Result<MutexGuard<'_, TxWriter<Out>>, ParsError> {
+        self.writer
+            .lock()
+            .map_err(|_| ParsError::IoError(io::Error::other("Писатель транзакций недоступен")))
+    }
+
+    /// Запись одной транзакции
+    pub fn write_transaction(&self, tx: &Transaction) -> Result<(), ParsError> {
+        self.lock()?.write_transaction(tx)
+    }
+
+    /// Запись набора транзакций одним непрерывным блоком
+    pub fn write_all(&self, txs: &[Transaction]) -> Result<(), ParsError> {
+        self.lock()?.write_all(txs)
+    }
+
+    /// Запись набора транзакций одним буферизованным блоком, см. [TxWriter::write_batch]
+    pub fn write_batch(&self, txs: &[Transaction]) -> Result<(), ParsError> {
+        self.lock()?.write_batch(txs)
+    }
+
+    /// Сброс буферизованных данных в поток
+    pub fn flush(&self) -> Result<(), ParsError> {
+        self.lock()?.flush()
+    }
+
+    /// Завершение записи, см. [TxWriter::finish]
+    pub fn finish(&self) -> Result<(), ParsError> {
+        self.lock()?.finish()
+    }
+
+    /// Возврат исходного писателя
+    pub fn into_inner(self) -> Result<TxWriter<Out>, ParsError> {
+        self.writer
+            .into_inner()
+            .map_err(|_| ParsError::IoError(io::Error::other("Писатель транзакций недоступен")))
+    }
+}
+
+impl<Out: Write> TransactionWrite for SyncTxWriter<Out> {
+    fn write_transaction(&mut self, tx: &Transaction) -> Result<(), ParsError> {
+        SyncTxWriter::write_transaction(self, tx)
+    }
+
+    fn write_batch(&mut self, txs: &[Transaction]) -> Result<(), ParsError> {
+        SyncTxWriter::write_batch(self, txs)
+    }
+
+    fn flush(&mut self) -> Result<(), ParsError> {
+        SyncTxWriter::flush(self)
+    }
+
+    fn finish(&mut self) -> Result<(), ParsError> {
+        SyncTxWriter::finish(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::Format;
+    use crate::test_util::tx;
+    use crate::tx_format::TxReader;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_concurrent_writes() {
+        for fin_format in Format::ALL {
+            let writer = SyncTxWriter::new(TxWriter::new(Vec::new(), fin_format).unwrap());
+            std::thread::scope(|scope| {
+                for thread_idx in 0..4 {
+                    let writer = &writer;
+                    scope.spawn(move || {
+                        for idx in 0..50 {
+                            writer
+                                .write_transaction(&tx(thread_idx * 1000 + idx))
+                                .unwrap();
+                        }
+                    });
+                }
+            });
+            let buf = writer.into_inner().unwrap().into_inner().unwrap();
+
+            let mut reader = TxReader::new(Cursor::new(buf), fin_format).unwrap();
+            let mut txs = reader.read_all().unwrap();
+            txs.sort_by_key(|tx| tx.tx_id);
+            assert_eq!(txs.len(), 200);
+            for res in txs {
+                assert_eq!(res, tx(res.tx_id));
+            }
+        }
+    }
+}
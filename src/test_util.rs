@@ -0,0 +1,39 @@
+use super::format::Format;
+use super::transaction::{Transaction, TxStatus, TxType};
+use super::tx_format::{TxReader, TxWriter};
+use chrono::DateTime;
+use std::io::Cursor;
+use std::ops::Range;
+
+/// Транзакция для тестов: успешное пополнение пользователя 1 на 100
+/// с описанием `Record number {tx_id}`. Отличающиеся поля задаются
+/// через `Transaction { amount, ..tx(tx_id) }`
+pub(crate) fn tx(tx_id: u64) -> Transaction {
+    Transaction {
+        tx_id,
+        tx_type: TxType::Deposit,
+        from_user_id: 0,
+        to_user_id: 1,
+        amount: 100,
+        timestamp: DateTime::from_timestamp(1633036860, 0).unwrap(),
+        status: TxStatus::Success,
+        description: format!("Record number {tx_id}"),
+    }
+}
+
+/// Транзакции [tx] с идентификаторами из ids
+pub(crate) fn txs(ids: Range<u64>) -> Vec<Transaction> {
+    ids.map(tx).collect()
+}
+
+/// Транзакции, записанные в формате format
+pub(crate) fn encode(txs: &[Transaction], format: Format) -> Vec<u8> {
+    let mut writer = TxWriter::new(Vec::new(), format).unwrap();
+    writer.write_all(txs).unwrap();
+    writer.into_inner().unwrap()
+}
+
+/// Читатель транзакций, записанных в формате format
+pub(crate) fn reader(txs: &[Transaction], format: Format) -> TxReader<Cursor<Vec<u8>>> {
+    TxReader::new(Cursor::new(encode(txs, format)), format).unwrap()
+}
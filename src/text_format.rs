@@ -1,24 +1,33 @@
 use super::constants::*;
 use super::error::ParsError;
+use super::options::{ReaderOptions, WriterOptions};
 use super::transaction::*;
-use super::utils::{read_byte, remove_quotes};
-use chrono::DateTime;
-use std::collections::HashMap;
-use std::io::{Read, Write};
-
+#[cfg(feature = "redis")]
+use super::utils::unescape;
+use super::utils::{
+    CountingReader, CountingWriter, Location, check_timestamp, invalid_enum_value,
+    parse_description, parse_number, quote_description, read_byte, remove_quotes, snippet,
+    timestamp_from_unit, timestamp_to_unit,
+};
+use super::warning::{Warning, WarningKind, WarningSink};
+use std::io::{BufWriter, Read, Write};
+
+/// Вид прочитанной лексемы. Текст ключа и значения остается во внутренних буферах
+/// парсера и доступен через [Parser::key_value] до чтения следующей лексемы
 enum Token {
-    KeyValue((String, String)),
+    KeyValue,
     SplitRecords,
-    EndOfStream(Option<(String, String)>),
+    EndOfStream { has_value: bool },
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Eq, PartialEq)]
 enum PrevParserState {
     WaitStartRecord,
     WaitStartKey,
 }
 
-#[derive(Clone, Copy)]
+#[allow(clippy::enum_variant_names)]
+#[derive(Clone, Copy, Eq, PartialEq)]
 enum ParserState {
     WaitStartRecord,
     WaitStartKey,
@@ -32,86 +41,131 @@ enum ParserState {
 
 struct Parser<In: Read> {
     state: ParserState,
-    stream: In,
+    stream: CountingReader<In>,
+    record_start: Location,
+    raw: Vec<u8>,
+    // Сохранять запись в raw целиком, а не только начало для сообщений об ошибках
+    capture: bool,
+    key_buf: Vec<u8>,
+    val_buf: Vec<u8>,
 }
 
 impl<In: Read> Parser<In> {
     fn new(stream: In) -> Self {
         Self {
             state: ParserState::WaitStartRecord,
-            stream,
+            stream: CountingReader::new(stream),
+            record_start: Location::default(),
+            raw: Vec::new(),
+            capture: false,
+            key_buf: Vec::new(),
+            val_buf: Vec::new(),
+        }
+    }
+
+    /// Запоминание положения первого байта записи
+    fn mark_record_start(&mut self, byte: u8) {
+        self.raw.clear();
+        self.raw.push(byte);
+        let location = self.stream.location();
+        self.record_start = Location {
+            byte: location.byte - 1,
+            line: location.line,
+            column: location.column - 1,
+        };
+    }
+
+    /// Переход к началу следующей записи (после пустой строки) при ошибке внутри записи
+    fn resync(&mut self) -> Result<(), ParsError> {
+        if self.state == ParserState::WaitStartRecord {
+            return Ok(());
+        }
+        let mut blank_line = self.state == ParserState::WaitStartKey;
+        self.state = ParserState::WaitStartRecord;
+        loop {
+            match read_byte(&mut self.stream) {
+                Ok(b'\n') if blank_line => return Ok(()),
+                Ok(b'\n') => blank_line = true,
+                Ok(b' ') | Ok(b'\r') | Ok(b'\t') => continue,
+                Ok(_) => blank_line = false,
+                Err(ParsError::EndOfStream) => return Ok(()),
+                Err(e) => return Err(e),
+            }
         }
     }
 
+    /// Ключ и значение последней прочитанной пары без окружающих пробелов
+    fn key_value(&self) -> Result<(&str, &str), ParsError> {
+        let key = std::str::from_utf8(&self.key_buf)?.trim();
+        let val = std::str::from_utf8(&self.val_buf)?.trim();
+        Ok((key, val))
+    }
+
     fn get_next_token(&mut self) -> Result<Token, ParsError> {
-        let mut key_buf = Vec::new();
-        let mut val_buf = Vec::new();
+        self.key_buf.clear();
+        self.val_buf.clear();
         loop {
             let byte = match read_byte(&mut self.stream) {
                 Ok(val) => val,
-                Err(e) => match e {
-                    ParsError::EndOfStream => {
-                        let key_text = std::str::from_utf8(&key_buf)?.trim().to_string();
-                        let val_text = std::str::from_utf8(&val_buf)?.trim().to_string();
-                        if !(key_text.is_empty() && val_text.is_empty()) {
-                            return Ok(Token::EndOfStream(Some((key_text, val_text))));
-                        } else {
-                            return Ok(Token::EndOfStream(None));
-                        }
-                    }
-                    _ => {
-                        return Err(e);
-                    }
-                },
+                Err(ParsError::EndOfStream) => {
+                    let (key, val) = self.key_value()?;
+                    let has_value = !(key.is_empty() && val.is_empty());
+                    return Ok(Token::EndOfStream { has_value });
+                }
+                Err(e) => return Err(e),
             };
+            if self.capture || self.raw.len() < MAX_SNIPPET_LEN {
+                self.raw.push(byte);
+            }
             match self.state {
                 ParserState::WaitStartRecord => {
-                    if byte == ' ' as u8 || byte == '\n' as u8 {
+                    if byte == b' ' || byte == b'\n' {
                         continue;
                     }
 
-                    if byte == '#' as u8 {
+                    if byte == b'#' {
                         self.state = ParserState::WaitEndComment(PrevParserState::WaitStartRecord);
                         continue;
                     }
 
-                    key_buf.push(byte);
+                    self.mark_record_start(byte);
+                    self.key_buf.push(byte);
                     self.state = ParserState::WaitEndKey;
                 }
                 ParserState::WaitStartKey => {
-                    if byte == ' ' as u8 {
+                    if byte == b' ' {
                         continue;
                     }
 
-                    if byte == '#' as u8 {
+                    if byte == b'#' {
                         self.state = ParserState::WaitEndComment(PrevParserState::WaitStartKey);
                         continue;
                     }
 
-                    if byte == '\n' as u8 {
+                    if byte == b'\n' {
                         self.state = ParserState::WaitStartRecord;
                         return Ok(Token::SplitRecords);
                     }
 
-                    key_buf.push(byte);
+                    self.key_buf.push(byte);
                     self.state = ParserState::WaitEndKey;
                 }
 
                 ParserState::WaitEndKey => {
-                    if byte == ':' as u8 {
+                    if byte == b':' {
                         self.state = ParserState::WaitStartValue;
                         continue;
                     }
-                    key_buf.push(byte);
+                    self.key_buf.push(byte);
                 }
 
                 ParserState::WaitStartValue => {
-                    if byte == ' ' as u8 {
+                    if byte == b' ' {
                         continue;
                     }
-                    val_buf.push(byte);
+                    self.val_buf.push(byte);
 
-                    if byte == '"' as u8 {
+                    if byte == b'"' {
                         self.state = ParserState::WaitEndString;
                         continue;
                     }
@@ -119,33 +173,31 @@ impl<In: Read> Parser<In> {
                 }
 
                 ParserState::WaitEndRegular => {
-                    if byte == '\n' as u8 {
-                        let key_text = std::str::from_utf8(&key_buf)?.trim().to_string();
-                        let val_text = std::str::from_utf8(&val_buf)?.trim().to_string();
+                    if byte == b'\n' {
                         self.state = ParserState::WaitStartKey;
-                        return Ok(Token::KeyValue((key_text, val_text)));
+                        return Ok(Token::KeyValue);
                     }
-                    val_buf.push(byte);
+                    self.val_buf.push(byte);
                 }
 
                 ParserState::WaitEndString => {
-                    if byte == '\\' as u8 {
+                    if byte == b'\\' {
                         self.state = ParserState::WaitEscaped;
                         continue;
                     }
-                    val_buf.push(byte);
-                    if byte == '"' as u8 {
+                    self.val_buf.push(byte);
+                    if byte == b'"' {
                         self.state = ParserState::WaitEndRegular;
                         continue;
                     }
                 }
                 ParserState::WaitEscaped => {
-                    val_buf.push(byte);
+                    self.val_buf.push(byte);
                     self.state = ParserState::WaitEndString;
                     continue;
                 }
                 ParserState::WaitEndComment(prev_state) => {
-                    if byte == '\n' as u8 {
+                    if byte == b'\n' {
                         match prev_state {
                             PrevParserState::WaitStartKey => {
                                 self.state = ParserState::WaitStartKey;
@@ -161,124 +213,151 @@ impl<In: Read> Parser<In> {
     }
 }
 
+/// Длина первой целой записи (вместе с завершающей пустой строкой) в начале буфера
+/// или None, если запись еще не дочитана. Переходы повторяют [Parser::get_next_token]
+#[cfg(feature = "async")]
+pub fn frame_len(buf: &[u8]) -> Option<usize> {
+    let mut state = ParserState::WaitStartRecord;
+    for (idx, &byte) in buf.iter().enumerate() {
+        state = match state {
+            ParserState::WaitStartRecord if byte == b' ' || byte == b'\n' => state,
+            ParserState::WaitStartRecord if byte == b'#' => {
+                ParserState::WaitEndComment(PrevParserState::WaitStartRecord)
+            }
+            ParserState::WaitStartRecord => ParserState::WaitEndKey,
+            ParserState::WaitStartKey if byte == b' ' => state,
+            ParserState::WaitStartKey if byte == b'#' => {
+                ParserState::WaitEndComment(PrevParserState::WaitStartKey)
+            }
+            ParserState::WaitStartKey if byte == b'\n' => return Some(idx + 1),
+            ParserState::WaitStartKey => ParserState::WaitEndKey,
+            ParserState::WaitEndKey if byte == b':' => ParserState::WaitStartValue,
+            ParserState::WaitEndKey => state,
+            ParserState::WaitStartValue if byte == b' ' => state,
+            ParserState::WaitStartValue if byte == b'"' => ParserState::WaitEndString,
+            ParserState::WaitStartValue => ParserState::WaitEndRegular,
+            ParserState::WaitEndRegular if byte == b'\n' => ParserState::WaitStartKey,
+            ParserState::WaitEndRegular => state,
+            ParserState::WaitEndString if byte == b'\\' => ParserState::WaitEscaped,
+            ParserState::WaitEndString if byte == b'"' => ParserState::WaitEndRegular,
+            ParserState::WaitEndString => state,
+            ParserState::WaitEscaped => ParserState::WaitEndString,
+            ParserState::WaitEndComment(PrevParserState::WaitStartKey) if byte == b'\n' => {
+                ParserState::WaitStartKey
+            }
+            ParserState::WaitEndComment(PrevParserState::WaitStartRecord) if byte == b'\n' => {
+                ParserState::WaitStartRecord
+            }
+            ParserState::WaitEndComment(_) => state,
+        };
+    }
+    None
+}
+
+fn missing_field(name: &str) -> ParsError {
+    ParsError::MissingField {
+        name: name.to_owned(),
+    }
+}
+
+/// Запись text: пары ключ-значение в порядке следования.
+/// При повторе ключа используется последнее значение
+#[derive(Eq, PartialEq, Debug)]
 struct TextTxRecord {
-    fields: HashMap<String, String>,
+    fields: Vec<(String, String)>,
 }
 
 impl TextTxRecord {
+    fn get(&self, key: &str) -> Option<&String> {
+        self.fields
+            .iter()
+            .rev()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v)
+    }
+
     fn serialize<Out: Write>(&self, out: &mut Out) -> Result<(), ParsError> {
         for (k, v) in self.fields.iter() {
-            let line = format!("{k}: {v}\n");
-            out.write(line.as_bytes())?;
+            out.write_all(k.as_bytes())?;
+            out.write_all(b": ")?;
+            out.write_all(v.as_bytes())?;
+            out.write_all(b"\n")?;
         }
-        out.write(b"\n")?;
+        out.write_all(b"\n")?;
         Ok(())
     }
 
-    fn to_transaction(&self) -> Result<Transaction, ParsError> {
-        if self.fields.len() != CNT_VALUES {
-            return Err(ParsError::WrongFormat(format!(
-                "Неверрный формат записи: {:?}",
-                self.fields
-            )));
+    fn to_transaction_ref(&self, options: &ReaderOptions) -> Result<TransactionRef<'_>, ParsError> {
+        if options.strict && self.fields.len() != CNT_VALUES {
+            return Err(ParsError::FieldCountMismatch {
+                expected: CNT_VALUES,
+                found: self.fields.len(),
+            });
         }
 
-        let tx_id = if let Some(val) = self.fields.get(TX_ID) {
-            val.parse::<u64>()?
+        let tx_id = if let Some(val) = self.get(TX_ID) {
+            parse_number::<u64>(TX_ID, val)?
         } else {
-            return Err(ParsError::WrongFormat(format!(
-                "Отсутствует запись: {TX_ID}"
-            )));
+            return Err(missing_field(TX_ID));
         };
 
-        let tx_type = if let Some(val) = self.fields.get(TX_TYPE) {
+        let tx_type = if let Some(val) = self.get(TX_TYPE) {
             match val.as_str() {
                 DEPOSIT => TxType::Deposit,
                 TRANSFER => TxType::Transfer,
                 WITHDRAWAL => TxType::Withdrawal,
                 _ => {
-                    return Err(ParsError::WrongFormat(format!(
-                        "Неверный тип транзакции: {val}"
-                    )));
+                    return Err(invalid_enum_value(TX_TYPE, val));
                 }
             }
         } else {
-            return Err(ParsError::WrongFormat(format!(
-                "Отсутствует запись: {TX_ID}"
-            )));
+            return Err(missing_field(TX_TYPE));
         };
 
-        let from_user_id = if let Some(val) = self.fields.get(FROM_USER_ID) {
-            val.parse::<u64>()?
+        let from_user_id = if let Some(val) = self.get(FROM_USER_ID) {
+            parse_number::<u64>(FROM_USER_ID, val)?
         } else {
-            return Err(ParsError::WrongFormat(format!(
-                "Отсутствует запись: {FROM_USER_ID}"
-            )));
+            return Err(missing_field(FROM_USER_ID));
         };
 
-        let to_user_id = if let Some(val) = self.fields.get(TO_USER_ID) {
-            val.parse::<u64>()?
+        let to_user_id = if let Some(val) = self.get(TO_USER_ID) {
+            parse_number::<u64>(TO_USER_ID, val)?
         } else {
-            return Err(ParsError::WrongFormat(format!(
-                "Отсутствует запись: {TO_USER_ID}"
-            )));
+            return Err(missing_field(TO_USER_ID));
         };
 
-        let amount = if let Some(val) = self.fields.get(AMOUNT) {
-            val.parse::<i64>()?
+        let amount = if let Some(val) = self.get(AMOUNT) {
+            parse_number::<i64>(AMOUNT, val)?
         } else {
-            return Err(ParsError::WrongFormat(format!(
-                "Отсутствует запись: {AMOUNT}"
-            )));
+            return Err(missing_field(AMOUNT));
         };
 
-        let timestamp = if let Some(val) = self.fields.get(TIMESTAMP) {
-            let millis = val.parse::<u64>()?;
-            if let Some(date_time) = DateTime::from_timestamp_millis(millis as i64) {
-                date_time
-            } else {
-                return Err(ParsError::WrongFormat(format!(
-                    "Неверный формат времени: {millis}"
-                )));
-            }
+        let timestamp = if let Some(val) = self.get(TIMESTAMP) {
+            timestamp_from_unit(parse_number(TIMESTAMP, val)?, options.timestamp_unit)?
         } else {
-            return Err(ParsError::WrongFormat(format!(
-                "Отсутствует запись: {TIMESTAMP}"
-            )));
+            return Err(missing_field(TIMESTAMP));
         };
 
-        let status = if let Some(val) = self.fields.get(STATUS) {
+        let status = if let Some(val) = self.get(STATUS) {
             match val.as_str() {
                 SUCCESS => TxStatus::Success,
                 FAILURE => TxStatus::Failure,
                 PENDING => TxStatus::Pending,
                 _ => {
-                    return Err(ParsError::WrongFormat(format!(
-                        "Неверный статус транзакции: {val}"
-                    )));
+                    return Err(invalid_enum_value(STATUS, val));
                 }
             }
         } else {
-            return Err(ParsError::WrongFormat(format!(
-                "Отсутствует запись: {STATUS}"
-            )));
+            return Err(missing_field(STATUS));
         };
 
-        let description = if let Some(val) = self.fields.get(DESCRIPTION) {
-            if !(val.starts_with('"') && val.ends_with('"')) {
-                return Err(ParsError::WrongFormat(format!(
-                    "Wrong description: {}",
-                    val
-                )));
-            }
-            remove_quotes(&val)
+        let description = if let Some(val) = self.get(DESCRIPTION) {
+            parse_description(val, options)?
         } else {
-            return Err(ParsError::WrongFormat(format!(
-                "Отсутствует запись: {DESCRIPTION}"
-            )));
+            return Err(missing_field(DESCRIPTION));
         };
 
-        Ok(Transaction {
+        Ok(TransactionRef {
             tx_id,
             tx_type,
             from_user_id,
@@ -290,93 +369,365 @@ impl TextTxRecord {
         })
     }
 
-    fn from_transaction(tx: &Transaction) -> Self {
-        let mut fields = HashMap::new();
-        fields.insert(TX_ID.to_owned(), tx.tx_id.to_string());
+    /// Проверка полей без построения транзакции: те же ошибки, что у [Self::to_transaction_ref]
+    fn validate(&self, options: &ReaderOptions) -> Result<(), ParsError> {
+        if options.strict && self.fields.len() != CNT_VALUES {
+            return Err(ParsError::FieldCountMismatch {
+                expected: CNT_VALUES,
+                found: self.fields.len(),
+            });
+        }
+        let field = |name: &str| {
+            self.get(name)
+                .map(String::as_str)
+                .ok_or_else(|| missing_field(name))
+        };
+        parse_number::<u64>(TX_ID, field(TX_ID)?)?;
+        let tx_type = field(TX_TYPE)?;
+        if ![DEPOSIT, TRANSFER, WITHDRAWAL].contains(&tx_type) {
+            return Err(invalid_enum_value(TX_TYPE, tx_type));
+        }
+        parse_number::<u64>(FROM_USER_ID, field(FROM_USER_ID)?)?;
+        parse_number::<u64>(TO_USER_ID, field(TO_USER_ID)?)?;
+        parse_number::<i64>(AMOUNT, field(AMOUNT)?)?;
+        let timestamp = parse_number(TIMESTAMP, field(TIMESTAMP)?)?;
+        check_timestamp(timestamp, options.timestamp_unit)?;
+        let status = field(STATUS)?;
+        if ![SUCCESS, FAILURE, PENDING].contains(&status) {
+            return Err(invalid_enum_value(STATUS, status));
+        }
+        parse_description(field(DESCRIPTION)?, options)?;
+        Ok(())
+    }
+
+    fn from_transaction(tx: &Transaction, options: &WriterOptions) -> Self {
+        let mut fields = Vec::with_capacity(CNT_VALUES);
+        fields.push((TX_ID.to_owned(), tx.tx_id.to_string()));
         let tx_type = match tx.tx_type {
             TxType::Deposit => DEPOSIT,
             TxType::Transfer => TRANSFER,
             TxType::Withdrawal => WITHDRAWAL,
         };
-        fields.insert(TX_TYPE.to_owned(), tx_type.to_owned());
-        fields.insert(FROM_USER_ID.to_owned(), tx.from_user_id.to_string());
-        fields.insert(TO_USER_ID.to_owned(), tx.to_user_id.to_string());
-        fields.insert(AMOUNT.to_owned(), tx.amount.to_string());
-        let timestamp = tx.timestamp.timestamp_millis() as u64;
-        fields.insert(TIMESTAMP.to_owned(), timestamp.to_string());
+        fields.push((TX_TYPE.to_owned(), tx_type.to_owned()));
+        fields.push((FROM_USER_ID.to_owned(), tx.from_user_id.to_string()));
+        fields.push((TO_USER_ID.to_owned(), tx.to_user_id.to_string()));
+        fields.push((AMOUNT.to_owned(), tx.amount.to_string()));
+        let timestamp = timestamp_to_unit(&tx.timestamp, options.timestamp_unit);
+        fields.push((TIMESTAMP.to_owned(), timestamp.to_string()));
         let status = match tx.status {
             TxStatus::Success => SUCCESS,
             TxStatus::Failure => FAILURE,
             TxStatus::Pending => PENDING,
         };
-        fields.insert(STATUS.to_owned(), status.to_string());
-        let description = format!("\"{}\"", tx.description);
-        fields.insert(DESCRIPTION.to_owned(), description.to_string());
+        fields.push((STATUS.to_owned(), status.to_string()));
+        fields.push((DESCRIPTION.to_owned(), quote_description(&tx.description)));
 
         Self { fields }
     }
 }
 
+/// Поля транзакции парами ключ-значение, как в записи text
+#[cfg(feature = "redis")]
+pub(crate) fn to_fields(tx: &Transaction, options: &WriterOptions) -> Vec<(String, String)> {
+    TextTxRecord::from_transaction(tx, options).fields
+}
+
+/// Транзакция из пар ключ-значение записи text
+#[cfg(feature = "redis")]
+pub(crate) fn from_fields(
+    fields: Vec<(String, String)>,
+    options: &ReaderOptions,
+) -> Result<Transaction, ParsError> {
+    // Описание хранится экранированным, как в записи text
+    let fields = fields
+        .into_iter()
+        .map(|(key, val)| match key == DESCRIPTION {
+            true => (key, unescape(&val)),
+            false => (key, val),
+        })
+        .collect();
+    let record = TextTxRecord { fields };
+    record.to_transaction_ref(options).map(Transaction::from)
+}
+
 pub struct TextTxReader<In: Read> {
     parser: Parser<In>,
+    // Запись переиспользуется между вызовами, чтобы не выделять память под поля
+    record: TextTxRecord,
+    options: ReaderOptions,
+    records: u64,
+    warnings: WarningSink,
 }
 
 impl<In: Read> TextTxReader<In> {
-    pub fn new(stream: In) -> Result<Self, ParsError> {
+    pub fn with_options(stream: In, options: ReaderOptions) -> Result<Self, ParsError> {
         Ok(Self {
             parser: Parser::new(stream),
+            record: TextTxRecord { fields: Vec::new() },
+            warnings: WarningSink::new(&options),
+            options,
+            records: 0,
         })
     }
 
+    /// Ошибка в данных записи: указывается начало записи и ее исходный текст
+    fn record_error(&self, e: ParsError) -> ParsError {
+        let position = self.parser.record_start.error_position(self.records);
+        e.at_record(position, Some(self.raw_record()))
+    }
+
+    /// Ошибка разбиения на поля: указывается место, до которого дочитан поток
+    fn stream_error(&self, e: ParsError) -> ParsError {
+        let position = self.parser.stream.location().error_position(self.records);
+        e.at_record(position, Some(self.raw_record()))
+    }
+
+    pub fn bytes_read(&self) -> u64 {
+        self.parser.stream.count()
+    }
+
+    /// Смещение начала последней прочитанной записи
+    pub fn last_record_start(&self) -> u64 {
+        self.parser.record_start.byte
+    }
+
+    /// Положение начала последней прочитанной записи
+    #[cfg(feature = "parallel")]
+    pub fn last_record_location(&self) -> Location {
+        self.parser.record_start
+    }
+
+    /// Сохранение исходных байт записей для [Self::take_rejected]
+    pub fn set_capture_raw(&mut self, capture: bool) {
+        self.parser.stream.set_capture(capture);
+    }
+
+    /// Очистка исходных байт после успешно прочитанной записи
+    pub fn clear_captured(&mut self) {
+        self.parser.stream.clear_captured();
+    }
+
+    /// Смещение начала и исходные байты записи, пропущенной через resync
+    pub fn take_rejected(&mut self) -> (u64, Vec<u8>) {
+        let end = self.parser.stream.count();
+        let raw = self
+            .parser
+            .stream
+            .captured()
+            .map(std::mem::take)
+            .unwrap_or_default();
+        (end - raw.len() as u64, raw)
+    }
+
+    #[cfg(feature = "async")]
+    pub fn get_mut(&mut self) -> &mut In {
+        self.parser.stream.get_mut()
+    }
+
+    /// Пропуск записи без построения транзакции
+    pub fn skip_record(&mut self) -> Result<bool, ParsError> {
+        if !self.read_fields().map_err(|e| self.stream_error(e))? {
+            return Ok(false);
+        }
+        self.records += 1;
+        Ok(true)
+    }
+
+    /// Проверка записи без построения транзакции
+    pub fn validate_record(&mut self) -> Result<bool, ParsError> {
+        if !self.read_fields().map_err(|e| self.stream_error(e))? {
+            return Ok(false);
+        }
+        self.record
+            .validate(&self.options)
+            .map_err(|e| self.record_error(e))?;
+        self.records += 1;
+        Ok(true)
+    }
+
+    /// Чтение исходного текста записи целиком, запись с завершающей пустой строкой
+    /// дописывается в out. Возвращает false, если поток закончился
+    #[cfg(feature = "parallel")]
+    pub fn read_raw_record(&mut self, out: &mut Vec<u8>) -> Result<bool, ParsError> {
+        self.parser.capture = true;
+        let res = self.skip_record();
+        self.parser.capture = false;
+        if !res? {
+            return Ok(false);
+        }
+        out.extend_from_slice(&self.parser.raw);
+        while !out.ends_with(b"\n\n") {
+            out.push(b'\n');
+        }
+        Ok(true)
+    }
+
+    #[cfg(feature = "parallel")]
+    pub fn options(&self) -> &ReaderOptions {
+        &self.options
+    }
+
+    /// Исходный текст текущей записи, усеченный до MAX_SNIPPET_LEN байт
+    fn raw_record(&self) -> String {
+        snippet(&self.parser.raw)
+    }
+
+    pub fn take_warnings(&mut self) -> Vec<Warning> {
+        self.warnings.take()
+    }
+
+    /// Переход к следующей записи после ошибки чтения
+    pub fn resync(&mut self) -> Result<(), ParsError> {
+        self.parser.resync()?;
+        self.records += 1;
+        Ok(())
+    }
+
     pub fn read_transaction(&mut self) -> Result<Option<Transaction>, ParsError> {
-        let mut fields = HashMap::new();
+        Ok(self.read_transaction_ref()?.map(Transaction::from))
+    }
+
+    /// Чтение транзакции с описанием, заимствованным из буфера записи
+    pub fn read_transaction_ref(&mut self) -> Result<Option<TransactionRef<'_>>, ParsError> {
+        if !self.read_fields().map_err(|e| self.stream_error(e))? {
+            return Ok(None);
+        }
+
+        let tx = self
+            .record
+            .to_transaction_ref(&self.options)
+            .map_err(|e| self.record_error(e))?;
+        for (name, _) in self.record.fields.iter() {
+            if !HEADER_VALUES.contains(&name.as_str()) {
+                let kind = WarningKind::UnknownField { name: name.clone() };
+                self.warnings.push(self.records, kind);
+            }
+        }
+        if let Some(raw_description) = self.record.get(DESCRIPTION) {
+            self.warnings
+                .check_transaction(self.records, &tx, raw_description);
+        }
+        self.records += 1;
+        Ok(Some(tx))
+    }
+
+    /// Описание последней прочитанной транзакции
+    pub fn last_description(&self) -> &str {
+        self.record
+            .get(DESCRIPTION)
+            .map_or("", |val| remove_quotes(val))
+    }
+
+    /// Чтение пар ключ-значение одной записи в поля переиспользуемой записи.
+    /// Возвращает false, если поток закончился и полей нет
+    fn read_fields(&mut self) -> Result<bool, ParsError> {
+        let fields = &mut self.record.fields;
+        let mut cnt = 0;
         loop {
             let token = self.parser.get_next_token()?;
-            match token {
-                Token::KeyValue((k, v)) => {
-                    fields.insert(k, v);
-                }
-                Token::SplitRecords => {
-                    break;
-                }
-                Token::EndOfStream(reminder) => {
-                    if let Some((k, v)) = reminder {
-                        fields.insert(k, v);
+            if matches!(
+                token,
+                Token::KeyValue | Token::EndOfStream { has_value: true }
+            ) {
+                let (key, val) = self.parser.key_value()?;
+                match fields.get_mut(cnt) {
+                    Some((k, v)) => {
+                        k.clear();
+                        k.push_str(key);
+                        v.clear();
+                        v.push_str(val);
                     }
-                    break;
+                    None => fields.push((key.to_owned(), val.to_owned())),
                 }
+                cnt += 1;
+            }
+            if !matches!(token, Token::KeyValue) {
+                fields.truncate(cnt);
+                return Ok(cnt > 0);
             }
         }
-
-        if fields.is_empty() {
-            return Ok(None);
-        }
-
-        let text_record = TextTxRecord { fields };
-
-        Ok(Some(text_record.to_transaction()?))
     }
 }
 
 pub struct TextTxWriter<Out: Write> {
-    stream: Out,
+    stream: CountingWriter<BufWriter<Out>>,
+    options: WriterOptions,
+    buf: Vec<u8>,
 }
 
 impl<Out: Write> TextTxWriter<Out> {
-    pub fn new(stream: Out) -> Result<Self, ParsError> {
-        Ok(Self { stream })
+    pub fn with_options(stream: Out, options: WriterOptions) -> Result<Self, ParsError> {
+        Ok(Self {
+            stream: CountingWriter::new(BufWriter::new(stream)),
+            options,
+            buf: Vec::new(),
+        })
     }
 
     pub fn write_transaction(&mut self, data: &Transaction) -> Result<(), ParsError> {
-        let record = TextTxRecord::from_transaction(&data);
+        let record = TextTxRecord::from_transaction(data, &self.options);
         record.serialize(&mut self.stream)?;
         Ok(())
     }
+
+    /// Запись набора транзакций: записи собираются в буфер и пишутся в поток одним write_all
+    pub fn write_batch(&mut self, txs: &[Transaction]) -> Result<(), ParsError> {
+        self.buf.clear();
+        for tx in txs {
+            TextTxRecord::from_transaction(tx, &self.options).serialize(&mut self.buf)?;
+        }
+        self.stream.write_all(&self.buf)?;
+        Ok(())
+    }
+
+    /// Писатель в память с теми же настройками для сериализации части данных
+    #[cfg(feature = "parallel")]
+    pub fn chunk_writer(&mut self) -> TextTxWriter<Vec<u8>> {
+        TextTxWriter {
+            stream: CountingWriter::new(BufWriter::new(Vec::new())),
+            options: self.options.clone(),
+            buf: Vec::new(),
+        }
+    }
+
+    /// Запись уже сериализованных данных этого формата
+    #[cfg(feature = "parallel")]
+    pub fn write_raw(&mut self, data: &[u8]) -> Result<(), ParsError> {
+        self.stream.write_all(data)?;
+        Ok(())
+    }
+
+    #[cfg(feature = "async")]
+    pub fn get_mut(&mut self) -> &mut Out {
+        self.stream.get_mut().get_mut()
+    }
+
+    pub fn flush(&mut self) -> Result<(), ParsError> {
+        self.stream.flush()?;
+        Ok(())
+    }
+
+    pub fn finish(&mut self) -> Result<(), ParsError> {
+        self.flush()
+    }
+
+    /// Количество байт, переданных в поток, включая еще не сброшенные из буфера
+    pub fn bytes_written(&self) -> u64 {
+        self.stream.count()
+    }
+
+    pub fn into_inner(self) -> Result<Out, ParsError> {
+        self.stream
+            .into_inner()
+            .into_inner()
+            .map_err(|e| ParsError::IoError(e.into_error()))
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use chrono::DateTime;
     use std::io::Cursor;
 
     const EXPECTED_TEXT_MULT: &str = r#"
@@ -401,22 +752,6 @@ mod tests {
         TO_USER_ID: 9223372036854775807
     "#;
 
-    fn eq_hash_maps(lhs: &HashMap<String, String>, rhs: &HashMap<String, String>) -> bool {
-        if lhs.len() != rhs.len() {
-            return false;
-        }
-
-        let res = lhs.iter().all(|lhs_item| {
-            if let Some(rhs_val) = rhs.get(lhs_item.0) {
-                if lhs_item.1 == rhs_val { true } else { false }
-            } else {
-                false
-            }
-        });
-
-        res
-    }
-
     fn tx1_for_test() -> Transaction {
         Transaction {
             tx_id: 1000000000000000,
@@ -424,7 +759,7 @@ mod tests {
             from_user_id: 0,
             to_user_id: 9223372036854775807,
             amount: 100,
-            timestamp: DateTime::from_timestamp_millis(1633036860000 as i64).unwrap(),
+            timestamp: DateTime::from_timestamp_millis(1633036860000).unwrap(),
             status: TxStatus::Failure,
             description: "Record number 1".to_owned(),
         }
@@ -437,22 +772,23 @@ mod tests {
             from_user_id: 9223372036854775807,
             to_user_id: 9223372036854775807,
             amount: 200,
-            timestamp: DateTime::from_timestamp_millis(1633036920000 as i64).unwrap(),
+            timestamp: DateTime::from_timestamp_millis(1633036920000).unwrap(),
             status: TxStatus::Pending,
             description: "Record number 2".to_owned(),
         }
     }
 
     fn text_record_for_test() -> TextTxRecord {
-        let mut fields = HashMap::new();
-        fields.insert(TX_ID.to_owned(), "1000000000000000".to_owned());
-        fields.insert(TX_TYPE.to_owned(), "DEPOSIT".to_owned());
-        fields.insert(FROM_USER_ID.to_owned(), "0".to_owned());
-        fields.insert(TO_USER_ID.to_owned(), "9223372036854775807".to_owned());
-        fields.insert(AMOUNT.to_owned(), "100".to_owned());
-        fields.insert(TIMESTAMP.to_owned(), "1633036860000".to_owned());
-        fields.insert(STATUS.to_owned(), "FAILURE".to_owned());
-        fields.insert(DESCRIPTION.to_owned(), "\"Record number 1\"".to_owned());
+        let fields = vec![
+            (TX_ID.to_owned(), "1000000000000000".to_owned()),
+            (TX_TYPE.to_owned(), "DEPOSIT".to_owned()),
+            (FROM_USER_ID.to_owned(), "0".to_owned()),
+            (TO_USER_ID.to_owned(), "9223372036854775807".to_owned()),
+            (AMOUNT.to_owned(), "100".to_owned()),
+            (TIMESTAMP.to_owned(), "1633036860000".to_owned()),
+            (STATUS.to_owned(), "FAILURE".to_owned()),
+            (DESCRIPTION.to_owned(), "\"Record number 1\"".to_owned()),
+        ];
         TextTxRecord { fields }
     }
 
@@ -460,7 +796,9 @@ mod tests {
     fn test_text_to_transaction() {
         let text_record = text_record_for_test();
         let expected = tx1_for_test();
-        let tx = text_record.to_transaction().unwrap();
+        let tx = text_record
+            .to_transaction_ref(&ReaderOptions::default())
+            .unwrap();
 
         assert_eq!(tx, expected);
     }
@@ -469,15 +807,15 @@ mod tests {
     fn test_text_from_transaction() {
         let tx = tx1_for_test();
         let expected = text_record_for_test();
-        let record = TextTxRecord::from_transaction(&tx);
+        let record = TextTxRecord::from_transaction(&tx, &WriterOptions::default());
 
-        assert!(eq_hash_maps(&record.fields, &expected.fields));
+        assert_eq!(record, expected);
     }
 
     #[test]
     fn test_text_reader() {
         let stream = Cursor::new(EXPECTED_TEXT_MULT.as_bytes());
-        let mut csv_reader = TextTxReader::new(stream).unwrap();
+        let mut csv_reader = TextTxReader::with_options(stream, ReaderOptions::default()).unwrap();
 
         let mut fin_info = Vec::new();
         while let Some(tx) = csv_reader.read_transaction().unwrap() {
@@ -493,14 +831,14 @@ mod tests {
     fn test_text_writer() {
         let buf = Vec::new();
         let stream = Cursor::new(buf);
-        let mut csv_writer = TextTxWriter::new(stream).unwrap();
+        let mut csv_writer = TextTxWriter::with_options(stream, WriterOptions::default()).unwrap();
 
         csv_writer.write_transaction(&tx1_for_test()).unwrap();
         csv_writer.write_transaction(&tx2_for_test()).unwrap();
 
-        let buf = csv_writer.stream.get_ref();
+        let buf = csv_writer.into_inner().unwrap().into_inner();
         let stream = Cursor::new(buf);
-        let mut text_reader = TextTxReader::new(stream).unwrap();
+        let mut text_reader = TextTxReader::with_options(stream, ReaderOptions::default()).unwrap();
         let mut fin_info = Vec::new();
         while let Some(tx) = text_reader.read_transaction().unwrap() {
             fin_info.push(tx);
@@ -510,4 +848,105 @@ mod tests {
         assert_eq!(fin_info[0], tx1_for_test());
         assert_eq!(fin_info[1], tx2_for_test());
     }
+
+    #[test]
+    fn test_text_lenient() {
+        let mut text_record = text_record_for_test();
+        text_record
+            .fields
+            .push(("CURRENCY".to_owned(), "RUB".to_owned()));
+        assert!(
+            text_record
+                .to_transaction_ref(&ReaderOptions::default())
+                .is_err()
+        );
+
+        let options = ReaderOptions {
+            strict: false,
+            ..Default::default()
+        };
+        assert_eq!(
+            text_record.to_transaction_ref(&options).unwrap(),
+            tx1_for_test()
+        );
+    }
+
+    #[test]
+    fn test_text_skip_record() {
+        let stream = Cursor::new(EXPECTED_TEXT_MULT.as_bytes());
+        let mut text_reader = TextTxReader::with_options(stream, ReaderOptions::default()).unwrap();
+
+        assert!(text_reader.skip_record().unwrap());
+        assert_eq!(
+            text_reader.read_transaction().unwrap(),
+            Some(tx2_for_test())
+        );
+        assert!(!text_reader.skip_record().unwrap());
+        assert_eq!(text_reader.bytes_read(), EXPECTED_TEXT_MULT.len() as u64);
+    }
+
+    #[test]
+    fn test_text_reused_record() {
+        let data = EXPECTED_TEXT_MULT.replacen("AMOUNT: 100", "AMOUNT: 100\nCURRENCY: RUB", 1);
+        let options = ReaderOptions {
+            strict: false,
+            ..Default::default()
+        };
+        let mut text_reader =
+            TextTxReader::with_options(Cursor::new(data.as_bytes()), options).unwrap();
+
+        assert_eq!(
+            text_reader.read_transaction().unwrap(),
+            Some(tx1_for_test())
+        );
+        assert_eq!(text_reader.record.fields.len(), CNT_VALUES + 1);
+        assert_eq!(
+            text_reader.read_transaction().unwrap(),
+            Some(tx2_for_test())
+        );
+        assert_eq!(text_reader.record.fields.len(), CNT_VALUES);
+        assert_eq!(text_reader.record.get(TX_ID).unwrap(), "1000000000000001");
+    }
+
+    #[test]
+    fn test_text_missing_field() {
+        let mut text_record = text_record_for_test();
+        text_record.fields.retain(|(k, _)| k != STATUS);
+        let options = ReaderOptions {
+            strict: false,
+            ..Default::default()
+        };
+        assert!(matches!(
+            text_record.to_transaction_ref(&options),
+            Err(ParsError::MissingField { name }) if name == STATUS
+        ));
+    }
+
+    #[test]
+    fn test_text_error_position() {
+        let data = EXPECTED_TEXT_MULT.replace("AMOUNT: 200", "AMOUNT: two hundred");
+        let mut text_reader =
+            TextTxReader::with_options(Cursor::new(data.as_bytes()), ReaderOptions::default())
+                .unwrap();
+
+        assert_eq!(
+            text_reader.read_transaction().unwrap(),
+            Some(tx1_for_test())
+        );
+        let err = text_reader.read_transaction().unwrap_err();
+        let position = err.position().unwrap();
+        assert_eq!(position.record, 1);
+        assert_eq!(position.line, Some(13));
+        assert_eq!(position.column, Some(9));
+        assert_eq!(
+            position.byte,
+            data.find("DESCRIPTION: \"Record number 2").unwrap() as u64
+        );
+        assert!(matches!(
+            err.inner(),
+            ParsError::InvalidNumber { field, value } if field == AMOUNT && value == "two hundred"
+        ));
+        assert_eq!(err.field(), Some(AMOUNT));
+        assert!(err.snippet().unwrap().contains("AMOUNT: two hundred"));
+    }
 }
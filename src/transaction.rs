@@ -1,6 +1,17 @@
+use super::constants::HEADER_VALUES;
+use super::error::ParsError;
+use super::fingerprint::{Fingerprint, transaction_fingerprint};
+use super::format::Format;
+use super::money::Money;
+#[cfg(feature = "serde")]
+use super::reconcile::{status_name, type_name};
+use super::tx_format::{TxReader, TxWriter};
 use chrono::{DateTime, Utc};
+use std::io::Cursor;
+use std::str::FromStr;
 
-#[derive(Eq, PartialEq, Debug)]
+#[derive(Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 /// Тип транзакции
 pub enum TxType {
     /// Зачисление средств
@@ -11,7 +22,8 @@ pub enum TxType {
     Withdrawal,
 }
 
-#[derive(Eq, PartialEq, Debug)]
+#[derive(Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 /// Статус транзакции
 pub enum TxStatus {
     /// Успешная транзакция
@@ -22,12 +34,16 @@ pub enum TxStatus {
     Pending,
 }
 
-/// Тип данных, описывающий информацию о транзакции
-#[derive(Eq, PartialEq, Debug)]
+/// Тип данных, описывающий информацию о транзакции.
+/// При feature `serde` сериализуется так же, как [Transaction::to_json]:
+/// тип и статус именами из csv, время в миллисекундах
+#[derive(Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Transaction {
     /// Идентификатор транзакции
     pub tx_id: u64,
     /// Тип транзакции
+    #[cfg_attr(feature = "serde", serde(serialize_with = "serialize_tx_type"))]
     pub tx_type: TxType,
     /// Идентификатор инициатора транзакции
     pub from_user_id: u64,
@@ -36,9 +52,256 @@ pub struct Transaction {
     /// Сумма транзакции
     pub amount: i64,
     /// Время транзакции
+    #[cfg_attr(feature = "serde", serde(with = "chrono::serde::ts_milliseconds"))]
     pub timestamp: DateTime<Utc>,
     /// Статус транзакции
+    #[cfg_attr(feature = "serde", serde(serialize_with = "serialize_status"))]
     pub status: TxStatus,
     /// Описание транзакции
     pub description: String,
 }
+
+/// Транзакция, описание которой заимствовано из внутреннего буфера читателя
+/// ([crate::tx_format::TxReader::read_transaction_ref]). Позволяет фильтровать и считать
+/// записи без выделения памяти под строки. Ссылка действительна до следующего чтения
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub struct TransactionRef<'a> {
+    /// Идентификатор транзакции
+    pub tx_id: u64,
+    /// Тип транзакции
+    pub tx_type: TxType,
+    /// Идентификатор инициатора транзакции
+    pub from_user_id: u64,
+    /// Идентификатор получателя транзакции
+    pub to_user_id: u64,
+    /// Сумма транзакции
+    pub amount: i64,
+    /// Время транзакции
+    pub timestamp: DateTime<Utc>,
+    /// Статус транзакции
+    pub status: TxStatus,
+    /// Описание транзакции
+    pub description: &'a str,
+}
+
+impl TransactionRef<'_> {
+    /// Построение транзакции, владеющей описанием
+    pub fn to_transaction(&self) -> Transaction {
+        Transaction::from(*self)
+    }
+
+    /// Отпечаток содержимого транзакции, см. [Transaction::fingerprint]
+    pub fn fingerprint(&self) -> Fingerprint {
+        transaction_fingerprint(self)
+    }
+
+    /// Сумма со знаком, см. [Transaction::signed_amount]
+    pub fn signed_amount(&self) -> Result<Money, ParsError> {
+        let amount = Money::new(self.amount);
+        match self.tx_type {
+            TxType::Withdrawal => amount.checked_neg(),
+            TxType::Deposit | TxType::Transfer => Ok(amount),
+        }
+    }
+}
+
+impl From<TransactionRef<'_>> for Transaction {
+    fn from(tx: TransactionRef<'_>) -> Self {
+        Self {
+            tx_id: tx.tx_id,
+            tx_type: tx.tx_type,
+            from_user_id: tx.from_user_id,
+            to_user_id: tx.to_user_id,
+            amount: tx.amount,
+            timestamp: tx.timestamp,
+            status: tx.status,
+            description: tx.description.to_owned(),
+        }
+    }
+}
+
+impl<'a> From<&'a Transaction> for TransactionRef<'a> {
+    fn from(tx: &'a Transaction) -> Self {
+        Self {
+            tx_id: tx.tx_id,
+            tx_type: tx.tx_type,
+            from_user_id: tx.from_user_id,
+            to_user_id: tx.to_user_id,
+            amount: tx.amount,
+            timestamp: tx.timestamp,
+            status: tx.status,
+            description: &tx.description,
+        }
+    }
+}
+
+impl PartialEq<Transaction> for TransactionRef<'_> {
+    fn eq(&self, other: &Transaction) -> bool {
+        *self == TransactionRef::from(other)
+    }
+}
+
+impl Transaction {
+    /// Чтение одной транзакции из байтов в заданном формате.
+    /// Для csv заголовок необязателен
+    pub fn from_bytes_format(data: &[u8], fin_format: Format) -> Result<Self, ParsError> {
+        let mut buf = Vec::new();
+        if fin_format == Format::Csv
+            && !data
+                .trim_ascii_start()
+                .starts_with(HEADER_VALUES[0].as_bytes())
+        {
+            buf.extend_from_slice(HEADER_VALUES.join(",").as_bytes());
+            buf.push(b'\n');
+        }
+        buf.extend_from_slice(data);
+
+        let mut reader = TxReader::new(Cursor::new(buf), fin_format)?;
+        let tx = reader
+            .read_transaction()?
+            .ok_or_else(|| ParsError::WrongFormat("Отсутствует транзакция".to_owned()))?;
+        if reader.read_transaction()?.is_some() {
+            return Err(ParsError::WrongFormat(
+                "Ожидалась одна транзакция".to_owned(),
+            ));
+        }
+        Ok(tx)
+    }
+
+    /// Чтение одной транзакции из строки в заданном формате
+    pub fn from_str_format(s: &str, fin_format: Format) -> Result<Self, ParsError> {
+        Self::from_bytes_format(s.as_bytes(), fin_format)
+    }
+
+    /// Запись транзакции в байты в заданном формате
+    pub fn to_bytes_format(&self, fin_format: Format) -> Result<Vec<u8>, ParsError> {
+        let mut writer = TxWriter::new(Vec::new(), fin_format)?;
+        writer.write_transaction(self)?;
+        writer.into_inner()
+    }
+
+    /// Запись транзакции в строку в заданном формате. Для bin возвращается ошибка,
+    /// так как результат не является строкой UTF-8
+    pub fn to_string_format(&self, fin_format: Format) -> Result<String, ParsError> {
+        let buf = self.to_bytes_format(fin_format)?;
+        Ok(std::str::from_utf8(&buf)?.to_owned())
+    }
+
+    /// Отпечаток содержимого: SHA-256 канонического представления полей (как в теле
+    /// записи bin). Не зависит от формата, из которого прочитана транзакция, и не меняется
+    /// между версиями библиотеки
+    pub fn fingerprint(&self) -> Fingerprint {
+        transaction_fingerprint(&TransactionRef::from(self))
+    }
+
+    /// Сумма со знаком: отрицательная для трат, для зачислений и передач равна amount.
+    /// Ошибка [ParsError::AmountOverflow], если знак трат на i64::MIN нельзя сменить
+    pub fn signed_amount(&self) -> Result<Money, ParsError> {
+        TransactionRef::from(self).signed_amount()
+    }
+
+    /// Объект json транзакции в одну строку, например `{"tx_id":1,"tx_type":"DEPOSIT",
+    /// "from_user_id":0,"to_user_id":2,"amount":100,"timestamp":1633036860000,
+    /// "status":"SUCCESS","description":"..."}`. Время записывается в миллисекундах
+    #[cfg(feature = "serde")]
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("Сериализация транзакции в json не завершается ошибкой")
+    }
+}
+
+#[cfg(feature = "serde")]
+fn serialize_tx_type<S: serde::Serializer>(val: &TxType, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(type_name(*val))
+}
+
+#[cfg(feature = "serde")]
+fn serialize_status<S: serde::Serializer>(
+    val: &TxStatus,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(status_name(*val))
+}
+
+/// Разбор транзакции из строки в формате text
+impl FromStr for Transaction {
+    type Err = ParsError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_str_format(s, Format::Text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tx_for_test() -> Transaction {
+        Transaction {
+            tx_id: 1000000000000000,
+            tx_type: TxType::Deposit,
+            from_user_id: 0,
+            to_user_id: 9223372036854775807,
+            amount: 100,
+            timestamp: DateTime::from_timestamp_millis(1633036860000).unwrap(),
+            status: TxStatus::Failure,
+            description: "Record number 1".to_owned(),
+        }
+    }
+
+    #[test]
+    fn test_from_str() {
+        let text = r#"
+            TX_TYPE: DEPOSIT
+            TO_USER_ID: 9223372036854775807
+            FROM_USER_ID: 0
+            TIMESTAMP: 1633036860000
+            DESCRIPTION: "Record number 1"
+            TX_ID: 1000000000000000
+            AMOUNT: 100
+            STATUS: FAILURE
+        "#;
+        assert_eq!(text.parse::<Transaction>().unwrap(), tx_for_test());
+    }
+
+    #[test]
+    fn test_signed_amount() {
+        let mut tx = tx_for_test();
+        assert_eq!(tx.signed_amount().unwrap(), Money::new(100));
+        tx.tx_type = TxType::Withdrawal;
+        assert_eq!(tx.signed_amount().unwrap(), Money::new(-100));
+        tx.amount = i64::MIN;
+        assert!(matches!(
+            tx.signed_amount(),
+            Err(ParsError::AmountOverflow { .. })
+        ));
+    }
+
+    #[test]
+    fn test_csv_without_header() {
+        let csv = r#"1000000000000000,DEPOSIT,0,9223372036854775807,100,1633036860000,FAILURE,"Record number 1""#;
+        let tx = Transaction::from_str_format(csv, Format::Csv).unwrap();
+        assert_eq!(tx, tx_for_test());
+    }
+
+    #[test]
+    fn test_string_format_roundtrip() {
+        for fin_format in [Format::Csv, Format::Text] {
+            let s = tx_for_test().to_string_format(fin_format).unwrap();
+            let tx = Transaction::from_str_format(&s, fin_format).unwrap();
+            assert_eq!(tx, tx_for_test());
+        }
+
+        let buf = tx_for_test().to_bytes_format(Format::Bin).unwrap();
+        let tx = Transaction::from_bytes_format(&buf, Format::Bin).unwrap();
+        assert_eq!(tx, tx_for_test());
+        assert!(tx_for_test().to_string_format(Format::Bin).is_err());
+    }
+
+    #[test]
+    fn test_multiple_records() {
+        let mut buf = tx_for_test().to_bytes_format(Format::Bin).unwrap();
+        buf.extend(tx_for_test().to_bytes_format(Format::Bin).unwrap());
+        assert!(Transaction::from_bytes_format(&buf, Format::Bin).is_err());
+        assert!(Transaction::from_bytes_format(&[], Format::Bin).is_err());
+    }
+}
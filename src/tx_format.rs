@@ -1,23 +1,38 @@
 use super::bin_format::{BinTxReader, BinTxWriter};
+use super::builder::{TxReaderBuilder, TxWriterBuilder};
+use super::chain::ChainedReader;
 use super::csv_format::{CsvTxReader, CsvTxWriter};
+use super::dead_letter::{DeadLetter, Rejected};
+#[cfg(feature = "parallel")]
+use super::error::ErrorPosition;
 use super::error::ParsError;
+use super::format::{
+    DETECT_PREFIX_LEN, Format, TransactionRead, TransactionWrite, find_format, read_fin_data,
+    write_fin_data,
+};
+use super::merkle::MerkleFooter;
+use super::metrics::Metrics;
+use super::options::{ErrorPolicy, ReaderOptions, WriterOptions};
+use super::report::{ErrorEntry, ErrorReport};
 use super::text_format::{TextTxReader, TextTxWriter};
 use super::transaction::*;
+#[cfg(feature = "parallel")]
+use super::utils::Location;
+use super::warning::Warning;
 
-use std::io::{Read, Write};
-
-const CSV_FORMAT: &str = "csv";
-const TEXT_FORMAT: &str = "text";
-const BIN_FORMAT: &str = "bin";
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Chain, Cursor, Read, Write};
+use std::path::Path;
 
 /// # Основной функционал библиотеки,
 /// # реализующий методы записи и чтения транзакций в различных форматах
 /// ## Example
-
+///
 ///```
+/// use fin_parser::format::Format;
 /// use fin_parser::tx_format::{TxReader, TxWriter};
 /// use std::io::Cursor;
-
+///
 /// fn main() {
 ///     let text_tx = r#"# Record 1 (DEPOSIT)
 ///     TX_TYPE: DEPOSIT
@@ -29,94 +44,1451 @@ const BIN_FORMAT: &str = "bin";
 ///     AMOUNT: 100
 ///     STATUS: FAILURE
 ///     "#;
-
+///
 ///     let cursor = Cursor::new(text_tx.as_bytes());
-///     let mut reader = TxReader::new(cursor, "text").unwrap();
+///     let mut reader = TxReader::new(cursor, Format::Text).unwrap();
 ///     let tx = reader.read_transaction().unwrap().unwrap();
-
-///     let mut writer = TxWriter::new(std::io::stdout(), "csv").unwrap();
+///
+///     let mut writer = TxWriter::new(std::io::stdout(), Format::Csv).unwrap();
 ///     writer.write_transaction(&tx).unwrap();
 /// }
-
 ///```
-
+///
 /// Обертка над потоком Read, читающая транзакции, записанные в различных форматах
-pub enum TxReader<In: Read> {
-    /// csv
+pub struct TxReader<In: Read> {
+    reader: FormatReader<In>,
+    records: u64,
+    peeked: Option<Option<Transaction>>,
+    // Транзакция, на которую ссылается результат read_transaction_ref
+    // для пользовательских форматов и просмотренных через peek записей
+    current: Option<Transaction>,
+    error_policy: ErrorPolicy,
+    report: ErrorReport,
+    metrics: Option<Metrics>,
+    // Позиция, до которой чтение учтено в метриках
+    metrics_synced: Position,
+    dead_letter: Option<Box<dyn DeadLetter>>,
+}
+
+/// Позиция читателя в потоке
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Default)]
+pub struct Position {
+    /// Количество прочитанных или пропущенных записей
+    pub records: u64,
+    /// Количество байт, прочитанных из потока формата (после распаковки).
+    /// Для пользовательских форматов всегда 0
+    pub bytes: u64,
+}
+
+enum FormatReader<In: Read> {
     Csv(CsvTxReader<In>),
-    /// text
     Text(TextTxReader<In>),
-    /// bin
     Bin(BinTxReader<In>),
-    /// Неподдерживаемый формат
-    Unsupported(String),
+    Custom(Box<dyn TransactionRead + Send>),
 }
 
 impl<In: Read> TxReader<In> {
-    /// Конструктор, принимающий на вход поток и один из трёх форматов
-    /// - csv
-    /// - text
-    /// - bin
-    pub fn new(stream: In, fin_format: &str) -> Result<Self, ParsError> {
-        let res = match fin_format {
-            CSV_FORMAT => Self::Csv(CsvTxReader::new(stream)?),
-            TEXT_FORMAT => Self::Text(TextTxReader::new(stream)?),
-            BIN_FORMAT => Self::Bin(BinTxReader::new(stream)?),
-            _ => Self::Unsupported(fin_format.to_owned()),
+    /// Конструктор, принимающий на вход поток и формат данных
+    pub fn new(stream: In, fin_format: Format) -> Result<Self, ParsError> {
+        Self::with_options(stream, fin_format, ReaderOptions::default())
+    }
+
+    /// Конструктор с настройками чтения. Пользовательские форматы открываются
+    /// через [TxReader::with_custom]. Сжатие из настроек здесь не применяется,
+    /// для него используется [crate::builder::TxReaderBuilder]
+    pub fn with_options(
+        stream: In,
+        fin_format: Format,
+        options: ReaderOptions,
+    ) -> Result<Self, ParsError> {
+        if options.time_range.is_some() && fin_format != Format::Bin {
+            return Err(ParsError::WrongFormat(format!(
+                "Отбор по времени при чтении поддерживается только для bin, формат {fin_format}"
+            )));
+        }
+        let error_policy = options.error_policy;
+        let report = ErrorReport::new(options.max_errors);
+        let reader = match fin_format {
+            Format::Csv => FormatReader::Csv(CsvTxReader::with_options(stream, options)?),
+            Format::Text => FormatReader::Text(TextTxReader::with_options(stream, options)?),
+            Format::Bin => FormatReader::Bin(BinTxReader::with_options(stream, options)?),
+            Format::Custom(name) => return Err(custom_format_error(name)),
         };
-        Ok(res)
+        #[cfg(feature = "tracing")]
+        tracing::debug!(format = %fin_format, "Открыт читатель транзакций");
+        Ok(Self::from_format_reader(reader, error_policy, report))
+    }
+
+    fn from_format_reader(
+        reader: FormatReader<In>,
+        error_policy: ErrorPolicy,
+        report: ErrorReport,
+    ) -> Self {
+        Self {
+            reader,
+            records: 0,
+            peeked: None,
+            current: None,
+            error_policy,
+            report,
+            metrics: None,
+            metrics_synced: Position::default(),
+            dead_letter: None,
+        }
+    }
+
+    /// Определение формата по первым байтам потока. Возвращает найденный формат
+    /// и читателя, который читает поток целиком, включая просмотренные байты
+    pub fn detect(stream: In) -> Result<(Format, DetectedTxReader<In>), ParsError> {
+        Self::detect_with_options(stream, ReaderOptions::default())
+    }
+
+    /// Определение формата по первым байтам потока с настройками чтения
+    pub fn detect_with_options(
+        mut stream: In,
+        options: ReaderOptions,
+    ) -> Result<(Format, DetectedTxReader<In>), ParsError> {
+        let mut prefix = Vec::new();
+        stream
+            .by_ref()
+            .take(DETECT_PREFIX_LEN)
+            .read_to_end(&mut prefix)?;
+        let fin_format = Format::detect(&prefix)?;
+        let reader =
+            TxReader::with_options(Cursor::new(prefix).chain(stream), fin_format, options)?;
+        Ok((fin_format, reader))
+    }
+}
+
+impl<In: Read + Send + 'static> TxReader<In> {
+    /// Конструктор, поддерживающий кроме встроенных форматов пользовательские
+    /// ([Format::Custom]). Читатель пользовательского формата владеет потоком,
+    /// поэтому поток должен быть `Send + 'static`
+    pub fn with_custom(
+        stream: In,
+        fin_format: Format,
+        options: ReaderOptions,
+    ) -> Result<Self, ParsError> {
+        let Format::Custom(name) = fin_format else {
+            return Self::with_options(stream, fin_format, options);
+        };
+        if options.time_range.is_some() {
+            return Err(ParsError::WrongFormat(format!(
+                "Отбор по времени при чтении поддерживается только для bin, формат {fin_format}"
+            )));
+        }
+        let error_policy = options.error_policy;
+        let report = ErrorReport::new(options.max_errors);
+        let reader = FormatReader::Custom(find_format(name)?.reader(Box::new(stream))?);
+        #[cfg(feature = "tracing")]
+        tracing::debug!(format = %fin_format, "Открыт читатель транзакций");
+        Ok(Self::from_format_reader(reader, error_policy, report))
+    }
+}
+
+impl TxReader<Box<dyn Read + Send>> {
+    /// Открытие файла на чтение. Формат и сжатие определяются по расширению файла:
+    /// `.csv`, `.txt`, `.bin`, а также `.csv.gz` и т.п. для сжатых gzip файлов
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self, ParsError> {
+        TxReaderBuilder::new().open(path)
+    }
+
+    /// Чтение нескольких файлов одного формата как одного потока, например файлов
+    /// выгрузки по дням. Формат определяется по расширению и должен совпадать у всех файлов
+    pub fn chain<P: AsRef<Path>>(
+        paths: impl IntoIterator<Item = P>,
+    ) -> Result<ChainedReader, ParsError> {
+        ChainedReader::open(paths)
+    }
+}
+
+/// Читатель, возвращаемый [TxReader::detect]: сначала отдает просмотренные байты,
+/// затем оставшуюся часть исходного потока
+pub type DetectedTxReader<In> = TxReader<Chain<Cursor<Vec<u8>>, In>>;
+
+impl<In: Read> TxReader<In> {
+    /// Поведение при ошибке в записи. В режиме [ErrorPolicy::Skip] ошибочная запись
+    /// пропускается, а ошибка сохраняется в отчете [TxReader::error_report].
+    /// Ошибки ввода-вывода и заголовка, а также ошибки пользовательских форматов
+    /// не пропускаются
+    pub fn with_error_policy(mut self, policy: ErrorPolicy) -> Self {
+        self.error_policy = policy;
+        self
+    }
+
+    /// Предел количества пропущенных ошибочных записей в режиме [ErrorPolicy::Skip].
+    /// При превышении предела чтение прерывается с ошибкой [ParsError::TooManyErrors]
+    pub fn with_max_errors(mut self, max_errors: usize) -> Self {
+        self.report = ErrorReport::new(Some(max_errors));
+        self
+    }
+
+    /// Отчет об ошибках записей, пропущенных в режиме [ErrorPolicy::Skip]
+    pub fn error_report(&self) -> &ErrorReport {
+        &self.report
+    }
+
+    /// Корень дерева Меркла из последнего прочитанного блока корня файла bin,
+    /// см. [crate::merkle::MerkleTree]. None для других форматов и до чтения блока
+    pub fn merkle_footer(&self) -> Option<&MerkleFooter> {
+        match &self.reader {
+            FormatReader::Bin(bin_reader) => bin_reader.merkle_footer(),
+            _ => None,
+        }
+    }
+
+    /// Смещение начала последней прочитанной записи в байтах. Для форматов,
+    /// зарегистрированных через [crate::format::register_format], ноль
+    pub(crate) fn last_record_start(&self) -> u64 {
+        match &self.reader {
+            FormatReader::Csv(csv_reader) => csv_reader.last_record_start(),
+            FormatReader::Text(text_reader) => text_reader.last_record_start(),
+            FormatReader::Bin(bin_reader) => bin_reader.last_record_start(),
+            FormatReader::Custom(_) => 0,
+        }
+    }
+
+    /// Длина заголовка схемы файла bin версии 2. Для остальных файлов ноль
+    pub(crate) fn bin_header_len(&mut self) -> Result<u64, ParsError> {
+        match &mut self.reader {
+            FormatReader::Bin(bin_reader) => bin_reader.header_len(),
+            _ => Ok(0),
+        }
+    }
+
+    /// Учет чтения в метриках: счетчики обновляются при каждом чтении и пропуске записей,
+    /// ошибки записей учитываются независимо от политики обработки ошибок
+    pub fn with_metrics(mut self, metrics: Metrics) -> Self {
+        self.metrics_synced = self.position();
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// Перенос в метрики записей и байт, прочитанных с предыдущего обновления
+    fn sync_metrics(&mut self) {
+        let Some(metrics) = &self.metrics else {
+            return;
+        };
+        let position = self.position();
+        metrics.add_read(
+            position.records - self.metrics_synced.records,
+            position.bytes - self.metrics_synced.bytes,
+        );
+        self.metrics_synced = position;
+    }
+
+    /// Передача записей, пропущенных в режиме [ErrorPolicy::Skip], получателю
+    /// вместе с исходными байтами, например для исправления и повторной загрузки
+    /// ([crate::dead_letter::DeadLetterWriter]). Для пользовательских форматов
+    /// ошибки не пропускаются, поэтому получатель не вызывается
+    pub fn with_dead_letter(mut self, dead_letter: impl DeadLetter + 'static) -> Self {
+        match &mut self.reader {
+            FormatReader::Csv(csv_reader) => csv_reader.set_capture_raw(true),
+            FormatReader::Text(text_reader) => text_reader.set_capture_raw(true),
+            FormatReader::Bin(bin_reader) => bin_reader.set_capture_raw(true),
+            FormatReader::Custom(_) => {}
+        }
+        self.dead_letter = Some(Box::new(dead_letter));
+        self
+    }
+
+    /// Сброс исходных байт после успешно прочитанной записи
+    fn clear_captured(&mut self) {
+        if self.dead_letter.is_none() {
+            return;
+        }
+        match &mut self.reader {
+            FormatReader::Csv(csv_reader) => csv_reader.clear_captured(),
+            FormatReader::Text(text_reader) => text_reader.clear_captured(),
+            FormatReader::Bin(bin_reader) => bin_reader.clear_captured(),
+            FormatReader::Custom(_) => {}
+        }
+    }
+
+    /// Извлечение отчета об ошибках. Предел количества ошибок сохраняется
+    pub fn take_error_report(&mut self) -> ErrorReport {
+        let max_errors = self.report.max_errors();
+        std::mem::replace(&mut self.report, ErrorReport::new(max_errors))
+    }
+
+    /// Обработка ошибки записи согласно политике: ошибка либо возвращается,
+    /// либо сохраняется, а читатель переходит к следующей записи
+    fn recover(&mut self, e: ParsError) -> Result<(), ParsError> {
+        let skip = self.error_policy == ErrorPolicy::Skip
+            && e.is_recoverable()
+            && !matches!(self.reader, FormatReader::Custom(_));
+        #[cfg(feature = "tracing")]
+        tracing::warn!(
+            record = e.position().map_or(self.records, |position| position.record),
+            error = %e,
+            skip,
+            "Ошибка чтения записи"
+        );
+        if let Some(metrics) = &self.metrics {
+            metrics.add_error();
+        }
+        if !skip {
+            return Err(e);
+        }
+        match &mut self.reader {
+            FormatReader::Csv(csv_reader) => csv_reader.resync()?,
+            FormatReader::Text(text_reader) => text_reader.resync()?,
+            FormatReader::Bin(bin_reader) => bin_reader.resync()?,
+            FormatReader::Custom(_) => return Err(e),
+        }
+        let record = e
+            .position()
+            .map_or(self.records, |position| position.record);
+        if let Some(dead_letter) = &mut self.dead_letter {
+            let (byte, raw) = match &mut self.reader {
+                FormatReader::Csv(csv_reader) => csv_reader.take_rejected(),
+                FormatReader::Text(text_reader) => text_reader.take_rejected(),
+                FormatReader::Bin(bin_reader) => bin_reader.take_rejected(),
+                FormatReader::Custom(_) => return Err(e),
+            };
+            dead_letter.reject(&Rejected {
+                record,
+                byte,
+                error: &e,
+                raw: &raw,
+            })?;
+        }
+        let snippet = e.snippet().map(str::to_owned);
+        self.report.push(ErrorEntry {
+            record,
+            error: e,
+            snippet,
+        })
+    }
+
+    /// Извлечение предупреждений, накопленных с момента предыдущего вызова.
+    /// Предупреждения собираются, только если это включено в настройках
+    /// ([crate::builder::TxReaderBuilder::collect_warnings]). Для пользовательских
+    /// форматов предупреждения не собираются
+    pub fn take_warnings(&mut self) -> Vec<Warning> {
+        match &mut self.reader {
+            FormatReader::Csv(csv_reader) => csv_reader.take_warnings(),
+            FormatReader::Text(text_reader) => text_reader.take_warnings(),
+            FormatReader::Bin(bin_reader) => bin_reader.take_warnings(),
+            FormatReader::Custom(_) => Vec::new(),
+        }
     }
 
     /// Метод чтения одной транзакции. TxReader читает порциями из потока, чтобы не создавать
     /// дополнительную нагрузку на память
     pub fn read_transaction(&mut self) -> Result<Option<Transaction>, ParsError> {
-        match self {
-            Self::Csv(csv_reader) => csv_reader.read_transaction(),
-            Self::Text(text_reader) => text_reader.read_transaction(),
-            Self::Bin(bin_reader) => bin_reader.read_transaction(),
-            Self::Unsupported(err) => {
-                return Err(ParsError::WrongFormat(err.to_owned()));
+        let res = match self.peeked.take() {
+            Some(peeked) => peeked,
+            None => self.read_next()?,
+        };
+        if res.is_some() {
+            self.records += 1;
+            #[cfg(feature = "tracing")]
+            tracing::trace!(record = self.records - 1, "Прочитана транзакция");
+        }
+        self.sync_metrics();
+        Ok(res)
+    }
+
+    /// Чтение транзакции без выделения памяти под описание: описание заимствуется
+    /// из внутреннего буфера читателя и действительно до следующего чтения.
+    /// Для пользовательских форматов транзакция читается целиком
+    pub fn read_transaction_ref(&mut self) -> Result<Option<TransactionRef<'_>>, ParsError> {
+        if let Some(peeked) = self.peeked.take() {
+            if peeked.is_some() {
+                self.records += 1;
+            }
+            self.sync_metrics();
+            self.current = peeked;
+            return Ok(self.current.as_ref().map(TransactionRef::from));
+        }
+        // Ссылка на буфер читателя не может пережить восстановление после ошибки,
+        // поэтому в цикле описание отбрасывается и берется у читателя после него
+        let tx = loop {
+            let res = match &mut self.reader {
+                FormatReader::Csv(csv_reader) => csv_reader.read_transaction_ref().map(detach),
+                FormatReader::Text(text_reader) => text_reader.read_transaction_ref().map(detach),
+                FormatReader::Bin(bin_reader) => bin_reader.read_transaction_ref().map(detach),
+                FormatReader::Custom(reader) => reader.read_transaction().map(|tx| {
+                    self.current = tx;
+                    detach(self.current.as_ref().map(TransactionRef::from))
+                }),
+            };
+            match res {
+                Ok(tx) => break tx,
+                Err(e) => self.recover(e)?,
+            }
+        };
+        self.clear_captured();
+        let Some(tx) = tx else {
+            self.sync_metrics();
+            return Ok(None);
+        };
+        self.records += 1;
+        self.sync_metrics();
+        let description = match &self.reader {
+            FormatReader::Csv(csv_reader) => csv_reader.last_description(),
+            FormatReader::Text(text_reader) => text_reader.last_description(),
+            FormatReader::Bin(bin_reader) => bin_reader.last_description(),
+            FormatReader::Custom(_) => self
+                .current
+                .as_ref()
+                .map_or("", |tx| tx.description.as_str()),
+        };
+        Ok(Some(TransactionRef { description, ..tx }))
+    }
+
+    /// Просмотр следующей транзакции без ее извлечения: следующий вызов
+    /// [TxReader::read_transaction] вернет эту же транзакцию
+    pub fn peek(&mut self) -> Result<Option<&Transaction>, ParsError> {
+        if self.peeked.is_none() {
+            self.peeked = Some(self.read_next()?);
+        }
+        Ok(self.peeked.as_ref().and_then(Option::as_ref))
+    }
+
+    fn read_next(&mut self) -> Result<Option<Transaction>, ParsError> {
+        loop {
+            let res = match &mut self.reader {
+                FormatReader::Csv(csv_reader) => csv_reader.read_transaction(),
+                FormatReader::Text(text_reader) => text_reader.read_transaction(),
+                FormatReader::Bin(bin_reader) => bin_reader.read_transaction(),
+                FormatReader::Custom(reader) => reader.read_transaction(),
+            };
+            match res {
+                Ok(tx) => {
+                    self.clear_captured();
+                    return Ok(tx);
+                }
+                Err(e) => self.recover(e)?,
             }
         }
     }
+
+    /// Пропуск n записей без построения транзакций. Для bin используется размер записи
+    /// из заголовка, для csv и text записи только разбиваются на поля.
+    /// Возвращает количество пропущенных записей, которое меньше n, если поток закончился
+    pub fn skip(&mut self, n: u64) -> Result<u64, ParsError> {
+        self.advance(n, false)
+    }
+
+    /// Проверка n записей без построения транзакций: структура записей и синтаксис
+    /// полей проверяются так же, как при чтении, но значения не собираются в транзакции,
+    /// а описания и время не создаются. Предупреждения при этом не собираются.
+    /// Ошибки обрабатываются согласно политике ([TxReader::with_error_policy]).
+    /// Возвращает количество корректных записей, которое меньше n, если поток закончился
+    ///
+    /// ```
+    /// use fin_parser::format::Format;
+    /// use fin_parser::tx_format::TxReader;
+    /// use std::io::Cursor;
+    ///
+    /// let csv = "TX_ID,TX_TYPE,FROM_USER_ID,TO_USER_ID,AMOUNT,TIMESTAMP,STATUS,DESCRIPTION\n\
+    ///     1,DEPOSIT,0,5,100,1633036860000,SUCCESS,\"Record number 1\"\n\
+    ///     2,DEPOSIT,0,6,2O0,1633036920000,SUCCESS,\"Record number 2\"\n";
+    /// let mut reader = TxReader::new(Cursor::new(csv), Format::Csv).unwrap();
+    /// assert_eq!(reader.validate_only(1).unwrap(), 1);
+    /// assert!(reader.validate_only(u64::MAX).is_err());
+    /// ```
+    pub fn validate_only(&mut self, n: u64) -> Result<u64, ParsError> {
+        self.advance(n, true)
+    }
+
+    /// Переход через n записей с проверкой полей или без нее
+    fn advance(&mut self, n: u64, validate: bool) -> Result<u64, ParsError> {
+        let mut skipped = 0;
+        if n > 0
+            && let Some(peeked) = self.peeked.take()
+        {
+            if peeked.is_none() {
+                return Ok(0);
+            }
+            skipped += 1;
+            self.records += 1;
+        }
+        while skipped < n {
+            let res = match (&mut self.reader, validate) {
+                (FormatReader::Csv(csv_reader), false) => csv_reader.skip_record(),
+                (FormatReader::Csv(csv_reader), true) => csv_reader.validate_record(),
+                (FormatReader::Text(text_reader), false) => text_reader.skip_record(),
+                (FormatReader::Text(text_reader), true) => text_reader.validate_record(),
+                (FormatReader::Bin(bin_reader), false) => bin_reader.skip_record(),
+                (FormatReader::Bin(bin_reader), true) => bin_reader.validate_record(),
+                (FormatReader::Custom(reader), _) => {
+                    reader.read_transaction().map(|tx| tx.is_some())
+                }
+            };
+            let has_record = match res {
+                Ok(val) => val,
+                Err(e) => {
+                    self.recover(e)?;
+                    continue;
+                }
+            };
+            if !has_record {
+                break;
+            }
+            self.clear_captured();
+            skipped += 1;
+            self.records += 1;
+        }
+        self.sync_metrics();
+        Ok(skipped)
+    }
+
+    /// Подсчет оставшихся в потоке записей без построения транзакций
+    pub fn count(&mut self) -> Result<u64, ParsError> {
+        self.skip(u64::MAX)
+    }
+
+    /// Текущая позиция читателя: количество пройденных записей и прочитанных байт.
+    /// Запись, просмотренная через [TxReader::peek], учитывается в байтах, но не в записях
+    pub fn position(&self) -> Position {
+        let bytes = match &self.reader {
+            FormatReader::Csv(csv_reader) => csv_reader.bytes_read(),
+            FormatReader::Text(text_reader) => text_reader.bytes_read(),
+            FormatReader::Bin(bin_reader) => bin_reader.bytes_read(),
+            FormatReader::Custom(_) => 0,
+        };
+        Position {
+            records: self.records,
+            bytes,
+        }
+    }
+
+    /// Чтение до max транзакций с добавлением в конец out.
+    /// Возвращает количество прочитанных транзакций, 0 означает конец потока
+    pub fn read_batch(
+        &mut self,
+        out: &mut Vec<Transaction>,
+        max: usize,
+    ) -> Result<usize, ParsError> {
+        TransactionRead::read_batch(self, out, max)
+    }
+
+    /// Метод чтения всех оставшихся в потоке транзакций
+    pub fn read_all(&mut self) -> Result<Vec<Transaction>, ParsError> {
+        read_fin_data(self)
+    }
+
+    /// Исходный поток встроенного формата. Для пользовательских форматов возвращается None
+    #[cfg(feature = "async")]
+    pub(crate) fn get_mut(&mut self) -> Option<&mut In> {
+        match &mut self.reader {
+            FormatReader::Csv(csv_reader) => Some(csv_reader.get_mut()),
+            FormatReader::Text(text_reader) => Some(text_reader.get_mut()),
+            FormatReader::Bin(bin_reader) => Some(bin_reader.get_mut()),
+            FormatReader::Custom(_) => None,
+        }
+    }
+
+    /// Транзакция, просмотренная через [TxReader::peek] и еще не прочитанная
+    #[cfg(feature = "parallel")]
+    pub(crate) fn take_peeked(&mut self) -> Option<Transaction> {
+        let tx = self.peeked.take().flatten();
+        if tx.is_some() {
+            self.records += 1;
+        }
+        self.sync_metrics();
+        tx
+    }
+
+    /// Чтение до max записей в исходном виде для разбора в другом потоке.
+    /// Для пользовательских форматов возвращается None
+    #[cfg(feature = "parallel")]
+    pub(crate) fn read_raw_chunk(&mut self, max: usize) -> Result<Option<RawChunk>, ParsError> {
+        let (fin_format, mut options, mut data) = match &mut self.reader {
+            FormatReader::Csv(csv_reader) => {
+                let header = csv_reader.header_line()?;
+                (Format::Csv, csv_reader.options().clone(), header)
+            }
+            FormatReader::Text(text_reader) => {
+                (Format::Text, text_reader.options().clone(), Vec::new())
+            }
+            FormatReader::Bin(bin_reader) => {
+                let header = bin_reader.schema_header()?;
+                (Format::Bin, bin_reader.options().clone(), header)
+            }
+            FormatReader::Custom(_) => return Ok(None),
+        };
+        // Ошибки частей не пропускаются: отчет и предупреждения части недоступны читателю
+        options.error_policy = ErrorPolicy::Fail;
+        options.collect_warnings = false;
+
+        let first_record = self.records;
+        let mut starts = Vec::new();
+        while starts.len() < max {
+            let offset = data.len();
+            let stream = match &mut self.reader {
+                FormatReader::Csv(csv_reader) => csv_reader
+                    .read_raw_record(&mut data)?
+                    .then(|| csv_reader.last_record_location()),
+                FormatReader::Text(text_reader) => text_reader
+                    .read_raw_record(&mut data)?
+                    .then(|| text_reader.last_record_location()),
+                FormatReader::Bin(bin_reader) => {
+                    bin_reader.read_raw_record(&mut data)?.then(|| Location {
+                        byte: bin_reader.last_record_start(),
+                        ..Default::default()
+                    })
+                }
+                FormatReader::Custom(_) => return Ok(None),
+            };
+            let Some(stream) = stream else {
+                break;
+            };
+            self.clear_captured();
+            starts.push(RecordStart { offset, stream });
+        }
+        let records = starts.len();
+        self.records += records as u64;
+        self.sync_metrics();
+        Ok(Some(RawChunk {
+            fin_format,
+            options,
+            data,
+            first_record,
+            starts,
+            records,
+        }))
+    }
+}
+
+/// Начало записи части: смещение в данных части и положение в исходном потоке
+#[cfg(feature = "parallel")]
+struct RecordStart {
+    offset: usize,
+    stream: Location,
+}
+
+/// Часть потока из целых записей в исходном формате
+#[cfg(feature = "parallel")]
+pub(crate) struct RawChunk {
+    fin_format: Format,
+    options: ReaderOptions,
+    data: Vec<u8>,
+    // Номер первой записи части в исходном потоке
+    first_record: u64,
+    starts: Vec<RecordStart>,
+    pub(crate) records: usize,
+}
+
+#[cfg(feature = "parallel")]
+impl RawChunk {
+    /// Разбор всех записей части. Место ошибки пересчитывается
+    /// от начала исходного потока
+    pub(crate) fn parse(self) -> Result<Vec<Transaction>, ParsError> {
+        let Self {
+            fin_format,
+            options,
+            data,
+            first_record,
+            starts,
+            ..
+        } = self;
+        TxReader::with_options(Cursor::new(data.as_slice()), fin_format, options)
+            .and_then(|mut reader| reader.read_all())
+            .map_err(|e| match e {
+                ParsError::WithPosition {
+                    position,
+                    error,
+                    snippet,
+                } => ParsError::WithPosition {
+                    position: stream_position(&data, first_record, &starts, position),
+                    error,
+                    snippet,
+                },
+                e => e,
+            })
+    }
+}
+
+/// Место ошибки в исходном потоке по месту в данных части
+#[cfg(feature = "parallel")]
+fn stream_position(
+    data: &[u8],
+    first_record: u64,
+    starts: &[RecordStart],
+    position: ErrorPosition,
+) -> ErrorPosition {
+    let Some(start) = starts.get(position.record as usize) else {
+        return ErrorPosition {
+            record: first_record + position.record,
+            ..position
+        };
+    };
+    // Строка начала записи в данных части, с единицы
+    let chunk_line = 1 + data[..start.offset].iter().filter(|b| **b == b'\n').count() as u64;
+    let line = position
+        .line
+        .map(|line| start.stream.line + line.saturating_sub(chunk_line));
+    // Запись в части начинается с первого столбца строки
+    let column = match position.line {
+        Some(line) if line == chunk_line => position
+            .column
+            .map(|column| column + start.stream.column - 1),
+        _ => position.column,
+    };
+    ErrorPosition {
+        record: first_record + position.record,
+        byte: start.stream.byte + position.byte.saturating_sub(start.offset as u64),
+        line,
+        column,
+    }
+}
+
+/// Транзакция без заимствованного описания
+fn detach(tx: Option<TransactionRef<'_>>) -> Option<TransactionRef<'static>> {
+    tx.map(|tx| TransactionRef {
+        description: "",
+        ..tx
+    })
 }
 
-/// Обертка над потоком Write, пишущая транзакции, в различных форматах
-pub enum TxWriter<Out: Write> {
-    /// Csv
+enum FormatWriter<Out: Write> {
     Csv(CsvTxWriter<Out>),
-    /// Text
     Text(TextTxWriter<Out>),
-    /// Bin
     Bin(BinTxWriter<Out>),
-    /// Неподдерживаемый формат
-    Unsupported(String),
+    Custom(Box<dyn TransactionWrite + Send>),
+}
+
+/// Обертка над потоком Write, пишущая транзакции, в различных форматах.
+/// При уничтожении писатель вызывает [TxWriter::finish], игнорируя ошибки,
+/// поэтому для контроля ошибок записи finish следует вызывать явно
+pub struct TxWriter<Out: Write> {
+    writer: Option<FormatWriter<Out>>,
+    metrics: Option<Metrics>,
+    // Количество байт, учтенных в метриках
+    metrics_bytes: u64,
 }
 
 impl<Out: Write> TxWriter<Out> {
-    /// Конструктор, принимающий на вход поток и один из трёх форматов
-    /// - csv
-    /// - text
-    /// - bin
-    pub fn new(stream: Out, fin_format: &str) -> Result<Self, ParsError> {
-        let res = match fin_format {
-            CSV_FORMAT => Self::Csv(CsvTxWriter::new(stream)?),
-            TEXT_FORMAT => Self::Text(TextTxWriter::new(stream)?),
-            BIN_FORMAT => Self::Bin(BinTxWriter::new(stream)?),
-            _ => Self::Unsupported(fin_format.to_owned()),
+    /// Конструктор, принимающий на вход поток и формат данных
+    pub fn new(stream: Out, fin_format: Format) -> Result<Self, ParsError> {
+        Self::with_options(stream, fin_format, WriterOptions::default())
+    }
+
+    /// Конструктор с настройками записи. Пользовательские форматы открываются
+    /// через [TxWriter::with_custom]. Сжатие из настроек здесь не применяется,
+    /// для него используется [crate::builder::TxWriterBuilder]
+    pub fn with_options(
+        stream: Out,
+        fin_format: Format,
+        options: WriterOptions,
+    ) -> Result<Self, ParsError> {
+        let writer = match fin_format {
+            Format::Csv => FormatWriter::Csv(CsvTxWriter::with_options(stream, options)?),
+            Format::Text => FormatWriter::Text(TextTxWriter::with_options(stream, options)?),
+            Format::Bin => FormatWriter::Bin(BinTxWriter::with_options(stream, options)?),
+            Format::Custom(name) => return Err(custom_format_error(name)),
         };
-        Ok(res)
+        #[cfg(feature = "tracing")]
+        tracing::debug!(format = %fin_format, "Открыт писатель транзакций");
+        Ok(Self::from_format_writer(writer))
+    }
+
+    fn from_format_writer(writer: FormatWriter<Out>) -> Self {
+        Self {
+            writer: Some(writer),
+            metrics: None,
+            metrics_bytes: 0,
+        }
+    }
+}
+
+impl<Out: Write + Send + 'static> TxWriter<Out> {
+    /// Конструктор, поддерживающий кроме встроенных форматов пользовательские
+    /// ([Format::Custom]). Писатель пользовательского формата владеет потоком,
+    /// поэтому поток должен быть `Send + 'static`
+    pub fn with_custom(
+        stream: Out,
+        fin_format: Format,
+        options: WriterOptions,
+    ) -> Result<Self, ParsError> {
+        let Format::Custom(name) = fin_format else {
+            return Self::with_options(stream, fin_format, options);
+        };
+        let writer =
+            FormatWriter::Custom(find_format(name)?.writer(Box::new(BufWriter::new(stream)))?);
+        #[cfg(feature = "tracing")]
+        tracing::debug!(format = %fin_format, "Открыт писатель транзакций");
+        Ok(Self::from_format_writer(writer))
+    }
+}
+
+impl TxWriter<Box<dyn Write + Send>> {
+    /// Создание файла на запись. Формат и сжатие определяются по расширению файла
+    /// аналогично [TxReader::from_path]. Существующий файл перезаписывается
+    pub fn create<P: AsRef<Path>>(path: P) -> Result<Self, ParsError> {
+        TxWriterBuilder::new().create(path)
+    }
+}
+
+impl<Out: Write> TxWriter<Out> {
+    fn writer(&mut self) -> Result<&mut FormatWriter<Out>, ParsError> {
+        self.writer
+            .as_mut()
+            .ok_or_else(|| ParsError::IoError(io::Error::other("Поток записи закрыт")))
+    }
+
+    /// Учет записи в метриках: счетчики обновляются при каждой записи транзакций
+    pub fn with_metrics(mut self, metrics: Metrics) -> Self {
+        self.metrics_bytes = self.bytes_written();
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// Перенос в метрики результата записи records транзакций
+    fn sync_metrics(&mut self, res: Result<(), ParsError>, records: u64) -> Result<(), ParsError> {
+        let Some(metrics) = &self.metrics else {
+            return res;
+        };
+        let bytes = self.bytes_written();
+        match res {
+            Ok(()) => metrics.add_written(records, bytes - self.metrics_bytes),
+            Err(_) => {
+                metrics.add_written(0, bytes - self.metrics_bytes);
+                metrics.add_error();
+            }
+        }
+        self.metrics_bytes = bytes;
+        res
     }
 
     /// Метод записи одной транзакции.
     pub fn write_transaction(&mut self, tx: &Transaction) -> Result<(), ParsError> {
-        match self {
-            Self::Csv(csv_writer) => csv_writer.write_transaction(tx),
-            Self::Text(text_writer) => text_writer.write_transaction(tx),
-            Self::Bin(bin_writer) => bin_writer.write_transaction(tx),
-            Self::Unsupported(err) => {
-                return Err(ParsError::WrongFormat(err.to_owned()));
+        let res = match self.writer()? {
+            FormatWriter::Csv(csv_writer) => csv_writer.write_transaction(tx),
+            FormatWriter::Text(text_writer) => text_writer.write_transaction(tx),
+            FormatWriter::Bin(bin_writer) => bin_writer.write_transaction(tx),
+            FormatWriter::Custom(writer) => writer.write_transaction(tx),
+        };
+        self.sync_metrics(res, 1)
+    }
+
+    /// Метод записи набора транзакций
+    pub fn write_all(&mut self, txs: &[Transaction]) -> Result<(), ParsError> {
+        write_fin_data(self, txs)
+    }
+
+    /// Запись набора транзакций. Для встроенных форматов записи собираются в буфер
+    /// и пишутся в поток одним вызовом write_all
+    pub fn write_batch(&mut self, txs: &[Transaction]) -> Result<(), ParsError> {
+        let res = match self.writer()? {
+            FormatWriter::Csv(csv_writer) => csv_writer.write_batch(txs),
+            FormatWriter::Text(text_writer) => text_writer.write_batch(txs),
+            FormatWriter::Bin(bin_writer) => bin_writer.write_batch(txs),
+            FormatWriter::Custom(writer) => writer.write_batch(txs),
+        };
+        self.sync_metrics(res, txs.len() as u64)
+    }
+
+    /// Сброс буферизованных данных в поток
+    pub fn flush(&mut self) -> Result<(), ParsError> {
+        match self.writer()? {
+            FormatWriter::Csv(csv_writer) => csv_writer.flush(),
+            FormatWriter::Text(text_writer) => text_writer.flush(),
+            FormatWriter::Bin(bin_writer) => bin_writer.flush(),
+            FormatWriter::Custom(writer) => writer.flush(),
+        }?;
+        #[cfg(feature = "tracing")]
+        tracing::debug!(bytes = self.bytes_written(), "Буфер записи сброшен");
+        Ok(())
+    }
+
+    /// Завершение записи: дописывает служебные данные формата (например, заголовок
+    /// csv для пустого набора транзакций) и сбрасывает буферы в поток
+    pub fn finish(&mut self) -> Result<(), ParsError> {
+        let res = match self.writer()? {
+            FormatWriter::Csv(csv_writer) => csv_writer.finish(),
+            FormatWriter::Text(text_writer) => text_writer.finish(),
+            FormatWriter::Bin(bin_writer) => bin_writer.finish(),
+            FormatWriter::Custom(writer) => writer.finish(),
+        };
+        // Служебные данные формата, например заголовок csv, учитываются в метриках
+        self.sync_metrics(res, 0)?;
+        #[cfg(feature = "tracing")]
+        tracing::debug!(bytes = self.bytes_written(), "Запись завершена");
+        Ok(())
+    }
+
+    /// Количество байт, записанных в поток формата (до сжатия), включая еще
+    /// не сброшенные из буфера. Для пользовательских форматов всегда 0
+    pub fn bytes_written(&self) -> u64 {
+        match &self.writer {
+            Some(FormatWriter::Csv(csv_writer)) => csv_writer.bytes_written(),
+            Some(FormatWriter::Text(text_writer)) => text_writer.bytes_written(),
+            Some(FormatWriter::Bin(bin_writer)) => bin_writer.bytes_written(),
+            Some(FormatWriter::Custom(_)) | None => 0,
+        }
+    }
+
+    /// Исходный поток встроенного формата. Для пользовательских форматов и закрытого
+    /// писателя возвращается None
+    #[cfg(feature = "async")]
+    pub(crate) fn get_mut(&mut self) -> Option<&mut Out> {
+        match self.writer.as_mut()? {
+            FormatWriter::Csv(csv_writer) => Some(csv_writer.get_mut()),
+            FormatWriter::Text(text_writer) => Some(text_writer.get_mut()),
+            FormatWriter::Bin(bin_writer) => Some(bin_writer.get_mut()),
+            FormatWriter::Custom(_) => None,
+        }
+    }
+
+    #[cfg(feature = "parallel")]
+    pub(crate) fn is_custom(&self) -> bool {
+        matches!(self.writer, Some(FormatWriter::Custom(_)))
+    }
+
+    /// Писатель в память с теми же форматом и настройками для сериализации части данных.
+    /// Для пользовательских форматов не поддерживается
+    #[cfg(feature = "parallel")]
+    pub(crate) fn chunk_writer(&mut self) -> Result<TxWriter<Vec<u8>>, ParsError> {
+        let writer = match self.writer()? {
+            FormatWriter::Csv(csv_writer) => FormatWriter::Csv(csv_writer.chunk_writer()?),
+            FormatWriter::Text(text_writer) => FormatWriter::Text(text_writer.chunk_writer()),
+            FormatWriter::Bin(bin_writer) => FormatWriter::Bin(bin_writer.chunk_writer()),
+            FormatWriter::Custom(_) => {
+                return Err(ParsError::WrongFormat(
+                    "Поток пользовательского формата недоступен".to_owned(),
+                ));
             }
+        };
+        Ok(TxWriter {
+            writer: Some(writer),
+            metrics: None,
+            metrics_bytes: 0,
+        })
+    }
+
+    /// Запись records транзакций, сериализованных писателем из [TxWriter::chunk_writer]
+    #[cfg(feature = "parallel")]
+    pub(crate) fn write_raw(&mut self, data: &[u8], records: u64) -> Result<(), ParsError> {
+        let res = match self.writer()? {
+            FormatWriter::Csv(csv_writer) => csv_writer.write_raw(data),
+            FormatWriter::Text(text_writer) => text_writer.write_raw(data),
+            FormatWriter::Bin(bin_writer) => bin_writer.write_raw(data),
+            FormatWriter::Custom(_) => Err(ParsError::WrongFormat(
+                "Поток пользовательского формата недоступен".to_owned(),
+            )),
+        };
+        self.sync_metrics(res, records)
+    }
+
+    /// Завершение записи и возврат исходного потока.
+    /// Для пользовательских форматов поток недоступен и возвращается ошибка
+    pub fn into_inner(mut self) -> Result<Out, ParsError> {
+        self.finish()?;
+        match self.writer.take() {
+            Some(FormatWriter::Csv(csv_writer)) => csv_writer.into_inner(),
+            Some(FormatWriter::Text(text_writer)) => text_writer.into_inner(),
+            Some(FormatWriter::Bin(bin_writer)) => bin_writer.into_inner(),
+            Some(FormatWriter::Custom(_)) => Err(ParsError::WrongFormat(
+                "Поток пользовательского формата недоступен".to_owned(),
+            )),
+            None => Err(ParsError::IoError(io::Error::other("Поток записи закрыт"))),
+        }
+    }
+}
+
+impl<Out: Write> Drop for TxWriter<Out> {
+    fn drop(&mut self) {
+        if self.writer.is_some() {
+            let _ = self.finish();
+        }
+    }
+}
+
+impl<In: Read> TransactionRead for TxReader<In> {
+    fn read_transaction(&mut self) -> Result<Option<Transaction>, ParsError> {
+        TxReader::read_transaction(self)
+    }
+}
+
+impl<Out: Write> TransactionWrite for TxWriter<Out> {
+    fn write_transaction(&mut self, tx: &Transaction) -> Result<(), ParsError> {
+        TxWriter::write_transaction(self, tx)
+    }
+
+    fn write_batch(&mut self, txs: &[Transaction]) -> Result<(), ParsError> {
+        TxWriter::write_batch(self, txs)
+    }
+
+    fn flush(&mut self) -> Result<(), ParsError> {
+        TxWriter::flush(self)
+    }
+
+    fn finish(&mut self) -> Result<(), ParsError> {
+        TxWriter::finish(self)
+    }
+}
+
+/// Ошибка открытия пользовательского формата конструктором для любых потоков
+fn custom_format_error(name: &str) -> ParsError {
+    ParsError::WrongFormat(format!(
+        "Пользовательский формат {name} открывается через with_custom"
+    ))
+}
+
+/// Чтение всех транзакций из файла в заданном формате
+pub fn read_file<P: AsRef<Path>>(
+    path: P,
+    fin_format: Format,
+) -> Result<Vec<Transaction>, ParsError> {
+    let file = File::open(path)?;
+    let mut reader =
+        TxReader::with_custom(BufReader::new(file), fin_format, ReaderOptions::default())?;
+    reader.read_all()
+}
+
+/// Подсчет записей в файле заданного формата без построения транзакций
+pub fn count_records<P: AsRef<Path>>(path: P, fin_format: Format) -> Result<u64, ParsError> {
+    let file = File::open(path)?;
+    let mut reader =
+        TxReader::with_custom(BufReader::new(file), fin_format, ReaderOptions::default())?;
+    reader.count()
+}
+
+/// Запись транзакций в файл в заданном формате. Существующий файл перезаписывается
+pub fn write_file<P: AsRef<Path>>(
+    path: P,
+    fin_format: Format,
+    txs: &[Transaction],
+) -> Result<(), ParsError> {
+    let file = File::create(path)?;
+    let mut writer = TxWriter::with_custom(file, fin_format, WriterOptions::default())?;
+    writer.write_all(txs)?;
+    writer.finish()
+}
+
+/// Разбор всех транзакций из буфера до первой ошибки. Точка входа для фаззинга
+/// (`fuzz/fuzz_targets`): на любых входных данных разбор завершается ошибкой,
+/// а не паникой или выделением памяти сверх ограничений [ReaderOptions]
+#[doc(hidden)]
+pub fn parse_bytes(data: &[u8], fin_format: Format) -> Result<Vec<Transaction>, ParsError> {
+    TxReader::new(Cursor::new(data), fin_format)?.read_all()
+}
+
+/// Разбор буфера в формате bin, см. [parse_bytes]
+#[doc(hidden)]
+pub fn parse_bin_bytes(data: &[u8]) -> Result<Vec<Transaction>, ParsError> {
+    parse_bytes(data, Format::Bin)
+}
+
+/// Разбор буфера в формате csv, см. [parse_bytes]
+#[doc(hidden)]
+pub fn parse_csv_bytes(data: &[u8]) -> Result<Vec<Transaction>, ParsError> {
+    parse_bytes(data, Format::Csv)
+}
+
+/// Разбор буфера в формате text, см. [parse_bytes]
+#[doc(hidden)]
+pub fn parse_text_bytes(data: &[u8]) -> Result<Vec<Transaction>, ParsError> {
+    parse_bytes(data, Format::Text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{encode, tx};
+    use crate::warning::WarningKind;
+    use chrono::{DateTime, TimeDelta};
+    use std::io::Cursor;
+
+    /// Транзакции с граничными идентификаторами пользователей
+    fn txs_for_test() -> Vec<Transaction> {
+        let first = tx(1000000000000000);
+        let second = tx(1000000000000001);
+        vec![
+            Transaction {
+                to_user_id: 9223372036854775807,
+                status: TxStatus::Failure,
+                description: "Record number 1".to_owned(),
+                ..first
+            },
+            Transaction {
+                tx_type: TxType::Transfer,
+                from_user_id: 9223372036854775807,
+                to_user_id: 9223372036854775807,
+                amount: 200,
+                timestamp: second.timestamp + TimeDelta::minutes(1),
+                status: TxStatus::Pending,
+                description: "Record number 2".to_owned(),
+                ..second
+            },
+        ]
+    }
+
+    struct CountingWriter {
+        buf: Vec<u8>,
+        writes: usize,
+    }
+
+    impl Write for CountingWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.writes += 1;
+            self.buf.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_batch() {
+        for fin_format in Format::ALL {
+            let stream = CountingWriter {
+                buf: Vec::new(),
+                writes: 0,
+            };
+            let mut writer = TxWriter::new(stream, fin_format).unwrap();
+            writer.write_batch(&txs_for_test()).unwrap();
+            let stream = writer.into_inner().unwrap();
+            assert_eq!(stream.writes, 1);
+
+            let expected = CountingWriter {
+                buf: Vec::new(),
+                writes: 0,
+            };
+            let mut writer = TxWriter::new(expected, fin_format).unwrap();
+            for tx in txs_for_test() {
+                writer.write_transaction(&tx).unwrap();
+            }
+            let expected = writer.into_inner().unwrap();
+            assert_eq!(expected.writes, 1);
+            assert_eq!(stream.buf, expected.buf);
+
+            let mut reader = TxReader::new(Cursor::new(stream.buf), fin_format).unwrap();
+            let mut txs = Vec::new();
+            assert_eq!(reader.read_batch(&mut txs, 1).unwrap(), 1);
+            assert_eq!(reader.read_batch(&mut txs, 5).unwrap(), 1);
+            assert_eq!(reader.read_batch(&mut txs, 5).unwrap(), 0);
+            assert_eq!(txs, txs_for_test());
+        }
+    }
+
+    #[test]
+    fn test_read_transaction_ref() {
+        for fin_format in Format::ALL {
+            let buf = encode(&txs_for_test(), fin_format);
+            let mut reader = TxReader::new(Cursor::new(buf), fin_format).unwrap();
+
+            let tx = reader.read_transaction_ref().unwrap().unwrap();
+            assert_eq!(tx, txs_for_test()[0]);
+            assert_eq!(tx.description, "Record number 1");
+            assert!(reader.peek().unwrap().is_some());
+            let tx = reader.read_transaction_ref().unwrap().unwrap();
+            assert_eq!(tx.to_transaction(), txs_for_test()[1]);
+            assert!(reader.read_transaction_ref().unwrap().is_none());
+            assert_eq!(reader.position().records, 2);
+        }
+    }
+
+    #[test]
+    fn test_read_transaction_ref_skip() {
+        for fin_format in Format::ALL {
+            let (buf, expected) = corrupted_for_test(fin_format);
+            let mut reader = TxReader::new(Cursor::new(buf), fin_format)
+                .unwrap()
+                .with_error_policy(ErrorPolicy::Skip);
+            let mut txs = Vec::new();
+            while let Some(tx) = reader.read_transaction_ref().unwrap() {
+                txs.push(tx.to_transaction());
+            }
+            assert_eq!(txs, expected);
+            assert_eq!(reader.error_report().len(), 1);
+        }
+    }
+
+    #[test]
+    fn test_write_all_read_all() {
+        for fin_format in Format::ALL {
+            let buf = encode(&txs_for_test(), fin_format);
+
+            let mut reader = TxReader::new(Cursor::new(buf), fin_format).unwrap();
+            assert_eq!(reader.read_all().unwrap(), txs_for_test());
+        }
+    }
+
+    #[test]
+    fn test_borrowed_streams() {
+        let mut buf = Vec::new();
+        let mut writer = TxWriter::new(&mut buf, Format::Csv).unwrap();
+        writer.write_all(&txs_for_test()).unwrap();
+        writer.finish().unwrap();
+        drop(writer);
+        let mut reader = TxReader::new(&buf[..], Format::Csv).unwrap();
+        assert_eq!(reader.read_all().unwrap(), txs_for_test());
+        // Потоки без Send тоже подходят для встроенных форматов
+        let reader = TxReader::new(io::stdin().lock(), Format::Bin);
+        assert!(reader.is_ok());
+    }
+
+    #[test]
+    fn test_read_write_file() {
+        let path = std::env::temp_dir().join(format!("fin_parser_rw_{}.bin", std::process::id()));
+        write_file(&path, Format::Bin, &txs_for_test()).unwrap();
+        let txs = read_file(&path, Format::Bin).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(txs, txs_for_test());
+    }
+
+    #[test]
+    fn test_count_records() {
+        for fin_format in Format::ALL {
+            let path = std::env::temp_dir().join(format!(
+                "fin_parser_count_{}.{fin_format}",
+                std::process::id()
+            ));
+            write_file(&path, fin_format, &txs_for_test()).unwrap();
+            let cnt = count_records(&path, fin_format).unwrap();
+            std::fs::remove_file(&path).unwrap();
+
+            assert_eq!(cnt, 2);
+        }
+    }
+
+    #[test]
+    fn test_skip_position() {
+        for fin_format in Format::ALL {
+            let buf = encode(&txs_for_test(), fin_format);
+            let len = buf.len() as u64;
+            let mut reader = TxReader::new(Cursor::new(buf), fin_format).unwrap();
+
+            assert_eq!(reader.skip(1).unwrap(), 1);
+            assert_eq!(reader.position().records, 1);
+            assert_eq!(reader.read_transaction().unwrap(), txs_for_test().pop());
+            assert_eq!(reader.skip(5).unwrap(), 0);
+            assert_eq!(
+                reader.position(),
+                Position {
+                    records: 2,
+                    bytes: len
+                }
+            );
+        }
+    }
+
+    #[test]
+    fn test_peek() {
+        let buf = encode(&txs_for_test(), Format::Bin);
+        let mut reader = TxReader::new(Cursor::new(buf), Format::Bin).unwrap();
+        let txs = txs_for_test();
+
+        assert_eq!(reader.peek().unwrap(), Some(&txs[0]));
+        assert_eq!(reader.peek().unwrap(), Some(&txs[0]));
+        assert_eq!(reader.position().records, 0);
+        assert_eq!(reader.read_transaction().unwrap().as_ref(), Some(&txs[0]));
+        assert_eq!(reader.peek().unwrap(), Some(&txs[1]));
+        assert_eq!(reader.skip(1).unwrap(), 1);
+        assert_eq!(reader.peek().unwrap(), None);
+        assert_eq!(reader.read_transaction().unwrap(), None);
+        assert_eq!(reader.position().records, 2);
+    }
+
+    fn corrupted_for_test(fin_format: Format) -> (Vec<u8>, Vec<Transaction>) {
+        let mut third = txs_for_test().remove(0);
+        third.tx_id += 2;
+        let mut txs = txs_for_test();
+        txs.push(third);
+        let mut buf = encode(&txs, fin_format);
+        match fin_format {
+            Format::Bin => {
+                let offset = encode(&txs[..1], Format::Bin).len();
+                buf[offset] = 0;
+            }
+            _ => {
+                let text = String::from_utf8(buf).unwrap();
+                buf = text.replacen("TRANSFER", "TRANSFUR", 1).into_bytes();
+            }
+        }
+        txs.remove(1);
+        (buf, txs)
+    }
+
+    #[test]
+    fn test_error_policy_skip() {
+        for fin_format in Format::ALL {
+            let (buf, expected) = corrupted_for_test(fin_format);
+
+            let mut reader = TxReader::new(Cursor::new(buf.clone()), fin_format).unwrap();
+            assert_eq!(
+                reader.read_transaction().unwrap().as_ref(),
+                Some(&expected[0])
+            );
+            assert!(reader.read_transaction().is_err());
+
+            let mut reader = TxReader::new(Cursor::new(buf.clone()), fin_format)
+                .unwrap()
+                .with_error_policy(ErrorPolicy::Skip);
+            assert_eq!(reader.read_all().unwrap(), expected);
+            let report = reader.error_report();
+            assert_eq!(report.len(), 1);
+            assert_eq!(report.entries()[0].record, 1);
+            if fin_format != Format::Bin {
+                let snippet = report.entries()[0].snippet.as_deref().unwrap();
+                assert!(snippet.contains("TRANSFUR"));
+            }
+
+            let mut reader = TxReader::new(Cursor::new(buf), fin_format)
+                .unwrap()
+                .with_error_policy(ErrorPolicy::Skip);
+            // Подсчет не разбирает поля, поэтому пропускается только запись с неверной сигнатурой
+            let skipped = usize::from(fin_format == Format::Bin);
+            assert_eq!(reader.count().unwrap(), 3 - skipped as u64);
+            assert_eq!(reader.take_error_report().len(), skipped);
+            assert!(reader.error_report().is_empty());
+        }
+    }
+
+    #[test]
+    fn test_validate_only() {
+        for fin_format in Format::ALL {
+            let (buf, expected) = corrupted_for_test(fin_format);
+
+            let mut reader = TxReader::new(Cursor::new(buf.clone()), fin_format).unwrap();
+            reader.read_transaction().unwrap();
+            let read_err = reader.read_transaction().unwrap_err();
+            let mut reader = TxReader::new(Cursor::new(buf.clone()), fin_format).unwrap();
+            assert_eq!(reader.validate_only(1).unwrap(), 1);
+            let err = reader.validate_only(u64::MAX).unwrap_err();
+            assert_eq!(err.to_string(), read_err.to_string(), "{fin_format}");
+            assert_eq!(err.position(), read_err.position(), "{fin_format}");
+
+            let mut reader = TxReader::new(Cursor::new(buf), fin_format)
+                .unwrap()
+                .with_error_policy(ErrorPolicy::Skip);
+            assert_eq!(reader.validate_only(u64::MAX).unwrap(), 2);
+            assert_eq!(reader.error_report().entries()[0].record, 1);
+            assert_eq!(reader.position().records, expected.len() as u64);
+        }
+
+        // Граница представимого времени совпадает с чтением
+        let max_millis = DateTime::<chrono::Utc>::MAX_UTC.timestamp_millis() as u64;
+        for timestamp in [max_millis, max_millis + 1] {
+            let csv = format!(
+                "TX_ID,TX_TYPE,FROM_USER_ID,TO_USER_ID,AMOUNT,TIMESTAMP,STATUS,DESCRIPTION\n\
+                1,DEPOSIT,0,5,100,{timestamp},SUCCESS,\"Record number 1\"\n"
+            );
+            let read = TxReader::new(Cursor::new(csv.clone()), Format::Csv)
+                .unwrap()
+                .read_all();
+            let validated = TxReader::new(Cursor::new(csv), Format::Csv)
+                .unwrap()
+                .validate_only(u64::MAX);
+            assert_eq!(read.is_ok(), validated.is_ok(), "{timestamp}");
+        }
+    }
+
+    #[test]
+    fn test_max_errors() {
+        let (buf, _) = corrupted_for_test(Format::Csv);
+        let mut reader = TxReaderBuilder::new()
+            .format(Format::Csv)
+            .error_policy(ErrorPolicy::Skip)
+            .max_errors(0)
+            .build(Cursor::new(buf))
+            .unwrap();
+
+        assert!(reader.read_transaction().unwrap().is_some());
+        assert!(matches!(
+            reader.read_transaction(),
+            Err(ParsError::TooManyErrors { limit: 0 })
+        ));
+        assert_eq!(reader.error_report().len(), 1);
+    }
+
+    #[test]
+    fn test_warnings() {
+        const LENIENT_CSV: &str = "TX_ID,TX_TYPE,FROM_USER_ID,TO_USER_ID,AMOUNT,TIMESTAMP,STATUS,DESCRIPTION,CURRENCY\n\
+            1,DEPOSIT,0,1,100,1633036860,SUCCESS,\"Seconds\",RUB\n\
+            2,DEPOSIT,0,1,100,1633036860000,SUCCESS,Unquoted,RUB\n";
+
+        let mut reader = TxReaderBuilder::new()
+            .format(Format::Csv)
+            .strict(false)
+            .collect_warnings(true)
+            .build(Cursor::new(LENIENT_CSV.as_bytes()))
+            .unwrap();
+        assert_eq!(reader.read_all().unwrap().len(), 2);
+
+        let warnings = reader.take_warnings();
+        let kinds: Vec<_> = warnings.iter().map(|w| (w.record, &w.kind)).collect();
+        assert_eq!(kinds.len(), 3);
+        assert_eq!(
+            kinds[0],
+            (
+                0,
+                &WarningKind::UnknownField {
+                    name: "CURRENCY".to_owned()
+                }
+            )
+        );
+        assert!(matches!(
+            kinds[1],
+            (0, WarningKind::ImplausibleTimestamp { .. })
+        ));
+        assert_eq!(kinds[2], (1, &WarningKind::UnquotedDescription));
+        assert!(reader.take_warnings().is_empty());
+
+        let mut reader = TxReaderBuilder::new()
+            .format(Format::Csv)
+            .strict(false)
+            .build(Cursor::new(LENIENT_CSV.as_bytes()))
+            .unwrap();
+        assert_eq!(reader.read_all().unwrap().len(), 2);
+        assert!(reader.take_warnings().is_empty());
+    }
+
+    #[test]
+    fn test_finish_empty() {
+        for fin_format in Format::ALL {
+            let buf = encode(&[], fin_format);
+            let mut reader = TxReader::new(Cursor::new(buf), fin_format).unwrap();
+            assert!(reader.read_all().unwrap().is_empty());
+        }
+    }
+
+    #[test]
+    fn test_parse_bytes() {
+        let txs = txs_for_test();
+        let buf = encode(&txs, Format::Csv);
+        assert_eq!(parse_csv_bytes(&buf).unwrap(), txs);
+        // Заголовок записи с RECORD_SIZE и DESC_LEN около 4 ГБ без данных
+        let mut huge = crate::constants::MAGIC.to_be_bytes().to_vec();
+        huge.extend([0xff; 8]);
+        assert!(parse_bin_bytes(&huge).is_err());
+        assert!(parse_text_bytes(b"TX_ID: 1\nTX_TYPE: \xff\n\n").is_err());
+    }
+
+    #[test]
+    fn test_detect() {
+        for fin_format in Format::ALL {
+            let buf = encode(&txs_for_test(), fin_format);
+
+            let (detected, mut reader) = TxReader::detect(Cursor::new(buf)).unwrap();
+            assert_eq!(detected, fin_format);
+            assert_eq!(reader.read_all().unwrap(), txs_for_test());
+        }
+    }
+
+    #[test]
+    fn test_from_path_create() {
+        for ext in ["csv", "txt", "bin.gz"] {
+            let path =
+                std::env::temp_dir().join(format!("fin_parser_path_{}.{ext}", std::process::id()));
+            let mut writer = TxWriter::create(&path).unwrap();
+            writer.write_all(&txs_for_test()).unwrap();
+            drop(writer);
+
+            let mut reader = TxReader::from_path(&path).unwrap();
+            let txs = reader.read_all().unwrap();
+            std::fs::remove_file(&path).unwrap();
+
+            assert_eq!(txs, txs_for_test());
         }
     }
 }
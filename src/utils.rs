@@ -1,11 +1,43 @@
-use super::error::ParsError;
-use std::io::Read;
+use super::error::{ErrorPosition, ParsError};
+use super::options::{ReaderOptions, TimestampUnit};
+use chrono::{DateTime, Utc};
+use std::io::{IoSlice, Read, Write};
+use std::str::FromStr;
 
-pub fn remove_quotes(input: &str) -> String {
-    if input.starts_with('"') && input.ends_with('"') {
-        input[1..input.len() - 1].to_string()
+/// Описание в двойных кавычках. Кавычки и обратная косая черта внутри описания
+/// экранируются обратной косой чертой, как ожидают читатели csv и text
+pub fn quote_description(description: &str) -> String {
+    let mut res = String::with_capacity(description.len() + 2);
+    res.push('"');
+    for ch in description.chars() {
+        if ch == '"' || ch == '\\' {
+            res.push('\\');
+        }
+        res.push(ch);
+    }
+    res.push('"');
+    res
+}
+
+/// Снятие экранирования обратной косой чертой, обратное [quote_description]
+#[cfg(feature = "redis")]
+pub fn unescape(value: &str) -> String {
+    let mut res = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '\\' => res.extend(chars.next()),
+            ch => res.push(ch),
+        }
+    }
+    res
+}
+
+pub fn remove_quotes(input: &str) -> &str {
+    if input.len() >= 2 && input.starts_with('"') && input.ends_with('"') {
+        &input[1..input.len() - 1]
     } else {
-        input.to_string()
+        input
     }
 }
 
@@ -18,3 +50,216 @@ pub fn read_byte<In: Read>(stream: &mut In) -> Result<u8, ParsError> {
     }
     Ok(buf[0])
 }
+
+pub fn timestamp_from_unit(value: u64, unit: TimestampUnit) -> Result<DateTime<Utc>, ParsError> {
+    let value_i64 = i64::try_from(value).ok();
+    let res = match unit {
+        TimestampUnit::Seconds => value_i64.and_then(|val| DateTime::from_timestamp(val, 0)),
+        TimestampUnit::Millis => value_i64.and_then(DateTime::from_timestamp_millis),
+        TimestampUnit::Micros => value_i64.and_then(DateTime::from_timestamp_micros),
+    };
+    res.ok_or(ParsError::InvalidTimestamp { value })
+}
+
+/// Проверка, что время в единицах unit представимо, без построения [DateTime].
+/// Ошибка та же, что у [timestamp_from_unit]
+pub fn check_timestamp(value: u64, unit: TimestampUnit) -> Result<(), ParsError> {
+    let max = &DateTime::<Utc>::MAX_UTC;
+    let max = match unit {
+        TimestampUnit::Seconds => max.timestamp(),
+        TimestampUnit::Millis => max.timestamp_millis(),
+        TimestampUnit::Micros => max.timestamp_micros(),
+    };
+    match value <= max as u64 {
+        true => Ok(()),
+        false => Err(ParsError::InvalidTimestamp { value }),
+    }
+}
+
+/// Фрагмент исходной записи для сообщений об ошибках
+pub fn snippet(raw: &[u8]) -> String {
+    String::from_utf8_lossy(raw).trim().to_string()
+}
+
+/// Разбор числового поля записи
+pub fn parse_number<T: FromStr>(field: &str, value: &str) -> Result<T, ParsError> {
+    value.parse::<T>().map_err(|_| ParsError::InvalidNumber {
+        field: field.to_owned(),
+        value: value.to_owned(),
+    })
+}
+
+/// Ошибка недопустимого значения перечисления
+pub fn invalid_enum_value(field: &str, value: impl ToString) -> ParsError {
+    ParsError::InvalidEnumValue {
+        field: field.to_owned(),
+        value: value.to_string(),
+    }
+}
+
+pub fn timestamp_to_unit(timestamp: &DateTime<Utc>, unit: TimestampUnit) -> u64 {
+    match unit {
+        TimestampUnit::Seconds => timestamp.timestamp() as u64,
+        TimestampUnit::Millis => timestamp.timestamp_millis() as u64,
+        TimestampUnit::Micros => timestamp.timestamp_micros() as u64,
+    }
+}
+
+pub fn parse_description<'a>(
+    input: &'a str,
+    options: &ReaderOptions,
+) -> Result<&'a str, ParsError> {
+    let quoted = input.len() >= 2 && input.starts_with('"') && input.ends_with('"');
+    if options.strict && !quoted {
+        return Err(ParsError::InvalidDescription {
+            value: input.to_owned(),
+        });
+    }
+    let description = remove_quotes(input);
+    if description.len() > options.max_description_len {
+        return Err(ParsError::DescriptionTooLong {
+            len: description.len(),
+            max: options.max_description_len,
+        });
+    }
+    Ok(description)
+}
+
+/// Положение в потоке: смещение в байтах, строка и столбец (с единицы)
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub struct Location {
+    pub byte: u64,
+    pub line: u64,
+    pub column: u64,
+}
+
+impl Default for Location {
+    fn default() -> Self {
+        Self {
+            byte: 0,
+            line: 1,
+            column: 1,
+        }
+    }
+}
+
+impl Location {
+    pub fn error_position(&self, record: u64) -> ErrorPosition {
+        ErrorPosition {
+            record,
+            byte: self.byte,
+            line: Some(self.line),
+            column: Some(self.column),
+        }
+    }
+}
+
+/// Обертка над потоком, подсчитывающая количество прочитанных байт и строк.
+/// Прочитанные байты могут сохраняться для вывода отвергнутых записей
+pub struct CountingReader<R: Read> {
+    inner: R,
+    location: Location,
+    captured: Option<Vec<u8>>,
+}
+
+impl<R: Read> CountingReader<R> {
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            location: Location::default(),
+            captured: None,
+        }
+    }
+
+    /// Включение или отключение сохранения прочитанных байт
+    pub fn set_capture(&mut self, capture: bool) {
+        self.captured = capture.then(Vec::new);
+    }
+
+    /// Байты, прочитанные с последней очистки
+    pub fn captured(&mut self) -> Option<&mut Vec<u8>> {
+        self.captured.as_mut()
+    }
+
+    /// Очистка сохраненных байт
+    pub fn clear_captured(&mut self) {
+        if let Some(captured) = &mut self.captured {
+            captured.clear();
+        }
+    }
+
+    pub fn count(&self) -> u64 {
+        self.location.byte
+    }
+
+    pub fn location(&self) -> Location {
+        self.location
+    }
+
+    #[cfg(feature = "async")]
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+}
+
+impl<R: Read> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let cnt = self.inner.read(buf)?;
+        for byte in &buf[..cnt] {
+            if *byte == b'\n' {
+                self.location.line += 1;
+                self.location.column = 1;
+            } else {
+                self.location.column += 1;
+            }
+        }
+        self.location.byte += cnt as u64;
+        if let Some(captured) = &mut self.captured {
+            captured.extend_from_slice(&buf[..cnt]);
+        }
+        Ok(cnt)
+    }
+}
+
+/// Обертка над потоком, подсчитывающая количество записанных байт
+pub struct CountingWriter<W: Write> {
+    inner: W,
+    count: u64,
+}
+
+impl<W: Write> CountingWriter<W> {
+    pub fn new(inner: W) -> Self {
+        Self { inner, count: 0 }
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    #[cfg(feature = "async")]
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W: Write> Write for CountingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let cnt = self.inner.write(buf)?;
+        self.count += cnt as u64;
+        Ok(cnt)
+    }
+
+    fn write_vectored(&mut self, bufs: &[IoSlice<'_>]) -> std::io::Result<usize> {
+        let cnt = self.inner.write_vectored(bufs)?;
+        self.count += cnt as u64;
+        Ok(cnt)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
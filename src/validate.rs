@@ -0,0 +1,292 @@
+use super::error::{Language, ParsError, language};
+use super::transaction::{Transaction, TxType};
+use super::tx_format::TxReader;
+use chrono::{DateTime, Utc};
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::fmt;
+use std::io::Read;
+use std::str::FromStr;
+
+/// Правило проверки транзакций
+#[derive(Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum Rule {
+    /// tx_id уже встречался в потоке
+    DuplicateTxId,
+    /// Сумма не больше нуля
+    NonPositiveAmount,
+    /// Системный пользователь 0 указан не в зачислении (инициатор)
+    /// или не в списании (получатель)
+    SystemUser,
+    /// Передача самому себе
+    SelfTransfer,
+    /// Время транзакции позже допустимого
+    FutureTimestamp,
+}
+
+impl Rule {
+    /// Все правила
+    pub const ALL: [Rule; 5] = [
+        Self::DuplicateTxId,
+        Self::NonPositiveAmount,
+        Self::SystemUser,
+        Self::SelfTransfer,
+        Self::FutureTimestamp,
+    ];
+
+    /// Имя правила, например `duplicate_tx_id`
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::DuplicateTxId => "duplicate_tx_id",
+            Self::NonPositiveAmount => "non_positive_amount",
+            Self::SystemUser => "system_user",
+            Self::SelfTransfer => "self_transfer",
+            Self::FutureTimestamp => "future_timestamp",
+        }
+    }
+}
+
+impl FromStr for Rule {
+    type Err = ParsError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|rule| rule.name() == s)
+            .ok_or_else(|| ParsError::WrongFormat(format!("Неизвестное правило проверки: {s}")))
+    }
+}
+
+/// Нарушение правила в одной транзакции
+#[derive(Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Finding {
+    /// Порядковый номер записи, начиная с нуля
+    pub record: u64,
+    /// Идентификатор транзакции
+    pub tx_id: u64,
+    /// Нарушенное правило
+    pub rule: Rule,
+    /// Описание нарушения
+    pub message: String,
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let record = match language() {
+            Language::Ru => "Запись",
+            Language::En => "Record",
+        };
+        write!(
+            f,
+            "{record} {}, tx_id {}: {} ({})",
+            self.record,
+            self.tx_id,
+            self.message,
+            self.rule.name()
+        )
+    }
+}
+
+/// Результат проверки потока
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ValidationReport {
+    /// Количество проверенных транзакций
+    pub records: u64,
+    /// Нарушения в порядке записей
+    pub findings: Vec<Finding>,
+}
+
+impl ValidationReport {
+    /// Нарушений нет
+    pub fn is_valid(&self) -> bool {
+        self.findings.is_empty()
+    }
+
+    /// Количество нарушений по правилам
+    pub fn counts(&self) -> BTreeMap<Rule, u64> {
+        let mut res = BTreeMap::new();
+        for finding in &self.findings {
+            *res.entry(finding.rule).or_default() += 1;
+        }
+        res
+    }
+}
+
+/// Проверка транзакций набором правил [Rule]. По умолчанию включены все правила,
+/// допустимое время транзакции ограничено моментом создания проверки
+#[derive(Clone, Debug)]
+pub struct Validator {
+    rules: BTreeSet<Rule>,
+    max_timestamp: DateTime<Utc>,
+    seen: HashSet<u64>,
+}
+
+impl Default for Validator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Validator {
+    /// Проверка всеми правилами
+    pub fn new() -> Self {
+        Self {
+            rules: Rule::ALL.into_iter().collect(),
+            max_timestamp: Utc::now(),
+            seen: HashSet::new(),
+        }
+    }
+
+    /// Отключение правила
+    pub fn without(mut self, rule: Rule) -> Self {
+        self.rules.remove(&rule);
+        self
+    }
+
+    /// Наибольшее допустимое время транзакции для правила [Rule::FutureTimestamp]
+    pub fn with_max_timestamp(mut self, max_timestamp: DateTime<Utc>) -> Self {
+        self.max_timestamp = max_timestamp;
+        self
+    }
+
+    /// Проверка очередной транзакции потока. record используется только в отчете
+    pub fn check(&mut self, record: u64, tx: &Transaction) -> Vec<Finding> {
+        let mut res = Vec::new();
+        let mut report = |rule, message: String| {
+            if self.rules.contains(&rule) {
+                res.push(Finding {
+                    record,
+                    tx_id: tx.tx_id,
+                    rule,
+                    message,
+                });
+            }
+        };
+        if !self.seen.insert(tx.tx_id) {
+            report(
+                Rule::DuplicateTxId,
+                "Повтор идентификатора транзакции".into(),
+            );
+        }
+        if tx.amount <= 0 {
+            report(
+                Rule::NonPositiveAmount,
+                format!("Сумма {} не больше нуля", tx.amount),
+            );
+        }
+        if tx.from_user_id == 0 && tx.tx_type != TxType::Deposit {
+            report(
+                Rule::SystemUser,
+                "Инициатор 0 допустим только для DEPOSIT".into(),
+            );
+        }
+        if tx.to_user_id == 0 && tx.tx_type != TxType::Withdrawal {
+            report(
+                Rule::SystemUser,
+                "Получатель 0 допустим только для WITHDRAWAL".into(),
+            );
+        }
+        if tx.tx_type == TxType::Transfer && tx.from_user_id == tx.to_user_id {
+            report(
+                Rule::SelfTransfer,
+                format!("Передача пользователем {} самому себе", tx.from_user_id),
+            );
+        }
+        if tx.timestamp > self.max_timestamp {
+            report(
+                Rule::FutureTimestamp,
+                format!("Время {} позже допустимого", tx.timestamp.to_rfc3339()),
+            );
+        }
+        res
+    }
+
+    /// Проверка всех оставшихся в потоке транзакций. Номера записей берутся
+    /// из позиции читателя, поэтому учитывают записи, пропущенные при ошибках разбора
+    pub fn validate<In: Read>(
+        &mut self,
+        reader: &mut TxReader<In>,
+    ) -> Result<ValidationReport, ParsError> {
+        let mut report = ValidationReport::default();
+        while let Some(tx) = reader.read_transaction()? {
+            let record = reader.position().records - 1;
+            report.findings.extend(self.check(record, &tx));
+            report.records += 1;
+        }
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::Format;
+    use crate::test_util::{self, reader};
+
+    fn tx(tx_id: u64, tx_type: TxType, from_user_id: u64, to_user_id: u64) -> Transaction {
+        Transaction {
+            tx_type,
+            from_user_id,
+            to_user_id,
+            ..test_util::tx(tx_id)
+        }
+    }
+
+    #[test]
+    fn test_validate() {
+        let mut negative = tx(4, TxType::Withdrawal, 1, 0);
+        negative.amount = -5;
+        let mut future = tx(5, TxType::Deposit, 0, 1);
+        future.timestamp = DateTime::from_timestamp(1700000000, 0).unwrap();
+        let txs = [
+            tx(1, TxType::Deposit, 0, 1),
+            tx(2, TxType::Transfer, 0, 2),
+            tx(3, TxType::Transfer, 2, 2),
+            negative,
+            future,
+            tx(1, TxType::Withdrawal, 1, 0),
+        ];
+        let mut reader = reader(&txs, Format::Bin);
+
+        let mut validator =
+            Validator::new().with_max_timestamp(DateTime::from_timestamp(1650000000, 0).unwrap());
+        let report = validator.validate(&mut reader).unwrap();
+        assert_eq!(report.records, 6);
+        let found: Vec<(u64, Rule)> = report
+            .findings
+            .iter()
+            .map(|finding| (finding.record, finding.rule))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                (1, Rule::SystemUser),
+                (2, Rule::SelfTransfer),
+                (3, Rule::NonPositiveAmount),
+                (4, Rule::FutureTimestamp),
+                (5, Rule::DuplicateTxId),
+            ]
+        );
+        assert_eq!(report.counts()[&Rule::SystemUser], 1);
+        assert!(!report.is_valid());
+    }
+
+    #[test]
+    fn test_disabled_rule() {
+        let mut validator = Validator::new().without(Rule::SelfTransfer);
+        assert!(
+            validator
+                .check(0, &tx(1, TxType::Transfer, 2, 2))
+                .is_empty()
+        );
+        let findings = validator.check(1, &tx(1, TxType::Transfer, 2, 3));
+        assert_eq!(findings[0].rule, Rule::DuplicateTxId);
+        assert_eq!(
+            findings[0].to_string(),
+            "Запись 1, tx_id 1: Повтор идентификатора транзакции (duplicate_tx_id)"
+        );
+        assert_eq!("system_user".parse::<Rule>().unwrap(), Rule::SystemUser);
+    }
+}
@@ -0,0 +1,121 @@
+use super::error::{Language, language};
+use super::options::ReaderOptions;
+use super::transaction::TransactionRef;
+use chrono::{DateTime, Datelike, Utc};
+use std::fmt;
+
+/// Первый год правдоподобного диапазона меток времени
+pub const MIN_PLAUSIBLE_YEAR: i32 = 2000;
+/// Последний год правдоподобного диапазона меток времени
+pub const MAX_PLAUSIBLE_YEAR: i32 = 2100;
+
+/// Вид предупреждения
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum WarningKind {
+    /// Неизвестное поле записи или столбец заголовка проигнорированы
+    UnknownField {
+        /// Имя поля
+        name: String,
+    },
+    /// Метка времени вне правдоподобного диапазона. Обычно это означает,
+    /// что единица измерения времени задана неверно
+    ImplausibleTimestamp {
+        /// Прочитанная метка времени
+        value: DateTime<Utc>,
+    },
+    /// Принято описание без кавычек
+    UnquotedDescription,
+}
+
+impl fmt::Display for WarningKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self, language()) {
+            (Self::UnknownField { name }, Language::Ru) => {
+                write!(f, "Неизвестное поле {name} проигнорировано")
+            }
+            (Self::UnknownField { name }, Language::En) => {
+                write!(f, "Unknown field {name} ignored")
+            }
+            (Self::ImplausibleTimestamp { value }, Language::Ru) => {
+                write!(f, "Неправдоподобная метка времени: {value}")
+            }
+            (Self::ImplausibleTimestamp { value }, Language::En) => {
+                write!(f, "Implausible timestamp: {value}")
+            }
+            (Self::UnquotedDescription, Language::Ru) => write!(f, "Описание без кавычек"),
+            (Self::UnquotedDescription, Language::En) => write!(f, "Unquoted description"),
+        }
+    }
+}
+
+/// Предупреждение о допустимой, но подозрительной особенности данных.
+/// В отличие от ошибки не прерывает чтение
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct Warning {
+    /// Порядковый номер записи, начиная с нуля
+    pub record: u64,
+    /// Вид предупреждения
+    pub kind: WarningKind,
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let record = match language() {
+            Language::Ru => "Запись",
+            Language::En => "Record",
+        };
+        write!(f, "{record} {}: {}", self.record, self.kind)
+    }
+}
+
+/// Накопитель предупреждений читателя формата. Предупреждения собираются,
+/// только если это включено в настройках чтения
+pub(crate) struct WarningSink {
+    enabled: bool,
+    warnings: Vec<Warning>,
+}
+
+impl WarningSink {
+    pub fn new(options: &ReaderOptions) -> Self {
+        Self {
+            enabled: options.collect_warnings,
+            warnings: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, record: u64, kind: WarningKind) {
+        if self.enabled {
+            self.warnings.push(Warning { record, kind });
+        }
+    }
+
+    /// Проверка прочитанной транзакции и исходного текста ее описания
+    pub fn check_transaction(
+        &mut self,
+        record: u64,
+        tx: &TransactionRef<'_>,
+        raw_description: &str,
+    ) {
+        if !self.enabled {
+            return;
+        }
+        if !(MIN_PLAUSIBLE_YEAR..=MAX_PLAUSIBLE_YEAR).contains(&tx.timestamp.year()) {
+            self.push(
+                record,
+                WarningKind::ImplausibleTimestamp {
+                    value: tx.timestamp,
+                },
+            );
+        }
+        let quoted = raw_description.len() >= 2
+            && raw_description.starts_with('"')
+            && raw_description.ends_with('"');
+        if !quoted {
+            self.push(record, WarningKind::UnquotedDescription);
+        }
+    }
+
+    pub fn take(&mut self) -> Vec<Warning> {
+        std::mem::take(&mut self.warnings)
+    }
+}
@@ -0,0 +1,212 @@
+use super::error::ParsError;
+use super::follow::DEFAULT_POLL_INTERVAL;
+use super::format::{Format, TransactionRead};
+use super::transaction::Transaction;
+use super::tx_format::TxReader;
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+/// Источник транзакций из каталога, куда поступают файлы. Файлы читаются
+/// по одному в порядке поступления (времени изменения, при равенстве по имени),
+/// формат и сжатие определяются по расширению, как в [TxReader::from_path].
+/// Прочитанный до конца файл переносится в каталог архива.
+/// Файлы с неизвестным расширением и скрытые файлы пропускаются, поэтому
+/// производитель должен записывать файл под временным именем (например `data.bin.part`)
+/// и переименовывать его после завершения записи.
+/// Когда файлов нет, каталог опрашивается с интервалом poll_interval. Конец потока
+/// возвращается после установки флага остановки ([DirSource::stop_flag]) или,
+/// если задан idle_timeout, после того как новые файлы не появлялись дольше idle_timeout
+pub struct DirSource {
+    dir: PathBuf,
+    archive: PathBuf,
+    poll_interval: Duration,
+    idle_timeout: Option<Duration>,
+    stop: Arc<AtomicBool>,
+    current: Option<(PathBuf, TxReader<Box<dyn Read + Send>>)>,
+    processed: u64,
+}
+
+impl DirSource {
+    /// Источник из каталога dir с переносом прочитанных файлов в archive.
+    /// Каталог архива создается при переносе первого файла
+    pub fn new<P: Into<PathBuf>, A: Into<PathBuf>>(dir: P, archive: A) -> Self {
+        Self {
+            dir: dir.into(),
+            archive: archive.into(),
+            poll_interval: DEFAULT_POLL_INTERVAL,
+            idle_timeout: None,
+            stop: Arc::new(AtomicBool::new(false)),
+            current: None,
+            processed: 0,
+        }
+    }
+
+    /// Интервал опроса каталога, когда в нем нет новых файлов
+    pub fn with_poll_interval(mut self, poll_interval: Duration) -> Self {
+        self.poll_interval = poll_interval;
+        self
+    }
+
+    /// Время ожидания новых файлов, после которого возвращается конец потока
+    pub fn with_idle_timeout(mut self, idle_timeout: Duration) -> Self {
+        self.idle_timeout = Some(idle_timeout);
+        self
+    }
+
+    /// Флаг остановки. После установки флага в true источник возвращает конец потока,
+    /// как только закончатся файлы в каталоге. Флаг можно передать в другой поток
+    pub fn stop_flag(&self) -> Arc<AtomicBool> {
+        Arc::clone(&self.stop)
+    }
+
+    /// Путь читаемого файла
+    pub fn current_file(&self) -> Option<&Path> {
+        self.current.as_ref().map(|(path, _)| path.as_path())
+    }
+
+    /// Количество прочитанных и перенесенных в архив файлов
+    pub fn processed(&self) -> u64 {
+        self.processed
+    }
+
+    /// Чтение следующей транзакции. None означает остановку источника
+    pub fn read_transaction(&mut self) -> Result<Option<Transaction>, ParsError> {
+        let mut idle = Duration::ZERO;
+        loop {
+            if let Some((_, reader)) = &mut self.current {
+                if let Some(tx) = reader.read_transaction()? {
+                    return Ok(Some(tx));
+                }
+                self.archive_current()?;
+                idle = Duration::ZERO;
+                continue;
+            }
+
+            if let Some(path) = self.next_file()? {
+                let reader = TxReader::from_path(&path)?;
+                self.current = Some((path, reader));
+                continue;
+            }
+
+            if self.stop.load(Ordering::Relaxed) {
+                return Ok(None);
+            }
+            if let Some(idle_timeout) = self.idle_timeout
+                && idle >= idle_timeout
+            {
+                return Ok(None);
+            }
+            thread::sleep(self.poll_interval);
+            idle += self.poll_interval;
+        }
+    }
+
+    /// Перенос прочитанного файла в архив
+    fn archive_current(&mut self) -> Result<(), ParsError> {
+        if let Some((path, reader)) = self.current.take() {
+            // Файл закрывается до переноса
+            drop(reader);
+            fs::create_dir_all(&self.archive)?;
+            let name = path.file_name().unwrap_or_default();
+            fs::rename(&path, self.archive.join(name))?;
+            self.processed += 1;
+        }
+        Ok(())
+    }
+
+    /// Самый ранний из поступивших файлов известного формата
+    fn next_file(&self) -> Result<Option<PathBuf>, ParsError> {
+        let mut next: Option<(SystemTime, PathBuf)> = None;
+        for entry in fs::read_dir(&self.dir)? {
+            let entry = entry?;
+            let path = entry.path();
+            let hidden = entry.file_name().to_string_lossy().starts_with('.');
+            if hidden || !entry.file_type()?.is_file() || Format::from_path(&path).is_err() {
+                continue;
+            }
+            let modified = entry.metadata()?.modified()?;
+            let candidate = (modified, path);
+            if next.as_ref().is_none_or(|val| candidate < *val) {
+                next = Some(candidate);
+            }
+        }
+        Ok(next.map(|(_, path)| path))
+    }
+}
+
+impl TransactionRead for DirSource {
+    fn read_transaction(&mut self) -> Result<Option<Transaction>, ParsError> {
+        DirSource::read_transaction(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::read_fin_data;
+    use crate::test_util::txs;
+    use crate::tx_format::write_file;
+
+    fn file_names(dir: &Path) -> Vec<String> {
+        let mut names: Vec<String> = fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn test_dir_source() {
+        let root = std::env::temp_dir().join(format!("fin-parser-watch-{}", std::process::id()));
+        let (dir, archive) = (root.join("in"), root.join("archive"));
+        fs::create_dir_all(&dir).unwrap();
+        write_file(dir.join("b.bin"), Format::Bin, &txs(0..2)).unwrap();
+        thread::sleep(Duration::from_millis(20));
+        write_file(dir.join("a.csv"), Format::Csv, &txs(2..3)).unwrap();
+        fs::write(dir.join("c.bin.part"), b"partial").unwrap();
+
+        let mut source = DirSource::new(&dir, &archive)
+            .with_poll_interval(Duration::from_millis(5))
+            .with_idle_timeout(Duration::from_millis(200));
+        let producer = {
+            let dir = dir.clone();
+            thread::spawn(move || {
+                thread::sleep(Duration::from_millis(30));
+                let part = dir.join("d.txt.part");
+                write_file(&part, Format::Text, &txs(3..5)).unwrap();
+                fs::rename(part, dir.join("d.txt")).unwrap();
+            })
+        };
+        let txs = read_fin_data(&mut source).unwrap();
+        producer.join().unwrap();
+
+        let ids: Vec<u64> = txs.iter().map(|tx| tx.tx_id).collect();
+        assert_eq!(ids, [0, 1, 2, 3, 4]);
+        assert_eq!(source.processed(), 3);
+        assert!(source.current_file().is_none());
+        assert_eq!(file_names(&dir), ["c.bin.part"]);
+        assert_eq!(file_names(&archive), ["a.csv", "b.bin", "d.txt"]);
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_stop_flag() {
+        let root =
+            std::env::temp_dir().join(format!("fin-parser-watch-stop-{}", std::process::id()));
+        fs::create_dir_all(&root).unwrap();
+        let mut source = DirSource::new(&root, root.join("archive"))
+            .with_poll_interval(Duration::from_millis(1));
+        let stop = source.stop_flag();
+        let handle = thread::spawn(move || source.read_transaction());
+        thread::sleep(Duration::from_millis(10));
+        stop.store(true, Ordering::Relaxed);
+        assert!(handle.join().unwrap().unwrap().is_none());
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
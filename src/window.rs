@@ -0,0 +1,272 @@
+use super::analytics::Summary;
+use super::error::ParsError;
+use super::format::TransactionRead;
+use super::transaction::Transaction;
+use chrono::{DateTime, Datelike, NaiveDate, TimeDelta, Timelike, Utc};
+
+/// Размер окна группировки. Часовые, дневные и месячные окна выравниваются
+/// по календарю UTC, окна фиксированной длины отсчитываются от начала эпохи
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum Window {
+    /// Час
+    Hour,
+    /// Сутки
+    Day,
+    /// Календарный месяц
+    Month,
+    /// Окно фиксированной длины. Длина меньше 1 мс считается равной 1 мс
+    Fixed(TimeDelta),
+}
+
+/// Интервал времени окна: начало включается, конец нет
+#[derive(Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+pub struct TimeWindow {
+    /// Начало окна
+    pub start: DateTime<Utc>,
+    /// Конец окна
+    pub end: DateTime<Utc>,
+}
+
+impl TimeWindow {
+    /// Попадает ли время в окно
+    pub fn contains(&self, time: DateTime<Utc>) -> bool {
+        self.start <= time && time < self.end
+    }
+}
+
+impl Window {
+    /// Окно, в которое попадает время
+    pub fn window_of(&self, time: DateTime<Utc>) -> TimeWindow {
+        let midnight = |date: NaiveDate| date.and_time(Default::default()).and_utc();
+        match self {
+            Self::Hour => {
+                let start = midnight(time.date_naive()) + TimeDelta::hours(time.hour() as i64);
+                TimeWindow {
+                    start,
+                    end: start + TimeDelta::hours(1),
+                }
+            }
+            Self::Day => {
+                let start = midnight(time.date_naive());
+                TimeWindow {
+                    start,
+                    end: start + TimeDelta::days(1),
+                }
+            }
+            Self::Month => {
+                let first = time.date_naive().with_day(1).unwrap_or_default();
+                let next = first
+                    .checked_add_months(chrono::Months::new(1))
+                    .unwrap_or(NaiveDate::MAX);
+                TimeWindow {
+                    start: midnight(first),
+                    end: midnight(next),
+                }
+            }
+            Self::Fixed(len) => {
+                let len = len.num_milliseconds().max(1);
+                let start = time.timestamp_millis().div_euclid(len) * len;
+                let start = DateTime::from_timestamp_millis(start).unwrap_or_default();
+                TimeWindow {
+                    start,
+                    end: start + TimeDelta::milliseconds(len),
+                }
+            }
+        }
+    }
+}
+
+/// Группировка упорядоченного по времени потока по окнам. Каждое окно возвращается
+/// целиком вместе со своими транзакциями, окна без транзакций пропускаются.
+/// Внутри окна транзакции могут идти в любом порядке, но транзакция раньше начала
+/// текущего окна считается нарушением порядка и приводит к ошибке [ParsError::WrongFormat]
+pub struct Windows<R: TransactionRead> {
+    reader: R,
+    window: Window,
+    pending: Option<Transaction>,
+}
+
+impl<R: TransactionRead> Windows<R> {
+    /// Чтение следующего окна с передачей его транзакций в add
+    fn next_window<F: FnMut(Transaction)>(
+        &mut self,
+        mut add: F,
+    ) -> Result<Option<TimeWindow>, ParsError> {
+        let first = match self.pending.take() {
+            Some(tx) => tx,
+            None => match self.reader.read_transaction()? {
+                Some(tx) => tx,
+                None => return Ok(None),
+            },
+        };
+        let current = self.window.window_of(first.timestamp);
+        add(first);
+        while let Some(tx) = self.reader.read_transaction()? {
+            if tx.timestamp < current.start {
+                return Err(ParsError::WrongFormat(format!(
+                    "Поток не упорядочен по времени: транзакция {} ({}) раньше начала окна {}",
+                    tx.tx_id, tx.timestamp, current.start
+                )));
+            }
+            if tx.timestamp >= current.end {
+                self.pending = Some(tx);
+                break;
+            }
+            add(tx);
+        }
+        Ok(Some(current))
+    }
+
+    /// Переход от транзакций окон к сводной статистике по каждому окну
+    /// без хранения транзакций в памяти
+    pub fn summaries(self) -> WindowSummaries<R> {
+        WindowSummaries { windows: self }
+    }
+
+    /// Возврат исходного читателя
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+impl<R: TransactionRead> Iterator for Windows<R> {
+    type Item = Result<(TimeWindow, Vec<Transaction>), ParsError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut txs = Vec::new();
+        self.next_window(|tx| txs.push(tx))
+            .map(|window| window.map(|window| (window, txs)))
+            .transpose()
+    }
+}
+
+/// Сводная статистика по окнам, см. [Windows::summaries]
+pub struct WindowSummaries<R: TransactionRead> {
+    windows: Windows<R>,
+}
+
+impl<R: TransactionRead> Iterator for WindowSummaries<R> {
+    type Item = Result<(TimeWindow, Summary), ParsError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut summary = Summary::default();
+        self.windows
+            .next_window(|tx| summary.add_tx(&tx))
+            .map(|window| window.map(|window| (window, summary)))
+            .transpose()
+    }
+}
+
+/// Группировка упорядоченного по времени потока по окнам, см. [Windows]
+pub fn windows<R: TransactionRead>(reader: R, window: Window) -> Windows<R> {
+    Windows {
+        reader,
+        window,
+        pending: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::Format;
+    use crate::transaction::{TxStatus, TxType};
+    use crate::tx_format::{TxReader, TxWriter};
+    use std::io::Cursor;
+
+    fn time(val: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(val).unwrap().to_utc()
+    }
+
+    fn reader_for_test(times: &[&str]) -> TxReader<Cursor<Vec<u8>>> {
+        let txs: Vec<Transaction> = times
+            .iter()
+            .enumerate()
+            .map(|(idx, val)| Transaction {
+                tx_id: idx as u64,
+                tx_type: TxType::Deposit,
+                from_user_id: 0,
+                to_user_id: 1,
+                amount: 100,
+                timestamp: time(val),
+                status: TxStatus::Success,
+                description: String::new(),
+            })
+            .collect();
+        let mut writer = TxWriter::new(Vec::new(), Format::Bin).unwrap();
+        writer.write_all(&txs).unwrap();
+        let buf = writer.into_inner().unwrap();
+        TxReader::new(Cursor::new(buf), Format::Bin).unwrap()
+    }
+
+    const TIMES: [&str; 5] = [
+        "2021-10-01T10:15:00Z",
+        "2021-10-01T10:05:00Z",
+        "2021-10-01T23:59:59Z",
+        "2021-10-31T00:00:00Z",
+        "2021-11-02T08:00:00Z",
+    ];
+
+    fn grouped(window: Window) -> Vec<(DateTime<Utc>, Vec<u64>)> {
+        windows(reader_for_test(&TIMES), window)
+            .map(|res| {
+                let (window, txs) = res.unwrap();
+                (window.start, txs.iter().map(|tx| tx.tx_id).collect())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_window_of() {
+        let at = time("2021-12-15T13:45:10Z");
+        let window = Window::Hour.window_of(at);
+        assert_eq!(window.start, time("2021-12-15T13:00:00Z"));
+        assert_eq!(window.end, time("2021-12-15T14:00:00Z"));
+        assert!(window.contains(at) && !window.contains(window.end));
+        let window = Window::Month.window_of(at);
+        assert_eq!(window.start, time("2021-12-01T00:00:00Z"));
+        assert_eq!(window.end, time("2022-01-01T00:00:00Z"));
+        let window = Window::Fixed(TimeDelta::minutes(15)).window_of(at);
+        assert_eq!(window.start, time("2021-12-15T13:45:00Z"));
+        let before_epoch = time("1969-12-31T23:59:00Z");
+        let window = Window::Fixed(TimeDelta::hours(1)).window_of(before_epoch);
+        assert_eq!(window.start, time("1969-12-31T23:00:00Z"));
+    }
+
+    #[test]
+    fn test_windows() {
+        assert_eq!(
+            grouped(Window::Day),
+            [
+                (time("2021-10-01T00:00:00Z"), vec![0, 1, 2]),
+                (time("2021-10-31T00:00:00Z"), vec![3]),
+                (time("2021-11-02T00:00:00Z"), vec![4]),
+            ]
+        );
+        assert_eq!(
+            grouped(Window::Month),
+            [
+                (time("2021-10-01T00:00:00Z"), vec![0, 1, 2, 3]),
+                (time("2021-11-01T00:00:00Z"), vec![4]),
+            ]
+        );
+        assert_eq!(grouped(Window::Hour).len(), 4);
+
+        let summaries: Vec<(TimeWindow, Summary)> = windows(reader_for_test(&TIMES), Window::Day)
+            .summaries()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        let totals: Vec<i128> = summaries.iter().map(|(_, val)| val.total.sum).collect();
+        assert_eq!(totals, [300, 100, 100]);
+    }
+
+    #[test]
+    fn test_windows_unordered() {
+        let reader = reader_for_test(&["2021-10-02T10:00:00Z", "2021-10-01T10:00:00Z"]);
+        let mut windows = windows(reader, Window::Day);
+        assert!(matches!(
+            windows.next().unwrap().unwrap_err(),
+            ParsError::WrongFormat(msg) if msg.contains("транзакция 1")
+        ));
+    }
+}